@@ -0,0 +1,245 @@
+use libeir_intern::Symbol;
+use libeir_ir::{Const, ConstantContainer, FunctionIdent};
+
+/// A label within a module.
+///
+/// Labels are numbered from 1 and are unique across the whole module. Label
+/// 0 is reserved, it is used as the failure label of instructions that raise
+/// an exception instead of branching.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Label(pub u32);
+
+impl Label {
+    pub const NONE: Label = Label(0);
+}
+
+/// A BEAM register.
+///
+/// `x` registers hold arguments and temporaries, and are clobbered by calls.
+/// `y` registers are slots in the stack frame of the function.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Reg {
+    X(u32),
+    Y(u32),
+}
+
+impl Reg {
+    pub fn is_x(self) -> bool {
+        match self {
+            Reg::X(_) => true,
+            Reg::Y(_) => false,
+        }
+    }
+}
+
+/// A source operand.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Src {
+    Reg(Reg),
+    /// A constant in the constant container of the module. Immediates are
+    /// printed in their specific form, like `{atom,foo}`, anything else is a
+    /// `{literal,..}`.
+    Const(Const),
+    /// An external function, `fun m:f/a`.
+    ExtFun(FunctionIdent),
+}
+
+impl From<Reg> for Src {
+    fn from(reg: Reg) -> Src {
+        Src::Reg(reg)
+    }
+}
+
+/// The type tests used by the `test` instruction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Test {
+    IsEqExact,
+    IsNeExact,
+    IsAtom,
+    IsList,
+    IsNonemptyList,
+    IsNil,
+    IsTuple,
+    IsMap,
+    IsNumber,
+    IsInteger,
+    IsFloat,
+}
+
+impl Test {
+    pub fn name(self) -> &'static str {
+        match self {
+            Test::IsEqExact => "is_eq_exact",
+            Test::IsNeExact => "is_ne_exact",
+            Test::IsAtom => "is_atom",
+            Test::IsList => "is_list",
+            Test::IsNonemptyList => "is_nonempty_list",
+            Test::IsNil => "is_nil",
+            Test::IsTuple => "is_tuple",
+            Test::IsMap => "is_map",
+            Test::IsNumber => "is_number",
+            Test::IsInteger => "is_integer",
+            Test::IsFloat => "is_float",
+        }
+    }
+}
+
+/// A BEAM assembly instruction, as it appears in the output of `erlc -S`.
+///
+/// Only the subset of the instruction set the code generator produces is
+/// represented.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instr {
+    Label(Label),
+    FuncInfo {
+        module: Symbol,
+        name: Symbol,
+        arity: u32,
+    },
+
+    /// Allocates a stack frame of `stack` y registers, saving the
+    /// continuation pointer. The first `live` x registers are preserved.
+    Allocate {
+        stack: u32,
+        live: u32,
+    },
+    /// As `Allocate`, but also initializes every y register to `[]`.
+    AllocateZero {
+        stack: u32,
+        live: u32,
+    },
+    Deallocate(u32),
+    Return,
+
+    Move {
+        src: Src,
+        dst: Reg,
+    },
+    Jump(Label),
+
+    /// Calls a local function, with the arguments in `x0..xN`.
+    Call {
+        arity: u32,
+        target: Label,
+    },
+    /// Deallocates the stack frame and tail calls a local function.
+    CallLast {
+        arity: u32,
+        target: Label,
+        dealloc: u32,
+    },
+    /// Tail calls a local function from a function without a stack frame.
+    CallOnly {
+        arity: u32,
+        target: Label,
+    },
+    CallExt(FunctionIdent),
+    CallExtLast {
+        fun: FunctionIdent,
+        dealloc: u32,
+    },
+    CallExtOnly(FunctionIdent),
+    /// Calls the fun in `xN`, with the arguments in `x0..xN`.
+    CallFun(u32),
+
+    /// Calls a guard BIF that raises on failure.
+    Bif {
+        name: Symbol,
+        args: Vec<Src>,
+        dst: Reg,
+    },
+    /// Branches to `fail` if the test does not hold.
+    Test {
+        test: Test,
+        fail: Label,
+        args: Vec<Src>,
+    },
+    /// Branches to `fail` if the tuple in `src` does not have the given arity.
+    TestArity {
+        fail: Label,
+        src: Reg,
+        arity: u32,
+    },
+
+    GetTupleElement {
+        src: Reg,
+        index: u32,
+        dst: Reg,
+    },
+    GetList {
+        src: Reg,
+        head: Reg,
+        tail: Reg,
+    },
+    /// Looks up the keys in the map in `src`, branching to `fail` if any
+    /// of them are missing.
+    GetMapElements {
+        fail: Label,
+        src: Reg,
+        pairs: Vec<(Src, Reg)>,
+    },
+
+    /// Ensures there are `need` words available on the heap, garbage
+    /// collecting if there are not. The first `live` x registers are
+    /// preserved.
+    TestHeap {
+        need: u32,
+        live: u32,
+    },
+    PutTuple2 {
+        dst: Reg,
+        elements: Vec<Src>,
+    },
+    PutList {
+        head: Src,
+        tail: Src,
+        dst: Reg,
+    },
+    /// Creates a fun of the function at `target`, with the environment taken
+    /// from `x0..x(num_free)`. The fun is returned in `x0`.
+    MakeFun2 {
+        target: Label,
+        index: u32,
+        old_uniq: u32,
+        num_free: u32,
+    },
+
+    /// Enters a try block. Exceptions raised before the matching `TryEnd`
+    /// continue at `handler`.
+    Try {
+        reg: Reg,
+        handler: Label,
+    },
+    TryEnd(Reg),
+    /// Starts a try handler, with the class, reason and raw stack trace of
+    /// the exception in `x0..x2`.
+    TryCase(Reg),
+    /// Reraises the exception with the class, reason and raw stack trace in
+    /// `x0..x2`, as received by a try handler.
+    RawRaise,
+    /// Converts the raw stack trace in `x0` into a list.
+    BuildStacktrace,
+}
+
+/// A function in a module.
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: Symbol,
+    pub arity: u32,
+    /// The label the function is called at. The code starts with the label
+    /// of the `func_info` instruction, immediately preceding it.
+    pub entry: Label,
+    pub code: Vec<Instr>,
+}
+
+/// A module in BEAM assembly form.
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub name: Symbol,
+    pub exports: Vec<(Symbol, u32)>,
+    pub functions: Vec<Function>,
+    /// One more than the highest label used in the module.
+    pub labels: u32,
+    /// The constants referenced by `Src::Const`.
+    pub cons: ConstantContainer,
+}
@@ -0,0 +1,116 @@
+//! Generic opcodes and the compact term encoding of their operands.
+
+/// Generic opcodes, as numbered in `genop.tab`.
+pub mod op {
+    pub const LABEL: u8 = 1;
+    pub const FUNC_INFO: u8 = 2;
+    pub const INT_CODE_END: u8 = 3;
+    pub const CALL: u8 = 4;
+    pub const CALL_LAST: u8 = 5;
+    pub const CALL_ONLY: u8 = 6;
+    pub const CALL_EXT: u8 = 7;
+    pub const CALL_EXT_LAST: u8 = 8;
+    pub const BIF0: u8 = 9;
+    pub const BIF1: u8 = 10;
+    pub const BIF2: u8 = 11;
+    pub const ALLOCATE: u8 = 12;
+    pub const ALLOCATE_ZERO: u8 = 14;
+    pub const TEST_HEAP: u8 = 16;
+    pub const DEALLOCATE: u8 = 18;
+    pub const RETURN: u8 = 19;
+    pub const IS_EQ_EXACT: u8 = 43;
+    pub const IS_NE_EXACT: u8 = 44;
+    pub const IS_INTEGER: u8 = 45;
+    pub const IS_FLOAT: u8 = 46;
+    pub const IS_NUMBER: u8 = 47;
+    pub const IS_ATOM: u8 = 48;
+    pub const IS_NIL: u8 = 52;
+    pub const IS_LIST: u8 = 55;
+    pub const IS_NONEMPTY_LIST: u8 = 56;
+    pub const IS_TUPLE: u8 = 57;
+    pub const TEST_ARITY: u8 = 58;
+    pub const JUMP: u8 = 61;
+    pub const MOVE: u8 = 64;
+    pub const GET_LIST: u8 = 65;
+    pub const GET_TUPLE_ELEMENT: u8 = 66;
+    pub const PUT_LIST: u8 = 69;
+    pub const CALL_FUN: u8 = 75;
+    pub const CALL_EXT_ONLY: u8 = 78;
+    pub const MAKE_FUN2: u8 = 103;
+    pub const TRY: u8 = 104;
+    pub const TRY_END: u8 = 105;
+    pub const TRY_CASE: u8 = 106;
+    pub const IS_MAP: u8 = 156;
+    pub const GET_MAP_ELEMENTS: u8 = 158;
+    pub const BUILD_STACKTRACE: u8 = 160;
+    pub const RAW_RAISE: u8 = 161;
+    pub const PUT_TUPLE2: u8 = 164;
+
+    /// The number of operands of an opcode, for the opcodes above.
+    pub fn arity(opcode: u8) -> Option<usize> {
+        let arity = match opcode {
+            INT_CODE_END | RETURN | BUILD_STACKTRACE | RAW_RAISE => 0,
+            LABEL | DEALLOCATE | JUMP | CALL_FUN | MAKE_FUN2 | TRY_END | TRY_CASE => 1,
+            CALL | CALL_ONLY | CALL_EXT | BIF0 | ALLOCATE | ALLOCATE_ZERO | TEST_HEAP
+            | IS_INTEGER | IS_FLOAT | IS_NUMBER | IS_ATOM | IS_NIL | IS_LIST | IS_NONEMPTY_LIST
+            | IS_TUPLE | MOVE | CALL_EXT_ONLY | TRY | IS_MAP | PUT_TUPLE2 => 2,
+            FUNC_INFO | CALL_LAST | CALL_EXT_LAST | IS_EQ_EXACT | IS_NE_EXACT | TEST_ARITY
+            | GET_LIST | GET_TUPLE_ELEMENT | PUT_LIST | GET_MAP_ELEMENTS => 3,
+            BIF1 => 4,
+            BIF2 => 5,
+            _ => return None,
+        };
+        Some(arity)
+    }
+}
+
+/// Operand tags, in the low 3 bits of the first byte of an operand.
+pub mod tag {
+    /// An unsigned integer, like an arity or a table index.
+    pub const U: u8 = 0;
+    pub const I: u8 = 1;
+    /// An index into the atom table, 0 is `[]`.
+    pub const A: u8 = 2;
+    pub const X: u8 = 3;
+    pub const Y: u8 = 4;
+    pub const F: u8 = 5;
+    /// Extended operand, the value is one of the `EXT_` kinds.
+    pub const Z: u8 = 7;
+
+    /// `{list,[..]}`, followed by the length and the elements.
+    pub const EXT_LIST: i64 = 1;
+    /// `{literal,..}`, followed by an index into the literal table.
+    pub const EXT_LITERAL: i64 = 4;
+}
+
+/// Encodes an operand in the compact term format.
+///
+/// Values below 16 fit in the tag byte, and values below 2048 take one
+/// extra byte. Anything else is written as a big endian two's complement
+/// number of at least 2 bytes, with the length in the tag byte.
+pub fn encode(out: &mut Vec<u8>, tag: u8, value: i64) {
+    if (0..16).contains(&value) {
+        out.push((value as u8) << 4 | tag);
+    } else if (0..0x800).contains(&value) {
+        out.push(((value >> 3) as u8 & 0b1110_0000) | 0b0000_1000 | tag);
+        out.push(value as u8);
+    } else {
+        let bytes = value.to_be_bytes();
+        let mut start = 0;
+        while start < bytes.len() - 2 {
+            let redundant = match bytes[start] {
+                0x00 => bytes[start + 1] & 0x80 == 0,
+                0xff => bytes[start + 1] & 0x80 != 0,
+                _ => false,
+            };
+            if !redundant {
+                break;
+            }
+            start += 1;
+        }
+
+        let len = (bytes.len() - start) as u8;
+        out.push(((len - 2) << 5) | 0b0001_1000 | tag);
+        out.extend_from_slice(&bytes[start..]);
+    }
+}
@@ -0,0 +1,589 @@
+//! Loadable `.beam` files.
+//!
+//! A `.beam` file is an IFF container, `FOR1` followed by the size and the
+//! form type `BEAM`, and then the chunks. Every chunk is padded to a multiple
+//! of 4 bytes. We write:
+//!
+//! * `AtU8`: the atom table, the module name is atom 1.
+//! * `Code`: generic opcodes, with their operands in the compact term
+//!   format.
+//! * `StrT`: the string table, nothing we emit uses it so it is empty.
+//! * `ImpT`, `ExpT`, `LocT`: the imported, exported and local functions.
+//! * `FunT`: the lambda table, one entry for every fun made with
+//!   `make_fun2`.
+//! * `LitT`: the literal table, ETF encoded terms in a zlib stream.
+//! * `Attr`, `CInf`: attributes and compile information, both empty lists.
+//! * `Line`: the line table, empty as no `line` instructions are emitted.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::Hash;
+use std::io::{Result, Write};
+
+use libeir_etf::Writer;
+use libeir_intern::{Ident, Symbol};
+use libeir_ir::{AtomicTerm, Const, ConstKind, ConstantContainer, FunctionIdent};
+use libeir_util_binary::BitCarrier;
+use libeir_util_number::BigInt;
+
+use crate::asm::{Instr, Label, Module, Reg, Src, Test};
+
+mod encode;
+use encode::{encode, op, tag};
+
+mod zlib;
+
+#[cfg(test)]
+mod reader;
+#[cfg(test)]
+mod tests;
+
+/// The version byte every ETF encoded term starts with.
+const ETF_VERSION: u8 = 131;
+
+impl Module {
+    /// Assembles the module into the contents of a `.beam` file.
+    pub fn to_beam(&self) -> Vec<u8> {
+        let mut asm = Assembler::new(self);
+        for function in self.functions.iter() {
+            for instr in function.code.iter() {
+                asm.instr(instr);
+            }
+        }
+        asm.op(op::INT_CODE_END);
+        asm.finish()
+    }
+}
+
+/// Assigns indices to entries in the order they are first used.
+struct Table<K> {
+    entries: Vec<K>,
+    indices: HashMap<K, u32>,
+}
+
+impl<K: Copy + Eq + Hash> Table<K> {
+    fn new() -> Self {
+        Table {
+            entries: Vec::new(),
+            indices: HashMap::new(),
+        }
+    }
+
+    fn index(&mut self, key: K) -> u32 {
+        let entries = &mut self.entries;
+        *self.indices.entry(key).or_insert_with(|| {
+            entries.push(key);
+            entries.len() as u32 - 1
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Literal {
+    Const(Const),
+    ExtFun(FunctionIdent),
+}
+
+/// A lambda table entry: the target, index, old uniq and number of free
+/// variables of a `make_fun2`.
+type Lambda = (Label, u32, u32, u32);
+
+struct Assembler<'a> {
+    module: &'a Module,
+    code: Vec<u8>,
+    max_opcode: u8,
+    atoms: Table<Symbol>,
+    imports: Table<FunctionIdent>,
+    literals: Table<Literal>,
+    lambdas: Table<Lambda>,
+}
+
+impl<'a> Assembler<'a> {
+    fn new(module: &'a Module) -> Self {
+        let mut atoms = Table::new();
+        atoms.index(module.name);
+        Assembler {
+            module,
+            code: Vec::new(),
+            max_opcode: 0,
+            atoms,
+            imports: Table::new(),
+            literals: Table::new(),
+            lambdas: Table::new(),
+        }
+    }
+
+    fn instr(&mut self, instr: &Instr) {
+        match instr {
+            Instr::Label(label) => {
+                self.op(op::LABEL);
+                self.unsigned(label.0);
+            }
+            Instr::FuncInfo {
+                module,
+                name,
+                arity,
+            } => {
+                self.op(op::FUNC_INFO);
+                self.atom(*module);
+                self.atom(*name);
+                self.unsigned(*arity);
+            }
+            Instr::Allocate { stack, live } => {
+                self.op(op::ALLOCATE);
+                self.unsigned(*stack);
+                self.unsigned(*live);
+            }
+            Instr::AllocateZero { stack, live } => {
+                self.op(op::ALLOCATE_ZERO);
+                self.unsigned(*stack);
+                self.unsigned(*live);
+            }
+            Instr::Deallocate(n) => {
+                self.op(op::DEALLOCATE);
+                self.unsigned(*n);
+            }
+            Instr::Return => self.op(op::RETURN),
+            Instr::Move { src, dst } => {
+                self.op(op::MOVE);
+                self.src(src);
+                self.reg(*dst);
+            }
+            Instr::Jump(label) => {
+                self.op(op::JUMP);
+                self.label(*label);
+            }
+            Instr::Call { arity, target } => {
+                self.op(op::CALL);
+                self.unsigned(*arity);
+                self.label(*target);
+            }
+            Instr::CallLast {
+                arity,
+                target,
+                dealloc,
+            } => {
+                self.op(op::CALL_LAST);
+                self.unsigned(*arity);
+                self.label(*target);
+                self.unsigned(*dealloc);
+            }
+            Instr::CallOnly { arity, target } => {
+                self.op(op::CALL_ONLY);
+                self.unsigned(*arity);
+                self.label(*target);
+            }
+            Instr::CallExt(fun) => {
+                self.op(op::CALL_EXT);
+                self.unsigned(fun.arity as u32);
+                self.import(*fun);
+            }
+            Instr::CallExtLast { fun, dealloc } => {
+                self.op(op::CALL_EXT_LAST);
+                self.unsigned(fun.arity as u32);
+                self.import(*fun);
+                self.unsigned(*dealloc);
+            }
+            Instr::CallExtOnly(fun) => {
+                self.op(op::CALL_EXT_ONLY);
+                self.unsigned(fun.arity as u32);
+                self.import(*fun);
+            }
+            Instr::CallFun(arity) => {
+                self.op(op::CALL_FUN);
+                self.unsigned(*arity);
+            }
+            Instr::Bif { name, args, dst } => {
+                let fun = FunctionIdent {
+                    module: Ident::from_str("erlang"),
+                    name: Ident::with_empty_span(*name),
+                    arity: args.len(),
+                };
+                // `bif0` can not fail, and has no failure label.
+                match args.len() {
+                    0 => self.op(op::BIF0),
+                    1 => {
+                        self.op(op::BIF1);
+                        self.label(Label::NONE);
+                    }
+                    2 => {
+                        self.op(op::BIF2);
+                        self.label(Label::NONE);
+                    }
+                    n => panic!("no bif instruction for arity {}", n),
+                }
+                self.import(fun);
+                args.iter().for_each(|arg| self.src(arg));
+                self.reg(*dst);
+            }
+            Instr::Test { test, fail, args } => {
+                self.op(test_opcode(*test));
+                self.label(*fail);
+                args.iter().for_each(|arg| self.src(arg));
+            }
+            Instr::TestArity { fail, src, arity } => {
+                self.op(op::TEST_ARITY);
+                self.label(*fail);
+                self.reg(*src);
+                self.unsigned(*arity);
+            }
+            Instr::GetTupleElement { src, index, dst } => {
+                self.op(op::GET_TUPLE_ELEMENT);
+                self.reg(*src);
+                self.unsigned(*index);
+                self.reg(*dst);
+            }
+            Instr::GetList { src, head, tail } => {
+                self.op(op::GET_LIST);
+                self.reg(*src);
+                self.reg(*head);
+                self.reg(*tail);
+            }
+            Instr::GetMapElements { fail, src, pairs } => {
+                self.op(op::GET_MAP_ELEMENTS);
+                self.label(*fail);
+                self.reg(*src);
+                self.list(pairs.len() * 2);
+                for (key, dst) in pairs.iter() {
+                    self.src(key);
+                    self.reg(*dst);
+                }
+            }
+            Instr::TestHeap { need, live } => {
+                self.op(op::TEST_HEAP);
+                self.unsigned(*need);
+                self.unsigned(*live);
+            }
+            Instr::PutTuple2 { dst, elements } => {
+                self.op(op::PUT_TUPLE2);
+                self.reg(*dst);
+                self.list(elements.len());
+                elements.iter().for_each(|elem| self.src(elem));
+            }
+            Instr::PutList { head, tail, dst } => {
+                self.op(op::PUT_LIST);
+                self.src(head);
+                self.src(tail);
+                self.reg(*dst);
+            }
+            Instr::MakeFun2 {
+                target,
+                index,
+                old_uniq,
+                num_free,
+            } => {
+                let lambda = self.lambdas.index((*target, *index, *old_uniq, *num_free));
+                self.op(op::MAKE_FUN2);
+                self.unsigned(lambda);
+            }
+            Instr::Try { reg, handler } => {
+                self.op(op::TRY);
+                self.reg(*reg);
+                self.label(*handler);
+            }
+            Instr::TryEnd(reg) => {
+                self.op(op::TRY_END);
+                self.reg(*reg);
+            }
+            Instr::TryCase(reg) => {
+                self.op(op::TRY_CASE);
+                self.reg(*reg);
+            }
+            Instr::RawRaise => self.op(op::RAW_RAISE),
+            Instr::BuildStacktrace => self.op(op::BUILD_STACKTRACE),
+        }
+    }
+
+    fn op(&mut self, opcode: u8) {
+        self.max_opcode = self.max_opcode.max(opcode);
+        self.code.push(opcode);
+    }
+
+    fn unsigned(&mut self, value: u32) {
+        encode(&mut self.code, tag::U, value as i64);
+    }
+
+    fn label(&mut self, label: Label) {
+        encode(&mut self.code, tag::F, label.0 as i64);
+    }
+
+    fn list(&mut self, len: usize) {
+        encode(&mut self.code, tag::Z, tag::EXT_LIST);
+        encode(&mut self.code, tag::U, len as i64);
+    }
+
+    fn reg(&mut self, reg: Reg) {
+        match reg {
+            Reg::X(n) => encode(&mut self.code, tag::X, n as i64),
+            Reg::Y(n) => encode(&mut self.code, tag::Y, n as i64),
+        }
+    }
+
+    /// Atoms are numbered from 1, 0 stands for `[]`.
+    fn atom_index(&mut self, atom: Symbol) -> u32 {
+        self.atoms.index(atom) + 1
+    }
+
+    fn atom(&mut self, atom: Symbol) {
+        let index = self.atom_index(atom);
+        encode(&mut self.code, tag::A, index as i64);
+    }
+
+    fn import(&mut self, fun: FunctionIdent) {
+        let index = self.imports.index(fun);
+        self.unsigned(index);
+    }
+
+    fn literal(&mut self, literal: Literal) {
+        let index = self.literals.index(literal);
+        encode(&mut self.code, tag::Z, tag::EXT_LITERAL);
+        self.unsigned(index);
+    }
+
+    fn src(&mut self, src: &Src) {
+        match src {
+            Src::Reg(reg) => self.reg(*reg),
+            Src::Const(value) => match self.module.cons.const_kind(*value) {
+                ConstKind::Atomic(AtomicTerm::Atom(atom)) => self.atom(atom.0),
+                ConstKind::Atomic(AtomicTerm::Int(int)) => {
+                    encode(&mut self.code, tag::I, int.value())
+                }
+                ConstKind::Atomic(AtomicTerm::Nil) => encode(&mut self.code, tag::A, 0),
+                _ => self.literal(Literal::Const(*value)),
+            },
+            Src::ExtFun(fun) => self.literal(Literal::ExtFun(*fun)),
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let module = self.module;
+
+        let mut code = Vec::new();
+        // The size of the rest of the header.
+        push_u32(&mut code, 16);
+        // The instruction set version.
+        push_u32(&mut code, 0);
+        push_u32(&mut code, self.max_opcode as u32);
+        push_u32(&mut code, module.labels);
+        push_u32(&mut code, module.functions.len() as u32);
+        code.extend_from_slice(&self.code);
+
+        let mut exports = Vec::new();
+        let mut locals = Vec::new();
+        for function in module.functions.iter() {
+            let row = [
+                self.atom_index(function.name),
+                function.arity,
+                function.entry.0,
+            ];
+            if module.exports.contains(&(function.name, function.arity)) {
+                exports.push(row);
+            } else {
+                locals.push(row);
+            }
+        }
+
+        let mut lambdas = Vec::new();
+        for (target, index, old_uniq, num_free) in self.lambdas.entries.clone() {
+            let function = module
+                .functions
+                .iter()
+                .find(|f| f.entry == target)
+                .expect("make_fun2 target is not a function entry");
+            let name = self.atom_index(function.name);
+            lambdas.push([name, function.arity, target.0, index, num_free, old_uniq]);
+        }
+
+        let mut imports = Vec::new();
+        for fun in self.imports.entries.clone() {
+            let module = self.atom_index(fun.module.name);
+            let name = self.atom_index(fun.name.name);
+            imports.push([module, name, fun.arity as u32]);
+        }
+
+        let mut literals = Vec::new();
+        push_u32(&mut literals, self.literals.entries.len() as u32);
+        for literal in self.literals.entries.iter() {
+            let mut term = vec![ETF_VERSION];
+            write_literal(&mut Writer::new(&mut term), &module.cons, *literal).unwrap();
+            push_u32(&mut literals, term.len() as u32);
+            literals.extend_from_slice(&term);
+        }
+        let mut compressed = Vec::new();
+        push_u32(&mut compressed, literals.len() as u32);
+        compressed.extend_from_slice(&zlib::store(&literals));
+
+        let mut atoms = Vec::new();
+        push_u32(&mut atoms, self.atoms.entries.len() as u32);
+        for atom in self.atoms.entries.iter() {
+            let atom = atom.as_str();
+            let bytes = atom.as_bytes();
+            assert!(bytes.len() <= 255, "atom too long");
+            atoms.push(bytes.len() as u8);
+            atoms.extend_from_slice(bytes);
+        }
+
+        let mut empty_list = vec![ETF_VERSION];
+        Writer::new(&mut empty_list).nil().unwrap();
+
+        // Version 0, without any line instructions, line items or file names.
+        let mut line = Vec::new();
+        for _ in 0..5 {
+            push_u32(&mut line, 0);
+        }
+
+        let mut chunks = Vec::new();
+        chunk(&mut chunks, b"AtU8", &atoms);
+        chunk(&mut chunks, b"Code", &code);
+        chunk(&mut chunks, b"StrT", &[]);
+        chunk(&mut chunks, b"ImpT", &table(&imports));
+        chunk(&mut chunks, b"ExpT", &table(&exports));
+        if !lambdas.is_empty() {
+            chunk(&mut chunks, b"FunT", &table(&lambdas));
+        }
+        if !self.literals.entries.is_empty() {
+            chunk(&mut chunks, b"LitT", &compressed);
+        }
+        chunk(&mut chunks, b"LocT", &table(&locals));
+        chunk(&mut chunks, b"Attr", &empty_list);
+        chunk(&mut chunks, b"CInf", &empty_list);
+        chunk(&mut chunks, b"Line", &line);
+
+        let mut out = b"FOR1".to_vec();
+        push_u32(&mut out, chunks.len() as u32 + 4);
+        out.extend_from_slice(b"BEAM");
+        out.extend_from_slice(&chunks);
+        out
+    }
+}
+
+fn test_opcode(test: Test) -> u8 {
+    match test {
+        Test::IsEqExact => op::IS_EQ_EXACT,
+        Test::IsNeExact => op::IS_NE_EXACT,
+        Test::IsAtom => op::IS_ATOM,
+        Test::IsList => op::IS_LIST,
+        Test::IsNonemptyList => op::IS_NONEMPTY_LIST,
+        Test::IsNil => op::IS_NIL,
+        Test::IsTuple => op::IS_TUPLE,
+        Test::IsMap => op::IS_MAP,
+        Test::IsNumber => op::IS_NUMBER,
+        Test::IsInteger => op::IS_INTEGER,
+        Test::IsFloat => op::IS_FLOAT,
+    }
+}
+
+fn push_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+/// A table of rows of 32 bit integers, preceded by the number of rows.
+fn table<R: AsRef<[u32]>>(rows: &[R]) -> Vec<u8> {
+    let mut out = Vec::new();
+    push_u32(&mut out, rows.len() as u32);
+    for row in rows.iter() {
+        row.as_ref()
+            .iter()
+            .for_each(|value| push_u32(&mut out, *value));
+    }
+    out
+}
+
+fn chunk(out: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(id);
+    push_u32(out, data.len() as u32);
+    out.extend_from_slice(data);
+    while out.len() % 4 != 0 {
+        out.push(0);
+    }
+}
+
+fn write_literal<W: Write>(
+    writer: &mut Writer<W>,
+    cons: &ConstantContainer,
+    literal: Literal,
+) -> Result<()> {
+    match literal {
+        Literal::Const(value) => write_const(writer, cons, value),
+        Literal::ExtFun(fun) => writer.export(
+            &fun.module.name.as_str(),
+            &fun.name.name.as_str(),
+            fun.arity as u8,
+        ),
+    }
+}
+
+fn write_const<W: Write>(
+    writer: &mut Writer<W>,
+    cons: &ConstantContainer,
+    value: Const,
+) -> Result<()> {
+    match cons.const_kind(value) {
+        ConstKind::Atomic(atomic) => write_atomic(writer, atomic)?,
+        ConstKind::ListCell { .. } => {
+            let mut elements = Vec::new();
+            let mut tail = value;
+            while let ConstKind::ListCell { head, tail: next } = cons.const_kind(tail) {
+                elements.push(*head);
+                tail = *next;
+            }
+
+            writer.list(elements.len())?;
+            for element in elements {
+                write_const(writer, cons, element)?;
+            }
+            writer.next_tail();
+            write_const(writer, cons, tail)?;
+            writer.pop();
+        }
+        ConstKind::Tuple { entries } => {
+            let entries = entries.as_slice(&cons.const_pool);
+            writer.tuple(entries.len())?;
+            for entry in entries.iter() {
+                write_const(writer, cons, *entry)?;
+            }
+            writer.pop();
+        }
+        ConstKind::Map { keys, values } => {
+            let keys = keys.as_slice(&cons.const_pool);
+            let values = values.as_slice(&cons.const_pool);
+            writer.map(keys.len())?;
+            for (key, value) in keys.iter().zip(values.iter()) {
+                write_const(writer, cons, *key)?;
+                write_const(writer, cons, *value)?;
+                writer.next_kv();
+            }
+            writer.pop();
+        }
+    }
+    Ok(())
+}
+
+fn write_atomic<W: Write>(writer: &mut Writer<W>, atomic: &AtomicTerm) -> Result<()> {
+    match atomic {
+        AtomicTerm::Int(int) => {
+            let int = int.value();
+            if let Ok(int) = u8::try_from(int) {
+                writer.integer_u8(int)
+            } else if let Ok(int) = i32::try_from(int) {
+                writer.integer_i32(int)
+            } else {
+                writer.integer_big(&BigInt::from(int))
+            }
+        }
+        AtomicTerm::BigInt(int) => writer.integer_big(int.value()),
+        AtomicTerm::Float(float) => writer.float(float.value()),
+        AtomicTerm::Atom(atom) => writer.atom(&atom.0.as_str()),
+        AtomicTerm::Nil => writer.nil(),
+        AtomicTerm::Binary(bin) => {
+            let bin = bin.value();
+            let bits = bin.bit_len();
+            let bytes = &bin.as_ref()[..(bits + 7) / 8];
+            match bits % 8 {
+                0 => writer.binary(bytes.len())?,
+                rem => writer.bit_binary(bytes.len(), rem as u8)?,
+            }
+            writer.push_data(bytes)
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! A reader for the `.beam` files we write, used to check their structure.
+//! Anything unexpected panics.
+
+use std::convert::TryInto;
+use std::io::Cursor;
+
+use libeir_etf::{Reader, Term};
+
+use super::encode::{op, tag};
+use super::zlib::adler32;
+
+/// A decoded operand.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    U(i64),
+    I(i64),
+    A(i64),
+    X(i64),
+    Y(i64),
+    F(i64),
+    List(Vec<Arg>),
+    Literal(i64),
+}
+
+pub struct BeamFile {
+    pub chunks: Vec<([u8; 4], Vec<u8>)>,
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn rows(data: &[u8], width: usize) -> Vec<Vec<u32>> {
+    let count = u32_at(data, 0) as usize;
+    assert_eq!(data.len(), 4 + count * width * 4);
+    (0..count)
+        .map(|row| {
+            (0..width)
+                .map(|col| u32_at(data, 4 + (row * width + col) * 4))
+                .collect()
+        })
+        .collect()
+}
+
+impl BeamFile {
+    pub fn parse(data: &[u8]) -> BeamFile {
+        assert_eq!(&data[0..4], b"FOR1");
+        assert_eq!(u32_at(data, 4) as usize, data.len() - 8);
+        assert_eq!(&data[8..12], b"BEAM");
+
+        let mut chunks = Vec::new();
+        let mut offset = 12;
+        while offset < data.len() {
+            assert_eq!(offset % 4, 0);
+            let id: [u8; 4] = data[offset..offset + 4].try_into().unwrap();
+            let size = u32_at(data, offset + 4) as usize;
+            let start = offset + 8;
+            chunks.push((id, data[start..start + size].to_vec()));
+
+            let end = (start + size + 3) & !3;
+            assert!(data[start + size..end].iter().all(|b| *b == 0));
+            offset = end;
+        }
+        assert_eq!(offset, data.len());
+
+        BeamFile { chunks }
+    }
+
+    pub fn ids(&self) -> Vec<&str> {
+        self.chunks
+            .iter()
+            .map(|(id, _)| std::str::from_utf8(id).unwrap())
+            .collect()
+    }
+
+    pub fn chunk(&self, id: &str) -> &[u8] {
+        &self
+            .chunks
+            .iter()
+            .find(|(chunk_id, _)| chunk_id == id.as_bytes())
+            .unwrap_or_else(|| panic!("no {} chunk", id))
+            .1
+    }
+
+    pub fn atoms(&self) -> Vec<String> {
+        let data = self.chunk("AtU8");
+        let mut offset = 4;
+        let mut atoms = Vec::new();
+        for _ in 0..u32_at(data, 0) {
+            let len = data[offset] as usize;
+            let atom = std::str::from_utf8(&data[offset + 1..offset + 1 + len]).unwrap();
+            atoms.push(atom.to_string());
+            offset += 1 + len;
+        }
+        assert_eq!(offset, data.len());
+        atoms
+    }
+
+    fn atom(&self, index: u32) -> String {
+        self.atoms()[index as usize - 1].clone()
+    }
+
+    /// The imports, as `(module, function, arity)`.
+    pub fn imports(&self) -> Vec<(String, String, u32)> {
+        rows(self.chunk("ImpT"), 3)
+            .iter()
+            .map(|row| (self.atom(row[0]), self.atom(row[1]), row[2]))
+            .collect()
+    }
+
+    /// The exports, as `(function, arity, label)`.
+    pub fn exports(&self) -> Vec<(String, u32, u32)> {
+        rows(self.chunk("ExpT"), 3)
+            .iter()
+            .map(|row| (self.atom(row[0]), row[1], row[2]))
+            .collect()
+    }
+
+    /// The local functions, as `(function, arity, label)`.
+    pub fn locals(&self) -> Vec<(String, u32, u32)> {
+        rows(self.chunk("LocT"), 3)
+            .iter()
+            .map(|row| (self.atom(row[0]), row[1], row[2]))
+            .collect()
+    }
+
+    /// The lambdas, as `(function, arity, label, index, num_free, old_uniq)`.
+    pub fn lambdas(&self) -> Vec<(String, u32, u32, u32, u32, u32)> {
+        rows(self.chunk("FunT"), 6)
+            .iter()
+            .map(|r| (self.atom(r[0]), r[1], r[2], r[3], r[4], r[5]))
+            .collect()
+    }
+
+    pub fn literals(&self) -> Vec<Term> {
+        let data = self.chunk("LitT");
+        let table = inflate_stored(&data[4..]);
+        assert_eq!(u32_at(data, 0) as usize, table.len());
+
+        let mut offset = 4;
+        let mut literals = Vec::new();
+        for _ in 0..u32_at(&table, 0) {
+            let size = u32_at(&table, offset) as usize;
+            let mut cursor = Cursor::new(&table[offset + 4..offset + 4 + size]);
+            let mut reader = Reader::new(&mut cursor);
+            reader.header().unwrap();
+            literals.push(reader.term().unwrap());
+            assert_eq!(cursor.position() as usize, size);
+            offset += 4 + size;
+        }
+        assert_eq!(offset, table.len());
+        literals
+    }
+
+    /// The code chunk header, as `(max_opcode, labels, functions)`.
+    pub fn code_header(&self) -> (u32, u32, u32) {
+        let data = self.chunk("Code");
+        assert_eq!(u32_at(data, 0), 16);
+        assert_eq!(u32_at(data, 4), 0);
+        (u32_at(data, 8), u32_at(data, 12), u32_at(data, 16))
+    }
+
+    pub fn code(&self) -> Vec<(u8, Vec<Arg>)> {
+        let data = &self.chunk("Code")[20..];
+        let mut offset = 0;
+        let mut instrs = Vec::new();
+        while offset < data.len() {
+            let opcode = data[offset];
+            offset += 1;
+            let arity = op::arity(opcode).unwrap_or_else(|| panic!("opcode {}", opcode));
+            let args = (0..arity).map(|_| decode(data, &mut offset)).collect();
+            instrs.push((opcode, args));
+        }
+        assert_eq!(instrs.last().unwrap().0, op::INT_CODE_END);
+        instrs
+    }
+}
+
+pub fn decode(data: &[u8], offset: &mut usize) -> Arg {
+    let first = data[*offset];
+    let (tag, value) = decode_value(data, offset);
+    match tag {
+        tag::U => Arg::U(value),
+        tag::I => Arg::I(value),
+        tag::A => Arg::A(value),
+        tag::X => Arg::X(value),
+        tag::Y => Arg::Y(value),
+        tag::F => Arg::F(value),
+        tag::Z => {
+            // The kind of an extended operand is always small.
+            assert_eq!(first & 0b1000, 0);
+            let operand = decode(data, offset);
+            match (value, operand) {
+                (tag::EXT_LIST, Arg::U(len)) => {
+                    Arg::List((0..len).map(|_| decode(data, offset)).collect())
+                }
+                (tag::EXT_LITERAL, Arg::U(index)) => Arg::Literal(index),
+                (kind, operand) => panic!("extended operand {} {:?}", kind, operand),
+            }
+        }
+        tag => panic!("tag {}", tag),
+    }
+}
+
+fn decode_value(data: &[u8], offset: &mut usize) -> (u8, i64) {
+    let first = data[*offset];
+    *offset += 1;
+    let tag = first & 0b111;
+
+    if first & 0b1000 == 0 {
+        (tag, (first >> 4) as i64)
+    } else if first & 0b1_0000 == 0 {
+        let value = ((first as i64 & 0b1110_0000) << 3) | data[*offset] as i64;
+        *offset += 1;
+        (tag, value)
+    } else {
+        let len = (first >> 5) as usize + 2;
+        assert!(len <= 8);
+        let bytes = &data[*offset..*offset + len];
+        *offset += len;
+        let mut value = if bytes[0] & 0x80 != 0 { -1 } else { 0 };
+        for byte in bytes.iter() {
+            value = (value << 8) | *byte as i64;
+        }
+        (tag, value)
+    }
+}
+
+/// Inflates a zlib stream made up of stored blocks only.
+fn inflate_stored(data: &[u8]) -> Vec<u8> {
+    assert_eq!(data[0] & 0x0f, 8);
+    assert_eq!(u16::from_be_bytes([data[0], data[1]]) % 31, 0);
+
+    let mut out = Vec::new();
+    let mut offset = 2;
+    loop {
+        let header = data[offset];
+        assert_eq!(header & 0b110, 0, "not a stored block");
+        let len = u16::from_le_bytes([data[offset + 1], data[offset + 2]]);
+        let nlen = u16::from_le_bytes([data[offset + 3], data[offset + 4]]);
+        assert_eq!(len, !nlen);
+        offset += 5;
+        out.extend_from_slice(&data[offset..offset + len as usize]);
+        offset += len as usize;
+        if header & 1 == 1 {
+            break;
+        }
+    }
+
+    assert_eq!(u32_at(data, offset), adler32(&out));
+    assert_eq!(offset + 4, data.len());
+    out
+}
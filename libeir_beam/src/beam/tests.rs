@@ -0,0 +1,243 @@
+use libeir_etf::Term;
+use libeir_intern::Symbol;
+use libeir_ir::{parse_module_unwrap, ConstantContainer};
+
+use super::encode::{encode, op, tag};
+use super::reader::{decode, Arg, BeamFile};
+use crate::asm::{Function, Instr, Label, Module, Reg, Src};
+use crate::compile_module;
+
+fn assemble(text: &str) -> BeamFile {
+    let module = parse_module_unwrap(text);
+    let asm = compile_module(&module).unwrap();
+    BeamFile::parse(&asm.to_beam())
+}
+
+fn assert_has_instr(file: &BeamFile, opcode: u8, args: Vec<Arg>) {
+    let instr = (opcode, args);
+    let code = file.code();
+    assert!(code.contains(&instr), "{:?} not in {:?}", instr, code);
+}
+
+#[test]
+fn compact_encoding() {
+    let cases: &[(u8, i64, &[u8])] = &[
+        (tag::U, 0, &[0x00]),
+        (tag::X, 15, &[0xf3]),
+        (tag::U, 16, &[0x08, 0x10]),
+        (tag::F, 2047, &[0xed, 0xff]),
+        (tag::I, 2048, &[0x19, 0x08, 0x00]),
+        (tag::I, -1, &[0x19, 0xff, 0xff]),
+        (tag::I, 0x8000, &[0x39, 0x00, 0x80, 0x00]),
+        (tag::I, i64::MIN, &[0xd9, 0x80, 0, 0, 0, 0, 0, 0, 0]),
+    ];
+
+    for (tag, value, expected) in cases.iter() {
+        let mut out = Vec::new();
+        encode(&mut out, *tag, *value);
+        assert_eq!(&out[..], *expected, "{}", value);
+
+        let mut offset = 0;
+        let arg = decode(&out, &mut offset);
+        assert_eq!(offset, out.len());
+        let decoded = match arg {
+            Arg::U(n) | Arg::I(n) | Arg::X(n) | Arg::F(n) => n,
+            arg => panic!("{:?}", arg),
+        };
+        assert_eq!(decoded, *value);
+    }
+}
+
+#[test]
+fn chunk_layout() {
+    let file = assemble(
+        "
+a'foo' {
+    a'id'/1 {
+        entry(%ret, %thr, %a):
+            %ret(%a);
+    }
+}
+",
+    );
+
+    assert_eq!(
+        file.ids(),
+        vec!["AtU8", "Code", "StrT", "ImpT", "ExpT", "LocT", "Attr", "CInf", "Line"]
+    );
+    assert_eq!(file.atoms(), vec!["foo", "id"]);
+    assert_eq!(file.exports(), vec![("id".to_string(), 1, 2)]);
+    assert!(file.locals().is_empty());
+    assert!(file.imports().is_empty());
+    assert!(file.chunk("StrT").is_empty());
+    assert_eq!(file.chunk("Attr"), &[131, 106]);
+
+    assert_eq!(file.code_header(), (op::RETURN as u32, 3, 1));
+    assert_eq!(
+        file.code(),
+        vec![
+            (op::LABEL, vec![Arg::U(1)]),
+            (op::FUNC_INFO, vec![Arg::A(1), Arg::A(2), Arg::U(1)]),
+            (op::LABEL, vec![Arg::U(2)]),
+            (op::RETURN, vec![]),
+            (op::INT_CODE_END, vec![]),
+        ]
+    );
+}
+
+#[test]
+fn imports() {
+    let file = assemble(
+        "
+a'foo' {
+    a'pair'/1 {
+        entry(%ret, %thr, %a):
+            a'lists':a'reverse'/1(%a) => next except %thr;
+        next(%r):
+            %ret({%a, %r});
+    }
+}
+",
+    );
+
+    assert_eq!(
+        file.imports(),
+        vec![("lists".to_string(), "reverse".to_string(), 1)]
+    );
+    assert_has_instr(&file, op::CALL_EXT, vec![Arg::U(1), Arg::U(0)]);
+    assert_has_instr(
+        &file,
+        op::PUT_TUPLE2,
+        vec![Arg::X(0), Arg::List(vec![Arg::Y(0), Arg::X(0)])],
+    );
+}
+
+#[test]
+fn lambda_table() {
+    let file = assemble(
+        "
+a'foo' {
+    a'adder'/1 {
+        entry(%ret, %thr, %a):
+            %ret(inner);
+        inner(%iret, %ithr, %b):
+            %iret(%a);
+    }
+}
+",
+    );
+
+    let lambda = "-adder/1-fun-0-".to_string();
+    assert_eq!(file.lambdas(), vec![(lambda.clone(), 2, 4, 0, 1, 0)]);
+    assert_eq!(file.exports(), vec![("adder".to_string(), 1, 2)]);
+    assert_eq!(file.locals(), vec![(lambda, 2, 4)]);
+    assert_has_instr(&file, op::MAKE_FUN2, vec![Arg::U(0)]);
+}
+
+#[test]
+fn literal_table() {
+    let mut cons = ConstantContainer::new();
+    let float = cons.from(1.5);
+    let ok = cons.from(Symbol::intern("ok"));
+    let one = cons.from(1i64);
+    let two = cons.from(2i64);
+    let large = cons.from(70000i64);
+    let nil = cons.nil();
+    let tail = cons.list_cell(two, nil);
+    let list = cons.list_cell(one, tail);
+    let mut tuple = cons.tuple_builder();
+    tuple.push(ok, &mut cons);
+    tuple.push(list, &mut cons);
+    tuple.push(large, &mut cons);
+    let tuple = tuple.finish(&mut cons);
+    // Spans more than one stored block in the zlib stream.
+    let binary = cons.from(vec![7u8; 70000]);
+    let negative = cons.from(-5i64);
+
+    let name = Symbol::intern("lit");
+    let module = Module {
+        name: Symbol::intern("foo"),
+        exports: vec![(name, 0)],
+        functions: vec![Function {
+            name,
+            arity: 0,
+            entry: Label(2),
+            code: vec![
+                Instr::Label(Label(1)),
+                Instr::FuncInfo {
+                    module: Symbol::intern("foo"),
+                    name,
+                    arity: 0,
+                },
+                Instr::Label(Label(2)),
+                Instr::Move {
+                    src: Src::Const(float),
+                    dst: Reg::X(0),
+                },
+                Instr::Move {
+                    src: Src::Const(tuple),
+                    dst: Reg::X(1),
+                },
+                Instr::Move {
+                    src: Src::Const(float),
+                    dst: Reg::X(2),
+                },
+                Instr::Move {
+                    src: Src::Const(binary),
+                    dst: Reg::X(3),
+                },
+                Instr::Move {
+                    src: Src::Const(negative),
+                    dst: Reg::X(4),
+                },
+                Instr::Move {
+                    src: Src::Const(ok),
+                    dst: Reg::X(5),
+                },
+                Instr::Move {
+                    src: Src::Const(nil),
+                    dst: Reg::X(6),
+                },
+                Instr::Return,
+            ],
+        }],
+        labels: 3,
+        cons,
+    };
+    let file = BeamFile::parse(&module.to_beam());
+
+    assert_eq!(
+        file.literals(),
+        vec![
+            Term::Float(1.5),
+            Term::Tuple(vec![
+                Term::Atom("ok".to_string()),
+                Term::List(
+                    vec![Term::Integer(1), Term::Integer(2)],
+                    Box::new(Term::Nil)
+                ),
+                Term::Integer(70000),
+            ]),
+            Term::Binary(vec![7; 70000]),
+        ]
+    );
+
+    let moves: Vec<_> = file
+        .code()
+        .into_iter()
+        .filter(|(opcode, _)| *opcode == op::MOVE)
+        .map(|(_, args)| args[0].clone())
+        .collect();
+    assert_eq!(
+        moves,
+        vec![
+            Arg::Literal(0),
+            Arg::Literal(1),
+            Arg::Literal(0),
+            Arg::Literal(2),
+            Arg::I(-5),
+            Arg::A(3),
+            Arg::A(0),
+        ]
+    );
+}
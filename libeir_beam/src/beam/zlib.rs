@@ -0,0 +1,41 @@
+//! The zlib format, as required by the literal table.
+//!
+//! The data is written in stored deflate blocks, without any actual
+//! compression. The loader only cares that the stream inflates.
+
+/// The largest amount of data a stored block can hold.
+const MAX_BLOCK: usize = 0xffff;
+
+pub fn store(data: &[u8]) -> Vec<u8> {
+    // Deflate, with a 32K window and no preset dictionary.
+    let mut out = vec![0x78, 0x01];
+
+    let mut blocks: Vec<&[u8]> = data.chunks(MAX_BLOCK).collect();
+    if blocks.is_empty() {
+        blocks.push(&[]);
+    }
+
+    let last = blocks.len() - 1;
+    for (idx, block) in blocks.iter().enumerate() {
+        // BFINAL in the lowest bit, BTYPE 00 for a stored block.
+        out.push((idx == last) as u8);
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    for byte in data.iter() {
+        a = (a + *byte as u32) % MOD;
+        b = (b + a) % MOD;
+    }
+    (b << 16) | a
+}
@@ -0,0 +1,1165 @@
+//! Code generation from LIR to BEAM assembly.
+//!
+//! Every Eir function in the module is lowered to LIR, and every LIR
+//! function becomes a BEAM function. Nested functions are named like the
+//! funs `erlc` generates, `-name/arity-fun-N-`, and take their environment
+//! as trailing arguments.
+
+mod moves;
+mod regalloc;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use libeir_intern::{Ident, Symbol};
+use libeir_ir::{AtomicTerm, BasicType, BinOp, ConstKind, ConstantContainer, FunctionIdent};
+use libeir_ir::{LogicOp, MatchKind, OpKind, PrimOpKind};
+use libeir_lir::{lower_function, Block, Cont, FunId, Function, Inst, LowerError};
+use libeir_lir::{Operand, Terminator, Unit, Var};
+
+use self::moves::parallel_move;
+use self::regalloc::Allocation;
+use crate::asm::{self, Instr, Label, Reg, Src, Test};
+
+#[derive(Debug)]
+pub enum CompileError {
+    /// The function could not be lowered to LIR.
+    Lower {
+        fun: FunctionIdent,
+        error: LowerError,
+    },
+    /// The function contains an operation the backend can not generate
+    /// code for.
+    UnsupportedOp { fun: FunctionIdent, op: String },
+    /// A local function was captured that is not defined in the module.
+    UnknownLocal {
+        fun: FunctionIdent,
+        name: Symbol,
+        arity: usize,
+    },
+}
+
+/// Generates BEAM assembly for every function in the module. All functions
+/// are exported.
+pub fn compile_module(module: &libeir_ir::Module) -> Result<asm::Module, CompileError> {
+    let mut units = Vec::new();
+    for def in module.function_iter() {
+        let fun = def.function();
+        let data = libeir_lowerutils::analyze(fun);
+        let unit = lower_function(fun, &data).map_err(|error| CompileError::Lower {
+            fun: *fun.ident(),
+            error,
+        })?;
+        units.push(unit);
+    }
+
+    let mut ctx = ModuleContext {
+        name: module.name().name,
+        next_label: 1,
+        entries: HashMap::new(),
+        lambdas: HashMap::new(),
+        cons: ConstantContainer::new(),
+    };
+
+    // Calls can go to any function in the module, the labels of every
+    // function are assigned before any code is generated.
+    let mut layouts = Vec::with_capacity(units.len());
+    for unit in units.iter() {
+        let name = unit.ident.name.name;
+        let arity = unit.ident.arity;
+
+        let mut layout = BTreeMap::new();
+        let mut lambdas = 0;
+        for (id, function) in unit.functions.iter() {
+            let (fun_name, fun_arity) = if id == unit.root {
+                (name, arity)
+            } else {
+                let lambda = format!("-{}/{}-fun-{}-", name, arity, lambdas);
+                lambdas += 1;
+                (
+                    Symbol::intern(&lambda),
+                    function.arity() + function.env.len(),
+                )
+            };
+            let info = ctx.label();
+            let entry = ctx.label();
+            layout.insert(
+                id,
+                FunctionLayout {
+                    name: fun_name,
+                    arity: fun_arity as u32,
+                    info,
+                    entry,
+                },
+            );
+        }
+
+        ctx.entries.insert((name, arity), layout[&unit.root].entry);
+        layouts.push(layout);
+    }
+
+    // Funs go after all top level functions, as with `erlc`.
+    let mut functions = Vec::new();
+    let mut lambdas = Vec::new();
+    for (unit, layout) in units.iter().zip(layouts.iter()) {
+        for (id, function) in unit.functions.iter() {
+            let code = FunctionGen::new(&mut ctx, unit, layout, function)?.generate(id)?;
+            if id == unit.root {
+                functions.push(code);
+            } else {
+                lambdas.push(code);
+            }
+        }
+    }
+    functions.extend(lambdas);
+    let labels = renumber_labels(&mut functions);
+
+    Ok(asm::Module {
+        name: ctx.name,
+        exports: units
+            .iter()
+            .map(|unit| (unit.ident.name.name, unit.ident.arity as u32))
+            .collect(),
+        functions,
+        labels,
+        cons: ctx.cons,
+    })
+}
+
+/// Renumbers labels in the order they are defined, returning one more than
+/// the highest label.
+fn renumber_labels(functions: &mut [asm::Function]) -> u32 {
+    let mut map = HashMap::new();
+    map.insert(Label::NONE, Label::NONE);
+    for function in functions.iter() {
+        for instr in function.code.iter() {
+            if let Instr::Label(label) = instr {
+                let next = Label(map.len() as u32);
+                map.insert(*label, next);
+            }
+        }
+    }
+
+    let rename = |label: &mut Label| *label = map[label];
+    for function in functions.iter_mut() {
+        rename(&mut function.entry);
+        for instr in function.code.iter_mut() {
+            match instr {
+                Instr::Label(label)
+                | Instr::Jump(label)
+                | Instr::Call { target: label, .. }
+                | Instr::CallLast { target: label, .. }
+                | Instr::CallOnly { target: label, .. }
+                | Instr::Test { fail: label, .. }
+                | Instr::TestArity { fail: label, .. }
+                | Instr::GetMapElements { fail: label, .. }
+                | Instr::MakeFun2 { target: label, .. }
+                | Instr::Try { handler: label, .. } => rename(label),
+                _ => (),
+            }
+        }
+    }
+
+    map.len() as u32
+}
+
+struct ModuleContext {
+    name: Symbol,
+    next_label: u32,
+    /// The entry labels of the top level functions, by name and arity.
+    entries: HashMap<(Symbol, usize), Label>,
+    /// The index of every function a fun is made from, in the order they
+    /// were first used.
+    lambdas: HashMap<Label, u32>,
+    cons: ConstantContainer,
+}
+
+impl ModuleContext {
+    fn label(&mut self) -> Label {
+        let label = Label(self.next_label);
+        self.next_label += 1;
+        label
+    }
+
+    fn lambda_index(&mut self, target: Label) -> u32 {
+        let next = self.lambdas.len() as u32;
+        *self.lambdas.entry(target).or_insert(next)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct FunctionLayout {
+    name: Symbol,
+    arity: u32,
+    /// The label of the `func_info` instruction.
+    info: Label,
+    entry: Label,
+}
+
+/// A function that is called directly, rather than through a fun.
+#[derive(Debug, Copy, Clone)]
+enum Callee {
+    /// A function in the current module, with its entry label.
+    Local(FunctionIdent, Label),
+    External(FunctionIdent),
+}
+
+enum Target {
+    Local(Label),
+    External(FunctionIdent),
+    Fun,
+}
+
+fn operand_vars(operand: &Operand, out: &mut BTreeSet<Var>) {
+    match operand {
+        Operand::Var(var) => {
+            out.insert(*var);
+        }
+        Operand::List(list) => list.iter().for_each(|o| operand_vars(o, out)),
+        Operand::Const(_) | Operand::Block(_) => (),
+    }
+}
+
+fn const_atom(cons: &ConstantContainer, operand: &Operand) -> Option<Symbol> {
+    match operand {
+        Operand::Const(value) => match cons.const_kind(*value) {
+            ConstKind::Atomic(AtomicTerm::Atom(atom)) => Some(atom.0),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn const_int(cons: &ConstantContainer, operand: &Operand) -> Option<usize> {
+    match operand {
+        Operand::Const(value) => match cons.const_kind(*value) {
+            ConstKind::Atomic(AtomicTerm::Int(int)) if int.value() >= 0 => {
+                Some(int.value() as usize)
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn erlang_fun(name: &str, arity: usize) -> FunctionIdent {
+    FunctionIdent {
+        module: Ident::from_str("erlang"),
+        name: Ident::from_str(name),
+        arity,
+    }
+}
+
+fn binop_name(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Equal => "==",
+        BinOp::NotEqual => "/=",
+        BinOp::LessEqual => "=<",
+        BinOp::Less => "<",
+        BinOp::GreaterEqual => ">=",
+        BinOp::Greater => ">",
+        BinOp::ExactEqual => "=:=",
+        BinOp::ExactNotEqual => "=/=",
+    }
+}
+
+/// Facts about a function that decide both register allocation and code
+/// generation.
+struct Analysis {
+    /// Function captures with constant targets.
+    callees: HashMap<Var, Callee>,
+    /// Captures that are only ever called, they are never constructed.
+    skipped: HashSet<Var>,
+    /// Raw stack traces captured outside of an exception handler. These
+    /// carry no information, the exception is raised from scratch.
+    fresh: HashSet<Var>,
+}
+
+impl Analysis {
+    fn new(
+        ctx: &ModuleContext,
+        unit: &Unit,
+        function: &Function,
+    ) -> Result<Analysis, CompileError> {
+        let cons = &unit.cons;
+
+        let mut callees = HashMap::new();
+        let mut used = BTreeSet::new();
+        let mut fresh = HashSet::new();
+
+        for data in function.blocks.values() {
+            for inst in data.insts.iter() {
+                inst.operands()
+                    .iter()
+                    .for_each(|o| operand_vars(o, &mut used));
+
+                let (dest, kind, args) = match inst {
+                    Inst::PrimOp { dest, kind, args } => (*dest, kind, args),
+                    Inst::MakeClosure { .. } => continue,
+                };
+                match (kind, &args[..]) {
+                    (PrimOpKind::CaptureFunction, [m, f, a]) => {
+                        let target = (const_atom(cons, m), const_atom(cons, f), const_int(cons, a));
+                        if let (Some(m), Some(f), Some(a)) = target {
+                            let ident = FunctionIdent {
+                                module: Ident::with_empty_span(m),
+                                name: Ident::with_empty_span(f),
+                                arity: a,
+                            };
+                            // Calls within the module stay local, as they
+                            // do with `erlc`.
+                            let callee = match ctx.entries.get(&(f, a)) {
+                                Some(entry) if m == ctx.name => Callee::Local(ident, *entry),
+                                _ => Callee::External(ident),
+                            };
+                            callees.insert(dest, callee);
+                        }
+                    }
+                    (PrimOpKind::CaptureLocalFunction, [f, a]) => {
+                        if let (Some(f), Some(a)) = (const_atom(cons, f), const_int(cons, a)) {
+                            let entry = match ctx.entries.get(&(f, a)) {
+                                Some(entry) => *entry,
+                                None => {
+                                    return Err(CompileError::UnknownLocal {
+                                        fun: unit.ident,
+                                        name: f,
+                                        arity: a,
+                                    })
+                                }
+                            };
+                            let ident = FunctionIdent {
+                                module: Ident::with_empty_span(ctx.name),
+                                name: Ident::with_empty_span(f),
+                                arity: a,
+                            };
+                            callees.insert(dest, Callee::Local(ident, entry));
+                        }
+                    }
+                    _ => (),
+                }
+            }
+
+            match &data.term {
+                Terminator::Call { args, .. } | Terminator::TailCall { args, .. } => {
+                    args.iter().for_each(|o| operand_vars(o, &mut used));
+                }
+                Terminator::Op {
+                    kind: OpKind::TraceCaptureRaw,
+                    reads,
+                } => {
+                    if let Some(Operand::Block(target)) = reads.get(0) {
+                        fresh.extend(function.blocks[*target].params.first().cloned());
+                    }
+                }
+                term => term.walk_operands(|o| operand_vars(o, &mut used)),
+            }
+        }
+
+        let skipped = callees
+            .keys()
+            .filter(|var| !used.contains(var))
+            .cloned()
+            .collect();
+
+        Ok(Analysis {
+            callees,
+            skipped,
+            fresh,
+        })
+    }
+
+    fn direct(&self, callee: &Operand) -> Option<&Callee> {
+        match callee {
+            Operand::Var(var) => self.callees.get(var),
+            _ => None,
+        }
+    }
+
+    fn is_fresh(&self, trace: &Operand) -> bool {
+        match trace {
+            Operand::Var(var) => self.fresh.contains(var),
+            _ => true,
+        }
+    }
+
+    /// Whether the instruction overwrites x registers other than its
+    /// destination.
+    fn clobbers(&self, inst: &Inst) -> bool {
+        match inst {
+            Inst::MakeClosure { .. } => true,
+            Inst::PrimOp {
+                kind: PrimOpKind::CaptureLocalFunction,
+                ..
+            } => true,
+            _ => false,
+        }
+    }
+}
+
+struct FunctionGen<'a> {
+    ctx: &'a mut ModuleContext,
+    unit: &'a Unit,
+    layout: &'a BTreeMap<FunId, FunctionLayout>,
+    function: &'a Function,
+
+    analysis: Analysis,
+    alloc: Allocation,
+    blocks: HashMap<Block, Label>,
+    /// The number of y registers in the stack frame, if the function needs
+    /// one.
+    frame: Option<u32>,
+    /// Holds the catch tag while in a try block.
+    try_tag: Reg,
+    /// An x register that is never allocated, for breaking move cycles and
+    /// for materializing constants.
+    scratch: Reg,
+
+    code: Vec<Instr>,
+    /// Exception handlers, placed after the blocks of the function.
+    handlers: Vec<Instr>,
+}
+
+impl<'a> FunctionGen<'a> {
+    fn new(
+        ctx: &'a mut ModuleContext,
+        unit: &'a Unit,
+        layout: &'a BTreeMap<FunId, FunctionLayout>,
+        function: &'a Function,
+    ) -> Result<Self, CompileError> {
+        let analysis = Analysis::new(ctx, unit, function)?;
+        let alloc = regalloc::allocate(function, &analysis);
+
+        let mut needs_frame = alloc.y_count > 0;
+        let mut has_try = false;
+        let mut max_x = alloc
+            .x_count
+            .max((function.arity() + function.env.len()) as u32)
+            .max(3);
+        for data in function.blocks.values() {
+            match &data.term {
+                Terminator::Call { args, thr, .. } => {
+                    needs_frame = true;
+                    has_try |= matches!(thr, Cont::Block(_));
+                    max_x = max_x.max(args.len() as u32 + 1);
+                }
+                Terminator::TailCall { callee, args } => {
+                    // Calling a fun can not replace the stack frame.
+                    needs_frame |= analysis.direct(callee).is_none();
+                    max_x = max_x.max(args.len() as u32 + 1);
+                }
+                _ => (),
+            }
+            for inst in data.insts.iter() {
+                if let Inst::MakeClosure { env, .. } = inst {
+                    max_x = max_x.max(env.len() as u32);
+                }
+            }
+        }
+
+        let frame = if needs_frame || has_try {
+            Some(alloc.y_count + has_try as u32)
+        } else {
+            None
+        };
+
+        let mut blocks = HashMap::new();
+        for block in function.blocks.keys() {
+            if block != function.entry {
+                blocks.insert(block, ctx.label());
+            }
+        }
+
+        Ok(FunctionGen {
+            ctx,
+            unit,
+            layout,
+            function,
+            try_tag: Reg::Y(alloc.y_count),
+            scratch: Reg::X(max_x),
+            analysis,
+            alloc,
+            blocks,
+            frame,
+            code: Vec::new(),
+            handlers: Vec::new(),
+        })
+    }
+
+    fn generate(mut self, id: FunId) -> Result<asm::Function, CompileError> {
+        let function = self.function;
+        let layout = self.layout[&id];
+
+        self.code.push(Instr::Label(layout.info));
+        self.code.push(Instr::FuncInfo {
+            module: self.ctx.name,
+            name: layout.name,
+            arity: layout.arity,
+        });
+        self.code.push(Instr::Label(layout.entry));
+
+        if let Some(stack) = self.frame {
+            let live = layout.arity;
+            self.code.push(if stack == 0 {
+                Instr::Allocate { stack, live }
+            } else {
+                Instr::AllocateZero { stack, live }
+            });
+        }
+
+        // Arguments that need to survive a call are moved to the stack.
+        let entry = &function.blocks[function.entry];
+        let moves = entry
+            .params
+            .iter()
+            .chain(function.env.iter())
+            .enumerate()
+            .map(|(idx, var)| (Src::Reg(Reg::X(idx as u32)), self.alloc.reg(*var)))
+            .collect();
+        self.moves(moves);
+
+        for (block, data) in function.blocks.iter() {
+            if block != function.entry {
+                self.code.push(Instr::Label(self.blocks[&block]));
+            }
+            for (idx, inst) in data.insts.iter().enumerate() {
+                self.inst(block, idx, inst)?;
+            }
+            self.terminator(&data.term)?;
+        }
+
+        let handlers = std::mem::replace(&mut self.handlers, Vec::new());
+        self.code.extend(handlers);
+
+        Ok(asm::Function {
+            name: layout.name,
+            arity: layout.arity,
+            entry: layout.entry,
+            code: self.code,
+        })
+    }
+
+    fn unsupported<T>(&self, op: String) -> Result<T, CompileError> {
+        Err(CompileError::UnsupportedOp {
+            fun: self.unit.ident,
+            op,
+        })
+    }
+
+    fn src(&mut self, operand: &Operand) -> Result<Src, CompileError> {
+        match operand {
+            Operand::Var(var) => Ok(Src::Reg(self.alloc.reg(*var))),
+            Operand::Const(value) => Ok(Src::Const(self.ctx.cons.import(&self.unit.cons, *value))),
+            operand => self.unsupported(format!("{:?} as a value", operand)),
+        }
+    }
+
+    fn srcs(&mut self, operands: &[Operand]) -> Result<Vec<Src>, CompileError> {
+        operands.iter().map(|o| self.src(o)).collect()
+    }
+
+    /// A value in a register, constants are moved into the scratch register.
+    fn reg(&mut self, operand: &Operand) -> Result<Reg, CompileError> {
+        match self.src(operand)? {
+            Src::Reg(reg) => Ok(reg),
+            src => {
+                self.code.push(Instr::Move {
+                    src,
+                    dst: self.scratch,
+                });
+                Ok(self.scratch)
+            }
+        }
+    }
+
+    fn atom(&mut self, name: &str) -> Src {
+        Src::Const(self.ctx.cons.from(Symbol::intern(name)))
+    }
+
+    fn boolean(&mut self, value: bool) -> Src {
+        Src::Const(self.ctx.cons.from(value))
+    }
+
+    fn nil(&mut self) -> Src {
+        Src::Const(self.ctx.cons.nil())
+    }
+
+    fn block_operand(&self, operand: &Operand) -> Result<Block, CompileError> {
+        match operand {
+            Operand::Block(block) => Ok(*block),
+            operand => self.unsupported(format!("{:?} as a destination", operand)),
+        }
+    }
+
+    fn params(&self, block: Block) -> Vec<Reg> {
+        self.function.blocks[block]
+            .params
+            .iter()
+            .map(|var| self.alloc.reg(*var))
+            .collect()
+    }
+
+    fn moves(&mut self, moves: Vec<(Src, Reg)>) {
+        parallel_move(&moves, self.scratch, &mut self.code);
+    }
+
+    fn args(&mut self, args: Vec<Src>) {
+        let moves = args
+            .into_iter()
+            .enumerate()
+            .map(|(idx, src)| (src, Reg::X(idx as u32)))
+            .collect();
+        self.moves(moves);
+    }
+
+    fn jump(&mut self, target: Block, values: Vec<Src>) {
+        let moves = values.into_iter().zip(self.params(target)).collect();
+        self.moves(moves);
+        self.code.push(Instr::Jump(self.blocks[&target]));
+    }
+
+    fn ret(&mut self) {
+        if let Some(stack) = self.frame {
+            self.code.push(Instr::Deallocate(stack));
+        }
+        self.code.push(Instr::Return);
+    }
+
+    fn tail_call_ext(&mut self, fun: FunctionIdent) {
+        self.code.push(match self.frame {
+            Some(dealloc) => Instr::CallExtLast { fun, dealloc },
+            None => Instr::CallExtOnly(fun),
+        });
+    }
+
+    /// The number of x registers that must be preserved by the heap
+    /// allocation of instruction `idx` in `block`, which defines `dest`.
+    ///
+    /// The garbage collector reads every register below the count, those
+    /// that hold nothing are cleared.
+    fn heap_live(&mut self, block: Block, idx: usize, dest: Var, operands: &[Src]) -> u32 {
+        let mut live = BTreeSet::new();
+        for var in self.alloc.live_after(block, idx).iter() {
+            if *var == dest {
+                continue;
+            }
+            if let Reg::X(n) = self.alloc.reg(*var) {
+                live.insert(n);
+            }
+        }
+        for src in operands.iter() {
+            if let Src::Reg(Reg::X(n)) = src {
+                live.insert(*n);
+            }
+        }
+
+        let count = live.iter().next_back().map(|n| n + 1).unwrap_or(0);
+        for n in 0..count {
+            if !live.contains(&n) {
+                let nil = self.nil();
+                self.code.push(Instr::Move {
+                    src: nil,
+                    dst: Reg::X(n),
+                });
+            }
+        }
+        count
+    }
+
+    fn inst(&mut self, block: Block, idx: usize, inst: &Inst) -> Result<(), CompileError> {
+        let (dest, kind, args) = match inst {
+            Inst::PrimOp { dest, kind, args } => (*dest, kind, args),
+            Inst::MakeClosure { dest, fun, env } => {
+                let target = self.layout[fun].entry;
+                let env = self.srcs(env)?;
+                let dst = self.alloc.reg(*dest);
+                self.make_fun(target, env, dst);
+                return Ok(());
+            }
+        };
+        if self.analysis.skipped.contains(&dest) {
+            return Ok(());
+        }
+        let dst = self.alloc.reg(dest);
+
+        match kind {
+            PrimOpKind::Tuple if args.is_empty() => {
+                let builder = self.ctx.cons.tuple_builder();
+                let empty = builder.finish(&mut self.ctx.cons);
+                self.code.push(Instr::Move {
+                    src: Src::Const(empty),
+                    dst,
+                });
+            }
+            PrimOpKind::Tuple => {
+                let elements = self.srcs(args)?;
+                let live = self.heap_live(block, idx, dest, &elements);
+                self.code.push(Instr::TestHeap {
+                    need: elements.len() as u32 + 1,
+                    live,
+                });
+                self.code.push(Instr::PutTuple2 { dst, elements });
+            }
+            PrimOpKind::ListCell => {
+                let cell = self.srcs(args)?;
+                let live = self.heap_live(block, idx, dest, &cell);
+                self.code.push(Instr::TestHeap { need: 2, live });
+                self.code.push(Instr::PutList {
+                    head: cell[0],
+                    tail: cell[1],
+                    dst,
+                });
+            }
+            PrimOpKind::BinOp(op) => {
+                let args = self.srcs(args)?;
+                self.code.push(Instr::Bif {
+                    name: Symbol::intern(binop_name(*op)),
+                    args,
+                    dst,
+                });
+            }
+            PrimOpKind::LogicOp(LogicOp::Eq) if args.len() == 2 => {
+                let args = self.srcs(args)?;
+                self.code.push(Instr::Bif {
+                    name: Symbol::intern("=:="),
+                    args,
+                    dst,
+                });
+            }
+            PrimOpKind::LogicOp(LogicOp::And) => self.logic_op("and", true, args, dst)?,
+            PrimOpKind::LogicOp(LogicOp::Or) => self.logic_op("or", false, args, dst)?,
+            PrimOpKind::IsType(typ) => {
+                let src = self.reg(&args[0])?;
+                let fail = self.ctx.label();
+                let done = self.ctx.label();
+                self.type_test(*typ, src, fail)?;
+                let true_val = self.boolean(true);
+                self.code.push(Instr::Move { src: true_val, dst });
+                self.code.push(Instr::Jump(done));
+                self.code.push(Instr::Label(fail));
+                let false_val = self.boolean(false);
+                self.code.push(Instr::Move {
+                    src: false_val,
+                    dst,
+                });
+                self.code.push(Instr::Label(done));
+            }
+            PrimOpKind::CaptureFunction => match self.analysis.callees.get(&dest) {
+                Some(Callee::Local(ident, _)) | Some(Callee::External(ident)) => {
+                    self.code.push(Instr::Move {
+                        src: Src::ExtFun(*ident),
+                        dst,
+                    });
+                }
+                None => return self.unsupported("capture of a dynamic function".to_owned()),
+            },
+            PrimOpKind::CaptureLocalFunction => match self.analysis.callees.get(&dest) {
+                Some(Callee::Local(_, entry)) => {
+                    let entry = *entry;
+                    self.make_fun(entry, Vec::new(), dst);
+                }
+                _ => return self.unsupported("capture of a dynamic function".to_owned()),
+            },
+            kind => return self.unsupported(format!("{:?}", kind)),
+        }
+
+        Ok(())
+    }
+
+    fn make_fun(&mut self, target: Label, env: Vec<Src>, dst: Reg) {
+        let num_free = env.len() as u32;
+        self.args(env);
+        let index = self.ctx.lambda_index(target);
+        self.code.push(Instr::MakeFun2 {
+            target,
+            index,
+            old_uniq: 0,
+            num_free,
+        });
+        self.moves(vec![(Src::Reg(Reg::X(0)), dst)]);
+    }
+
+    fn logic_op(
+        &mut self,
+        name: &str,
+        identity: bool,
+        args: &[Operand],
+        dst: Reg,
+    ) -> Result<(), CompileError> {
+        let name = Symbol::intern(name);
+        let args = self.srcs(args)?;
+
+        let (first, rest) = match args.split_first() {
+            Some(split) => split,
+            None => {
+                let src = self.boolean(identity);
+                self.code.push(Instr::Move { src, dst });
+                return Ok(());
+            }
+        };
+        if rest.is_empty() {
+            self.code.push(Instr::Move { src: *first, dst });
+            return Ok(());
+        }
+
+        // Intermediate results go through the scratch register, `dst` may
+        // hold one of the later arguments.
+        let mut acc = *first;
+        for (idx, arg) in rest.iter().enumerate() {
+            let out = if idx == rest.len() - 1 {
+                dst
+            } else {
+                self.scratch
+            };
+            self.code.push(Instr::Bif {
+                name,
+                args: vec![acc, *arg],
+                dst: out,
+            });
+            acc = Src::Reg(out);
+        }
+        Ok(())
+    }
+
+    /// Branches to `fail` if `src` is not of the given type.
+    fn type_test(&mut self, typ: BasicType, src: Reg, fail: Label) -> Result<(), CompileError> {
+        let test = match typ {
+            BasicType::List => Test::IsList,
+            BasicType::ListCell => Test::IsNonemptyList,
+            BasicType::Nil => Test::IsNil,
+            BasicType::Tuple(_) => Test::IsTuple,
+            BasicType::Map => Test::IsMap,
+            BasicType::Number => Test::IsNumber,
+            BasicType::Float => Test::IsFloat,
+            BasicType::Integer => Test::IsInteger,
+            typ => return self.unsupported(format!("type test {:?}", typ)),
+        };
+        self.code.push(Instr::Test {
+            test,
+            fail,
+            args: vec![Src::Reg(src)],
+        });
+        if let BasicType::Tuple(arity) = typ {
+            self.code.push(Instr::TestArity {
+                fail,
+                src,
+                arity: arity as u32,
+            });
+        }
+        Ok(())
+    }
+
+    fn terminator(&mut self, term: &Terminator) -> Result<(), CompileError> {
+        match term {
+            Terminator::Jump { target, args } => {
+                let values = self.srcs(args)?;
+                self.jump(*target, values);
+            }
+            Terminator::Return { values } => {
+                if values.len() != 1 {
+                    return self.unsupported(format!("return of {} values", values.len()));
+                }
+                let value = self.src(&values[0])?;
+                self.moves(vec![(value, Reg::X(0))]);
+                self.ret();
+            }
+            Terminator::Throw { values } => self.throw(values)?,
+            Terminator::Call {
+                callee,
+                args,
+                ret,
+                thr,
+            } => self.call(callee, args, *ret, *thr)?,
+            Terminator::TailCall { callee, args } => {
+                let arity = args.len() as u32;
+                match self.setup_call(callee, args)? {
+                    Target::Local(target) => self.code.push(match self.frame {
+                        Some(dealloc) => Instr::CallLast {
+                            arity,
+                            target,
+                            dealloc,
+                        },
+                        None => Instr::CallOnly { arity, target },
+                    }),
+                    Target::External(fun) => self.tail_call_ext(fun),
+                    Target::Fun => {
+                        self.code.push(Instr::CallFun(arity));
+                        self.ret();
+                    }
+                }
+            }
+            Terminator::Op { kind, reads } => self.op(kind, reads)?,
+            Terminator::Unreachable => self.unreachable(),
+        }
+        Ok(())
+    }
+
+    /// Moves the arguments of a call into place, the callee goes after the
+    /// arguments when calling a fun.
+    fn setup_call(&mut self, callee: &Operand, args: &[Operand]) -> Result<Target, CompileError> {
+        let mut srcs = self.srcs(args)?;
+        let target = match self.analysis.direct(callee).cloned() {
+            Some(Callee::Local(ident, entry)) if ident.arity == args.len() => Target::Local(entry),
+            Some(Callee::External(ident)) if ident.arity == args.len() => Target::External(ident),
+            Some(_) => {
+                return self.unsupported("call with the wrong number of arguments".to_owned())
+            }
+            None => {
+                srcs.push(self.src(callee)?);
+                Target::Fun
+            }
+        };
+        self.args(srcs);
+        Ok(target)
+    }
+
+    fn call(
+        &mut self,
+        callee: &Operand,
+        args: &[Operand],
+        ret: Cont,
+        thr: Cont,
+    ) -> Result<(), CompileError> {
+        if ret == Cont::Throw || thr == Cont::Return {
+            return self.unsupported(format!("call continuing to {:?}/{:?}", ret, thr));
+        }
+
+        let handler = match thr {
+            Cont::Block(block) => {
+                let handler = self.ctx.label();
+                self.code.push(Instr::Try {
+                    reg: self.try_tag,
+                    handler,
+                });
+                Some((block, handler))
+            }
+            _ => None,
+        };
+
+        let arity = args.len() as u32;
+        let instr = match self.setup_call(callee, args)? {
+            Target::Local(target) => Instr::Call { arity, target },
+            Target::External(fun) => Instr::CallExt(fun),
+            Target::Fun => Instr::CallFun(arity),
+        };
+        self.code.push(instr);
+
+        if let Some((block, handler)) = handler {
+            self.code.push(Instr::TryEnd(self.try_tag));
+
+            self.handlers.push(Instr::Label(handler));
+            self.handlers.push(Instr::TryCase(self.try_tag));
+            let moves: Vec<_> = (0..3)
+                .map(|n| Src::Reg(Reg::X(n)))
+                .zip(self.params(block))
+                .collect();
+            parallel_move(&moves, self.scratch, &mut self.handlers);
+            self.handlers.push(Instr::Jump(self.blocks[&block]));
+        }
+
+        match ret {
+            Cont::Block(block) => self.jump(block, vec![Src::Reg(Reg::X(0))]),
+            _ => self.ret(),
+        }
+        Ok(())
+    }
+
+    fn throw(&mut self, values: &[Operand]) -> Result<(), CompileError> {
+        let (class, reason, trace) = match values {
+            [class, reason, trace] => (class, reason, trace),
+            _ => return self.unsupported(format!("throw of {} values", values.len())),
+        };
+
+        let reason_src = self.src(reason)?;
+        if !self.analysis.is_fresh(trace) {
+            // Rethrowing an exception from a handler.
+            let class_src = self.src(class)?;
+            let trace_src = self.src(trace)?;
+            self.args(vec![class_src, reason_src, trace_src]);
+            self.code.push(Instr::RawRaise);
+            return Ok(());
+        }
+
+        let class_name = const_atom(&self.unit.cons, class).map(|name| name.as_str());
+        match class_name.as_deref() {
+            Some(name @ "error") | Some(name @ "exit") | Some(name @ "throw") => {
+                let fun = erlang_fun(name, 1);
+                self.args(vec![reason_src]);
+                self.tail_call_ext(fun);
+            }
+            _ => {
+                let class_src = self.src(class)?;
+                let nil = self.nil();
+                self.args(vec![class_src, reason_src, nil]);
+                self.tail_call_ext(erlang_fun("raise", 3));
+            }
+        }
+        Ok(())
+    }
+
+    fn unreachable(&mut self) {
+        let reason = self.atom("unreachable");
+        self.args(vec![reason]);
+        self.tail_call_ext(erlang_fun("error", 1));
+    }
+
+    fn op(&mut self, kind: &OpKind, reads: &[Operand]) -> Result<(), CompileError> {
+        match kind {
+            OpKind::IfBool => self.if_bool(reads)?,
+            OpKind::Match { branches } => self.match_op(branches, reads)?,
+            OpKind::UnpackValueList(_) => {
+                let target = self.block_operand(&reads[0])?;
+                let values = match &reads[1] {
+                    Operand::List(list) => self.srcs(list)?,
+                    value => vec![self.src(value)?],
+                };
+                self.jump(target, values);
+            }
+            OpKind::TraceCaptureRaw => {
+                let target = self.block_operand(&reads[0])?;
+                let nil = self.nil();
+                self.jump(target, vec![nil]);
+            }
+            OpKind::TraceConstruct => {
+                let target = self.block_operand(&reads[0])?;
+                if self.analysis.is_fresh(&reads[1]) {
+                    let nil = self.nil();
+                    self.jump(target, vec![nil]);
+                } else {
+                    let raw = self.src(&reads[1])?;
+                    self.args(vec![raw]);
+                    self.code.push(Instr::BuildStacktrace);
+                    self.jump(target, vec![Src::Reg(Reg::X(0))]);
+                }
+            }
+            kind => return self.unsupported(format!("{:?}", kind)),
+        }
+        Ok(())
+    }
+
+    fn if_bool(&mut self, reads: &[Operand]) -> Result<(), CompileError> {
+        let (t, f, e, value) = match reads {
+            [t, f, value] => (t, f, None, value),
+            [t, f, e, value] => (t, f, Some(e), value),
+            _ => return self.unsupported(format!("if_bool with {} reads", reads.len())),
+        };
+        let t = self.block_operand(t)?;
+        let f = self.block_operand(f)?;
+        let e = match e {
+            Some(e) => Some(self.block_operand(e)?),
+            None => None,
+        };
+
+        let value = Src::Reg(self.reg(value)?);
+        let not_true = self.ctx.label();
+        let true_val = self.boolean(true);
+        self.code.push(Instr::Test {
+            test: Test::IsEqExact,
+            fail: not_true,
+            args: vec![value, true_val],
+        });
+        self.jump(t, Vec::new());
+        self.code.push(Instr::Label(not_true));
+
+        match e {
+            // Anything that is not `true` is `false`.
+            None => self.jump(f, Vec::new()),
+            Some(e) => {
+                let not_false = self.ctx.label();
+                let false_val = self.boolean(false);
+                self.code.push(Instr::Test {
+                    test: Test::IsEqExact,
+                    fail: not_false,
+                    args: vec![value, false_val],
+                });
+                self.jump(f, Vec::new());
+                self.code.push(Instr::Label(not_false));
+                self.jump(e, Vec::new());
+            }
+        }
+        Ok(())
+    }
+
+    fn match_op(&mut self, branches: &[MatchKind], reads: &[Operand]) -> Result<(), CompileError> {
+        let targets = match &reads[0] {
+            Operand::List(list) => list.clone(),
+            target => vec![target.clone()],
+        };
+
+        // Elements are extracted from x registers.
+        let mut subject = self.reg(&reads[1])?;
+        if !subject.is_x() {
+            self.code.push(Instr::Move {
+                src: Src::Reg(subject),
+                dst: self.scratch,
+            });
+            subject = self.scratch;
+        }
+
+        for (idx, (kind, target)) in branches.iter().zip(targets.iter()).enumerate() {
+            let target = self.block_operand(target)?;
+            let args = match reads.get(2 + idx) {
+                Some(Operand::List(list)) => list.clone(),
+                Some(arg) => vec![arg.clone()],
+                None => Vec::new(),
+            };
+            let fail = self.ctx.label();
+
+            match kind {
+                MatchKind::Value => {
+                    let value = self.src(&args[0])?;
+                    self.code.push(Instr::Test {
+                        test: Test::IsEqExact,
+                        fail,
+                        args: vec![Src::Reg(subject), value],
+                    });
+                }
+                MatchKind::Type(typ) => self.type_test(*typ, subject, fail)?,
+                MatchKind::Tuple(arity) => {
+                    self.type_test(BasicType::Tuple(*arity), subject, fail)?;
+
+                    // An element that overwrites the tuple is extracted last.
+                    let params = self.params(target);
+                    let mut order: Vec<usize> = (0..*arity).collect();
+                    order.sort_by_key(|idx| params[*idx] == subject);
+                    for idx in order {
+                        self.code.push(Instr::GetTupleElement {
+                            src: subject,
+                            index: idx as u32,
+                            dst: params[idx],
+                        });
+                    }
+                }
+                MatchKind::ListCell => {
+                    self.type_test(BasicType::ListCell, subject, fail)?;
+                    let params = self.params(target);
+                    self.code.push(Instr::GetList {
+                        src: subject,
+                        head: params[0],
+                        tail: params[1],
+                    });
+                }
+                MatchKind::MapItem => {
+                    self.type_test(BasicType::Map, subject, fail)?;
+                    let key = self.src(&args[0])?;
+                    let params = self.params(target);
+                    self.code.push(Instr::GetMapElements {
+                        fail,
+                        src: subject,
+                        pairs: vec![(key, params[0])],
+                    });
+                }
+                MatchKind::Wildcard => {
+                    // Any later branches are never reached.
+                    self.code.push(Instr::Jump(self.blocks[&target]));
+                    return Ok(());
+                }
+                MatchKind::Binary(_) => return self.unsupported("binary matching".to_owned()),
+            }
+
+            self.code.push(Instr::Jump(self.blocks[&target]));
+            self.code.push(Instr::Label(fail));
+        }
+
+        self.unreachable();
+        Ok(())
+    }
+}
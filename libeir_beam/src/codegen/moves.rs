@@ -0,0 +1,51 @@
+use crate::asm::{Instr, Reg, Src};
+
+/// Sequences a set of moves that happen in parallel, every destination
+/// receives the value its source had before any of the moves.
+///
+/// Destinations must be distinct. Cycles are broken by going through
+/// `scratch`, which must not be a source or destination of any move.
+pub fn parallel_move(moves: &[(Src, Reg)], scratch: Reg, out: &mut Vec<Instr>) {
+    let mut pending: Vec<(Src, Reg)> = moves
+        .iter()
+        .filter(|(src, dst)| *src != Src::Reg(*dst))
+        .cloned()
+        .collect();
+
+    debug_assert!({
+        let mut dsts: Vec<_> = pending.iter().map(|(_, dst)| *dst).collect();
+        dsts.sort();
+        dsts.windows(2).all(|w| w[0] != w[1])
+    });
+
+    while !pending.is_empty() {
+        // A move can be performed when no other pending move still needs to
+        // read its destination.
+        let ready = pending.iter().position(|(_, dst)| {
+            !pending
+                .iter()
+                .any(|(src, other)| other != dst && *src == Src::Reg(*dst))
+        });
+
+        match ready {
+            Some(idx) => {
+                let (src, dst) = pending.remove(idx);
+                out.push(Instr::Move { src, dst });
+            }
+            None => {
+                // Every remaining move is part of a cycle. Save one of the
+                // destinations, which frees it up.
+                let dst = pending[0].1;
+                out.push(Instr::Move {
+                    src: Src::Reg(dst),
+                    dst: scratch,
+                });
+                for (src, _) in pending.iter_mut() {
+                    if *src == Src::Reg(dst) {
+                        *src = Src::Reg(scratch);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! Register allocation.
+//!
+//! Every variable gets a single register for its whole lifetime. Variables
+//! that are live across an instruction that clobbers the x registers, like a
+//! call, are placed in y registers. Everything else goes in x registers.
+//! Registers are assigned by greedily coloring the interference graph, the
+//! functions are small enough that this does well.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use cranelift_entity::SecondaryMap;
+
+use libeir_ir::{MatchKind, OpKind};
+use libeir_lir::{Block, Function, Operand, Terminator, Var};
+
+use super::{operand_vars, Analysis};
+use crate::asm::Reg;
+
+pub struct Allocation {
+    regs: BTreeMap<Var, Reg>,
+    /// The number of y registers used by variables.
+    pub y_count: u32,
+    /// One more than the highest x register used by a variable.
+    pub x_count: u32,
+    /// The variables live after each instruction of a block.
+    live_after: SecondaryMap<Block, Vec<BTreeSet<Var>>>,
+}
+
+impl Allocation {
+    pub fn reg(&self, var: Var) -> Reg {
+        self.regs[&var]
+    }
+
+    pub fn live_after(&self, block: Block, inst: usize) -> &BTreeSet<Var> {
+        &self.live_after[block][inst]
+    }
+}
+
+/// The variables read by a terminator. Callees that are called directly are
+/// not read.
+fn terminator_uses(term: &Terminator, analysis: &Analysis) -> BTreeSet<Var> {
+    let mut uses = BTreeSet::new();
+    match term {
+        Terminator::Call { callee, args, .. } | Terminator::TailCall { callee, args } => {
+            if analysis.direct(callee).is_none() {
+                operand_vars(callee, &mut uses);
+            }
+            args.iter().for_each(|o| operand_vars(o, &mut uses));
+        }
+        term => term.walk_operands(|o| operand_vars(o, &mut uses)),
+    }
+    uses
+}
+
+struct Graph {
+    edges: BTreeMap<Var, BTreeSet<Var>>,
+}
+
+impl Graph {
+    fn add(&mut self, var: Var) {
+        self.edges.entry(var).or_insert_with(BTreeSet::new);
+    }
+
+    fn interfere(&mut self, a: Var, b: Var) {
+        if a != b {
+            self.edges.entry(a).or_insert_with(BTreeSet::new).insert(b);
+            self.edges.entry(b).or_insert_with(BTreeSet::new).insert(a);
+        }
+    }
+
+    /// Makes every variable in `defs` interfere with each other and with
+    /// everything in `live`.
+    fn define_all(&mut self, defs: &[Var], live: &BTreeSet<Var>) {
+        for def in defs.iter() {
+            self.add(*def);
+            for other in defs.iter().chain(live.iter()) {
+                self.interfere(*def, *other);
+            }
+        }
+    }
+}
+
+pub fn allocate(function: &Function, analysis: &Analysis) -> Allocation {
+    // Liveness, solved with a backwards dataflow over the blocks.
+    let mut live_in: SecondaryMap<Block, BTreeSet<Var>> = SecondaryMap::new();
+    let mut live_out: SecondaryMap<Block, BTreeSet<Var>> = SecondaryMap::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (block, data) in function.blocks.iter().rev() {
+            let mut out = BTreeSet::new();
+            for succ in data.term.successors() {
+                out.extend(live_in[succ].iter().cloned());
+            }
+
+            let mut live = out.clone();
+            live.extend(terminator_uses(&data.term, analysis));
+            for inst in data.insts.iter().rev() {
+                let dest = inst.dest();
+                if analysis.skipped.contains(&dest) {
+                    continue;
+                }
+                live.remove(&dest);
+                inst.operands()
+                    .iter()
+                    .for_each(|o| operand_vars(o, &mut live));
+            }
+            for param in data.params.iter() {
+                live.remove(param);
+            }
+
+            live_out[block] = out;
+            if live != live_in[block] {
+                live_in[block] = live;
+                changed = true;
+            }
+        }
+    }
+
+    let mut graph = Graph {
+        edges: BTreeMap::new(),
+    };
+    let mut needs_y = BTreeSet::new();
+    let mut live_after: SecondaryMap<Block, Vec<BTreeSet<Var>>> = SecondaryMap::new();
+
+    for (block, data) in function.blocks.iter() {
+        let out = &live_out[block];
+
+        match &data.term {
+            // Calls clobber every x register.
+            Terminator::Call { .. } => needs_y.extend(out.iter().cloned()),
+            // So does building a stack trace.
+            Terminator::Op {
+                kind: OpKind::TraceConstruct,
+                reads,
+            } if !analysis.is_fresh(&reads[1]) => needs_y.extend(out.iter().cloned()),
+            // Values are read from a map before it is known whether the
+            // branch is taken, they can not overwrite anything needed by
+            // the other branches.
+            Terminator::Op {
+                kind: OpKind::Match { branches },
+                reads,
+            } => {
+                let mut live = out.clone();
+                live.extend(terminator_uses(&data.term, analysis));
+                if let Operand::List(targets) = &reads[0] {
+                    for (kind, target) in branches.iter().zip(targets.iter()) {
+                        if let (MatchKind::MapItem, Operand::Block(target)) = (kind, target) {
+                            graph.define_all(&function.blocks[*target].params, &live);
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        let mut live = out.clone();
+        live.extend(terminator_uses(&data.term, analysis));
+
+        let mut after = vec![BTreeSet::new(); data.insts.len()];
+        for (idx, inst) in data.insts.iter().enumerate().rev() {
+            after[idx] = live.clone();
+
+            let dest = inst.dest();
+            if analysis.skipped.contains(&dest) {
+                continue;
+            }
+            graph.define_all(&[dest], &live);
+            if analysis.clobbers(inst) {
+                needs_y.extend(live.iter().filter(|v| **v != dest).cloned());
+            }
+
+            live.remove(&dest);
+            inst.operands()
+                .iter()
+                .for_each(|o| operand_vars(o, &mut live));
+        }
+        live_after[block] = after;
+
+        if block == function.entry {
+            let mut defs = function.env.clone();
+            defs.extend(data.params.iter().cloned());
+            graph.define_all(&defs, &live);
+        } else {
+            graph.define_all(&data.params, &live);
+        }
+    }
+
+    // Arguments arrive in x registers, they stay where they are unless they
+    // need to survive a call.
+    let mut regs = BTreeMap::new();
+    let entry_vars = function.blocks[function.entry]
+        .params
+        .iter()
+        .chain(function.env.iter());
+    for (idx, var) in entry_vars.enumerate() {
+        if !needs_y.contains(var) {
+            regs.insert(*var, Reg::X(idx as u32));
+        }
+    }
+
+    for (var, neighbors) in graph.edges.iter() {
+        if regs.contains_key(var) {
+            continue;
+        }
+        let y = needs_y.contains(var);
+        let taken: BTreeSet<u32> = neighbors
+            .iter()
+            .filter_map(|n| regs.get(n))
+            .filter_map(|reg| match (reg, y) {
+                (Reg::X(n), false) | (Reg::Y(n), true) => Some(*n),
+                _ => None,
+            })
+            .collect();
+        let color = (0..).find(|n| !taken.contains(n)).unwrap();
+        let reg = if y { Reg::Y(color) } else { Reg::X(color) };
+        regs.insert(*var, reg);
+    }
+
+    let mut x_count = 0;
+    let mut y_count = 0;
+    for reg in regs.values() {
+        match reg {
+            Reg::X(n) => x_count = x_count.max(n + 1),
+            Reg::Y(n) => y_count = y_count.max(n + 1),
+        }
+    }
+
+    Allocation {
+        regs,
+        y_count,
+        x_count,
+        live_after,
+    }
+}
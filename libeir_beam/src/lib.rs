@@ -0,0 +1,35 @@
+//! # BEAM assembly backend
+//! Generates BEAM assembly from Eir, in the textual format produced by
+//! `erlc -S`. The output can be assembled with `erlc +from_asm`, which makes
+//! it possible to compare our passes against those of `erlc` on the same
+//! runtime. It can also be assembled directly into a loadable `.beam` file
+//! with `Module::to_beam`.
+//!
+//! Functions are lowered through LIR, which makes stack frames and closure
+//! environments explicit. From there:
+//!
+//! * Variables get x registers, or y registers if they need to survive a
+//!   call.
+//! * Calls to constant functions become `call`, or `call_ext` for other
+//!   modules. Tail calls become `call_only`/`call_last`.
+//! * `Match` branches become `test` instructions, falling through to the
+//!   next branch on failure.
+//! * Calls with a throw continuation in the function are wrapped in a
+//!   `try` block.
+//!
+//! The instruction set targeted is that of OTP 22 and 23.
+
+mod asm;
+pub use asm::{Function, Instr, Label, Module, Reg, Src, Test};
+
+mod term;
+
+mod printer;
+
+mod beam;
+
+mod codegen;
+pub use codegen::{compile_module, CompileError};
+
+#[cfg(test)]
+mod tests;
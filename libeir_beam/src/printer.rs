@@ -0,0 +1,343 @@
+//! Textual BEAM assembly, in the format of `erlc -S`.
+//!
+//! ```text
+//! {module, foo}.  %% version = 0
+//!
+//! {exports, [{bar,1}]}.
+//!
+//! {attributes, []}.
+//!
+//! {labels, 3}.
+//!
+//!
+//! {function, bar, 1, 2}.
+//!   {label,1}.
+//!     {func_info,{atom,foo},{atom,bar},1}.
+//!   {label,2}.
+//!     return.
+//! ```
+
+use std::fmt::{Display, Formatter, Result, Write};
+
+use libeir_intern::Symbol;
+use libeir_ir::{AtomicTerm, ConstKind, FunctionIdent};
+
+use crate::asm::{Function, Instr, Label, Module, Reg, Src};
+use crate::term::{write_atom, write_const, write_float};
+
+impl Module {
+    pub fn to_text(&self) -> String {
+        let mut printer = Printer {
+            module: self,
+            out: String::new(),
+        };
+        printer.module();
+        printer.out
+    }
+}
+
+impl Display for Module {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.write_str(&self.to_text())
+    }
+}
+
+struct Printer<'a> {
+    module: &'a Module,
+    out: String,
+}
+
+impl<'a> Printer<'a> {
+    fn module(&mut self) {
+        let module = self.module;
+
+        self.out.push_str("{module, ");
+        self.atom(module.name);
+        self.out.push_str("}.  %% version = 0\n\n");
+
+        let mut exports = module.exports.clone();
+        exports.sort();
+        self.out.push_str("{exports, [");
+        for (idx, (name, arity)) in exports.iter().enumerate() {
+            if idx != 0 {
+                self.out.push(',');
+            }
+            self.out.push('{');
+            self.atom(*name);
+            write!(self.out, ",{}}}", arity).unwrap();
+        }
+        self.out.push_str("]}.\n\n");
+
+        self.out.push_str("{attributes, []}.\n\n");
+        write!(self.out, "{{labels, {}}}.\n\n", module.labels).unwrap();
+
+        for function in module.functions.iter() {
+            self.out.push('\n');
+            self.function(function);
+        }
+    }
+
+    fn function(&mut self, function: &Function) {
+        self.out.push_str("{function, ");
+        self.atom(function.name);
+        writeln!(self.out, ", {}, {}}}.", function.arity, function.entry.0).unwrap();
+
+        for instr in function.code.iter() {
+            match instr {
+                Instr::Label(_) => self.out.push_str("  "),
+                _ => self.out.push_str("    "),
+            }
+            self.instr(instr);
+            self.out.push_str(".\n");
+        }
+    }
+
+    fn instr(&mut self, instr: &Instr) {
+        match instr {
+            Instr::Label(label) => write!(self.out, "{{label,{}}}", label.0).unwrap(),
+            Instr::FuncInfo {
+                module,
+                name,
+                arity,
+            } => {
+                self.out.push_str("{func_info,{atom,");
+                self.atom(*module);
+                self.out.push_str("},{atom,");
+                self.atom(*name);
+                write!(self.out, "}},{}}}", arity).unwrap();
+            }
+            Instr::Allocate { stack, live } => {
+                write!(self.out, "{{allocate,{},{}}}", stack, live).unwrap()
+            }
+            Instr::AllocateZero { stack, live } => {
+                write!(self.out, "{{allocate_zero,{},{}}}", stack, live).unwrap()
+            }
+            Instr::Deallocate(n) => write!(self.out, "{{deallocate,{}}}", n).unwrap(),
+            Instr::Return => self.out.push_str("return"),
+            Instr::Move { src, dst } => {
+                self.out.push_str("{move,");
+                self.src(src);
+                self.out.push(',');
+                self.reg(*dst);
+                self.out.push('}');
+            }
+            Instr::Jump(label) => {
+                self.out.push_str("{jump,");
+                self.label(*label);
+                self.out.push('}');
+            }
+            Instr::Call { arity, target } => {
+                write!(self.out, "{{call,{},", arity).unwrap();
+                self.label(*target);
+                self.out.push('}');
+            }
+            Instr::CallLast {
+                arity,
+                target,
+                dealloc,
+            } => {
+                write!(self.out, "{{call_last,{},", arity).unwrap();
+                self.label(*target);
+                write!(self.out, ",{}}}", dealloc).unwrap();
+            }
+            Instr::CallOnly { arity, target } => {
+                write!(self.out, "{{call_only,{},", arity).unwrap();
+                self.label(*target);
+                self.out.push('}');
+            }
+            Instr::CallExt(fun) => {
+                write!(self.out, "{{call_ext,{},", fun.arity).unwrap();
+                self.extfunc(fun);
+                self.out.push('}');
+            }
+            Instr::CallExtLast { fun, dealloc } => {
+                write!(self.out, "{{call_ext_last,{},", fun.arity).unwrap();
+                self.extfunc(fun);
+                write!(self.out, ",{}}}", dealloc).unwrap();
+            }
+            Instr::CallExtOnly(fun) => {
+                write!(self.out, "{{call_ext_only,{},", fun.arity).unwrap();
+                self.extfunc(fun);
+                self.out.push('}');
+            }
+            Instr::CallFun(arity) => write!(self.out, "{{call_fun,{}}}", arity).unwrap(),
+            Instr::Bif { name, args, dst } => {
+                self.out.push_str("{bif,");
+                self.atom(*name);
+                self.out.push(',');
+                self.label(Label::NONE);
+                self.out.push(',');
+                self.src_list(args);
+                self.out.push(',');
+                self.reg(*dst);
+                self.out.push('}');
+            }
+            Instr::Test { test, fail, args } => {
+                write!(self.out, "{{test,{},", test.name()).unwrap();
+                self.label(*fail);
+                self.out.push(',');
+                self.src_list(args);
+                self.out.push('}');
+            }
+            Instr::TestArity { fail, src, arity } => {
+                self.out.push_str("{test,test_arity,");
+                self.label(*fail);
+                self.out.push_str(",[");
+                self.reg(*src);
+                write!(self.out, ",{}]}}", arity).unwrap();
+            }
+            Instr::GetTupleElement { src, index, dst } => {
+                self.out.push_str("{get_tuple_element,");
+                self.reg(*src);
+                write!(self.out, ",{},", index).unwrap();
+                self.reg(*dst);
+                self.out.push('}');
+            }
+            Instr::GetList { src, head, tail } => {
+                self.out.push_str("{get_list,");
+                self.reg(*src);
+                self.out.push(',');
+                self.reg(*head);
+                self.out.push(',');
+                self.reg(*tail);
+                self.out.push('}');
+            }
+            Instr::GetMapElements { fail, src, pairs } => {
+                self.out.push_str("{get_map_elements,");
+                self.label(*fail);
+                self.out.push(',');
+                self.reg(*src);
+                self.out.push_str(",{list,[");
+                for (idx, (key, dst)) in pairs.iter().enumerate() {
+                    if idx != 0 {
+                        self.out.push(',');
+                    }
+                    self.src(key);
+                    self.out.push(',');
+                    self.reg(*dst);
+                }
+                self.out.push_str("]}}");
+            }
+            Instr::TestHeap { need, live } => {
+                write!(self.out, "{{test_heap,{},{}}}", need, live).unwrap()
+            }
+            Instr::PutTuple2 { dst, elements } => {
+                self.out.push_str("{put_tuple2,");
+                self.reg(*dst);
+                self.out.push_str(",{list,");
+                self.src_list(elements);
+                self.out.push_str("}}");
+            }
+            Instr::PutList { head, tail, dst } => {
+                self.out.push_str("{put_list,");
+                self.src(head);
+                self.out.push(',');
+                self.src(tail);
+                self.out.push(',');
+                self.reg(*dst);
+                self.out.push('}');
+            }
+            Instr::MakeFun2 {
+                target,
+                index,
+                old_uniq,
+                num_free,
+            } => {
+                self.out.push_str("{make_fun2,");
+                self.label(*target);
+                write!(self.out, ",{},{},{}}}", index, old_uniq, num_free).unwrap();
+            }
+            Instr::Try { reg, handler } => {
+                self.out.push_str("{'try',");
+                self.reg(*reg);
+                self.out.push(',');
+                self.label(*handler);
+                self.out.push('}');
+            }
+            Instr::TryEnd(reg) => {
+                self.out.push_str("{try_end,");
+                self.reg(*reg);
+                self.out.push('}');
+            }
+            Instr::TryCase(reg) => {
+                self.out.push_str("{try_case,");
+                self.reg(*reg);
+                self.out.push('}');
+            }
+            Instr::RawRaise => self.out.push_str("raw_raise"),
+            Instr::BuildStacktrace => self.out.push_str("build_stacktrace"),
+        }
+    }
+
+    fn atom(&mut self, atom: Symbol) {
+        write_atom(&mut self.out, &atom.as_str());
+    }
+
+    fn label(&mut self, label: Label) {
+        write!(self.out, "{{f,{}}}", label.0).unwrap();
+    }
+
+    fn reg(&mut self, reg: Reg) {
+        match reg {
+            Reg::X(n) => write!(self.out, "{{x,{}}}", n).unwrap(),
+            Reg::Y(n) => write!(self.out, "{{y,{}}}", n).unwrap(),
+        }
+    }
+
+    fn extfunc(&mut self, fun: &FunctionIdent) {
+        self.out.push_str("{extfunc,");
+        self.atom(fun.module.name);
+        self.out.push(',');
+        self.atom(fun.name.name);
+        write!(self.out, ",{}}}", fun.arity).unwrap();
+    }
+
+    fn src_list(&mut self, srcs: &[Src]) {
+        self.out.push('[');
+        for (idx, src) in srcs.iter().enumerate() {
+            if idx != 0 {
+                self.out.push(',');
+            }
+            self.src(src);
+        }
+        self.out.push(']');
+    }
+
+    fn src(&mut self, src: &Src) {
+        let cons = &self.module.cons;
+        match src {
+            Src::Reg(reg) => self.reg(*reg),
+            Src::Const(value) => match cons.const_kind(*value) {
+                ConstKind::Atomic(AtomicTerm::Atom(atom)) => {
+                    self.out.push_str("{atom,");
+                    self.atom(atom.0);
+                    self.out.push('}');
+                }
+                ConstKind::Atomic(AtomicTerm::Int(int)) => {
+                    write!(self.out, "{{integer,{}}}", int.value()).unwrap()
+                }
+                ConstKind::Atomic(AtomicTerm::BigInt(int)) => {
+                    write!(self.out, "{{integer,{}}}", int.value()).unwrap()
+                }
+                ConstKind::Atomic(AtomicTerm::Float(float)) => {
+                    self.out.push_str("{float,");
+                    write_float(&mut self.out, float.value());
+                    self.out.push('}');
+                }
+                ConstKind::Atomic(AtomicTerm::Nil) => self.out.push_str("nil"),
+                _ => {
+                    self.out.push_str("{literal,");
+                    write_const(&mut self.out, cons, *value);
+                    self.out.push('}');
+                }
+            },
+            Src::ExtFun(fun) => {
+                self.out.push_str("{literal,fun ");
+                self.atom(fun.module.name);
+                self.out.push(':');
+                self.atom(fun.name.name);
+                write!(self.out, "/{}}}", fun.arity).unwrap();
+            }
+        }
+    }
+}
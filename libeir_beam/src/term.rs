@@ -0,0 +1,146 @@
+//! Erlang term syntax, as it is written in assembly files.
+
+use std::fmt::Write;
+
+use libeir_ir::{AtomicTerm, Const, ConstKind, ConstantContainer};
+use libeir_util_binary::BitCarrier;
+
+const RESERVED: &[&str] = &[
+    "after", "and", "andalso", "band", "begin", "bnot", "bor", "bsl", "bsr", "bxor", "case",
+    "catch", "cond", "div", "else", "end", "fun", "if", "let", "maybe", "not", "of", "or",
+    "orelse", "receive", "rem", "try", "when", "xor",
+];
+
+/// Writes an atom, quoting it if it is not a valid bare atom.
+pub fn write_atom(out: &mut String, atom: &str) {
+    let mut chars = atom.chars();
+    let bare = match chars.next() {
+        Some(first) => {
+            first.is_ascii_lowercase()
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '@')
+                && !RESERVED.contains(&atom)
+        }
+        None => false,
+    };
+
+    if bare {
+        out.push_str(atom);
+        return;
+    }
+
+    out.push('\'');
+    for c in atom.chars() {
+        match c {
+            '\'' => out.push_str("\\'"),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 || c as u32 == 0x7f => {
+                write!(out, "\\{:03o}", c as u32).unwrap();
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('\'');
+}
+
+/// Writes a float so that it reads back as a float in Erlang, which requires
+/// a fractional part before any exponent.
+pub fn write_float(out: &mut String, float: f64) {
+    let text = format!("{:?}", float);
+    match text.find('e') {
+        Some(idx) if !text[..idx].contains('.') => {
+            out.push_str(&text[..idx]);
+            out.push_str(".0");
+            out.push_str(&text[idx..]);
+        }
+        _ => out.push_str(&text),
+    }
+}
+
+/// Writes a constant in Erlang term syntax.
+pub fn write_const(out: &mut String, cons: &ConstantContainer, value: Const) {
+    match cons.const_kind(value) {
+        ConstKind::Atomic(atomic) => write_atomic(out, atomic),
+        ConstKind::ListCell { .. } => {
+            out.push('[');
+            let mut current = value;
+            let mut first = true;
+            loop {
+                match cons.const_kind(current) {
+                    ConstKind::ListCell { head, tail } => {
+                        if !first {
+                            out.push(',');
+                        }
+                        first = false;
+                        write_const(out, cons, *head);
+                        current = *tail;
+                    }
+                    ConstKind::Atomic(AtomicTerm::Nil) => break,
+                    _ => {
+                        out.push('|');
+                        write_const(out, cons, current);
+                        break;
+                    }
+                }
+            }
+            out.push(']');
+        }
+        ConstKind::Tuple { entries } => {
+            out.push('{');
+            for (idx, entry) in entries.as_slice(&cons.const_pool).iter().enumerate() {
+                if idx != 0 {
+                    out.push(',');
+                }
+                write_const(out, cons, *entry);
+            }
+            out.push('}');
+        }
+        ConstKind::Map { keys, values } => {
+            out.push_str("#{");
+            let keys = keys.as_slice(&cons.const_pool);
+            let values = values.as_slice(&cons.const_pool);
+            for (idx, (key, value)) in keys.iter().zip(values.iter()).enumerate() {
+                if idx != 0 {
+                    out.push(',');
+                }
+                write_const(out, cons, *key);
+                out.push_str(" => ");
+                write_const(out, cons, *value);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_atomic(out: &mut String, atomic: &AtomicTerm) {
+    match atomic {
+        AtomicTerm::Int(int) => write!(out, "{}", int.value()).unwrap(),
+        AtomicTerm::BigInt(int) => write!(out, "{}", int.value()).unwrap(),
+        AtomicTerm::Float(float) => write_float(out, float.value()),
+        AtomicTerm::Atom(atom) => write_atom(out, &atom.0.as_str()),
+        AtomicTerm::Nil => out.push_str("[]"),
+        AtomicTerm::Binary(bin) => {
+            let bin = bin.value();
+            let bits = bin.bit_len();
+            let bytes = bin.as_ref();
+
+            out.push_str("<<");
+            for idx in 0..(bits / 8) {
+                if idx != 0 {
+                    out.push(',');
+                }
+                write!(out, "{}", bytes[idx]).unwrap();
+            }
+            let rem = bits % 8;
+            if rem != 0 {
+                if bits >= 8 {
+                    out.push(',');
+                }
+                let last = bytes[bits / 8] >> (8 - rem);
+                write!(out, "{}:{}", last, rem).unwrap();
+            }
+            out.push_str(">>");
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use libeir_ir::parse_module_unwrap;
+
+use crate::{compile_module, Module};
+
+fn compile(text: &str) -> String {
+    let module = parse_module_unwrap(text);
+    let asm: Module = compile_module(&module).unwrap();
+    asm.to_text()
+}
+
+fn assert_contains(text: &str, expected: &str) {
+    let expected = expected.trim_start_matches('\n');
+    assert!(
+        text.contains(expected),
+        "expected:\n{}\nin:\n{}",
+        expected,
+        text
+    );
+}
+
+#[test]
+fn simple_return() {
+    let text = compile(
+        "
+a'foo' {
+    a'id'/1 {
+        entry(%ret, %thr, %a):
+            %ret(%a);
+    }
+}
+",
+    );
+
+    assert_eq!(
+        text,
+        "\
+{module, foo}.  %% version = 0
+
+{exports, [{id,1}]}.
+
+{attributes, []}.
+
+{labels, 3}.
+
+
+{function, id, 1, 2}.
+  {label,1}.
+    {func_info,{atom,foo},{atom,id},1}.
+  {label,2}.
+    return.
+"
+    );
+}
+
+#[test]
+fn tail_calls() {
+    let text = compile(
+        "
+a'foo' {
+    a'id'/1 {
+        entry(%ret, %thr, %a):
+            %ret(%a);
+    }
+    a'local'/1 {
+        entry(%ret, %thr, %a):
+            a'foo':a'id'/1(%a) => %ret except %thr;
+    }
+    a'remote'/1 {
+        entry(%ret, %thr, %a):
+            a'lists':a'reverse'/1(%a) => %ret except %thr;
+    }
+}
+",
+    );
+
+    assert_contains(
+        &text,
+        "
+  {label,4}.
+    {call_only,1,{f,2}}.
+",
+    );
+    assert_contains(
+        &text,
+        "
+  {label,6}.
+    {call_ext_only,1,{extfunc,lists,reverse,1}}.
+",
+    );
+}
+
+#[test]
+fn swapped_arguments() {
+    let text = compile(
+        "
+a'foo' {
+    a'swap'/2 {
+        entry(%ret, %thr, %a, %b):
+            a'foo':a'swap'/2(%b, %a) => %ret except %thr;
+    }
+}
+",
+    );
+
+    assert_contains(
+        &text,
+        "
+  {label,2}.
+    {move,{x,0},{x,3}}.
+    {move,{x,1},{x,0}}.
+    {move,{x,3},{x,1}}.
+    {call_only,2,{f,2}}.
+",
+    );
+}
+
+#[test]
+fn value_live_across_call() {
+    let text = compile(
+        "
+a'foo' {
+    a'pair'/1 {
+        entry(%ret, %thr, %a):
+            a'lists':a'reverse'/1(%a) => next except %thr;
+        next(%r):
+            %ret({%a, %r});
+    }
+}
+",
+    );
+
+    assert_contains(
+        &text,
+        "
+  {label,2}.
+    {allocate_zero,1,1}.
+    {move,{x,0},{y,0}}.
+    {move,{y,0},{x,0}}.
+    {call_ext,1,{extfunc,lists,reverse,1}}.
+    {jump,{f,3}}.
+  {label,3}.
+    {test_heap,3,1}.
+    {put_tuple2,{x,0},{list,[{y,0},{x,0}]}}.
+    {deallocate,1}.
+    return.
+",
+    );
+}
+
+#[test]
+fn match_tuple() {
+    let text = compile(
+        "
+a'foo' {
+    a'first'/1 {
+        entry(%ret, %thr, %a):
+            match %a {
+                {} arity 2 => tup;
+                _ => other;
+            };
+        tup(%x, %y):
+            %ret(%x);
+        other():
+            %ret(a'none');
+    }
+}
+",
+    );
+
+    assert_contains(
+        &text,
+        "
+  {label,2}.
+    {test,is_tuple,{f,3},[{x,0}]}.
+    {test,test_arity,{f,3},[{x,0},2]}.
+    {get_tuple_element,{x,0},1,{x,1}}.
+    {get_tuple_element,{x,0},0,{x,0}}.
+    {jump,{f,4}}.
+  {label,3}.
+    {jump,{f,5}}.
+  {label,4}.
+    return.
+  {label,5}.
+    {move,{atom,none},{x,0}}.
+    return.
+",
+    );
+}
+
+#[test]
+fn closure() {
+    let text = compile(
+        "
+a'foo' {
+    a'adder'/1 {
+        entry(%ret, %thr, %a):
+            %ret(inner);
+        inner(%iret, %ithr, %b):
+            %iret(%a);
+    }
+}
+",
+    );
+
+    assert_contains(
+        &text,
+        "
+{function, adder, 1, 2}.
+  {label,1}.
+    {func_info,{atom,foo},{atom,adder},1}.
+  {label,2}.
+    {make_fun2,{f,4},0,0,1}.
+    return.
+
+{function, '-adder/1-fun-0-', 2, 4}.
+  {label,3}.
+    {func_info,{atom,foo},{atom,'-adder/1-fun-0-'},2}.
+  {label,4}.
+    {move,{x,1},{x,0}}.
+    return.
+",
+    );
+}
+
+#[test]
+fn try_catch() {
+    let text = compile(
+        "
+a'foo' {
+    a'safe'/1 {
+        entry(%ret, %thr, %a):
+            a'erlang':a'hd'/1(%a) => %ret except handler;
+        handler(%class, %reason, %trace):
+            %ret(%reason);
+    }
+}
+",
+    );
+
+    assert_contains(
+        &text,
+        "
+  {label,2}.
+    {allocate_zero,1,1}.
+    {'try',{y,0},{f,4}}.
+    {call_ext,1,{extfunc,erlang,hd,1}}.
+    {try_end,{y,0}}.
+    {deallocate,1}.
+    return.
+  {label,3}.
+    {move,{x,1},{x,0}}.
+    {deallocate,1}.
+    return.
+  {label,4}.
+    {try_case,{y,0}}.
+    {jump,{f,3}}.
+",
+    );
+}
+
+#[test]
+fn throw_fresh_trace() {
+    let text = compile(
+        "
+a'foo' {
+    a'fail'/1 {
+        entry(%ret, %thr, %a):
+            trace_capture_raw trace;
+        trace(%t):
+            %thr(a'error', %a, %t);
+    }
+}
+",
+    );
+
+    assert_contains(
+        &text,
+        "
+  {label,2}.
+    {move,nil,{x,1}}.
+    {jump,{f,3}}.
+  {label,3}.
+    {call_ext_only,1,{extfunc,erlang,error,1}}.
+",
+    );
+}
@@ -0,0 +1,204 @@
+//! Coverage collection for interpreted code.
+//!
+//! When enabled through `VMState::enable_coverage`, the executor counts every
+//! executed Erlang block and every call to a function. Block counts are
+//! mapped back to source lines through the location information of the
+//! function.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+use libeir_diagnostics::CodeMap;
+use libeir_intern::Symbol;
+use libeir_ir::{Block, FunctionIdent};
+
+use crate::debugger::source_position;
+use crate::module::ModuleType;
+use crate::VMState;
+
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    blocks: HashMap<FunctionIdent, HashMap<Block, u64>>,
+    calls: HashMap<FunctionIdent, u64>,
+}
+
+/// Line coverage of a single function.
+#[derive(Debug, Clone)]
+pub struct FunctionLines {
+    pub ident: FunctionIdent,
+    /// One based line of the function entry, if known.
+    pub line: Option<u32>,
+    pub calls: u64,
+}
+
+/// Line coverage of a single source file.
+#[derive(Debug, Clone, Default)]
+pub struct FileLines {
+    pub functions: Vec<FunctionLines>,
+    /// Execution count of every instrumented line, keyed by the one based
+    /// line number.
+    pub lines: BTreeMap<u32, u64>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_block(&mut self, ident: &FunctionIdent, block: Block) {
+        *self
+            .blocks
+            .entry(*ident)
+            .or_insert_with(HashMap::new)
+            .entry(block)
+            .or_insert(0) += 1;
+    }
+
+    pub fn record_call(&mut self, ident: &FunctionIdent) {
+        *self.calls.entry(*ident).or_insert(0) += 1;
+    }
+
+    pub fn block_count(&self, ident: &FunctionIdent, block: Block) -> u64 {
+        self.blocks
+            .get(ident)
+            .and_then(|b| b.get(&block))
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    pub fn call_count(&self, ident: &FunctionIdent) -> u64 {
+        self.calls.get(ident).cloned().unwrap_or(0)
+    }
+
+    pub fn merge(&mut self, other: &Coverage) {
+        for (ident, blocks) in other.blocks.iter() {
+            let entry = self.blocks.entry(*ident).or_insert_with(HashMap::new);
+            for (block, count) in blocks.iter() {
+                *entry.entry(*block).or_insert(0) += count;
+            }
+        }
+        for (ident, count) in other.calls.iter() {
+            *self.calls.entry(*ident).or_insert(0) += count;
+        }
+    }
+
+    /// Maps the block counts of every function in the given module to source
+    /// lines, grouped by source file. A line is counted as many times as the
+    /// most executed block on it.
+    /// Blocks with no file and line in their location are resolved through
+    /// the codemap if one is given.
+    pub fn module_lines(
+        &self,
+        vm: &VMState,
+        module: Symbol,
+        codemap: Option<&CodeMap>,
+    ) -> BTreeMap<String, FileLines> {
+        let mut files: BTreeMap<String, FileLines> = BTreeMap::new();
+
+        let loaded = match vm.module(module) {
+            Some(loaded) => loaded,
+            None => return files,
+        };
+        let erl = match &*loaded {
+            ModuleType::Erlang(erl, _) => erl,
+            _ => return files,
+        };
+
+        let mut idents: Vec<_> = erl.functions.keys().collect();
+        idents.sort();
+
+        for ident in idents {
+            let fun = &erl.functions[ident].fun;
+
+            let entry = fun.block_entry();
+            let entry_pos = source_position(fun, fun.block_location(entry), codemap);
+
+            let graph = fun.block_graph();
+            for block in graph.dfs_iter() {
+                let pos = match source_position(fun, fun.block_location(block), codemap) {
+                    Some(pos) => pos,
+                    None => continue,
+                };
+                let count = self.block_count(ident, block);
+                let line = files
+                    .entry(pos.file)
+                    .or_default()
+                    .lines
+                    .entry(pos.line)
+                    .or_insert(0);
+                *line = (*line).max(count);
+            }
+
+            let file = entry_pos
+                .as_ref()
+                .map(|p| p.file.clone())
+                .unwrap_or_else(|| module.to_string());
+            files.entry(file).or_default().functions.push(FunctionLines {
+                ident: *ident,
+                line: entry_pos.map(|p| p.line),
+                calls: self.call_count(ident),
+            });
+        }
+
+        files
+    }
+
+    /// Writes line and function coverage of the given modules as a LCOV
+    /// tracefile.
+    pub fn write_lcov<W: Write>(
+        &self,
+        vm: &VMState,
+        modules: &[Symbol],
+        codemap: Option<&CodeMap>,
+        out: &mut W,
+    ) -> io::Result<()> {
+        for module in modules {
+            for (file, lines) in self.module_lines(vm, *module, codemap) {
+                writeln!(out, "TN:{}", module)?;
+                writeln!(out, "SF:{}", file)?;
+
+                for fun in lines.functions.iter() {
+                    writeln!(out, "FN:{},{}", fun.line.unwrap_or(0), fun.ident)?;
+                }
+                for fun in lines.functions.iter() {
+                    writeln!(out, "FNDA:{},{}", fun.calls, fun.ident)?;
+                }
+                let hit = lines.functions.iter().filter(|f| f.calls > 0).count();
+                writeln!(out, "FNF:{}", lines.functions.len())?;
+                writeln!(out, "FNH:{}", hit)?;
+
+                for (line, count) in lines.lines.iter() {
+                    writeln!(out, "DA:{},{}", line, count)?;
+                }
+                let hit = lines.lines.values().filter(|c| **c > 0).count();
+                writeln!(out, "LF:{}", lines.lines.len())?;
+                writeln!(out, "LH:{}", hit)?;
+
+                writeln!(out, "end_of_record")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the number of calls to every function, most called first.
+    /// Erlang functions that were loaded but never called are included with
+    /// a count of zero.
+    pub fn write_call_counts<W: Write>(&self, vm: &VMState, out: &mut W) -> io::Result<()> {
+        let mut counts: HashMap<FunctionIdent, u64> = self.calls.clone();
+        for module in vm.modules.borrow().values() {
+            if let ModuleType::Erlang(erl, _) = &**module {
+                for ident in erl.functions.keys() {
+                    counts.entry(*ident).or_insert(0);
+                }
+            }
+        }
+
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by(|(i1, c1), (i2, c2)| c2.cmp(c1).then(i1.cmp(i2)));
+
+        for (ident, count) in counts {
+            writeln!(out, "{:>10} {}", count, ident)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,319 @@
+//! Source level debugger on top of the interpreter.
+//!
+//! The interpreter executes a single block for every `TermCall`, which makes
+//! the boundaries between calls natural stopping points. The debugger drives
+//! the call loop itself, and stops before executing an Erlang block when a
+//! breakpoint is hit or a step finishes.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use libeir_diagnostics::CodeMap;
+use libeir_ir::{Block, Function, FunctionIdent, Location, Value};
+
+use crate::process::{call_bindings, call_target, CallExecutor, Continuation, TermCall};
+use crate::process::{ProcessContext, StackFrame};
+use crate::{ErlangFunction, Term, VMState};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourcePosition {
+    pub file: String,
+    /// One based line number.
+    pub line: u32,
+}
+impl Display for SourcePosition {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Stops when entering the given line.
+    /// The file matches any source file with the given path suffix.
+    Line { file: String, line: u32 },
+    /// Stops when entering the given function.
+    Function(FunctionIdent),
+}
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Breakpoint::Line { file, line } => write!(f, "{}:{}", file, line),
+            Breakpoint::Function(ident) => write!(f, "{}", ident),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StepMode {
+    /// Stop before the next Erlang block.
+    Block,
+    /// Stop before the next Erlang block on a different source line.
+    Line,
+    /// Only stop at breakpoints.
+    Continue,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// Stopped at the breakpoint with the given id.
+    Breakpoint(usize),
+    Step,
+    /// The initial call returned or threw. See `Debugger::result`.
+    Finished,
+}
+
+pub type CallResult = Result<Rc<Term>, (Rc<Term>, Rc<Term>, Rc<Term>)>;
+
+pub struct Debugger<'a> {
+    vm: &'a VMState,
+    codemap: Option<Arc<CodeMap>>,
+
+    process: ProcessContext,
+    executor: CallExecutor,
+
+    /// The call that will be executed next.
+    pending: Option<TermCall>,
+    result: Option<CallResult>,
+
+    /// Breakpoints by id. Ids are never reused, so they stay the same when
+    /// other breakpoints are removed.
+    breakpoints: BTreeMap<usize, Breakpoint>,
+    next_breakpoint: usize,
+
+    /// Whether we are currently stopped at `pending`. Prevents stopping at
+    /// the same point twice when resuming.
+    stopped: bool,
+    /// Source position of the last executed block with a known position.
+    last_position: Option<SourcePosition>,
+}
+
+impl<'a> Debugger<'a> {
+    /// The codemap is used to resolve source lines for blocks that only
+    /// carry a span. Without it, only locations that already contain a
+    /// file and line are used.
+    pub fn new(vm: &'a VMState, codemap: Option<Arc<CodeMap>>) -> Self {
+        Debugger {
+            vm,
+            codemap,
+
+            process: ProcessContext::new(crate::Pid(0)),
+            executor: CallExecutor::new(),
+
+            pending: None,
+            result: None,
+
+            breakpoints: BTreeMap::new(),
+            next_breakpoint: 0,
+
+            stopped: false,
+            last_position: None,
+        }
+    }
+
+    /// Adds a breakpoint, returns its id.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+        let id = self.next_breakpoint;
+        self.next_breakpoint += 1;
+        self.breakpoints.insert(id, breakpoint);
+        id
+    }
+
+    pub fn remove_breakpoint(&mut self, id: usize) -> Option<Breakpoint> {
+        self.breakpoints.remove(&id)
+    }
+
+    pub fn breakpoint(&self, id: usize) -> Option<&Breakpoint> {
+        self.breakpoints.get(&id)
+    }
+
+    /// The breakpoints along with their ids, ordered by id.
+    pub fn breakpoints(&self) -> impl Iterator<Item = (usize, &Breakpoint)> {
+        self.breakpoints.iter().map(|(id, bp)| (*id, bp))
+    }
+
+    /// Sets up a call to the given function.
+    /// Nothing is executed until the debugger is resumed.
+    pub fn start(&mut self, fun: &FunctionIdent, args: &[Term]) {
+        let (process, call) = self.vm.start_call(fun, args);
+        self.process = process;
+        self.executor = CallExecutor::new();
+        self.pending = Some(call);
+        self.result = None;
+        self.stopped = false;
+        self.last_position = None;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub fn result(&self) -> Option<&CallResult> {
+        self.result.as_ref()
+    }
+
+    pub fn step_block(&mut self) -> StopReason {
+        self.resume(StepMode::Block)
+    }
+
+    pub fn step_line(&mut self) -> StopReason {
+        self.resume(StepMode::Line)
+    }
+
+    pub fn cont(&mut self) -> StopReason {
+        self.resume(StepMode::Continue)
+    }
+
+    pub fn resume(&mut self, mode: StepMode) -> StopReason {
+        let start_position = self.current_position();
+
+        loop {
+            if self.pending.is_none() {
+                return StopReason::Finished;
+            }
+
+            if let Some((fun, block)) = self.current_block() {
+                let fun = &fun.fun;
+                let position = self.block_position(fun, block);
+
+                if !self.stopped {
+                    if let Some(id) = self.hit_breakpoint(fun, block, position.as_ref()) {
+                        self.stopped = true;
+                        return StopReason::Breakpoint(id);
+                    }
+
+                    let step_done = match mode {
+                        StepMode::Block => true,
+                        StepMode::Line => position.is_some() && position != start_position,
+                        StepMode::Continue => false,
+                    };
+                    if step_done {
+                        self.stopped = true;
+                        return StopReason::Step;
+                    }
+                }
+
+                if position.is_some() {
+                    self.last_position = position;
+                }
+            }
+
+            self.stopped = false;
+            let call = self.pending.take().unwrap();
+            match self.executor.run(self.vm, &mut self.process, call) {
+                Continuation::Term(next) => self.pending = Some(next),
+                Continuation::ReturnOk(ret) => self.result = Some(Ok(ret)),
+                Continuation::ReturnThrow(typ, reason, trace) => {
+                    self.result = Some(Err((typ, reason, trace)))
+                }
+            }
+        }
+    }
+
+    fn hit_breakpoint(
+        &self,
+        fun: &Function,
+        block: Block,
+        position: Option<&SourcePosition>,
+    ) -> Option<usize> {
+        let hit = self.breakpoints.iter().find(|(_, bp)| match bp {
+            Breakpoint::Function(ident) => fun.ident() == ident && fun.block_entry() == block,
+            Breakpoint::Line { file, line } => match position {
+                // Only break when entering the line, a single line is usually
+                // made up of many blocks.
+                Some(pos) => {
+                    pos.line == *line
+                        && pos.file.ends_with(file.as_str())
+                        && self.last_position.as_ref() != Some(pos)
+                }
+                None => false,
+            },
+        });
+        hit.map(|(id, _)| *id)
+    }
+
+    /// The Erlang function and block that will be executed next, if any.
+    pub fn current_block(&self) -> Option<(Rc<ErlangFunction>, Block)> {
+        let call = self.pending.as_ref()?;
+        call_target(self.vm, call)
+    }
+
+    /// The source position of the block that will be executed next.
+    pub fn current_position(&self) -> Option<SourcePosition> {
+        let (fun, block) = self.current_block()?;
+        self.block_position(&fun.fun, block)
+    }
+
+    /// The values bound at the block that will be executed next.
+    /// This is the live values of the block, followed by the block
+    /// arguments.
+    pub fn bindings(&self) -> Vec<(Value, Rc<Term>)> {
+        let call = match self.pending.as_ref() {
+            Some(call) => call,
+            None => return Vec::new(),
+        };
+        match call_target(self.vm, call) {
+            Some((fun, block)) => call_bindings(&fun, block, call),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn binding(&self, value: Value) -> Option<Rc<Term>> {
+        self.bindings()
+            .into_iter()
+            .find(|(v, _)| *v == value)
+            .map(|(_, t)| t)
+    }
+
+    /// The Erlang level call stack, innermost frame first.
+    pub fn stack(&self) -> impl Iterator<Item = &StackFrame> {
+        self.process.stack.iter()
+    }
+
+    /// Resolves the source position of a frame, if it is executing Erlang
+    /// code.
+    pub fn frame_position(&self, frame: &StackFrame) -> Option<SourcePosition> {
+        let block = frame.block?;
+        let fun = self.vm.erlang_function(&frame.ident)?;
+        self.block_position(&fun.fun, block)
+    }
+
+    pub fn block_position(&self, fun: &Function, block: Block) -> Option<SourcePosition> {
+        source_position(fun, fun.block_location(block), self.codemap.as_deref())
+    }
+}
+
+/// Resolves a location to a source position.
+/// Terminals that already carry a file and line are used directly, otherwise
+/// the span is looked up in the codemap if one is given.
+pub(crate) fn source_position(
+    fun: &Function,
+    location: Location,
+    codemap: Option<&CodeMap>,
+) -> Option<SourcePosition> {
+    let locs = &fun.locations;
+    for terminal in locs.terminals(location).iter().rev() {
+        if let (Some(file), Some(line)) =
+            (locs.terminal_file(*terminal), locs.terminal_line(*terminal))
+        {
+            return Some(SourcePosition {
+                file: file.to_string(),
+                line: line + 1,
+            });
+        }
+
+        if let Some(codemap) = codemap {
+            let span = locs.terminal_span(*terminal);
+            if let Some(file) = codemap.get(span.source_id()) {
+                let line = file.line_index(span.start_index());
+                return Some(SourcePosition {
+                    file: file.name().to_string(),
+                    line: line.0 + 1,
+                });
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,403 @@
+use std::rc::Rc;
+
+use libeir_intern::Symbol;
+
+use num_bigint::{BigInt, Sign};
+use num_traits::{Signed, ToPrimitive};
+
+use crate::module::{NativeModule, NativeReturn};
+use crate::process::ProcessContext;
+use crate::term::Term;
+use crate::vm::VMState;
+
+use super::util::{badarg, binary_bytes, bytes_to_binary, proper_list};
+
+fn bytes_arg(term: &Term) -> Result<Vec<u8>, NativeReturn> {
+    binary_bytes(term).ok_or_else(badarg)
+}
+
+/// Reads a pattern argument, which is either a binary or a list of binaries.
+/// Empty patterns are not allowed.
+fn patterns_arg(term: &Rc<Term>) -> Result<Vec<Vec<u8>>, NativeReturn> {
+    let patterns = match &**term {
+        Term::ListCell(_, _) => proper_list(term)?
+            .iter()
+            .map(|pat| bytes_arg(pat))
+            .collect::<Result<Vec<_>, _>>()?,
+        _ => vec![bytes_arg(term)?],
+    };
+    if patterns.is_empty() || patterns.iter().any(|pat| pat.is_empty()) {
+        return Err(badarg());
+    }
+    Ok(patterns)
+}
+
+/// Reads the option list of `split/3` and `replace/4`. Returns whether the
+/// `global` option was given.
+fn global_option(term: &Rc<Term>) -> Result<bool, NativeReturn> {
+    let mut global = false;
+    for opt in proper_list(term)? {
+        match opt.as_atom() {
+            Some(atom) if atom == "global" => global = true,
+            Some(atom) if atom == "trim" || atom == "trim_all" => (),
+            _ => return Err(badarg()),
+        }
+    }
+    Ok(global)
+}
+
+/// Finds the first match of any pattern at or after `from`. When several
+/// patterns match at the same position, the longest one is chosen.
+fn find_match(subject: &[u8], patterns: &[Vec<u8>], from: usize) -> Option<(usize, usize)> {
+    (from..subject.len()).find_map(|pos| {
+        patterns
+            .iter()
+            .filter(|pat| subject[pos..].starts_with(pat))
+            .map(|pat| pat.len())
+            .max()
+            .map(|len| (pos, len))
+    })
+}
+
+/// Finds all non-overlapping matches, or only the first one.
+fn find_matches(subject: &[u8], patterns: &[Vec<u8>], global: bool) -> Vec<(usize, usize)> {
+    let mut matches = Vec::new();
+    let mut from = 0;
+    while let Some((pos, len)) = find_match(subject, patterns, from) {
+        matches.push((pos, len));
+        from = pos + len;
+        if !global {
+            break;
+        }
+    }
+    matches
+}
+
+fn list(elems: &[Rc<Term>]) -> Rc<Term> {
+    Term::slice_to_list(elems, Term::Nil.into())
+}
+
+fn part_term(pos: usize, len: usize) -> Rc<Term> {
+    Term::Tuple(vec![
+        Term::new_usize(pos).into(),
+        Term::new_usize(len).into(),
+    ])
+    .into()
+}
+
+fn split(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2 || args.len() == 3);
+    let subject = native_try!(bytes_arg(&args[0]));
+    let patterns = native_try!(patterns_arg(&args[1]));
+    let global = match args.get(2) {
+        Some(opts) => native_try!(global_option(opts)),
+        None => false,
+    };
+
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (pos, len) in find_matches(&subject, &patterns, global) {
+        parts.push(bytes_to_binary(subject[start..pos].to_vec()));
+        start = pos + len;
+    }
+    parts.push(bytes_to_binary(subject[start..].to_vec()));
+    NativeReturn::Return { term: list(&parts) }
+}
+
+fn replace(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 3 || args.len() == 4);
+    let subject = native_try!(bytes_arg(&args[0]));
+    let patterns = native_try!(patterns_arg(&args[1]));
+    let replacement = native_try!(bytes_arg(&args[2]));
+    let global = match args.get(3) {
+        Some(opts) => native_try!(global_option(opts)),
+        None => false,
+    };
+
+    let mut out = Vec::new();
+    let mut start = 0;
+    for (pos, len) in find_matches(&subject, &patterns, global) {
+        out.extend_from_slice(&subject[start..pos]);
+        out.extend_from_slice(&replacement);
+        start = pos + len;
+    }
+    out.extend_from_slice(&subject[start..]);
+    NativeReturn::Return {
+        term: bytes_to_binary(out),
+    }
+}
+
+fn match_2(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let subject = native_try!(bytes_arg(&args[0]));
+    let patterns = native_try!(patterns_arg(&args[1]));
+    let term = match find_match(&subject, &patterns, 0) {
+        Some((pos, len)) => part_term(pos, len),
+        None => Term::new_atom("nomatch").into(),
+    };
+    NativeReturn::Return { term }
+}
+
+fn matches_2(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let subject = native_try!(bytes_arg(&args[0]));
+    let patterns = native_try!(patterns_arg(&args[1]));
+    let found: Vec<_> = find_matches(&subject, &patterns, true)
+        .into_iter()
+        .map(|(pos, len)| part_term(pos, len))
+        .collect();
+    NativeReturn::Return { term: list(&found) }
+}
+
+/// Reads a `{Pos, Len}` part of `size` bytes. A negative length selects the
+/// bytes before `Pos`.
+fn part_range(term: &Term, size: usize) -> Result<(usize, usize), NativeReturn> {
+    let (pos, len) = match term.as_tuple() {
+        Some([pos, len]) => (pos, len),
+        _ => return Err(badarg()),
+    };
+    let pos = pos.as_i64().ok_or_else(badarg)?;
+    let len = len.as_i64().ok_or_else(badarg)?;
+    let (start, end) = if len < 0 {
+        (pos + len, pos)
+    } else {
+        (pos, pos + len)
+    };
+    if start < 0 || end as u64 > size as u64 {
+        return Err(badarg());
+    }
+    Ok((start as usize, end as usize))
+}
+
+fn part(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2 || args.len() == 3);
+    let subject = native_try!(bytes_arg(&args[0]));
+    let (start, end) = if args.len() == 3 {
+        let range = Term::Tuple(vec![args[1].clone(), args[2].clone()]);
+        native_try!(part_range(&range, subject.len()))
+    } else {
+        native_try!(part_range(&args[1], subject.len()))
+    };
+    NativeReturn::Return {
+        term: bytes_to_binary(subject[start..end].to_vec()),
+    }
+}
+
+fn at(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let subject = native_try!(bytes_arg(&args[0]));
+    match args[1].as_usize().and_then(|pos| subject.get(pos)) {
+        Some(byte) => NativeReturn::Return {
+            term: Term::new_usize(*byte as usize).into(),
+        },
+        None => badarg(),
+    }
+}
+
+fn first(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let subject = native_try!(bytes_arg(&args[0]));
+    match subject.first() {
+        Some(byte) => NativeReturn::Return {
+            term: Term::new_usize(*byte as usize).into(),
+        },
+        None => badarg(),
+    }
+}
+
+fn last(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let subject = native_try!(bytes_arg(&args[0]));
+    match subject.last() {
+        Some(byte) => NativeReturn::Return {
+            term: Term::new_usize(*byte as usize).into(),
+        },
+        None => badarg(),
+    }
+}
+
+fn copy(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1 || args.len() == 2);
+    let subject = native_try!(bytes_arg(&args[0]));
+    let times = match args.get(1) {
+        Some(times) => match times.as_usize() {
+            Some(times) => times,
+            None => return badarg(),
+        },
+        None => 1,
+    };
+    NativeReturn::Return {
+        term: bytes_to_binary(subject.repeat(times)),
+    }
+}
+
+fn bin_to_list(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() >= 1 && args.len() <= 3);
+    let subject = native_try!(bytes_arg(&args[0]));
+    let (start, end) = match args.len() {
+        1 => (0, subject.len()),
+        2 => native_try!(part_range(&args[1], subject.len())),
+        _ => {
+            let range = Term::Tuple(vec![args[1].clone(), args[2].clone()]);
+            native_try!(part_range(&range, subject.len()))
+        }
+    };
+    let elems: Vec<_> = subject[start..end]
+        .iter()
+        .map(|b| Term::new_usize(*b as usize).into())
+        .collect();
+    NativeReturn::Return { term: list(&elems) }
+}
+
+fn list_to_bin(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let mut out = Vec::new();
+    for elem in native_try!(proper_list(&args[0])) {
+        match elem.as_integer().and_then(|i| i.to_u8()) {
+            Some(byte) => out.push(byte),
+            None => match binary_bytes(&elem) {
+                Some(bytes) => out.extend(bytes),
+                None => return badarg(),
+            },
+        }
+    }
+    NativeReturn::Return {
+        term: bytes_to_binary(out),
+    }
+}
+
+fn is_big_endian(args: &[Rc<Term>], idx: usize) -> Result<bool, NativeReturn> {
+    match args.get(idx).map(|e| e.as_atom()) {
+        None => Ok(true),
+        Some(Some(atom)) if atom == "big" => Ok(true),
+        Some(Some(atom)) if atom == "little" => Ok(false),
+        _ => Err(badarg()),
+    }
+}
+
+fn encode_unsigned(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1 || args.len() == 2);
+    let big = native_try!(is_big_endian(args, 1));
+    let int = match args[0].as_integer() {
+        Some(int) if !int.is_negative() => int,
+        _ => return badarg(),
+    };
+    let bytes = if big {
+        int.to_bytes_be().1
+    } else {
+        int.to_bytes_le().1
+    };
+    NativeReturn::Return {
+        term: bytes_to_binary(bytes),
+    }
+}
+
+fn decode_unsigned(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1 || args.len() == 2);
+    let big = native_try!(is_big_endian(args, 1));
+    let bytes = native_try!(bytes_arg(&args[0]));
+    let int = if big {
+        BigInt::from_bytes_be(Sign::Plus, &bytes)
+    } else {
+        BigInt::from_bytes_le(Sign::Plus, &bytes)
+    };
+    NativeReturn::Return {
+        term: Term::Integer(int).into(),
+    }
+}
+
+fn common_affix(args: &[Rc<Term>], suffix: bool) -> NativeReturn {
+    assert!(args.len() == 1);
+    let mut binaries = native_try!(native_try!(proper_list(&args[0]))
+        .iter()
+        .map(|bin| bytes_arg(bin))
+        .collect::<Result<Vec<_>, _>>());
+    if binaries.is_empty() {
+        return badarg();
+    }
+    if suffix {
+        for bin in binaries.iter_mut() {
+            bin.reverse();
+        }
+    }
+    let first = &binaries[0];
+    let len = binaries[1..].iter().fold(first.len(), |len, bin| {
+        first
+            .iter()
+            .zip(bin.iter())
+            .take(len)
+            .take_while(|(a, b)| a == b)
+            .count()
+    });
+    NativeReturn::Return {
+        term: Term::new_usize(len).into(),
+    }
+}
+
+fn longest_common_prefix(
+    _vm: &VMState,
+    _proc: &mut ProcessContext,
+    args: &[Rc<Term>],
+) -> NativeReturn {
+    common_affix(args, false)
+}
+
+fn longest_common_suffix(
+    _vm: &VMState,
+    _proc: &mut ProcessContext,
+    args: &[Rc<Term>],
+) -> NativeReturn {
+    common_affix(args, true)
+}
+
+pub fn make_binary() -> NativeModule {
+    let mut module = NativeModule::new(Symbol::intern("binary"));
+    module.add_fun(Symbol::intern("split"), 2, Box::new(split));
+    module.add_fun(Symbol::intern("split"), 3, Box::new(split));
+    module.add_fun(Symbol::intern("replace"), 3, Box::new(replace));
+    module.add_fun(Symbol::intern("replace"), 4, Box::new(replace));
+    module.add_fun(Symbol::intern("match"), 2, Box::new(match_2));
+    module.add_fun(Symbol::intern("matches"), 2, Box::new(matches_2));
+    module.add_fun(Symbol::intern("part"), 2, Box::new(part));
+    module.add_fun(Symbol::intern("part"), 3, Box::new(part));
+    module.add_fun(Symbol::intern("at"), 2, Box::new(at));
+    module.add_fun(Symbol::intern("first"), 1, Box::new(first));
+    module.add_fun(Symbol::intern("last"), 1, Box::new(last));
+    module.add_fun(Symbol::intern("copy"), 1, Box::new(copy));
+    module.add_fun(Symbol::intern("copy"), 2, Box::new(copy));
+    module.add_fun(Symbol::intern("bin_to_list"), 1, Box::new(bin_to_list));
+    module.add_fun(Symbol::intern("bin_to_list"), 2, Box::new(bin_to_list));
+    module.add_fun(Symbol::intern("bin_to_list"), 3, Box::new(bin_to_list));
+    module.add_fun(Symbol::intern("list_to_bin"), 1, Box::new(list_to_bin));
+    module.add_fun(
+        Symbol::intern("encode_unsigned"),
+        1,
+        Box::new(encode_unsigned),
+    );
+    module.add_fun(
+        Symbol::intern("encode_unsigned"),
+        2,
+        Box::new(encode_unsigned),
+    );
+    module.add_fun(
+        Symbol::intern("decode_unsigned"),
+        1,
+        Box::new(decode_unsigned),
+    );
+    module.add_fun(
+        Symbol::intern("decode_unsigned"),
+        2,
+        Box::new(decode_unsigned),
+    );
+    module.add_fun(
+        Symbol::intern("longest_common_prefix"),
+        1,
+        Box::new(longest_common_prefix),
+    );
+    module.add_fun(
+        Symbol::intern("longest_common_suffix"),
+        1,
+        Box::new(longest_common_suffix),
+    );
+    module
+}
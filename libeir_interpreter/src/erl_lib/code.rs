@@ -0,0 +1,99 @@
+//! The `code` module. Object code is Erlang source, or any other format the
+//! code loader has a frontend for, selected by the extension of the file name.
+
+use std::rc::Rc;
+use std::sync::Arc;
+
+use libeir_diagnostics::CodeMap;
+use libeir_intern::Symbol;
+
+use crate::loader::CodeLoader;
+use crate::module::{NativeModule, NativeReturn};
+use crate::process::ProcessContext;
+use crate::term::Term;
+use crate::vm::{LoadError, VMState};
+
+use super::util::{badarg, binary_bytes, list_to_string};
+
+fn error(reason: &str) -> NativeReturn {
+    NativeReturn::Return {
+        term: Term::Tuple(vec![
+            Term::new_atom("error").into(),
+            Term::new_atom(reason).into(),
+        ])
+        .into(),
+    }
+}
+
+fn load_binary(vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 3);
+    let name = match args[0].as_atom() {
+        Some(name) => name,
+        None => return badarg(),
+    };
+    let filename = match list_to_string(&args[1]) {
+        Some(filename) => filename,
+        None => return badarg(),
+    };
+    let source = match binary_bytes(&args[2]).map(String::from_utf8) {
+        Some(Ok(source)) => source,
+        Some(Err(_)) => return error("badfile"),
+        None => return badarg(),
+    };
+
+    let module = match &mut *vm.code_loader.borrow_mut() {
+        Some(loader) => loader.load_source(name, &filename, source),
+        None => CodeLoader::new(Arc::new(CodeMap::new())).load_source(name, &filename, source),
+    };
+    let module = match module {
+        Some(module) => module,
+        None => return error("badfile"),
+    };
+
+    match vm.load_module(module) {
+        Ok(()) => NativeReturn::Return {
+            term: Term::Tuple(vec![Term::new_atom("module").into(), args[0].clone()]).into(),
+        },
+        Err(LoadError::NotPurged) => error("not_purged"),
+        Err(LoadError::InUse) => error("in_use"),
+    }
+}
+
+/// Processes that still run the old code are not killed right away, they are
+/// killed when they next call into it.
+/// Returns `true` if the calling process will be killed.
+fn purge(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let name = match args[0].as_atom() {
+        Some(name) => name,
+        None => return badarg(),
+    };
+    let killed = proc.uses_old_code(vm, name);
+    vm.purge_module(name);
+    NativeReturn::Return {
+        term: Term::new_bool(killed).into(),
+    }
+}
+
+fn soft_purge(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let name = match args[0].as_atom() {
+        Some(name) => name,
+        None => return badarg(),
+    };
+    let purged = !proc.uses_old_code(vm, name);
+    if purged {
+        vm.purge_module(name);
+    }
+    NativeReturn::Return {
+        term: Term::new_bool(purged).into(),
+    }
+}
+
+pub fn make_code() -> NativeModule {
+    let mut module = NativeModule::new(Symbol::intern("code"));
+    module.add_fun(Symbol::intern("load_binary"), 3, Box::new(load_binary));
+    module.add_fun(Symbol::intern("purge"), 1, Box::new(purge));
+    module.add_fun(Symbol::intern("soft_purge"), 1, Box::new(soft_purge));
+    module
+}
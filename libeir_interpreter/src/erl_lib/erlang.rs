@@ -1,4 +1,6 @@
-use libeir_intern::Symbol;
+use libeir_intern::{Ident, Symbol};
+use libeir_ir::FunctionIdent;
+use libeir_util_binary::{BitCarrier, BitRead, BitSlice, BitVec};
 use libeir_util_number::bigint_to_double;
 
 use crate::module::{NativeModule, NativeReturn};
@@ -9,7 +11,13 @@ use crate::term::ListIteratorItem;
 use crate::term::Term;
 use crate::term::{ErlEq, ErlExactEq, ErlOrd};
 
-use ::num_traits::Signed;
+use super::util::{
+    badarg, badarith, binary_bytes, bytes_to_binary, error_tuple, flatten_iolist, list_to_string,
+    string_to_list,
+};
+
+use ::num_bigint::BigInt;
+use ::num_traits::{FromPrimitive, Signed, ToPrimitive, Zero};
 
 use std::rc::Rc;
 
@@ -107,7 +115,7 @@ fn invert(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> Nativ
         Term::Float(ref f1) => NativeReturn::Return {
             term: Term::Float((-f1.0).into()).into(),
         },
-        _ => unimplemented!(),
+        _ => badarith(),
     }
 }
 
@@ -122,7 +130,14 @@ fn mul(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeRe
         (Term::Integer(ref i1), Term::Integer(ref i2)) => NativeReturn::Return {
             term: Term::Integer(i1.clone() * i2).into(),
         },
-        _ => unimplemented!(),
+        (Term::Integer(ref int), Term::Float(ref flt))
+        | (Term::Float(ref flt), Term::Integer(ref int)) => NativeReturn::Return {
+            term: Term::Float((bigint_to_double(int) * flt.0).into()).into(),
+        },
+        (Term::Float(f1), Term::Float(f2)) => NativeReturn::Return {
+            term: Term::Float((f1.0 * f2.0).into()).into(),
+        },
+        _ => badarith(),
     }
 }
 
@@ -134,13 +149,16 @@ fn div(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeRe
     let a1 = match &*args[0] {
         Term::Integer(i1) => bigint_to_double(i1),
         Term::Float(flt) => flt.0,
-        _ => panic!(),
+        _ => return badarith(),
     };
     let a2 = match &*args[1] {
         Term::Integer(i1) => bigint_to_double(i1),
         Term::Float(flt) => flt.0,
-        _ => panic!(),
+        _ => return badarith(),
     };
+    if a2 == 0.0 {
+        return badarith();
+    }
 
     NativeReturn::Return {
         term: Term::Float((a1 / a2).into()).into(),
@@ -234,26 +252,16 @@ fn is_map(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> Nativ
     }
 }
 
-//fn list_append(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
-//    // TODO: Validate semantics
-//    assert!(args.len() == 2);
-//    match (&*args[0], &*args[1]) {
-//        (Term::Nil, Term::Nil) => NativeReturn::Return { term: Term::Nil.into() },
-//        (Term::Nil, Term::List(_, _)) => NativeReturn::Return { term: args[1].clone() },
-//        (Term::List(_, ref tail), Term::Nil) if tail.erl_eq(&Term::Nil)
-//            => NativeReturn::Return { term: args[0].clone() },
-//        (Term::List(ref _f_head, ref _f_tail), Term::List(ref b_head, ref b_tail)) => {
-//            let (mut f_head_terms, f_tail_term) = args[0].as_inproper_list();
-//            if let Term::Nil = f_tail_term {
-//                f_head_terms.extend(b_head.iter().cloned());
-//                NativeReturn::Return { term: Term::List(f_head_terms, b_tail.clone()).into() }
-//            } else {
-//                NativeReturn::Throw
-//            }
-//        }
-//        _ => NativeReturn::Throw,
-//    }
-//}
+fn list_append(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    match Term::as_list(&args[0]) {
+        Some(head) => NativeReturn::Return {
+            term: Term::slice_to_list(&head, args[1].clone()),
+        },
+        None => badarg(),
+    }
+}
+
 fn list_subtract(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
     let (mut to_remove_vec, tail) = Term::as_inproper_list(&args[1]);
     assert!(tail.erl_eq(&Term::Nil));
@@ -331,7 +339,7 @@ fn tuple_size(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> N
     }
 }
 
-fn is_function(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+fn is_function(vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
     assert!(args.len() == 1 || args.len() == 2);
 
     let arity_ref = if args.len() == 2 {
@@ -344,20 +352,18 @@ fn is_function(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) ->
         None
     };
 
-    if let Term::CapturedFunction { ident, .. } = &*args[0] {
-        let res = arity_ref.map(|a| a == ident.arity as i64).unwrap_or(true);
-        NativeReturn::Return {
-            term: Term::new_bool(res).into(),
-        }
-    } else if let Term::BoundLambda { ident, .. } = &*args[0] {
-        let res = arity_ref.map(|a| a == ident.arity as i64).unwrap_or(true);
-        NativeReturn::Return {
-            term: Term::new_bool(res).into(),
-        }
-    } else {
-        NativeReturn::Return {
-            term: Term::new_bool(false).into(),
+    let res = match &*args[0] {
+        Term::CapturedFunction { .. } | Term::BoundLambda { .. } | Term::Closure { .. } => {
+            match arity_ref {
+                // The arity of a lambda from purged code is unknown
+                Some(arity) => vm.fun_arity(&args[0]).map(|a| a as i64) == Some(arity),
+                None => true,
+            }
         }
+        _ => false,
+    };
+    NativeReturn::Return {
+        term: Term::new_bool(res).into(),
     }
 }
 
@@ -486,14 +492,11 @@ fn is_binary(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> Na
     assert!(args.len() == 1);
     let a1 = &*args[0];
 
-    match a1 {
-        Term::Binary(_) => NativeReturn::Return {
-            term: Term::new_bool(true).into(),
+    match a1.as_binary_bits() {
+        Some((_, _, bit_length)) => NativeReturn::Return {
+            term: Term::new_bool(bit_length % 8 == 0).into(),
         },
-        Term::BinarySlice { .. } => NativeReturn::Return {
-            term: Term::new_bool(true).into(),
-        },
-        _ => NativeReturn::Return {
+        None => NativeReturn::Return {
             term: Term::new_bool(false).into(),
         },
     }
@@ -612,6 +615,19 @@ fn erl_self(_vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> Nati
     }
 }
 
+/// Only the calling process runs in the interpreter, so no other process can
+/// be executing old code.
+fn check_process_code(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let (pid, module) = match (&*args[0], args[1].as_atom()) {
+        (Term::Pid(pid), Some(module)) => (*pid, module),
+        _ => return badarg(),
+    };
+    NativeReturn::Return {
+        term: Term::new_bool(pid == proc.pid && proc.uses_old_code(vm, module)).into(),
+    }
+}
+
 //fn process_flag(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
 //    assert!(args.len() == 2);
 //    if args[0].erl_eq(&Term::new_atom("trap_exit")) {
@@ -670,6 +686,103 @@ fn erase(_vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeR
     }
 }
 
+fn dict_entries(proc: &ProcessContext) -> Rc<Term> {
+    let entries: Vec<_> = proc
+        .dict
+        .iter()
+        .map(|(key, val)| Term::Tuple(vec![key.clone(), val.clone()]).into())
+        .collect();
+    Term::slice_to_list(&entries, Term::Nil.into())
+}
+
+fn get_0(_vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 0);
+    NativeReturn::Return {
+        term: dict_entries(proc),
+    }
+}
+
+fn get_keys(_vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() <= 1);
+    let keys: Vec<_> = proc
+        .dict
+        .iter()
+        .filter(|(_key, val)| args.get(0).map(|v| val.erl_exact_eq(v)).unwrap_or(true))
+        .map(|(key, _val)| key.clone())
+        .collect();
+    NativeReturn::Return {
+        term: Term::slice_to_list(&keys, Term::Nil.into()),
+    }
+}
+
+fn erase_0(_vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 0);
+    let term = dict_entries(proc);
+    proc.dict.clear();
+    NativeReturn::Return { term }
+}
+
+fn register(vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let name = match args[0].as_atom() {
+        Some(name) if name != "undefined" => name,
+        _ => return badarg(),
+    };
+    let pid = match &*args[1] {
+        Term::Pid(pid) => *pid,
+        _ => return badarg(),
+    };
+
+    let mut registry = vm.registry.borrow_mut();
+    if registry.contains_key(&name) || registry.values().any(|p| *p == pid) {
+        return badarg();
+    }
+    registry.insert(name, pid);
+    NativeReturn::Return {
+        term: Term::new_bool(true).into(),
+    }
+}
+
+fn unregister(vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let name = match args[0].as_atom() {
+        Some(name) => name,
+        None => return badarg(),
+    };
+    match vm.registry.borrow_mut().remove(&name) {
+        Some(_) => NativeReturn::Return {
+            term: Term::new_bool(true).into(),
+        },
+        None => badarg(),
+    }
+}
+
+fn whereis(vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let name = match args[0].as_atom() {
+        Some(name) => name,
+        None => return badarg(),
+    };
+    let term = match vm.registry.borrow().get(&name) {
+        Some(pid) => Term::Pid(*pid).into(),
+        None => Term::new_atom("undefined").into(),
+    };
+    NativeReturn::Return { term }
+}
+
+fn registered(vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 0);
+    let names: Vec<_> = vm
+        .registry
+        .borrow()
+        .keys()
+        .map(|name| Term::Atom(*name).into())
+        .collect();
+    NativeReturn::Return {
+        term: Term::slice_to_list(&names, Term::Nil.into()),
+    }
+}
+
 fn length(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
     assert!(args.len() == 1);
     let mut len = 0;
@@ -735,6 +848,1146 @@ fn map_size(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> Nat
     }
 }
 
+fn equal(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    NativeReturn::Return {
+        term: Term::new_bool(args[0].erl_eq(&*args[1])).into(),
+    }
+}
+fn not_equal(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    NativeReturn::Return {
+        term: Term::new_bool(!args[0].erl_eq(&*args[1])).into(),
+    }
+}
+
+fn xor(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    if let (Some(a1), Some(a2)) = (args[0].as_boolean(), args[1].as_boolean()) {
+        NativeReturn::Return {
+            term: Term::new_bool(a1 ^ a2).into(),
+        }
+    } else {
+        badarg()
+    }
+}
+
+fn plus(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    match &*args[0] {
+        Term::Integer(_) | Term::Float(_) => NativeReturn::Return {
+            term: args[0].clone(),
+        },
+        _ => badarith(),
+    }
+}
+
+/// Implements the integer only arithmetic operators.
+fn integer_op<F>(args: &[Rc<Term>], op: F) -> NativeReturn
+where
+    F: FnOnce(&BigInt, &BigInt) -> Option<BigInt>,
+{
+    assert!(args.len() == 2);
+    match (&*args[0], &*args[1]) {
+        (Term::Integer(i1), Term::Integer(i2)) => match op(i1, i2) {
+            Some(res) => NativeReturn::Return {
+                term: Term::Integer(res).into(),
+            },
+            None => badarith(),
+        },
+        _ => badarith(),
+    }
+}
+
+fn int_div(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    integer_op(
+        args,
+        |i1, i2| {
+            if i2.is_zero() {
+                None
+            } else {
+                Some(i1 / i2)
+            }
+        },
+    )
+}
+fn int_rem(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    integer_op(
+        args,
+        |i1, i2| {
+            if i2.is_zero() {
+                None
+            } else {
+                Some(i1 % i2)
+            }
+        },
+    )
+}
+
+fn band(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    integer_op(args, |i1, i2| Some(i1 & i2))
+}
+fn bor(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    integer_op(args, |i1, i2| Some(i1 | i2))
+}
+fn bxor(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    integer_op(args, |i1, i2| Some(i1 ^ i2))
+}
+
+/// Arithmetic shift, a negative `shift` shifts right.
+/// Right shifts round towards negative infinity.
+fn shift_left(int: &BigInt, shift: &BigInt) -> Option<BigInt> {
+    if shift.is_negative() {
+        let shift = (-shift).to_usize().unwrap_or(usize::max_value());
+        if int.is_negative() {
+            let shifted = (-int - 1u32) >> shift;
+            Some(-shifted - 1u32)
+        } else {
+            Some(int.clone() >> shift)
+        }
+    } else {
+        // Shifting by more than this would not fit in memory anyways
+        let shift = shift
+            .to_usize()
+            .filter(|s| *s <= u32::max_value() as usize)?;
+        Some(int.clone() << shift)
+    }
+}
+
+fn bsl(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    integer_op(args, shift_left)
+}
+fn bsr(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    integer_op(args, |i1, i2| shift_left(i1, &-i2))
+}
+
+fn bnot(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    match &*args[0] {
+        Term::Integer(int) => NativeReturn::Return {
+            term: Term::Integer(-int - 1u32).into(),
+        },
+        _ => badarith(),
+    }
+}
+
+fn float(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    match &*args[0] {
+        Term::Integer(int) => NativeReturn::Return {
+            term: Term::Float(bigint_to_double(int).into()).into(),
+        },
+        Term::Float(_) => NativeReturn::Return {
+            term: args[0].clone(),
+        },
+        _ => badarg(),
+    }
+}
+
+/// Implements the BIFs converting a number to an integer.
+fn float_to_integer<F>(args: &[Rc<Term>], op: F) -> NativeReturn
+where
+    F: FnOnce(f64) -> f64,
+{
+    assert!(args.len() == 1);
+    match &*args[0] {
+        Term::Integer(_) => NativeReturn::Return {
+            term: args[0].clone(),
+        },
+        Term::Float(flt) => NativeReturn::Return {
+            term: Term::Integer(BigInt::from_f64(op(flt.0)).unwrap()).into(),
+        },
+        _ => badarg(),
+    }
+}
+
+fn round(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    float_to_integer(args, f64::round)
+}
+fn trunc(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    float_to_integer(args, f64::trunc)
+}
+fn floor(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    float_to_integer(args, f64::floor)
+}
+fn ceil(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    float_to_integer(args, f64::ceil)
+}
+
+fn max(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let term = if args[1].erl_ord(&*args[0]) == std::cmp::Ordering::Greater {
+        args[1].clone()
+    } else {
+        args[0].clone()
+    };
+    NativeReturn::Return { term }
+}
+fn min(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let term = if args[1].erl_ord(&*args[0]) == std::cmp::Ordering::Less {
+        args[1].clone()
+    } else {
+        args[0].clone()
+    };
+    NativeReturn::Return { term }
+}
+
+fn is_float(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    match &*args[0] {
+        Term::Float(_) => NativeReturn::Return {
+            term: Term::new_bool(true).into(),
+        },
+        _ => NativeReturn::Return {
+            term: Term::new_bool(false).into(),
+        },
+    }
+}
+
+fn is_number(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    match &*args[0] {
+        Term::Integer(_) | Term::Float(_) => NativeReturn::Return {
+            term: Term::new_bool(true).into(),
+        },
+        _ => NativeReturn::Return {
+            term: Term::new_bool(false).into(),
+        },
+    }
+}
+
+fn is_boolean(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    NativeReturn::Return {
+        term: Term::new_bool(args[0].as_boolean().is_some()).into(),
+    }
+}
+
+fn is_reference(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    match &*args[0] {
+        Term::Reference(_) => NativeReturn::Return {
+            term: Term::new_bool(true).into(),
+        },
+        _ => NativeReturn::Return {
+            term: Term::new_bool(false).into(),
+        },
+    }
+}
+
+fn is_bitstring(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    NativeReturn::Return {
+        term: Term::new_bool(args[0].as_binary_bits().is_some()).into(),
+    }
+}
+
+fn is_port(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    // Ports are not supported by the interpreter
+    NativeReturn::Return {
+        term: Term::new_bool(false).into(),
+    }
+}
+
+fn is_record(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2 || args.len() == 3);
+
+    let tag = match args[1].as_atom() {
+        Some(tag) => tag,
+        None => return badarg(),
+    };
+    let size = if args.len() == 3 {
+        match args[2].as_usize() {
+            Some(size) => Some(size),
+            None => return badarg(),
+        }
+    } else {
+        None
+    };
+
+    let res = match args[0].as_tuple() {
+        Some(elems) if !elems.is_empty() => {
+            elems[0].as_atom() == Some(tag) && size.map(|s| s == elems.len()).unwrap_or(true)
+        }
+        _ => false,
+    };
+    NativeReturn::Return {
+        term: Term::new_bool(res).into(),
+    }
+}
+
+fn is_map_key(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    match args[1].as_map() {
+        Some(map) => NativeReturn::Return {
+            term: Term::new_bool(map.get(&args[0]).is_some()).into(),
+        },
+        None => error_tuple("badmap", args[1].clone()),
+    }
+}
+
+fn map_get(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    match args[1].as_map() {
+        Some(map) => match map.get(&args[0]) {
+            Some(term) => NativeReturn::Return { term },
+            None => error_tuple("badkey", args[0].clone()),
+        },
+        None => error_tuple("badmap", args[1].clone()),
+    }
+}
+
+fn size(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    if let Some(elems) = args[0].as_tuple() {
+        NativeReturn::Return {
+            term: Term::new_usize(elems.len()).into(),
+        }
+    } else if let Some((_, _, bit_length)) = args[0].as_binary_bits() {
+        NativeReturn::Return {
+            term: Term::new_usize(bit_length / 8).into(),
+        }
+    } else {
+        badarg()
+    }
+}
+
+fn byte_size(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    match args[0].as_binary_bits() {
+        Some((_, _, bit_length)) => NativeReturn::Return {
+            term: Term::new_usize((bit_length + 7) / 8).into(),
+        },
+        None => badarg(),
+    }
+}
+
+fn bit_size(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    match args[0].as_binary_bits() {
+        Some((_, _, bit_length)) => NativeReturn::Return {
+            term: Term::new_usize(bit_length).into(),
+        },
+        None => badarg(),
+    }
+}
+
+fn tuple_to_list(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    match args[0].as_tuple() {
+        Some(elems) => NativeReturn::Return {
+            term: Term::slice_to_list(elems, Term::Nil.into()),
+        },
+        None => badarg(),
+    }
+}
+
+fn list_to_tuple(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    match Term::as_list(&args[0]) {
+        Some(elems) => NativeReturn::Return {
+            term: Term::Tuple(elems).into(),
+        },
+        None => badarg(),
+    }
+}
+
+fn make_tuple(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2 || args.len() == 3);
+
+    let arity = match args[0].as_integer().and_then(|i| i.to_usize()) {
+        Some(arity) => arity,
+        None => return badarg(),
+    };
+    let mut elems = vec![args[1].clone(); arity];
+
+    if args.len() == 3 {
+        let init = match Term::as_list(&args[2]) {
+            Some(init) => init,
+            None => return badarg(),
+        };
+        for entry in init.iter() {
+            let (pos, value) = match entry.as_tuple() {
+                Some([pos, value]) => (pos, value),
+                _ => return badarg(),
+            };
+            match pos.as_integer().and_then(|i| i.to_usize()) {
+                Some(pos) if pos >= 1 && pos <= arity => elems[pos - 1] = value.clone(),
+                _ => return badarg(),
+            }
+        }
+    }
+
+    NativeReturn::Return {
+        term: Term::Tuple(elems).into(),
+    }
+}
+
+fn append_element(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    match args[0].as_tuple() {
+        Some(elems) => {
+            let mut elems = elems.to_vec();
+            elems.push(args[1].clone());
+            NativeReturn::Return {
+                term: Term::Tuple(elems).into(),
+            }
+        }
+        None => badarg(),
+    }
+}
+
+/// Decodes an atom name from a binary in the given encoding.
+fn binary_to_atom_name(bin: &Term, encoding: &Term) -> Option<Symbol> {
+    let bytes = binary_bytes(bin)?;
+    let name = match encoding.as_atom()?.as_str().get() {
+        "latin1" => bytes.iter().map(|b| *b as char).collect(),
+        "utf8" | "unicode" => String::from_utf8(bytes).ok()?,
+        _ => return None,
+    };
+    Some(Symbol::intern(&name))
+}
+
+fn list_to_atom(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    match list_to_string(&args[0]) {
+        Some(name) => NativeReturn::Return {
+            term: Term::Atom(Symbol::intern(&name)).into(),
+        },
+        None => badarg(),
+    }
+}
+
+fn atom_to_binary(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let (atom, encoding) = match (args[0].as_atom(), args[1].as_atom()) {
+        (Some(atom), Some(encoding)) => (atom, encoding),
+        _ => return badarg(),
+    };
+    let name = atom.as_str();
+    let bytes = match encoding.as_str().get() {
+        "latin1" => {
+            let bytes: Option<Vec<u8>> = name
+                .chars()
+                .map(|c| {
+                    if (c as u32) < 256 {
+                        Some(c as u8)
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            match bytes {
+                Some(bytes) => bytes,
+                None => return badarg(),
+            }
+        }
+        "utf8" | "unicode" => name.as_bytes().to_vec(),
+        _ => return badarg(),
+    };
+    NativeReturn::Return {
+        term: bytes_to_binary(bytes),
+    }
+}
+
+fn binary_to_atom(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    match binary_to_atom_name(&args[0], &args[1]) {
+        Some(name) => NativeReturn::Return {
+            term: Term::Atom(name).into(),
+        },
+        None => badarg(),
+    }
+}
+
+fn get_base(args: &[Rc<Term>], idx: usize) -> Option<u32> {
+    match args.get(idx) {
+        None => Some(10),
+        Some(base) => base
+            .as_integer()
+            .and_then(|b| b.to_u32())
+            .filter(|b| *b >= 2 && *b <= 36),
+    }
+}
+
+fn integer_to_string(args: &[Rc<Term>]) -> Option<String> {
+    assert!(args.len() == 1 || args.len() == 2);
+    let int = args[0].as_integer()?;
+    let base = get_base(args, 1)?;
+    Some(int.to_str_radix(base).to_uppercase())
+}
+
+/// Parses an integer with an optional sign.
+fn string_to_integer(string: &str, base: u32) -> Option<BigInt> {
+    let (negative, digits) = match string.chars().next() {
+        Some('-') => (true, &string[1..]),
+        Some('+') => (false, &string[1..]),
+        _ => (false, string),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(base)) {
+        return None;
+    }
+    let int = BigInt::parse_bytes(digits.as_bytes(), base)?;
+    Some(if negative { -int } else { int })
+}
+
+fn integer_to_list(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    match integer_to_string(args) {
+        Some(string) => NativeReturn::Return {
+            term: string_to_list(&string),
+        },
+        None => badarg(),
+    }
+}
+
+fn integer_to_binary(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    match integer_to_string(args) {
+        Some(string) => NativeReturn::Return {
+            term: bytes_to_binary(string.into_bytes()),
+        },
+        None => badarg(),
+    }
+}
+
+fn list_to_integer(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1 || args.len() == 2);
+    let int =
+        list_to_string(&args[0]).and_then(|string| string_to_integer(&string, get_base(args, 1)?));
+    match int {
+        Some(int) => NativeReturn::Return {
+            term: Term::Integer(int).into(),
+        },
+        None => badarg(),
+    }
+}
+
+fn binary_to_integer(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1 || args.len() == 2);
+    let int = binary_bytes(&args[0])
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .and_then(|string| string_to_integer(&string, get_base(args, 1)?));
+    match int {
+        Some(int) => NativeReturn::Return {
+            term: Term::Integer(int).into(),
+        },
+        None => badarg(),
+    }
+}
+
+/// Formats a float the way `float_to_list/1` does, in scientific notation
+/// with 20 decimals.
+fn format_float(num: f64) -> String {
+    let formatted = format!("{:.20e}", num);
+    let (mantissa, exp) = formatted.split_at(formatted.find('e').unwrap());
+    let exp: i32 = exp[1..].parse().unwrap();
+    let sign = if exp < 0 { '-' } else { '+' };
+    format!("{}e{}{:02}", mantissa, sign, exp.abs())
+}
+
+/// Parses a float in the strict format accepted by `list_to_float/1`.
+/// A decimal point with digits on both sides is required.
+fn parse_float(string: &str) -> Option<f64> {
+    let unsigned = string.trim_start_matches(|c| c == '-' || c == '+');
+    if string.len() - unsigned.len() > 1 {
+        return None;
+    }
+    let mantissa = unsigned.split(|c| c == 'e' || c == 'E').next().unwrap();
+    let mut parts = mantissa.splitn(2, '.');
+    let whole = parts.next().unwrap();
+    let frac = parts.next()?;
+    let is_digits = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    if !is_digits(whole) || !is_digits(frac) {
+        return None;
+    }
+    string.parse().ok()
+}
+
+fn float_to_list(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    match &*args[0] {
+        Term::Float(flt) => NativeReturn::Return {
+            term: string_to_list(&format_float(flt.0)),
+        },
+        _ => badarg(),
+    }
+}
+
+fn float_to_binary(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    match &*args[0] {
+        Term::Float(flt) => NativeReturn::Return {
+            term: bytes_to_binary(format_float(flt.0).into_bytes()),
+        },
+        _ => badarg(),
+    }
+}
+
+fn list_to_float(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    match list_to_string(&args[0]).and_then(|string| parse_float(&string)) {
+        Some(num) => NativeReturn::Return {
+            term: Term::Float(num.into()).into(),
+        },
+        None => badarg(),
+    }
+}
+
+fn binary_to_float(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let num = binary_bytes(&args[0])
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .and_then(|string| parse_float(&string));
+    match num {
+        Some(num) => NativeReturn::Return {
+            term: Term::Float(num.into()).into(),
+        },
+        None => badarg(),
+    }
+}
+
+fn binary_to_list(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1 || args.len() == 3);
+    let bytes = match binary_bytes(&args[0]) {
+        Some(bytes) => bytes,
+        None => return badarg(),
+    };
+
+    // Start and stop positions are 1 based and inclusive
+    let range = if args.len() == 3 {
+        match (args[1].as_integer(), args[2].as_integer()) {
+            (Some(start), Some(stop)) => match (start.to_usize(), stop.to_usize()) {
+                (Some(start), Some(stop)) if 1 <= start && start <= stop && stop <= bytes.len() => {
+                    (start - 1)..stop
+                }
+                _ => return badarg(),
+            },
+            _ => return badarg(),
+        }
+    } else {
+        0..bytes.len()
+    };
+
+    let elems: Vec<_> = bytes[range]
+        .iter()
+        .map(|b| Term::new_usize(*b as usize).into())
+        .collect();
+    NativeReturn::Return {
+        term: Term::slice_to_list(&elems, Term::Nil.into()),
+    }
+}
+
+fn bitstring_to_list(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let (buf, bit_offset, bit_length) = match args[0].as_binary_bits() {
+        Some(bits) => bits,
+        None => return badarg(),
+    };
+
+    let slice = BitSlice::with_offset_length(buf, bit_offset, bit_length);
+    let elems: Vec<_> = (0..bit_length / 8)
+        .map(|n| Term::new_usize(slice.read_word(n) as usize).into())
+        .collect();
+
+    // Trailing bits end up as a bitstring in the tail of the list
+    let rest = bit_length % 8;
+    let tail = if rest == 0 {
+        Term::Nil.into()
+    } else {
+        let mut bits = BitVec::new();
+        bits.push(BitSlice::with_offset_length(
+            buf,
+            bit_offset + bit_length - rest,
+            rest,
+        ));
+        Term::ListCell(Term::Binary(Rc::new(bits)).into(), Term::Nil.into()).into()
+    };
+
+    NativeReturn::Return {
+        term: Term::slice_to_list(&elems, tail),
+    }
+}
+
+fn iolist_to_binary(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let mut out = BitVec::new();
+    match flatten_iolist(&args[0], false, &mut out) {
+        Some(()) => NativeReturn::Return {
+            term: Term::Binary(Rc::new(out)).into(),
+        },
+        None => badarg(),
+    }
+}
+
+fn list_to_binary(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    if let Term::ListCell(_, _) | Term::Nil = &*args[0] {
+        let mut out = BitVec::new();
+        if flatten_iolist(&args[0], false, &mut out).is_some() {
+            return NativeReturn::Return {
+                term: Term::Binary(Rc::new(out)).into(),
+            };
+        }
+    }
+    badarg()
+}
+
+fn list_to_bitstring(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    if let Term::ListCell(_, _) | Term::Nil = &*args[0] {
+        let mut out = BitVec::new();
+        if flatten_iolist(&args[0], true, &mut out).is_some() {
+            return NativeReturn::Return {
+                term: Term::Binary(Rc::new(out)).into(),
+            };
+        }
+    }
+    badarg()
+}
+
+fn iolist_size(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let mut out = BitVec::new();
+    match flatten_iolist(&args[0], false, &mut out) {
+        Some(()) => NativeReturn::Return {
+            term: Term::new_usize(out.bit_len() / 8).into(),
+        },
+        None => badarg(),
+    }
+}
+
+/// Returns the byte range of `binary_part/2,3`. The length may be negative,
+/// in which case the part ends at `start`.
+fn binary_part_range(bytes: &[u8], start: &Term, length: &Term) -> Option<(usize, usize)> {
+    let start = start.as_integer()?.to_i64()?;
+    let length = length.as_integer()?.to_i64()?;
+    let (from, to) = if length < 0 {
+        (start + length, start)
+    } else {
+        (start, start + length)
+    };
+    if from < 0 || to as usize > bytes.len() {
+        return None;
+    }
+    Some((from as usize, to as usize))
+}
+
+fn binary_part(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2 || args.len() == 3);
+    let (start, length) = if args.len() == 3 {
+        (args[1].clone(), args[2].clone())
+    } else {
+        match args[1].as_tuple() {
+            Some([start, length]) => (start.clone(), length.clone()),
+            _ => return badarg(),
+        }
+    };
+
+    let bytes = match binary_bytes(&args[0]) {
+        Some(bytes) => bytes,
+        None => return badarg(),
+    };
+    match binary_part_range(&bytes, &start, &length) {
+        Some((from, to)) => NativeReturn::Return {
+            term: bytes_to_binary(bytes[from..to].to_vec()),
+        },
+        None => badarg(),
+    }
+}
+
+fn split_binary(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let bytes = match binary_bytes(&args[0]) {
+        Some(bytes) => bytes,
+        None => return badarg(),
+    };
+    match args[1].as_integer().and_then(|i| i.to_usize()) {
+        Some(pos) if pos <= bytes.len() => {
+            let (first, second) = bytes.split_at(pos);
+            NativeReturn::Return {
+                term: Term::Tuple(vec![
+                    bytes_to_binary(first.to_vec()),
+                    bytes_to_binary(second.to_vec()),
+                ])
+                .into(),
+            }
+        }
+        _ => badarg(),
+    }
+}
+
+fn error(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    // The arguments of `error/2` are only used in the stack trace
+    assert!(args.len() == 1 || args.len() == 2);
+    NativeReturn::Throw {
+        typ: Term::new_atom("error").into(),
+        reason: args[0].clone(),
+    }
+}
+
+fn throw(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    NativeReturn::Throw {
+        typ: Term::new_atom("throw").into(),
+        reason: args[0].clone(),
+    }
+}
+
+fn exit(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    NativeReturn::Throw {
+        typ: Term::new_atom("exit").into(),
+        reason: args[0].clone(),
+    }
+}
+
+fn apply(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2 || args.len() == 3);
+
+    let call_args = match Term::as_list(&args[args.len() - 1]) {
+        Some(call_args) => call_args,
+        None => return badarg(),
+    };
+
+    let fun = if args.len() == 3 {
+        match (args[0].as_atom(), args[1].as_atom()) {
+            (Some(module), Some(name)) => Term::CapturedFunction {
+                ident: FunctionIdent {
+                    module: Ident::with_empty_span(module),
+                    name: Ident::with_empty_span(name),
+                    arity: call_args.len(),
+                },
+                version: None,
+            }
+            .into(),
+            _ => return badarg(),
+        }
+    } else {
+        // The executor raises `badfun` and `badarity`
+        args[0].clone()
+    };
+
+    NativeReturn::Call {
+        fun,
+        args: call_args,
+    }
+}
+
+fn phash2(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1 || args.len() == 2);
+    let range = if args.len() == 2 {
+        match args[1].as_integer().and_then(|i| i.to_u64()) {
+            Some(range) if range >= 1 && range <= 1 << 32 => range,
+            _ => return badarg(),
+        }
+    } else {
+        1 << 27
+    };
+
+    let hash = make_hash2(&args[0]);
+    NativeReturn::Return {
+        term: Term::Integer((hash as u64 % range).into()).into(),
+    }
+}
+
+const HCONST: u32 = 0x9e37_79b9;
+const fn hconst(n: u32) -> u32 {
+    HCONST.wrapping_mul(n)
+}
+
+fn mix(mut a: u32, mut b: u32, mut c: u32) -> u32 {
+    a = a.wrapping_sub(b).wrapping_sub(c) ^ (c >> 13);
+    b = b.wrapping_sub(c).wrapping_sub(a) ^ (a << 8);
+    c = c.wrapping_sub(a).wrapping_sub(b) ^ (b >> 13);
+    a = a.wrapping_sub(b).wrapping_sub(c) ^ (c >> 12);
+    b = b.wrapping_sub(c).wrapping_sub(a) ^ (a << 16);
+    c = c.wrapping_sub(a).wrapping_sub(b) ^ (b >> 5);
+    a = a.wrapping_sub(b).wrapping_sub(c) ^ (c >> 3);
+    b = b.wrapping_sub(c).wrapping_sub(a) ^ (a << 10);
+    c.wrapping_sub(a).wrapping_sub(b) ^ (b >> 15)
+}
+
+fn block_hash(bytes: &[u8], init: u32) -> u32 {
+    let word = |k: &[u8]| k.iter().rev().fold(0u32, |acc, b| (acc << 8) | *b as u32);
+
+    let (mut a, mut b, mut c) = (HCONST, HCONST, init);
+    let mut chunks = bytes.chunks_exact(12);
+    for k in &mut chunks {
+        a = a.wrapping_add(word(&k[0..4]));
+        b = b.wrapping_add(word(&k[4..8]));
+        c = c.wrapping_add(word(&k[8..12]));
+        c = mix(a, b, c);
+    }
+
+    // The first byte of `c` is reserved for the length
+    let k = chunks.remainder();
+    c = c.wrapping_add(bytes.len() as u32);
+    a = a.wrapping_add(word(&k[..k.len().min(4)]));
+    if k.len() > 4 {
+        b = b.wrapping_add(word(&k[4..k.len().min(8)]));
+    }
+    if k.len() > 8 {
+        c = c.wrapping_add(word(&k[8..]) << 8);
+    }
+    mix(a, b, c)
+}
+
+/// The hashpjw hash of an atom's text. UTF-8 encoded latin1 characters are
+/// hashed as their latin1 byte.
+fn atom_hash(atom: Symbol) -> u32 {
+    let text = atom.as_str();
+    let mut bytes = text.as_bytes().iter().cloned().peekable();
+    let mut h: u32 = 0;
+    while let Some(mut v) = bytes.next() {
+        if v & 0xfe == 0xc2 {
+            if let Some(next) = bytes.peek().filter(|next| *next & 0xc0 == 0x80) {
+                v = (v << 6) | (next & 0x3f);
+                bytes.next();
+            }
+        }
+        h = (h << 4).wrapping_add(v as u32);
+        let g = h & 0xf000_0000;
+        if g != 0 {
+            h ^= g >> 24;
+            h ^= g;
+        }
+    }
+    h
+}
+
+enum Hash2Item<'a> {
+    Term(&'a Term),
+    /// The hash of a key-value pair of a map is done.
+    MapPair,
+    /// All pairs of a map are done, restores the state from before the map.
+    MapTail {
+        hash: u32,
+        xor_pairs: u32,
+    },
+}
+
+/// A port of `make_hash2` from ERTS, the hash `erlang:phash2` is defined
+/// by. It only depends on the contents of the term, not on how atoms
+/// happen to be interned, so it is the same across runs and nodes.
+///
+/// Funs, pids and references are hashed by their contents in the
+/// interpreter, and do not hash to the values ERTS gives them.
+fn make_hash2(term: &Term) -> u32 {
+    let mut hash: u32 = 0;
+    let mut xor_pairs: u32 = 0;
+
+    let uint32_hash_2 = |hash: &mut u32, e1: u32, e2: u32, aconst: u32| {
+        *hash = mix(aconst.wrapping_add(e1), aconst.wrapping_add(e2), *hash);
+    };
+    let uint32_hash = |hash: &mut u32, e: u32, aconst: u32| uint32_hash_2(hash, e, 0, aconst);
+
+    let mut stack = vec![Hash2Item::Term(term)];
+    while let Some(item) = stack.pop() {
+        let term = match item {
+            Hash2Item::Term(term) => term,
+            Hash2Item::MapPair => {
+                xor_pairs ^= hash;
+                hash = 0;
+                continue;
+            }
+            Hash2Item::MapTail {
+                hash: outer_hash,
+                xor_pairs: outer_xor_pairs,
+            } => {
+                hash = outer_hash;
+                uint32_hash(&mut hash, xor_pairs, hconst(19));
+                xor_pairs = outer_xor_pairs;
+                continue;
+            }
+        };
+
+        match term {
+            Term::Nil => {
+                if hash == 0 {
+                    hash = 3_468_870_702;
+                } else {
+                    uint32_hash(&mut hash, 2, hconst(2));
+                }
+            }
+            Term::Atom(atom) => {
+                if hash == 0 {
+                    hash = atom_hash(*atom);
+                } else {
+                    uint32_hash(&mut hash, atom_hash(*atom), hconst(3));
+                }
+            }
+            Term::Integer(int) => match int.to_i32() {
+                Some(small) if small >= -(1 << 27) && small < 1 << 27 => {
+                    if small < 0 {
+                        uint32_hash(&mut hash, small.wrapping_neg() as u32, HCONST);
+                    }
+                    uint32_hash(&mut hash, small as u32, HCONST);
+                }
+                _ => {
+                    let (sign, bytes) = int.to_bytes_le();
+                    let con = if sign == num_bigint::Sign::Minus {
+                        hconst(10)
+                    } else {
+                        hconst(11)
+                    };
+                    for digit in bytes.chunks(8) {
+                        let mut buf = [0; 8];
+                        buf[..digit.len()].copy_from_slice(digit);
+                        let digit = u64::from_le_bytes(buf);
+                        uint32_hash_2(&mut hash, digit as u32, (digit >> 32) as u32, con);
+                    }
+                }
+            },
+            Term::Float(float) => {
+                // -0.0 hashes as 0.0
+                let bits = if float.0 == 0.0 { 0 } else { float.0.to_bits() };
+                uint32_hash_2(&mut hash, (bits >> 32) as u32, bits as u32, hconst(12));
+            }
+            Term::Tuple(elems) => {
+                uint32_hash(&mut hash, elems.len() as u32, hconst(9));
+                stack.extend(elems.iter().rev().map(|e| Hash2Item::Term(&**e)));
+            }
+            Term::ListCell(_, _) => {
+                // Runs of bytes, like strings, are hashed four at a time
+                let mut rest = term;
+                let mut count = 0;
+                let mut bytes: u32 = 0;
+                while let Term::ListCell(head, tail) = rest {
+                    let byte = match head.as_i64() {
+                        Some(byte) if byte >= 0 && byte <= 255 => byte as u32,
+                        _ => break,
+                    };
+                    bytes = (bytes << 8) + byte;
+                    if count == 3 {
+                        uint32_hash(&mut hash, bytes, hconst(4));
+                        count = 0;
+                        bytes = 0;
+                    } else {
+                        count += 1;
+                    }
+                    rest = &**tail;
+                }
+                if count > 0 {
+                    uint32_hash(&mut hash, bytes, hconst(4));
+                }
+                if let Term::ListCell(head, tail) = rest {
+                    stack.push(Hash2Item::Term(&**tail));
+                    stack.push(Hash2Item::Term(&**head));
+                } else {
+                    stack.push(Hash2Item::Term(rest));
+                }
+            }
+            Term::Map(map) => {
+                // Pairs are hashed separately and combined with xor, the
+                // hash does not depend on the order of the keys.
+                uint32_hash(&mut hash, map.len() as u32, hconst(16));
+                if map.len() > 0 {
+                    stack.push(Hash2Item::MapTail { hash, xor_pairs });
+                    hash = 0;
+                    xor_pairs = 0;
+                    for (key, value) in map.iter() {
+                        stack.push(Hash2Item::MapPair);
+                        stack.push(Hash2Item::Term(&**value));
+                        stack.push(Hash2Item::Term(&**key));
+                    }
+                }
+            }
+            Term::Binary(_) | Term::BinarySlice { .. } => {
+                let (buf, bit_offset, bit_length) = term.as_binary_bits().unwrap();
+                let slice = BitSlice::with_offset_length(buf, bit_offset, bit_length);
+                let whole = bit_length / 8;
+                let partial = bit_length % 8;
+
+                let con = hconst(13).wrapping_add(hash);
+                if bit_length == 0 {
+                    hash = con;
+                } else {
+                    let bytes: Vec<u8> = (0..whole).map(|n| slice.read_word(n)).collect();
+                    hash = block_hash(&bytes, con);
+                    if partial > 0 {
+                        let last = (0..partial).fold(0u32, |acc, n| {
+                            (acc << 1) | slice.read_bit(whole * 8 + n) as u32
+                        });
+                        uint32_hash_2(&mut hash, partial as u32, last, hconst(15));
+                    }
+                }
+            }
+            Term::CapturedFunction { ident, .. } => {
+                uint32_hash_2(
+                    &mut hash,
+                    ident.arity as u32,
+                    atom_hash(ident.module.name),
+                    HCONST,
+                );
+                uint32_hash(&mut hash, atom_hash(ident.name.name), hconst(14));
+            }
+            Term::BoundLambda {
+                ident, environment, ..
+            } => {
+                uint32_hash_2(
+                    &mut hash,
+                    environment.len() as u32,
+                    atom_hash(ident.module.name),
+                    HCONST,
+                );
+                uint32_hash_2(
+                    &mut hash,
+                    atom_hash(ident.name.name),
+                    ident.arity as u32,
+                    HCONST,
+                );
+                stack.extend(environment.iter().rev().map(|e| Hash2Item::Term(&**e)));
+            }
+            Term::Closure {
+                ident, environment, ..
+            } => {
+                uint32_hash_2(
+                    &mut hash,
+                    ident.arity as u32,
+                    atom_hash(ident.module.name),
+                    HCONST,
+                );
+                uint32_hash(&mut hash, atom_hash(ident.name.name), hconst(14));
+                stack.push(Hash2Item::Term(&**environment));
+            }
+            Term::Pid(pid) => uint32_hash(&mut hash, pid.0 as u32, hconst(5)),
+            Term::Reference(reference) => uint32_hash(&mut hash, reference.0 as u32, hconst(7)),
+            Term::ValueList(_) | Term::ReturnOk | Term::ReturnThrow => unreachable!(),
+        }
+    }
+
+    hash
+}
+
+fn make_ref(vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 0);
+    NativeReturn::Return {
+        term: Term::Reference(vm.ref_gen.borrow_mut().next()).into(),
+    }
+}
+
+fn node(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 0);
+    NativeReturn::Return {
+        term: Term::new_atom("nonode@nohost").into(),
+    }
+}
+
+fn is_alive(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 0);
+    NativeReturn::Return {
+        term: Term::new_bool(false).into(),
+    }
+}
+
+fn garbage_collect(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 0);
+    NativeReturn::Return {
+        term: Term::new_bool(true).into(),
+    }
+}
+
 pub fn make_erlang() -> NativeModule {
     let mut module = NativeModule::new(Symbol::intern("erlang"));
     module.add_fun(Symbol::intern("+"), 2, Box::new(add));
@@ -743,7 +1996,7 @@ pub fn make_erlang() -> NativeModule {
     module.add_fun(Symbol::intern("*"), 2, Box::new(mul));
     module.add_fun(Symbol::intern("/"), 2, Box::new(div));
     module.add_fun(Symbol::intern("abs"), 1, Box::new(abs));
-    //module.add_fun(Symbol::intern("++"), 2, Box::new(list_append));
+    module.add_fun(Symbol::intern("++"), 2, Box::new(list_append));
     module.add_fun(Symbol::intern("--"), 2, Box::new(list_subtract));
     module.add_fun(Symbol::intern("=:="), 2, Box::new(exact_eq));
     module.add_fun(Symbol::intern("=/="), 2, Box::new(exact_not_eq));
@@ -770,12 +2023,179 @@ pub fn make_erlang() -> NativeModule {
     module.add_fun(Symbol::intern("element"), 2, Box::new(element));
     module.add_fun(Symbol::intern("length"), 1, Box::new(length));
     module.add_fun(Symbol::intern("self"), 0, Box::new(erl_self));
+    module.add_fun(
+        Symbol::intern("check_process_code"),
+        2,
+        Box::new(check_process_code),
+    );
     module.add_fun(Symbol::intern("put"), 2, Box::new(put));
     module.add_fun(Symbol::intern("get"), 1, Box::new(get));
     module.add_fun(Symbol::intern("erase"), 1, Box::new(erase));
+    module.add_fun(Symbol::intern("get"), 0, Box::new(get_0));
+    module.add_fun(Symbol::intern("get_keys"), 0, Box::new(get_keys));
+    module.add_fun(Symbol::intern("get_keys"), 1, Box::new(get_keys));
+    module.add_fun(Symbol::intern("erase"), 0, Box::new(erase_0));
+    module.add_fun(Symbol::intern("register"), 2, Box::new(register));
+    module.add_fun(Symbol::intern("unregister"), 1, Box::new(unregister));
+    module.add_fun(Symbol::intern("whereis"), 1, Box::new(whereis));
+    module.add_fun(Symbol::intern("registered"), 0, Box::new(registered));
     module.add_fun(Symbol::intern("hd"), 1, Box::new(hd));
     module.add_fun(Symbol::intern("tl"), 1, Box::new(tl));
     module.add_fun(Symbol::intern("map_size"), 1, Box::new(map_size));
+    module.add_fun(Symbol::intern("=="), 2, Box::new(equal));
+    module.add_fun(Symbol::intern("/="), 2, Box::new(not_equal));
+    module.add_fun(Symbol::intern("xor"), 2, Box::new(xor));
+    module.add_fun(Symbol::intern("+"), 1, Box::new(plus));
+    module.add_fun(Symbol::intern("div"), 2, Box::new(int_div));
+    module.add_fun(Symbol::intern("rem"), 2, Box::new(int_rem));
+    module.add_fun(Symbol::intern("band"), 2, Box::new(band));
+    module.add_fun(Symbol::intern("bor"), 2, Box::new(bor));
+    module.add_fun(Symbol::intern("bxor"), 2, Box::new(bxor));
+    module.add_fun(Symbol::intern("bsl"), 2, Box::new(bsl));
+    module.add_fun(Symbol::intern("bsr"), 2, Box::new(bsr));
+    module.add_fun(Symbol::intern("bnot"), 1, Box::new(bnot));
+    module.add_fun(Symbol::intern("float"), 1, Box::new(float));
+    module.add_fun(Symbol::intern("round"), 1, Box::new(round));
+    module.add_fun(Symbol::intern("trunc"), 1, Box::new(trunc));
+    module.add_fun(Symbol::intern("floor"), 1, Box::new(floor));
+    module.add_fun(Symbol::intern("ceil"), 1, Box::new(ceil));
+    module.add_fun(Symbol::intern("max"), 2, Box::new(max));
+    module.add_fun(Symbol::intern("min"), 2, Box::new(min));
+    module.add_fun(Symbol::intern("is_float"), 1, Box::new(is_float));
+    module.add_fun(Symbol::intern("is_number"), 1, Box::new(is_number));
+    module.add_fun(Symbol::intern("is_boolean"), 1, Box::new(is_boolean));
+    module.add_fun(Symbol::intern("is_reference"), 1, Box::new(is_reference));
+    module.add_fun(Symbol::intern("is_bitstring"), 1, Box::new(is_bitstring));
+    module.add_fun(Symbol::intern("is_port"), 1, Box::new(is_port));
+    module.add_fun(Symbol::intern("is_record"), 2, Box::new(is_record));
+    module.add_fun(Symbol::intern("is_record"), 3, Box::new(is_record));
+    module.add_fun(Symbol::intern("is_map_key"), 2, Box::new(is_map_key));
+    module.add_fun(Symbol::intern("map_get"), 2, Box::new(map_get));
+    module.add_fun(Symbol::intern("size"), 1, Box::new(size));
+    module.add_fun(Symbol::intern("byte_size"), 1, Box::new(byte_size));
+    module.add_fun(Symbol::intern("bit_size"), 1, Box::new(bit_size));
+    module.add_fun(Symbol::intern("tuple_to_list"), 1, Box::new(tuple_to_list));
+    module.add_fun(Symbol::intern("list_to_tuple"), 1, Box::new(list_to_tuple));
+    module.add_fun(Symbol::intern("make_tuple"), 2, Box::new(make_tuple));
+    module.add_fun(Symbol::intern("make_tuple"), 3, Box::new(make_tuple));
+    module.add_fun(
+        Symbol::intern("append_element"),
+        2,
+        Box::new(append_element),
+    );
+    module.add_fun(Symbol::intern("list_to_atom"), 1, Box::new(list_to_atom));
+    module.add_fun(
+        Symbol::intern("atom_to_binary"),
+        2,
+        Box::new(atom_to_binary),
+    );
+    module.add_fun(
+        Symbol::intern("binary_to_atom"),
+        2,
+        Box::new(binary_to_atom),
+    );
+    module.add_fun(
+        Symbol::intern("integer_to_list"),
+        1,
+        Box::new(integer_to_list),
+    );
+    module.add_fun(
+        Symbol::intern("integer_to_list"),
+        2,
+        Box::new(integer_to_list),
+    );
+    module.add_fun(
+        Symbol::intern("integer_to_binary"),
+        1,
+        Box::new(integer_to_binary),
+    );
+    module.add_fun(
+        Symbol::intern("integer_to_binary"),
+        2,
+        Box::new(integer_to_binary),
+    );
+    module.add_fun(
+        Symbol::intern("list_to_integer"),
+        1,
+        Box::new(list_to_integer),
+    );
+    module.add_fun(
+        Symbol::intern("list_to_integer"),
+        2,
+        Box::new(list_to_integer),
+    );
+    module.add_fun(
+        Symbol::intern("binary_to_integer"),
+        1,
+        Box::new(binary_to_integer),
+    );
+    module.add_fun(
+        Symbol::intern("binary_to_integer"),
+        2,
+        Box::new(binary_to_integer),
+    );
+    module.add_fun(Symbol::intern("float_to_list"), 1, Box::new(float_to_list));
+    module.add_fun(
+        Symbol::intern("float_to_binary"),
+        1,
+        Box::new(float_to_binary),
+    );
+    module.add_fun(Symbol::intern("list_to_float"), 1, Box::new(list_to_float));
+    module.add_fun(
+        Symbol::intern("binary_to_float"),
+        1,
+        Box::new(binary_to_float),
+    );
+    module.add_fun(
+        Symbol::intern("binary_to_list"),
+        1,
+        Box::new(binary_to_list),
+    );
+    module.add_fun(
+        Symbol::intern("binary_to_list"),
+        3,
+        Box::new(binary_to_list),
+    );
+    module.add_fun(
+        Symbol::intern("bitstring_to_list"),
+        1,
+        Box::new(bitstring_to_list),
+    );
+    module.add_fun(
+        Symbol::intern("iolist_to_binary"),
+        1,
+        Box::new(iolist_to_binary),
+    );
+    module.add_fun(
+        Symbol::intern("list_to_binary"),
+        1,
+        Box::new(list_to_binary),
+    );
+    module.add_fun(
+        Symbol::intern("list_to_bitstring"),
+        1,
+        Box::new(list_to_bitstring),
+    );
+    module.add_fun(Symbol::intern("iolist_size"), 1, Box::new(iolist_size));
+    module.add_fun(Symbol::intern("binary_part"), 2, Box::new(binary_part));
+    module.add_fun(Symbol::intern("binary_part"), 3, Box::new(binary_part));
+    module.add_fun(Symbol::intern("split_binary"), 2, Box::new(split_binary));
+    module.add_fun(Symbol::intern("error"), 1, Box::new(error));
+    module.add_fun(Symbol::intern("error"), 2, Box::new(error));
+    module.add_fun(Symbol::intern("throw"), 1, Box::new(throw));
+    module.add_fun(Symbol::intern("exit"), 1, Box::new(exit));
+    module.add_fun(Symbol::intern("apply"), 2, Box::new(apply));
+    module.add_fun(Symbol::intern("apply"), 3, Box::new(apply));
+    module.add_fun(Symbol::intern("phash2"), 1, Box::new(phash2));
+    module.add_fun(Symbol::intern("phash2"), 2, Box::new(phash2));
+    module.add_fun(Symbol::intern("make_ref"), 0, Box::new(make_ref));
+    module.add_fun(Symbol::intern("node"), 0, Box::new(node));
+    module.add_fun(Symbol::intern("is_alive"), 0, Box::new(is_alive));
+    module.add_fun(
+        Symbol::intern("garbage_collect"),
+        0,
+        Box::new(garbage_collect),
+    );
     //module.add_fun(Symbol::intern("spawn"), 1, Box::new(spawn_1));
     //module.add_fun(Symbol::intern("monitor"), 2, Box::new(monitor_2));
     //module.add_fun(Symbol::intern("process_flag"), 2, Box::new(process_flag));
@@ -0,0 +1,192 @@
+//! The core of the `ets` module. Tables are stored in the VM, see
+//! `EtsTables`. Access rights and concurrency options are accepted, but have
+//! no effect.
+
+use std::rc::Rc;
+
+use libeir_intern::Symbol;
+
+use crate::ets::{match_pattern, run_match_spec, MatchBindings, Table, TableKind};
+use crate::module::{NativeModule, NativeReturn};
+use crate::process::ProcessContext;
+use crate::term::{Reference, Term};
+use crate::vm::VMState;
+
+use super::util::{badarg, proper_list};
+
+fn list(elems: &[Rc<Term>]) -> Rc<Term> {
+    Term::slice_to_list(elems, Term::Nil.into())
+}
+
+fn true_term() -> NativeReturn {
+    NativeReturn::Return {
+        term: Term::new_bool(true).into(),
+    }
+}
+
+/// Resolves a table argument, raising `badarg` if there is no such table.
+fn table_arg(vm: &VMState, tab: &Term) -> Result<Reference, NativeReturn> {
+    vm.ets.borrow().resolve(tab).ok_or_else(badarg)
+}
+
+fn new(vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let name = match args[0].as_atom() {
+        Some(name) => name,
+        None => return badarg(),
+    };
+
+    let mut kind = TableKind::Set;
+    let mut named = false;
+    let mut keypos = 1;
+    for option in native_try!(proper_list(&args[1])) {
+        if let Some(atom) = option.as_atom() {
+            match atom.as_str().get() {
+                "set" => kind = TableKind::Set,
+                "ordered_set" => kind = TableKind::OrderedSet,
+                "bag" => kind = TableKind::Bag,
+                "duplicate_bag" => kind = TableKind::DuplicateBag,
+                "named_table" => named = true,
+                "public" | "protected" | "private" | "compressed" => (),
+                _ => return badarg(),
+            }
+            continue;
+        }
+        match option.as_tuple() {
+            Some([key, value]) if key.as_atom().map(|k| k == "keypos").unwrap_or(false) => {
+                keypos = match value.as_usize() {
+                    Some(pos) if pos >= 1 => pos,
+                    _ => return badarg(),
+                };
+            }
+            Some([key, _]) if key.as_atom().is_some() => {
+                match key.as_atom().unwrap().as_str().get() {
+                    "read_concurrency"
+                    | "write_concurrency"
+                    | "decentralized_counters"
+                    | "heir" => (),
+                    _ => return badarg(),
+                }
+            }
+            Some([key, _, _]) if key.as_atom().map(|k| k == "heir").unwrap_or(false) => (),
+            _ => return badarg(),
+        }
+    }
+
+    let id = vm.ref_gen.borrow_mut().next();
+    let table = Table::new(name, kind, keypos - 1);
+    if !vm.ets.borrow_mut().create(id, table, named) {
+        return badarg();
+    }
+
+    let term = if named {
+        args[0].clone()
+    } else {
+        Term::Reference(id).into()
+    };
+    NativeReturn::Return { term }
+}
+
+fn insert(vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let id = native_try!(table_arg(vm, &args[0]));
+    let objects = match &*args[1] {
+        Term::Tuple(_) => vec![args[1].clone()],
+        _ => native_try!(proper_list(&args[1])),
+    };
+
+    let mut tables = vm.ets.borrow_mut();
+    let table = tables.get_mut(id).unwrap();
+    if !objects.iter().all(|o| table.accepts(o)) {
+        return badarg();
+    }
+    for object in objects {
+        table.insert(object);
+    }
+    true_term()
+}
+
+fn lookup(vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let id = native_try!(table_arg(vm, &args[0]));
+    let tables = vm.ets.borrow();
+    let objects = tables.get(id).unwrap().lookup(&args[1]);
+    NativeReturn::Return {
+        term: list(&objects),
+    }
+}
+
+fn delete_1(vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let id = native_try!(table_arg(vm, &args[0]));
+    vm.ets.borrow_mut().delete(id);
+    true_term()
+}
+
+fn delete_2(vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let id = native_try!(table_arg(vm, &args[0]));
+    vm.ets.borrow_mut().get_mut(id).unwrap().delete(&args[1]);
+    true_term()
+}
+
+/// Returns the bindings of the match variables for every matching object,
+/// ordered by variable number.
+fn match_2(vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let id = native_try!(table_arg(vm, &args[0]));
+    let tables = vm.ets.borrow();
+
+    let mut results = Vec::new();
+    for object in tables.get(id).unwrap().objects() {
+        let mut binds = MatchBindings::new();
+        if match_pattern(&args[1], object, &mut binds) {
+            let values: Vec<_> = binds.values().cloned().collect();
+            results.push(list(&values));
+        }
+    }
+    NativeReturn::Return {
+        term: list(&results),
+    }
+}
+
+fn select(vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let id = native_try!(table_arg(vm, &args[0]));
+    let spec = native_try!(proper_list(&args[1]));
+    let tables = vm.ets.borrow();
+
+    let mut results = Vec::new();
+    for object in tables.get(id).unwrap().objects() {
+        match run_match_spec(&spec, object) {
+            Ok(Some(result)) => results.push(result),
+            Ok(None) => (),
+            Err(()) => return badarg(),
+        }
+    }
+    NativeReturn::Return {
+        term: list(&results),
+    }
+}
+
+fn tab2list(vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let id = native_try!(table_arg(vm, &args[0]));
+    let tables = vm.ets.borrow();
+    NativeReturn::Return {
+        term: list(tables.get(id).unwrap().objects()),
+    }
+}
+
+pub fn make_ets() -> NativeModule {
+    let mut module = NativeModule::new(Symbol::intern("ets"));
+    module.add_fun(Symbol::intern("new"), 2, Box::new(new));
+    module.add_fun(Symbol::intern("insert"), 2, Box::new(insert));
+    module.add_fun(Symbol::intern("lookup"), 2, Box::new(lookup));
+    module.add_fun(Symbol::intern("delete"), 1, Box::new(delete_1));
+    module.add_fun(Symbol::intern("delete"), 2, Box::new(delete_2));
+    module.add_fun(Symbol::intern("match"), 2, Box::new(match_2));
+    module.add_fun(Symbol::intern("select"), 2, Box::new(select));
+    module.add_fun(Symbol::intern("tab2list"), 1, Box::new(tab2list));
+    module
+}
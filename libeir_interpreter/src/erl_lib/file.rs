@@ -1,17 +1,40 @@
-use ::module::NativeModule;
-use ::vm::VMState;
-use ::term::Term;
-use ::process::{ CallReturn, ProcessContext };
+use std::io::ErrorKind;
+use std::rc::Rc;
 
-fn delete(_vm: &VMState, _proc: &mut ProcessContext, args: &[Term]) -> CallReturn {
+use libeir_intern::Symbol;
+
+use crate::module::{NativeModule, NativeReturn};
+use crate::process::ProcessContext;
+use crate::term::Term;
+use crate::vm::VMState;
+
+use super::util::{badarg, chardata_to_string};
+
+fn delete(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
     assert!(args.len() == 1);
-    // TODO
-    let n = vec![Term::new_atom("error"), Term::new_atom("enoent")];
-    CallReturn::Return { term: Term::Tuple(n) }
+    let path = match chardata_to_string(&args[0], false) {
+        Some(path) => path,
+        None => return badarg(),
+    };
+    let term = match std::fs::remove_file(&path) {
+        Ok(()) => Term::new_atom("ok"),
+        Err(err) => {
+            let reason = match err.kind() {
+                ErrorKind::NotFound => "enoent",
+                ErrorKind::PermissionDenied => "eacces",
+                _ => "eio",
+            };
+            Term::Tuple(vec![
+                Term::new_atom("error").into(),
+                Term::new_atom(reason).into(),
+            ])
+        }
+    };
+    NativeReturn::Return { term: term.into() }
 }
 
-pub fn make_time() -> NativeModule {
-    let mut module = NativeModule::new("file".to_string());
-    module.add_fun("delete".to_string(), 1, Box::new(delete));
+pub fn make_file() -> NativeModule {
+    let mut module = NativeModule::new(Symbol::intern("file"));
+    module.add_fun(Symbol::intern("delete"), 1, Box::new(delete));
     module
 }
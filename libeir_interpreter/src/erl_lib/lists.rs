@@ -1,37 +1,61 @@
+use std::cmp::Ordering;
 use std::rc::Rc;
 
 use crate::module::{NativeModule, NativeReturn};
 use crate::process::ProcessContext;
-use crate::term::{ErlEq, Term};
+use crate::term::{ErlEq, ErlExactEq, ErlOrd, Term};
 use crate::vm::VMState;
 
 use libeir_intern::Symbol;
+use libeir_util_number::bigint_to_double;
+
+use num_bigint::BigInt;
+use num_traits::{Signed, ToPrimitive, Zero};
+
+use super::util::{badarg, call_fun, call_pred, proper_list};
+
+fn list(elems: &[Rc<Term>]) -> Rc<Term> {
+    Term::slice_to_list(elems, Term::Nil.into())
+}
+
+fn tuple(elems: Vec<Rc<Term>>) -> Rc<Term> {
+    Term::Tuple(elems).into()
+}
+
+/// Reads a 1 based index argument.
+fn index(term: &Term) -> Result<usize, NativeReturn> {
+    match term.as_integer().and_then(|i| i.to_usize()) {
+        Some(n) if n >= 1 => Ok(n),
+        _ => Err(badarg()),
+    }
+}
+
+/// Reads a non-negative length argument.
+fn length(term: &Term) -> Result<usize, NativeReturn> {
+    term.as_integer()
+        .and_then(|i| i.to_usize())
+        .ok_or_else(badarg)
+}
+
+/// Returns the element at the 1 based index `n` of `term` if it is a tuple
+/// that is large enough.
+fn tuple_key(term: &Term, n: usize) -> Option<&Rc<Term>> {
+    term.as_tuple().and_then(|elems| elems.get(n - 1))
+}
+
+fn member(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let elems = native_try!(proper_list(&args[1]));
+    let res = elems.iter().any(|elem| elem.erl_exact_eq(&*args[0]));
+    NativeReturn::Return {
+        term: Term::new_bool(res).into(),
+    }
+}
 
-//fn member_list(item: &Term, list: &Term) -> NativeReturn {
-//    if let Term::Nil = list {
-//        NativeReturn::Return { term: Term::new_bool(false).into() }
-//    } else if let Term::List(ref head, ref tail) = list {
-//        for l_item in head {
-//            if item.erl_exact_eq(l_item) {
-//                return NativeReturn::Return { term: Term::new_bool(true).into() };
-//            }
-//        }
-//        member_list(item, tail)
-//    } else {
-//        NativeReturn::Throw
-//    }
-//}
-//
-//fn member(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
-//    assert!(args.len() == 2);
-//    member_list(&args[0], &args[1])
-//}
-//
 fn reverse_2(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
     assert!(args.len() == 2);
 
-    let (mut head, tail) = Term::as_inproper_list(&args[0]);
-    assert!(tail.erl_eq(&Term::Nil));
+    let mut head = native_try!(proper_list(&args[0]));
 
     head.reverse();
     NativeReturn::Return {
@@ -44,37 +68,717 @@ fn reverse_1(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> Nati
     reverse_2(vm, proc, &[args[0].clone(), Term::Nil.into()])
 }
 
-//fn keyfind(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
-//    assert!(args.len() == 3);
-//    let key = &*args[0];
-//    let pos = if let Some(int) = args[1].as_i64() {
-//        int
-//    } else {
-//        return NativeReturn::Throw;
-//    };
-//    let list_term = &*args[2];
-//    let (list, list_tail) = list_term.as_inproper_list();
-//    for term in list.iter() {
-//        if let Term::Tuple(values) = &**term {
-//            if let Some(val_term) = values.get(pos as usize) {
-//                if val_term.erl_eq(key) {
-//                    return NativeReturn::Return { term: term.clone() };
-//                }
-//            }
-//        }
-//    }
-//    if let Term::Nil = list_tail {
-//        NativeReturn::Return { term: Term::new_bool(false).into() }
-//    } else {
-//        NativeReturn::Throw
-//    }
-//}
+fn keyfind(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 3);
+    let n = native_try!(index(&args[1]));
+    let elems = native_try!(proper_list(&args[2]));
+
+    let found = elems.iter().find(|elem| {
+        tuple_key(elem, n)
+            .map(|key| key.erl_eq(&*args[0]))
+            .unwrap_or(false)
+    });
+    NativeReturn::Return {
+        term: found
+            .cloned()
+            .unwrap_or_else(|| Term::new_bool(false).into()),
+    }
+}
+
+fn keymember(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    match keyfind(vm, proc, args) {
+        NativeReturn::Return { term } => NativeReturn::Return {
+            term: Term::new_bool(term.as_boolean() != Some(false)).into(),
+        },
+        other => other,
+    }
+}
+
+fn keysearch(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    match keyfind(vm, proc, args) {
+        NativeReturn::Return { term } if term.as_boolean() != Some(false) => NativeReturn::Return {
+            term: tuple(vec![Term::new_atom("value").into(), term]),
+        },
+        other => other,
+    }
+}
+
+/// Finds the position of the first tuple with the given key.
+fn key_position(key: &Term, n: usize, elems: &[Rc<Term>]) -> Option<usize> {
+    elems
+        .iter()
+        .position(|elem| tuple_key(elem, n).map(|k| k.erl_eq(key)).unwrap_or(false))
+}
+
+fn keydelete(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 3);
+    let n = native_try!(index(&args[1]));
+    let mut elems = native_try!(proper_list(&args[2]));
+    if let Some(pos) = key_position(&args[0], n, &elems) {
+        elems.remove(pos);
+    }
+    NativeReturn::Return { term: list(&elems) }
+}
+
+fn keyreplace(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 4);
+    let n = native_try!(index(&args[1]));
+    let mut elems = native_try!(proper_list(&args[2]));
+    if let Some(pos) = key_position(&args[0], n, &elems) {
+        elems[pos] = args[3].clone();
+    }
+    NativeReturn::Return { term: list(&elems) }
+}
+
+fn keystore(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 4);
+    let n = native_try!(index(&args[1]));
+    let mut elems = native_try!(proper_list(&args[2]));
+    match key_position(&args[0], n, &elems) {
+        Some(pos) => elems[pos] = args[3].clone(),
+        None => elems.push(args[3].clone()),
+    }
+    NativeReturn::Return { term: list(&elems) }
+}
+
+fn keytake(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 3);
+    let n = native_try!(index(&args[1]));
+    let mut elems = native_try!(proper_list(&args[2]));
+    match key_position(&args[0], n, &elems) {
+        Some(pos) => {
+            let found = elems.remove(pos);
+            NativeReturn::Return {
+                term: tuple(vec![Term::new_atom("value").into(), found, list(&elems)]),
+            }
+        }
+        None => NativeReturn::Return {
+            term: Term::new_bool(false).into(),
+        },
+    }
+}
+
+fn keysort(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let n = native_try!(index(&args[0]));
+    let mut elems = native_try!(proper_list(&args[1]));
+    if elems.iter().any(|elem| tuple_key(elem, n).is_none()) {
+        return badarg();
+    }
+    elems.sort_by(|a, b| tuple_key(a, n).unwrap().erl_ord(tuple_key(b, n).unwrap()));
+    NativeReturn::Return { term: list(&elems) }
+}
+
+fn append_1(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let lists = native_try!(proper_list(&args[0]));
+    let mut out = Vec::new();
+    for (idx, elem) in lists.iter().enumerate() {
+        // The last list is used as the tail, and may be improper
+        if idx == lists.len() - 1 {
+            return NativeReturn::Return {
+                term: Term::slice_to_list(&out, elem.clone()),
+            };
+        }
+        out.extend(native_try!(proper_list(elem)));
+    }
+    NativeReturn::Return {
+        term: Term::Nil.into(),
+    }
+}
+
+fn append_2(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let head = native_try!(proper_list(&args[0]));
+    NativeReturn::Return {
+        term: Term::slice_to_list(&head, args[1].clone()),
+    }
+}
+
+fn flatten_into(term: &Rc<Term>, out: &mut Vec<Rc<Term>>) -> Result<(), NativeReturn> {
+    for elem in proper_list(term)? {
+        match &*elem {
+            Term::ListCell(_, _) | Term::Nil => flatten_into(&elem, out)?,
+            _ => out.push(elem),
+        }
+    }
+    Ok(())
+}
+
+fn flatten(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1 || args.len() == 2);
+    let mut out = Vec::new();
+    native_try!(flatten_into(&args[0], &mut out));
+    let tail = args.get(1).cloned().unwrap_or_else(|| Term::Nil.into());
+    NativeReturn::Return {
+        term: Term::slice_to_list(&out, tail),
+    }
+}
+
+fn nth(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let n = native_try!(index(&args[0]));
+    let elems = native_try!(proper_list(&args[1]));
+    match elems.get(n - 1) {
+        Some(term) => NativeReturn::Return { term: term.clone() },
+        None => badarg(),
+    }
+}
+
+fn nthtail(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let n = native_try!(length(&args[0]));
+    let mut term = args[1].clone();
+    for _ in 0..n {
+        term = match &*term {
+            Term::ListCell(_, tail) => tail.clone(),
+            _ => return badarg(),
+        };
+    }
+    NativeReturn::Return { term }
+}
+
+fn last(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let elems = native_try!(proper_list(&args[0]));
+    match elems.last() {
+        Some(term) => NativeReturn::Return { term: term.clone() },
+        None => badarg(),
+    }
+}
+
+fn droplast(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let mut elems = native_try!(proper_list(&args[0]));
+    if elems.pop().is_none() {
+        return badarg();
+    }
+    NativeReturn::Return { term: list(&elems) }
+}
+
+fn sublist(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2 || args.len() == 3);
+    let elems = native_try!(proper_list(&args[0]));
+    let (start, len) = if args.len() == 3 {
+        let start = native_try!(index(&args[1]));
+        if start > elems.len() + 1 {
+            return badarg();
+        }
+        (start - 1, native_try!(length(&args[2])))
+    } else {
+        (0, native_try!(length(&args[1])))
+    };
+    let end = std::cmp::min(start + len, elems.len());
+    NativeReturn::Return {
+        term: list(&elems[start..end]),
+    }
+}
+
+fn seq(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2 || args.len() == 3);
+    let from = args[0].as_integer();
+    let to = args[1].as_integer();
+    let incr = match args.get(2) {
+        Some(incr) => incr.as_integer().cloned(),
+        None => Some(BigInt::from(1)),
+    };
+    let (from, to, incr) = match (from, to, incr) {
+        (Some(from), Some(to), Some(incr)) => (from.clone(), to.clone(), incr),
+        _ => return badarg(),
+    };
+
+    let mut out = Vec::new();
+    if incr.is_zero() {
+        if from != to {
+            return badarg();
+        }
+        out.push(Term::Integer(from).into());
+    } else if incr.is_positive() {
+        if to < &from - &incr {
+            return badarg();
+        }
+        let mut curr = from;
+        while curr <= to {
+            out.push(Term::Integer(curr.clone()).into());
+            curr += &incr;
+        }
+    } else {
+        if to > &from - &incr {
+            return badarg();
+        }
+        let mut curr = from;
+        while curr >= to {
+            out.push(Term::Integer(curr.clone()).into());
+            curr += &incr;
+        }
+    }
+    NativeReturn::Return { term: list(&out) }
+}
+
+fn sum(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let elems = native_try!(proper_list(&args[0]));
+
+    let mut int_acc = BigInt::zero();
+    let mut float_acc: Option<f64> = None;
+    for elem in elems.iter() {
+        match &**elem {
+            Term::Integer(int) => int_acc += int,
+            Term::Float(flt) => *float_acc.get_or_insert(0.0) += flt.0,
+            _ => return badarg(),
+        }
+    }
+
+    let term = match float_acc {
+        Some(flt) => Term::Float((flt + bigint_to_double(&int_acc)).into()),
+        None => Term::Integer(int_acc),
+    };
+    NativeReturn::Return { term: term.into() }
+}
+
+/// Returns the extreme element of a list under the given ordering.
+fn extreme(args: &[Rc<Term>], keep: Ordering) -> NativeReturn {
+    assert!(args.len() == 1);
+    let elems = native_try!(proper_list(&args[0]));
+    let mut iter = elems.iter();
+    let mut acc = match iter.next() {
+        Some(first) => first,
+        None => return badarg(),
+    };
+    for elem in iter {
+        if elem.erl_ord(acc) == keep {
+            acc = elem;
+        }
+    }
+    NativeReturn::Return { term: acc.clone() }
+}
+
+fn max(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    extreme(args, Ordering::Greater)
+}
+
+fn min(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    extreme(args, Ordering::Less)
+}
+
+/// Stable merge sort with a fallible `le` comparator.
+fn merge_sort<F>(mut elems: Vec<Rc<Term>>, le: &mut F) -> Result<Vec<Rc<Term>>, NativeReturn>
+where
+    F: FnMut(&Rc<Term>, &Rc<Term>) -> Result<bool, NativeReturn>,
+{
+    if elems.len() <= 1 {
+        return Ok(elems);
+    }
+    let right = elems.split_off(elems.len() / 2);
+    let left = merge_sort(elems, le)?;
+    let right = merge_sort(right, le)?;
+
+    let mut out = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    loop {
+        let take_left = match (left.peek(), right.peek()) {
+            (Some(l), Some(r)) => le(l, r)?,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => break,
+        };
+        if take_left {
+            out.push(left.next().unwrap());
+        } else {
+            out.push(right.next().unwrap());
+        }
+    }
+    Ok(out)
+}
+
+fn sort(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1 || args.len() == 2);
+    if args.len() == 1 {
+        let mut elems = native_try!(proper_list(&args[0]));
+        elems.sort_by(|a, b| a.erl_ord(b));
+        NativeReturn::Return { term: list(&elems) }
+    } else {
+        let elems = native_try!(proper_list(&args[1]));
+        let fun = &args[0];
+        let sorted = native_try!(merge_sort(elems, &mut |a, b| call_pred(
+            vm,
+            proc,
+            fun,
+            &[a.clone(), b.clone()]
+        )));
+        NativeReturn::Return {
+            term: list(&sorted),
+        }
+    }
+}
+
+fn usort(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let mut elems = native_try!(proper_list(&args[0]));
+    elems.sort_by(|a, b| a.erl_ord(b));
+    elems.dedup_by(|a, b| a.erl_eq(&**b));
+    NativeReturn::Return { term: list(&elems) }
+}
+
+fn zip(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let left = native_try!(proper_list(&args[0]));
+    let right = native_try!(proper_list(&args[1]));
+    if left.len() != right.len() {
+        return badarg();
+    }
+    let out: Vec<_> = left
+        .into_iter()
+        .zip(right.into_iter())
+        .map(|(l, r)| tuple(vec![l, r]))
+        .collect();
+    NativeReturn::Return { term: list(&out) }
+}
+
+fn unzip(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let elems = native_try!(proper_list(&args[0]));
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+    for elem in elems.iter() {
+        match elem.as_tuple() {
+            Some([l, r]) => {
+                left.push(l.clone());
+                right.push(r.clone());
+            }
+            _ => return badarg(),
+        }
+    }
+    NativeReturn::Return {
+        term: tuple(vec![list(&left), list(&right)]),
+    }
+}
+
+fn duplicate(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let n = native_try!(length(&args[0]));
+    NativeReturn::Return {
+        term: list(&vec![args[1].clone(); n]),
+    }
+}
+
+fn delete(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let mut elems = native_try!(proper_list(&args[1]));
+    if let Some(pos) = elems.iter().position(|e| e.erl_exact_eq(&*args[0])) {
+        elems.remove(pos);
+    }
+    NativeReturn::Return { term: list(&elems) }
+}
+
+fn subtract(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let mut elems = native_try!(proper_list(&args[0]));
+    let remove = native_try!(proper_list(&args[1]));
+    for term in remove.iter() {
+        if let Some(pos) = elems.iter().position(|e| e.erl_exact_eq(&**term)) {
+            elems.remove(pos);
+        }
+    }
+    NativeReturn::Return { term: list(&elems) }
+}
+
+fn split(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let n = native_try!(length(&args[0]));
+    let elems = native_try!(proper_list(&args[1]));
+    if n > elems.len() {
+        return badarg();
+    }
+    NativeReturn::Return {
+        term: tuple(vec![list(&elems[..n]), list(&elems[n..])]),
+    }
+}
+
+fn prefix(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let pre = native_try!(proper_list(&args[0]));
+    let elems = native_try!(proper_list(&args[1]));
+    let res = pre.len() <= elems.len()
+        && pre
+            .iter()
+            .zip(elems.iter())
+            .all(|(a, b)| a.erl_exact_eq(&**b));
+    NativeReturn::Return {
+        term: Term::new_bool(res).into(),
+    }
+}
+
+fn suffix(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let suf = native_try!(proper_list(&args[0]));
+    let elems = native_try!(proper_list(&args[1]));
+    let res = suf.len() <= elems.len()
+        && suf
+            .iter()
+            .zip(elems[elems.len() - suf.len()..].iter())
+            .all(|(a, b)| a.erl_exact_eq(&**b));
+    NativeReturn::Return {
+        term: Term::new_bool(res).into(),
+    }
+}
+
+fn join(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let elems = native_try!(proper_list(&args[1]));
+    let mut out = Vec::new();
+    for (idx, elem) in elems.into_iter().enumerate() {
+        if idx != 0 {
+            out.push(args[0].clone());
+        }
+        out.push(elem);
+    }
+    NativeReturn::Return { term: list(&out) }
+}
+
+fn map(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let elems = native_try!(proper_list(&args[1]));
+    let mut out = Vec::with_capacity(elems.len());
+    for elem in elems {
+        out.push(native_try!(call_fun(vm, proc, &args[0], &[elem])));
+    }
+    NativeReturn::Return { term: list(&out) }
+}
+
+fn flatmap(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let elems = native_try!(proper_list(&args[1]));
+    let mut out = Vec::new();
+    for elem in elems {
+        let res = native_try!(call_fun(vm, proc, &args[0], &[elem]));
+        out.extend(native_try!(proper_list(&res)));
+    }
+    NativeReturn::Return { term: list(&out) }
+}
+
+fn foldl(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 3);
+    let elems = native_try!(proper_list(&args[2]));
+    let mut acc = args[1].clone();
+    for elem in elems {
+        acc = native_try!(call_fun(vm, proc, &args[0], &[elem, acc]));
+    }
+    NativeReturn::Return { term: acc }
+}
+
+fn foldr(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 3);
+    let elems = native_try!(proper_list(&args[2]));
+    let mut acc = args[1].clone();
+    for elem in elems.into_iter().rev() {
+        acc = native_try!(call_fun(vm, proc, &args[0], &[elem, acc]));
+    }
+    NativeReturn::Return { term: acc }
+}
+
+fn mapfoldl(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 3);
+    let elems = native_try!(proper_list(&args[2]));
+    let mut acc = args[1].clone();
+    let mut out = Vec::with_capacity(elems.len());
+    for elem in elems {
+        let res = native_try!(call_fun(vm, proc, &args[0], &[elem, acc]));
+        match res.as_tuple() {
+            Some([mapped, new_acc]) => {
+                out.push(mapped.clone());
+                acc = new_acc.clone();
+            }
+            _ => return badarg(),
+        }
+    }
+    NativeReturn::Return {
+        term: tuple(vec![list(&out), acc]),
+    }
+}
+
+fn foreach(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let elems = native_try!(proper_list(&args[1]));
+    for elem in elems {
+        native_try!(call_fun(vm, proc, &args[0], &[elem]));
+    }
+    NativeReturn::Return {
+        term: Term::new_atom("ok").into(),
+    }
+}
+
+fn filter(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let elems = native_try!(proper_list(&args[1]));
+    let mut out = Vec::new();
+    for elem in elems {
+        if native_try!(call_pred(vm, proc, &args[0], &[elem.clone()])) {
+            out.push(elem);
+        }
+    }
+    NativeReturn::Return { term: list(&out) }
+}
+
+fn filtermap(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let elems = native_try!(proper_list(&args[1]));
+    let mut out = Vec::new();
+    for elem in elems {
+        let res = native_try!(call_fun(vm, proc, &args[0], &[elem.clone()]));
+        match (res.as_boolean(), res.as_tuple()) {
+            (Some(true), _) => out.push(elem),
+            (Some(false), _) => (),
+            (None, Some([tag, value])) if tag.as_boolean() == Some(true) => out.push(value.clone()),
+            _ => return badarg(),
+        }
+    }
+    NativeReturn::Return { term: list(&out) }
+}
+
+fn partition(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let elems = native_try!(proper_list(&args[1]));
+    let mut satisfying = Vec::new();
+    let mut rest = Vec::new();
+    for elem in elems {
+        if native_try!(call_pred(vm, proc, &args[0], &[elem.clone()])) {
+            satisfying.push(elem);
+        } else {
+            rest.push(elem);
+        }
+    }
+    NativeReturn::Return {
+        term: tuple(vec![list(&satisfying), list(&rest)]),
+    }
+}
+
+fn any(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let elems = native_try!(proper_list(&args[1]));
+    for elem in elems {
+        if native_try!(call_pred(vm, proc, &args[0], &[elem])) {
+            return NativeReturn::Return {
+                term: Term::new_bool(true).into(),
+            };
+        }
+    }
+    NativeReturn::Return {
+        term: Term::new_bool(false).into(),
+    }
+}
+
+fn all(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let elems = native_try!(proper_list(&args[1]));
+    for elem in elems {
+        if !native_try!(call_pred(vm, proc, &args[0], &[elem])) {
+            return NativeReturn::Return {
+                term: Term::new_bool(false).into(),
+            };
+        }
+    }
+    NativeReturn::Return {
+        term: Term::new_bool(true).into(),
+    }
+}
+
+/// Returns the length of the prefix of `elems` that satisfies the predicate.
+fn prefix_len(
+    vm: &VMState,
+    proc: &mut ProcessContext,
+    fun: &Rc<Term>,
+    elems: &[Rc<Term>],
+) -> Result<usize, NativeReturn> {
+    for (idx, elem) in elems.iter().enumerate() {
+        if !call_pred(vm, proc, fun, &[elem.clone()])? {
+            return Ok(idx);
+        }
+    }
+    Ok(elems.len())
+}
+
+fn takewhile(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let elems = native_try!(proper_list(&args[1]));
+    let len = native_try!(prefix_len(vm, proc, &args[0], &elems));
+    NativeReturn::Return {
+        term: list(&elems[..len]),
+    }
+}
+
+fn dropwhile(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let elems = native_try!(proper_list(&args[1]));
+    let len = native_try!(prefix_len(vm, proc, &args[0], &elems));
+    NativeReturn::Return {
+        term: list(&elems[len..]),
+    }
+}
+
+fn splitwith(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let elems = native_try!(proper_list(&args[1]));
+    let len = native_try!(prefix_len(vm, proc, &args[0], &elems));
+    NativeReturn::Return {
+        term: tuple(vec![list(&elems[..len]), list(&elems[len..])]),
+    }
+}
 
 pub fn make_lists() -> NativeModule {
     let mut module = NativeModule::new(Symbol::intern("lists"));
-    //module.add_fun(Symbol::intern("member"), 2, Box::new(member));
+    module.add_fun(Symbol::intern("member"), 2, Box::new(member));
     module.add_fun(Symbol::intern("reverse"), 1, Box::new(reverse_1));
     module.add_fun(Symbol::intern("reverse"), 2, Box::new(reverse_2));
-    //module.add_fun(Symbol::intern("keyfind"), 3, Box::new(keyfind));
+    module.add_fun(Symbol::intern("keyfind"), 3, Box::new(keyfind));
+    module.add_fun(Symbol::intern("keymember"), 3, Box::new(keymember));
+    module.add_fun(Symbol::intern("keysearch"), 3, Box::new(keysearch));
+    module.add_fun(Symbol::intern("keydelete"), 3, Box::new(keydelete));
+    module.add_fun(Symbol::intern("keyreplace"), 4, Box::new(keyreplace));
+    module.add_fun(Symbol::intern("keystore"), 4, Box::new(keystore));
+    module.add_fun(Symbol::intern("keytake"), 3, Box::new(keytake));
+    module.add_fun(Symbol::intern("keysort"), 2, Box::new(keysort));
+    module.add_fun(Symbol::intern("append"), 1, Box::new(append_1));
+    module.add_fun(Symbol::intern("append"), 2, Box::new(append_2));
+    module.add_fun(Symbol::intern("flatten"), 1, Box::new(flatten));
+    module.add_fun(Symbol::intern("flatten"), 2, Box::new(flatten));
+    module.add_fun(Symbol::intern("nth"), 2, Box::new(nth));
+    module.add_fun(Symbol::intern("nthtail"), 2, Box::new(nthtail));
+    module.add_fun(Symbol::intern("last"), 1, Box::new(last));
+    module.add_fun(Symbol::intern("droplast"), 1, Box::new(droplast));
+    module.add_fun(Symbol::intern("sublist"), 2, Box::new(sublist));
+    module.add_fun(Symbol::intern("sublist"), 3, Box::new(sublist));
+    module.add_fun(Symbol::intern("seq"), 2, Box::new(seq));
+    module.add_fun(Symbol::intern("seq"), 3, Box::new(seq));
+    module.add_fun(Symbol::intern("sum"), 1, Box::new(sum));
+    module.add_fun(Symbol::intern("max"), 1, Box::new(max));
+    module.add_fun(Symbol::intern("min"), 1, Box::new(min));
+    module.add_fun(Symbol::intern("sort"), 1, Box::new(sort));
+    module.add_fun(Symbol::intern("sort"), 2, Box::new(sort));
+    module.add_fun(Symbol::intern("usort"), 1, Box::new(usort));
+    module.add_fun(Symbol::intern("zip"), 2, Box::new(zip));
+    module.add_fun(Symbol::intern("unzip"), 1, Box::new(unzip));
+    module.add_fun(Symbol::intern("duplicate"), 2, Box::new(duplicate));
+    module.add_fun(Symbol::intern("delete"), 2, Box::new(delete));
+    module.add_fun(Symbol::intern("subtract"), 2, Box::new(subtract));
+    module.add_fun(Symbol::intern("split"), 2, Box::new(split));
+    module.add_fun(Symbol::intern("prefix"), 2, Box::new(prefix));
+    module.add_fun(Symbol::intern("suffix"), 2, Box::new(suffix));
+    module.add_fun(Symbol::intern("join"), 2, Box::new(join));
+    module.add_fun(Symbol::intern("map"), 2, Box::new(map));
+    module.add_fun(Symbol::intern("flatmap"), 2, Box::new(flatmap));
+    module.add_fun(Symbol::intern("foldl"), 3, Box::new(foldl));
+    module.add_fun(Symbol::intern("foldr"), 3, Box::new(foldr));
+    module.add_fun(Symbol::intern("mapfoldl"), 3, Box::new(mapfoldl));
+    module.add_fun(Symbol::intern("foreach"), 2, Box::new(foreach));
+    module.add_fun(Symbol::intern("filter"), 2, Box::new(filter));
+    module.add_fun(Symbol::intern("filtermap"), 2, Box::new(filtermap));
+    module.add_fun(Symbol::intern("partition"), 2, Box::new(partition));
+    module.add_fun(Symbol::intern("any"), 2, Box::new(any));
+    module.add_fun(Symbol::intern("all"), 2, Box::new(all));
+    module.add_fun(Symbol::intern("takewhile"), 2, Box::new(takewhile));
+    module.add_fun(Symbol::intern("dropwhile"), 2, Box::new(dropwhile));
+    module.add_fun(Symbol::intern("splitwith"), 2, Box::new(splitwith));
     module
 }
@@ -4,42 +4,256 @@ use crate::module::{NativeModule, NativeReturn};
 use crate::process::ProcessContext;
 use crate::vm::VMState;
 
-use crate::term::{ListIteratorItem, MapTerm, Term};
+use crate::term::{MapTerm, Term};
 
 use std::rc::Rc;
 
-fn new_0(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
-    assert!(args.len() == 0);
+use super::util::{badarg, call_fun, call_pred, error_tuple, proper_list};
+
+/// Reads a map argument, raising `{badmap, Term}` if it is not one.
+fn map_arg(term: &Rc<Term>) -> Result<&MapTerm, NativeReturn> {
+    term.as_map()
+        .ok_or_else(|| error_tuple("badmap", term.clone()))
+}
+
+fn map_term(map: MapTerm) -> NativeReturn {
     NativeReturn::Return {
-        term: Term::Map(MapTerm::new()).into(),
+        term: Term::Map(map).into(),
     }
 }
 
+fn list(elems: &[Rc<Term>]) -> Rc<Term> {
+    Term::slice_to_list(elems, Term::Nil.into())
+}
+
+fn new_0(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 0);
+    map_term(MapTerm::new())
+}
+
 fn from_list_1(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
     assert!(args.len() == 1);
     let mut map = MapTerm::new();
 
-    for elem in Term::list_iter(&args[0]) {
-        match elem {
-            ListIteratorItem::Elem(tup_term) => {
-                let tup = tup_term.as_tuple().unwrap();
-                assert!(tup.len() == 2);
-                map.insert(tup[0].clone(), tup[1].clone());
-            }
-            ListIteratorItem::Tail(term) => {
-                assert!(term.is_nil());
+    for elem in native_try!(proper_list(&args[0])) {
+        match elem.as_tuple() {
+            Some([key, value]) => {
+                map.insert(key.clone(), value.clone());
             }
+            _ => return badarg(),
         }
     }
 
+    map_term(map)
+}
+
+fn to_list_1(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let map = native_try!(map_arg(&args[0]));
+    let elems: Vec<_> = map
+        .iter()
+        .map(|(k, v)| Term::Tuple(vec![k.clone(), v.clone()]).into())
+        .collect();
+    NativeReturn::Return { term: list(&elems) }
+}
+
+fn keys_1(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let map = native_try!(map_arg(&args[0]));
+    let elems: Vec<_> = map.iter().map(|(k, _)| k.clone()).collect();
+    NativeReturn::Return { term: list(&elems) }
+}
+
+fn values_1(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let map = native_try!(map_arg(&args[0]));
+    let elems: Vec<_> = map.iter().map(|(_, v)| v.clone()).collect();
+    NativeReturn::Return { term: list(&elems) }
+}
+
+fn size_1(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let map = native_try!(map_arg(&args[0]));
     NativeReturn::Return {
-        term: Term::Map(map).into(),
+        term: Term::new_usize(map.len()).into(),
+    }
+}
+
+fn get_2(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let map = native_try!(map_arg(&args[1]));
+    match map.get(&args[0]) {
+        Some(term) => NativeReturn::Return { term },
+        None => error_tuple("badkey", args[0].clone()),
+    }
+}
+
+fn get_3(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 3);
+    let map = native_try!(map_arg(&args[1]));
+    NativeReturn::Return {
+        term: map.get(&args[0]).unwrap_or_else(|| args[2].clone()),
+    }
+}
+
+fn find_2(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let map = native_try!(map_arg(&args[1]));
+    let term = match map.get(&args[0]) {
+        Some(value) => Term::Tuple(vec![Term::new_atom("ok").into(), value]).into(),
+        None => Term::new_atom("error").into(),
+    };
+    NativeReturn::Return { term }
+}
+
+fn is_key_2(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let map = native_try!(map_arg(&args[1]));
+    NativeReturn::Return {
+        term: Term::new_bool(map.get(&args[0]).is_some()).into(),
+    }
+}
+
+fn put_3(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 3);
+    let mut map = native_try!(map_arg(&args[2])).clone();
+    map.insert(args[0].clone(), args[1].clone());
+    map_term(map)
+}
+
+fn update_3(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 3);
+    let mut map = native_try!(map_arg(&args[2])).clone();
+    if map.get(&args[0]).is_none() {
+        return error_tuple("badkey", args[0].clone());
+    }
+    map.insert(args[0].clone(), args[1].clone());
+    map_term(map)
+}
+
+fn remove_2(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let mut map = native_try!(map_arg(&args[1])).clone();
+    map.remove(&args[0]);
+    map_term(map)
+}
+
+fn take_2(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let mut map = native_try!(map_arg(&args[1])).clone();
+    let term = match map.remove(&args[0]) {
+        Some(value) => Term::Tuple(vec![value, Term::Map(map).into()]).into(),
+        None => Term::new_atom("error").into(),
+    };
+    NativeReturn::Return { term }
+}
+
+fn merge_2(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let mut map = native_try!(map_arg(&args[0])).clone();
+    let other = native_try!(map_arg(&args[1]));
+    for (key, value) in other.iter() {
+        map.insert(key.clone(), value.clone());
+    }
+    map_term(map)
+}
+
+fn with_2(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let keys = native_try!(proper_list(&args[0]));
+    let map = native_try!(map_arg(&args[1]));
+    let mut out = MapTerm::new();
+    for key in keys {
+        if let Some(value) = map.get(&key) {
+            out.insert(key, value);
+        }
+    }
+    map_term(out)
+}
+
+fn without_2(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let keys = native_try!(proper_list(&args[0]));
+    let mut map = native_try!(map_arg(&args[1])).clone();
+    for key in keys.iter() {
+        map.remove(key);
+    }
+    map_term(map)
+}
+
+fn update_with(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 3 || args.len() == 4);
+    let mut map = native_try!(map_arg(&args[2])).clone();
+    let value = match (map.get(&args[0]), args.get(3)) {
+        (Some(old), _) => native_try!(call_fun(vm, proc, &args[1], &[old])),
+        (None, Some(init)) => init.clone(),
+        (None, None) => return error_tuple("badkey", args[0].clone()),
+    };
+    map.insert(args[0].clone(), value);
+    map_term(map)
+}
+
+fn fold_3(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 3);
+    let map = native_try!(map_arg(&args[2]));
+    let mut acc = args[1].clone();
+    for (key, value) in map.iter() {
+        acc = native_try!(call_fun(
+            vm,
+            proc,
+            &args[0],
+            &[key.clone(), value.clone(), acc]
+        ));
+    }
+    NativeReturn::Return { term: acc }
+}
+
+fn map_2(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let map = native_try!(map_arg(&args[1]));
+    let mut out = MapTerm::new();
+    for (key, value) in map.iter() {
+        let new = native_try!(call_fun(vm, proc, &args[0], &[key.clone(), value.clone()]));
+        out.insert(key.clone(), new);
+    }
+    map_term(out)
+}
+
+fn filter_2(vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let map = native_try!(map_arg(&args[1]));
+    let mut out = MapTerm::new();
+    for (key, value) in map.iter() {
+        if native_try!(call_pred(vm, proc, &args[0], &[key.clone(), value.clone()])) {
+            out.insert(key.clone(), value.clone());
+        }
     }
+    map_term(out)
 }
 
 pub fn make_maps() -> NativeModule {
     let mut module = NativeModule::new(Symbol::intern("maps"));
     module.add_fun(Symbol::intern("new"), 0, Box::new(new_0));
     module.add_fun(Symbol::intern("from_list"), 1, Box::new(from_list_1));
+    module.add_fun(Symbol::intern("to_list"), 1, Box::new(to_list_1));
+    module.add_fun(Symbol::intern("keys"), 1, Box::new(keys_1));
+    module.add_fun(Symbol::intern("values"), 1, Box::new(values_1));
+    module.add_fun(Symbol::intern("size"), 1, Box::new(size_1));
+    module.add_fun(Symbol::intern("get"), 2, Box::new(get_2));
+    module.add_fun(Symbol::intern("get"), 3, Box::new(get_3));
+    module.add_fun(Symbol::intern("find"), 2, Box::new(find_2));
+    module.add_fun(Symbol::intern("is_key"), 2, Box::new(is_key_2));
+    module.add_fun(Symbol::intern("put"), 3, Box::new(put_3));
+    module.add_fun(Symbol::intern("update"), 3, Box::new(update_3));
+    module.add_fun(Symbol::intern("remove"), 2, Box::new(remove_2));
+    module.add_fun(Symbol::intern("take"), 2, Box::new(take_2));
+    module.add_fun(Symbol::intern("merge"), 2, Box::new(merge_2));
+    module.add_fun(Symbol::intern("with"), 2, Box::new(with_2));
+    module.add_fun(Symbol::intern("without"), 2, Box::new(without_2));
+    module.add_fun(Symbol::intern("update_with"), 3, Box::new(update_with));
+    module.add_fun(Symbol::intern("update_with"), 4, Box::new(update_with));
+    module.add_fun(Symbol::intern("fold"), 3, Box::new(fold_3));
+    module.add_fun(Symbol::intern("map"), 2, Box::new(map_2));
+    module.add_fun(Symbol::intern("filter"), 2, Box::new(filter_2));
     module
 }
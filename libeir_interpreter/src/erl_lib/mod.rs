@@ -1,8 +1,11 @@
+#[macro_use]
+mod util;
+
 mod erlang;
 pub use self::erlang::make_erlang;
 
-//mod os;
-//pub use self::os::make_os;
+mod os;
+pub use self::os::make_os;
 
 mod lists;
 pub use self::lists::make_lists;
@@ -10,8 +13,23 @@ pub use self::lists::make_lists;
 mod math;
 pub use self::math::make_math;
 
-//mod file;
-//pub use self::file::make_time;
+mod file;
+pub use self::file::make_file;
 
 mod maps;
 pub use self::maps::make_maps;
+
+mod string;
+pub use self::string::make_string;
+
+mod binary;
+pub use self::binary::make_binary;
+
+mod unicode;
+pub use self::unicode::make_unicode;
+
+mod code;
+pub use self::code::make_code;
+
+mod ets;
+pub use self::ets::make_ets;
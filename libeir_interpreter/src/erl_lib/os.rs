@@ -1,27 +1,46 @@
-use ::vm::VMState;
-use ::module::NativeModule;
-use ::term::Term;
-use ::process::{ CallReturn, ProcessContext };
+use std::rc::Rc;
 
-fn getenv(_vm: &VMState, _proc: &mut ProcessContext, args: &[Term]) -> CallReturn {
-    if args.len() == 1 {
-        CallReturn::Return { term: Term::new_bool(false) }
-    } else {
-        CallReturn::Throw
-    }
+use libeir_intern::Symbol;
+
+use crate::module::{NativeModule, NativeReturn};
+use crate::process::ProcessContext;
+use crate::term::Term;
+use crate::vm::VMState;
+
+use super::util::{badarg, list_to_string, string_to_list};
+
+fn getenv(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let name = match list_to_string(&args[0]) {
+        Some(name) => name,
+        None => return badarg(),
+    };
+    let term = match std::env::var(&name) {
+        Ok(value) => string_to_list(&value),
+        Err(_) => Term::new_bool(false).into(),
+    };
+    NativeReturn::Return { term }
 }
 
-fn os_type(_vm: &VMState, _proc: &mut ProcessContext, args: &[Term]) -> CallReturn {
+fn os_type(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
     assert!(args.len() == 0);
-    // TODO
-    let family = Term::new_atom("unix");
-    let name = Term::new_atom("linux");
-    CallReturn::Return { term: Term::Tuple(vec![family, name]) }
+    let (family, name) = if cfg!(windows) {
+        ("win32", "nt")
+    } else {
+        ("unix", std::env::consts::OS)
+    };
+    NativeReturn::Return {
+        term: Term::Tuple(vec![
+            Term::new_atom(family).into(),
+            Term::new_atom(name).into(),
+        ])
+        .into(),
+    }
 }
 
 pub fn make_os() -> NativeModule {
-    let mut module = NativeModule::new("os".to_string());
-    module.add_fun("getenv".to_string(), 1, Box::new(getenv));
-    module.add_fun("type".to_string(), 0, Box::new(os_type));
+    let mut module = NativeModule::new(Symbol::intern("os"));
+    module.add_fun(Symbol::intern("getenv"), 1, Box::new(getenv));
+    module.add_fun(Symbol::intern("type"), 0, Box::new(os_type));
     module
 }
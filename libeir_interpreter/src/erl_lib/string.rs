@@ -0,0 +1,426 @@
+//! The `string` module. Strings are handled as sequences of codepoints, so
+//! functions that operate on grapheme clusters in OTP operate on single
+//! codepoints here.
+//!
+//! Results are binaries when the subject string is a binary, and flat lists
+//! of codepoints otherwise.
+
+use std::rc::Rc;
+
+use libeir_intern::Symbol;
+
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+
+use crate::module::{NativeModule, NativeReturn};
+use crate::process::ProcessContext;
+use crate::term::Term;
+use crate::vm::VMState;
+
+use super::util::{badarg, bytes_to_binary, chardata_to_string, proper_list};
+
+/// A string argument, along with whether results derived from it should be
+/// binaries.
+struct Chars {
+    chars: Vec<char>,
+    binary: bool,
+}
+
+impl Chars {
+    fn make(&self, chars: &[char]) -> Rc<Term> {
+        chars_to_term(chars, self.binary)
+    }
+}
+
+fn chars_to_term(chars: &[char], binary: bool) -> Rc<Term> {
+    if binary {
+        let string: String = chars.iter().collect();
+        bytes_to_binary(string.into_bytes())
+    } else {
+        let elems: Vec<_> = chars
+            .iter()
+            .map(|c| Term::new_usize(*c as usize).into())
+            .collect();
+        Term::slice_to_list(&elems, Term::Nil.into())
+    }
+}
+
+fn chars_arg(term: &Rc<Term>) -> Result<Chars, NativeReturn> {
+    let binary = match &**term {
+        Term::Binary(_) | Term::BinarySlice { .. } => true,
+        _ => false,
+    };
+    let string = chardata_to_string(term, false).ok_or_else(badarg)?;
+    Ok(Chars {
+        chars: string.chars().collect(),
+        binary,
+    })
+}
+
+/// Reads a flat list of codepoints, as taken by the separator arguments.
+fn char_list(term: &Rc<Term>) -> Result<Vec<char>, NativeReturn> {
+    proper_list(term)?
+        .iter()
+        .map(|c| {
+            c.as_integer()
+                .and_then(|i| i.to_u32())
+                .and_then(std::char::from_u32)
+                .ok_or_else(badarg)
+        })
+        .collect()
+}
+
+fn atom_arg(term: &Term, allowed: &[&str]) -> Result<Symbol, NativeReturn> {
+    match term.as_atom() {
+        Some(atom) if allowed.iter().any(|a| atom == *a) => Ok(atom),
+        _ => Err(badarg()),
+    }
+}
+
+fn find_seq(haystack: &[char], needle: &[char], from: usize) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(from);
+    }
+    (from..haystack.len()).find(|idx| haystack[*idx..].starts_with(needle))
+}
+
+fn rfind_seq(haystack: &[char], needle: &[char]) -> Option<usize> {
+    if needle.len() > haystack.len() {
+        return None;
+    }
+    (0..=haystack.len() - needle.len())
+        .rev()
+        .find(|idx| haystack[*idx..].starts_with(needle))
+}
+
+fn length(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let string = native_try!(chars_arg(&args[0]));
+    NativeReturn::Return {
+        term: Term::new_usize(string.chars.len()).into(),
+    }
+}
+
+fn is_empty(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let string = native_try!(chars_arg(&args[0]));
+    NativeReturn::Return {
+        term: Term::new_bool(string.chars.is_empty()).into(),
+    }
+}
+
+fn to_upper(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let string = native_try!(chars_arg(&args[0]));
+    let upper: Vec<char> = string.chars.iter().flat_map(|c| c.to_uppercase()).collect();
+    NativeReturn::Return {
+        term: string.make(&upper),
+    }
+}
+
+fn to_lower(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let string = native_try!(chars_arg(&args[0]));
+    let lower: Vec<char> = string.chars.iter().flat_map(|c| c.to_lowercase()).collect();
+    NativeReturn::Return {
+        term: string.make(&lower),
+    }
+}
+
+fn trim(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() >= 1 && args.len() <= 3);
+    let string = native_try!(chars_arg(&args[0]));
+    let dir = match args.get(1) {
+        Some(dir) => native_try!(atom_arg(dir, &["leading", "trailing", "both"])),
+        None => Symbol::intern("both"),
+    };
+    let separators = match args.get(2) {
+        Some(seps) => Some(native_try!(char_list(seps))),
+        None => None,
+    };
+    let is_sep = |c: &char| match &separators {
+        Some(seps) => seps.contains(c),
+        None => c.is_whitespace(),
+    };
+
+    let mut start = 0;
+    let mut end = string.chars.len();
+    if dir != "trailing" {
+        while start < end && is_sep(&string.chars[start]) {
+            start += 1;
+        }
+    }
+    if dir != "leading" {
+        while end > start && is_sep(&string.chars[end - 1]) {
+            end -= 1;
+        }
+    }
+    NativeReturn::Return {
+        term: string.make(&string.chars[start..end]),
+    }
+}
+
+fn split(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2 || args.len() == 3);
+    let string = native_try!(chars_arg(&args[0]));
+    let pattern = native_try!(chars_arg(&args[1]));
+    if pattern.chars.is_empty() {
+        return badarg();
+    }
+    let pattern = &pattern.chars;
+    let place = match args.get(2) {
+        Some(place) => native_try!(atom_arg(place, &["leading", "trailing", "all"])),
+        None => Symbol::intern("leading"),
+    };
+
+    let chars = &string.chars;
+    let mut parts = Vec::new();
+    if place == "all" {
+        let mut start = 0;
+        while let Some(idx) = find_seq(chars, pattern, start) {
+            parts.push(string.make(&chars[start..idx]));
+            start = idx + pattern.len();
+        }
+        parts.push(string.make(&chars[start..]));
+    } else {
+        let found = if place == "leading" {
+            find_seq(chars, pattern, 0)
+        } else {
+            rfind_seq(chars, pattern)
+        };
+        match found {
+            Some(idx) => {
+                parts.push(string.make(&chars[..idx]));
+                parts.push(string.make(&chars[idx + pattern.len()..]));
+            }
+            None => parts.push(string.make(chars)),
+        }
+    }
+    NativeReturn::Return {
+        term: Term::slice_to_list(&parts, Term::Nil.into()),
+    }
+}
+
+/// Splits on any of the separators, dropping empty parts.
+fn split_tokens(string: &Chars, separators: &[char]) -> Vec<Rc<Term>> {
+    string
+        .chars
+        .split(|c| separators.contains(c))
+        .filter(|part| !part.is_empty())
+        .map(|part| string.make(part))
+        .collect()
+}
+
+fn lexemes(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let string = native_try!(chars_arg(&args[0]));
+    let separators = native_try!(char_list(&args[1]));
+    NativeReturn::Return {
+        term: Term::slice_to_list(&split_tokens(&string, &separators), Term::Nil.into()),
+    }
+}
+
+fn tokens(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let mut string = native_try!(chars_arg(&args[0]));
+    string.binary = false;
+    let separators = native_try!(char_list(&args[1]));
+    NativeReturn::Return {
+        term: Term::slice_to_list(&split_tokens(&string, &separators), Term::Nil.into()),
+    }
+}
+
+fn find(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2 || args.len() == 3);
+    let string = native_try!(chars_arg(&args[0]));
+    let pattern = native_try!(chars_arg(&args[1]));
+    let dir = match args.get(2) {
+        Some(dir) => native_try!(atom_arg(dir, &["leading", "trailing"])),
+        None => Symbol::intern("leading"),
+    };
+    let found = if dir == "leading" {
+        find_seq(&string.chars, &pattern.chars, 0)
+    } else {
+        rfind_seq(&string.chars, &pattern.chars)
+    };
+    let term = match found {
+        Some(idx) => string.make(&string.chars[idx..]),
+        None => Term::new_atom("nomatch").into(),
+    };
+    NativeReturn::Return { term }
+}
+
+fn equal(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let left = native_try!(chars_arg(&args[0]));
+    let right = native_try!(chars_arg(&args[1]));
+    NativeReturn::Return {
+        term: Term::new_bool(left.chars == right.chars).into(),
+    }
+}
+
+fn reverse(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let mut string = native_try!(chars_arg(&args[0]));
+    string.binary = false;
+    let reversed: Vec<char> = string.chars.iter().rev().cloned().collect();
+    NativeReturn::Return {
+        term: string.make(&reversed),
+    }
+}
+
+fn slice(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2 || args.len() == 3);
+    let string = native_try!(chars_arg(&args[0]));
+    let start = match args[1].as_usize() {
+        Some(start) => std::cmp::min(start, string.chars.len()),
+        None => return badarg(),
+    };
+    let len = match args.get(2) {
+        None => string.chars.len(),
+        Some(len) if len.as_atom().map(|a| a == "infinity") == Some(true) => string.chars.len(),
+        Some(len) => match len.as_usize() {
+            Some(len) => len,
+            None => return badarg(),
+        },
+    };
+    let end = std::cmp::min(start.saturating_add(len), string.chars.len());
+    NativeReturn::Return {
+        term: string.make(&string.chars[start..end]),
+    }
+}
+
+fn prefix(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let string = native_try!(chars_arg(&args[0]));
+    let prefix = native_try!(chars_arg(&args[1]));
+    let term = if string.chars.starts_with(&prefix.chars) {
+        string.make(&string.chars[prefix.chars.len()..])
+    } else {
+        Term::new_atom("nomatch").into()
+    };
+    NativeReturn::Return { term }
+}
+
+fn join(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let strings = native_try!(proper_list(&args[0]));
+    let separator = native_try!(char_list(&args[1]));
+    let mut out = Vec::new();
+    for (idx, string) in strings.iter().enumerate() {
+        if idx != 0 {
+            out.extend(separator.iter().cloned());
+        }
+        out.extend(native_try!(char_list(string)));
+    }
+    NativeReturn::Return {
+        term: chars_to_term(&out, false),
+    }
+}
+
+fn concat(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let mut out = native_try!(char_list(&args[0]));
+    out.extend(native_try!(char_list(&args[1])));
+    NativeReturn::Return {
+        term: chars_to_term(&out, false),
+    }
+}
+
+fn substr(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2 || args.len() == 3);
+    let chars = native_try!(char_list(&args[0]));
+    let start = match args[1].as_usize() {
+        Some(start) if start >= 1 && start <= chars.len() + 1 => start - 1,
+        _ => return badarg(),
+    };
+    let end = match args.get(2) {
+        Some(len) => match len.as_usize() {
+            Some(len) if start + len <= chars.len() => start + len,
+            _ => return badarg(),
+        },
+        None => chars.len(),
+    };
+    NativeReturn::Return {
+        term: chars_to_term(&chars[start..end], false),
+    }
+}
+
+fn chr(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 2);
+    let chars = native_try!(char_list(&args[0]));
+    let chr = match args[1].as_integer().and_then(|i| i.to_u32()) {
+        Some(chr) => chr,
+        None => return badarg(),
+    };
+    let idx = chars
+        .iter()
+        .position(|c| *c as u32 == chr)
+        .map(|idx| idx + 1)
+        .unwrap_or(0);
+    NativeReturn::Return {
+        term: Term::new_usize(idx).into(),
+    }
+}
+
+fn to_integer(_vm: &VMState, _proc: &mut ProcessContext, args: &[Rc<Term>]) -> NativeReturn {
+    assert!(args.len() == 1);
+    let string = native_try!(chars_arg(&args[0]));
+    let chars = &string.chars;
+
+    let sign_len = match chars.first() {
+        Some('+') | Some('-') => 1,
+        _ => 0,
+    };
+    let digits = chars[sign_len..]
+        .iter()
+        .take_while(|c| c.is_ascii_digit())
+        .count();
+
+    let term = if digits == 0 {
+        Term::Tuple(vec![
+            Term::new_atom("error").into(),
+            Term::new_atom("no_integer").into(),
+        ])
+    } else {
+        let end = sign_len + digits;
+        let text: String = chars[sign_len..end].iter().collect();
+        let mut int: BigInt = text.parse().unwrap();
+        if chars[0] == '-' {
+            int = -int;
+        }
+        Term::Tuple(vec![Term::Integer(int).into(), string.make(&chars[end..])])
+    };
+    NativeReturn::Return { term: term.into() }
+}
+
+pub fn make_string() -> NativeModule {
+    let mut module = NativeModule::new(Symbol::intern("string"));
+    module.add_fun(Symbol::intern("length"), 1, Box::new(length));
+    module.add_fun(Symbol::intern("is_empty"), 1, Box::new(is_empty));
+    module.add_fun(Symbol::intern("to_upper"), 1, Box::new(to_upper));
+    module.add_fun(Symbol::intern("to_lower"), 1, Box::new(to_lower));
+    module.add_fun(Symbol::intern("uppercase"), 1, Box::new(to_upper));
+    module.add_fun(Symbol::intern("lowercase"), 1, Box::new(to_lower));
+    module.add_fun(Symbol::intern("trim"), 1, Box::new(trim));
+    module.add_fun(Symbol::intern("trim"), 2, Box::new(trim));
+    module.add_fun(Symbol::intern("trim"), 3, Box::new(trim));
+    module.add_fun(Symbol::intern("split"), 2, Box::new(split));
+    module.add_fun(Symbol::intern("split"), 3, Box::new(split));
+    module.add_fun(Symbol::intern("lexemes"), 2, Box::new(lexemes));
+    module.add_fun(Symbol::intern("tokens"), 2, Box::new(tokens));
+    module.add_fun(Symbol::intern("find"), 2, Box::new(find));
+    module.add_fun(Symbol::intern("find"), 3, Box::new(find));
+    module.add_fun(Symbol::intern("equal"), 2, Box::new(equal));
+    module.add_fun(Symbol::intern("reverse"), 1, Box::new(reverse));
+    module.add_fun(Symbol::intern("slice"), 2, Box::new(slice));
+    module.add_fun(Symbol::intern("slice"), 3, Box::new(slice));
+    module.add_fun(Symbol::intern("prefix"), 2, Box::new(prefix));
+    module.add_fun(Symbol::intern("join"), 2, Box::new(join));
+    module.add_fun(Symbol::intern("concat"), 2, Box::new(concat));
+    module.add_fun(Symbol::intern("substr"), 2, Box::new(substr));
+    module.add_fun(Symbol::intern("substr"), 3, Box::new(substr));
+    module.add_fun(Symbol::intern("chr"), 2, Box::new(chr));
+    module.add_fun(Symbol::intern("to_integer"), 1, Box::new(to_integer));
+    module
+}
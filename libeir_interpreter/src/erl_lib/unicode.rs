@@ -0,0 +1,138 @@
+//! The `unicode` module. Invalid or incomplete input raises `badarg` rather
+//! than returning an `{error, _, _}` or `{incomplete, _, _}` tuple.
+
+use std::rc::Rc;
+
+use libeir_intern::Symbol;
+
+use crate::module::{NativeModule, NativeReturn};
+use crate::process::ProcessContext;
+use crate::term::Term;
+use crate::vm::VMState;
+
+use super::util::{badarg, bytes_to_binary, chardata_to_string, string_to_list};
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Encoding {
+    Latin1,
+    Utf8,
+    Utf16,
+    Utf32,
+}
+
+/// Reads an encoding argument, defaulting to `unicode` when it is absent.
+fn encoding_arg(term: Option<&Rc<Term>>) -> Result<Encoding, NativeReturn> {
+    let term = match term {
+        Some(term) => term,
+        None => return Ok(Encoding::Utf8),
+    };
+    let atom = term.as_atom().ok_or_else(badarg)?;
+    if atom == "latin1" {
+        Ok(Encoding::Latin1)
+    } else if atom == "unicode" || atom == "utf8" {
+        Ok(Encoding::Utf8)
+    } else if atom == "utf16" {
+        Ok(Encoding::Utf16)
+    } else if atom == "utf32" {
+        Ok(Encoding::Utf32)
+    } else {
+        Err(badarg())
+    }
+}
+
+fn read_chardata(term: &Rc<Term>, encoding: Encoding) -> Result<String, NativeReturn> {
+    match encoding {
+        Encoding::Latin1 => chardata_to_string(term, true).ok_or_else(badarg),
+        Encoding::Utf8 => chardata_to_string(term, false).ok_or_else(badarg),
+        // Only plain lists of codepoints are supported for the wider input
+        // encodings.
+        Encoding::Utf16 | Encoding::Utf32 => match &**term {
+            Term::ListCell(_, _) | Term::Nil => chardata_to_string(term, false).ok_or_else(badarg),
+            _ => Err(badarg()),
+        },
+    }
+}
+
+fn encode(string: &str, encoding: Encoding) -> Result<Vec<u8>, NativeReturn> {
+    let mut out = Vec::new();
+    match encoding {
+        Encoding::Latin1 => {
+            for chr in string.chars() {
+                if chr as u32 > 255 {
+                    return Err(badarg());
+                }
+                out.push(chr as u8);
+            }
+        }
+        Encoding::Utf8 => out.extend_from_slice(string.as_bytes()),
+        Encoding::Utf16 => {
+            for unit in string.encode_utf16() {
+                out.extend_from_slice(&unit.to_be_bytes());
+            }
+        }
+        Encoding::Utf32 => {
+            for chr in string.chars() {
+                out.extend_from_slice(&(chr as u32).to_be_bytes());
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn characters_to_binary(
+    _vm: &VMState,
+    _proc: &mut ProcessContext,
+    args: &[Rc<Term>],
+) -> NativeReturn {
+    assert!(args.len() >= 1 && args.len() <= 3);
+    let in_encoding = native_try!(encoding_arg(args.get(1)));
+    let out_encoding = native_try!(encoding_arg(args.get(2)));
+    let string = native_try!(read_chardata(&args[0], in_encoding));
+    let bytes = native_try!(encode(&string, out_encoding));
+    NativeReturn::Return {
+        term: bytes_to_binary(bytes),
+    }
+}
+
+fn characters_to_list(
+    _vm: &VMState,
+    _proc: &mut ProcessContext,
+    args: &[Rc<Term>],
+) -> NativeReturn {
+    assert!(args.len() == 1 || args.len() == 2);
+    let in_encoding = native_try!(encoding_arg(args.get(1)));
+    let string = native_try!(read_chardata(&args[0], in_encoding));
+    NativeReturn::Return {
+        term: string_to_list(&string),
+    }
+}
+
+pub fn make_unicode() -> NativeModule {
+    let mut module = NativeModule::new(Symbol::intern("unicode"));
+    module.add_fun(
+        Symbol::intern("characters_to_binary"),
+        1,
+        Box::new(characters_to_binary),
+    );
+    module.add_fun(
+        Symbol::intern("characters_to_binary"),
+        2,
+        Box::new(characters_to_binary),
+    );
+    module.add_fun(
+        Symbol::intern("characters_to_binary"),
+        3,
+        Box::new(characters_to_binary),
+    );
+    module.add_fun(
+        Symbol::intern("characters_to_list"),
+        1,
+        Box::new(characters_to_list),
+    );
+    module.add_fun(
+        Symbol::intern("characters_to_list"),
+        2,
+        Box::new(characters_to_list),
+    );
+    module
+}
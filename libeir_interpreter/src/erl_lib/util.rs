@@ -0,0 +1,200 @@
+//! Helpers shared by the native implementations of the standard library.
+
+use std::rc::Rc;
+
+use libeir_util_binary::{BitRead, BitSlice, BitVec};
+
+use num_traits::ToPrimitive;
+
+use crate::module::NativeReturn;
+use crate::process::ProcessContext;
+use crate::term::{ListIteratorItem, Term};
+use crate::vm::VMState;
+
+/// Unwraps a `Result<T, NativeReturn>`, returning the error from the
+/// enclosing native function.
+macro_rules! native_try {
+    ($expr:expr) => {
+        match $expr {
+            Ok(val) => val,
+            Err(err) => return err,
+        }
+    };
+}
+
+pub(super) fn badarg() -> NativeReturn {
+    NativeReturn::Throw {
+        typ: Term::new_atom("error").into(),
+        reason: Term::new_atom("badarg").into(),
+    }
+}
+
+pub(super) fn badarith() -> NativeReturn {
+    NativeReturn::Throw {
+        typ: Term::new_atom("error").into(),
+        reason: Term::new_atom("badarith").into(),
+    }
+}
+
+/// Raises an `error:{Tag, Term}` exception.
+pub(super) fn error_tuple(tag: &str, term: Rc<Term>) -> NativeReturn {
+    NativeReturn::Throw {
+        typ: Term::new_atom("error").into(),
+        reason: Term::Tuple(vec![Term::new_atom(tag).into(), term]).into(),
+    }
+}
+
+/// Reads a list of unicode codepoints into a string.
+pub(super) fn list_to_string(term: &Rc<Term>) -> Option<String> {
+    Term::as_list(term)?
+        .iter()
+        .map(|c| {
+            c.as_integer()
+                .and_then(|i| i.to_u32())
+                .and_then(std::char::from_u32)
+        })
+        .collect()
+}
+
+pub(super) fn string_to_list(string: &str) -> Rc<Term> {
+    let chars: Vec<_> = string
+        .chars()
+        .map(|c| Term::new_usize(c as usize).into())
+        .collect();
+    Term::slice_to_list(&chars, Term::Nil.into())
+}
+
+/// Reads the bytes of a binary. `None` if the term is not a binary, or if the
+/// bitstring is not a whole number of bytes.
+pub(super) fn binary_bytes(term: &Term) -> Option<Vec<u8>> {
+    let (buf, bit_offset, bit_length) = term.as_binary_bits()?;
+    if bit_length % 8 != 0 {
+        return None;
+    }
+    let slice = BitSlice::with_offset_length(buf, bit_offset, bit_length);
+    Some((0..bit_length / 8).map(|n| slice.read_word(n)).collect())
+}
+
+pub(super) fn bytes_to_binary(bytes: Vec<u8>) -> Rc<Term> {
+    Term::Binary(Rc::new(bytes.into())).into()
+}
+
+/// Appends the contents of an iolist to `out`. When `bits` is set,
+/// bitstrings that are not a whole number of bytes are accepted.
+pub(super) fn flatten_iolist(term: &Rc<Term>, bits: bool, out: &mut BitVec) -> Option<()> {
+    match &**term {
+        Term::Binary(_) | Term::BinarySlice { .. } => {
+            let (buf, bit_offset, bit_length) = term.as_binary_bits().unwrap();
+            if !bits && bit_length % 8 != 0 {
+                return None;
+            }
+            out.push(BitSlice::with_offset_length(buf, bit_offset, bit_length));
+        }
+        Term::Nil => (),
+        Term::ListCell(_, _) => {
+            for item in Term::list_iter(term) {
+                match item {
+                    ListIteratorItem::Elem(elem) => match &*elem {
+                        Term::Integer(int) => out.push(int.to_u8()?),
+                        _ => flatten_iolist(&elem, bits, out)?,
+                    },
+                    ListIteratorItem::Tail(tail) => match &*tail {
+                        Term::ListCell(_, _) => unreachable!(),
+                        _ => flatten_iolist(&tail, bits, out)?,
+                    },
+                }
+            }
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+/// Reads a proper list, raising `badarg` if the term is not one.
+pub(super) fn proper_list(term: &Rc<Term>) -> Result<Vec<Rc<Term>>, NativeReturn> {
+    Term::as_list(term).ok_or_else(badarg)
+}
+
+/// Checks that `fun` is a fun that can be called with `args`, raising
+/// `badfun` or `badarity` otherwise.
+pub(super) fn check_fun(
+    vm: &VMState,
+    fun: &Rc<Term>,
+    args: &[Rc<Term>],
+) -> Result<(), NativeReturn> {
+    match vm.fun_arity(fun) {
+        Some(arity) if arity == args.len() => Ok(()),
+        Some(_) => {
+            let args_list = Term::slice_to_list(args, Term::Nil.into());
+            let info = Term::Tuple(vec![fun.clone(), args_list]);
+            Err(error_tuple("badarity", info.into()))
+        }
+        None => Err(error_tuple("badfun", fun.clone())),
+    }
+}
+
+/// Calls a fun from a native function, and waits for it to return.
+/// Exceptions are returned as a `NativeReturn` that rethrows them.
+pub(super) fn call_fun(
+    vm: &VMState,
+    proc: &mut ProcessContext,
+    fun: &Rc<Term>,
+    args: &[Rc<Term>],
+) -> Result<Rc<Term>, NativeReturn> {
+    check_fun(vm, fun, args)?;
+    vm.call_fun(proc, fun.clone(), args)
+        .map_err(|(typ, reason, _trace)| NativeReturn::Throw { typ, reason })
+}
+
+/// Calls a predicate fun, raising `badarg` if it does not return a boolean.
+pub(super) fn call_pred(
+    vm: &VMState,
+    proc: &mut ProcessContext,
+    fun: &Rc<Term>,
+    args: &[Rc<Term>],
+) -> Result<bool, NativeReturn> {
+    call_fun(vm, proc, fun, args)?
+        .as_boolean()
+        .ok_or_else(badarg)
+}
+
+/// Reads unicode chardata into a string. Chardata is a possibly deep list of
+/// codepoints and binaries. Binaries are decoded as utf8, or as latin1 if
+/// `latin1` is set.
+pub(super) fn chardata_to_string(term: &Rc<Term>, latin1: bool) -> Option<String> {
+    let mut out = String::new();
+    extend_chardata(term, latin1, &mut out)?;
+    Some(out)
+}
+
+fn extend_chardata(term: &Rc<Term>, latin1: bool, out: &mut String) -> Option<()> {
+    match &**term {
+        Term::Binary(_) | Term::BinarySlice { .. } => {
+            let bytes = binary_bytes(term)?;
+            if latin1 {
+                out.extend(bytes.iter().map(|b| *b as char));
+            } else {
+                out.push_str(std::str::from_utf8(&bytes).ok()?);
+            }
+        }
+        Term::Nil => (),
+        Term::ListCell(_, _) => {
+            for item in Term::list_iter(term) {
+                match item {
+                    ListIteratorItem::Elem(elem) => match &*elem {
+                        Term::Integer(int) => {
+                            out.push(int.to_u32().and_then(std::char::from_u32)?);
+                        }
+                        _ => extend_chardata(&elem, latin1, out)?,
+                    },
+                    ListIteratorItem::Tail(tail) => match &*tail {
+                        Term::ListCell(_, _) => unreachable!(),
+                        _ => extend_chardata(&tail, latin1, out)?,
+                    },
+                }
+            }
+        }
+        _ => return None,
+    }
+    Some(())
+}
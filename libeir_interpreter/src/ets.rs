@@ -0,0 +1,359 @@
+//! Storage for ETS tables, and the matching used by `ets:match/2` and
+//! `ets:select/2`.
+//!
+//! Tables live in the VM and are not owned by any process. Objects are kept
+//! in insertion order, except in `ordered_set` tables where they are kept
+//! sorted by key in term order.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use libeir_intern::Symbol;
+
+use crate::term::{ErlEq, ErlExactEq, ErlOrd, Reference, Term};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TableKind {
+    Set,
+    OrderedSet,
+    Bag,
+    DuplicateBag,
+}
+
+pub struct Table {
+    pub name: Symbol,
+    pub kind: TableKind,
+    /// Zero based index of the key in stored tuples.
+    pub keypos: usize,
+    objects: Vec<Rc<Term>>,
+}
+
+impl Table {
+    pub fn new(name: Symbol, kind: TableKind, keypos: usize) -> Self {
+        Table {
+            name,
+            kind,
+            keypos,
+            objects: Vec::new(),
+        }
+    }
+
+    /// Whether the object is a tuple large enough to have a key.
+    pub fn accepts(&self, object: &Term) -> bool {
+        object
+            .as_tuple()
+            .map(|elems| elems.len() > self.keypos)
+            .unwrap_or(false)
+    }
+
+    fn key<'a>(&self, object: &'a Term) -> &'a Rc<Term> {
+        &object.as_tuple().unwrap()[self.keypos]
+    }
+
+    /// Keys in `ordered_set` tables compare equal, other tables match them.
+    fn key_eq(&self, lhs: &Term, rhs: &Term) -> bool {
+        match self.kind {
+            TableKind::OrderedSet => lhs.erl_ord(rhs) == Ordering::Equal,
+            _ => lhs.erl_exact_eq(rhs),
+        }
+    }
+
+    pub fn insert(&mut self, object: Rc<Term>) {
+        assert!(self.accepts(&object));
+        let key = self.key(&object).clone();
+        match self.kind {
+            TableKind::Set => {
+                let existing = self
+                    .objects
+                    .iter()
+                    .position(|o| self.key_eq(self.key(o), &key));
+                match existing {
+                    Some(idx) => self.objects[idx] = object,
+                    None => self.objects.push(object),
+                }
+            }
+            TableKind::OrderedSet => {
+                let res = self.objects.binary_search_by(|o| self.key(o).erl_ord(&key));
+                match res {
+                    Ok(idx) => self.objects[idx] = object,
+                    Err(idx) => self.objects.insert(idx, object),
+                }
+            }
+            TableKind::Bag => {
+                if !self.objects.iter().any(|o| o.erl_exact_eq(&object)) {
+                    self.objects.push(object);
+                }
+            }
+            TableKind::DuplicateBag => self.objects.push(object),
+        }
+    }
+
+    pub fn lookup(&self, key: &Term) -> Vec<Rc<Term>> {
+        self.objects
+            .iter()
+            .filter(|o| self.key_eq(self.key(o), key))
+            .cloned()
+            .collect()
+    }
+
+    pub fn delete(&mut self, key: &Term) {
+        let objects = std::mem::replace(&mut self.objects, Vec::new());
+        self.objects = objects
+            .into_iter()
+            .filter(|o| !self.key_eq(self.key(o), key))
+            .collect();
+    }
+
+    pub fn objects(&self) -> &[Rc<Term>] {
+        &self.objects
+    }
+}
+
+#[derive(Default)]
+pub struct EtsTables {
+    tables: HashMap<Reference, Table>,
+    named: HashMap<Symbol, Reference>,
+}
+
+impl EtsTables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a table under the given reference.
+    /// Returns `false` if the table is named, and the name is already taken.
+    pub fn create(&mut self, id: Reference, table: Table, named: bool) -> bool {
+        if named {
+            if self.named.contains_key(&table.name) {
+                return false;
+            }
+            self.named.insert(table.name, id);
+        }
+        self.tables.insert(id, table);
+        true
+    }
+
+    /// Resolves a table identifier, which is either the reference returned
+    /// by `ets:new/2`, or the name of a named table.
+    pub fn resolve(&self, tab: &Term) -> Option<Reference> {
+        match tab {
+            Term::Reference(id) if self.tables.contains_key(id) => Some(*id),
+            Term::Atom(name) => self.named.get(name).cloned(),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, id: Reference) -> Option<&Table> {
+        self.tables.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: Reference) -> Option<&mut Table> {
+        self.tables.get_mut(&id)
+    }
+
+    pub fn delete(&mut self, id: Reference) -> bool {
+        match self.tables.remove(&id) {
+            Some(table) => {
+                if self.named.get(&table.name) == Some(&id) {
+                    self.named.remove(&table.name);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Bindings of match variables, keyed by variable number.
+pub type MatchBindings = BTreeMap<usize, Rc<Term>>;
+
+/// Returns the number of a `'$N'` match variable.
+fn match_var(atom: Symbol) -> Option<usize> {
+    let name = atom.as_str().get();
+    if name.starts_with('$') {
+        name[1..].parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Matches a term against an ETS match pattern, where `'_'` matches anything
+/// and `'$N'` variables bind the matched term.
+pub fn match_pattern(pattern: &Rc<Term>, term: &Rc<Term>, binds: &mut MatchBindings) -> bool {
+    match &**pattern {
+        Term::Atom(atom) if *atom == "_" => true,
+        Term::Atom(atom) => match match_var(*atom) {
+            Some(var) => match binds.get(&var) {
+                Some(bound) => bound.erl_exact_eq(term),
+                None => {
+                    binds.insert(var, term.clone());
+                    true
+                }
+            },
+            None => pattern.erl_exact_eq(term),
+        },
+        Term::Tuple(pats) => match term.as_tuple() {
+            Some(elems) if elems.len() == pats.len() => pats
+                .iter()
+                .zip(elems.iter())
+                .all(|(pat, elem)| match_pattern(pat, elem, binds)),
+            _ => false,
+        },
+        Term::ListCell(pat_head, pat_tail) => match &**term {
+            Term::ListCell(head, tail) => {
+                match_pattern(pat_head, head, binds) && match_pattern(pat_tail, tail, binds)
+            }
+            _ => false,
+        },
+        _ => pattern.erl_exact_eq(term),
+    }
+}
+
+/// Runs a match specification, a list of `{Head, Guards, Body}` clauses, on
+/// an object.
+/// Returns the result of the first clause that matches, or `None` if no
+/// clause matches. Fails if the match specification is malformed.
+pub fn run_match_spec(spec: &[Rc<Term>], object: &Rc<Term>) -> Result<Option<Rc<Term>>, ()> {
+    for clause in spec.iter() {
+        let (head, guards, body) = match clause.as_tuple() {
+            Some([head, guards, body]) => (head, guards, body),
+            _ => return Err(()),
+        };
+        let guards = Term::as_list(guards).ok_or(())?;
+        let body = Term::as_list(body).ok_or(())?;
+
+        let mut binds = MatchBindings::new();
+        if !match_pattern(head, object, &mut binds) {
+            continue;
+        }
+
+        // Guards that fail to evaluate fail the clause
+        let pass = guards.iter().all(|guard| {
+            eval_ms_expr(guard, object, &binds)
+                .map(|res| res.as_boolean() == Some(true))
+                .unwrap_or(false)
+        });
+        if !pass {
+            continue;
+        }
+
+        let mut result = None;
+        for expr in body.iter() {
+            result = Some(eval_ms_expr(expr, object, &binds)?);
+        }
+        return result.map(Some).ok_or(());
+    }
+    Ok(None)
+}
+
+/// Evaluates a guard or body expression of a match specification.
+fn eval_ms_expr(expr: &Rc<Term>, object: &Rc<Term>, binds: &MatchBindings) -> Result<Rc<Term>, ()> {
+    match &**expr {
+        Term::Atom(atom) if *atom == "$_" => Ok(object.clone()),
+        Term::Atom(atom) if *atom == "$$" => {
+            let values: Vec<_> = binds.values().cloned().collect();
+            Ok(Term::slice_to_list(&values, Term::Nil.into()))
+        }
+        Term::Atom(atom) => match match_var(*atom) {
+            Some(var) => binds.get(&var).cloned().ok_or(()),
+            None => Ok(expr.clone()),
+        },
+        Term::ListCell(head, tail) => Ok(Term::ListCell(
+            eval_ms_expr(head, object, binds)?,
+            eval_ms_expr(tail, object, binds)?,
+        )
+        .into()),
+        Term::Tuple(elems) => match elems.as_slice() {
+            // Tuples are written as `{{A, B}}` to tell them apart from calls
+            [inner] if inner.as_tuple().is_some() => {
+                let elems = inner
+                    .as_tuple()
+                    .unwrap()
+                    .iter()
+                    .map(|e| eval_ms_expr(e, object, binds))
+                    .collect::<Result<_, _>>()?;
+                Ok(Term::Tuple(elems).into())
+            }
+            [op, value] if op.as_atom().map(|a| a == "const").unwrap_or(false) => Ok(value.clone()),
+            [op, args @ ..] => {
+                let op = op.as_atom().ok_or(())?;
+                eval_ms_call(op, args, object, binds)
+            }
+            [] => Err(()),
+        },
+        _ => Ok(expr.clone()),
+    }
+}
+
+fn eval_ms_call(
+    op: Symbol,
+    args: &[Rc<Term>],
+    object: &Rc<Term>,
+    binds: &MatchBindings,
+) -> Result<Rc<Term>, ()> {
+    let eval_bool = |expr: &Rc<Term>| eval_ms_expr(expr, object, binds)?.as_boolean().ok_or(());
+
+    // Short circuiting operators only evaluate what they need
+    match (op.as_str().get(), args) {
+        ("andalso", [lhs, rhs]) => {
+            let res = eval_bool(lhs)? && eval_bool(rhs)?;
+            return Ok(Term::new_bool(res).into());
+        }
+        ("orelse", [lhs, rhs]) => {
+            let res = eval_bool(lhs)? || eval_bool(rhs)?;
+            return Ok(Term::new_bool(res).into());
+        }
+        _ => (),
+    }
+
+    let args = args
+        .iter()
+        .map(|arg| eval_ms_expr(arg, object, binds))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let res = match (op.as_str().get(), args.as_slice()) {
+        ("==", [lhs, rhs]) => lhs.erl_eq(rhs),
+        ("/=", [lhs, rhs]) => !lhs.erl_eq(rhs),
+        ("=:=", [lhs, rhs]) => lhs.erl_exact_eq(rhs),
+        ("=/=", [lhs, rhs]) => !lhs.erl_exact_eq(rhs),
+        ("<", [lhs, rhs]) => lhs.erl_ord(rhs) == Ordering::Less,
+        (">", [lhs, rhs]) => lhs.erl_ord(rhs) == Ordering::Greater,
+        ("=<", [lhs, rhs]) => lhs.erl_ord(rhs) != Ordering::Greater,
+        (">=", [lhs, rhs]) => lhs.erl_ord(rhs) != Ordering::Less,
+        ("and", [lhs, rhs]) => lhs.as_boolean().ok_or(())? & rhs.as_boolean().ok_or(())?,
+        ("or", [lhs, rhs]) => lhs.as_boolean().ok_or(())? | rhs.as_boolean().ok_or(())?,
+        ("xor", [lhs, rhs]) => lhs.as_boolean().ok_or(())? ^ rhs.as_boolean().ok_or(())?,
+        ("not", [val]) => !val.as_boolean().ok_or(())?,
+        ("is_atom", [val]) => val.as_atom().is_some(),
+        ("is_integer", [val]) => val.as_integer().is_some(),
+        ("is_float", [val]) => match &**val {
+            Term::Float(_) => true,
+            _ => false,
+        },
+        ("is_number", [val]) => match &**val {
+            Term::Integer(_) | Term::Float(_) => true,
+            _ => false,
+        },
+        ("is_tuple", [val]) => val.as_tuple().is_some(),
+        ("is_list", [val]) => match &**val {
+            Term::Nil | Term::ListCell(_, _) => true,
+            _ => false,
+        },
+        ("is_binary", [val]) => match val.as_binary_bits() {
+            Some((_, _, bit_length)) => bit_length % 8 == 0,
+            None => false,
+        },
+        ("is_map", [val]) => val.as_map().is_some(),
+        ("is_pid", [val]) => match &**val {
+            Term::Pid(_) => true,
+            _ => false,
+        },
+        ("is_reference", [val]) => match &**val {
+            Term::Reference(_) => true,
+            _ => false,
+        },
+        _ => return Err(()),
+    };
+    Ok(Term::new_bool(res).into())
+}
@@ -5,15 +5,29 @@
 //! details.
 
 mod term;
-pub use term::{ErlEq, ErlExactEq, ErlOrd, Pid, Reference, Term, TermType};
+pub use term::{ErlEq, ErlExactEq, ErlOrd, MapTerm, Pid, Reference, Term, TermType};
 
 pub mod erl_lib;
 
 mod vm;
-pub use vm::{VMState, WatchType};
+pub use vm::{LoadError, VMState, WatchType};
 
 mod process;
+pub use process::{CallStack, ProcessContext, StackFrame};
+
+mod debugger;
+pub use debugger::{Breakpoint, CallResult, Debugger, SourcePosition, StepMode, StopReason};
+
+mod coverage;
+pub use coverage::{Coverage, FileLines, FunctionLines};
 
 mod module;
+pub use module::ErlangFunction;
+
+mod loader;
+pub use loader::CodeLoader;
+
+mod ets;
+pub use ets::{EtsTables, Table, TableKind};
 
-//mod trace;
+mod trace;
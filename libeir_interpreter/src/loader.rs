@@ -0,0 +1,157 @@
+//! On demand loading of Erlang modules from a set of code paths.
+//!
+//! When the VM is asked for a module it does not know, the code paths are
+//! searched in order for `<module>.erl`, `<module>.abstr` and `<module>.eir`.
+//! The first file found is compiled through the matching frontend and run
+//! through the configured pass pipeline.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use libeir_diagnostics::{CodeMap, Diagnostic};
+use libeir_frontend::{
+    abstr_erlang::AbstrErlangFrontend, eir::EirFrontend, erlang::ErlangFrontend, AnyFrontend,
+    DynFrontend,
+};
+use libeir_intern::Symbol;
+use libeir_ir::Module;
+use libeir_passes::PassManager;
+use libeir_syntax_erl::ParseConfig;
+
+/// Source file extensions, in the order they are searched for.
+const EXTENSIONS: &[&str] = &["erl", "abstr", "eir"];
+
+pub struct CodeLoader {
+    codemap: Arc<CodeMap>,
+    paths: Vec<PathBuf>,
+    config: ParseConfig,
+    passes: PassManager,
+
+    /// Modules that have been searched for, but could not be loaded.
+    /// These are not searched for again.
+    failed: HashSet<Symbol>,
+    /// Diagnostics emitted while compiling loaded modules.
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl CodeLoader {
+    /// Creates a loader with no code paths, the default parser configuration
+    /// and the default pass pipeline.
+    pub fn new(codemap: Arc<CodeMap>) -> Self {
+        CodeLoader {
+            codemap,
+            paths: Vec::new(),
+            config: ParseConfig::default(),
+            passes: PassManager::default(),
+            failed: HashSet::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Sets the parser configuration used for `.erl` files.
+    pub fn with_parse_config(mut self, config: ParseConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sets the passes run on every loaded module.
+    pub fn with_passes(mut self, passes: PassManager) -> Self {
+        self.passes = passes;
+        self
+    }
+
+    /// Appends a directory to the code path.
+    pub fn add_path<P: Into<PathBuf>>(&mut self, path: P) {
+        self.paths.push(path.into());
+        // A new path may contain modules that were not found earlier
+        self.failed.clear();
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    pub fn codemap(&self) -> &Arc<CodeMap> {
+        &self.codemap
+    }
+
+    /// Diagnostics emitted while compiling loaded modules.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Finds the source file for a module in the code path.
+    pub fn find(&self, name: Symbol) -> Option<PathBuf> {
+        let name = name.as_str();
+        for dir in self.paths.iter() {
+            for ext in EXTENSIONS {
+                let path = dir.join(format!("{}.{}", &*name, ext));
+                if path.is_file() {
+                    return Some(path);
+                }
+            }
+        }
+        None
+    }
+
+    fn frontend(&self, path: &Path) -> AnyFrontend {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("abstr") => AbstrErlangFrontend::new(self.codemap.clone()).into(),
+            Some("eir") => EirFrontend::new(self.codemap.clone()).into(),
+            _ => ErlangFrontend::new(self.config.clone(), self.codemap.clone()).into(),
+        }
+    }
+
+    /// Finds and compiles a module.
+    ///
+    /// Returns `None` if the module is not in the code path, if it fails to
+    /// compile, or if the file defines a module with a different name.
+    pub fn load(&mut self, name: Symbol) -> Option<Module> {
+        if self.failed.contains(&name) {
+            return None;
+        }
+
+        let module = self.compile(name);
+        if module.is_none() {
+            self.failed.insert(name);
+        }
+        module
+    }
+
+    /// Compiles a module from source that is not in the code path, as done
+    /// by `code:load_binary/3`.
+    /// The frontend is chosen by the extension of `filename`.
+    pub fn load_source(&mut self, name: Symbol, filename: &str, source: String) -> Option<Module> {
+        let id = self.codemap.add(filename.to_owned(), source);
+        let file = self.codemap.get(id).unwrap();
+
+        let frontend = self.frontend(Path::new(filename));
+        let (res, diagnostics) = frontend.parse_source_dyn(file);
+        self.finish(name, res, diagnostics)
+    }
+
+    fn compile(&mut self, name: Symbol) -> Option<Module> {
+        let path = self.find(name)?;
+
+        let (res, diagnostics) = self.frontend(&path).parse_file_dyn(&path);
+        self.finish(name, res, diagnostics)
+    }
+
+    fn finish(
+        &mut self,
+        name: Symbol,
+        res: Result<Module, ()>,
+        diagnostics: Vec<Diagnostic>,
+    ) -> Option<Module> {
+        self.diagnostics.extend(diagnostics);
+
+        let mut module = res.ok()?;
+        if module.name().name != name {
+            return None;
+        }
+
+        self.passes.run(&mut module);
+        Some(module)
+    }
+}
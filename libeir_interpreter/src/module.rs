@@ -8,8 +8,19 @@ use libeir_intern::Symbol;
 use libeir_ir::{Function, FunctionIdent, LiveValues, Module};
 
 pub enum NativeReturn {
-    Return { term: Rc<Term> },
-    Throw { typ: Rc<Term>, reason: Rc<Term> },
+    Return {
+        term: Rc<Term>,
+    },
+    Throw {
+        typ: Rc<Term>,
+        reason: Rc<Term>,
+    },
+    /// Tail calls the given function with the continuations the native
+    /// function was called with.
+    Call {
+        fun: Rc<Term>,
+        args: Vec<Rc<Term>>,
+    },
 }
 
 pub struct NativeModule {
@@ -44,15 +55,19 @@ impl NativeModule {
 pub struct ErlangFunction {
     pub fun: Function,
     pub live: LiveValues,
+    /// The version of the module this function was loaded with.
+    pub version: usize,
 }
 
 pub struct ErlangModule {
     pub name: Symbol,
-    pub functions: HashMap<FunctionIdent, ErlangFunction>,
+    /// Incremented every time new code is loaded for the module.
+    pub version: usize,
+    pub functions: HashMap<FunctionIdent, Rc<ErlangFunction>>,
 }
 
 impl ErlangModule {
-    pub fn from_eir(module: Module) -> Self {
+    pub fn from_eir(module: Module, version: usize) -> Self {
         let functions = module
             .index_iter()
             .map(|idx| {
@@ -61,13 +76,15 @@ impl ErlangModule {
                 let nfun = ErlangFunction {
                     live: fun.live_values(),
                     fun: fun.clone(),
+                    version,
                 };
-                (fun.ident().clone(), nfun)
+                (fun.ident().clone(), Rc::new(nfun))
             })
             .collect();
 
         ErlangModule {
             name: module.name().name,
+            version,
             functions,
         }
     }
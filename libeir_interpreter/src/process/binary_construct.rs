@@ -0,0 +1,168 @@
+use num_bigint::BigInt;
+use num_traits::cast::ToPrimitive;
+
+use libeir_ir::BinaryEntrySpecifier;
+
+use libeir_util_binary::{integer_to_carrier, BitSlice, BitVec, Endian};
+
+use crate::Term;
+
+use super::r#match::endian;
+
+/// Copies a binary term into a new, owned `BitVec`.
+pub(super) fn binary_to_bitvec(term: &Term) -> Option<BitVec> {
+    let (buf, bit_offset, bit_length) = term.as_binary_bits()?;
+    let mut new = BitVec::new();
+    new.push(BitSlice::with_offset_length(buf, bit_offset, bit_length));
+    Some(new)
+}
+
+/// Reads the size operand of a binary entry. Sizes must be non-negative
+/// integers.
+fn entry_size(size: Option<&Term>) -> Result<Option<usize>, ()> {
+    match size {
+        None => Ok(None),
+        Some(Term::Integer(int)) => int.to_usize().map(Some).ok_or(()),
+        Some(_) => Err(()),
+    }
+}
+
+fn push_uint(bin: &mut BitVec, int: u64, bits: usize, endian: Endian) {
+    bin.push(integer_to_carrier(BigInt::from(int), bits, endian));
+}
+
+fn f64_to_f16(num: f64) -> Option<u16> {
+    let sign = if num.is_sign_negative() { 0x8000 } else { 0 };
+    let abs = num.abs();
+    if abs == 0.0 {
+        return Some(sign);
+    }
+
+    // Below the smallest normal number, encode as subnormal
+    if abs < 2f64.powi(-14) {
+        let frac = (abs * 2f64.powi(24)).round() as u16;
+        return Some(sign | frac);
+    }
+
+    let mut exp = abs.log2().floor() as i32;
+    while 2f64.powi(exp) > abs {
+        exp -= 1;
+    }
+    while 2f64.powi(exp + 1) <= abs {
+        exp += 1;
+    }
+
+    let mut frac = ((abs / 2f64.powi(exp) - 1.0) * 1024.0).round() as u16;
+    if frac == 1024 {
+        frac = 0;
+        exp += 1;
+    }
+    if exp > 15 {
+        return None;
+    }
+    Some(sign | (((exp + 15) as u16) << 10) | frac)
+}
+
+/// Appends a single binary entry to `bin`.
+///
+/// Returns `Err` when the value or size is not valid for the specifier,
+/// which should be raised as `badarg`. `bin` is left in an unspecified
+/// state on error.
+pub(super) fn push_binary_entry(
+    bin: &mut BitVec,
+    specifier: &BinaryEntrySpecifier,
+    value: &Term,
+    size: Option<&Term>,
+) -> Result<(), ()> {
+    let size = entry_size(size)?;
+
+    match *specifier {
+        BinaryEntrySpecifier::Integer {
+            endianness, unit, ..
+        } => {
+            let bits = size.ok_or(())? * unit as usize;
+            let int = value.as_integer().ok_or(())?;
+            if bits != 0 {
+                bin.push(integer_to_carrier(int.clone(), bits, endian(endianness)));
+            }
+        }
+        BinaryEntrySpecifier::Float { endianness, unit } => {
+            let bits = size.ok_or(())? * unit as usize;
+            let num = match value {
+                Term::Float(flt) => flt.0,
+                Term::Integer(int) => int.to_f64().ok_or(())?,
+                _ => return Err(()),
+            };
+            if !num.is_finite() {
+                return Err(());
+            }
+
+            let raw = match bits {
+                16 => f64_to_f16(num).ok_or(())? as u64,
+                32 => {
+                    let num = num as f32;
+                    if !num.is_finite() {
+                        return Err(());
+                    }
+                    num.to_bits() as u64
+                }
+                64 => num.to_bits(),
+                _ => return Err(()),
+            };
+            push_uint(bin, raw, bits, endian(endianness));
+        }
+        BinaryEntrySpecifier::Bytes { unit } | BinaryEntrySpecifier::Bits { unit } => {
+            let unit = unit as usize;
+            let (buf, bit_offset, bit_length) = value.as_binary_bits().ok_or(())?;
+
+            let bits = match size {
+                // A sized entry takes a prefix of the value
+                Some(size) => {
+                    let bits = size * unit;
+                    if bits > bit_length {
+                        return Err(());
+                    }
+                    bits
+                }
+                // Without a size, all of the value is appended
+                None => {
+                    if unit != 0 && bit_length % unit != 0 {
+                        return Err(());
+                    }
+                    bit_length
+                }
+            };
+
+            bin.push(BitSlice::with_offset_length(buf, bit_offset, bits));
+        }
+        BinaryEntrySpecifier::Utf8 => {
+            let chr = value_char(value, size)?;
+            let mut buf = [0; 4];
+            for byte in chr.encode_utf8(&mut buf).bytes() {
+                push_uint(bin, byte as u64, 8, Endian::Big);
+            }
+        }
+        BinaryEntrySpecifier::Utf16 { endianness } => {
+            let chr = value_char(value, size)?;
+            let mut buf = [0; 2];
+            for unit in chr.encode_utf16(&mut buf).iter() {
+                push_uint(bin, *unit as u64, 16, endian(endianness));
+            }
+        }
+        BinaryEntrySpecifier::Utf32 { endianness } => {
+            let chr = value_char(value, size)?;
+            push_uint(bin, chr as u64, 32, endian(endianness));
+        }
+    }
+
+    Ok(())
+}
+
+/// Utf entries take a codepoint, and never have a size.
+fn value_char(value: &Term, size: Option<usize>) -> Result<char, ()> {
+    if size.is_some() {
+        return Err(());
+    }
+    let cp = value.as_integer().and_then(|int| int.to_u32()).ok_or(())?;
+    std::char::from_u32(cp).ok_or(())
+}
@@ -1,3 +1,7 @@
+use std::rc::Rc;
+
+use num_traits::cast::ToPrimitive;
+
 use libeir_ir::{BasicType, BinaryEntrySpecifier, Block, Endianness, MatchKind};
 
 use libeir_util_binary::BitCarrier;
@@ -7,42 +11,42 @@ use crate::module::ErlangFunction;
 use crate::term::ErlExactEq;
 use crate::Term;
 
-use super::{CallExecutor, TermCall};
+use super::{error_tuple, CallExecutor, TermCall};
 
 pub fn match_op(
     exec: &mut CallExecutor,
     fun: &ErlangFunction,
     branches: &[MatchKind],
     block: Block,
-) -> TermCall {
+) -> Result<TermCall, Rc<Term>> {
     let reads = fun.fun.block_reads(block);
 
-    let branches_elems = Term::as_value_list(&exec.make_term(fun, reads[0]));
+    let branches_elems = Term::as_value_list(&exec.make_term(fun, reads[0])?);
 
-    let unpack_term = exec.make_term(fun, reads[1]);
+    let unpack_term = exec.make_term(fun, reads[1])?;
 
     for (idx, kind) in branches.iter().enumerate() {
-        let branch_args = Term::as_value_list(&exec.make_term(fun, reads[idx + 2]));
+        let branch_args = Term::as_value_list(&exec.make_term(fun, reads[idx + 2])?);
 
         match kind {
             MatchKind::Value => {
                 assert!(branch_args.len() == 1);
 
                 if unpack_term.erl_exact_eq(&*branch_args[0]) {
-                    return TermCall {
+                    return Ok(TermCall {
                         fun: branches_elems[idx].clone(),
                         args: vec![],
-                    };
+                    });
                 }
             }
             MatchKind::ListCell => {
                 assert!(branch_args.len() == 0);
                 match &*unpack_term {
                     Term::ListCell(head, tail) => {
-                        return TermCall {
+                        return Ok(TermCall {
                             fun: branches_elems[idx].clone(),
                             args: vec![head.clone(), tail.clone()],
-                        };
+                        });
                     }
                     _ => (),
                 }
@@ -51,10 +55,10 @@ pub fn match_op(
                 assert!(branch_args.len() == 0);
                 match &*unpack_term {
                     Term::Tuple(elems) if elems.len() == *len => {
-                        return TermCall {
+                        return Ok(TermCall {
                             fun: branches_elems[idx].clone(),
                             args: elems.clone(),
-                        };
+                        });
                     }
                     _ => (),
                 }
@@ -63,10 +67,10 @@ pub fn match_op(
                 assert!(branch_args.len() == 0);
                 match &*unpack_term {
                     Term::Map(_) => {
-                        return TermCall {
+                        return Ok(TermCall {
                             fun: branches_elems[idx].clone(),
                             args: vec![],
-                        };
+                        });
                     }
                     _ => (),
                 }
@@ -76,128 +80,248 @@ pub fn match_op(
                 match &*unpack_term {
                     Term::Map(map) => {
                         if let Some(v) = map.get(&branch_args[0]) {
-                            return TermCall {
+                            return Ok(TermCall {
                                 fun: branches_elems[idx].clone(),
                                 args: vec![v.clone()],
-                            };
+                            });
                         }
                     }
-                    _ => unreachable!(),
+                    _ => continue,
                 }
             }
-            MatchKind::Binary(BinaryEntrySpecifier::Integer {
-                unit,
-                endianness,
-                signed,
-            }) => {
-                let size = branch_args[0].as_usize().unwrap();
-                let bit_len = (*unit as usize) * size;
-
-                let ret = match &*unpack_term {
-                    Term::Binary(bin) => {
-                        if (bin.len() * 8) < bit_len {
-                            continue;
-                        }
-
-                        let int_slice = BitSlice::with_offset_length(&**bin, 0, bit_len);
-                        let endian = match *endianness {
-                            Endianness::Big => Endian::Big,
-                            Endianness::Little => Endian::Little,
-                            Endianness::Native => Endian::Big,
-                        };
-                        let int = carrier_to_integer(int_slice, *signed, endian);
+            MatchKind::Binary(specifier) => {
+                // A size that is not a non-negative integer never matches
+                let size = match branch_args.get(0) {
+                    Some(size) => match size.as_integer().and_then(|i| i.to_usize()) {
+                        Some(size) => Some(size),
+                        None => continue,
+                    },
+                    None => None,
+                };
 
-                        TermCall {
-                            fun: branches_elems[idx].clone(),
-                            args: vec![
-                                Term::Integer(int).into(),
-                                Term::BinarySlice {
-                                    buf: bin.clone(),
-                                    bit_offset: bit_len,
-                                    bit_length: bin.bit_len() - bit_len,
-                                }
-                                .into(),
-                            ],
-                        }
-                    }
+                let (buf, bit_offset, bit_length) = match &*unpack_term {
+                    Term::Binary(bin) => (bin, 0, bin.bit_len()),
                     Term::BinarySlice {
                         buf,
                         bit_offset,
                         bit_length,
-                    } => {
-                        if *bit_length < bit_len {
-                            continue;
-                        }
-
-                        let int_slice = BitSlice::with_offset_length(&**buf, *bit_offset, bit_len);
-                        let endian = match *endianness {
-                            Endianness::Big => Endian::Big,
-                            Endianness::Little => Endian::Little,
-                            Endianness::Native => Endian::Big,
-                        };
-                        let int = carrier_to_integer(int_slice, *signed, endian);
-
-                        TermCall {
-                            fun: branches_elems[idx].clone(),
-                            args: vec![
-                                Term::Integer(int).into(),
-                                Term::BinarySlice {
-                                    buf: buf.clone(),
-                                    bit_offset: *bit_offset + bit_len,
-                                    bit_length: *bit_length - bit_len,
-                                }
-                                .into(),
-                            ],
-                        }
-                    }
+                    } => (buf, *bit_offset, *bit_length),
                     _ => continue,
                 };
-                return ret;
-            }
-            MatchKind::Binary(BinaryEntrySpecifier::Bytes { unit }) => {
-                let size = branch_args[0].as_usize().unwrap_or(1);
-                let byte_len = (*unit as usize) * size;
-
-                match &*unpack_term {
-                    Term::Binary(bin) => {
-                        //if bin.bit_len() % 8 != 0 {
-                        //    continue;
-                        //}
-
-                        return TermCall {
-                            fun: branches_elems[idx].clone(),
-                            args: vec![
-                                unpack_term.clone(),
-                                Term::Binary(BitVec::new().into()).into(),
-                            ],
-                        };
-                    }
-                    Term::BinarySlice { bit_length, .. } => {
-                        //if *bit_length % 8 != 0 {
-                        //    continue;
-                        //}
 
-                        return TermCall {
-                            fun: branches_elems[idx].clone(),
-                            args: vec![
-                                unpack_term.clone(),
-                                Term::Binary(BitVec::new().into()).into(),
-                            ],
-                        };
-                    }
-                    _ => (),
+                if let Some((value, consumed)) =
+                    match_binary_entry(specifier, size, buf, bit_offset, bit_length)
+                {
+                    return Ok(TermCall {
+                        fun: branches_elems[idx].clone(),
+                        args: vec![
+                            value,
+                            Term::BinarySlice {
+                                buf: buf.clone(),
+                                bit_offset: bit_offset + consumed,
+                                bit_length: bit_length - consumed,
+                            }
+                            .into(),
+                        ],
+                    });
                 }
-            },
+            }
             MatchKind::Wildcard => {
                 assert!(branch_args.len() == 0);
-                return TermCall {
+                return Ok(TermCall {
                     fun: branches_elems[idx].clone(),
                     args: vec![],
-                };
+                });
             }
             kind => unimplemented!("{:?}", kind),
         }
     }
 
-    panic!()
+    Err(error_tuple("badmatch", unpack_term))
+}
+
+pub(super) fn endian(endianness: Endianness) -> Endian {
+    match endianness {
+        Endianness::Big => Endian::Big,
+        Endianness::Little => Endian::Little,
+        Endianness::Native => Endian::Big,
+    }
+}
+
+/// Reads an unsigned integer of at most 64 bits.
+fn read_uint(buf: &BitVec, bit_offset: usize, bits: usize, endian: Endian) -> u64 {
+    let slice = BitSlice::with_offset_length(buf, bit_offset, bits);
+    carrier_to_integer(slice, false, endian).to_u64().unwrap()
+}
+
+fn f16_to_f64(bits: u16) -> Option<f64> {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = ((bits >> 10) & 0x1f) as i32;
+    let frac = (bits & 0x3ff) as f64;
+    match exp {
+        0 => Some(sign * frac * 2f64.powi(-24)),
+        0x1f => None,
+        _ => Some(sign * (1.0 + frac / 1024.0) * 2f64.powi(exp - 15)),
+    }
+}
+
+fn valid_codepoint(cp: u32) -> bool {
+    cp <= 0x10ffff && !(cp >= 0xd800 && cp <= 0xdfff)
+}
+
+fn match_utf8(buf: &BitVec, bit_offset: usize, bit_length: usize) -> Option<(u32, usize)> {
+    let avail = bit_length / 8;
+    if avail == 0 {
+        return None;
+    }
+    let byte = |n: usize| read_uint(buf, bit_offset + n * 8, 8, Endian::Big) as u32;
+
+    let first = byte(0);
+    let (len, init, min) = match first {
+        0x00..=0x7f => return Some((first, 8)),
+        0xc0..=0xdf => (2, first & 0x1f, 0x80),
+        0xe0..=0xef => (3, first & 0x0f, 0x800),
+        0xf0..=0xf7 => (4, first & 0x07, 0x10000),
+        _ => return None,
+    };
+    if avail < len {
+        return None;
+    }
+
+    let mut cp = init;
+    for n in 1..len {
+        let cont = byte(n);
+        if cont & 0xc0 != 0x80 {
+            return None;
+        }
+        cp = (cp << 6) | (cont & 0x3f);
+    }
+
+    // Overlong encodings are not valid
+    if cp < min || !valid_codepoint(cp) {
+        return None;
+    }
+    Some((cp, len * 8))
+}
+
+fn match_utf16(
+    buf: &BitVec,
+    bit_offset: usize,
+    bit_length: usize,
+    endian: Endian,
+) -> Option<(u32, usize)> {
+    if bit_length < 16 {
+        return None;
+    }
+    let first = read_uint(buf, bit_offset, 16, endian) as u32;
+    match first {
+        0xd800..=0xdbff => {
+            if bit_length < 32 {
+                return None;
+            }
+            let second = read_uint(buf, bit_offset + 16, 16, endian) as u32;
+            if second < 0xdc00 || second > 0xdfff {
+                return None;
+            }
+            let cp = 0x10000 + (((first - 0xd800) << 10) | (second - 0xdc00));
+            Some((cp, 32))
+        }
+        0xdc00..=0xdfff => None,
+        _ => Some((first, 16)),
+    }
+}
+
+/// Matches a single binary entry at the start of the given bits.
+/// Returns the matched value and the number of bits consumed.
+fn match_binary_entry(
+    specifier: &BinaryEntrySpecifier,
+    size: Option<usize>,
+    buf: &Rc<BitVec>,
+    bit_offset: usize,
+    bit_length: usize,
+) -> Option<(Rc<Term>, usize)> {
+    match *specifier {
+        BinaryEntrySpecifier::Integer {
+            signed,
+            endianness,
+            unit,
+        } => {
+            let bits = size? * unit as usize;
+            if bits > bit_length {
+                return None;
+            }
+
+            let int = if bits == 0 {
+                0.into()
+            } else {
+                let slice = BitSlice::with_offset_length(&**buf, bit_offset, bits);
+                carrier_to_integer(slice, signed, endian(endianness))
+            };
+            Some((Term::Integer(int).into(), bits))
+        }
+        BinaryEntrySpecifier::Float { endianness, unit } => {
+            let bits = size? * unit as usize;
+            if bits > bit_length {
+                return None;
+            }
+
+            let raw = match bits {
+                16 | 32 | 64 => read_uint(&**buf, bit_offset, bits, endian(endianness)),
+                _ => return None,
+            };
+            let num = match bits {
+                16 => f16_to_f64(raw as u16)?,
+                32 => f32::from_bits(raw as u32) as f64,
+                64 => f64::from_bits(raw),
+                _ => unreachable!(),
+            };
+
+            // NaN and infinities are not terms, and never match
+            if !num.is_finite() {
+                return None;
+            }
+            Some((Term::Float(num.into()).into(), bits))
+        }
+        BinaryEntrySpecifier::Bytes { unit } | BinaryEntrySpecifier::Bits { unit } => {
+            let unit = unit as usize;
+            let bits = match size {
+                Some(size) => size * unit,
+                // Without a size, the rest of the binary is matched
+                None => {
+                    if unit != 0 && bit_length % unit != 0 {
+                        return None;
+                    }
+                    bit_length
+                }
+            };
+            if bits > bit_length {
+                return None;
+            }
+
+            let value = Term::BinarySlice {
+                buf: buf.clone(),
+                bit_offset,
+                bit_length: bits,
+            };
+            Some((value.into(), bits))
+        }
+        BinaryEntrySpecifier::Utf8 => {
+            let (cp, bits) = match_utf8(&**buf, bit_offset, bit_length)?;
+            Some((Term::new_usize(cp as usize).into(), bits))
+        }
+        BinaryEntrySpecifier::Utf16 { endianness } => {
+            let (cp, bits) = match_utf16(&**buf, bit_offset, bit_length, endian(endianness))?;
+            Some((Term::new_usize(cp as usize).into(), bits))
+        }
+        BinaryEntrySpecifier::Utf32 { endianness } => {
+            if bit_length < 32 {
+                return None;
+            }
+            let cp = read_uint(&**buf, bit_offset, 32, endian(endianness)) as u32;
+            if !valid_codepoint(cp) {
+                return None;
+            }
+            Some((Term::new_usize(cp as usize).into(), 32))
+        }
+    }
 }
@@ -2,25 +2,26 @@ use std::any::TypeId;
 use std::collections::HashMap;
 use std::rc::Rc;
 
-use num_traits::cast::ToPrimitive;
-
-use libeir_intern::Ident;
+use libeir_intern::{Ident, Symbol};
 use libeir_ir::constant::{AtomicTerm, Const, ConstKind};
 use libeir_ir::operation::binary_construct::{
     BinaryConstructFinish, BinaryConstructPush, BinaryConstructStart,
 };
 use libeir_ir::MapPutUpdate;
-use libeir_ir::{BinOp, Block, FunctionIdent, LogicOp, OpKind, PrimOpKind, Value, ValueKind};
-use libeir_ir::{BinaryEntrySpecifier, Endianness};
-
-use libeir_util_binary::{integer_to_carrier, BitSlice, BitVec, Endian};
+use libeir_ir::{
+    BinOp, Block, CallKind, FunctionIdent, LogicOp, OpKind, PrimOpKind, Value, ValueKind,
+};
 
 use crate::module::{ErlangFunction, ErlangModule, ModuleType, NativeModule, NativeReturn};
 use crate::term::{ErlEq, MapTerm, Pid, Term};
 use crate::vm::VMState;
 
+mod binary_construct;
 mod r#match;
 
+mod stack;
+pub use stack::{CallStack, StackFrame};
+
 #[derive(Debug)]
 pub struct TermCall {
     pub fun: Rc<Term>,
@@ -46,25 +47,61 @@ impl CallExecutor {
 
     pub fn run(&mut self, vm: &VMState, proc: &mut ProcessContext, call: TermCall) -> Continuation {
         self.binds.clear();
+        crate::trace::set_pid(proc.pid);
+        proc.stack.unwind_to(&call.fun);
         match &*call.fun {
             Term::BoundLambda {
                 ident,
+                version,
                 block,
                 environment,
             } => {
-                let module = &vm.modules[&ident.module.name];
-                match module {
-                    ModuleType::Erlang(erl, _overlay) => Continuation::Term(
-                        self.run_erlang(vm, erl, ident, Some((*block, &*environment)), &call.args)
-                            .unwrap(),
-                    ),
+                let module = match vm.module_version(ident.module.name, *version) {
+                    Some(module) => module,
+                    None => return self.killed(vm, proc),
+                };
+                match &*module {
+                    ModuleType::Erlang(erl, _overlay) => {
+                        if let Some(fun) = erl.functions.get(ident) {
+                            if fun.fun.block_args(*block).len() != call.args.len() {
+                                return self.badarity(vm, proc, &call);
+                            }
+                            proc.stack.set_block(*block, fun.fun.block_location(*block));
+                        }
+                        match self.run_erlang(
+                            vm,
+                            proc,
+                            erl,
+                            ident,
+                            Some((*block, &*environment)),
+                            &call.args,
+                        ) {
+                            Some(res) => Continuation::Term(res),
+                            None => self.undef(vm, proc, &call.args),
+                        }
+                    }
                     ModuleType::Native(_native) => unreachable!(),
                 }
             }
-            Term::CapturedFunction { ident } => {
-                let module = &vm.modules[&ident.module.name];
+            Term::CapturedFunction { ident, version } => {
+                if call.args.len() != ident.arity + 2 {
+                    return self.badarity(vm, proc, &call);
+                }
+                vm.record_coverage(|c| c.record_call(ident));
+                // Local captures stay in their version of the module, other
+                // calls go to the current version.
+                let module = match version {
+                    Some(version) => match vm.module_version(ident.module.name, *version) {
+                        Some(module) => module,
+                        None => return self.killed(vm, proc),
+                    },
+                    None => match vm.module(ident.module.name) {
+                        Some(module) => module,
+                        None => return self.undef(vm, proc, &call.args),
+                    },
+                };
                 println!("{}", ident);
-                match module {
+                match &*module {
                     ModuleType::Erlang(erl, overlay) => {
                         if let Some(native) = overlay {
                             if let Some(res) = self.run_native(vm, proc, native, ident, &call.args)
@@ -72,17 +109,51 @@ impl CallExecutor {
                                 return Continuation::Term(res);
                             }
                         }
-                        Continuation::Term(
-                            self.run_erlang(vm, erl, ident, None, &call.args).unwrap(),
-                        )
+                        if let Some(fun) = erl.functions.get(ident) {
+                            let entry = fun.fun.block_entry();
+                            proc.stack.set_block(entry, fun.fun.block_location(entry));
+                        }
+                        match self.run_erlang(vm, proc, erl, ident, None, &call.args) {
+                            Some(res) => Continuation::Term(res),
+                            None => self.undef(vm, proc, &call.args),
+                        }
+                    }
+                    ModuleType::Native(native) => {
+                        match self.run_native(vm, proc, native, ident, &call.args) {
+                            Some(res) => Continuation::Term(res),
+                            None => self.undef(vm, proc, &call.args),
+                        }
+                    }
+                }
+            }
+            Term::Closure {
+                ident,
+                version,
+                environment,
+            } => {
+                // The environment is passed as the last argument
+                if call.args.len() + 1 != ident.arity + 2 {
+                    return self.badarity(vm, proc, &call);
+                }
+                vm.record_coverage(|c| c.record_call(ident));
+                let module = match vm.module_version(ident.module.name, *version) {
+                    Some(module) => module,
+                    None => return self.killed(vm, proc),
+                };
+                let mut args = call.args.clone();
+                args.push(environment.clone());
+                match &*module {
+                    ModuleType::Erlang(erl, _overlay) => {
+                        if let Some(fun) = erl.functions.get(ident) {
+                            let entry = fun.fun.block_entry();
+                            proc.stack.set_block(entry, fun.fun.block_location(entry));
+                        }
+                        match self.run_erlang(vm, proc, erl, ident, None, &args) {
+                            Some(res) => Continuation::Term(res),
+                            None => self.undef(vm, proc, &call.args),
+                        }
                     }
-                    ModuleType::Native(native) => Continuation::Term(
-                        if let Some(res) = self.run_native(vm, proc, native, ident, &call.args) {
-                            res
-                        } else {
-                            panic!("Could not find native function {}", ident);
-                        },
-                    ),
+                    ModuleType::Native(_native) => unreachable!(),
                 }
             }
             Term::ReturnOk => {
@@ -97,9 +168,43 @@ impl CallExecutor {
                     call.args[2].clone(),
                 )
             }
-            // TODO can't call term type, throw exception
-            _ => unimplemented!(),
+            // Only function calls, which always have a throw continuation,
+            // can target terms that are not callable.
+            _ if call.args.len() >= 2 => {
+                let reason = error_tuple("badfun", call.fun.clone());
+                Continuation::Term(raise(vm, proc, call.args[1].clone(), reason))
+            }
+            _ => panic!("Control flow call to non-callable term {:?}", call.fun),
+        }
+    }
+
+    /// Raises `error:undef` through the throw continuation of a call to a
+    /// function that does not exist.
+    fn undef(&self, vm: &VMState, proc: &mut ProcessContext, args: &[Rc<Term>]) -> Continuation {
+        let reason = Term::new_atom("undef").into();
+        Continuation::Term(raise(vm, proc, args[1].clone(), reason))
+    }
+
+    /// Ends a process that calls into code that has been purged, with the
+    /// `killed` reason it would have been killed with by `code:purge/1`.
+    fn killed(&self, vm: &VMState, proc: &mut ProcessContext) -> Continuation {
+        Continuation::ReturnThrow(
+            Term::new_atom("exit").into(),
+            Term::new_atom("killed").into(),
+            proc.stack.to_term(vm),
+        )
+    }
+
+    /// Raises `error:{badarity, {Fun, Args}}` through the throw continuation
+    /// of a call with the wrong number of arguments.
+    fn badarity(&self, vm: &VMState, proc: &mut ProcessContext, call: &TermCall) -> Continuation {
+        if call.args.len() < 2 {
+            panic!("Control flow call with wrong arity to {:?}", call.fun);
         }
+        let args = Term::slice_to_list(&call.args[2..], Term::Nil.into());
+        let info = Term::Tuple(vec![call.fun.clone(), args]).into();
+        let reason = error_tuple("badarity", info);
+        Continuation::Term(raise(vm, proc, call.args[1].clone(), reason))
     }
 
     pub fn run_native(
@@ -118,8 +223,31 @@ impl CallExecutor {
                 }),
                 NativeReturn::Throw { typ, reason } => Some(TermCall {
                     fun: args[1].clone(),
-                    args: vec![typ, reason, Term::Nil.into()],
+                    args: vec![typ, reason, proc.stack.to_term(vm)],
                 }),
+                NativeReturn::Call {
+                    fun,
+                    args: call_args,
+                } => {
+                    let mut n_args = vec![args[0].clone(), args[1].clone()];
+                    n_args.extend(call_args);
+
+                    match &*fun {
+                        Term::CapturedFunction { ident, .. }
+                        | Term::BoundLambda { ident, .. }
+                        | Term::Closure { ident, .. } => {
+                            proc.stack.push_call(
+                                *ident,
+                                args[0].clone(),
+                                args[1].clone(),
+                                &n_args[2..],
+                            );
+                        }
+                        _ => (),
+                    }
+
+                    Some(TermCall { fun, args: n_args })
+                }
             }
         } else {
             None
@@ -129,6 +257,7 @@ impl CallExecutor {
     pub fn run_erlang(
         &mut self,
         vm: &VMState,
+        proc: &mut ProcessContext,
         module: &ErlangModule,
         ident: &FunctionIdent,
         state: Option<(Block, &[Rc<Term>])>,
@@ -156,8 +285,10 @@ impl CallExecutor {
                 self.binds.insert(*v, t.clone());
             }
 
+            vm.record_coverage(|c| c.record_block(ident, block));
+
             // Execute operation
-            Some(self.run_erlang_op(vm, fun, block))
+            Some(self.run_erlang_op(vm, proc, fun, block))
         } else {
             None
         }
@@ -207,17 +338,21 @@ impl CallExecutor {
         }
     }
 
-    fn make_term(&self, fun: &ErlangFunction, value: Value) -> Rc<Term> {
-        match fun.fun.value_kind(value) {
+    /// Builds the term for a value.
+    /// Fails with an error reason if a primop is applied to terms of the
+    /// wrong type.
+    fn make_term(&self, fun: &ErlangFunction, value: Value) -> Result<Rc<Term>, Rc<Term>> {
+        let term = match fun.fun.value_kind(value) {
             ValueKind::Block(block) => {
                 let live = &fun.live.live_at(block);
                 let mut env = Vec::new();
                 for v in live.iter() {
                     assert!(fun.fun.value_argument(v).is_some());
-                    env.push(self.make_term(fun, v));
+                    env.push(self.make_term(fun, v)?);
                 }
                 Term::BoundLambda {
                     ident: fun.fun.ident().clone(),
+                    version: fun.version,
                     block,
                     environment: env,
                 }
@@ -229,30 +364,30 @@ impl CallExecutor {
                 let reads = fun.fun.primop_reads(prim);
                 match fun.fun.primop_kind(prim) {
                     PrimOpKind::ValueList => {
-                        let terms: Vec<_> = reads.iter().map(|r| self.make_term(fun, *r)).collect();
+                        let terms = self.make_terms(fun, reads)?;
                         Term::ValueList(terms).into()
                     }
                     PrimOpKind::Tuple => {
-                        let terms: Vec<_> = reads.iter().map(|r| self.make_term(fun, *r)).collect();
+                        let terms = self.make_terms(fun, reads)?;
                         Term::Tuple(terms).into()
                     }
                     PrimOpKind::ListCell => {
                         assert!(reads.len() == 2);
-                        let head = self.make_term(fun, reads[0]);
-                        let tail = self.make_term(fun, reads[1]);
+                        let head = self.make_term(fun, reads[0])?;
+                        let tail = self.make_term(fun, reads[1])?;
                         Term::ListCell(head, tail).into()
                     }
                     PrimOpKind::BinOp(BinOp::Equal) => {
                         assert!(reads.len() == 2);
-                        let lhs = self.make_term(fun, reads[0]);
-                        let rhs = self.make_term(fun, reads[1]);
+                        let lhs = self.make_term(fun, reads[0])?;
+                        let rhs = self.make_term(fun, reads[1])?;
                         Term::new_bool(lhs.erl_eq(&*rhs)).into()
                     }
                     PrimOpKind::LogicOp(LogicOp::And) => {
                         let mut acc = true;
                         for read in reads.iter() {
-                            let term = self.make_term(fun, *read);
-                            let res = term.as_boolean().unwrap();
+                            let term = self.make_term(fun, *read)?;
+                            let res = term.as_boolean().ok_or_else(badarg)?;
                             acc = acc & res;
                         }
                         Term::new_bool(acc).into()
@@ -260,16 +395,20 @@ impl CallExecutor {
                     PrimOpKind::LogicOp(LogicOp::Or) => {
                         let mut acc = false;
                         for read in reads.iter() {
-                            let term = self.make_term(fun, *read);
-                            let res = term.as_boolean().unwrap();
+                            let term = self.make_term(fun, *read)?;
+                            let res = term.as_boolean().ok_or_else(badarg)?;
                             acc = acc | res;
                         }
                         Term::new_bool(acc).into()
                     }
                     PrimOpKind::CaptureFunction => {
-                        let module = self.make_term(fun, reads[0]).as_atom().unwrap();
-                        let name = self.make_term(fun, reads[1]).as_atom().unwrap();
-                        let arity = self.make_term(fun, reads[2]).as_usize().unwrap();
+                        let module = self.make_term(fun, reads[0])?.as_atom();
+                        let name = self.make_term(fun, reads[1])?.as_atom();
+                        let arity = self.make_term(fun, reads[2])?.as_usize();
+                        let (module, name, arity) = match (module, name, arity) {
+                            (Some(module), Some(name), Some(arity)) => (module, name, arity),
+                            _ => return Err(badarg()),
+                        };
 
                         let ident = FunctionIdent {
                             module: Ident::with_empty_span(module),
@@ -277,77 +416,190 @@ impl CallExecutor {
                             arity,
                         };
 
-                        Term::CapturedFunction { ident }.into()
+                        Term::CapturedFunction {
+                            ident,
+                            version: None,
+                        }
+                        .into()
+                    }
+                    PrimOpKind::CaptureLocalFunction => {
+                        let name = self.make_term(fun, reads[0])?.as_atom();
+                        let arity = self.make_term(fun, reads[1])?.as_usize();
+                        let (name, arity) = match (name, arity) {
+                            (Some(name), Some(arity)) => (name, arity),
+                            _ => return Err(badarg()),
+                        };
+
+                        let ident = FunctionIdent {
+                            module: fun.fun.ident().module,
+                            name: Ident::with_empty_span(name),
+                            arity,
+                        };
+
+                        Term::CapturedFunction {
+                            ident,
+                            version: Some(fun.version),
+                        }
+                        .into()
+                    }
+                    PrimOpKind::MakeClosure => {
+                        let name = self.make_term(fun, reads[0])?.as_atom();
+                        let arity = self.make_term(fun, reads[1])?.as_usize();
+                        let (name, arity) = match (name, arity) {
+                            (Some(name), Some(arity)) => (name, arity),
+                            _ => return Err(badarg()),
+                        };
+                        let environment = self.make_terms(fun, &reads[2..])?;
+
+                        // The lifted function takes the environment as an
+                        // extra argument
+                        let ident = FunctionIdent {
+                            module: fun.fun.ident().module,
+                            name: Ident::with_empty_span(name),
+                            arity: arity + 1,
+                        };
+
+                        Term::Closure {
+                            ident,
+                            version: fun.version,
+                            environment: Term::Tuple(environment).into(),
+                        }
+                        .into()
+                    }
+                    PrimOpKind::ClosureEnvGet(index) => {
+                        let environment = self.make_term(fun, reads[0])?;
+                        environment
+                            .as_tuple()
+                            .and_then(|entries| entries.get(*index))
+                            .cloned()
+                            .ok_or_else(badarg)?
                     }
                     kind => unimplemented!("{:?}", kind),
                 }
             }
+        };
+        Ok(term)
+    }
+
+    fn make_terms(
+        &self,
+        fun: &ErlangFunction,
+        values: &[Value],
+    ) -> Result<Vec<Rc<Term>>, Rc<Term>> {
+        values.iter().map(|v| self.make_term(fun, *v)).collect()
+    }
+
+    /// Runs the operation of a block.
+    /// Runtime errors are raised through the throw continuation of the
+    /// current stack frame.
+    pub fn run_erlang_op(
+        &mut self,
+        vm: &VMState,
+        proc: &mut ProcessContext,
+        fun: &ErlangFunction,
+        block: Block,
+    ) -> TermCall {
+        match self.try_run_erlang_op(vm, proc, fun, block) {
+            Ok(call) => call,
+            Err(reason) => {
+                let thr = match proc.stack.top() {
+                    Some(frame) => frame.thr.clone(),
+                    None => Term::ReturnThrow.into(),
+                };
+                raise(vm, proc, thr, reason)
+            }
         }
     }
 
-    pub fn run_erlang_op(&mut self, _vm: &VMState, fun: &ErlangFunction, block: Block) -> TermCall {
+    fn try_run_erlang_op(
+        &mut self,
+        vm: &VMState,
+        proc: &mut ProcessContext,
+        fun: &ErlangFunction,
+        block: Block,
+    ) -> Result<TermCall, Rc<Term>> {
         let reads = fun.fun.block_reads(block);
         println!("OP: {:?}", fun.fun.block_kind(block).unwrap());
-        match fun.fun.block_kind(block).unwrap() {
-            OpKind::Call(_) => TermCall {
-                fun: self.make_term(fun, reads[0]),
-                args: reads
-                    .iter()
-                    .skip(1)
-                    .map(|r| self.make_term(fun, *r))
-                    .collect(),
-            },
+        let call = match fun.fun.block_kind(block).unwrap() {
+            OpKind::Call(kind) => {
+                let call = TermCall {
+                    fun: self.make_term(fun, reads[0])?,
+                    args: self.make_terms(fun, &reads[1..])?,
+                };
+                if *kind == CallKind::Function {
+                    match &*call.fun {
+                        Term::CapturedFunction { ident, .. }
+                        | Term::BoundLambda { ident, .. }
+                        | Term::Closure { ident, .. } => {
+                            proc.stack.push_call(
+                                *ident,
+                                call.args[0].clone(),
+                                call.args[1].clone(),
+                                &call.args[2..],
+                            );
+                        }
+                        _ => (),
+                    }
+                }
+                call
+            }
             OpKind::UnpackValueList(num) => {
                 assert!(reads.len() == 2);
-                let term = self.make_term(fun, reads[1]);
-                match &*term {
+                let term = self.make_term(fun, reads[1])?;
+                let args = match &*term {
+                    Term::ValueList(items) if items.len() == *num => items.clone(),
                     Term::ValueList(items) => {
-                        assert!(items.len() == *num);
-                        TermCall {
-                            fun: self.make_term(fun, reads[0]),
-                            args: items.clone(),
-                        }
+                        let tuple = Term::Tuple(items.clone()).into();
+                        return Err(error_tuple("badmatch", tuple));
                     }
-                    _ => TermCall {
-                        fun: self.make_term(fun, reads[0]),
-                        args: vec![term],
-                    },
+                    _ if *num == 1 => vec![term],
+                    _ => return Err(error_tuple("badmatch", term)),
+                };
+                TermCall {
+                    fun: self.make_term(fun, reads[0])?,
+                    args,
                 }
             }
             OpKind::IfBool => {
                 let call_n = if reads.len() == 4 {
-                    let bool_term = self.make_term(fun, reads[3]);
+                    let bool_term = self.make_term(fun, reads[3])?;
                     match bool_term.as_boolean() {
                         Some(true) => 0,
                         Some(false) => 1,
                         None => 2,
                     }
                 } else if reads.len() == 3 {
-                    let bool_term = self.make_term(fun, reads[2]);
+                    let bool_term = self.make_term(fun, reads[2])?;
                     match bool_term.as_boolean() {
                         Some(true) => 0,
                         Some(false) => 1,
-                        None => unreachable!(),
+                        None => return Err(badarg()),
                     }
                 } else {
                     unreachable!()
                 };
 
                 TermCall {
-                    fun: self.make_term(fun, reads[call_n]),
+                    fun: self.make_term(fun, reads[call_n])?,
                     args: vec![],
                 }
             }
+            // The raw trace is already constructed at capture time, the
+            // stack will have changed by the time it is constructed.
             OpKind::TraceCaptureRaw => TermCall {
-                fun: self.make_term(fun, reads[0]),
-                args: vec![Term::Nil.into()],
+                fun: self.make_term(fun, reads[0])?,
+                args: vec![proc.stack.to_term(vm)],
             },
-            OpKind::Match { branches } => self::r#match::match_op(self, fun, branches, block),
+            OpKind::TraceConstruct => TermCall {
+                fun: self.make_term(fun, reads[0])?,
+                args: vec![self.make_term(fun, reads[1])?],
+            },
+            OpKind::Match { branches } => self::r#match::match_op(self, fun, branches, block)?,
             OpKind::Dyn(dyn_op) => {
                 let tid = dyn_op.type_id();
                 match () {
                     _ if tid == TypeId::of::<BinaryConstructStart>() => TermCall {
-                        fun: self.make_term(fun, reads[0]),
+                        fun: self.make_term(fun, reads[0])?,
                         args: vec![Term::Binary(Default::default()).into()],
                     },
                     _ if tid == TypeId::of::<BinaryConstructPush>() => {
@@ -360,100 +612,38 @@ impl CallExecutor {
                         let bin_push = dyn_op.downcast_ref::<BinaryConstructPush>().unwrap();
                         let specifier = bin_push.specifier;
 
-                        let bin_term = self.make_term(fun, bin_ref);
-                        let mut bin = match &*bin_term {
-                            Term::Binary(bin) => (**bin).clone(),
-                            Term::BinarySlice {
-                                buf,
-                                bit_offset,
-                                bit_length,
-                            } => {
-                                let slice =
-                                    BitSlice::with_offset_length(&**buf, *bit_offset, *bit_length);
-                                let mut new = BitVec::new();
-                                new.push(slice);
-                                new
-                            }
-                            _ => panic!(),
-                        };
-
-                        let val_term = self.make_term(fun, reads[3]);
+                        let bin_term = self.make_term(fun, bin_ref)?;
+                        let val_term = self.make_term(fun, value)?;
 
                         assert!(reads.len() == 4 || reads.len() == 5);
-                        let size_term = reads.get(4).map(|r| self.make_term(fun, *r));
-
-                        match specifier {
-                            BinaryEntrySpecifier::Integer {
-                                signed: _,
-                                unit,
-                                endianness,
-                            } => {
-                                let size = size_term.unwrap().as_usize().unwrap();
-                                let bit_size = unit as usize * size;
-
-                                let endian = match endianness {
-                                    Endianness::Big => Endian::Big,
-                                    Endianness::Little => Endian::Little,
-                                    Endianness::Native => Endian::Big,
-                                };
-
-                                let val = val_term.as_integer().unwrap().clone();
-                                let carrier = integer_to_carrier(val, bit_size, endian);
-
-                                bin.push(carrier);
-                            }
-                            BinaryEntrySpecifier::Float {
-                                endianness: Endianness::Big,
-                                unit,
-                            } => {
-                                let size = size_term.unwrap().as_usize().unwrap();
-                                let bit_size = unit as usize * size;
-
-                                assert!(bit_size == 32 || bit_size == 64);
-
-                                let num = match &*val_term {
-                                    Term::Float(flt) => flt.0,
-                                    Term::Integer(int) => {
-                                        let int_f = int.to_i64().unwrap();
-                                        int_f as f64
-                                    }
-                                    _ => panic!(),
-                                };
-
-                                match bit_size {
-                                    32 => bin.push(&num),
-                                    64 => bin.push(&num),
-                                    _ => unreachable!(),
-                                }
-                            }
-                            BinaryEntrySpecifier::Bytes { unit: 1 } => {
-                                let binary = val_term.as_binary().unwrap();
-
-                                if let Some(size_term) = size_term {
-                                    dbg!(&size_term, &binary);
-                                    assert!(size_term.as_usize().unwrap() == binary.len());
-                                }
+                        let size_term = match size {
+                            Some(size) => Some(self.make_term(fun, *size)?),
+                            None => None,
+                        };
 
-                                bin.push(binary);
-                            }
-                            BinaryEntrySpecifier::Bits { unit: 1 } => {
-                                let binary = val_term.as_binary().unwrap();
-                                // TODO validate size
-                                bin.push(binary);
-                            }
-                            k => unimplemented!("{:?}", k),
+                        let mut bin = binary_construct::binary_to_bitvec(&bin_term).unwrap();
+                        let res = binary_construct::push_binary_entry(
+                            &mut bin,
+                            &specifier,
+                            &val_term,
+                            size_term.as_ref().map(|t| &**t),
+                        );
+
+                        if res.is_err() {
+                            return Ok(TermCall {
+                                fun: self.make_term(fun, err_cont)?,
+                                args: vec![],
+                            });
                         }
 
-                        return TermCall {
-                            fun: self.make_term(fun, ok_cont),
-                            args: vec![Term::Binary(bin.into()).into()],
-                        };
-                    }
-                    _ if tid == TypeId::of::<BinaryConstructFinish>() => {
                         TermCall {
-                            fun: self.make_term(fun, reads[0]),
-                            args: vec![self.make_term(fun, reads[1])],
+                            fun: self.make_term(fun, ok_cont)?,
+                            args: vec![Term::Binary(bin.into()).into()],
                         }
+                    }
+                    _ if tid == TypeId::of::<BinaryConstructFinish>() => TermCall {
+                        fun: self.make_term(fun, reads[0])?,
+                        args: vec![self.make_term(fun, reads[1])?],
                     },
                     _ => unimplemented!(),
                 }
@@ -538,24 +728,31 @@ impl CallExecutor {
             //    };
             //}
             OpKind::MapPut { action } => {
-                let map_term = self.make_term(fun, reads[2]);
+                let map_term = self.make_term(fun, reads[2])?;
                 println!("{:#?}", map_term);
-                let mut map = map_term.as_map().unwrap().clone();
+                let mut map = match map_term.as_map() {
+                    Some(map) => map.clone(),
+                    None => return Err(error_tuple("badmap", map_term)),
+                };
 
                 let mut idx = 3;
                 for action in action.iter() {
-                    let key = self.make_term(fun, reads[idx]);
-                    let val = self.make_term(fun, reads[idx + 1]);
+                    let key = self.make_term(fun, reads[idx])?;
+                    let val = self.make_term(fun, reads[idx + 1])?;
                     idx += 2;
 
-                    let replaced = map.insert(key, val);
-                    if *action == MapPutUpdate::Update {
-                        assert!(replaced)
+                    // Updates of keys that are not in the map fail with the key
+                    if *action == MapPutUpdate::Update && map.get(&key).is_none() {
+                        return Ok(TermCall {
+                            fun: self.make_term(fun, reads[1])?,
+                            args: vec![key],
+                        });
                     }
+                    map.insert(key, val);
                 }
 
                 TermCall {
-                    fun: self.make_term(fun, reads[0]),
+                    fun: self.make_term(fun, reads[0])?,
                     args: vec![Term::Map(map).into()],
                 }
             }
@@ -565,13 +762,105 @@ impl CallExecutor {
                 unreachable!();
             }
             kind => unimplemented!("{:?}", kind),
+        };
+        Ok(call)
+    }
+}
+
+/// Raises an `error` class exception through the given throw continuation.
+fn raise(vm: &VMState, proc: &mut ProcessContext, thr: Rc<Term>, reason: Rc<Term>) -> TermCall {
+    TermCall {
+        fun: thr,
+        args: vec![
+            Term::new_atom("error").into(),
+            reason,
+            proc.stack.to_term(vm),
+        ],
+    }
+}
+
+/// Builds a `{Tag, Term}` error reason.
+pub(super) fn error_tuple(tag: &str, term: Rc<Term>) -> Rc<Term> {
+    Term::Tuple(vec![Term::new_atom(tag).into(), term]).into()
+}
+
+fn badarg() -> Rc<Term> {
+    Term::new_atom("badarg").into()
+}
+
+/// Resolves the Erlang function and block a call would start executing.
+/// Returns `None` if the call is to a native function or a continuation that
+/// leaves the interpreter.
+pub fn call_target(vm: &VMState, call: &TermCall) -> Option<(Rc<ErlangFunction>, Block)> {
+    match &*call.fun {
+        Term::BoundLambda {
+            ident,
+            version,
+            block,
+            ..
+        } => match &*vm.module_version(ident.module.name, *version)? {
+            ModuleType::Erlang(erl, _) => Some((erl.functions.get(ident)?.clone(), *block)),
+            ModuleType::Native(_) => None,
+        },
+        Term::CapturedFunction { ident, version } => {
+            let module = match version {
+                Some(version) => vm.module_version(ident.module.name, *version)?,
+                None => vm.module(ident.module.name)?,
+            };
+            match &*module {
+                ModuleType::Erlang(erl, overlay) => {
+                    if overlay.as_ref().map(|o| o.has_fun(ident)).unwrap_or(false) {
+                        return None;
+                    }
+                    let fun = erl.functions.get(ident)?;
+                    Some((fun.clone(), fun.fun.block_entry()))
+                }
+                ModuleType::Native(_) => None,
+            }
         }
+        Term::Closure { ident, version, .. } => {
+            match &*vm.module_version(ident.module.name, *version)? {
+                ModuleType::Erlang(erl, _) => {
+                    let fun = erl.functions.get(ident)?;
+                    Some((fun.clone(), fun.fun.block_entry()))
+                }
+                ModuleType::Native(_) => None,
+            }
+        }
+        _ => None,
     }
 }
 
+/// Returns the values bound when executing the given call, as they would be
+/// seen by the target block.
+/// This is the live environment of the block followed by the block arguments.
+pub fn call_bindings(
+    fun: &ErlangFunction,
+    block: Block,
+    call: &TermCall,
+) -> Vec<(Value, Rc<Term>)> {
+    let mut binds = Vec::new();
+    if let Term::BoundLambda { environment, .. } = &*call.fun {
+        for (v, t) in fun.live.live_at(block).iter().zip(environment.iter()) {
+            binds.push((v, t.clone()));
+        }
+    }
+    // Closures get their environment as the last argument
+    let environment = match &*call.fun {
+        Term::Closure { environment, .. } => Some(environment),
+        _ => None,
+    };
+    let args = call.args.iter().chain(environment);
+    for (v, t) in fun.fun.block_args(block).iter().zip(args) {
+        binds.push((*v, t.clone()));
+    }
+    binds
+}
+
 pub struct ProcessContext {
     pub pid: Pid,
     pub dict: Vec<(Rc<Term>, Rc<Term>)>,
+    pub stack: CallStack,
 }
 
 impl ProcessContext {
@@ -579,6 +868,15 @@ impl ProcessContext {
         ProcessContext {
             pid,
             dict: Vec::new(),
+            stack: CallStack::new(),
+        }
+    }
+
+    /// Whether the process is still executing the old code of a module.
+    pub fn uses_old_code(&self, vm: &VMState, module: Symbol) -> bool {
+        match vm.old_module_version(module) {
+            Some(version) => self.stack.uses_module_version(module, version),
+            None => false,
         }
     }
 }
@@ -0,0 +1,177 @@
+use std::rc::Rc;
+
+use libeir_intern::Symbol;
+use libeir_ir::{Block, FunctionIdent, Location};
+
+use crate::{Term, VMState};
+
+/// An Erlang level stack frame.
+///
+/// Eir is in CPS, so there is no explicit stack in the IR. A frame is entered
+/// by a `CallKind::Function` call, and is left when either the return or the
+/// throw continuation it was entered with is called.
+#[derive(Debug, Clone)]
+pub struct StackFrame {
+    /// The function executing in the frame.
+    pub ident: FunctionIdent,
+    /// The block currently being executed within the frame.
+    /// `None` for native functions.
+    pub block: Option<Block>,
+    /// Location of `block`.
+    pub location: Option<Location>,
+
+    /// Return continuation the frame was entered with.
+    pub ret: Rc<Term>,
+    /// Throw continuation the frame was entered with.
+    pub thr: Rc<Term>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CallStack {
+    frames: Vec<StackFrame>,
+}
+
+impl CallStack {
+    pub fn new() -> Self {
+        CallStack { frames: Vec::new() }
+    }
+
+    /// Registers a function call.
+    /// If the call is passed the same continuations as the top frame, this is
+    /// a tail call and the top frame is replaced.
+    /// `args` are only used for tracing.
+    pub fn push_call(
+        &mut self,
+        ident: FunctionIdent,
+        ret: Rc<Term>,
+        thr: Rc<Term>,
+        args: &[Rc<Term>],
+    ) {
+        let frame = StackFrame {
+            ident,
+            block: None,
+            location: None,
+            ret,
+            thr,
+        };
+
+        if let Some(top) = self.frames.last_mut() {
+            if Rc::ptr_eq(&top.ret, &frame.ret) && Rc::ptr_eq(&top.thr, &frame.thr) {
+                // The replaced frame must end before the new one begins
+                crate::trace::exit_function(&top.ident);
+                crate::trace::enter_function(&frame.ident, args);
+                *top = frame;
+                return;
+            }
+        }
+
+        crate::trace::enter_function(&frame.ident, args);
+        self.frames.push(frame);
+    }
+
+    /// Called with the target of every call.
+    /// If the target is an escape continuation of a frame, that frame and
+    /// every frame above it is popped.
+    pub fn unwind_to(&mut self, target: &Rc<Term>) {
+        let pos = self
+            .frames
+            .iter()
+            .rposition(|f| Rc::ptr_eq(&f.ret, target) || Rc::ptr_eq(&f.thr, target));
+        if let Some(pos) = pos {
+            for frame in self.frames.drain(pos..).rev() {
+                crate::trace::exit_function(&frame.ident);
+            }
+        }
+    }
+
+    pub fn set_block(&mut self, block: Block, location: Location) {
+        if let Some(top) = self.frames.last_mut() {
+            top.block = Some(block);
+            top.location = Some(location);
+        }
+    }
+
+    pub fn top(&self) -> Option<&StackFrame> {
+        self.frames.last()
+    }
+
+    /// Whether any frame returns or throws into the given version of a
+    /// module, meaning the process is still executing that code.
+    pub fn uses_module_version(&self, module: Symbol, version: usize) -> bool {
+        let in_version = |term: &Rc<Term>| match &**term {
+            Term::BoundLambda {
+                ident, version: v, ..
+            } => ident.module.name == module && *v == version,
+            _ => false,
+        };
+        self.frames
+            .iter()
+            .any(|frame| in_version(&frame.ret) || in_version(&frame.thr))
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Iterates the frames, innermost first.
+    pub fn iter(&self) -> impl Iterator<Item = &StackFrame> {
+        self.frames.iter().rev()
+    }
+
+    /// Builds a stack trace in the format used by OTP, a list of
+    /// `{Module, Function, Arity, [{file, File}, {line, Line}]}`, innermost
+    /// frame first.
+    /// The BIFs used to raise exceptions are not included in the trace.
+    pub fn to_term(&self, vm: &VMState) -> Rc<Term> {
+        let mut trace: Rc<Term> = Term::Nil.into();
+        for frame in self.frames.iter() {
+            if is_raise_bif(&frame.ident) {
+                continue;
+            }
+            trace = Term::ListCell(frame_to_term(vm, frame), trace).into();
+        }
+        trace
+    }
+}
+
+fn is_raise_bif(ident: &FunctionIdent) -> bool {
+    ident.module.as_str() == "erlang"
+        && match (ident.name.as_str().get(), ident.arity) {
+            ("error", 1) | ("error", 2) | ("exit", 1) | ("throw", 1) | ("raise", 3) => true,
+            _ => false,
+        }
+}
+
+fn frame_to_term(vm: &VMState, frame: &StackFrame) -> Rc<Term> {
+    let mut info: Rc<Term> = Term::Nil.into();
+
+    let file_line = frame.location.and_then(|location| {
+        let fun = vm.erlang_function(&frame.ident)?;
+        fun.fun.locations.file_line(location)
+    });
+    if let Some((file, line)) = file_line {
+        let line = Term::Tuple(vec![
+            Term::new_atom("line").into(),
+            Term::new_usize(line as usize + 1).into(),
+        ]);
+        let file = Term::Tuple(vec![
+            Term::new_atom("file").into(),
+            Term::slice_to_list(
+                &file
+                    .chars()
+                    .map(|c| Term::new_i64(c as i64).into())
+                    .collect::<Vec<_>>(),
+                Term::Nil.into(),
+            ),
+        ]);
+        info = Term::slice_to_list(&[file.into(), line.into()], info);
+    }
+
+    Term::Tuple(vec![
+        Term::Atom(frame.ident.module.name).into(),
+        Term::Atom(frame.ident.name.name).into(),
+        Term::new_usize(frame.ident.arity).into(),
+        info,
+    ])
+    .into()
+}
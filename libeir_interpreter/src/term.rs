@@ -7,11 +7,13 @@ use libeir_intern::{LocalInternedString, Symbol};
 
 use libeir_ir::{Block, FunctionIdent};
 
-use libeir_util_binary::{BitSlice, BitVec};
+use libeir_util_binary::{BitCarrier, BitRead, BitSlice, BitVec};
 
 use num_bigint::BigInt;
 use num_traits::cast::ToPrimitive;
 
+use libeir_util_number::bigint_to_double;
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
 pub struct Pid(pub usize);
 
@@ -34,6 +36,7 @@ pub enum TermType {
 
     BoundLambda,
     CapturedFunction,
+    Closure,
 
     // Internal
     ValueList,
@@ -96,9 +99,22 @@ impl MapTerm {
         self.map.get(key).cloned()
     }
 
+    pub fn remove(&mut self, key: &Rc<Term>) -> Option<Rc<Term>> {
+        let val = self.map.remove(key)?;
+        if let Ok(idx) = self.sorted.binary_search_by(|(k, _)| k.cmp(key)) {
+            self.sorted.remove(idx);
+        }
+        Some(val)
+    }
+
     pub fn len(&self) -> usize {
         self.map.len()
     }
+
+    /// Iterates the entries of the map in key order.
+    pub fn iter(&self) -> impl Iterator<Item = &(Rc<Term>, Rc<Term>)> {
+        self.sorted.iter()
+    }
 }
 impl PartialEq for MapTerm {
     fn eq(&self, other: &MapTerm) -> bool {
@@ -141,11 +157,25 @@ pub enum Term {
     },
     BoundLambda {
         ident: FunctionIdent,
+        /// The version of the module the lambda runs in.
+        version: usize,
         block: Block,
         environment: Vec<Rc<Term>>,
     },
     CapturedFunction {
         ident: FunctionIdent,
+        /// Set for captures of local functions, which stay in the version of
+        /// the module they were captured in.
+        version: Option<usize>,
+    },
+    /// A closure made by closure converted code. Calling it calls the
+    /// lambda lifted function `ident` with the environment as the last
+    /// argument.
+    Closure {
+        ident: FunctionIdent,
+        version: usize,
+        /// A tuple of the values the closure closes over.
+        environment: Rc<Term>,
     },
 
     // Internal
@@ -165,6 +195,7 @@ impl Term {
             Term::Reference(_) => 3,
             Term::BoundLambda { .. } => 4,
             Term::CapturedFunction { .. } => 4,
+            Term::Closure { .. } => 4,
             //Term::Port(_) => 5,
             Term::Pid(_) => 6,
             Term::Tuple(_) => 7,
@@ -234,16 +265,39 @@ impl PartialEq for Term {
             (
                 BoundLambda {
                     ident: li,
+                    version: lv,
                     block: lb,
                     environment: le,
                 },
                 BoundLambda {
                     ident: ri,
+                    version: rv,
                     block: rb,
                     environment: re,
                 },
-            ) => li == ri && lb == rb && le == re,
-            (CapturedFunction { ident: li }, CapturedFunction { ident: ri }) => li == ri,
+            ) => li == ri && lv == rv && lb == rb && le == re,
+            (
+                CapturedFunction {
+                    ident: li,
+                    version: lv,
+                },
+                CapturedFunction {
+                    ident: ri,
+                    version: rv,
+                },
+            ) => li == ri && lv == rv,
+            (
+                Closure {
+                    ident: li,
+                    version: lv,
+                    environment: le,
+                },
+                Closure {
+                    ident: ri,
+                    version: rv,
+                    environment: re,
+                },
+            ) => li == ri && lv == rv && le == re,
             (ValueList(l), ValueList(r)) => l == r,
             (ReturnOk, ReturnOk) => true,
             (ReturnThrow, ReturnThrow) => true,
@@ -316,22 +370,57 @@ impl PartialOrd for Term {
             (
                 BoundLambda {
                     ident: li,
+                    version: lv,
                     block: lb,
                     environment: le,
                 },
                 BoundLambda {
                     ident: ri,
+                    version: rv,
                     block: rb,
                     environment: re,
                 },
             ) => match li.partial_cmp(ri) {
-                Some(Ordering::Equal) | None => match lb.partial_cmp(rb) {
+                Some(Ordering::Equal) | None => match lv.partial_cmp(rv) {
+                    Some(Ordering::Equal) | None => match lb.partial_cmp(rb) {
+                        Some(Ordering::Equal) | None => le.partial_cmp(re),
+                        non_eq => non_eq,
+                    },
+                    non_eq => non_eq,
+                },
+                non_eq => non_eq,
+            },
+            (
+                CapturedFunction {
+                    ident: li,
+                    version: lv,
+                },
+                CapturedFunction {
+                    ident: ri,
+                    version: rv,
+                },
+            ) => match li.partial_cmp(ri) {
+                Some(Ordering::Equal) | None => lv.partial_cmp(rv),
+                non_eq => non_eq,
+            },
+            (
+                Closure {
+                    ident: li,
+                    version: lv,
+                    environment: le,
+                },
+                Closure {
+                    ident: ri,
+                    version: rv,
+                    environment: re,
+                },
+            ) => match li.partial_cmp(ri) {
+                Some(Ordering::Equal) | None => match lv.partial_cmp(rv) {
                     Some(Ordering::Equal) | None => le.partial_cmp(re),
                     non_eq => non_eq,
                 },
                 non_eq => non_eq,
             },
-            (CapturedFunction { ident: li }, CapturedFunction { ident: ri }) => li.partial_cmp(ri),
             (ValueList(l), ValueList(r)) => l.partial_cmp(r),
             (ReturnOk, ReturnOk) => Some(Ordering::Equal),
             (ReturnThrow, ReturnThrow) => Some(Ordering::Equal),
@@ -369,14 +458,28 @@ impl Hash for Term {
             }
             BoundLambda {
                 ident,
+                version,
                 block,
                 environment,
             } => {
                 ident.hash(state);
+                version.hash(state);
                 block.hash(state);
                 environment.hash(state);
             }
-            CapturedFunction { ident } => ident.hash(state),
+            CapturedFunction { ident, version } => {
+                ident.hash(state);
+                version.hash(state);
+            }
+            Closure {
+                ident,
+                version,
+                environment,
+            } => {
+                ident.hash(state);
+                version.hash(state);
+                environment.hash(state);
+            }
             ValueList(i) => i.hash(state),
             ReturnOk => (),
             ReturnThrow => (),
@@ -390,6 +493,96 @@ impl From<i64> for Term {
     }
 }
 
+fn fmt_binary<C>(f: &mut std::fmt::Formatter, carrier: C) -> std::fmt::Result
+where
+    C: BitRead<T = u8>,
+{
+    write!(f, "<<")?;
+    for idx in 0..carrier.word_len() {
+        if idx != 0 {
+            write!(f, ",")?;
+        }
+        write!(f, "{}", carrier.read_word(idx))?;
+    }
+    write!(f, ">>")
+}
+
+fn fmt_seq(f: &mut std::fmt::Formatter, terms: &[Rc<Term>]) -> std::fmt::Result {
+    for (idx, term) in terms.iter().enumerate() {
+        if idx != 0 {
+            write!(f, ",")?;
+        }
+        write!(f, "{}", term)?;
+    }
+    Ok(())
+}
+
+/// Formats the term roughly the way the Erlang shell would.
+impl std::fmt::Display for Term {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Term::Nil => write!(f, "[]"),
+            Term::Integer(int) => write!(f, "{}", int),
+            Term::Float(flt) => write!(f, "{:?}", flt.0),
+            Term::Atom(atom) => write!(f, "{}", atom),
+            Term::Tuple(elems) => {
+                write!(f, "{{")?;
+                fmt_seq(f, elems)?;
+                write!(f, "}}")
+            }
+            Term::ListCell(head, tail) => {
+                write!(f, "[{}", head)?;
+                let mut tail = tail;
+                loop {
+                    match &**tail {
+                        Term::ListCell(head, next) => {
+                            write!(f, ",{}", head)?;
+                            tail = next;
+                        }
+                        Term::Nil => break,
+                        other => {
+                            write!(f, "|{}", other)?;
+                            break;
+                        }
+                    }
+                }
+                write!(f, "]")
+            }
+            Term::Map(map) => {
+                write!(f, "#{{")?;
+                for (idx, (key, val)) in map.sorted.iter().enumerate() {
+                    if idx != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{} => {}", key, val)?;
+                }
+                write!(f, "}}")
+            }
+            Term::Pid(pid) => write!(f, "<0.{}.0>", pid.0),
+            Term::Reference(reference) => write!(f, "#Ref<0.{}>", reference.0),
+            Term::Binary(bin) => fmt_binary(f, &**bin),
+            Term::BinarySlice {
+                buf,
+                bit_offset,
+                bit_length,
+            } => {
+                let slice = BitSlice::with_offset_length(&**buf, *bit_offset, *bit_length);
+                fmt_binary(f, slice)
+            }
+            Term::BoundLambda { ident, block, .. } => write!(f, "#Fun<{}-{}>", ident, block),
+            Term::CapturedFunction { ident, .. } => write!(f, "fun {}", ident),
+            Term::Closure { ident, .. } => write!(f, "#Fun<{}>", ident),
+            Term::ValueList(elems) => {
+                write!(f, "<")?;
+                fmt_seq(f, elems)?;
+                write!(f, ">")
+            }
+            Term::ReturnOk => write!(f, "#ReturnOk"),
+            Term::ReturnThrow => write!(f, "#ReturnThrow"),
+        }
+    }
+}
+
 pub enum ListIteratorItem {
     Elem(Rc<Term>),
     Tail(Rc<Term>),
@@ -505,6 +698,7 @@ impl Term {
             Term::Map(_) => TermType::Map,
             Term::BoundLambda { .. } => TermType::BoundLambda,
             Term::CapturedFunction { .. } => TermType::CapturedFunction,
+            Term::Closure { .. } => TermType::Closure,
             Term::ValueList(_) => TermType::ValueList,
             Term::ReturnOk => TermType::ReturnOk,
             Term::ReturnThrow => TermType::ReturnThrow,
@@ -573,6 +767,20 @@ impl Term {
         }
     }
 
+    /// Returns the bits of a binary or binary slice term as
+    /// `(buf, bit_offset, bit_length)`.
+    pub fn as_binary_bits(&self) -> Option<(&BitVec, usize, usize)> {
+        match self {
+            Term::Binary(bin) => Some((&**bin, 0, bin.bit_len())),
+            Term::BinarySlice {
+                buf,
+                bit_offset,
+                bit_length,
+            } => Some((&**buf, *bit_offset, *bit_length)),
+            _ => None,
+        }
+    }
+
     pub fn as_map(&self) -> Option<&MapTerm> {
         if let Term::Map(bin) = self {
             Some(bin)
@@ -764,8 +972,6 @@ impl ErlEq for f64 {
 impl ErlEq for Term {
     fn erl_eq(&self, other: &Term) -> bool {
         match (self, other) {
-            (Term::BoundLambda { .. }, _) => unreachable!(),
-            (_, Term::BoundLambda { .. }) => unreachable!(),
             (Term::ValueList(_), _) => unimplemented!(),
             (_, Term::ValueList(_)) => unimplemented!(),
 
@@ -798,16 +1004,19 @@ impl ErlEq for Term {
 
             (Term::Integer(ref i1), Term::Integer(ref i2)) => i1 == i2,
             (Term::Float(ref f1), Term::Float(ref f2)) => f1 == f2,
-            (Term::Integer(_), Term::Float(_)) => unimplemented!(),
-            (Term::Float(_), Term::Integer(_)) => unimplemented!(),
+            (Term::Integer(_), Term::Float(_)) => self.erl_ord(other) == Ordering::Equal,
+            (Term::Float(_), Term::Integer(_)) => self.erl_ord(other) == Ordering::Equal,
             (Term::Atom(ref a1), Term::Atom(ref a2)) => a1 == a2,
             (Term::Tuple(ref v1), Term::Tuple(ref v2)) => {
-                v1.iter().zip(v2).all(|(e1, e2)| e1.erl_eq(e2))
+                v1.len() == v2.len() && v1.iter().zip(v2).all(|(e1, e2)| e1.erl_eq(e2))
             }
-            (
-                Term::CapturedFunction { ident: ref ident1 },
-                Term::CapturedFunction { ident: ref ident2 },
-            ) => ident1 == ident2,
+            (Term::Map(_), Term::Map(_))
+            | (Term::Pid(_), Term::Pid(_))
+            | (Term::Reference(_), Term::Reference(_))
+            | (Term::BoundLambda { .. }, Term::BoundLambda { .. }) => self == other,
+            (Term::Binary(_), _) | (Term::BinarySlice { .. }, _) => self == other,
+            (Term::CapturedFunction { .. }, Term::CapturedFunction { .. }) => self == other,
+            (Term::Closure { .. }, Term::Closure { .. }) => self == other,
             _ => {
                 //crate::trace::warning_args(
                 //    "WARNING: ErlEq might be unimplemented".to_string(),
@@ -842,9 +1051,31 @@ impl ErlOrd for Term {
     fn erl_ord(&self, other: &Term) -> ::std::cmp::Ordering {
         match (self, other) {
             (Term::Integer(val1), Term::Integer(val2)) => val1.cmp(val2),
-            //(Term::Float(val1), Term::Float(val2)) =>
-            //    val1.cmp(val2),
-            (_, _) => unimplemented!(),
+            (Term::Float(val1), Term::Float(val2)) => val1.cmp(val2),
+            (Term::Integer(val1), Term::Float(val2)) => {
+                bigint_to_double(val1).partial_cmp(&val2.0).unwrap()
+            }
+            (Term::Float(val1), Term::Integer(val2)) => {
+                val1.0.partial_cmp(&bigint_to_double(val2)).unwrap()
+            }
+            // Atoms are ordered by their text, not their interned index
+            (Term::Atom(val1), Term::Atom(val2)) => val1.as_str().get().cmp(val2.as_str().get()),
+            // Tuples are ordered by size first
+            (Term::Tuple(val1), Term::Tuple(val2)) => val1.len().cmp(&val2.len()).then_with(|| {
+                val1.iter()
+                    .zip(val2.iter())
+                    .map(|(e1, e2)| e1.erl_ord(e2))
+                    .find(|ord| *ord != Ordering::Equal)
+                    .unwrap_or(Ordering::Equal)
+            }),
+            (Term::ListCell(h1, t1), Term::ListCell(h2, t2)) => {
+                h1.erl_ord(h2).then_with(|| t1.erl_ord(t2))
+            }
+            // Maps are ordered by size first
+            (Term::Map(val1), Term::Map(val2)) => {
+                val1.len().cmp(&val2.len()).then_with(|| val1.cmp(val2))
+            }
+            (_, _) => self.cmp(other),
         }
     }
 }
@@ -1,12 +1,15 @@
+//! Tracing of interpreter execution in the Chrome trace event format.
+//!
+//! Only recorded when built with the `trace` feature, and when recording is
+//! enabled in `libeir_util_prof`. Every Erlang process is displayed as a
+//! separate process in the trace.
 
-//#[cfg(not(feature = "trace"))]
-//#[macro_use]
+#[cfg(not(feature = "trace"))]
 mod dummy;
-//#[cfg(not(feature = "trace"))]
+#[cfg(not(feature = "trace"))]
 use self::dummy as trace;
 
-//#[cfg(feature = "trace")]
-//#[macro_use]
-//mod trace;
+#[cfg(feature = "trace")]
+mod trace;
 
 pub use self::trace::*;
@@ -2,7 +2,10 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
-use crate::module::{ErlangModule, ModuleType, NativeModule};
+use crate::coverage::Coverage;
+use crate::ets::EtsTables;
+use crate::loader::CodeLoader;
+use crate::module::{ErlangFunction, ErlangModule, ModuleType, NativeModule};
 use crate::process::{CallExecutor, Continuation, ProcessContext, TermCall};
 use crate::term::{Pid, Reference, Term};
 
@@ -29,11 +32,35 @@ impl ReferenceGenerator {
     }
 }
 
+/// Errors from loading new code for a module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadError {
+    /// The module still has old code, which has to be purged before another
+    /// version can be loaded.
+    NotPurged,
+    /// The current version of the module is still referenced, for example
+    /// through `VMState::module`, so it can not be replaced.
+    InUse,
+}
+
 pub struct VMState {
-    pub modules: HashMap<Symbol, ModuleType>,
+    /// The current version of every loaded module.
+    pub modules: RefCell<HashMap<Symbol, Rc<ModuleType>>>,
+    /// The old version of modules that have been reloaded, and not yet
+    /// purged. These are always Erlang modules without a native overlay.
+    pub old_modules: RefCell<HashMap<Symbol, Rc<ModuleType>>>,
+    /// Used to load modules that are called, but not yet loaded.
+    pub code_loader: RefCell<Option<CodeLoader>>,
     pub processes: RefCell<Vec<Rc<RefCell<ProcessContext>>>>,
 
     pub ref_gen: RefCell<ReferenceGenerator>,
+
+    /// Names registered with `erlang:register/2`.
+    pub registry: RefCell<HashMap<Symbol, Pid>>,
+    pub ets: RefCell<EtsTables>,
+
+    /// Collected coverage, if enabled.
+    pub coverage: RefCell<Option<Coverage>>,
     // Hashmap of all watches a process has placed on it.
     //pub watches: RefCell<HashMap<Pid, Vec<(Pid, WatchType)>>>,
 
@@ -43,45 +70,145 @@ pub struct VMState {
 impl VMState {
     pub fn new() -> Self {
         VMState {
-            modules: HashMap::new(),
+            modules: RefCell::new(HashMap::new()),
+            old_modules: RefCell::new(HashMap::new()),
+            code_loader: RefCell::new(None),
             processes: RefCell::new(Vec::new()),
             ref_gen: RefCell::new(ReferenceGenerator::new()),
+            registry: RefCell::new(HashMap::new()),
+            ets: RefCell::new(EtsTables::new()),
+            coverage: RefCell::new(None),
             //watches: RefCell::new(HashMap::new()),
             //mailboxes: RefCell::new(HashMap::new()),
         }
     }
 
-    pub fn add_erlang_module(&mut self, module: Module) {
-        let erl_mod = ErlangModule::from_eir(module);
-        match self.modules.remove(&erl_mod.name) {
-            None => {
-                self.modules
-                    .insert(erl_mod.name, ModuleType::Erlang(erl_mod, None));
+    /// Loads an Erlang module, replacing any code already loaded for it.
+    ///
+    /// Unlike `load_module`, this purges an old version of the module to
+    /// make room for the new one, even if processes still run it. Returns
+    /// `true` if an old version was purged.
+    ///
+    /// Panics if the current version of the module is in use.
+    pub fn add_erlang_module(&mut self, module: Module) -> bool {
+        let purged = self.purge_module(module.name().name);
+        self.load_module(module).expect("module is in use");
+        purged
+    }
+
+    /// Loads new code for a module, as with `code:load_binary/3`.
+    ///
+    /// The new code becomes the current version of the module, and the
+    /// previous current version becomes the old version. Fully qualified
+    /// calls always go to the current version, while code that is running
+    /// keeps running in its version.
+    ///
+    /// Fails if there still is an old version, or if the current version is
+    /// in use, in which case nothing changes.
+    pub fn load_module(&self, module: Module) -> Result<(), LoadError> {
+        let name = module.name().name;
+        if self.old_modules.borrow().contains_key(&name) {
+            return Err(LoadError::NotPurged);
+        }
+
+        let mut modules = self.modules.borrow_mut();
+        let current = match modules.remove(&name).map(Rc::try_unwrap) {
+            None => None,
+            Some(Ok(current)) => Some(current),
+            Some(Err(current)) => {
+                modules.insert(name, current);
+                return Err(LoadError::InUse);
             }
-            Some(ModuleType::Native(native)) => {
-                self.modules
-                    .insert(erl_mod.name, ModuleType::Erlang(erl_mod, Some(native)));
+        };
+        let (version, overlay) = match current {
+            None => (0, None),
+            Some(ModuleType::Native(native)) => (0, Some(native)),
+            Some(ModuleType::Erlang(erl, overlay)) => {
+                let version = erl.version + 1;
+                self.old_modules
+                    .borrow_mut()
+                    .insert(name, Rc::new(ModuleType::Erlang(erl, None)));
+                (version, overlay)
             }
-            _ => panic!(),
+        };
+
+        let erl_mod = ErlangModule::from_eir(module, version);
+        modules.insert(name, Rc::new(ModuleType::Erlang(erl_mod, overlay)));
+        Ok(())
+    }
+
+    /// Removes the old version of a module.
+    /// Returns `false` if there was no old version.
+    pub fn purge_module(&self, name: Symbol) -> bool {
+        self.old_modules.borrow_mut().remove(&name).is_some()
+    }
+
+    /// The version number of the old code of a module, if it has any.
+    pub fn old_module_version(&self, name: Symbol) -> Option<usize> {
+        match &**self.old_modules.borrow().get(&name)? {
+            ModuleType::Erlang(erl, _) => Some(erl.version),
+            ModuleType::Native(_) => unreachable!(),
+        }
+    }
+
+    /// Looks up the given version of an Erlang module.
+    /// Returns `None` if that version has been purged.
+    pub fn module_version(&self, name: Symbol, version: usize) -> Option<Rc<ModuleType>> {
+        if let Some(module) = self.module(name) {
+            if let ModuleType::Erlang(erl, _) = &*module {
+                if erl.version == version {
+                    return Some(module);
+                }
+            }
+        }
+
+        let old = self.old_modules.borrow().get(&name).cloned()?;
+        match &*old {
+            ModuleType::Erlang(erl, _) if erl.version == version => Some(old.clone()),
+            _ => None,
+        }
+    }
+
+    /// The number of arguments a fun takes, or `None` if the term is not a
+    /// fun that can be called. A lambda is identified by the function it is
+    /// defined in, its own arity comes from its block.
+    pub fn fun_arity(&self, fun: &Term) -> Option<usize> {
+        match fun {
+            Term::CapturedFunction { ident, .. } => Some(ident.arity),
+            Term::BoundLambda {
+                ident,
+                version,
+                block,
+                ..
+            } => match &*self.module_version(ident.module.name, *version)? {
+                ModuleType::Erlang(erl, _) => {
+                    let fun = erl.functions.get(ident)?;
+                    Some(fun.fun.block_args(*block).len() - 2)
+                }
+                ModuleType::Native(_) => None,
+            },
+            // The arity of the lifted function includes the environment
+            Term::Closure { ident, .. } => Some(ident.arity - 1),
+            _ => None,
         }
     }
 
     pub fn add_native_module(&mut self, module: NativeModule) {
-        match self.modules.remove(&module.name) {
+        let modules = self.modules.get_mut();
+        match modules.remove(&module.name).map(unwrap_module) {
             None => {
-                self.modules.insert(module.name, ModuleType::Native(module));
+                modules.insert(module.name, Rc::new(ModuleType::Native(module)));
             }
             Some(ModuleType::Erlang(erl, None)) => {
-                self.modules
-                    .insert(module.name, ModuleType::Erlang(erl, Some(module)));
+                modules.insert(module.name, Rc::new(ModuleType::Erlang(erl, Some(module))));
             }
             _ => panic!(),
         }
     }
 
     pub fn add_nif_overlay(&mut self, module: NativeModule) {
-        let existing = self.modules.get_mut(&module.name).unwrap();
-        if let ModuleType::Erlang(_, ref mut overlay) = existing {
+        let existing = self.modules.get_mut().get_mut(&module.name).unwrap();
+        if let Some(ModuleType::Erlang(_, ref mut overlay)) = Rc::get_mut(existing) {
             assert!(overlay.is_none());
             *overlay = Some(module);
         } else {
@@ -89,18 +216,75 @@ impl VMState {
         }
     }
 
+    /// Sets the loader used for modules that are called before they are
+    /// loaded.
+    pub fn set_code_loader(&mut self, loader: CodeLoader) {
+        *self.code_loader.get_mut() = Some(loader);
+    }
+
+    /// Looks up a module, loading it through the code loader if it is not
+    /// already loaded.
+    pub fn module(&self, name: Symbol) -> Option<Rc<ModuleType>> {
+        if let Some(module) = self.modules.borrow().get(&name) {
+            return Some(module.clone());
+        }
+
+        let eir = self.code_loader.borrow_mut().as_mut()?.load(name)?;
+        let module = Rc::new(ModuleType::Erlang(ErlangModule::from_eir(eir, 0), None));
+        self.modules.borrow_mut().insert(name, module.clone());
+        Some(module)
+    }
+
+    /// Looks up the Erlang implementation of a function, if any.
+    pub fn erlang_function(&self, ident: &FunctionIdent) -> Option<Rc<ErlangFunction>> {
+        match &*self.module(ident.module.name)? {
+            ModuleType::Erlang(erl, _) => erl.functions.get(ident).cloned(),
+            ModuleType::Native(_) => None,
+        }
+    }
+
+    /// Starts collecting coverage for all following calls.
+    pub fn enable_coverage(&self) {
+        let mut coverage = self.coverage.borrow_mut();
+        if coverage.is_none() {
+            *coverage = Some(Coverage::new());
+        }
+    }
+
+    /// Stops collecting coverage, returning what was collected.
+    pub fn take_coverage(&self) -> Option<Coverage> {
+        self.coverage.borrow_mut().take()
+    }
+
+    pub(crate) fn record_coverage<F>(&self, fun: F)
+    where
+        F: FnOnce(&mut Coverage),
+    {
+        if let Some(coverage) = &mut *self.coverage.borrow_mut() {
+            fun(coverage)
+        }
+    }
+
     pub fn add_builtin_modules(&mut self) {
         self.add_native_module(crate::erl_lib::make_erlang());
         self.add_native_module(crate::erl_lib::make_lists());
         self.add_native_module(crate::erl_lib::make_math());
         self.add_native_module(crate::erl_lib::make_maps());
+        self.add_native_module(crate::erl_lib::make_string());
+        self.add_native_module(crate::erl_lib::make_binary());
+        self.add_native_module(crate::erl_lib::make_unicode());
+        self.add_native_module(crate::erl_lib::make_os());
+        self.add_native_module(crate::erl_lib::make_file());
+        self.add_native_module(crate::erl_lib::make_code());
+        self.add_native_module(crate::erl_lib::make_ets());
     }
 
-    pub fn call(
-        &mut self,
+    /// Creates a new process and the initial call into the given function.
+    pub(crate) fn start_call(
+        &self,
         fun: &FunctionIdent,
         args: &[Term],
-    ) -> Result<Rc<Term>, (Rc<Term>, Rc<Term>, Rc<Term>)> {
+    ) -> (ProcessContext, TermCall) {
         let self_pid = {
             let processes = self.processes.borrow();
             Pid(processes.len())
@@ -108,18 +292,37 @@ impl VMState {
 
         let mut process = ProcessContext::new(self_pid);
 
-        let fun_term = Term::CapturedFunction { ident: fun.clone() };
+        let fun_term = Term::CapturedFunction {
+            ident: fun.clone(),
+            version: None,
+        };
+
+        let ret: Rc<Term> = Term::ReturnOk.into();
+        let thr: Rc<Term> = Term::ReturnThrow.into();
 
         let mut n_args = Vec::new();
-        n_args.push(Term::ReturnOk.into());
-        n_args.push(Term::ReturnThrow.into());
+        n_args.push(ret.clone());
+        n_args.push(thr.clone());
         n_args.extend(args.iter().cloned().map(|v| v.into()));
 
-        let mut continuation = TermCall {
+        crate::trace::set_pid(self_pid);
+        process.stack.push_call(*fun, ret, thr, &n_args[2..]);
+
+        let call = TermCall {
             fun: fun_term.into(),
             args: n_args,
         };
 
+        (process, call)
+    }
+
+    pub fn call(
+        &mut self,
+        fun: &FunctionIdent,
+        args: &[Term],
+    ) -> Result<Rc<Term>, (Rc<Term>, Rc<Term>, Rc<Term>)> {
+        let (mut process, mut continuation) = self.start_call(fun, args);
+
         let mut executor = CallExecutor::new();
         loop {
             match executor.run(self, &mut process, continuation) {
@@ -130,6 +333,46 @@ impl VMState {
         }
     }
 
+    /// Calls a fun from native code, running it to completion on the given
+    /// process.
+    ///
+    /// The fun is entered with fresh return and throw continuations, so this
+    /// returns as soon as the fun itself returns or throws.
+    pub fn call_fun(
+        &self,
+        proc: &mut ProcessContext,
+        fun: Rc<Term>,
+        args: &[Rc<Term>],
+    ) -> Result<Rc<Term>, (Rc<Term>, Rc<Term>, Rc<Term>)> {
+        let ret: Rc<Term> = Term::ReturnOk.into();
+        let thr: Rc<Term> = Term::ReturnThrow.into();
+
+        let mut n_args = Vec::new();
+        n_args.push(ret.clone());
+        n_args.push(thr.clone());
+        n_args.extend(args.iter().cloned());
+
+        match &*fun {
+            Term::CapturedFunction { ident, .. }
+            | Term::BoundLambda { ident, .. }
+            | Term::Closure { ident, .. } => {
+                proc.stack.push_call(*ident, ret, thr, args);
+            }
+            _ => (),
+        }
+
+        let mut continuation = TermCall { fun, args: n_args };
+
+        let mut executor = CallExecutor::new();
+        loop {
+            match executor.run(self, proc, continuation) {
+                Continuation::Term(call) => continuation = call,
+                Continuation::ReturnOk(ret) => return Ok(ret),
+                Continuation::ReturnThrow(r1, r2, r3) => return Err((r1, r2, r3)),
+            }
+        }
+    }
+
     //pub fn call(&mut self, module_name: &str, fun_name: &str, args: Vec<Term>)
     //            -> CallReturn {
     //    let fun_ident = FunctionIdent {
@@ -192,3 +435,13 @@ impl VMState {
     //    process.return_val.take().unwrap()
     //}
 }
+
+/// Takes a module out of its `Rc` in order to modify it.
+/// Modules are only modified like this while setting up the VM, when nothing
+/// else holds on to them.
+fn unwrap_module(module: Rc<ModuleType>) -> ModuleType {
+    match Rc::try_unwrap(module) {
+        Ok(module) => module,
+        Err(_) => panic!("module is in use"),
+    }
+}
@@ -28,6 +28,11 @@ pub enum EqualityFail {
         right: Value,
     },
 
+    MismatchingPrimOp {
+        left: PrimOp,
+        right: PrimOp,
+    },
+
     PrimReadsLength {
         left: PrimOp,
         right: PrimOp,
@@ -215,6 +220,12 @@ fn traverse_value<'a>(ctx: &mut EqCtx<'a>, l: Value, r: Value) -> Result<(), Equ
             Ok(())
         }
         (ValueKind::PrimOp(lp), ValueKind::PrimOp(rp)) => {
+            if ctx.lf.primop_kind(lp) != ctx.rf.primop_kind(rp) {
+                return Err(EqualityFail::MismatchingPrimOp {
+                    left: lp,
+                    right: rp,
+                });
+            }
             let l_reads = ctx.lf.primop_reads(lp);
             let r_reads = ctx.rf.primop_reads(rp);
             if l_reads.len() != r_reads.len() {
@@ -0,0 +1,312 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{Block, CallKind, Function, FunctionTree, LiveValues, OpKind};
+use crate::{PrimOpKind, Value, ValueKind};
+
+impl Function {
+    pub fn escape_analysis(&self, live: &LiveValues, tree: &FunctionTree) -> EscapeAnalysis {
+        EscapeAnalysis::new(self, live, tree)
+    }
+}
+
+/// How a value that implies a heap allocation is used.
+///
+/// Ordered from least to most restrictive, a value used in several ways is
+/// classified as the greatest of them.
+///
+/// Not to be confused with the escape continuations of a function, this is
+/// about where a constructed term ends up.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ConstructionUse {
+    /// The value is only taken apart, by a `Match` on it, or for closures by
+    /// calling it. It never needs to exist as a term.
+    Destructured,
+    /// The value is returned from the function it is used in, but not
+    /// otherwise stored.
+    Returned,
+    /// The value is stored in another term, passed to a call, thrown, or
+    /// otherwise needs to exist as a term.
+    Escapes,
+}
+
+/// # Escape analysis
+/// Finds the values that imply a heap allocation, and classifies how each
+/// of them is used. These are:
+/// * `Tuple`, `ListCell` and `Map` primops.
+/// * `MakeClosure` primops.
+/// * Captured blocks that have free variables, these are bound closures.
+///
+/// A value passed to a block as a control flow argument is followed through
+/// the block argument. A value nested within another primop is always
+/// considered to escape, even when the outer value does not.
+///
+/// Only blocks reachable in the `FunctionTree` are considered.
+#[derive(Debug, Clone)]
+pub struct EscapeAnalysis {
+    constructions: BTreeMap<Value, ConstructionUse>,
+}
+
+impl EscapeAnalysis {
+    pub fn new(fun: &Function, live: &LiveValues, tree: &FunctionTree) -> Self {
+        let mut owner = BTreeMap::new();
+        for (entry, function) in tree.functions.iter() {
+            for block in function.scope.iter() {
+                owner.insert(*block, *entry);
+            }
+        }
+
+        let ctx = EscapeCtx { fun, tree, owner };
+
+        let mut values = BTreeSet::new();
+        for block in ctx.owner.keys() {
+            fun.block_walk_nested_values::<_, ()>(*block, &mut |value| {
+                if ctx.is_construction(live, value) {
+                    values.insert(value);
+                }
+                Ok(())
+            })
+            .unwrap();
+        }
+
+        let constructions = values
+            .iter()
+            .map(|value| (*value, ctx.classify(*value)))
+            .collect();
+
+        EscapeAnalysis { constructions }
+    }
+
+    /// How the value is used, or `None` if it does not imply an allocation.
+    pub fn escape(&self, value: Value) -> Option<ConstructionUse> {
+        self.constructions.get(&value).cloned()
+    }
+
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (Value, ConstructionUse)> + 'a {
+        self.constructions.iter().map(|(v, e)| (*v, *e))
+    }
+}
+
+struct EscapeCtx<'a> {
+    fun: &'a Function,
+    tree: &'a FunctionTree,
+    /// The entry of the function every reachable block belongs to.
+    owner: BTreeMap<Block, Block>,
+}
+
+impl<'a> EscapeCtx<'a> {
+    fn is_construction(&self, live: &LiveValues, value: Value) -> bool {
+        match self.fun.value_kind(value) {
+            ValueKind::PrimOp(prim) => match self.fun.primop_kind(prim) {
+                PrimOpKind::Tuple
+                | PrimOpKind::ListCell
+                | PrimOpKind::Map
+                | PrimOpKind::MakeClosure => true,
+                _ => false,
+            },
+            ValueKind::Block(block) => {
+                block != self.tree.root_fun
+                    && self.tree.functions.contains_key(&block)
+                    && live.live_at(block).iter().next().is_some()
+            }
+            _ => false,
+        }
+    }
+
+    fn is_closure(&self, value: Value) -> bool {
+        match self.fun.value_kind(value) {
+            ValueKind::PrimOp(prim) => *self.fun.primop_kind(prim) == PrimOpKind::MakeClosure,
+            ValueKind::Block(_) => true,
+            _ => false,
+        }
+    }
+
+    fn classify(&self, value: Value) -> ConstructionUse {
+        let is_closure = self.is_closure(value);
+
+        let mut escape = ConstructionUse::Destructured;
+        let mut walked = BTreeSet::new();
+        let mut to_walk = vec![value];
+
+        while let Some(current) = to_walk.pop() {
+            if !walked.insert(current) {
+                continue;
+            }
+
+            for usage in self.fun.value_usages(current).iter() {
+                // Uses in unreachable blocks don't matter
+                let owner = match self.owner.get(&usage) {
+                    Some(owner) => *owner,
+                    None => continue,
+                };
+
+                let usage_escape = self.usage(current, is_closure, usage, owner, &mut to_walk);
+                escape = escape.max(usage_escape);
+
+                if escape == ConstructionUse::Escapes {
+                    return escape;
+                }
+            }
+        }
+
+        escape
+    }
+
+    /// How `value` is used by the operation in `block`.
+    fn usage(
+        &self,
+        value: Value,
+        is_closure: bool,
+        block: Block,
+        owner: Block,
+        to_walk: &mut Vec<Value>,
+    ) -> ConstructionUse {
+        let fun = self.fun;
+        let reads = fun.block_reads(block);
+        let kind = fun.block_kind(block).unwrap();
+
+        let mut escape = ConstructionUse::Destructured;
+        for (idx, read) in reads.iter().enumerate() {
+            if *read != value {
+                let mut nested = false;
+                fun.value_walk_nested_values::<_, ()>(*read, &mut |v| {
+                    nested |= v == value;
+                    Ok(())
+                })
+                .unwrap();
+
+                if nested {
+                    return ConstructionUse::Escapes;
+                }
+                continue;
+            }
+
+            let read_escape = match (kind, idx) {
+                (OpKind::Call(_), 0) if is_closure => ConstructionUse::Destructured,
+                (OpKind::Call(CallKind::ControlFlow), n) if n > 0 => {
+                    let target = reads[0];
+                    if Some(target) == self.tree.functions[&owner].ret {
+                        ConstructionUse::Returned
+                    } else if let Some(target_block) = fun.value_block(target) {
+                        // A jump, the value lives on in the block argument
+                        match fun.block_args(target_block).get(n - 1) {
+                            Some(arg) => {
+                                to_walk.push(*arg);
+                                ConstructionUse::Destructured
+                            }
+                            None => ConstructionUse::Escapes,
+                        }
+                    } else {
+                        ConstructionUse::Escapes
+                    }
+                }
+                (OpKind::Match { .. }, 1) => ConstructionUse::Destructured,
+                _ => ConstructionUse::Escapes,
+            };
+            escape = escape.max(read_escape);
+        }
+
+        escape
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConstructionUse;
+
+    use crate::text::LowerMap;
+    use crate::Function;
+
+    fn analyze(text: &str) -> (Function, LowerMap, super::EscapeAnalysis) {
+        let (ir, map) = crate::parse_function_map_unwrap(text);
+        let live = ir.live_values();
+        let tree = ir.func_tree(&live, true);
+        let escape = ir.escape_analysis(&live, &tree);
+        (ir, map, escape)
+    }
+
+    #[test]
+    fn destructured_tuple() {
+        let (ir, map, escape) = analyze(
+            "
+a'foo':a'bar'/2 {
+    entry(%ret, %thr, %a, %b):
+        match {%a, %b} {
+            {} arity 2 => unpack;
+        };
+    unpack(%x, %y):
+        %ret(%y);
+}
+",
+        );
+
+        let entry = map.get_block("entry");
+        let tuple = ir.block_reads(entry)[1];
+
+        assert!(escape.escape(tuple) == Some(ConstructionUse::Destructured));
+        assert!(escape.iter().count() == 1);
+    }
+
+    #[test]
+    fn returned_and_escaping() {
+        let (ir, map, escape) = analyze(
+            "
+a'foo':a'bar'/2 {
+    entry(%ret, %thr, %a, %b):
+        next([%a | %b]);
+    next(%list):
+        a'foo':a'baz'/1({%a, %b}) => call_ret except %thr;
+    call_ret(%r):
+        %ret(%list);
+}
+",
+        );
+
+        let entry = map.get_block("entry");
+        let next = map.get_block("next");
+        let list = ir.block_reads(entry)[1];
+        let tuple = ir.block_reads(next)[3];
+
+        assert!(escape.escape(list) == Some(ConstructionUse::Returned));
+        assert!(escape.escape(tuple) == Some(ConstructionUse::Escapes));
+    }
+
+    #[test]
+    fn nested_construction_escapes() {
+        let (ir, map, escape) = analyze(
+            "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %a):
+        match {{%a}} {
+            {} arity 1 => unpack;
+        };
+    unpack(%inner):
+        %ret(%inner);
+}
+",
+        );
+
+        let entry = map.get_block("entry");
+        let outer = ir.block_reads(entry)[1];
+        let inner = ir.primop_reads(ir.value_primop(outer).unwrap())[0];
+
+        assert!(escape.escape(outer) == Some(ConstructionUse::Destructured));
+        assert!(escape.escape(inner) == Some(ConstructionUse::Escapes));
+    }
+
+    #[test]
+    fn bound_closure() {
+        let (ir, map, escape) = analyze(
+            "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %a):
+        %ret(inner);
+    inner(%iret, %ithr, %b):
+        %iret(%a);
+}
+",
+        );
+
+        let inner = ir.block_value(map.get_block("inner"));
+        assert!(escape.escape(inner) == Some(ConstructionUse::Returned));
+    }
+}
@@ -1,6 +1,7 @@
 use crate::OpKind;
 use crate::{Function, FunctionBuilder};
 
+use super::datatypes::{MangleTarget, ToT};
 use super::{MangleBlock, MangleValue, ToValue};
 
 /// Trait used to generalize a single mangling implementation over
@@ -78,13 +79,24 @@ impl<'b, 'c> MangleReceiver<'b> for CopyMangleReceiver<'c, 'b> {
     fn to_fun<'a>(&'a self) -> &'a Function {
         self.to.fun()
     }
-    fn map_const(&mut self, _val: MangleValue) -> ToValue {
-        unimplemented!()
+    fn map_const(&mut self, val: MangleValue) -> ToValue {
+        match val {
+            MangleTarget::From(from_val) => {
+                let from_const = self.from.value_const(from_val.inner()).unwrap();
+                let to_const = self.to.cons_mut().import(self.from.cons(), from_const);
+                ToT(self.to.value(to_const))
+            }
+            MangleTarget::To(to_val) => to_val,
+        }
     }
     fn map_free_value(&mut self, _val: MangleValue) -> ToValue {
+        // Free values can't be carried across containers, they need to
+        // be renamed before the mangle is run.
         panic!()
     }
-    fn map_block_op(&mut self, _block: MangleBlock) -> OpKind {
-        unimplemented!()
+    fn map_block_op(&mut self, block: MangleBlock) -> OpKind {
+        block
+            .map_fun(&*self, |f, b| f.block_kind(b).unwrap().clone())
+            .inner()
     }
 }
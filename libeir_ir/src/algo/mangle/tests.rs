@@ -268,3 +268,30 @@ a'foo':a'bar'/0 {
     //    ),
     //}
 }
+
+#[test]
+fn mangle_across() {
+    let (ir, map) = crate::parse_function_map_unwrap(
+        "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %a):
+        %t = {a'foo', %a};
+        b1(%t);
+    b1(%b):
+        %ret(%b);
+}
+",
+    );
+
+    let entry = map.get_block("entry");
+
+    let mut new_ir = crate::Function::new(ir.span(), *ir.ident());
+    let mut b = new_ir.builder();
+
+    let mut mangler = Mangler::new();
+    mangler.start(super::MangleFrom(entry));
+    let new_entry = mangler.run_across(&ir, &mut b);
+    b.block_set_entry(new_entry);
+
+    assert!(b.fun().graph_eq(new_entry, &ir, ir.block_entry()).is_ok());
+}
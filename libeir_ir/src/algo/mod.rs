@@ -1,6 +1,8 @@
 pub mod equality;
+pub mod escape;
 pub mod func_tree;
 pub mod live;
 pub mod mangle;
 pub mod op_branches;
+pub mod tail_call;
 pub mod validate;
@@ -0,0 +1,253 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use petgraph::graphmap::DiGraphMap;
+
+use libeir_intern::Ident;
+
+use crate::{AtomicTerm, CallKind, ConstKind, OpKind, PrimOpKind};
+use crate::{Block, Function, FunctionIdent, Module, Value};
+
+/// A call to a function in the same module, made in tail position of the
+/// root function of the container.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TailCall {
+    /// The block containing the `CallKind::Function` operation.
+    pub block: Block,
+    pub callee: FunctionIdent,
+    /// Whether the callee is a local capture. A qualified call, even to the
+    /// module itself, always calls the latest loaded version of the module,
+    /// so only local calls can be resolved statically.
+    pub local: bool,
+}
+
+impl Function {
+    /// Finds all tail calls to functions in the same module.
+    ///
+    /// A call is in tail position when it passes the `ret` and `thr`
+    /// continuations of the entry block unchanged. Both local and qualified
+    /// calls are included, see `TailCall::local`. Calls made from closures
+    /// within the function are never tail calls of the function itself.
+    pub fn tail_calls(&self) -> Vec<TailCall> {
+        let entry = self.block_entry();
+        let entry_args = self.block_args(entry);
+        if entry_args.len() < 2 {
+            return Vec::new();
+        }
+        let ret = entry_args[0];
+        let thr = entry_args[1];
+
+        let mut calls = Vec::new();
+        for block in self.block_graph().dfs_iter() {
+            if let Some(OpKind::Call(CallKind::Function)) = self.block_kind(block) {
+                let reads = self.block_reads(block);
+                if reads[1] != ret || reads[2] != thr {
+                    continue;
+                }
+                if let Some((callee, local)) = self.local_callee(reads[0], reads.len() - 3) {
+                    calls.push(TailCall {
+                        block,
+                        callee,
+                        local,
+                    });
+                }
+            }
+        }
+        calls
+    }
+
+    /// If the value is a constant capture of a function in the same module
+    /// with the given arity, returns that function, and whether the capture
+    /// is local.
+    fn local_callee(&self, value: Value, arity: usize) -> Option<(FunctionIdent, bool)> {
+        let prim = self.value_primop(value)?;
+        let reads = self.primop_reads(prim);
+
+        let (module, name, arity_val) = match self.primop_kind(prim) {
+            PrimOpKind::CaptureFunction => (Some(reads[0]), reads[1], reads[2]),
+            PrimOpKind::CaptureLocalFunction => (None, reads[0], reads[1]),
+            _ => return None,
+        };
+
+        let atom = |value: Value| match self.const_kind(self.value_const(value)?) {
+            ConstKind::Atomic(AtomicTerm::Atom(atom)) => Some(atom.0),
+            _ => None,
+        };
+
+        let own_module = self.ident().module;
+        if let Some(module) = module {
+            if atom(module)? != own_module.name {
+                return None;
+            }
+        }
+
+        match self.const_kind(self.value_const(arity_val)?) {
+            ConstKind::Atomic(AtomicTerm::Int(int)) if int.value() == arity as i64 => (),
+            _ => return None,
+        }
+
+        let ident = FunctionIdent {
+            module: own_module,
+            name: Ident::with_empty_span(atom(name)?),
+            arity,
+        };
+        Some((ident, module.is_none()))
+    }
+}
+
+/// # Tail recursion analysis
+/// Builds the graph of tail calls between the functions of a module, and
+/// groups the functions that are tail recursive.
+///
+/// A function is self recursive if it tail calls itself. Functions are
+/// mutually recursive if they can reach each other through tail calls, they
+/// then form a recursion group. A function can be both.
+#[derive(Debug, Clone)]
+pub struct TailRecursion {
+    calls: BTreeMap<FunctionIdent, Vec<TailCall>>,
+    /// Recursion groups with more than one function.
+    groups: Vec<Vec<FunctionIdent>>,
+    group_of: BTreeMap<FunctionIdent, usize>,
+}
+
+impl Module {
+    pub fn tail_recursion(&self) -> TailRecursion {
+        TailRecursion::new(self)
+    }
+}
+
+impl TailRecursion {
+    pub fn new(module: &Module) -> Self {
+        let mut calls = BTreeMap::new();
+        let mut graph = DiGraphMap::new();
+
+        for fun_def in module.function_iter() {
+            let fun = fun_def.function();
+            let ident = *fun.ident();
+            graph.add_node(ident);
+
+            let tail_calls = fun.tail_calls();
+            for call in tail_calls.iter() {
+                if module.ident_index(&call.callee).is_some() {
+                    graph.add_edge(ident, call.callee, ());
+                }
+            }
+            calls.insert(ident, tail_calls);
+        }
+
+        let mut groups = Vec::new();
+        let mut group_of = BTreeMap::new();
+        for mut scc in petgraph::algo::kosaraju_scc(&graph) {
+            if scc.len() > 1 {
+                scc.sort();
+                for ident in scc.iter() {
+                    group_of.insert(*ident, groups.len());
+                }
+                groups.push(scc);
+            }
+        }
+
+        TailRecursion {
+            calls,
+            groups,
+            group_of,
+        }
+    }
+
+    /// The tail calls made by the function to functions in the module.
+    pub fn tail_calls(&self, ident: &FunctionIdent) -> &[TailCall] {
+        self.calls.get(ident).map(|c| c.as_slice()).unwrap_or(&[])
+    }
+
+    /// The tail calls the function makes to itself.
+    pub fn self_tail_calls<'a>(
+        &'a self,
+        ident: &'a FunctionIdent,
+    ) -> impl Iterator<Item = &'a TailCall> + 'a {
+        self.tail_calls(ident)
+            .iter()
+            .filter(move |call| call.callee == *ident)
+    }
+
+    pub fn is_self_recursive(&self, ident: &FunctionIdent) -> bool {
+        self.self_tail_calls(ident).next().is_some()
+    }
+
+    /// The group of mutually tail recursive functions the function belongs
+    /// to, if any. Sorted, and always contains the function itself.
+    pub fn recursion_group(&self, ident: &FunctionIdent) -> Option<&[FunctionIdent]> {
+        self.group_of
+            .get(ident)
+            .map(|idx| self.groups[*idx].as_slice())
+    }
+
+    pub fn is_mutually_recursive(&self, ident: &FunctionIdent) -> bool {
+        self.group_of.contains_key(ident)
+    }
+
+    pub fn recursion_groups(&self) -> impl Iterator<Item = &[FunctionIdent]> {
+        self.groups.iter().map(|g| g.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libeir_intern::Ident;
+
+    use crate::FunctionIdent;
+
+    fn ident(name: &str, arity: usize) -> FunctionIdent {
+        FunctionIdent {
+            module: Ident::from_str("foo"),
+            name: Ident::from_str(name),
+            arity,
+        }
+    }
+
+    #[test]
+    fn self_and_mutual_recursion() {
+        let module = crate::parse_module_unwrap(
+            "
+a'foo' {
+    a'count'/1 {
+        entry(%ret, %thr, %n):
+            a'erlang':a'-'/2(%n, 1) => next except %thr;
+        next(%m):
+            a'foo':a'count'/1(%m) => %ret except %thr;
+    }
+    a'even'/1 {
+        entry(%ret, %thr, %n):
+            a'foo':a'odd'/1(%n) => %ret except %thr;
+    }
+    a'odd'/1 {
+        entry(%ret, %thr, %n):
+            a'foo':a'even'/1(%n) => %ret except %thr;
+    }
+    a'body'/1 {
+        entry(%ret, %thr, %n):
+            a'foo':a'body'/1(%n) => next except %thr;
+        next(%r):
+            %ret(%r);
+    }
+}
+",
+        );
+
+        let analysis = module.tail_recursion();
+
+        assert!(analysis.is_self_recursive(&ident("count", 1)));
+        assert!(!analysis.is_mutually_recursive(&ident("count", 1)));
+        assert!(!analysis.tail_calls(&ident("count", 1))[0].local);
+
+        assert!(!analysis.is_self_recursive(&ident("even", 1)));
+        assert!(analysis.is_mutually_recursive(&ident("even", 1)));
+        let group = analysis.recursion_group(&ident("odd", 1)).unwrap();
+        assert!(group.len() == 2);
+        assert!(group.contains(&ident("even", 1)));
+
+        // Not in tail position
+        assert!(!analysis.is_self_recursive(&ident("body", 1)));
+        assert!(analysis.tail_calls(&ident("body", 1)).is_empty());
+
+        assert!(analysis.recursion_groups().count() == 1);
+    }
+}
@@ -211,6 +211,42 @@ impl ConstantContainer {
         }
     }
 
+    /// Copies a constant from another container into this one.
+    pub fn import(&mut self, from: &ConstantContainer, val: Const) -> Const {
+        match &from.const_values[val] {
+            ConstKind::Atomic(atomic) => self.from(atomic.clone()),
+            ConstKind::ListCell { head, tail } => {
+                let head = self.import(from, *head);
+                let tail = self.import(from, *tail);
+                self.list_cell(head, tail)
+            }
+            ConstKind::Tuple { entries } => {
+                let mut builder = TupleBuilder::new();
+                for entry in entries.as_slice(&from.const_pool) {
+                    let entry = self.import(from, *entry);
+                    builder.push(entry, self);
+                }
+                builder.finish(self)
+            }
+            ConstKind::Map { keys, values } => {
+                let mut new_keys = EntityList::new();
+                for key in keys.as_slice(&from.const_pool) {
+                    let key = self.import(from, *key);
+                    new_keys.push(key, &mut self.const_pool);
+                }
+                let mut new_values = EntityList::new();
+                for value in values.as_slice(&from.const_pool) {
+                    let value = self.import(from, *value);
+                    new_values.push(value, &mut self.const_pool);
+                }
+                self.from(ConstKind::Map {
+                    keys: new_keys,
+                    values: new_values,
+                })
+            }
+        }
+    }
+
     pub fn tuple_builder(&self) -> TupleBuilder {
         TupleBuilder::new()
     }
@@ -136,6 +136,7 @@ impl<'a> FunctionBuilder<'a> {
         &self.fun
     }
     pub fn fun_mut(&mut self) -> &mut Function {
+        self.fun.analysis_cache.invalidate();
         &mut self.fun
     }
 
@@ -191,6 +192,8 @@ impl<'a> FunctionBuilder<'a> {
     /// Updates the successors in the graph from the reads.
     /// Mainly used in the builder.
     pub(crate) fn graph_update_block(&mut self, block: Block) {
+        self.fun.analysis_cache.invalidate();
+
         let mut block_buf = self.block_buf.take().unwrap();
         let mut value_buf = self.value_buf.take().unwrap();
         debug_assert!(block_buf.is_empty());
@@ -286,6 +289,7 @@ impl<'a> FunctionBuilder<'a> {
     }
 
     pub fn block_set_entry(&mut self, block: Block) {
+        self.fun.analysis_cache.invalidate();
         self.fun.entry_block = Some(block);
     }
 
@@ -297,6 +301,8 @@ impl<'a> FunctionBuilder<'a> {
         #[cfg(debug_assertions)]
         self.fun().graph_validate_block(block);
 
+        self.fun.analysis_cache.invalidate();
+
         let mut value_buf = self.value_buf.take().unwrap();
         debug_assert!(value_buf.is_empty());
 
@@ -351,6 +357,7 @@ impl<'a> FunctionBuilder<'a> {
         }
 
         self.fun.blocks[block].reads = new_reads;
+        self.fun.analysis_cache.invalidate();
     }
 
     pub fn block_copy_body_map<F>(&mut self, from: Block, to: Block, mut map: F)
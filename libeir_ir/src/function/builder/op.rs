@@ -97,6 +97,38 @@ impl<'a> FunctionBuilder<'a> {
         cont
     }
 
+    pub fn op_trace_construct_next(
+        &mut self,
+        span: SourceSpan,
+        block: Block,
+        next: Value,
+        raw_trace: Value,
+    ) {
+        let data = self.fun.blocks.get_mut(block).unwrap();
+        assert!(data.op.is_none());
+        assert!(data.reads.is_empty());
+
+        data.op = Some(OpKind::TraceConstruct);
+        data.reads.push(next, &mut self.fun.pool.value);
+        data.reads.push(raw_trace, &mut self.fun.pool.value);
+
+        self.graph_update_block(block);
+    }
+    pub fn op_trace_construct(
+        &mut self,
+        span: SourceSpan,
+        block: Block,
+        raw_trace: Value,
+    ) -> Block {
+        let cont = self.fun.block_insert();
+        let cont_val = self.value(cont);
+        self.fun.block_arg_insert(cont);
+
+        self.op_trace_construct_next(span, block, cont_val, raw_trace);
+
+        cont
+    }
+
     pub fn op_intrinsic<'b, O: OpBuild>(
         &'b mut self,
         block: Block,
@@ -294,6 +294,74 @@ impl<'a> FunctionBuilder<'a> {
             .push_with_location(ValueKind::PrimOp(primop), Some(loc))
     }
 
+    pub fn prim_capture_local_function<F, A>(&mut self, span: SourceSpan, f: F, a: A) -> Value
+    where
+        F: IntoValue,
+        A: IntoValue,
+    {
+        let f_val = self.value(f);
+        let a_val = self.value(a);
+
+        let mut entries_list = EntityList::new();
+        entries_list.push(f_val, &mut self.fun.pool.value);
+        entries_list.push(a_val, &mut self.fun.pool.value);
+
+        let loc = self.fun.locations.location(None, None, None, None, span);
+        let primop = self.fun.primops.push(
+            PrimOpData {
+                op: PrimOpKind::CaptureLocalFunction,
+                reads: entries_list,
+            },
+            &self.fun.pool,
+        );
+        self.fun
+            .values
+            .push_with_location(ValueKind::PrimOp(primop), Some(loc))
+    }
+
+    pub fn prim_make_closure<F, A>(&mut self, span: SourceSpan, f: F, a: A, env: &[Value]) -> Value
+    where
+        F: IntoValue,
+        A: IntoValue,
+    {
+        let f_val = self.value(f);
+        let a_val = self.value(a);
+
+        let mut entries_list = EntityList::new();
+        entries_list.push(f_val, &mut self.fun.pool.value);
+        entries_list.push(a_val, &mut self.fun.pool.value);
+        entries_list.extend(env.iter().cloned(), &mut self.fun.pool.value);
+
+        let loc = self.fun.locations.location(None, None, None, None, span);
+        let primop = self.fun.primops.push(
+            PrimOpData {
+                op: PrimOpKind::MakeClosure,
+                reads: entries_list,
+            },
+            &self.fun.pool,
+        );
+        self.fun
+            .values
+            .push_with_location(ValueKind::PrimOp(primop), Some(loc))
+    }
+
+    pub fn prim_closure_env_get(&mut self, span: SourceSpan, env: Value, index: usize) -> Value {
+        let mut entries_list = EntityList::new();
+        entries_list.push(env, &mut self.fun.pool.value);
+
+        let loc = self.fun.locations.location(None, None, None, None, span);
+        let primop = self.fun.primops.push(
+            PrimOpData {
+                op: PrimOpKind::ClosureEnvGet(index),
+                reads: entries_list,
+            },
+            &self.fun.pool,
+        );
+        self.fun
+            .values
+            .push_with_location(ValueKind::PrimOp(primop), Some(loc))
+    }
+
     pub fn prim_from_kind(&mut self, span: SourceSpan, op: PrimOpKind, vals: &[Value]) -> Value {
         match op {
             PrimOpKind::ValueList => self.prim_value_list(vals),
@@ -302,6 +370,18 @@ impl<'a> FunctionBuilder<'a> {
                 assert!(vals.len() == 3);
                 self.prim_capture_function(span, vals[0], vals[1], vals[2])
             }
+            PrimOpKind::CaptureLocalFunction => {
+                assert!(vals.len() == 2);
+                self.prim_capture_local_function(span, vals[0], vals[1])
+            }
+            PrimOpKind::MakeClosure => {
+                assert!(vals.len() >= 2);
+                self.prim_make_closure(span, vals[0], vals[1], &vals[2..])
+            }
+            PrimOpKind::ClosureEnvGet(index) => {
+                assert!(vals.len() == 1);
+                self.prim_closure_env_get(span, vals[0], index)
+            }
             PrimOpKind::LogicOp(op) => self.prim_logic_op(span, op, vals),
             PrimOpKind::BinOp(op) => {
                 assert!(vals.len() == 2);
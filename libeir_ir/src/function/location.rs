@@ -101,6 +101,46 @@ impl LocationContainer {
         locs
     }
 
+    /// The terminals of a location, outermost first.
+    pub fn terminals(&self, location: Location) -> &[LocationTerminal] {
+        self.locations[location]
+            .terminals
+            .as_slice(&self.terminal_pool)
+    }
+
+    pub fn terminal_file(&self, terminal: LocationTerminal) -> Option<&str> {
+        self.terminals[terminal].file.as_ref().map(|s| s.as_str())
+    }
+
+    /// Zero based line index in the origin file.
+    pub fn terminal_line(&self, terminal: LocationTerminal) -> Option<u32> {
+        self.terminals[terminal].line
+    }
+
+    pub fn terminal_module(&self, terminal: LocationTerminal) -> Option<&str> {
+        self.terminals[terminal].module.as_ref().map(|s| s.as_str())
+    }
+
+    pub fn terminal_entity(&self, terminal: LocationTerminal) -> Option<&str> {
+        self.terminals[terminal].entity.as_ref().map(|s| s.as_str())
+    }
+
+    pub fn terminal_span(&self, terminal: LocationTerminal) -> SourceSpan {
+        self.terminals[terminal].span
+    }
+
+    /// The file and zero based line of the innermost terminal of the
+    /// location that has both.
+    pub fn file_line(&self, location: Location) -> Option<(&str, u32)> {
+        self.terminals(location).iter().rev().find_map(|terminal| {
+            let data = &self.terminals[*terminal];
+            match (&data.file, data.line) {
+                (Some(file), Some(line)) => Some((file.as_str(), line)),
+                _ => None,
+            }
+        })
+    }
+
     pub fn location_empty(&mut self) -> Location {
         self.locations.push(
             LocationData {
@@ -12,6 +12,7 @@ use libeir_util_datastructures::dedup_aux_primary_map::DedupAuxPrimaryMap;
 use libeir_diagnostics::SourceSpan;
 
 use crate::constant::{Const, ConstKind, ConstantContainer};
+use crate::graph::AnalysisCache;
 use crate::{ArcDialect, FunctionIdent};
 
 pub mod builder;
@@ -31,7 +32,7 @@ use value::ValueMap;
 pub use value::{Value, ValueKind};
 
 mod location;
-pub use location::{Location, LocationContainer};
+pub use location::{Location, LocationContainer, LocationTerminal};
 
 mod format;
 pub use format::{ContainerDebug, ContainerDebugAdapter};
@@ -137,6 +138,8 @@ pub struct Function {
     // Auxiliary information
     pub constant_values: HashSet<Value>,
     pub locations: LocationContainer,
+
+    pub(crate) analysis_cache: AnalysisCache,
 }
 
 impl Function {
@@ -562,6 +565,8 @@ impl Function {
             constant_values: HashSet::new(),
 
             locations: LocationContainer::new(),
+
+            analysis_cache: AnalysisCache::default(),
         }
     }
 
@@ -75,4 +75,23 @@ pub enum PrimOpKind {
     /// used instead. This will throw badarg at capture time.
     /// `(m, f, a)`
     CaptureFunction,
+
+    /// Returns a function of arity `a` in the module of the current function.
+    /// This differs from `CaptureFunction` on the current module in that
+    /// calls stay within the version of the module the capture was made in
+    /// when code is reloaded.
+    /// `(f, a)`
+    CaptureLocalFunction,
+
+    /// Returns a function of arity `a` that calls the lambda lifted function
+    /// `f/(a+1)` in the module of the current function, with the closure
+    /// environment as the last argument.
+    /// Produced by closure conversion.
+    /// `(f, a, env..)`
+    MakeClosure,
+
+    /// Reads entry `n` of a closure environment.
+    /// Produced by closure conversion.
+    /// `(env)`
+    ClosureEnvGet(usize),
 }
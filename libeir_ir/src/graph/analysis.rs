@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+use petgraph::visit::{IntoNeighborsDirected, Visitable};
+
+use crate::{Block, Function, Value};
+
+use super::{DominatorTree, GraphOrder, LoopForest};
+
+/// The control flow analyses of a graph, calculated together.
+///
+/// Exits of the graph are where control leaves the function being
+/// analyzed, in CPS these are the calls to continuations that are not
+/// blocks of the function, like the return and throw continuations, and
+/// calls to other functions. The post-dominators are relative to the exits.
+#[derive(Debug, Clone)]
+pub struct GraphAnalysis<N> {
+    order: GraphOrder<N>,
+    dominators: DominatorTree<N>,
+    post_dominators: DominatorTree<N>,
+    loops: LoopForest<N>,
+}
+
+impl<N> GraphAnalysis<N>
+where
+    N: Copy + Ord + Hash,
+{
+    pub fn new<G, F>(graph: G, entry: N, is_exit: F) -> Self
+    where
+        G: IntoNeighborsDirected<NodeId = N> + Visitable,
+        F: FnMut(N) -> bool,
+    {
+        let order = GraphOrder::new(graph, entry);
+        let dominators = DominatorTree::new(graph, entry);
+        let post_dominators = DominatorTree::new_post_dominators(graph, entry, is_exit);
+        let loops = LoopForest::new(graph, &dominators);
+
+        GraphAnalysis {
+            order,
+            dominators,
+            post_dominators,
+            loops,
+        }
+    }
+
+    pub fn order(&self) -> &GraphOrder<N> {
+        &self.order
+    }
+
+    pub fn dominators(&self) -> &DominatorTree<N> {
+        &self.dominators
+    }
+
+    pub fn post_dominators(&self) -> &DominatorTree<N> {
+        &self.post_dominators
+    }
+
+    pub fn loops(&self) -> &LoopForest<N> {
+        &self.loops
+    }
+}
+
+impl Function {
+    /// Whether control can leave the function from the block.
+    fn block_is_exit(&self, block: Block) -> bool {
+        match self.op_branch_len(block) {
+            None | Some(0) => true,
+            Some(_) => self
+                .op_branch_iter(block)
+                .any(|target| self.value_block(target).is_none()),
+        }
+    }
+
+    /// Analyses over the `LiveBlockGraph`, from the entry block.
+    ///
+    /// Block captures are edges in this graph, so closures are dominated by
+    /// the block capturing them.
+    ///
+    /// Cached until the function is changed through a `FunctionBuilder`.
+    pub fn block_analysis(&self) -> Arc<GraphAnalysis<Block>> {
+        let mut cache = self.analysis_cache.inner.lock().unwrap();
+        if let Some(analysis) = &cache.blocks {
+            return analysis.clone();
+        }
+
+        let graph = self.live_block_graph();
+        let analysis = Arc::new(GraphAnalysis::new(&graph, self.block_entry(), |block| {
+            self.block_is_exit(block)
+        }));
+
+        cache.blocks = Some(analysis.clone());
+        analysis
+    }
+
+    /// Analyses over the `ControlFlowGraph` calculated from `entry`.
+    ///
+    /// Only explicit control flow are edges, so each closure is analyzed
+    /// separately, with its block value as the entry. Continuations that
+    /// are not blocks, like the return continuation, are exits.
+    ///
+    /// Cached until the function is changed through a `FunctionBuilder`.
+    pub fn control_flow_analysis(&self, entry: Value) -> Arc<GraphAnalysis<Value>> {
+        let mut cache = self.analysis_cache.inner.lock().unwrap();
+        if let Some(analysis) = cache.control_flow.get(&entry) {
+            return analysis.clone();
+        }
+
+        let mut graph = self.control_flow_graph();
+        graph.calculate(self, entry);
+        let analysis = Arc::new(GraphAnalysis::new(&graph, entry, |value| {
+            self.value_block(value)
+                .map(|block| self.block_is_exit(block))
+                .unwrap_or(true)
+        }));
+
+        cache.control_flow.insert(entry, analysis.clone());
+        analysis
+    }
+}
+
+#[derive(Default)]
+struct CachedAnalyses {
+    blocks: Option<Arc<GraphAnalysis<Block>>>,
+    control_flow: HashMap<Value, Arc<GraphAnalysis<Value>>>,
+}
+
+/// Holds the graph analyses of a `Function`.
+///
+/// Cloning gives an empty cache, the analyses are calculated again when
+/// needed.
+#[derive(Default)]
+pub(crate) struct AnalysisCache {
+    inner: Mutex<CachedAnalyses>,
+}
+
+impl AnalysisCache {
+    pub(crate) fn invalidate(&mut self) {
+        let inner = self.inner.get_mut().unwrap();
+        inner.blocks = None;
+        inner.control_flow.clear();
+    }
+}
+
+impl Clone for AnalysisCache {
+    fn clone(&self) -> Self {
+        AnalysisCache::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn diamond() {
+        let (ir, map) = crate::parse_function_map_unwrap(
+            "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %a):
+        if_bool %a left right;
+    left():
+        join(a'l');
+    right():
+        join(a'r');
+    join(%r):
+        %ret(%r);
+}
+",
+        );
+
+        let entry = map.get_block("entry");
+        let left = map.get_block("left");
+        let right = map.get_block("right");
+        let join = map.get_block("join");
+
+        let analysis = ir.block_analysis();
+
+        let order = analysis.order();
+        assert!(order.number(entry) == Some(0));
+        assert!(order.number(join) == Some(3));
+
+        let doms = analysis.dominators();
+        assert!(doms.immediate_dominator(entry) == None);
+        assert!(doms.immediate_dominator(left) == Some(entry));
+        assert!(doms.immediate_dominator(join) == Some(entry));
+        assert!(doms.dominates(entry, join));
+        assert!(!doms.dominates(left, join));
+        assert!(doms.frontier(left).unwrap().contains(&join));
+        assert!(doms.frontier(join).unwrap().is_empty());
+
+        let post_doms = analysis.post_dominators();
+        assert!(post_doms.immediate_dominator(entry) == Some(join));
+        assert!(post_doms.immediate_dominator(left) == Some(join));
+        assert!(post_doms.immediate_dominator(join) == None);
+        // Both branches are control dependent on the `if_bool`
+        assert!(post_doms.frontier(left).unwrap().contains(&entry));
+
+        assert!(analysis.loops().loops().count() == 0);
+    }
+
+    #[test]
+    fn nested_loops() {
+        let (ir, map) = crate::parse_function_map_unwrap(
+            "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %a):
+        outer(%a);
+    outer(%o):
+        inner(%o);
+    inner(%i):
+        if_bool %i inner_latch outer_latch;
+    inner_latch():
+        inner(%i);
+    outer_latch():
+        if_bool %o outer done;
+    done():
+        %ret(%o);
+}
+",
+        );
+
+        let entry = map.get_block("entry");
+        let outer = map.get_block("outer");
+        let inner = map.get_block("inner");
+        let inner_latch = map.get_block("inner_latch");
+        let outer_latch = map.get_block("outer_latch");
+        let done = map.get_block("done");
+
+        let analysis = ir.block_analysis();
+        let loops = analysis.loops();
+
+        assert!(loops.loops().count() == 2);
+        assert!(loops.loop_depth(entry) == 0);
+        assert!(loops.loop_depth(outer) == 1);
+        assert!(loops.loop_depth(inner) == 2);
+        assert!(loops.loop_depth(inner_latch) == 2);
+        assert!(loops.loop_depth(outer_latch) == 1);
+        assert!(loops.loop_depth(done) == 0);
+
+        assert!(loops.is_loop_header(outer));
+        assert!(loops.is_loop_header(inner));
+        let inner_loop = loops.innermost_loop(inner).unwrap();
+        let outer_loop = loops.innermost_loop(outer).unwrap();
+        assert!(loops.loop_parent(inner_loop) == Some(outer_loop));
+        assert!(loops.loop_contains(outer_loop, inner_latch));
+        assert!(loops.loop_data(outer_loop).latches.contains(&outer_latch));
+
+        assert!(analysis.post_dominators().dominates(done, inner));
+    }
+
+    #[test]
+    fn continuations_are_exits() {
+        let (ir, map) = crate::parse_function_map_unwrap(
+            "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %a):
+        a'foo':a'baz'/1(%a) => call_ret except %thr;
+    call_ret(%r):
+        %ret(%r);
+}
+",
+        );
+
+        let entry = map.get_block("entry");
+        let call_ret = map.get_block("call_ret");
+
+        // The call can throw, so the return does not post-dominate it
+        let blocks = ir.block_analysis();
+        assert!(blocks.post_dominators().immediate_dominator(entry) == None);
+        assert!(blocks.post_dominators().contains(call_ret));
+
+        let entry_val = ir.block_value(entry);
+        let call_ret_val = ir.block_value(call_ret);
+        let thr = ir.block_args(entry)[1];
+
+        let cfg = ir.control_flow_analysis(entry_val);
+        assert!(cfg.dominators().immediate_dominator(call_ret_val) == Some(entry_val));
+        assert!(cfg.dominators().immediate_dominator(thr) == Some(entry_val));
+        assert!(!cfg.post_dominators().dominates(call_ret_val, entry_val));
+    }
+
+    #[test]
+    fn invalidated_by_builder() {
+        let (mut ir, map) = crate::parse_function_map_unwrap(
+            "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %a):
+        next();
+    next():
+        %ret(%a);
+}
+",
+        );
+
+        let entry = map.get_block("entry");
+        let next = map.get_block("next");
+
+        let before = ir.block_analysis();
+        assert!(before.dominators().contains(next));
+        assert!(std::sync::Arc::ptr_eq(&before, &ir.block_analysis()));
+
+        {
+            let mut b = ir.builder();
+            let ret = b.fun().block_args(entry)[0];
+            let a = b.fun().block_args(entry)[2];
+            b.block_clear(entry);
+            b.op_call_flow(entry, ret, &[a]);
+        }
+
+        let after = ir.block_analysis();
+        assert!(!after.dominators().contains(next));
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::{BTreeSet, HashMap};
+use std::hash::Hash;
+
+use petgraph::visit::{DfsPostOrder, IntoNeighborsDirected, Visitable, Walker};
+use petgraph::Direction;
+
+/// A reverse postorder numbering of the nodes reachable from an entry.
+///
+/// In reverse postorder every node comes before its successors, except
+/// along back edges.
+#[derive(Debug, Clone)]
+pub struct GraphOrder<N> {
+    nodes: Vec<N>,
+    index: HashMap<N, usize>,
+}
+
+impl<N> GraphOrder<N>
+where
+    N: Copy + Eq + Hash,
+{
+    pub fn new<G>(graph: G, entry: N) -> Self
+    where
+        G: IntoNeighborsDirected<NodeId = N> + Visitable,
+    {
+        let mut nodes: Vec<N> = DfsPostOrder::new(graph, entry).iter(graph).collect();
+        nodes.reverse();
+
+        let index = nodes.iter().enumerate().map(|(n, v)| (*v, n)).collect();
+        GraphOrder { nodes, index }
+    }
+
+    /// The nodes in reverse postorder.
+    pub fn nodes(&self) -> &[N] {
+        &self.nodes
+    }
+
+    /// The reverse postorder number of the node, `None` if it is not
+    /// reachable.
+    pub fn number(&self, node: N) -> Option<usize> {
+        self.index.get(&node).cloned()
+    }
+
+    pub fn contains(&self, node: N) -> bool {
+        self.index.contains_key(&node)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+/// A dominator tree, or a post-dominator tree.
+///
+/// Post-dominators are calculated on the reversed graph from a virtual exit
+/// node that all exits of the graph flow into. The virtual exit is not a
+/// node of the tree, nodes it immediately post-dominates have no immediate
+/// post-dominator. Nodes that never reach an exit, like the nodes of an
+/// infinite loop, are not part of the post-dominator tree.
+///
+/// Calculated with the algorithm from "A Simple, Fast Dominance Algorithm"
+/// by Cooper, Harvey and Kennedy.
+#[derive(Debug, Clone)]
+pub struct DominatorTree<N> {
+    /// The nodes in the tree, in reverse postorder of the graph the tree
+    /// was calculated on.
+    nodes: Vec<N>,
+    index: HashMap<N, usize>,
+    /// `None` for the root, or for nodes immediately dominated by the
+    /// virtual exit.
+    idom: Vec<Option<usize>>,
+    children: Vec<Vec<N>>,
+    frontiers: Vec<BTreeSet<N>>,
+}
+
+impl<N> DominatorTree<N>
+where
+    N: Copy + Ord + Hash,
+{
+    /// Calculates the dominator tree of the nodes reachable from `entry`.
+    pub fn new<G>(graph: G, entry: N) -> Self
+    where
+        G: IntoNeighborsDirected<NodeId = N> + Visitable,
+    {
+        let order = GraphOrder::new(graph, entry);
+        let num = order.len();
+
+        let mut preds = vec![Vec::new(); num];
+        for (n, node) in order.nodes.iter().enumerate() {
+            preds[n].extend(
+                graph
+                    .neighbors_directed(*node, Direction::Incoming)
+                    .filter_map(|p| order.number(p)),
+            );
+        }
+
+        // In reverse postorder the entry is always first
+        let idom = calculate_idoms(num, &[0], &preds);
+        Self::from_idoms(order.nodes, idom, &preds)
+    }
+
+    /// Calculates the post-dominator tree of the nodes reachable from
+    /// `entry`. `is_exit` tells which nodes can leave the graph, in addition
+    /// to the nodes without successors.
+    pub fn new_post_dominators<G, F>(graph: G, entry: N, mut is_exit: F) -> Self
+    where
+        G: IntoNeighborsDirected<NodeId = N> + Visitable,
+        F: FnMut(N) -> bool,
+    {
+        let order = GraphOrder::new(graph, entry);
+        let num = order.len();
+
+        // Successors in the forward graph are predecessors in the reversed
+        // graph. Exits are those that flow into the virtual exit.
+        let mut rev_succs = vec![Vec::new(); num];
+        let mut rev_preds = vec![Vec::new(); num];
+        let mut exits = Vec::new();
+        for (n, node) in order.nodes.iter().enumerate() {
+            rev_preds[n].extend(
+                graph
+                    .neighbors_directed(*node, Direction::Outgoing)
+                    .filter_map(|s| order.number(s)),
+            );
+            for pred in rev_preds[n].iter() {
+                rev_succs[*pred].push(n);
+            }
+            if rev_preds[n].is_empty() || is_exit(*node) {
+                exits.push(n);
+            }
+        }
+
+        // Reverse postorder of the reversed graph, starting from the virtual
+        // exit.
+        let mut post_order = Vec::new();
+        let mut visited = vec![false; num];
+        for exit in exits.iter() {
+            reverse_post_order_visit(*exit, &rev_succs, &mut visited, &mut post_order);
+        }
+        post_order.reverse();
+
+        let mut remap = vec![None; num];
+        for (new, old) in post_order.iter().enumerate() {
+            remap[*old] = Some(new);
+        }
+
+        let nodes: Vec<N> = post_order.iter().map(|n| order.nodes[*n]).collect();
+        let preds: Vec<Vec<usize>> = post_order
+            .iter()
+            .map(|n| rev_preds[*n].iter().filter_map(|p| remap[*p]).collect())
+            .collect();
+        let roots: Vec<usize> = exits.iter().map(|n| remap[*n].unwrap()).collect();
+
+        let idom = calculate_idoms(nodes.len(), &roots, &preds);
+        Self::from_idoms(nodes, idom, &preds)
+    }
+
+    fn from_idoms(nodes: Vec<N>, idom: Vec<Option<usize>>, preds: &[Vec<usize>]) -> Self {
+        let num = nodes.len();
+        let index = nodes.iter().enumerate().map(|(n, v)| (*v, n)).collect();
+
+        let mut children = vec![Vec::new(); num];
+        for (n, dom) in idom.iter().enumerate() {
+            if let Some(dom) = dom {
+                children[*dom].push(nodes[n]);
+            }
+        }
+
+        let mut frontiers = vec![BTreeSet::new(); num];
+        for (n, node_preds) in preds.iter().enumerate() {
+            if node_preds.len() < 2 {
+                continue;
+            }
+            for pred in node_preds.iter() {
+                let mut runner = Some(*pred);
+                while let Some(curr) = runner {
+                    if Some(curr) == idom[n] {
+                        break;
+                    }
+                    frontiers[curr].insert(nodes[n]);
+                    runner = idom[curr];
+                }
+            }
+        }
+
+        DominatorTree {
+            nodes,
+            index,
+            idom,
+            children,
+            frontiers,
+        }
+    }
+
+    pub fn contains(&self, node: N) -> bool {
+        self.index.contains_key(&node)
+    }
+
+    /// The nodes in the tree, every node comes after its dominators.
+    pub fn nodes(&self) -> &[N] {
+        &self.nodes
+    }
+
+    pub fn immediate_dominator(&self, node: N) -> Option<N> {
+        let idx = *self.index.get(&node)?;
+        self.idom[idx].map(|dom| self.nodes[dom])
+    }
+
+    /// All dominators of the node, starting with the node itself and
+    /// walking up the tree.
+    pub fn dominators<'a>(&'a self, node: N) -> impl Iterator<Item = N> + 'a {
+        let mut curr = self.index.get(&node).cloned();
+        std::iter::from_fn(move || {
+            let idx = curr?;
+            curr = self.idom[idx];
+            Some(self.nodes[idx])
+        })
+    }
+
+    /// Whether `a` dominates `b`. Every node dominates itself.
+    pub fn dominates(&self, a: N, b: N) -> bool {
+        self.contains(a) && self.dominators(b).any(|n| n == a)
+    }
+
+    pub fn strictly_dominates(&self, a: N, b: N) -> bool {
+        a != b && self.dominates(a, b)
+    }
+
+    /// The nodes immediately dominated by the node.
+    pub fn children(&self, node: N) -> &[N] {
+        match self.index.get(&node) {
+            Some(idx) => &self.children[*idx],
+            None => &[],
+        }
+    }
+
+    /// The dominance frontier of the node. For a post-dominator tree, this
+    /// is the set of nodes the node is control dependent on.
+    pub fn frontier(&self, node: N) -> Option<&BTreeSet<N>> {
+        self.index.get(&node).map(|idx| &self.frontiers[*idx])
+    }
+}
+
+fn reverse_post_order_visit(
+    node: usize,
+    succs: &[Vec<usize>],
+    visited: &mut [bool],
+    post_order: &mut Vec<usize>,
+) {
+    // Iterative to not overflow the stack on large functions
+    let mut stack = vec![(node, 0)];
+    if visited[node] {
+        return;
+    }
+    visited[node] = true;
+
+    while let Some((curr, next)) = stack.pop() {
+        if let Some(succ) = succs[curr].get(next) {
+            stack.push((curr, next + 1));
+            if !visited[*succ] {
+                visited[*succ] = true;
+                stack.push((*succ, 0));
+            }
+        } else {
+            post_order.push(curr);
+        }
+    }
+}
+
+/// `preds` and the result are indexed by reverse postorder number. Roots
+/// have no immediate dominator, when there are several they are dominated
+/// by a virtual root.
+fn calculate_idoms(num: usize, roots: &[usize], preds: &[Vec<usize>]) -> Vec<Option<usize>> {
+    const VIRTUAL: usize = std::usize::MAX;
+
+    let mut idom = vec![None; num];
+    for root in roots.iter() {
+        idom[*root] = Some(VIRTUAL);
+    }
+
+    let intersect = |idom: &[Option<usize>], mut a: usize, mut b: usize| {
+        while a != b {
+            if a == VIRTUAL || b == VIRTUAL {
+                return VIRTUAL;
+            }
+            while a > b {
+                a = idom[a].unwrap();
+                if a == VIRTUAL {
+                    return VIRTUAL;
+                }
+            }
+            while b > a {
+                b = idom[b].unwrap();
+                if b == VIRTUAL {
+                    return VIRTUAL;
+                }
+            }
+        }
+        a
+    };
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for node in 0..num {
+            if roots.contains(&node) {
+                continue;
+            }
+
+            let mut new_idom = None;
+            for pred in preds[node].iter() {
+                if idom[*pred].is_none() {
+                    continue;
+                }
+                new_idom = match new_idom {
+                    None => Some(*pred),
+                    Some(curr) => Some(intersect(&idom, curr, *pred)),
+                };
+            }
+
+            if new_idom.is_some() && new_idom != idom[node] {
+                idom[node] = new_idom;
+                changed = true;
+            }
+        }
+    }
+
+    idom.iter()
+        .map(|dom| dom.filter(|d| *d != VIRTUAL))
+        .collect()
+}
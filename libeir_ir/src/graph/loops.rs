@@ -0,0 +1,155 @@
+use std::collections::{BTreeSet, HashMap};
+use std::hash::Hash;
+
+use cranelift_entity::{entity_impl, PrimaryMap};
+
+use petgraph::visit::IntoNeighborsDirected;
+use petgraph::Direction;
+
+use super::DominatorTree;
+
+/// A natural loop in a `LoopForest`.
+#[derive(Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Loop(u32);
+entity_impl!(Loop, "loop");
+
+#[derive(Debug, Clone)]
+pub struct LoopData<N> {
+    /// The single entry of the loop, dominates every node in it.
+    pub header: N,
+    /// All nodes of the loop, including the header and the nodes of nested
+    /// loops.
+    pub nodes: BTreeSet<N>,
+    /// The sources of the back edges to the header.
+    pub latches: BTreeSet<N>,
+    pub parent: Option<Loop>,
+    /// 1 for outermost loops.
+    pub depth: usize,
+}
+
+/// # Loop nesting forest
+/// The natural loops of a graph, and how they nest.
+///
+/// A back edge is an edge whose target dominates its source. Back edges
+/// with the same target form a single loop. Cycles that are entered at more
+/// than one node, which are irreducible, have no back edge and are not
+/// considered loops.
+///
+/// In CPS, a loop is a block that is jumped back to with new arguments. A
+/// call to a function in the module is not an edge, so recursion does not
+/// show up as a loop.
+#[derive(Debug, Clone)]
+pub struct LoopForest<N> {
+    loops: PrimaryMap<Loop, LoopData<N>>,
+    innermost: HashMap<N, Loop>,
+}
+
+impl<N> LoopForest<N>
+where
+    N: Copy + Ord + Hash,
+{
+    pub fn new<G>(graph: G, dominators: &DominatorTree<N>) -> Self
+    where
+        G: IntoNeighborsDirected<NodeId = N>,
+    {
+        let mut headers: Vec<(N, BTreeSet<N>)> = Vec::new();
+        for node in dominators.nodes().iter() {
+            let latches: BTreeSet<N> = graph
+                .neighbors_directed(*node, Direction::Incoming)
+                .filter(|pred| dominators.dominates(*node, *pred))
+                .collect();
+            if !latches.is_empty() {
+                headers.push((*node, latches));
+            }
+        }
+
+        // Headers are in dominator tree order, outer loops come first
+        let mut loops: PrimaryMap<Loop, LoopData<N>> = PrimaryMap::new();
+        for (header, latches) in headers {
+            let mut nodes = BTreeSet::new();
+            nodes.insert(header);
+
+            let mut to_walk: Vec<N> = latches.iter().cloned().collect();
+            while let Some(node) = to_walk.pop() {
+                if !nodes.insert(node) {
+                    continue;
+                }
+                to_walk.extend(
+                    graph
+                        .neighbors_directed(node, Direction::Incoming)
+                        .filter(|pred| dominators.contains(*pred)),
+                );
+            }
+
+            // The innermost loop enclosing this one is the last one created
+            // that contains the header.
+            let parent = loops
+                .iter()
+                .filter(|(_, data)| data.nodes.contains(&header))
+                .map(|(lop, _)| lop)
+                .last();
+            let depth = parent.map(|p| loops[p].depth + 1).unwrap_or(1);
+
+            loops.push(LoopData {
+                header,
+                nodes,
+                latches,
+                parent,
+                depth,
+            });
+        }
+
+        let mut innermost = HashMap::new();
+        for (lop, data) in loops.iter() {
+            for node in data.nodes.iter() {
+                match innermost.get(node) {
+                    Some(prev) if loops[*prev].depth >= data.depth => (),
+                    _ => {
+                        innermost.insert(*node, lop);
+                    }
+                }
+            }
+        }
+
+        LoopForest { loops, innermost }
+    }
+
+    pub fn loops(&self) -> impl Iterator<Item = Loop> {
+        self.loops.keys()
+    }
+
+    pub fn loop_data(&self, lop: Loop) -> &LoopData<N> {
+        &self.loops[lop]
+    }
+
+    pub fn loop_header(&self, lop: Loop) -> N {
+        self.loops[lop].header
+    }
+
+    pub fn loop_parent(&self, lop: Loop) -> Option<Loop> {
+        self.loops[lop].parent
+    }
+
+    /// The innermost loop the node is part of.
+    pub fn innermost_loop(&self, node: N) -> Option<Loop> {
+        self.innermost.get(&node).cloned()
+    }
+
+    /// The number of loops the node is nested in, 0 outside of any loop.
+    pub fn loop_depth(&self, node: N) -> usize {
+        self.innermost_loop(node)
+            .map(|lop| self.loops[lop].depth)
+            .unwrap_or(0)
+    }
+
+    pub fn is_loop_header(&self, node: N) -> bool {
+        self.innermost_loop(node)
+            .map(|lop| self.loops[lop].header == node)
+            .unwrap_or(false)
+    }
+
+    /// Whether the node is inside the loop, or a loop nested in it.
+    pub fn loop_contains(&self, lop: Loop, node: N) -> bool {
+        self.loops[lop].nodes.contains(&node)
+    }
+}
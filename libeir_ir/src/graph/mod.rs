@@ -9,3 +9,13 @@ pub use live_block_graph::LiveBlockGraph;
 
 mod control_flow_graph;
 pub use control_flow_graph::ControlFlowGraph;
+
+mod dominators;
+pub use dominators::{DominatorTree, GraphOrder};
+
+mod loops;
+pub use loops::{Loop, LoopData, LoopForest};
+
+mod analysis;
+pub(crate) use analysis::AnalysisCache;
+pub use analysis::GraphAnalysis;
@@ -18,9 +18,11 @@ pub mod traits;
 // Auxiliary utilities
 mod algo;
 pub use algo::equality::GraphEqOptions;
+pub use algo::escape::{ConstructionUse, EscapeAnalysis};
 pub use algo::func_tree::{FunctionEntry, FunctionTree};
 pub use algo::live::LiveValues;
 pub use algo::mangle::{MangleFrom, MangleTarget, MangleTo, Mangler};
+pub use algo::tail_call::{TailCall, TailRecursion};
 pub use algo::validate::ValidationError;
 
 pub mod text;
@@ -38,6 +40,7 @@ pub use function::{
     BasicType, BinOp, CallKind, LogicOp, MapPutUpdate, MatchKind, OpKind, PrimOpKind,
 };
 pub use function::{Block, Function, Location, PrimOp, Value};
+pub use function::{LocationContainer, LocationTerminal};
 pub use function::{ContainerDebug, ContainerDebugAdapter};
 
 pub use function::builder::{DynValue, FunctionBuilder, IntoValue};
@@ -39,6 +39,8 @@ pub enum DynToken {
     Arity(SourceSpan),
     IfBool(SourceSpan),
     TraceCaptureRaw(SourceSpan),
+    TraceConstruct(SourceSpan),
+    Closure(SourceSpan),
     Value(SourceSpan),
     Match(SourceSpan),
     Type(SourceSpan),
@@ -84,6 +86,8 @@ impl DynToken {
             Arity(span) => *span,
             IfBool(span) => *span,
             TraceCaptureRaw(span) => *span,
+            TraceConstruct(span) => *span,
+            Closure(span) => *span,
             Value(span) => *span,
             Match(span) => *span,
             Type(span) => *span,
@@ -152,6 +156,7 @@ pub enum Op {
     CallFunction(CallFunctionOp),
     IfBool(IfBoolOp),
     TraceCaptureRaw(TraceCaptureRawOp),
+    TraceConstruct(TraceConstructOp),
     Match(MatchOp),
     Case(CaseOp),
     Unreachable,
@@ -252,6 +257,13 @@ pub struct TraceCaptureRawOp {
     pub then: Value,
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub struct TraceConstructOp {
+    pub span: SourceSpan,
+    pub then: Value,
+    pub raw: Value,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum Value {
     // Atomics
@@ -266,6 +278,11 @@ pub enum Value {
     Tuple(Vec<Value>),
     List(Vec<Value>, Option<Box<Value>>),
     CaptureFunction(Box<Value>, Box<Value>, Box<Value>),
+    CaptureLocalFunction(Box<Value>, Box<Value>),
+    /// `closure _:f/a[env..]`
+    MakeClosure(Box<Value>, Box<Value>, Vec<Value>),
+    /// `%env[n]`, only on the right hand side of an assignment
+    ClosureEnvGet(Box<Value>, usize),
     BinOp(Box<Value>, BinOp, Box<Value>),
 }
 impl Value {
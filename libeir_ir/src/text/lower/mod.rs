@@ -388,6 +388,11 @@ fn lower_operation(
             let then = lower_value(errors, b, scope, &trace_op.then)?;
             b.op_trace_capture_raw_next(SourceSpan::UNKNOWN, block, then);
         }
+        ast::Op::TraceConstruct(trace_op) => {
+            let then = lower_value(errors, b, scope, &trace_op.then)?;
+            let raw = lower_value(errors, b, scope, &trace_op.raw)?;
+            b.op_trace_construct_next(SourceSpan::UNKNOWN, block, then, raw);
+        }
         ast::Op::Match(match_op) => {
             let mut builder = b.op_match_build(SourceSpan::UNKNOWN);
             for entry in match_op.entries.iter() {
@@ -560,6 +565,27 @@ fn lower_value(
 
             Ok(b.prim_capture_function(SourceSpan::UNKNOWN, m_v, f_v, a_v))
         }
+        ast::Value::CaptureLocalFunction(f, a) => {
+            let f_v = lower_value(errors, b, scope, &*f)?;
+            let a_v = lower_value(errors, b, scope, &*a)?;
+
+            Ok(b.prim_capture_local_function(SourceSpan::UNKNOWN, f_v, a_v))
+        }
+        ast::Value::MakeClosure(f, a, env) => {
+            let f_v = lower_value(errors, b, scope, &*f)?;
+            let a_v = lower_value(errors, b, scope, &*a)?;
+            let env_v: Result<Vec<Value>, _> = env
+                .iter()
+                .map(|v| lower_value(errors, b, scope, v))
+                .collect();
+
+            Ok(b.prim_make_closure(SourceSpan::UNKNOWN, f_v, a_v, &env_v?))
+        }
+        ast::Value::ClosureEnvGet(env, index) => {
+            let env_v = lower_value(errors, b, scope, &*env)?;
+
+            Ok(b.prim_closure_env_get(SourceSpan::UNKNOWN, env_v, *index))
+        }
         ast::Value::BinOp(lhs, op, rhs) => {
             let lhs_v = lower_value(errors, b, scope, &*lhs)?;
             let rhs_v = lower_value(errors, b, scope, &*rhs)?;
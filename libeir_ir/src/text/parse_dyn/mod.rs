@@ -55,6 +55,8 @@ pub enum Token {
     Arity,
     IfBool,
     TraceCaptureRaw,
+    TraceConstruct,
+    Closure,
     Value,
     Match,
     Type,
@@ -121,6 +123,8 @@ pub fn flatten_dyn_token(tok: &[DynToken]) -> Vec<(Token, SourceSpan)> {
                 DynToken::Arity(span) => out.push((Token::Arity, *span)),
                 DynToken::IfBool(span) => out.push((Token::IfBool, *span)),
                 DynToken::TraceCaptureRaw(span) => out.push((Token::TraceCaptureRaw, *span)),
+                DynToken::TraceConstruct(span) => out.push((Token::TraceConstruct, *span)),
+                DynToken::Closure(span) => out.push((Token::Closure, *span)),
                 DynToken::Value(span) => out.push((Token::Value, *span)),
                 DynToken::Match(span) => out.push((Token::Match, *span)),
                 DynToken::Type(span) => out.push((Token::Type, *span)),
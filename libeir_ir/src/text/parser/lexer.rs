@@ -119,6 +119,8 @@ pub enum Token {
     Tuple,
     Arity,
     TraceCaptureRaw,
+    TraceConstruct,
+    Closure,
     Value,
     Match,
     Type,
@@ -138,6 +140,8 @@ lazy_static! {
         map.insert(Symbol::intern("unpack"), Token::UnpackValueList);
         map.insert(Symbol::intern("arity"), Token::Arity);
         map.insert(Symbol::intern("trace_capture_raw"), Token::TraceCaptureRaw);
+        map.insert(Symbol::intern("trace_construct"), Token::TraceConstruct);
+        map.insert(Symbol::intern("closure"), Token::Closure);
         map.insert(Symbol::intern("value"), Token::Value);
         map.insert(Symbol::intern("match"), Token::Match);
         map.insert(Symbol::intern("type"), Token::Type);
@@ -368,6 +368,41 @@ where
                         assert!(reads.len() == 3);
                         arena.nil().append(self.format_callee(config, state, reads))
                     }
+                    PrimOpKind::CaptureLocalFunction => {
+                        assert!(reads.len() == 2);
+                        arena
+                            .nil()
+                            .append(arena.text("_:"))
+                            .append(self.value_use(config, state, reads[0], Some(value)))
+                            .append(arena.text("/"))
+                            .append(self.value_use(config, state, reads[1], Some(value)))
+                    }
+                    PrimOpKind::MakeClosure => {
+                        assert!(reads.len() >= 2);
+                        arena
+                            .nil()
+                            .append(arena.text("closure _:"))
+                            .append(self.value_use(config, state, reads[0], Some(value)))
+                            .append(arena.text("/"))
+                            .append(self.value_use(config, state, reads[1], Some(value)))
+                            .append(
+                                arena
+                                    .intersperse(
+                                        reads[2..].iter().map(|r| {
+                                            self.value_use(config, state, *r, Some(value))
+                                        }),
+                                        arena.text(",").append(arena.space()),
+                                    )
+                                    .enclose(arena.text("["), arena.text("]")),
+                            )
+                    }
+                    PrimOpKind::ClosureEnvGet(index) => {
+                        assert!(reads.len() == 1);
+                        arena
+                            .nil()
+                            .append(self.value_use(config, state, reads[0], Some(value)))
+                            .append(arena.text(format!("[{}]", index)))
+                    }
                     PrimOpKind::Tuple => arena
                         .intersperse(
                             reads
@@ -718,4 +753,57 @@ a'woo':a'hoo'/1 {
         let text = ir.to_text(&mut StandardFormatConfig::default());
         println!("{}", text);
     }
+
+    #[test]
+    fn trace_ops_roundtrip() {
+        let ir = crate::parse_function_unwrap(
+            "
+a'woo':a'hoo'/1 {
+    entry(%ret, %thr, %a):
+        trace_capture_raw raw;
+    raw(%r):
+        trace_construct built %r;
+    built(%t):
+        %ret(%t);
+}
+",
+        );
+
+        let mut config = StandardFormatConfig::default();
+        config.print_locations = false;
+        let text = format!("a'woo':a'hoo'/1 {{\n{}\n}}", ir.to_text(&mut config));
+
+        let parsed = crate::parse_function_unwrap(&text);
+        assert!(ir
+            .graph_eq(ir.block_entry(), &parsed, parsed.block_entry())
+            .is_ok());
+    }
+
+    #[test]
+    fn closure_ops_roundtrip() {
+        let ir = crate::parse_function_unwrap(
+            "
+a'woo':a'-hoo/1-fun-0-'/2 {
+    entry(%ret, %thr, %a, %env):
+        %b = %env[1];
+        %c = closure _:a'-hoo/1-fun-1-'/1[%a, %b];
+        %d = %env[0];
+        %ret({%c, %d});
+}
+",
+        );
+
+        let mut config = StandardFormatConfig::default();
+        config.print_locations = false;
+        let text = format!(
+            "a'woo':a'-hoo/1-fun-0-'/2 {{\n{}\n}}",
+            ir.to_text(&mut config)
+        );
+        assert!(text.contains("closure _:a'-hoo/1-fun-1-'/1["));
+
+        let parsed = crate::parse_function_unwrap(&text);
+        assert!(ir
+            .graph_eq(ir.block_entry(), &parsed, parsed.block_entry())
+            .is_ok());
+    }
 }
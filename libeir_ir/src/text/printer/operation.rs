@@ -329,6 +329,18 @@ where
                     .append(arena.space())
                     .append(arg)
             }
+            OpKind::TraceConstruct => {
+                assert!(reads.len() == 2);
+                let arg = self.value_use(config, state, reads[0], None);
+                let raw = self.value_use(config, state, reads[1], None);
+                arena
+                    .nil()
+                    .append(arena.text("trace_construct"))
+                    .append(arena.space())
+                    .append(arg)
+                    .append(arena.space())
+                    .append(raw)
+            }
             OpKind::UnpackValueList(n) => {
                 assert!(reads.len() == 2);
                 let block = self.value_use(config, state, reads[0], None);
@@ -0,0 +1,748 @@
+use std::collections::HashMap;
+
+use cranelift_codegen::entity::EntityRef;
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{
+    types, AbiParam, Block as ClBlock, FuncRef, InstBuilder, MemFlags, Signature, StackSlotData,
+    StackSlotKind, TrapCode, Value as ClValue,
+};
+use cranelift_frontend::{FunctionBuilder, Variable};
+use cranelift_module::{FuncId, Module, ModuleError};
+use cranelift_simplejit::SimpleJITBackend;
+
+use num_traits::ToPrimitive;
+
+use libeir_intern::Symbol;
+use libeir_ir::{
+    AtomicTerm, BasicType, BinOp, ConstKind, FunctionIdent, LogicOp, MatchKind, OpKind, PrimOpKind,
+};
+use libeir_lir::{Block, Cont, Function, Inst, LowerError, Operand, Terminator, Unit, Var};
+
+use crate::runtime::{binop_code, type_code, Helper};
+use crate::term::{const_term, small_int, Word, NIL};
+use crate::JitState;
+
+#[derive(Debug)]
+pub enum CompileError {
+    Lower(LowerError),
+    /// The function uses something the JIT does not compile.
+    Unsupported(String),
+    Module(ModuleError),
+}
+
+impl From<LowerError> for CompileError {
+    fn from(err: LowerError) -> Self {
+        CompileError::Lower(err)
+    }
+}
+impl From<ModuleError> for CompileError {
+    fn from(err: ModuleError) -> Self {
+        CompileError::Module(err)
+    }
+}
+
+fn unsupported<T>(what: impl Into<String>) -> Result<T, CompileError> {
+    Err(CompileError::Unsupported(what.into()))
+}
+
+/// BIFs with a small integer fast path.
+#[derive(Debug, Copy, Clone)]
+enum Bif {
+    Add,
+    Sub,
+    Compare(BinOp),
+}
+
+impl Bif {
+    fn from_ident(ident: &FunctionIdent) -> Option<Bif> {
+        if ident.module.name != Symbol::intern("erlang") || ident.arity != 2 {
+            return None;
+        }
+        let bif = match &*ident.name.name.as_str() {
+            "+" => Bif::Add,
+            "-" => Bif::Sub,
+            "==" => Bif::Compare(BinOp::Equal),
+            "/=" => Bif::Compare(BinOp::NotEqual),
+            "=<" => Bif::Compare(BinOp::LessEqual),
+            "<" => Bif::Compare(BinOp::Less),
+            ">=" => Bif::Compare(BinOp::GreaterEqual),
+            ">" => Bif::Compare(BinOp::Greater),
+            "=:=" => Bif::Compare(BinOp::ExactEqual),
+            "=/=" => Bif::Compare(BinOp::ExactNotEqual),
+            _ => return None,
+        };
+        Some(bif)
+    }
+}
+
+fn int_cc(op: BinOp) -> IntCC {
+    match op {
+        BinOp::Equal | BinOp::ExactEqual => IntCC::Equal,
+        BinOp::NotEqual | BinOp::ExactNotEqual => IntCC::NotEqual,
+        BinOp::LessEqual => IntCC::SignedLessThanOrEqual,
+        BinOp::Less => IntCC::SignedLessThan,
+        BinOp::GreaterEqual => IntCC::SignedGreaterThanOrEqual,
+        BinOp::Greater => IntCC::SignedGreaterThan,
+    }
+}
+
+/// The signature of every compiled function, `(ctx, args) -> result`, where
+/// `args` points to the argument words.
+pub fn function_signature(module: &Module<SimpleJITBackend>) -> Signature {
+    let mut sig = module.make_signature();
+    sig.params.push(AbiParam::new(types::I64));
+    sig.params.push(AbiParam::new(types::I64));
+    sig.returns.push(AbiParam::new(types::I64));
+    sig
+}
+
+pub fn helper_signature(module: &Module<SimpleJITBackend>, helper: Helper) -> Signature {
+    let mut sig = module.make_signature();
+    for _ in 0..(helper.arity() + 1) {
+        sig.params.push(AbiParam::new(types::I64));
+    }
+    if helper.returns() {
+        sig.returns.push(AbiParam::new(types::I64));
+    }
+    sig
+}
+
+/// The function a constant capture refers to. Local captures refer to the
+/// module of the unit.
+fn capture_ident(unit: &Unit, fun: &Function, var: Var) -> Option<FunctionIdent> {
+    let defs = fun.blocks.values().flat_map(|b| b.insts.iter());
+    let (kind, args) = defs
+        .filter_map(|inst| match inst {
+            Inst::PrimOp { dest, kind, args } if *dest == var => Some((kind, args)),
+            _ => None,
+        })
+        .next()?;
+
+    let atom = |operand: &Operand| match operand {
+        Operand::Const(cons) => match unit.cons.const_kind(*cons) {
+            ConstKind::Atomic(AtomicTerm::Atom(atom)) => Some(atom.0),
+            _ => None,
+        },
+        _ => None,
+    };
+    let (module, rest) = match kind {
+        PrimOpKind::CaptureFunction => {
+            let module = libeir_intern::Ident::with_empty_span(atom(&args[0])?);
+            (module, &args[1..])
+        }
+        PrimOpKind::CaptureLocalFunction => (unit.ident.module, &args[..]),
+        _ => return None,
+    };
+    let arity = match &rest[1] {
+        Operand::Const(cons) => match unit.cons.const_kind(*cons) {
+            ConstKind::Atomic(AtomicTerm::Int(int)) => int.value() as usize,
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(FunctionIdent {
+        module,
+        name: libeir_intern::Ident::with_empty_span(atom(&rest[0])?),
+        arity,
+    })
+}
+
+/// Checks that every construct in the unit can be compiled.
+pub fn check(unit: &Unit) -> Result<(), CompileError> {
+    if unit.functions.len() != 1 {
+        return unsupported("closures");
+    }
+    let fun = &unit.functions[unit.root];
+
+    let value = |operand: &Operand| match operand {
+        Operand::Var(_) | Operand::Const(_) => Ok(()),
+        _ => unsupported("value list"),
+    };
+    let values = |operands: &[Operand]| operands.iter().try_for_each(value);
+
+    for block in fun.blocks.values() {
+        for inst in block.insts.iter() {
+            match inst {
+                Inst::PrimOp { kind, args, dest } => {
+                    values(args)?;
+                    match kind {
+                        PrimOpKind::Tuple
+                        | PrimOpKind::BinOp(_)
+                        | PrimOpKind::IsType(_)
+                        | PrimOpKind::LogicOp(LogicOp::And)
+                        | PrimOpKind::LogicOp(LogicOp::Or) => (),
+                        PrimOpKind::CaptureFunction | PrimOpKind::CaptureLocalFunction => {
+                            if capture_ident(unit, fun, *dest).is_none() {
+                                return unsupported("dynamic function capture");
+                            }
+                        }
+                        kind => return unsupported(format!("primop {:?}", kind)),
+                    }
+                }
+                Inst::MakeClosure { .. } => return unsupported("closures"),
+            }
+        }
+
+        match &block.term {
+            Terminator::Jump { args, .. } => values(args)?,
+            Terminator::Return { values: vals } if vals.len() == 1 => values(vals)?,
+            Terminator::Throw { values: vals } if vals.len() == 3 => values(vals)?,
+            Terminator::Call {
+                callee,
+                args,
+                ret,
+                thr,
+            } => {
+                value(callee)?;
+                values(args)?;
+                if *ret == Cont::Throw || *thr == Cont::Return {
+                    return unsupported("swapped continuations");
+                }
+            }
+            Terminator::TailCall { callee, args } => {
+                value(callee)?;
+                values(args)?;
+            }
+            Terminator::Op {
+                kind: OpKind::IfBool,
+                reads,
+            } => value(reads.last().unwrap())?,
+            Terminator::Op {
+                kind: OpKind::TraceCaptureRaw,
+                ..
+            } => (),
+            Terminator::Op {
+                kind: OpKind::Match { branches },
+                reads,
+            } => {
+                value(&reads[1])?;
+                for (idx, kind) in branches.iter().enumerate() {
+                    match kind {
+                        MatchKind::Value
+                        | MatchKind::Wildcard
+                        | MatchKind::Tuple(_)
+                        | MatchKind::Type(_) => (),
+                        kind => return unsupported(format!("match on {:?}", kind)),
+                    }
+                    match &reads[2 + idx] {
+                        Operand::List(list) => values(list)?,
+                        operand => value(operand)?,
+                    }
+                }
+            }
+            Terminator::Unreachable => (),
+            term => return unsupported(format!("terminator {:?}", term)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Builds the Cranelift IR for a unit that passed `check`.
+pub struct FunctionCompiler<'a, 'b> {
+    pub state: &'a mut JitState,
+    pub module: &'a mut Module<SimpleJITBackend>,
+    /// The functions compiled so far, along with the ones being compiled.
+    pub compiled: &'a HashMap<FunctionIdent, FuncId>,
+    pub unit: &'a Unit,
+    pub builder: FunctionBuilder<'b>,
+
+    pub helpers: HashMap<Helper, FuncRef>,
+    pub blocks: HashMap<Block, ClBlock>,
+    pub ctx: Option<ClValue>,
+}
+
+impl<'a, 'b> FunctionCompiler<'a, 'b> {
+    pub fn compile(mut self) {
+        let unit = self.unit;
+        let fun = &unit.functions[unit.root];
+
+        for helper in Helper::ALL.iter() {
+            let id = self.state.helpers[helper];
+            let fref = self
+                .module
+                .declare_func_in_func(id, &mut *self.builder.func);
+            self.helpers.insert(*helper, fref);
+        }
+        for var in fun.vars.keys() {
+            self.builder.declare_var(variable(var), types::I64);
+        }
+
+        // The entry block loads the arguments, and then continues with the
+        // body of the LIR entry block.
+        let entry = self.builder.create_block();
+        self.builder.append_block_params_for_function_params(entry);
+        for block in fun.blocks.keys() {
+            if block != fun.entry {
+                let cl_block = self.builder.create_block();
+                for _ in fun.blocks[block].params.iter() {
+                    self.builder.append_block_param(cl_block, types::I64);
+                }
+                self.blocks.insert(block, cl_block);
+            }
+        }
+
+        self.builder.switch_to_block(entry);
+        let params = self.builder.block_params(entry).to_vec();
+        self.ctx = Some(params[0]);
+        for (idx, param) in fun.blocks[fun.entry].params.iter().enumerate() {
+            let arg = self.builder.ins().load(
+                types::I64,
+                MemFlags::trusted(),
+                params[1],
+                (idx * 8) as i32,
+            );
+            self.builder.def_var(variable(*param), arg);
+        }
+        self.block(fun, fun.entry);
+
+        for block in fun.blocks.keys() {
+            if block != fun.entry {
+                let cl_block = self.blocks[&block];
+                self.builder.switch_to_block(cl_block);
+                let args = self.builder.block_params(cl_block).to_vec();
+                for (param, arg) in fun.blocks[block].params.iter().zip(args) {
+                    self.builder.def_var(variable(*param), arg);
+                }
+                self.block(fun, block);
+            }
+        }
+
+        self.builder.seal_all_blocks();
+        self.builder.finalize();
+    }
+
+    fn block(&mut self, fun: &Function, block: Block) {
+        let data = &fun.blocks[block];
+        for inst in data.insts.iter() {
+            self.inst(fun, inst);
+        }
+
+        match &data.term {
+            Terminator::Jump { target, args } => {
+                let args = self.words(args);
+                let target = self.blocks[target];
+                self.builder.ins().jump(target, &args);
+            }
+            Terminator::Return { values } => {
+                let value = self.word(&values[0]);
+                self.builder.ins().return_(&[value]);
+            }
+            Terminator::Throw { values } => {
+                let values = self.words(values);
+                self.helper(Helper::Throw, &values);
+                self.return_none();
+            }
+            Terminator::Call {
+                callee,
+                args,
+                ret,
+                thr,
+            } => {
+                let result = self.call(fun, callee, args);
+                self.continue_call(result, *ret, *thr);
+            }
+            Terminator::TailCall { callee, args } => {
+                let result = self.call(fun, callee, args);
+                self.builder.ins().return_(&[result]);
+            }
+            Terminator::Op { kind, reads } => self.op(kind, reads),
+            Terminator::Unreachable => {
+                self.builder.ins().trap(TrapCode::UnreachableCodeReached);
+            }
+        }
+    }
+
+    fn inst(&mut self, fun: &Function, inst: &Inst) {
+        let (dest, kind, args) = match inst {
+            Inst::PrimOp { dest, kind, args } => (*dest, kind, args),
+            Inst::MakeClosure { .. } => unreachable!(),
+        };
+
+        let value = match kind {
+            PrimOpKind::Tuple => {
+                let elems = self.words(args);
+                let (ptr, len) = self.array(&elems);
+                self.helper(Helper::MakeTuple, &[ptr, len]).unwrap()
+            }
+            PrimOpKind::BinOp(op) => {
+                let args = self.words(args);
+                self.compare(*op, args[0], args[1])
+            }
+            PrimOpKind::IsType(typ) => {
+                let term = self.word(&args[0]);
+                self.is_type(term, *typ)
+            }
+            PrimOpKind::LogicOp(op) => {
+                let truth = self.atom("true") as i64;
+                let mut acc = None;
+                for arg in args.iter() {
+                    let arg = self.word(arg);
+                    let is_true = self.builder.ins().icmp_imm(IntCC::Equal, arg, truth);
+                    let is_true = self.builder.ins().bint(types::I64, is_true);
+                    acc = Some(match (acc, op) {
+                        (None, _) => is_true,
+                        (Some(acc), LogicOp::And) => self.builder.ins().band(acc, is_true),
+                        (Some(acc), _) => self.builder.ins().bor(acc, is_true),
+                    });
+                }
+                match acc {
+                    Some(acc) => self.bool_word(acc),
+                    None => {
+                        let empty = self.atom(if *op == LogicOp::And { "true" } else { "false" });
+                        self.builder.ins().iconst(types::I64, empty as i64)
+                    }
+                }
+            }
+            PrimOpKind::CaptureFunction | PrimOpKind::CaptureLocalFunction => {
+                let ident = capture_ident(self.unit, fun, dest).unwrap();
+                let word = self.state.capture_word(ident);
+                self.builder.ins().iconst(types::I64, word as i64)
+            }
+            _ => unreachable!(),
+        };
+        self.builder.def_var(variable(dest), value);
+    }
+
+    fn op(&mut self, kind: &OpKind, reads: &[Operand]) {
+        match kind {
+            OpKind::IfBool => {
+                let value = self.word(reads.last().unwrap());
+                let targets: Vec<ClBlock> = reads[..reads.len() - 1]
+                    .iter()
+                    .map(|read| self.target(read))
+                    .collect();
+
+                let truth = self.atom("true") as i64;
+                let falsity = self.atom("false") as i64;
+
+                let is_true = self.builder.ins().icmp_imm(IntCC::Equal, value, truth);
+                self.builder.ins().brnz(is_true, targets[0], &[]);
+                self.next_block();
+                let is_false = self.builder.ins().icmp_imm(IntCC::Equal, value, falsity);
+                self.builder.ins().brnz(is_false, targets[1], &[]);
+                self.next_block();
+
+                if let Some(other) = targets.get(2) {
+                    self.builder.ins().jump(*other, &[]);
+                } else {
+                    let error = self.atom("error");
+                    let badarg = self.atom("badarg");
+                    let error = self.builder.ins().iconst(types::I64, error as i64);
+                    let badarg = self.builder.ins().iconst(types::I64, badarg as i64);
+                    let nil = self.builder.ins().iconst(types::I64, NIL as i64);
+                    self.helper(Helper::Throw, &[error, badarg, nil]);
+                    self.return_none();
+                }
+            }
+            OpKind::TraceCaptureRaw => {
+                let target = self.target(&reads[0]);
+                let nil = self.builder.ins().iconst(types::I64, NIL as i64);
+                self.builder.ins().jump(target, &[nil]);
+            }
+            OpKind::Match { branches } => {
+                let targets: Vec<ClBlock> = match &reads[0] {
+                    Operand::List(list) => list.iter().map(|read| self.target(read)).collect(),
+                    read => vec![self.target(read)],
+                };
+                let subject = self.word(&reads[1]);
+
+                for (idx, kind) in branches.iter().enumerate() {
+                    let target = targets[idx];
+                    let args: Vec<ClValue> = match &reads[2 + idx] {
+                        Operand::List(list) => self.words(list),
+                        read => vec![self.word(read)],
+                    };
+
+                    match kind {
+                        MatchKind::Wildcard => {
+                            self.builder.ins().jump(target, &[]);
+                            return;
+                        }
+                        MatchKind::Value => {
+                            let eq = self.helper(Helper::ExactEq, &[subject, args[0]]).unwrap();
+                            self.builder.ins().brnz(eq, target, &[]);
+                        }
+                        MatchKind::Type(typ) => {
+                            let (typ, arity) = type_code(*typ);
+                            let typ = self.builder.ins().iconst(types::I64, typ);
+                            let arity = self.builder.ins().iconst(types::I64, arity);
+                            let is = self.helper(Helper::IsType, &[subject, typ, arity]).unwrap();
+                            self.builder.ins().brnz(is, target, &[]);
+                        }
+                        MatchKind::Tuple(arity) => {
+                            let actual = self.helper(Helper::TupleArity, &[subject]).unwrap();
+                            let is =
+                                self.builder
+                                    .ins()
+                                    .icmp_imm(IntCC::Equal, actual, *arity as i64);
+                            let unpack = self.builder.create_block();
+                            let next = self.builder.create_block();
+                            self.builder.ins().brnz(is, unpack, &[]);
+                            self.builder.ins().jump(next, &[]);
+
+                            self.builder.switch_to_block(unpack);
+                            let mut elems = Vec::with_capacity(*arity);
+                            for n in 0..*arity {
+                                let n = self.builder.ins().iconst(types::I64, n as i64);
+                                let elem = self.helper(Helper::TupleElement, &[subject, n]);
+                                elems.push(elem.unwrap());
+                            }
+                            self.builder.ins().jump(target, &elems);
+
+                            self.builder.switch_to_block(next);
+                            continue;
+                        }
+                        _ => unreachable!(),
+                    }
+                    self.next_block();
+                }
+
+                self.builder.ins().trap(TrapCode::UnreachableCodeReached);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Calls a function, returning the result word. The result is `NONE` if
+    /// the callee threw.
+    fn call(&mut self, fun: &Function, callee: &Operand, args: &[Operand]) -> ClValue {
+        let args = self.words(args);
+
+        let ident = match callee {
+            Operand::Var(var) => capture_ident(self.unit, fun, *var),
+            _ => None,
+        };
+
+        if let Some(ident) = ident {
+            if let Some(id) = self.compiled.get(&ident) {
+                let fref = self
+                    .module
+                    .declare_func_in_func(*id, &mut *self.builder.func);
+                let (ptr, _) = self.array(&args);
+                let ctx = self.ctx.unwrap();
+                let call = self.builder.ins().call(fref, &[ctx, ptr]);
+                return self.builder.inst_results(call)[0];
+            }
+            if let Some(bif) = Bif::from_ident(&ident) {
+                return self.bif(bif, args[0], args[1], callee);
+            }
+        }
+
+        let callee = self.word(callee);
+        self.apply(callee, &args)
+    }
+
+    fn apply(&mut self, callee: ClValue, args: &[ClValue]) -> ClValue {
+        let (ptr, len) = self.array(args);
+        self.helper(Helper::Apply, &[callee, ptr, len]).unwrap()
+    }
+
+    /// A BIF call, inline when both arguments are small integers.
+    fn bif(&mut self, bif: Bif, lhs: ClValue, rhs: ClValue, callee: &Operand) -> ClValue {
+        if let Bif::Compare(op) = bif {
+            return self.compare(op, lhs, rhs);
+        }
+
+        let fast = self.builder.create_block();
+        let slow = self.builder.create_block();
+        let join = self.builder.create_block();
+        self.builder.append_block_param(join, types::I64);
+
+        let both = self.builder.ins().band(lhs, rhs);
+        let is_int = self.builder.ins().band_imm(both, 1);
+        self.builder.ins().brz(is_int, slow, &[]);
+        self.builder.ins().jump(fast, &[]);
+
+        // With the tag bit, `a + (b - 1)` is the tagged sum, and
+        // `a - (b - 1)` the tagged difference.
+        self.builder.switch_to_block(fast);
+        let untagged = self.builder.ins().iadd_imm(rhs, -1);
+        let (result, overflow) = match bif {
+            Bif::Add => {
+                let result = self.builder.ins().iadd(lhs, untagged);
+                let l = self.builder.ins().bxor(lhs, result);
+                let r = self.builder.ins().bxor(untagged, result);
+                (result, self.builder.ins().band(l, r))
+            }
+            Bif::Sub => {
+                let result = self.builder.ins().isub(lhs, untagged);
+                let l = self.builder.ins().bxor(lhs, untagged);
+                let r = self.builder.ins().bxor(lhs, result);
+                (result, self.builder.ins().band(l, r))
+            }
+            Bif::Compare(_) => unreachable!(),
+        };
+        let overflow = self
+            .builder
+            .ins()
+            .icmp_imm(IntCC::SignedLessThan, overflow, 0);
+        self.builder.ins().brnz(overflow, slow, &[]);
+        self.builder.ins().jump(join, &[result]);
+
+        self.builder.switch_to_block(slow);
+        let callee = self.word(callee);
+        let result = self.apply(callee, &[lhs, rhs]);
+        self.builder.ins().jump(join, &[result]);
+
+        self.builder.switch_to_block(join);
+        self.builder.block_params(join)[0]
+    }
+
+    /// A comparison, inline when both arguments are small integers. Tagging
+    /// preserves the order of small integers.
+    fn compare(&mut self, op: BinOp, lhs: ClValue, rhs: ClValue) -> ClValue {
+        let fast = self.builder.create_block();
+        let slow = self.builder.create_block();
+        let join = self.builder.create_block();
+        self.builder.append_block_param(join, types::I64);
+
+        let both = self.builder.ins().band(lhs, rhs);
+        let is_int = self.builder.ins().band_imm(both, 1);
+        self.builder.ins().brz(is_int, slow, &[]);
+        self.builder.ins().jump(fast, &[]);
+
+        self.builder.switch_to_block(fast);
+        let cmp = self.builder.ins().icmp(int_cc(op), lhs, rhs);
+        let cmp = self.builder.ins().bint(types::I64, cmp);
+        let result = self.bool_word(cmp);
+        self.builder.ins().jump(join, &[result]);
+
+        self.builder.switch_to_block(slow);
+        let code = self.builder.ins().iconst(types::I64, binop_code(op));
+        let result = self.helper(Helper::BinOp, &[code, lhs, rhs]).unwrap();
+        self.builder.ins().jump(join, &[result]);
+
+        self.builder.switch_to_block(join);
+        self.builder.block_params(join)[0]
+    }
+
+    fn is_type(&mut self, term: ClValue, typ: BasicType) -> ClValue {
+        let (typ, arity) = type_code(typ);
+        let typ = self.builder.ins().iconst(types::I64, typ);
+        let arity = self.builder.ins().iconst(types::I64, arity);
+        let is = self.helper(Helper::IsType, &[term, typ, arity]).unwrap();
+        self.bool_word(is)
+    }
+
+    /// Dispatches on the result of a call.
+    fn continue_call(&mut self, result: ClValue, ret: Cont, thr: Cont) {
+        let ok = self.builder.create_block();
+        let err = self.builder.create_block();
+        self.builder.ins().brz(result, err, &[]);
+        self.builder.ins().jump(ok, &[]);
+
+        self.builder.switch_to_block(ok);
+        match ret {
+            Cont::Block(block) => {
+                let target = self.blocks[&block];
+                self.builder.ins().jump(target, &[result]);
+            }
+            Cont::Return => {
+                self.builder.ins().return_(&[result]);
+            }
+            Cont::Throw => unreachable!(),
+        }
+
+        self.builder.switch_to_block(err);
+        match thr {
+            Cont::Block(block) => {
+                let mut exception = Vec::with_capacity(3);
+                for n in 0..3 {
+                    let n = self.builder.ins().iconst(types::I64, n);
+                    exception.push(self.helper(Helper::Exception, &[n]).unwrap());
+                }
+                let target = self.blocks[&block];
+                self.builder.ins().jump(target, &exception);
+            }
+            Cont::Throw => self.return_none(),
+            Cont::Return => unreachable!(),
+        }
+    }
+
+    fn return_none(&mut self) {
+        let none = self.builder.ins().iconst(types::I64, 0);
+        self.builder.ins().return_(&[none]);
+    }
+
+    /// Continues in a new block, after a conditional branch.
+    fn next_block(&mut self) {
+        let next = self.builder.create_block();
+        self.builder.ins().jump(next, &[]);
+        self.builder.switch_to_block(next);
+    }
+
+    fn helper(&mut self, helper: Helper, args: &[ClValue]) -> Option<ClValue> {
+        let mut call_args = Vec::with_capacity(args.len() + 1);
+        call_args.push(self.ctx.unwrap());
+        call_args.extend(args.iter().cloned());
+        let call = self.builder.ins().call(self.helpers[&helper], &call_args);
+        self.builder.inst_results(call).get(0).cloned()
+    }
+
+    /// Stores the values in a stack slot, returning its address and length.
+    fn array(&mut self, values: &[ClValue]) -> (ClValue, ClValue) {
+        let size = (values.len().max(1) * 8) as u32;
+        let slot = self
+            .builder
+            .create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, size));
+        for (idx, value) in values.iter().enumerate() {
+            self.builder
+                .ins()
+                .stack_store(*value, slot, (idx * 8) as i32);
+        }
+        let ptr = self.builder.ins().stack_addr(types::I64, slot, 0);
+        let len = self.builder.ins().iconst(types::I64, values.len() as i64);
+        (ptr, len)
+    }
+
+    /// Turns a 0 or 1 into `false` or `true`.
+    fn bool_word(&mut self, value: ClValue) -> ClValue {
+        let truth = self.atom("true") as i64;
+        let falsity = self.atom("false") as i64;
+        let truth = self.builder.ins().iconst(types::I64, truth);
+        let falsity = self.builder.ins().iconst(types::I64, falsity);
+        self.builder.ins().select(value, truth, falsity)
+    }
+
+    fn atom(&mut self, name: &str) -> Word {
+        self.state.atoms.borrow_mut().word(Symbol::intern(name))
+    }
+
+    fn target(&self, operand: &Operand) -> ClBlock {
+        match operand {
+            Operand::Block(block) => self.blocks[block],
+            _ => unreachable!(),
+        }
+    }
+
+    fn words(&mut self, operands: &[Operand]) -> Vec<ClValue> {
+        operands.iter().map(|o| self.word(o)).collect()
+    }
+
+    fn word(&mut self, operand: &Operand) -> ClValue {
+        match operand {
+            Operand::Var(var) => self.builder.use_var(variable(*var)),
+            Operand::Const(cons) => {
+                let word = match self.unit.cons.const_kind(*cons) {
+                    ConstKind::Atomic(AtomicTerm::Int(int)) => small_int(int.value()),
+                    ConstKind::Atomic(AtomicTerm::BigInt(int)) => {
+                        int.value().to_i64().and_then(small_int)
+                    }
+                    _ => None,
+                };
+                let word = word.unwrap_or_else(|| {
+                    let term = const_term(&self.unit.cons, *cons);
+                    self.state.constant_word(term)
+                });
+                self.builder.ins().iconst(types::I64, word as i64)
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn variable(var: Var) -> Variable {
+    Variable::new(var.as_u32() as usize)
+}
@@ -0,0 +1,229 @@
+//! Experimental native JIT for Eir, built on Cranelift.
+//!
+//! Functions are lowered to LIR, and the LIR is translated to Cranelift IR.
+//! Only x86_64 Linux is supported.
+//!
+//! The JIT compiles the subset of LIR that works on small integers, atoms and
+//! tuples, see `term` for how terms are represented. A function that uses
+//! anything else, like closures, lists or binaries, is left to the
+//! interpreter. Compiled code calls back into the interpreter for every
+//! function that was not compiled, including BIFs without an inline fast
+//! path.
+//!
+//! Compiled functions use the host stack, self recursion is not turned into
+//! loops.
+
+#![cfg(all(target_arch = "x86_64", target_os = "linux"))]
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_module::{default_libcall_names, FuncId, Linkage, Module};
+use cranelift_simplejit::{SimpleJITBackend, SimpleJITBuilder};
+
+use libeir_interpreter::{Term, VMState};
+use libeir_ir::FunctionIdent;
+
+mod term;
+use term::{AtomTable, Heap, Word, NONE};
+
+mod runtime;
+use runtime::{Context, Helper};
+
+mod compile;
+pub use compile::CompileError;
+use compile::FunctionCompiler;
+
+pub mod testing;
+
+#[cfg(test)]
+mod tests;
+
+type NativeFunction = extern "C" fn(&mut Context, *const Word) -> Word;
+
+/// State shared by all compiled code.
+pub(crate) struct JitState {
+    pub helpers: HashMap<Helper, FuncId>,
+    pub atoms: RefCell<AtomTable>,
+    /// Boxed constants referenced from compiled code.
+    pub constants: Heap,
+    captures: HashMap<FunctionIdent, Word>,
+}
+
+impl JitState {
+    pub fn constant_word(&mut self, term: Rc<Term>) -> Word {
+        self.constants.word(&mut *self.atoms.borrow_mut(), term)
+    }
+
+    /// The word for a capture of the given function.
+    pub fn capture_word(&mut self, ident: FunctionIdent) -> Word {
+        if let Some(word) = self.captures.get(&ident) {
+            return *word;
+        }
+        let term = Term::CapturedFunction {
+            ident,
+            version: None,
+        };
+        let word = self.constant_word(term.into());
+        self.captures.insert(ident, word);
+        word
+    }
+}
+
+pub struct Jit {
+    module: Module<SimpleJITBackend>,
+    state: JitState,
+    functions: HashMap<FunctionIdent, FuncId>,
+    finalized: HashMap<FunctionIdent, NativeFunction>,
+}
+
+impl Jit {
+    pub fn new() -> Self {
+        let mut builder = SimpleJITBuilder::new(default_libcall_names());
+        for helper in Helper::ALL.iter() {
+            builder.symbol(helper.name(), helper.address());
+        }
+        let mut module: Module<SimpleJITBackend> = Module::new(builder);
+
+        let mut helpers = HashMap::new();
+        for helper in Helper::ALL.iter() {
+            let sig = compile::helper_signature(&module, *helper);
+            let id = module
+                .declare_function(helper.name(), Linkage::Import, &sig)
+                .unwrap();
+            helpers.insert(*helper, id);
+        }
+
+        Jit {
+            module,
+            state: JitState {
+                helpers,
+                atoms: RefCell::new(AtomTable::default()),
+                constants: Heap::default(),
+                captures: HashMap::new(),
+            },
+            functions: HashMap::new(),
+            finalized: HashMap::new(),
+        }
+    }
+
+    /// Compiles every function in the module that the JIT supports.
+    /// Returns the functions that were left to the interpreter, and why.
+    ///
+    /// A function can only be compiled once, functions that are already
+    /// compiled are skipped.
+    pub fn add_module(&mut self, module: &libeir_ir::Module) -> Vec<(FunctionIdent, CompileError)> {
+        let mut skipped = Vec::new();
+
+        let mut units = Vec::new();
+        for def in module.function_iter() {
+            let fun = def.function();
+            let ident = *fun.ident();
+            if self.functions.contains_key(&ident) {
+                continue;
+            }
+
+            let data = libeir_lowerutils::analyze(fun);
+            let unit = libeir_lir::lower_function(fun, &data)
+                .map_err(CompileError::from)
+                .and_then(|unit| compile::check(&unit).map(|()| unit));
+            match unit {
+                Ok(unit) => units.push(unit),
+                Err(err) => skipped.push((ident, err)),
+            }
+        }
+
+        // All functions are declared first, so that they can call each other
+        // directly.
+        let sig = compile::function_signature(&self.module);
+        let mut declared = Vec::new();
+        for unit in units {
+            let name = unit.ident.to_string();
+            match self.module.declare_function(&name, Linkage::Local, &sig) {
+                Ok(id) => {
+                    self.functions.insert(unit.ident, id);
+                    declared.push((unit, id));
+                }
+                Err(err) => skipped.push((unit.ident, err.into())),
+            }
+        }
+
+        let mut ctx = self.module.make_context();
+        let mut builder_ctx = FunctionBuilderContext::new();
+        let mut defined = Vec::new();
+        for (unit, id) in declared.iter() {
+            ctx.func.signature = sig.clone();
+
+            let compiler = FunctionCompiler {
+                state: &mut self.state,
+                module: &mut self.module,
+                compiled: &self.functions,
+                unit,
+                builder: FunctionBuilder::new(&mut ctx.func, &mut builder_ctx),
+                helpers: HashMap::new(),
+                blocks: HashMap::new(),
+                ctx: None,
+            };
+            compiler.compile();
+
+            let mut trap_sink = cranelift_codegen::binemit::NullTrapSink {};
+            match self.module.define_function(*id, &mut ctx, &mut trap_sink) {
+                Ok(_) => defined.push((unit.ident, *id)),
+                Err(err) => skipped.push((unit.ident, err.into())),
+            }
+            self.module.clear_context(&mut ctx);
+        }
+
+        self.module.finalize_definitions();
+        for (ident, id) in defined {
+            let ptr = self.module.get_finalized_function(id);
+            let fun = unsafe { std::mem::transmute::<*const u8, NativeFunction>(ptr) };
+            self.finalized.insert(ident, fun);
+        }
+
+        skipped
+    }
+
+    pub fn is_compiled(&self, ident: &FunctionIdent) -> bool {
+        self.finalized.contains_key(ident)
+    }
+
+    /// Calls a function, natively if it was compiled, otherwise in the
+    /// interpreter. The modules the function calls must be loaded in `vm`.
+    pub fn call(
+        &self,
+        vm: &VMState,
+        fun: &FunctionIdent,
+        args: &[Term],
+    ) -> Result<Rc<Term>, (Rc<Term>, Rc<Term>, Rc<Term>)> {
+        let mut ctx = Context::new(vm, &self.state.atoms);
+        let args: Vec<Rc<Term>> = args.iter().cloned().map(Rc::new).collect();
+
+        match self.finalized.get(fun) {
+            Some(native) => {
+                let words: Vec<Word> = args.into_iter().map(|arg| ctx.word(arg)).collect();
+                let result = native(&mut ctx, words.as_ptr());
+                if result == NONE {
+                    Err(ctx.exception.take().unwrap())
+                } else {
+                    Ok(ctx.term(result))
+                }
+            }
+            None => {
+                let fun_term = Term::CapturedFunction {
+                    ident: *fun,
+                    version: None,
+                };
+                vm.call_fun(&mut ctx.proc, fun_term.into(), &args)
+            }
+        }
+    }
+}
+
+impl Default for Jit {
+    fn default() -> Self {
+        Jit::new()
+    }
+}
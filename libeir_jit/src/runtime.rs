@@ -0,0 +1,255 @@
+//! Functions called from compiled code.
+//!
+//! Anything beyond small integer arithmetic and tag checks is done here, on
+//! the terms of the interpreter. Calls to functions that were not compiled
+//! go back into the interpreter through `VMState::call_fun`.
+
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use num_traits::ToPrimitive;
+
+use libeir_interpreter::{ErlEq, ErlExactEq, ErlOrd, Pid, ProcessContext, Term, VMState};
+use libeir_ir::{BasicType, BinOp};
+
+use crate::term::{self, AtomTable, Heap, Word, NONE};
+
+/// The state of a call into compiled code.
+pub struct Context<'a> {
+    pub vm: &'a VMState,
+    pub proc: ProcessContext,
+    pub atoms: &'a RefCell<AtomTable>,
+    pub heap: Heap,
+    /// The exception thrown by the last function that returned `NONE`.
+    pub exception: Option<(Rc<Term>, Rc<Term>, Rc<Term>)>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(vm: &'a VMState, atoms: &'a RefCell<AtomTable>) -> Self {
+        Context {
+            vm,
+            proc: ProcessContext::new(Pid(0)),
+            atoms,
+            heap: Heap::default(),
+            exception: None,
+        }
+    }
+
+    pub fn word(&mut self, term: Rc<Term>) -> Word {
+        self.heap.word(&mut *self.atoms.borrow_mut(), term)
+    }
+
+    pub fn term(&self, word: Word) -> Rc<Term> {
+        term::term(&*self.atoms.borrow(), word)
+    }
+
+    fn words(&self, words: *const Word, num: u64) -> Vec<Rc<Term>> {
+        (0..num as usize)
+            .map(|n| self.term(unsafe { *words.add(n) }))
+            .collect()
+    }
+
+    fn bool_word(&mut self, value: bool) -> Word {
+        self.word(Term::new_bool(value).into())
+    }
+}
+
+/// The functions compiled code can call. All arguments and return values are
+/// 64 bit integers, the first argument is always the `Context`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Helper {
+    /// `(fun, args, num_args) -> result`
+    Apply,
+    /// `(elems, num_elems) -> tuple`
+    MakeTuple,
+    /// `(term) -> arity`, or -1 if the term is not a tuple.
+    TupleArity,
+    /// `(tuple, n) -> elem`
+    TupleElement,
+    /// `(lhs, rhs) -> 0 | 1`
+    ExactEq,
+    /// `(op, lhs, rhs) -> bool`, where `op` is from `binop_code`.
+    BinOp,
+    /// `(term, type, arity) -> 0 | 1`, where `type` is from `type_code`.
+    IsType,
+    /// `(class, reason, trace)`
+    Throw,
+    /// `(n) -> term`, reads entry `n` of the pending exception.
+    Exception,
+}
+
+impl Helper {
+    pub const ALL: &'static [Helper] = &[
+        Helper::Apply,
+        Helper::MakeTuple,
+        Helper::TupleArity,
+        Helper::TupleElement,
+        Helper::ExactEq,
+        Helper::BinOp,
+        Helper::IsType,
+        Helper::Throw,
+        Helper::Exception,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Helper::Apply => "__eir_jit_apply",
+            Helper::MakeTuple => "__eir_jit_make_tuple",
+            Helper::TupleArity => "__eir_jit_tuple_arity",
+            Helper::TupleElement => "__eir_jit_tuple_element",
+            Helper::ExactEq => "__eir_jit_exact_eq",
+            Helper::BinOp => "__eir_jit_binop",
+            Helper::IsType => "__eir_jit_is_type",
+            Helper::Throw => "__eir_jit_throw",
+            Helper::Exception => "__eir_jit_exception",
+        }
+    }
+
+    /// The number of arguments, not including the context.
+    pub fn arity(self) -> usize {
+        match self {
+            Helper::Apply => 3,
+            Helper::MakeTuple => 2,
+            Helper::TupleArity => 1,
+            Helper::TupleElement => 2,
+            Helper::ExactEq => 2,
+            Helper::BinOp => 3,
+            Helper::IsType => 3,
+            Helper::Throw => 3,
+            Helper::Exception => 1,
+        }
+    }
+
+    pub fn returns(self) -> bool {
+        self != Helper::Throw
+    }
+
+    pub fn address(self) -> *const u8 {
+        match self {
+            Helper::Apply => apply as *const u8,
+            Helper::MakeTuple => make_tuple as *const u8,
+            Helper::TupleArity => tuple_arity as *const u8,
+            Helper::TupleElement => tuple_element as *const u8,
+            Helper::ExactEq => exact_eq as *const u8,
+            Helper::BinOp => binop as *const u8,
+            Helper::IsType => is_type as *const u8,
+            Helper::Throw => throw as *const u8,
+            Helper::Exception => exception as *const u8,
+        }
+    }
+}
+
+pub fn binop_code(op: BinOp) -> i64 {
+    match op {
+        BinOp::Equal => 0,
+        BinOp::NotEqual => 1,
+        BinOp::LessEqual => 2,
+        BinOp::Less => 3,
+        BinOp::GreaterEqual => 4,
+        BinOp::Greater => 5,
+        BinOp::ExactEqual => 6,
+        BinOp::ExactNotEqual => 7,
+    }
+}
+
+/// The type code and arity passed to `Helper::IsType`.
+pub fn type_code(typ: BasicType) -> (i64, i64) {
+    match typ {
+        BasicType::List => (0, 0),
+        BasicType::ListCell => (1, 0),
+        BasicType::Nil => (2, 0),
+        BasicType::Tuple(arity) => (3, arity as i64),
+        BasicType::Map => (4, 0),
+        BasicType::Number => (5, 0),
+        BasicType::Float => (6, 0),
+        BasicType::Integer => (7, 0),
+        BasicType::SmallInteger => (8, 0),
+        BasicType::BigInteger => (9, 0),
+    }
+}
+
+extern "C" fn apply(ctx: &mut Context, fun: Word, args: *const Word, num_args: u64) -> Word {
+    let fun = ctx.term(fun);
+    let args = ctx.words(args, num_args);
+    match ctx.vm.call_fun(&mut ctx.proc, fun, &args) {
+        Ok(ret) => ctx.word(ret),
+        Err(exception) => {
+            ctx.exception = Some(exception);
+            NONE
+        }
+    }
+}
+
+extern "C" fn make_tuple(ctx: &mut Context, elems: *const Word, num_elems: u64) -> Word {
+    let elems = ctx.words(elems, num_elems);
+    ctx.word(Term::Tuple(elems).into())
+}
+
+extern "C" fn tuple_arity(ctx: &mut Context, term: Word) -> i64 {
+    match &*ctx.term(term) {
+        Term::Tuple(elems) => elems.len() as i64,
+        _ => -1,
+    }
+}
+
+extern "C" fn tuple_element(ctx: &mut Context, tuple: Word, n: u64) -> Word {
+    let elem = ctx.term(tuple).as_tuple().unwrap()[n as usize].clone();
+    ctx.word(elem)
+}
+
+extern "C" fn exact_eq(ctx: &mut Context, lhs: Word, rhs: Word) -> u64 {
+    (lhs == rhs || ctx.term(lhs).erl_exact_eq(&*ctx.term(rhs))) as u64
+}
+
+extern "C" fn binop(ctx: &mut Context, op: i64, lhs: Word, rhs: Word) -> Word {
+    let lhs = ctx.term(lhs);
+    let rhs = ctx.term(rhs);
+    let result = match op {
+        0 => lhs.erl_eq(&*rhs),
+        1 => !lhs.erl_eq(&*rhs),
+        2 => lhs.erl_ord(&*rhs) != Ordering::Greater,
+        3 => lhs.erl_ord(&*rhs) == Ordering::Less,
+        4 => lhs.erl_ord(&*rhs) != Ordering::Less,
+        5 => lhs.erl_ord(&*rhs) == Ordering::Greater,
+        6 => lhs.erl_exact_eq(&*rhs),
+        7 => !lhs.erl_exact_eq(&*rhs),
+        _ => unreachable!(),
+    };
+    ctx.bool_word(result)
+}
+
+extern "C" fn is_type(ctx: &mut Context, term: Word, typ: i64, arity: i64) -> u64 {
+    let term = ctx.term(term);
+    let result = match (typ, &*term) {
+        (0, Term::Nil) | (0, Term::ListCell(_, _)) => true,
+        (1, Term::ListCell(_, _)) => true,
+        (2, Term::Nil) => true,
+        (3, Term::Tuple(elems)) => elems.len() as i64 == arity,
+        (4, Term::Map(_)) => true,
+        (5, Term::Integer(_)) | (5, Term::Float(_)) => true,
+        (6, Term::Float(_)) => true,
+        (7, Term::Integer(_)) => true,
+        (8, Term::Integer(int)) => int.to_i64().is_some(),
+        (9, Term::Integer(int)) => int.to_i64().is_none(),
+        _ => false,
+    };
+    result as u64
+}
+
+extern "C" fn throw(ctx: &mut Context, typ: Word, reason: Word, trace: Word) {
+    ctx.exception = Some((ctx.term(typ), ctx.term(reason), ctx.term(trace)));
+}
+
+extern "C" fn exception(ctx: &mut Context, n: u64) -> Word {
+    let term = {
+        let (typ, reason, trace) = ctx.exception.as_ref().unwrap();
+        match n {
+            0 => typ.clone(),
+            1 => reason.clone(),
+            2 => trace.clone(),
+            _ => unreachable!(),
+        }
+    };
+    ctx.word(term)
+}
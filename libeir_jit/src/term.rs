@@ -0,0 +1,147 @@
+//! The representation of terms in compiled code.
+//!
+//! Every term is a single 64 bit word:
+//!
+//! * `xx1`: A small integer, stored in the upper 63 bits.
+//! * `010`: An atom, the upper bits are an index into the `AtomTable`.
+//! * `110`: Nil.
+//! * `000`: A pointer to a `Rc<Term>` of the interpreter. Every other term
+//!   is boxed like this. The boxes are owned by the `Jit` for constants, or
+//!   by the `Context` of the current call.
+//!
+//! The word `0` is never a valid term, compiled functions return it to
+//! signal that an exception was thrown.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use num_traits::ToPrimitive;
+
+use libeir_intern::Symbol;
+use libeir_interpreter::{MapTerm, Term};
+use libeir_ir::{AtomicTerm, Const, ConstKind, ConstantContainer};
+
+pub type Word = u64;
+
+/// Returned by compiled functions that throw.
+pub const NONE: Word = 0;
+pub const NIL: Word = 0b110;
+
+const TAG_MASK: Word = 0b111;
+const TAG_ATOM: Word = 0b010;
+
+/// The range of integers that fit in a small integer word.
+pub const SMALL_MIN: i64 = std::i64::MIN >> 1;
+pub const SMALL_MAX: i64 = std::i64::MAX >> 1;
+
+pub fn small_int(num: i64) -> Option<Word> {
+    if num >= SMALL_MIN && num <= SMALL_MAX {
+        Some(((num << 1) | 1) as Word)
+    } else {
+        None
+    }
+}
+
+pub fn is_small_int(word: Word) -> bool {
+    word & 1 == 1
+}
+
+/// Maps atoms to the indices used in atom words.
+///
+/// Atoms are never removed, an index stays valid for the lifetime of the
+/// table.
+#[derive(Default)]
+pub struct AtomTable {
+    symbols: Vec<Symbol>,
+    indices: HashMap<Symbol, usize>,
+}
+
+impl AtomTable {
+    pub fn word(&mut self, symbol: Symbol) -> Word {
+        let symbols = &mut self.symbols;
+        let idx = *self.indices.entry(symbol).or_insert_with(|| {
+            symbols.push(symbol);
+            symbols.len() - 1
+        });
+        ((idx as Word) << 3) | TAG_ATOM
+    }
+
+    pub fn symbol(&self, word: Word) -> Symbol {
+        debug_assert!(word & TAG_MASK == TAG_ATOM);
+        self.symbols[(word >> 3) as usize]
+    }
+}
+
+/// Storage for boxed terms. The address of a box is the word for it.
+#[derive(Default)]
+pub struct Heap {
+    boxes: Vec<Box<Rc<Term>>>,
+}
+
+impl Heap {
+    /// Converts a term to a word, boxing it if needed.
+    pub fn word(&mut self, atoms: &mut AtomTable, term: Rc<Term>) -> Word {
+        match &*term {
+            Term::Integer(int) => {
+                if let Some(word) = int.to_i64().and_then(small_int) {
+                    return word;
+                }
+            }
+            Term::Atom(atom) => return atoms.word(*atom),
+            Term::Nil => return NIL,
+            _ => (),
+        }
+
+        let boxed = Box::new(term);
+        let word = &*boxed as *const Rc<Term> as Word;
+        self.boxes.push(boxed);
+        word
+    }
+}
+
+/// Converts a word back to a term.
+///
+/// The word must have been created with the given atom table, and any box it
+/// points to must still be alive.
+pub fn term(atoms: &AtomTable, word: Word) -> Rc<Term> {
+    debug_assert!(word != NONE);
+    if is_small_int(word) {
+        Term::Integer(((word as i64) >> 1).into()).into()
+    } else if word == NIL {
+        Term::Nil.into()
+    } else if word & TAG_MASK == TAG_ATOM {
+        Term::Atom(atoms.symbol(word)).into()
+    } else {
+        unsafe { (*(word as *const Rc<Term>)).clone() }
+    }
+}
+
+/// Builds the term for a constant.
+pub fn const_term(cons: &ConstantContainer, value: Const) -> Rc<Term> {
+    match cons.const_kind(value) {
+        ConstKind::Atomic(AtomicTerm::Atom(atom)) => Term::Atom(atom.0).into(),
+        ConstKind::Atomic(AtomicTerm::Int(int)) => Term::Integer(int.0.into()).into(),
+        ConstKind::Atomic(AtomicTerm::BigInt(int)) => Term::Integer(int.0.clone()).into(),
+        ConstKind::Atomic(AtomicTerm::Float(flt)) => Term::Float(flt.0.inner().into()).into(),
+        ConstKind::Atomic(AtomicTerm::Binary(bin)) => {
+            Term::Binary(Rc::new(bin.0.clone().into())).into()
+        }
+        ConstKind::Atomic(AtomicTerm::Nil) => Term::Nil.into(),
+        ConstKind::ListCell { head, tail } => {
+            Term::ListCell(const_term(cons, *head), const_term(cons, *tail)).into()
+        }
+        ConstKind::Tuple { entries } => {
+            let entries = entries.as_slice(&cons.const_pool);
+            Term::Tuple(entries.iter().map(|e| const_term(cons, *e)).collect()).into()
+        }
+        ConstKind::Map { keys, values } => {
+            let keys = keys.as_slice(&cons.const_pool);
+            let values = values.as_slice(&cons.const_pool);
+            let mut map = MapTerm::new();
+            for (key, value) in keys.iter().zip(values.iter()) {
+                map.insert(const_term(cons, *key), const_term(cons, *value));
+            }
+            Term::Map(map).into()
+        }
+    }
+}
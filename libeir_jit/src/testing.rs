@@ -0,0 +1,45 @@
+//! Helpers for testing the JIT against the interpreter.
+
+use libeir_interpreter::{ErlExactEq, Term, VMState};
+use libeir_ir::{FunctionIdent, Module};
+
+use crate::Jit;
+
+/// Compiles the module, and loads it into a new VM along with the builtin
+/// modules.
+pub fn load(module: Module) -> (Jit, VMState) {
+    let mut jit = Jit::new();
+    jit.add_module(&module);
+
+    let mut vm = VMState::new();
+    vm.add_builtin_modules();
+    vm.add_erlang_module(module);
+
+    (jit, vm)
+}
+
+/// Calls the function both natively and in the interpreter, and checks
+/// that the results are the same. The function must be compiled.
+pub fn assert_same(jit: &Jit, vm: &mut VMState, fun: &FunctionIdent, args: &[Term]) -> Term {
+    assert!(jit.is_compiled(fun), "{} was not compiled", fun);
+
+    let expected = vm.call(fun, args);
+    let actual = jit.call(vm, fun, args);
+    match (expected, actual) {
+        (Ok(expected), Ok(actual)) => {
+            assert!(
+                expected.erl_exact_eq(&*actual),
+                "{:?} != {:?}",
+                expected,
+                actual
+            );
+            (*actual).clone()
+        }
+        (Err(expected), Err(actual)) => {
+            assert!(expected.0.erl_exact_eq(&*actual.0));
+            assert!(expected.1.erl_exact_eq(&*actual.1));
+            Term::Nil
+        }
+        (expected, actual) => panic!("{:?} != {:?}", expected, actual),
+    }
+}
@@ -0,0 +1,214 @@
+use libeir_intern::Ident;
+use libeir_interpreter::{Term, VMState};
+use libeir_ir::{parse_module_unwrap, FunctionIdent};
+
+use crate::testing::assert_same;
+use crate::Jit;
+
+fn load(text: &str) -> (Jit, VMState) {
+    crate::testing::load(parse_module_unwrap(text))
+}
+
+fn ident(name: &str, arity: usize) -> FunctionIdent {
+    FunctionIdent {
+        module: Ident::from_str("foo"),
+        name: Ident::from_str(name),
+        arity,
+    }
+}
+
+#[test]
+fn fib() {
+    let (jit, mut vm) = load(
+        "
+a'foo' {
+    a'fib'/1 {
+        entry(%ret, %thr, %x):
+            a'erlang':a'<'/2(%x, 2) => check except %thr;
+        check(%lt):
+            if_bool %lt base rec;
+        base():
+            %ret(1);
+        rec():
+            a'erlang':a'-'/2(%x, 1) => rec1 except %thr;
+        rec1(%x1):
+            a'foo':a'fib'/1(%x1) => rec2 except %thr;
+        rec2(%f1):
+            a'erlang':a'-'/2(%x, 2) => rec3 except %thr;
+        rec3(%x2):
+            a'foo':a'fib'/1(%x2) => rec4 except %thr;
+        rec4(%f2):
+            a'erlang':a'+'/2(%f1, %f2) => %ret except %thr;
+    }
+}
+",
+    );
+
+    let fib = ident("fib", 1);
+    assert!(jit.is_compiled(&fib));
+    for n in 0..15 {
+        assert_same(&jit, &mut vm, &fib, &[n.into()]);
+    }
+    let result = assert_same(&jit, &mut vm, &fib, &[20.into()]);
+    assert_eq!(result.as_i64(), Some(10946));
+}
+
+#[test]
+fn tuples() {
+    let (jit, mut vm) = load(
+        "
+a'foo' {
+    a'swap'/1 {
+        entry(%ret, %thr, %t):
+            match %t {
+                {} arity 2 => tup;
+                value a'none' => none;
+                _ => other;
+            };
+        tup(%a, %b):
+            %ret({%b, %a});
+        none():
+            %ret(a'empty');
+        other():
+            %ret({a'error', %t});
+    }
+}
+",
+    );
+
+    let swap = ident("swap", 1);
+    assert!(jit.is_compiled(&swap));
+
+    let pair = Term::Tuple(vec![Term::new_atom("a").into(), Term::new_i64(1).into()]);
+    let result = assert_same(&jit, &mut vm, &swap, &[pair]);
+    assert_eq!(
+        result.as_tuple().unwrap()[1].as_atom(),
+        Some(Ident::from_str("a").name)
+    );
+
+    assert_same(&jit, &mut vm, &swap, &[Term::new_atom("none")]);
+    assert_same(&jit, &mut vm, &swap, &[Term::new_i64(5)]);
+    assert_same(&jit, &mut vm, &swap, &[Term::Tuple(vec![])]);
+}
+
+#[test]
+fn integer_overflow() {
+    let (jit, mut vm) = load(
+        "
+a'foo' {
+    a'add'/2 {
+        entry(%ret, %thr, %a, %b):
+            a'erlang':a'+'/2(%a, %b) => %ret except %thr;
+    }
+    a'sub'/2 {
+        entry(%ret, %thr, %a, %b):
+            a'erlang':a'-'/2(%a, %b) => %ret except %thr;
+    }
+}
+",
+    );
+
+    let add = ident("add", 2);
+    let sub = ident("sub", 2);
+    assert!(jit.is_compiled(&add));
+    assert!(jit.is_compiled(&sub));
+
+    let max = crate::term::SMALL_MAX;
+    let min = crate::term::SMALL_MIN;
+    assert_same(&jit, &mut vm, &add, &[max.into(), 1.into()]);
+    assert_same(&jit, &mut vm, &add, &[min.into(), (-1).into()]);
+    assert_same(&jit, &mut vm, &sub, &[min.into(), 1.into()]);
+    assert_same(&jit, &mut vm, &sub, &[max.into(), (-1).into()]);
+    assert_same(
+        &jit,
+        &mut vm,
+        &add,
+        &[std::i64::MAX.into(), std::i64::MAX.into()],
+    );
+    assert_same(&jit, &mut vm, &add, &[Term::new_atom("a"), 2.into()]);
+}
+
+#[test]
+fn exceptions() {
+    let (jit, mut vm) = load(
+        "
+a'foo' {
+    a'catch'/1 {
+        entry(%ret, %thr, %a):
+            a'erlang':a'error'/1(%a) => %ret except handler;
+        handler(%class, %reason, %trace):
+            %ret({%class, %reason});
+    }
+    a'rethrow'/1 {
+        entry(%ret, %thr, %a):
+            a'erlang':a'error'/1(%a) => %ret except handler;
+        handler(%class, %reason, %trace):
+            %thr(%class, {a'wrapped', %reason}, %trace);
+    }
+}
+",
+    );
+
+    let catch = ident("catch", 1);
+    let rethrow = ident("rethrow", 1);
+    assert!(jit.is_compiled(&catch));
+    assert!(jit.is_compiled(&rethrow));
+
+    assert_same(&jit, &mut vm, &catch, &[Term::new_atom("oops")]);
+    assert_same(&jit, &mut vm, &rethrow, &[Term::new_atom("oops")]);
+}
+
+#[test]
+fn interpreter_fallback() {
+    let (jit, mut vm) = load(
+        "
+a'foo' {
+    a'adder'/1 {
+        entry(%ret, %thr, %a):
+            %ret(inner);
+        inner(%iret, %ithr, %b):
+            a'erlang':a'+'/2(%a, %b) => %iret except %ithr;
+    }
+    a'apply'/1 {
+        entry(%ret, %thr, %a):
+            a'foo':a'adder'/1(%a) => call except %thr;
+        call(%f):
+            %f(10) => %ret except %thr;
+    }
+}
+",
+    );
+
+    let adder = ident("adder", 1);
+    let apply = ident("apply", 1);
+    assert!(!jit.is_compiled(&adder));
+    assert!(jit.is_compiled(&apply));
+
+    let result = assert_same(&jit, &mut vm, &apply, &[5.into()]);
+    assert_eq!(result.as_i64(), Some(15));
+}
+
+#[test]
+fn local_calls() {
+    let (jit, mut vm) = load(
+        "
+a'foo' {
+    a'double'/1 {
+        entry(%ret, %thr, %a):
+            a'erlang':a'+'/2(%a, %a) => %ret except %thr;
+    }
+    a'quad'/1 {
+        entry(%ret, %thr, %a):
+            _:a'double'/1(%a) => next except %thr;
+        next(%b):
+            _:a'double'/1(%b) => %ret except %thr;
+    }
+}
+",
+    );
+
+    let quad = ident("quad", 1);
+    let result = assert_same(&jit, &mut vm, &quad, &[3.into()]);
+    assert_eq!(result.as_i64(), Some(12));
+    assert_same(&jit, &mut vm, &quad, &[Term::new_atom("a")]);
+}
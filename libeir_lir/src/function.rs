@@ -0,0 +1,209 @@
+use cranelift_entity::{entity_impl, PrimaryMap};
+
+use libeir_ir::{Const, ConstantContainer, FunctionIdent, OpKind, PrimOpKind, Value};
+
+/// A function in a LIR unit.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FunId(u32);
+entity_impl!(FunId, "fun");
+
+/// A basic block within a LIR function.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Block(u32);
+entity_impl!(Block, "block");
+
+/// A SSA variable within a LIR function.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Var(u32);
+entity_impl!(Var, "%");
+
+/// The result of lowering a single Eir function.
+///
+/// Contains the root function, along with every function nested within it.
+#[derive(Debug, Clone)]
+pub struct Unit {
+    pub ident: FunctionIdent,
+    /// The constants referenced by `Operand::Const`.
+    pub cons: ConstantContainer,
+    pub root: FunId,
+    pub functions: PrimaryMap<FunId, Function>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    /// The Eir block this function was created from.
+    pub source: libeir_ir::Block,
+    /// The variables captured from the enclosing function, in the order they
+    /// are stored in the closure environment. Always empty for the root
+    /// function.
+    pub env: Vec<Var>,
+    /// The first block executed when the function is called. Its parameters
+    /// are the arguments of the function.
+    pub entry: Block,
+    pub blocks: PrimaryMap<Block, BlockData>,
+    pub vars: PrimaryMap<Var, VarData>,
+}
+
+impl Function {
+    /// The number of arguments the function takes, not including the
+    /// environment.
+    pub fn arity(&self) -> usize {
+        self.blocks[self.entry].params.len()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VarData {
+    /// The Eir value this variable was created from.
+    pub source: Option<Value>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockData {
+    /// The Eir block this block was created from.
+    pub source: libeir_ir::Block,
+    pub params: Vec<Var>,
+    pub insts: Vec<Inst>,
+    pub term: Terminator,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    Var(Var),
+    Const(Const),
+    /// A block in the current function. Only valid as a destination of a
+    /// `Terminator::Op`.
+    Block(Block),
+    /// A value list. Not an actual value, only valid as a read of a
+    /// `Terminator::Op` or as arguments to a block.
+    List(Vec<Operand>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Inst {
+    /// `dest = kind(args..)`
+    PrimOp {
+        dest: Var,
+        kind: PrimOpKind,
+        args: Vec<Operand>,
+    },
+    /// Constructs a closure of `fun`, with the given values as its
+    /// environment.
+    MakeClosure {
+        dest: Var,
+        fun: FunId,
+        env: Vec<Operand>,
+    },
+}
+
+impl Inst {
+    pub fn dest(&self) -> Var {
+        match self {
+            Inst::PrimOp { dest, .. } => *dest,
+            Inst::MakeClosure { dest, .. } => *dest,
+        }
+    }
+
+    pub fn operands(&self) -> &[Operand] {
+        match self {
+            Inst::PrimOp { args, .. } => args,
+            Inst::MakeClosure { env, .. } => env,
+        }
+    }
+}
+
+/// Where a non-tail call continues.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Cont {
+    /// Branch to the block with the results as arguments.
+    Block(Block),
+    /// Return the results from the current function.
+    Return,
+    /// Throw the results from the current function.
+    Throw,
+}
+
+#[derive(Debug, Clone)]
+pub enum Terminator {
+    Jump {
+        target: Block,
+        args: Vec<Operand>,
+    },
+    /// Returns from the current function.
+    Return {
+        values: Vec<Operand>,
+    },
+    /// Throws from the current function.
+    Throw {
+        values: Vec<Operand>,
+    },
+    /// Calls a function in a new stack frame. At least one of `ret` and `thr`
+    /// is a block in the current function.
+    Call {
+        callee: Operand,
+        args: Vec<Operand>,
+        ret: Cont,
+        thr: Cont,
+    },
+    /// Calls a function, replacing the current stack frame.
+    TailCall {
+        callee: Operand,
+        args: Vec<Operand>,
+    },
+    /// Any other operation. Reads are as in Eir, with the destinations as
+    /// `Operand::Block`.
+    Op {
+        kind: OpKind,
+        reads: Vec<Operand>,
+    },
+    Unreachable,
+}
+
+impl Terminator {
+    /// Calls `f` for every operand read by the terminator, not including the
+    /// contents of value lists.
+    pub fn walk_operands<F>(&self, mut f: F)
+    where
+        F: FnMut(&Operand),
+    {
+        match self {
+            Terminator::Jump { args, .. } => args.iter().for_each(f),
+            Terminator::Return { values } | Terminator::Throw { values } => {
+                values.iter().for_each(f)
+            }
+            Terminator::Call { callee, args, .. } | Terminator::TailCall { callee, args } => {
+                f(callee);
+                args.iter().for_each(f);
+            }
+            Terminator::Op { reads, .. } => reads.iter().for_each(f),
+            Terminator::Unreachable => (),
+        }
+    }
+
+    /// The blocks this terminator can branch to.
+    pub fn successors(&self) -> Vec<Block> {
+        let mut out = Vec::new();
+        match self {
+            Terminator::Jump { target, .. } => out.push(*target),
+            Terminator::Call { ret, thr, .. } => {
+                for cont in [ret, thr].iter() {
+                    if let Cont::Block(block) = cont {
+                        out.push(*block);
+                    }
+                }
+            }
+            Terminator::Op { reads, .. } => {
+                fn collect(operand: &Operand, out: &mut Vec<Block>) {
+                    match operand {
+                        Operand::Block(block) => out.push(*block),
+                        Operand::List(list) => list.iter().for_each(|o| collect(o, out)),
+                        _ => (),
+                    }
+                }
+                reads.iter().for_each(|o| collect(o, &mut out));
+            }
+            _ => (),
+        }
+        out
+    }
+}
@@ -0,0 +1,32 @@
+//! # LIR
+//! A more traditional low level IR built from the CPS based Eir.
+//!
+//! Eir represents everything as continuations, which makes it a good fit
+//! for optimization, but means every backend needs to rediscover where the
+//! stack frames are. LIR does this once. A single Eir `Function` is split
+//! into one LIR function per `FunctionEntry` in its `FunctionTree`:
+//!
+//! * Calls to the return and throw continuations become explicit `return`
+//!   and `throw` terminators.
+//! * Function calls that pass on both continuations become tail calls.
+//! * Nested functions get an explicit environment, and are constructed with
+//!   a `closure` instruction.
+//! * Functions consist of basic blocks with parameters, which act as phi
+//!   nodes.
+//!
+//! No passes operate on LIR, it only exists to ease lowering to other IRs.
+
+mod function;
+pub use function::{Block, BlockData, Cont, FunId, Function, Inst, Operand, Terminator};
+pub use function::{Unit, Var, VarData};
+
+mod lower;
+pub use lower::{lower_function, LowerError};
+
+mod printer;
+
+mod validate;
+pub use validate::ValidationError;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,353 @@
+use std::collections::{BTreeMap, HashMap};
+
+use cranelift_entity::PrimaryMap;
+
+use libeir_ir::{CallKind, FunctionEntry, OpKind, PrimOpKind, Value, ValueKind};
+use libeir_lowerutils::LowerData;
+
+use crate::function::{Block, BlockData, Cont, FunId, Function, Inst, Operand, Terminator};
+use crate::function::{Unit, Var, VarData};
+
+#[derive(Debug)]
+pub enum LowerError {
+    /// The block has no operation.
+    UnfinishedBlock { block: libeir_ir::Block },
+    /// A value was read that is neither defined in the function nor captured
+    /// from its environment.
+    UnboundValue {
+        block: libeir_ir::Block,
+        value: Value,
+    },
+    /// An escape continuation was used as a regular value.
+    EscapeAsValue {
+        block: libeir_ir::Block,
+        value: Value,
+    },
+    /// Control flow to something that is not a block within the function or
+    /// one of its escapes.
+    InvalidControlFlow {
+        block: libeir_ir::Block,
+        target: Value,
+    },
+    /// The root function closes over values.
+    RootCaptures { values: Vec<Value> },
+}
+
+/// Splits an Eir function into LIR functions, one per `FunctionEntry` in the
+/// function tree of `data`.
+///
+/// The function tree must have been built with continuations resolved, as
+/// `libeir_lowerutils::analyze` does.
+pub fn lower_function(fun: &libeir_ir::Function, data: &LowerData) -> Result<Unit, LowerError> {
+    let tree = &data.func_tree;
+
+    // Assign function ids up front, the root function first.
+    let mut fun_ids = BTreeMap::new();
+    let mut next = 0;
+    fun_ids.insert(tree.root_fun, FunId::from_u32(0));
+    for block in tree.functions.keys() {
+        if *block != tree.root_fun {
+            next += 1;
+            fun_ids.insert(*block, FunId::from_u32(next));
+        }
+    }
+
+    // The environment of a function is the set of values live at its
+    // entry. The order here is the layout of the closure environment.
+    let envs: BTreeMap<libeir_ir::Block, Vec<Value>> = tree
+        .functions
+        .keys()
+        .map(|block| (*block, data.live.live_at(*block).iter().collect()))
+        .collect();
+
+    let root_env = &envs[&tree.root_fun];
+    if !root_env.is_empty() {
+        return Err(LowerError::RootCaptures {
+            values: root_env.clone(),
+        });
+    }
+
+    let mut functions = BTreeMap::new();
+    for (block, entry) in tree.functions.iter() {
+        let lowerer = FunctionLowerer {
+            fun,
+            entry,
+            fun_ids: &fun_ids,
+            envs: &envs,
+            vars: PrimaryMap::new(),
+            values: HashMap::new(),
+            blocks: BTreeMap::new(),
+        };
+        functions.insert(fun_ids[block], lowerer.lower()?);
+    }
+
+    let mut unit_functions = PrimaryMap::new();
+    for (id, function) in functions {
+        let pushed = unit_functions.push(function);
+        debug_assert!(pushed == id);
+    }
+
+    Ok(Unit {
+        ident: *fun.ident(),
+        cons: fun.cons().clone(),
+        root: fun_ids[&tree.root_fun],
+        functions: unit_functions,
+    })
+}
+
+struct FunctionLowerer<'a> {
+    fun: &'a libeir_ir::Function,
+    entry: &'a FunctionEntry,
+    fun_ids: &'a BTreeMap<libeir_ir::Block, FunId>,
+    envs: &'a BTreeMap<libeir_ir::Block, Vec<Value>>,
+
+    vars: PrimaryMap<Var, VarData>,
+    /// Eir values that are bound for the whole function, block arguments
+    /// and the environment.
+    values: HashMap<Value, Var>,
+    blocks: BTreeMap<libeir_ir::Block, Block>,
+}
+
+impl<'a> FunctionLowerer<'a> {
+    fn lower(mut self) -> Result<Function, LowerError> {
+        let fun = self.fun;
+        let entry = self.entry.entry;
+
+        let envs = self.envs;
+        let env = envs[&entry].iter().map(|value| self.bind(*value)).collect();
+
+        // Blocks are created in scope order, with the entry first.
+        let mut order = vec![entry];
+        order.extend(self.entry.scope.iter().cloned().filter(|b| *b != entry));
+        for (idx, block) in order.iter().enumerate() {
+            self.blocks.insert(*block, Block::from_u32(idx as u32));
+        }
+
+        // All parameters are bound before any block is lowered, blocks may
+        // read the arguments of any block dominating them.
+        let mut params = Vec::with_capacity(order.len());
+        for block in order.iter().cloned() {
+            let mut args = fun.block_args(block);
+            if block == entry {
+                let escapes = self.entry.ret.iter().chain(self.entry.thr.iter()).count();
+                args = &args[escapes..];
+            }
+            let block_params: Vec<_> = args.iter().map(|arg| self.bind(*arg)).collect();
+            params.push(block_params);
+        }
+
+        let mut blocks = PrimaryMap::new();
+        for (block, params) in order.iter().cloned().zip(params) {
+            let mut lowerer = BlockLowerer {
+                parent: &mut self,
+                block,
+                insts: Vec::new(),
+                cache: HashMap::new(),
+            };
+            let term = lowerer.terminator()?;
+            let insts = lowerer.insts;
+
+            blocks.push(BlockData {
+                source: block,
+                params,
+                insts,
+                term,
+            });
+        }
+
+        Ok(Function {
+            source: entry,
+            env,
+            entry: Block::from_u32(0),
+            blocks,
+            vars: self.vars,
+        })
+    }
+
+    fn bind(&mut self, value: Value) -> Var {
+        let var = self.vars.push(VarData {
+            source: Some(value),
+        });
+        self.values.insert(value, var);
+        var
+    }
+
+    fn is_escape(&self, value: Value) -> bool {
+        Some(value) == self.entry.ret || Some(value) == self.entry.thr
+    }
+}
+
+struct BlockLowerer<'a, 'b> {
+    parent: &'b mut FunctionLowerer<'a>,
+    block: libeir_ir::Block,
+    insts: Vec<Inst>,
+    /// Primops and closures are materialized once per block.
+    cache: HashMap<Value, Var>,
+}
+
+impl<'a, 'b> BlockLowerer<'a, 'b> {
+    fn terminator(&mut self) -> Result<Terminator, LowerError> {
+        let fun = self.parent.fun;
+        let block = self.block;
+        let reads = fun.block_reads(block);
+
+        let kind = match fun.block_kind(block) {
+            Some(kind) => kind,
+            None => return Err(LowerError::UnfinishedBlock { block }),
+        };
+
+        let term = match kind {
+            OpKind::Call(CallKind::ControlFlow) => {
+                let target = reads[0];
+                let args = self.operands(&reads[1..])?;
+                if Some(target) == self.parent.entry.ret {
+                    Terminator::Return { values: args }
+                } else if Some(target) == self.parent.entry.thr {
+                    Terminator::Throw { values: args }
+                } else {
+                    match self.local_block(target) {
+                        Some(target) => Terminator::Jump { target, args },
+                        None => return Err(LowerError::InvalidControlFlow { block, target }),
+                    }
+                }
+            }
+            OpKind::Call(CallKind::Function) => {
+                let callee = self.operand(reads[0])?;
+                let ret = self.cont(reads[1])?;
+                let thr = self.cont(reads[2])?;
+                let args = self.operands(&reads[3..])?;
+                match (ret, thr) {
+                    (Cont::Return, Cont::Throw) => Terminator::TailCall { callee, args },
+                    (ret, thr) => Terminator::Call {
+                        callee,
+                        args,
+                        ret,
+                        thr,
+                    },
+                }
+            }
+            OpKind::Unreachable => Terminator::Unreachable,
+            kind => {
+                let mut operands = Vec::with_capacity(reads.len());
+                for read in reads.iter() {
+                    operands.push(self.op_operand(*read)?);
+                }
+                Terminator::Op {
+                    kind: kind.clone(),
+                    reads: operands,
+                }
+            }
+        };
+        Ok(term)
+    }
+
+    /// A block within the current function that is branched to, rather than
+    /// captured.
+    fn local_block(&self, value: Value) -> Option<Block> {
+        let block = self.parent.fun.value_block(value)?;
+        if block == self.parent.entry.entry {
+            return None;
+        }
+        self.parent.blocks.get(&block).cloned()
+    }
+
+    /// A read of a `Terminator::Op`, where blocks may also appear within
+    /// value lists, like the branches of a `Match`.
+    fn op_operand(&mut self, value: Value) -> Result<Operand, LowerError> {
+        let fun = self.parent.fun;
+        if let Some(target) = self.local_block(value) {
+            return Ok(Operand::Block(target));
+        }
+        if let Some(prim) = fun.value_primop(value) {
+            if let PrimOpKind::ValueList = fun.primop_kind(prim) {
+                let mut list = Vec::new();
+                for read in fun.primop_reads(prim).iter() {
+                    list.push(self.op_operand(*read)?);
+                }
+                return Ok(Operand::List(list));
+            }
+        }
+        self.operand(value)
+    }
+
+    fn cont(&mut self, value: Value) -> Result<Cont, LowerError> {
+        if Some(value) == self.parent.entry.ret {
+            Ok(Cont::Return)
+        } else if Some(value) == self.parent.entry.thr {
+            Ok(Cont::Throw)
+        } else {
+            match self.local_block(value) {
+                Some(block) => Ok(Cont::Block(block)),
+                None => Err(LowerError::InvalidControlFlow {
+                    block: self.block,
+                    target: value,
+                }),
+            }
+        }
+    }
+
+    fn operands(&mut self, values: &[Value]) -> Result<Vec<Operand>, LowerError> {
+        values.iter().map(|v| self.operand(*v)).collect()
+    }
+
+    fn operand(&mut self, value: Value) -> Result<Operand, LowerError> {
+        let fun = self.parent.fun;
+
+        if let Some(var) = self.parent.values.get(&value) {
+            return Ok(Operand::Var(*var));
+        }
+        if let Some(var) = self.cache.get(&value) {
+            return Ok(Operand::Var(*var));
+        }
+        if self.parent.is_escape(value) {
+            return Err(LowerError::EscapeAsValue {
+                block: self.block,
+                value,
+            });
+        }
+
+        let inst = match fun.value_kind(value) {
+            ValueKind::Const(cons) => return Ok(Operand::Const(cons)),
+            ValueKind::PrimOp(prim) => {
+                let reads = fun.primop_reads(prim);
+                match fun.primop_kind(prim) {
+                    PrimOpKind::ValueList => return Ok(Operand::List(self.operands(reads)?)),
+                    kind => {
+                        let args = self.operands(reads)?;
+                        let dest = self.parent.vars.push(VarData {
+                            source: Some(value),
+                        });
+                        Inst::PrimOp {
+                            dest,
+                            kind: *kind,
+                            args,
+                        }
+                    }
+                }
+            }
+            ValueKind::Block(block) if self.parent.fun_ids.contains_key(&block) => {
+                let envs = self.parent.envs;
+                let env = self.operands(&envs[&block])?;
+                let dest = self.parent.vars.push(VarData {
+                    source: Some(value),
+                });
+                Inst::MakeClosure {
+                    dest,
+                    fun: self.parent.fun_ids[&block],
+                    env,
+                }
+            }
+            _ => {
+                return Err(LowerError::UnboundValue {
+                    block: self.block,
+                    value,
+                })
+            }
+        };
+
+        let dest = inst.dest();
+        self.insts.push(inst);
+        self.cache.insert(value, dest);
+        Ok(Operand::Var(dest))
+    }
+}
@@ -0,0 +1,164 @@
+//! Textual representation of LIR, for debugging.
+//!
+//! ```text
+//! unit foo:bar/1 {
+//!     fun0(%0) {
+//!       block0(%0):
+//!         %1 = closure fun1[%0]
+//!         return %1
+//!     }
+//!     fun1[%0]() {
+//!       block0():
+//!         return %0
+//!     }
+//! }
+//! ```
+
+use std::fmt::{Display, Formatter, Result, Write};
+
+use crate::function::{Cont, FunId, Function, Inst, Operand, Terminator, Unit};
+
+impl Unit {
+    pub fn to_text(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        writeln!(f, "unit {} {{", self.ident)?;
+        for (id, fun) in self.functions.iter() {
+            self.write_function(f, id, fun)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl Unit {
+    fn write_function(&self, f: &mut Formatter, id: FunId, fun: &Function) -> Result {
+        write!(f, "    {}", id)?;
+        if !fun.env.is_empty() {
+            write!(f, "[")?;
+            write_list(f, fun.env.iter())?;
+            write!(f, "]")?;
+        }
+        write!(f, "(")?;
+        write_list(f, fun.blocks[fun.entry].params.iter())?;
+        writeln!(f, ") {{")?;
+
+        for (block, data) in fun.blocks.iter() {
+            write!(f, "      {}(", block)?;
+            write_list(f, data.params.iter())?;
+            writeln!(f, "):")?;
+
+            for inst in data.insts.iter() {
+                write!(f, "        {} = ", inst.dest())?;
+                match inst {
+                    Inst::PrimOp { kind, args, .. } => {
+                        write!(f, "{:?}(", kind)?;
+                        self.write_operands(f, args)?;
+                        writeln!(f, ")")?;
+                    }
+                    Inst::MakeClosure { fun, env, .. } => {
+                        write!(f, "closure {}[", fun)?;
+                        self.write_operands(f, env)?;
+                        writeln!(f, "]")?;
+                    }
+                }
+            }
+
+            write!(f, "        ")?;
+            match &data.term {
+                Terminator::Jump { target, args } => {
+                    write!(f, "jump {}(", target)?;
+                    self.write_operands(f, args)?;
+                    write!(f, ")")?;
+                }
+                Terminator::Return { values } => {
+                    write!(f, "return ")?;
+                    self.write_operands(f, values)?;
+                }
+                Terminator::Throw { values } => {
+                    write!(f, "throw ")?;
+                    self.write_operands(f, values)?;
+                }
+                Terminator::Call {
+                    callee,
+                    args,
+                    ret,
+                    thr,
+                } => {
+                    write!(f, "call ")?;
+                    self.write_operand(f, callee)?;
+                    write!(f, "(")?;
+                    self.write_operands(f, args)?;
+                    write!(f, ") => {} except {}", DisplayCont(*ret), DisplayCont(*thr))?;
+                }
+                Terminator::TailCall { callee, args } => {
+                    write!(f, "tail_call ")?;
+                    self.write_operand(f, callee)?;
+                    write!(f, "(")?;
+                    self.write_operands(f, args)?;
+                    write!(f, ")")?;
+                }
+                Terminator::Op { kind, reads } => {
+                    write!(f, "{:?} ", kind)?;
+                    self.write_operands(f, reads)?;
+                }
+                Terminator::Unreachable => write!(f, "unreachable")?,
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f, "    }}")
+    }
+
+    fn write_operands(&self, f: &mut Formatter, operands: &[Operand]) -> Result {
+        for (idx, operand) in operands.iter().enumerate() {
+            if idx != 0 {
+                write!(f, ", ")?;
+            }
+            self.write_operand(f, operand)?;
+        }
+        Ok(())
+    }
+
+    fn write_operand(&self, f: &mut Formatter, operand: &Operand) -> Result {
+        match operand {
+            Operand::Var(var) => write!(f, "{}", var),
+            Operand::Const(cons) => {
+                let mut out = Vec::new();
+                self.cons.write(*cons, &mut out);
+                f.write_str(&String::from_utf8(out).unwrap())
+            }
+            Operand::Block(block) => write!(f, "{}", block),
+            Operand::List(list) => {
+                write!(f, "<")?;
+                self.write_operands(f, list)?;
+                write!(f, ">")
+            }
+        }
+    }
+}
+
+struct DisplayCont(Cont);
+impl Display for DisplayCont {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self.0 {
+            Cont::Block(block) => write!(f, "{}", block),
+            Cont::Return => write!(f, "return"),
+            Cont::Throw => write!(f, "throw"),
+        }
+    }
+}
+
+fn write_list<T: Display>(f: &mut Formatter, items: impl Iterator<Item = T>) -> Result {
+    for (idx, item) in items.enumerate() {
+        if idx != 0 {
+            f.write_char(',')?;
+            f.write_char(' ')?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
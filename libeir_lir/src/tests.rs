@@ -0,0 +1,393 @@
+use cranelift_entity::{EntityRef, PrimaryMap};
+
+use libeir_ir::{parse_function_unwrap, ConstantContainer};
+
+use crate::{lower_function, Cont, Inst, Operand, Terminator, Unit};
+use crate::{Block, BlockData, FunId, Function, ValidationError, Var, VarData};
+
+fn lower(text: &str) -> Unit {
+    let fun = parse_function_unwrap(text);
+    let data = libeir_lowerutils::analyze(&fun);
+    let unit = lower_function(&fun, &data).unwrap();
+    assert_eq!(unit.validate(), vec![]);
+    unit
+}
+
+fn function(env: usize) -> Function {
+    let mut vars = PrimaryMap::new();
+    let env = (0..env)
+        .map(|_| vars.push(VarData { source: None }))
+        .collect();
+    Function {
+        source: libeir_ir::Block::default(),
+        env,
+        entry: Block::new(0),
+        blocks: PrimaryMap::new(),
+        vars,
+    }
+}
+
+fn block(fun: &mut Function, params: usize) -> Block {
+    let params = (0..params)
+        .map(|_| fun.vars.push(VarData { source: None }))
+        .collect();
+    fun.blocks.push(BlockData {
+        source: libeir_ir::Block::default(),
+        params,
+        insts: Vec::new(),
+        term: Terminator::Unreachable,
+    })
+}
+
+/// A unit built by hand, so that the ids in it are known.
+///
+/// ```text
+/// fun0(%0):
+///   block0(%0):
+///     %1 = closure fun1[%0]
+///     call %1() => block1 except throw
+///   block1(%2):
+///     tail_call %2(%0, 1)
+/// fun1[%0]():
+///   block0():
+///     return %0, a'true'
+/// ```
+fn example_unit() -> Unit {
+    let source = parse_function_unwrap(
+        "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %a):
+        %ret(%a);
+}
+",
+    );
+    let mut cons = ConstantContainer::new();
+    let mut functions = PrimaryMap::new();
+
+    let mut root = function(0);
+    let entry = block(&mut root, 1);
+    let closure = root.vars.push(VarData { source: None });
+    let ret = block(&mut root, 1);
+    let arg = Operand::Var(root.blocks[entry].params[0]);
+    let fun1 = FunId::new(1);
+    root.blocks[entry].insts.push(Inst::MakeClosure {
+        dest: closure,
+        fun: fun1,
+        env: vec![arg.clone()],
+    });
+    root.blocks[entry].term = Terminator::Call {
+        callee: Operand::Var(closure),
+        args: vec![],
+        ret: Cont::Block(ret),
+        thr: Cont::Throw,
+    };
+    root.blocks[ret].term = Terminator::TailCall {
+        callee: Operand::Var(root.blocks[ret].params[0]),
+        args: vec![arg, Operand::Const(cons.from(1i64))],
+    };
+    let root = functions.push(root);
+
+    let mut inner = function(1);
+    let inner_entry = block(&mut inner, 0);
+    inner.blocks[inner_entry].term = Terminator::Return {
+        values: vec![Operand::Var(inner.env[0]), Operand::Const(cons.from(true))],
+    };
+    assert_eq!(functions.push(inner), fun1);
+
+    Unit {
+        ident: *source.ident(),
+        cons,
+        root,
+        functions,
+    }
+}
+
+#[test]
+fn simple_function() {
+    let unit = lower(
+        "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %a):
+        if_bool %a one two;
+    one():
+        %ret(a'true');
+    two():
+        %ret(a'foo');
+}
+",
+    );
+
+    assert_eq!(unit.functions.len(), 1);
+    let root = &unit.functions[unit.root];
+    assert_eq!(root.arity(), 1);
+    assert_eq!(root.blocks.len(), 3);
+
+    let entry = &root.blocks[root.entry];
+    match &entry.term {
+        Terminator::Op { reads, .. } => {
+            assert!(matches!(reads[0], Operand::Block(_)));
+            assert!(matches!(reads[1], Operand::Block(_)));
+        }
+        term => panic!("{:?}", term),
+    }
+    let returns = root
+        .blocks
+        .values()
+        .filter(|b| matches!(b.term, Terminator::Return { .. }))
+        .count();
+    assert_eq!(returns, 2);
+}
+
+#[test]
+fn nested_functions() {
+    let unit = lower(
+        "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %a):
+        %ret(inner);
+    inner(%iret, %ithr):
+        %iret(%a);
+}
+",
+    );
+
+    assert_eq!(unit.functions.len(), 2);
+    let root = &unit.functions[unit.root];
+    assert!(root.env.is_empty());
+
+    let entry = &root.blocks[root.entry];
+    let (closure_fun, env) = match &entry.insts[..] {
+        [Inst::MakeClosure { fun, env, .. }] => (*fun, env),
+        insts => panic!("{:?}", insts),
+    };
+    assert_eq!(env, &vec![Operand::Var(entry.params[0])]);
+
+    let inner = &unit.functions[closure_fun];
+    assert_eq!(inner.env.len(), 1);
+    assert_eq!(inner.arity(), 0);
+    match &inner.blocks[inner.entry].term {
+        Terminator::Return { values } => assert_eq!(values, &vec![Operand::Var(inner.env[0])]),
+        term => panic!("{:?}", term),
+    }
+}
+
+#[test]
+fn tail_and_non_tail_calls() {
+    let unit = lower(
+        "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %a):
+        %f = a'foo':a'baz'/1;
+        %f(%a) => ret except %thr;
+    ret(%r):
+        %f(%r) => %ret except %thr;
+}
+",
+    );
+
+    let root = &unit.functions[unit.root];
+    let entry = &root.blocks[root.entry];
+    assert!(matches!(entry.insts[..], [Inst::PrimOp { .. }]));
+    match &entry.term {
+        Terminator::Call { ret, thr, .. } => {
+            assert!(matches!(ret, Cont::Block(_)));
+            assert_eq!(*thr, Cont::Throw);
+        }
+        term => panic!("{:?}", term),
+    }
+
+    let tail_calls = root
+        .blocks
+        .values()
+        .filter(|b| matches!(b.term, Terminator::TailCall { .. }))
+        .count();
+    assert_eq!(tail_calls, 1);
+}
+
+#[test]
+fn match_branches() {
+    let unit = lower(
+        "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %a):
+        match %a {
+            {} arity 2 => tuple;
+            _ => other;
+        };
+    tuple(%x, %y):
+        %ret(%y);
+    other():
+        %ret(%a);
+}
+",
+    );
+
+    let root = &unit.functions[unit.root];
+    match &root.blocks[root.entry].term {
+        Terminator::Op { reads, .. } => match &reads[0] {
+            Operand::List(branches) => {
+                assert_eq!(branches.len(), 2);
+                assert!(branches.iter().all(|b| matches!(b, Operand::Block(_))));
+            }
+            read => panic!("{:?}", read),
+        },
+        term => panic!("{:?}", term),
+    }
+}
+
+#[test]
+fn print_unit() {
+    let unit = example_unit();
+    assert_eq!(unit.validate(), vec![]);
+    assert_eq!(
+        unit.to_text(),
+        "\
+unit foo:bar/1 {
+    fun0(%0) {
+      block0(%0):
+        %1 = closure fun1[%0]
+        call %1() => block1 except throw
+      block1(%2):
+        tail_call %2(%0, 1)
+    }
+    fun1[%0]() {
+      block0():
+        return %0, a'true'
+    }
+}
+"
+    );
+}
+
+#[test]
+fn validate_root_env() {
+    let mut unit = example_unit();
+    let root = &mut unit.functions[unit.root];
+    let var = root.vars.push(VarData { source: None });
+    root.env.push(var);
+    assert_eq!(unit.validate(), vec![ValidationError::RootHasEnv]);
+}
+
+#[test]
+fn validate_multiple_definitions() {
+    let mut unit = example_unit();
+    let root = &mut unit.functions[unit.root];
+    let var = Var::new(1);
+    root.blocks[Block::new(1)].params.push(var);
+    assert_eq!(
+        unit.validate(),
+        vec![ValidationError::MultipleDefinitions {
+            fun: unit.root,
+            var
+        }]
+    );
+}
+
+#[test]
+fn validate_undefined_var() {
+    let mut unit = example_unit();
+    let fun1 = FunId::new(1);
+    let var = Var::new(5);
+    unit.functions[fun1].blocks[Block::new(0)].term = Terminator::Return {
+        values: vec![Operand::Var(var)],
+    };
+    assert_eq!(
+        unit.validate(),
+        vec![ValidationError::UndefinedVar {
+            fun: fun1,
+            block: Block::new(0),
+            var,
+        }]
+    );
+}
+
+#[test]
+fn validate_invalid_block() {
+    let mut unit = example_unit();
+    let target = Block::new(10);
+    unit.functions[unit.root].blocks[Block::new(1)].term = Terminator::Jump {
+        target,
+        args: vec![],
+    };
+    assert_eq!(
+        unit.validate(),
+        vec![ValidationError::InvalidBlock {
+            fun: unit.root,
+            block: Block::new(1),
+            target,
+        }]
+    );
+}
+
+#[test]
+fn validate_block_arity() {
+    let mut unit = example_unit();
+    unit.functions[unit.root].blocks[Block::new(0)].term = Terminator::Jump {
+        target: Block::new(1),
+        args: vec![],
+    };
+    assert_eq!(
+        unit.validate(),
+        vec![ValidationError::BlockArity {
+            fun: unit.root,
+            block: Block::new(0),
+            target: Block::new(1),
+            attempted: 0,
+            actual: 1,
+        }]
+    );
+}
+
+#[test]
+fn validate_invalid_function() {
+    let mut unit = example_unit();
+    let target = FunId::new(10);
+    match &mut unit.functions[unit.root].blocks[Block::new(0)].insts[0] {
+        Inst::MakeClosure { fun, .. } => *fun = target,
+        inst => panic!("{:?}", inst),
+    }
+    assert_eq!(
+        unit.validate(),
+        vec![ValidationError::InvalidFunction {
+            fun: unit.root,
+            block: Block::new(0),
+            target,
+        }]
+    );
+}
+
+#[test]
+fn validate_env_arity() {
+    let mut unit = example_unit();
+    let cons = Operand::Const(unit.cons.from(2i64));
+    match &mut unit.functions[unit.root].blocks[Block::new(0)].insts[0] {
+        Inst::MakeClosure { env, .. } => env.push(cons),
+        inst => panic!("{:?}", inst),
+    }
+    assert_eq!(
+        unit.validate(),
+        vec![ValidationError::EnvArity {
+            fun: unit.root,
+            block: Block::new(0),
+            target: FunId::new(1),
+            attempted: 2,
+            actual: 1,
+        }]
+    );
+}
+
+#[test]
+fn validate_invalid_operand() {
+    let mut unit = example_unit();
+    match &mut unit.functions[unit.root].blocks[Block::new(1)].term {
+        Terminator::TailCall { callee, .. } => *callee = Operand::Block(Block::new(0)),
+        term => panic!("{:?}", term),
+    }
+    assert_eq!(
+        unit.validate(),
+        vec![ValidationError::InvalidOperand {
+            fun: unit.root,
+            block: Block::new(1),
+        }]
+    );
+}
@@ -0,0 +1,245 @@
+use std::collections::HashSet;
+
+use crate::function::{Block, Cont, FunId, Inst, Operand, Terminator, Unit, Var};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The root function has a closure environment.
+    RootHasEnv,
+    /// A variable was defined more than once.
+    MultipleDefinitions { fun: FunId, var: Var },
+    /// A variable was read that is never defined in the function.
+    UndefinedVar { fun: FunId, block: Block, var: Var },
+    /// A block that does not exist was referenced.
+    InvalidBlock {
+        fun: FunId,
+        block: Block,
+        target: Block,
+    },
+    /// Jumped to a block with the wrong number of arguments.
+    BlockArity {
+        fun: FunId,
+        block: Block,
+        target: Block,
+        attempted: usize,
+        actual: usize,
+    },
+    /// A closure was constructed for a function that does not exist.
+    InvalidFunction {
+        fun: FunId,
+        block: Block,
+        target: FunId,
+    },
+    /// A closure was constructed with the wrong number of environment values.
+    EnvArity {
+        fun: FunId,
+        block: Block,
+        target: FunId,
+        attempted: usize,
+        actual: usize,
+    },
+    /// A block or value list was used where a value is required.
+    InvalidOperand { fun: FunId, block: Block },
+}
+
+impl Unit {
+    /// Checks the structural invariants of the unit.
+    ///
+    /// Dominance of definitions over uses is not checked, only that every
+    /// variable read is defined somewhere in the function.
+    pub fn validate(&self) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+
+        if !self.functions[self.root].env.is_empty() {
+            errors.push(ValidationError::RootHasEnv);
+        }
+
+        for (fun_id, fun) in self.functions.iter() {
+            let mut defined = HashSet::new();
+            let mut define = |var: Var, errors: &mut Vec<ValidationError>| {
+                if !defined.insert(var) {
+                    errors.push(ValidationError::MultipleDefinitions { fun: fun_id, var });
+                }
+            };
+            for var in fun.env.iter() {
+                define(*var, &mut errors);
+            }
+            for data in fun.blocks.values() {
+                for var in data.params.iter() {
+                    define(*var, &mut errors);
+                }
+                for inst in data.insts.iter() {
+                    define(inst.dest(), &mut errors);
+                }
+            }
+
+            let mut checker = Checker {
+                unit: self,
+                fun: fun_id,
+                block: fun.entry,
+                defined: &defined,
+                errors: &mut errors,
+            };
+            for (block, data) in fun.blocks.iter() {
+                checker.block = block;
+                for inst in data.insts.iter() {
+                    for operand in inst.operands() {
+                        checker.value(operand);
+                    }
+                    if let Inst::MakeClosure {
+                        fun: target, env, ..
+                    } = inst
+                    {
+                        checker.closure(*target, env.len());
+                    }
+                }
+                checker.terminator(&data.term);
+            }
+        }
+
+        errors
+    }
+}
+
+struct Checker<'a> {
+    unit: &'a Unit,
+    fun: FunId,
+    block: Block,
+    defined: &'a HashSet<Var>,
+    errors: &'a mut Vec<ValidationError>,
+}
+
+impl<'a> Checker<'a> {
+    fn terminator(&mut self, term: &Terminator) {
+        match term {
+            Terminator::Jump { target, args } => {
+                for arg in args.iter() {
+                    self.value_or_list(arg);
+                }
+                if self.target(*target) {
+                    let actual = self.unit.functions[self.fun].blocks[*target].params.len();
+                    if actual != args.len() {
+                        self.errors.push(ValidationError::BlockArity {
+                            fun: self.fun,
+                            block: self.block,
+                            target: *target,
+                            attempted: args.len(),
+                            actual,
+                        });
+                    }
+                }
+            }
+            Terminator::Return { values } | Terminator::Throw { values } => {
+                for value in values.iter() {
+                    self.value_or_list(value);
+                }
+            }
+            Terminator::Call {
+                callee,
+                args,
+                ret,
+                thr,
+            } => {
+                self.value(callee);
+                for arg in args.iter() {
+                    self.value(arg);
+                }
+                for cont in [ret, thr].iter() {
+                    if let Cont::Block(target) = cont {
+                        self.target(*target);
+                    }
+                }
+            }
+            Terminator::TailCall { callee, args } => {
+                self.value(callee);
+                for arg in args.iter() {
+                    self.value(arg);
+                }
+            }
+            Terminator::Op { reads, .. } => {
+                for read in reads.iter() {
+                    self.any(read);
+                }
+            }
+            Terminator::Unreachable => (),
+        }
+    }
+
+    fn target(&mut self, target: Block) -> bool {
+        let valid = self.unit.functions[self.fun].blocks.is_valid(target);
+        if !valid {
+            self.errors.push(ValidationError::InvalidBlock {
+                fun: self.fun,
+                block: self.block,
+                target,
+            });
+        }
+        valid
+    }
+
+    fn closure(&mut self, target: FunId, env_len: usize) {
+        if !self.unit.functions.is_valid(target) {
+            self.errors.push(ValidationError::InvalidFunction {
+                fun: self.fun,
+                block: self.block,
+                target,
+            });
+            return;
+        }
+        let actual = self.unit.functions[target].env.len();
+        if actual != env_len {
+            self.errors.push(ValidationError::EnvArity {
+                fun: self.fun,
+                block: self.block,
+                target,
+                attempted: env_len,
+                actual,
+            });
+        }
+    }
+
+    /// An operand that must be an actual value.
+    fn value(&mut self, operand: &Operand) {
+        match operand {
+            Operand::Var(var) => self.var(*var),
+            Operand::Const(_) => (),
+            Operand::Block(_) | Operand::List(_) => {
+                self.errors.push(ValidationError::InvalidOperand {
+                    fun: self.fun,
+                    block: self.block,
+                });
+            }
+        }
+    }
+
+    /// An operand that may also be a value list of values.
+    fn value_or_list(&mut self, operand: &Operand) {
+        match operand {
+            Operand::List(list) => list.iter().for_each(|o| self.value(o)),
+            operand => self.value(operand),
+        }
+    }
+
+    /// An operand of an operation, anything goes as long as it refers to
+    /// something that exists.
+    fn any(&mut self, operand: &Operand) {
+        match operand {
+            Operand::Var(var) => self.var(*var),
+            Operand::Const(_) => (),
+            Operand::Block(block) => {
+                self.target(*block);
+            }
+            Operand::List(list) => list.iter().for_each(|o| self.any(o)),
+        }
+    }
+
+    fn var(&mut self, var: Var) {
+        if !self.defined.contains(&var) {
+            self.errors.push(ValidationError::UndefinedVar {
+                fun: self.fun,
+                block: self.block,
+                var,
+            });
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Closure conversion.
+//!
+//! Every nested function (lambda) in an Eir function is lifted into a top
+//! level function of its own. The values a lambda closes over are decided
+//! up front from the live values at its entry, which gives each lambda a
+//! fixed environment layout:
+//!
+//! * Where the lambda was referenced, a `MakeClosure` primop constructs the
+//!   closure from the name of the lifted function and the environment values.
+//! * The lifted function takes the environment as its last argument, and
+//!   reads the captured values from it with `ClosureEnvGet` primops.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use libeir_intern::Ident;
+use libeir_ir::{Block, Function, FunctionIdent, Value};
+use libeir_ir::{MangleFrom, Mangler};
+
+use crate::LowerData;
+
+/// A nested function that is lifted by closure conversion.
+#[derive(Debug, Clone)]
+pub struct Lambda {
+    /// The name of the lifted function. The arity includes the environment
+    /// argument.
+    pub ident: FunctionIdent,
+    /// The entry block of the lambda in the original function.
+    pub block: Block,
+    /// The number of arguments the closure is called with.
+    pub arity: usize,
+    /// The values the lambda closes over, in environment order.
+    pub env: Vec<Value>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClosureInfo {
+    /// Every lambda in the function, by entry block.
+    pub lambdas: BTreeMap<Block, Lambda>,
+    /// The lambdas constructed by each function in the function tree,
+    /// including the root function.
+    pub constructed: BTreeMap<Block, BTreeSet<Block>>,
+}
+
+/// Names every lambda in the function and computes its environment.
+///
+/// Lambdas are named `-name/arity-fun-N-` after the function they are
+/// contained in, numbered in block order. The names are stable as long as
+/// the function is not modified.
+pub fn analyze_closures(fun: &Function, data: &LowerData) -> ClosureInfo {
+    let tree = &data.func_tree;
+    let ident = fun.ident();
+
+    let mut lambdas = BTreeMap::new();
+    for (n, block) in tree
+        .functions
+        .keys()
+        .filter(|b| **b != tree.root_fun)
+        .enumerate()
+    {
+        // The first two arguments are the return and throw continuations.
+        let arity = fun.block_args(*block).len() - 2;
+        let name = format!("-{}/{}-fun-{}-", ident.name, ident.arity, n);
+        lambdas.insert(
+            *block,
+            Lambda {
+                ident: FunctionIdent {
+                    module: ident.module,
+                    name: Ident::from_str(&name),
+                    arity: arity + 1,
+                },
+                block: *block,
+                arity,
+                env: data.live.live_at(*block).iter().collect(),
+            },
+        );
+    }
+
+    let mut constructed = BTreeMap::new();
+    for (block, entry) in tree.functions.iter() {
+        let mut referenced = BTreeSet::new();
+        for scope_block in entry.scope.iter() {
+            fun.block_walk_nested_values::<_, ()>(*scope_block, &mut |value| {
+                if let Some(target) = fun.value_block(value) {
+                    if tree.functions.contains_key(&target) {
+                        referenced.insert(target);
+                    }
+                }
+                Ok(())
+            })
+            .unwrap();
+        }
+        constructed.insert(*block, referenced);
+    }
+
+    ClosureInfo {
+        lambdas,
+        constructed,
+    }
+}
+
+/// The result of closure conversion.
+pub struct ClosureConversion {
+    /// The original function, with its lambdas replaced by closure
+    /// construction.
+    pub root: Function,
+    /// The lambda lifted functions, in the same order as `info.lambdas`.
+    pub lifted: Vec<Function>,
+    pub info: ClosureInfo,
+}
+
+/// Performs closure conversion on a function.
+///
+/// Neither the root function nor the lifted functions contain any nested
+/// functions afterwards.
+pub fn closure_convert(fun: &Function) -> ClosureConversion {
+    let data = crate::analyze(fun);
+    let info = analyze_closures(fun, &data);
+
+    let mut mangler = Mangler::new();
+    let root = lift(&mut mangler, fun, &info, fun.block_entry(), *fun.ident());
+    let lifted = info
+        .lambdas
+        .values()
+        .map(|lambda| lift(&mut mangler, fun, &info, lambda.block, lambda.ident))
+        .collect();
+
+    ClosureConversion { root, lifted, info }
+}
+
+/// Copies the function with the given entry block into a new function
+/// container, replacing lambda references with closure construction.
+fn lift(
+    mangler: &mut Mangler,
+    fun: &Function,
+    info: &ClosureInfo,
+    entry: Block,
+    ident: FunctionIdent,
+) -> Function {
+    let span = fun.span();
+    let lambda = info.lambdas.get(&entry);
+
+    // The new entry, along with the primops for closure construction and
+    // environment access, are built in a scratch copy of the function. The
+    // mangler then copies only the relevant parts into the new container.
+    let mut scratch = fun.clone();
+    let mut renames = Vec::new();
+    let new_entry = {
+        let mut b = scratch.builder();
+
+        let new_entry = b.block_insert();
+        let old_args = b.fun().block_args(entry).to_vec();
+        for old_arg in old_args {
+            let new_arg = b.block_arg_insert(new_entry);
+            renames.push((old_arg, new_arg, true));
+        }
+
+        if let Some(lambda) = lambda {
+            let env_arg = b.block_arg_insert(new_entry);
+            for (idx, value) in lambda.env.iter().enumerate() {
+                let get = b.prim_closure_env_get(span, env_arg, idx);
+                renames.push((*value, get, false));
+            }
+        }
+
+        // The environment values of closures constructed here are renamed
+        // along with everything else when the primop is mangled. This also
+        // covers a lambda that references itself.
+        for target in info.constructed[&entry].iter() {
+            let target_val = b.fun().block_value(*target);
+            let closure = match info.lambdas.get(target) {
+                Some(target_lambda) => {
+                    let name = b.value(target_lambda.ident.name);
+                    let arity = b.value(target_lambda.arity);
+                    b.prim_make_closure(span, name, arity, &target_lambda.env)
+                }
+                // The root function, which is a plain local function.
+                None => {
+                    let root_ident = fun.ident();
+                    let name = b.value(root_ident.name);
+                    let arity = b.value(root_ident.arity);
+                    b.prim_capture_local_function(span, name, arity)
+                }
+            };
+            renames.push((target_val, closure, false));
+        }
+
+        b.block_copy_body_map(entry, new_entry, |_| None);
+        new_entry
+    };
+
+    mangler.start(MangleFrom(new_entry));
+    for (from, to, follow) in renames {
+        if follow {
+            mangler.add_rename(MangleFrom(from), MangleFrom(to));
+        } else {
+            mangler.add_rename_nofollow(MangleFrom(from), MangleFrom(to));
+        }
+    }
+
+    let mut lifted = Function::new(span, ident);
+    {
+        let mut b = lifted.builder();
+        let lifted_entry = mangler.run_across(&scratch, &mut b);
+        b.block_set_entry(lifted_entry);
+    }
+    lifted
+}
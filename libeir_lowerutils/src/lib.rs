@@ -6,6 +6,10 @@ use libeir_ir::{FunctionTree, LiveValues};
 
 use petgraph::visit::IntoNeighbors;
 
+mod closure_conversion;
+pub use closure_conversion::{analyze_closures, closure_convert};
+pub use closure_conversion::{ClosureConversion, ClosureInfo, Lambda};
+
 #[cfg(test)]
 mod tests;
 
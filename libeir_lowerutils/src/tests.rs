@@ -1,4 +1,4 @@
-use libeir_ir::parse_function_unwrap;
+use libeir_ir::{parse_function_unwrap, Function, PrimOpKind};
 
 #[test]
 fn simple_function() {
@@ -35,3 +35,70 @@ a'foo':a'bar'/1 {
     let analyzed = super::analyze(&fun);
     dbg!(analyzed);
 }
+
+fn entry_primops(fun: &Function) -> Vec<PrimOpKind> {
+    let mut kinds = Vec::new();
+    fun.block_walk_nested_values::<_, ()>(fun.block_entry(), &mut |value| {
+        if let Some(prim) = fun.value_primop(value) {
+            kinds.push(*fun.primop_kind(prim));
+        }
+        Ok(())
+    })
+    .unwrap();
+    kinds
+}
+
+#[test]
+fn closure_convert_nested() {
+    let fun = parse_function_unwrap(
+        "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %a):
+        %ret(inner);
+    inner(%iret, %ithr, %b):
+        %iret(%a);
+}
+",
+    );
+
+    let converted = super::closure_convert(&fun);
+
+    assert_eq!(converted.lifted.len(), 1);
+    let lambda = converted.info.lambdas.values().next().unwrap();
+    assert_eq!(lambda.env.len(), 1);
+    assert_eq!(lambda.arity, 1);
+
+    let lifted = &converted.lifted[0];
+    assert_eq!(lifted.ident().name.as_str(), "-bar/1-fun-0-");
+    assert_eq!(lifted.ident().arity, 2);
+    // Return and throw continuations, the argument and the environment.
+    assert_eq!(lifted.block_args(lifted.block_entry()).len(), 4);
+    assert_eq!(entry_primops(lifted), vec![PrimOpKind::ClosureEnvGet(0)]);
+
+    let root = &converted.root;
+    assert_eq!(root.block_args(root.block_entry()).len(), 3);
+    assert_eq!(entry_primops(root), vec![PrimOpKind::MakeClosure]);
+}
+
+#[test]
+fn closure_convert_without_lambdas() {
+    let fun = parse_function_unwrap(
+        "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %a):
+        if_bool %a one two;
+    one():
+        %ret(a'true');
+    two():
+        %ret(a'foo');
+}
+",
+    );
+
+    let converted = super::closure_convert(&fun);
+    assert!(converted.lifted.is_empty());
+    assert!(converted
+        .root
+        .graph_eq(converted.root.block_entry(), &fun, fun.block_entry())
+        .is_ok());
+}
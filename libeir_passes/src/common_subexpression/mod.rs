@@ -0,0 +1,251 @@
+use std::collections::BTreeMap;
+
+use log::trace;
+
+use libeir_intern::Ident;
+use libeir_ir::graph::DominatorTree;
+use libeir_ir::{AtomicTerm, CallKind, ConstKind, MatchKind, OpKind, PrimOpKind};
+use libeir_ir::{Block, Function, FunctionBuilder, FunctionIdent, Value};
+
+use super::FunctionPass;
+
+mod purity;
+use self::purity::is_pure_bif;
+
+#[cfg(test)]
+mod tests;
+
+/// Removes calls to pure BIFs and match tests that repeat an earlier one.
+///
+/// A call is redundant when the return continuation of an earlier call,
+/// which binds its result, dominates it. The redundant call is replaced by
+/// a jump to its own return continuation with the earlier result. When
+/// that continuation is not used by anything else, its argument is then
+/// known to be equal to the earlier result, so calls reading it are
+/// compared by the earlier result. This catches repeated accesses like
+/// `element(1, element(2, T))`.
+///
+/// A `Match` whose first branch repeats a test that an earlier match on the
+/// same value passed is replaced by a jump to that branch, with the values
+/// the earlier match unpacked. The earlier branch target must dominate the
+/// match, and be reachable only through that branch. Only `Value`, `Type`,
+/// `Tuple`, `ListCell` and `MapItem` tests are considered. This removes the
+/// repeated tuple tests of record accesses on the same record.
+///
+/// `PrimOp`s are already deduplicated on construction, and are not handled
+/// here.
+pub struct CommonSubexpressionPass {
+    /// Pure calls that are available, to the results they bind.
+    available: BTreeMap<(FunctionIdent, Vec<Value>), Vec<Value>>,
+    /// Match tests that are known to have passed, by the value they test.
+    /// Each is the kind of the branch, its reads, and its target.
+    tests: BTreeMap<Value, Vec<(MatchKind, Value, Block)>>,
+    /// Values known to be equal to an earlier value.
+    leaders: BTreeMap<Value, Value>,
+}
+
+impl CommonSubexpressionPass {
+    pub fn new() -> Self {
+        CommonSubexpressionPass {
+            available: BTreeMap::new(),
+            tests: BTreeMap::new(),
+            leaders: BTreeMap::new(),
+        }
+    }
+}
+
+impl FunctionPass for CommonSubexpressionPass {
+    fn name(&self) -> &str {
+        "common_subexpression"
+    }
+    fn run_function_pass(&mut self, b: &mut FunctionBuilder) {
+        self.common_subexpression(b);
+    }
+}
+
+/// If the value is a constant capture of a pure BIF, returns it.
+fn pure_callee(fun: &Function, value: Value) -> Option<FunctionIdent> {
+    let prim = fun.value_primop(value)?;
+    if *fun.primop_kind(prim) != PrimOpKind::CaptureFunction {
+        return None;
+    }
+    let reads = fun.primop_reads(prim);
+
+    let atom = |value: Value| match fun.const_kind(fun.value_const(value)?) {
+        ConstKind::Atomic(AtomicTerm::Atom(atom)) => Some(atom.0),
+        _ => None,
+    };
+    let arity = match fun.const_kind(fun.value_const(reads[2])?) {
+        ConstKind::Atomic(AtomicTerm::Int(int)) => int.value() as usize,
+        _ => return None,
+    };
+
+    let ident = FunctionIdent {
+        module: Ident::with_empty_span(atom(reads[0])?),
+        name: Ident::with_empty_span(atom(reads[1])?),
+        arity,
+    };
+    if is_pure_bif(&ident) {
+        Some(ident)
+    } else {
+        None
+    }
+}
+
+impl CommonSubexpressionPass {
+    fn leader(&self, mut value: Value) -> Value {
+        while let Some(leader) = self.leaders.get(&value) {
+            value = *leader;
+        }
+        value
+    }
+
+    pub fn common_subexpression(&mut self, b: &mut FunctionBuilder) {
+        self.available.clear();
+        self.tests.clear();
+        self.leaders.clear();
+
+        // Only edges are removed below, so dominance between the remaining
+        // blocks still holds.
+        let analysis = b.fun().block_analysis();
+        let dominators = analysis.dominators();
+
+        // In reverse postorder, a block is visited after its dominators
+        for block in analysis.order().nodes().iter().cloned() {
+            match b.fun().block_kind(block) {
+                Some(OpKind::Call(CallKind::Function)) => self.call(b, dominators, block),
+                Some(OpKind::Match { .. }) => self.match_test(b, dominators, block),
+                _ => (),
+            }
+        }
+    }
+
+    fn call(&mut self, b: &mut FunctionBuilder, dominators: &DominatorTree<Block>, block: Block) {
+        let fun = b.fun();
+        let reads = fun.block_reads(block);
+        let ident = match pure_callee(fun, reads[0]) {
+            Some(ident) => ident,
+            None => return,
+        };
+        if reads.len() - 3 != ident.arity {
+            return;
+        }
+        let ret = reads[1];
+        let args: Vec<Value> = reads[3..].iter().map(|v| self.leader(*v)).collect();
+        let key = (ident, args);
+
+        let earlier = self.available.get(&key).and_then(|results| {
+            results.iter().cloned().find(|result| {
+                let (result_block, _) = fun.value_argument(*result).unwrap();
+                dominators.dominates(result_block, block)
+            })
+        });
+
+        let ret_block = fun.value_block(ret);
+        let ret_result = ret_block
+            .filter(|ret_block| fun.block_args(*ret_block).len() == 1)
+            .map(|ret_block| fun.block_args(ret_block)[0]);
+
+        if let Some(earlier) = earlier {
+            trace!("{} in {} is redundant", ident, block);
+
+            // Nothing but this call can reach the continuation, its
+            // argument is always the earlier result.
+            if let Some(ret_result) = ret_result {
+                if fun.value_usages(ret).iter().count() == 1 {
+                    self.leaders.insert(ret_result, earlier);
+                }
+            }
+
+            b.block_clear(block);
+            b.op_call_flow(block, ret, &[earlier]);
+        } else if let Some(ret_result) = ret_result {
+            self.available
+                .entry(key)
+                .or_insert_with(Vec::new)
+                .push(ret_result);
+        }
+    }
+
+    fn match_test(
+        &mut self,
+        b: &mut FunctionBuilder,
+        dominators: &DominatorTree<Block>,
+        block: Block,
+    ) {
+        let fun = b.fun();
+        let branches = match fun.block_kind(block).unwrap() {
+            OpKind::Match { branches } => branches.clone(),
+            _ => unreachable!(),
+        };
+        let reads = fun.block_reads(block);
+        let value = self.leader(reads[1]);
+        let target = |idx: usize| {
+            fun.value_list_get_n(reads[0], idx)
+                .and_then(|v| fun.value_block(v))
+        };
+
+        // Any branch before a later one could match as well, so only the
+        // first branch is known to be taken.
+        if let (Some(kind), Some(taken)) = (branches.first(), target(0)) {
+            let earlier = self.tests.get(&value).and_then(|tests| {
+                tests
+                    .iter()
+                    .find(|(k, r, passed)| {
+                        k == kind && *r == reads[2] && dominators.dominates(*passed, block)
+                    })
+                    .map(|(_, _, passed)| *passed)
+            });
+
+            if let Some(earlier) = earlier {
+                trace!("match in {} is redundant", block);
+                let args = fun.block_args(earlier).to_vec();
+
+                // Nothing but this match can reach the branch, its arguments
+                // are always the earlier ones.
+                if fun.value_usages(fun.block_value(taken)).iter().count() == 1 {
+                    for (arg, earlier_arg) in fun.block_args(taken).iter().zip(args.iter()) {
+                        self.leaders.insert(*arg, *earlier_arg);
+                    }
+                }
+
+                b.block_clear(block);
+                b.op_call_flow(block, taken, &args);
+                return;
+            }
+        }
+
+        for (idx, kind) in branches.iter().enumerate() {
+            match kind {
+                MatchKind::Value
+                | MatchKind::Type(_)
+                | MatchKind::Tuple(_)
+                | MatchKind::ListCell
+                | MatchKind::MapItem => (),
+                _ => continue,
+            }
+            let passed = match target(idx) {
+                Some(passed) => passed,
+                None => continue,
+            };
+
+            // The test only holds in the target if this branch is the only
+            // way to reach it.
+            if fun.value_usages(fun.block_value(passed)).iter().count() != 1 {
+                continue;
+            }
+            if (0..branches.len())
+                .filter(|i| target(*i) == Some(passed))
+                .count()
+                != 1
+            {
+                continue;
+            }
+
+            self.tests
+                .entry(value)
+                .or_insert_with(Vec::new)
+                .push((*kind, reads[2 + idx], passed));
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use libeir_ir::FunctionIdent;
+
+/// Whether the function is a BIF without side effects. Calling it twice
+/// with the same arguments always gives the same result, or raises the
+/// same exception.
+///
+/// BIFs that depend on the state of the process or the node, like `self/0`
+/// or `get/1`, are not pure.
+pub(super) fn is_pure_bif(ident: &FunctionIdent) -> bool {
+    match ident.module.as_str().get() {
+        "erlang" => is_pure_erlang_bif(ident),
+        "maps" => match (ident.name.as_str().get(), ident.arity) {
+            ("get", 2) | ("is_key", 2) | ("find", 2) | ("size", 1) => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn is_pure_erlang_bif(ident: &FunctionIdent) -> bool {
+    match (ident.name.as_str().get(), ident.arity) {
+        // Arithmetic
+        ("+", 1) | ("-", 1) | ("+", 2) | ("-", 2) | ("*", 2) | ("/", 2) => true,
+        ("div", 2) | ("rem", 2) | ("abs", 1) => true,
+        ("float", 1) | ("trunc", 1) | ("round", 1) => true,
+        ("band", 2) | ("bor", 2) | ("bxor", 2) | ("bnot", 1) | ("bsl", 2) | ("bsr", 2) => true,
+        ("max", 2) | ("min", 2) => true,
+
+        // Comparison and boolean operators
+        ("==", 2) | ("/=", 2) | ("=:=", 2) | ("=/=", 2) => true,
+        ("<", 2) | (">", 2) | ("=<", 2) | (">=", 2) => true,
+        ("and", 2) | ("or", 2) | ("xor", 2) | ("not", 1) => true,
+
+        // Type tests
+        ("is_atom", 1) | ("is_binary", 1) | ("is_bitstring", 1) | ("is_boolean", 1) => true,
+        ("is_float", 1) | ("is_function", 1) | ("is_function", 2) | ("is_integer", 1) => true,
+        ("is_list", 1) | ("is_map", 1) | ("is_number", 1) | ("is_pid", 1) => true,
+        ("is_port", 1) | ("is_reference", 1) | ("is_tuple", 1) => true,
+        ("is_record", 2) | ("is_record", 3) | ("is_map_key", 2) => true,
+
+        // Term access
+        ("element", 2) | ("setelement", 3) | ("tuple_size", 1) | ("size", 1) => true,
+        ("map_get", 2) | ("map_size", 1) => true,
+        ("hd", 1) | ("tl", 1) | ("length", 1) => true,
+        ("byte_size", 1) | ("bit_size", 1) | ("binary_part", 2) | ("binary_part", 3) => true,
+
+        // Conversion
+        ("tuple_to_list", 1) | ("list_to_tuple", 1) => true,
+        ("atom_to_list", 1) | ("integer_to_list", 1) => true,
+        ("++", 2) | ("--", 2) => true,
+
+        _ => false,
+    }
+}
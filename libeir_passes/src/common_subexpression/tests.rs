@@ -0,0 +1,160 @@
+use libeir_ir::parse_function_unwrap;
+
+use crate::FunctionPass;
+
+fn assert_pass(before: &str, after: &str) {
+    let mut fun = parse_function_unwrap(before);
+    let mut b = fun.builder();
+
+    let mut pass = super::CommonSubexpressionPass::new();
+    pass.run_function_pass(&mut b);
+
+    let after = parse_function_unwrap(after);
+    assert!(b
+        .fun()
+        .graph_eq(b.fun().block_entry(), &after, after.block_entry())
+        .is_ok());
+}
+
+#[test]
+fn repeated_element() {
+    assert_pass(
+        "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %t):
+        a'erlang':a'element'/2(1, %t) => first except %thr;
+    first(%a):
+        a'erlang':a'element'/2(1, %t) => second except %thr;
+    second(%b):
+        %ret({%a, %b});
+}
+",
+        "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %t):
+        a'erlang':a'element'/2(1, %t) => first except %thr;
+    first(%a):
+        second(%a);
+    second(%b):
+        %ret({%a, %b});
+}
+",
+    );
+}
+
+#[test]
+fn nested_access() {
+    assert_pass(
+        "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %t):
+        a'erlang':a'element'/2(2, %t) => b1 except %thr;
+    b1(%x):
+        a'erlang':a'element'/2(1, %x) => b2 except %thr;
+    b2(%y):
+        a'erlang':a'element'/2(2, %t) => b3 except %thr;
+    b3(%z):
+        a'erlang':a'element'/2(1, %z) => b4 except %thr;
+    b4(%w):
+        %ret({%y, %w});
+}
+",
+        "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %t):
+        a'erlang':a'element'/2(2, %t) => b1 except %thr;
+    b1(%x):
+        a'erlang':a'element'/2(1, %x) => b2 except %thr;
+    b2(%y):
+        b3(%x);
+    b3(%z):
+        b4(%y);
+    b4(%w):
+        %ret({%y, %w});
+}
+",
+    );
+}
+
+#[test]
+fn not_dominating_or_impure_untouched() {
+    let text = "
+a'foo':a'bar'/2 {
+    entry(%ret, %thr, %c, %t):
+        if_bool %c left right;
+    left():
+        a'erlang':a'map_get'/2(a'k', %t) => join except %thr;
+    right():
+        a'erlang':a'map_get'/2(a'k', %t) => join except %thr;
+    join(%v):
+        a'erlang':a'put'/2(a'k', %v) => put_ret except %thr;
+    put_ret(%old):
+        a'erlang':a'put'/2(a'k', %v) => %ret except %thr;
+}
+";
+    assert_pass(text, text);
+}
+
+#[test]
+fn repeated_tuple_test() {
+    assert_pass(
+        "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %t):
+        match %t {
+            {} arity 2 => first;
+            _ => fail;
+        };
+    first(%a, %b):
+        match %t {
+            {} arity 2 => second;
+            _ => fail;
+        };
+    second(%c, %d):
+        %ret({%a, %d});
+    fail():
+        %thr(a'error', a'badarg', a'none');
+}
+",
+        "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %t):
+        match %t {
+            {} arity 2 => first;
+            _ => fail;
+        };
+    first(%a, %b):
+        second(%a, %b);
+    second(%c, %d):
+        %ret({%a, %d});
+    fail():
+        %thr(a'error', a'badarg', a'none');
+}
+",
+    );
+}
+
+#[test]
+fn test_not_taken_first_untouched() {
+    let text = "
+a'foo':a'bar'/1 {
+    entry(%ret, %thr, %t):
+        match %t {
+            {} arity 2 => first;
+            _ => fail;
+        };
+    first(%a, %b):
+        match %t {
+            value a'none' => none;
+            {} arity 2 => second;
+        };
+    none():
+        %ret(a'none');
+    second(%c, %d):
+        %ret({%a, %d});
+    fail():
+        %thr(a'error', a'badarg', a'none');
+}
+";
+    assert_pass(text, text);
+}
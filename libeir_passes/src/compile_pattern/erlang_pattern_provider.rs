@@ -75,7 +75,9 @@ impl NodeKind {
             NodeKind::ValueList => panic!(),
             NodeKind::Wildcard => 0,
             NodeKind::Binary { has_tail: true, .. } => 2,
-            NodeKind::Binary { has_tail: false, .. } => 1,
+            NodeKind::Binary {
+                has_tail: false, ..
+            } => 1,
         }
     }
 }
@@ -198,6 +200,17 @@ impl<'a> PatternProvider for ErlangPatternProvider<'a> {
         false
     }
 
+    fn kinds_overlap(&self, left: NodeKind, right: NodeKind) -> bool {
+        // A binary can be matched by segments of different layouts, or by
+        // a binary value.
+        match (left, right) {
+            (NodeKind::Binary { .. }, NodeKind::Binary { .. }) => true,
+            (NodeKind::Binary { .. }, NodeKind::Value(_)) => true,
+            (NodeKind::Value(_), NodeKind::Binary { .. }) => true,
+            _ => false,
+        }
+    }
+
     fn get_kind(&self, key: Node) -> NodeKind {
         self.nodes[key].kind
     }
@@ -173,7 +173,16 @@ fn lower_cfg_rec(
                 .map(|spans| spans.first().copied().unwrap_or(SourceSpan::UNKNOWN))
                 .unwrap_or(SourceSpan::UNKNOWN);
 
-            let mut wildcard_node = None;
+            let wildcard_edge = {
+                let mut wildcards = cfg
+                    .graph
+                    .edges(node)
+                    .filter(|e| e.weight().kind == Some(NodeKind::Wildcard));
+                let wildcard_edge = wildcards.next().unwrap();
+                assert!(wildcards.next().is_none());
+                wildcard_edge
+            };
+            assert!(wildcard_edge.weight().variable_binds.len() == 0);
 
             let mut match_builder = b.op_match_build(span);
 
@@ -182,19 +191,21 @@ fn lower_cfg_rec(
                 let kind = weight.kind.unwrap();
 
                 match kind {
-                    NodeKind::Wildcard => {
-                        assert!(wildcard_node.is_none());
-                        wildcard_node = Some(outgoing);
-                    }
-                    NodeKind::Binary { specifier, size, has_tail } => {
+                    NodeKind::Wildcard => (),
+                    NodeKind::Binary {
+                        specifier,
+                        size,
+                        has_tail,
+                    } => {
                         if has_tail {
                             assert!(weight.variable_binds.len() == 2);
                         } else {
                             assert!(weight.variable_binds.len() == 1);
                         }
 
-                        let size =
-                            size.map(|v| ctx.value_or_const_to_value(v, outgoing.target(), b, cfg));
+                        // The size may be bound by an earlier segment, which
+                        // is bound at the current node.
+                        let size = size.map(|v| ctx.value_or_const_to_value(v, node, b, cfg));
                         let mut ok = match_builder.push_binary(specifier, size, b);
 
                         let args = b.block_args(ok);
@@ -206,10 +217,26 @@ fn lower_cfg_rec(
                         if has_tail {
                             ctx.bind(weight.variable_binds[1], arg1);
                         } else {
+                            // The last segment must consume the whole binary.
+                            // If anything remains, we continue as if the
+                            // segment itself failed to match.
                             let mut inner_builder = b.op_match_build(span);
                             let empty_binary = b.value(Vec::<u8>::new());
                             let next = inner_builder.push_value(empty_binary, b);
+                            let rest_fail = inner_builder.push_wildcard(span, b);
                             inner_builder.finish(ok, arg1, b);
+
+                            lower_cfg_rec(
+                                bump,
+                                b,
+                                pat,
+                                ctx,
+                                cfg,
+                                clauses,
+                                rest_fail,
+                                wildcard_edge.target(),
+                            );
+
                             ok = next;
                         }
 
@@ -273,12 +300,10 @@ fn lower_cfg_rec(
                         let ok = match_builder.push_value(val, b);
                         lower_cfg_rec(bump, b, pat, ctx, cfg, clauses, ok, outgoing.target());
                     }
-                    _ => unimplemented!("{:?}", kind),
+                    NodeKind::ValueList => unreachable!(),
                 }
             }
 
-            let wildcard_edge = wildcard_node.unwrap();
-            assert!(wildcard_edge.weight().variable_binds.len() == 0);
             let wildcard_block = match_builder.push_wildcard(span, b);
             lower_cfg_rec(
                 bump,
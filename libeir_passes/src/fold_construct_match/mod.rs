@@ -0,0 +1,147 @@
+use log::trace;
+
+use libeir_ir::{BasicType, Block, ConstKind, MatchKind, OpKind, PrimOpKind, Value};
+use libeir_ir::{ConstructionUse, Function, FunctionBuilder};
+
+use super::FunctionPass;
+
+#[cfg(test)]
+mod tests;
+
+/// Resolves matches on tuples and list cells that are constructed in the
+/// same function, replacing the match with a jump to the branch that is
+/// known to be taken.
+///
+/// The constructions are found with `EscapeAnalysis`. When a construction is
+/// only ever destructured (`ConstructionUse::Destructured`), this removes its
+/// allocation entirely. Constructions passed through block arguments are
+/// only seen once `SimplifyCfgPass` has resolved the call chain.
+pub struct FoldConstructMatchPass {
+    matches_buf: Vec<(Block, Value)>,
+}
+
+impl FoldConstructMatchPass {
+    pub fn new() -> Self {
+        FoldConstructMatchPass {
+            matches_buf: Vec::new(),
+        }
+    }
+}
+
+impl FunctionPass for FoldConstructMatchPass {
+    fn name(&self) -> &str {
+        "fold_construct_match"
+    }
+    fn run_function_pass(&mut self, b: &mut FunctionBuilder) {
+        self.fold_construct_match(b);
+    }
+}
+
+/// The static result of testing a construction against a match branch.
+enum Outcome {
+    Match(Vec<Value>),
+    NoMatch,
+    Unknown,
+}
+
+/// `branch_args` are the reads of the branch, like the value compared
+/// against for `MatchKind::Value`.
+fn branch_outcome(
+    fun: &Function,
+    kind: &MatchKind,
+    branch_args: Value,
+    construction: &PrimOpKind,
+    elems: &[Value],
+) -> Outcome {
+    match (kind, construction) {
+        (MatchKind::Wildcard, _) => Outcome::Match(Vec::new()),
+        // An atomic constant is never equal to a tuple or list cell
+        (MatchKind::Value, _) => match fun
+            .value_list_get_n(branch_args, 0)
+            .and_then(|v| fun.value_const(v))
+        {
+            Some(cons) => match fun.const_kind(cons) {
+                ConstKind::Atomic(_) => Outcome::NoMatch,
+                _ => Outcome::Unknown,
+            },
+            None => Outcome::Unknown,
+        },
+
+        (MatchKind::Tuple(arity), PrimOpKind::Tuple) if *arity == elems.len() => {
+            Outcome::Match(elems.to_vec())
+        }
+        (MatchKind::ListCell, PrimOpKind::ListCell) => Outcome::Match(elems.to_vec()),
+
+        (MatchKind::Type(BasicType::Tuple(arity)), PrimOpKind::Tuple) if *arity == elems.len() => {
+            Outcome::Match(Vec::new())
+        }
+        (MatchKind::Type(BasicType::List), PrimOpKind::ListCell) => Outcome::Match(Vec::new()),
+        (MatchKind::Type(BasicType::ListCell), PrimOpKind::ListCell) => Outcome::Match(Vec::new()),
+
+        _ => Outcome::NoMatch,
+    }
+}
+
+impl FoldConstructMatchPass {
+    pub fn fold_construct_match(&mut self, b: &mut FunctionBuilder) {
+        self.matches_buf.clear();
+
+        let fun = b.fun();
+        let live = fun.live_values();
+        let tree = fun.func_tree(&live, true);
+        let escape = fun.escape_analysis(&live, &tree);
+
+        for (value, usage) in escape.iter() {
+            match fun.value_primop(value).map(|prim| fun.primop_kind(prim)) {
+                Some(PrimOpKind::Tuple) | Some(PrimOpKind::ListCell) => (),
+                _ => continue,
+            }
+            if usage == ConstructionUse::Destructured {
+                trace!("{} is only destructured, folding its matches", value);
+            }
+
+            for block in fun.value_usages(value).iter() {
+                if let Some(OpKind::Match { .. }) = fun.block_kind(block) {
+                    if fun.block_reads(block)[1] == value {
+                        self.matches_buf.push((block, value));
+                    }
+                }
+            }
+        }
+
+        for (block, value) in self.matches_buf.iter().cloned() {
+            let fun = b.fun();
+            let prim = fun.value_primop(value).unwrap();
+            let construction = *fun.primop_kind(prim);
+            let elems = fun.primop_reads(prim).to_vec();
+
+            let branches = match fun.block_kind(block).unwrap() {
+                OpKind::Match { branches } => branches.clone(),
+                _ => unreachable!(),
+            };
+
+            let mut target = None;
+            for (idx, kind) in branches.iter().enumerate() {
+                let branch_args = fun.block_reads(block)[idx + 2];
+                match branch_outcome(fun, kind, branch_args, &construction, &elems) {
+                    Outcome::Match(args) => {
+                        target = Some((idx, args));
+                        break;
+                    }
+                    Outcome::NoMatch => (),
+                    Outcome::Unknown => break,
+                }
+            }
+
+            if let Some((idx, args)) = target {
+                let branch = fun
+                    .value_list_get_n(fun.block_reads(block)[0], idx)
+                    .unwrap();
+                trace!("folding match in {} to branch {}", block, idx);
+
+                b.block_clear(block);
+                b.op_call_flow(block, branch, &args);
+            }
+        }
+    }
+}
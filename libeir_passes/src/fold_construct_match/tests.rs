@@ -0,0 +1,113 @@
+use libeir_ir::parse_function_unwrap;
+
+use crate::FunctionPass;
+
+#[test]
+fn fold_tuple_match() {
+    let mut fun = parse_function_unwrap(
+        "
+a'foo':a'bar'/2 {
+    entry(%ret, %thr, %a, %b):
+        match {%a, %b} {
+            value a'none' => none;
+            {} arity 3 => three;
+            {} arity 2 => two;
+        };
+    none():
+        %ret(a'none');
+    three(%x, %y, %z):
+        %ret(%z);
+    two(%p, %q):
+        %ret(%q);
+}
+",
+    );
+    let mut b = fun.builder();
+
+    let mut pass = super::FoldConstructMatchPass::new();
+    pass.run_function_pass(&mut b);
+
+    let after = parse_function_unwrap(
+        "
+a'foo':a'bar'/2 {
+    entry(%ret, %thr, %a, %b):
+        two(%a, %b);
+    two(%p, %q):
+        %ret(%q);
+}
+",
+    );
+
+    assert!(b
+        .fun()
+        .graph_eq(b.fun().block_entry(), &after, after.block_entry())
+        .is_ok());
+}
+
+#[test]
+fn fold_list_cell_match() {
+    let mut fun = parse_function_unwrap(
+        "
+a'foo':a'bar'/2 {
+    entry(%ret, %thr, %a, %b):
+        match [%a | %b] {
+            {} arity 2 => tup;
+            [] => cell;
+        };
+    tup(%x, %y):
+        %ret(%x);
+    cell(%h, %t):
+        %ret(%t);
+}
+",
+    );
+    let mut b = fun.builder();
+
+    let mut pass = super::FoldConstructMatchPass::new();
+    pass.run_function_pass(&mut b);
+
+    let after = parse_function_unwrap(
+        "
+a'foo':a'bar'/2 {
+    entry(%ret, %thr, %a, %b):
+        cell(%a, %b);
+    cell(%h, %t):
+        %ret(%t);
+}
+",
+    );
+
+    assert!(b
+        .fun()
+        .graph_eq(b.fun().block_entry(), &after, after.block_entry())
+        .is_ok());
+}
+
+#[test]
+fn value_match_not_folded() {
+    let mut fun = parse_function_unwrap(
+        "
+a'foo':a'bar'/2 {
+    entry(%ret, %thr, %a, %b):
+        match {%a, %b} {
+            value %a => same;
+            {} arity 2 => two;
+        };
+    same():
+        %ret(%a);
+    two(%p, %q):
+        %ret(%q);
+}
+",
+    );
+    let mut b = fun.builder();
+
+    let entry = b.fun().block_entry();
+    let mut pass = super::FoldConstructMatchPass::new();
+    pass.run_function_pass(&mut b);
+
+    match b.fun().block_kind(entry).unwrap() {
+        libeir_ir::OpKind::Match { .. } => (),
+        kind => panic!("{:?}", kind),
+    }
+}
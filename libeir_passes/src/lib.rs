@@ -7,15 +7,24 @@ use libeir_ir::{FunctionBuilder, Module};
 
 pub mod util;
 
+mod common_subexpression;
+pub use self::common_subexpression::CommonSubexpressionPass;
+
 mod compile_pattern;
 pub use self::compile_pattern::CompilePatternPass;
 
+mod fold_construct_match;
+pub use self::fold_construct_match::FoldConstructMatchPass;
+
 mod naive_inline_closures;
 pub use self::naive_inline_closures::NaiveInlineClosuresPass;
 
 mod simplify_cfg;
 pub use self::simplify_cfg::SimplifyCfgPass;
 
+mod tail_call_loop;
+pub use self::tail_call_loop::TailCallLoopPass;
+
 mod validate;
 pub use self::validate::ValidatePass;
 
@@ -49,6 +58,9 @@ impl PassManager {
             let fun = fun_def.function_mut();
             let ident = *fun.ident();
 
+            #[cfg(feature = "trace")]
+            let _fun_span = libeir_util_prof::span(0, 0, &ident.to_string(), "function");
+
             let mut b = FunctionBuilder::new(fun);
             b.fun().graph_validate_global();
             trace!("{}", b.fun().to_text_standard());
@@ -56,6 +68,8 @@ impl PassManager {
                 match pass {
                     PassType::Function(fun_pass) => {
                         info!("======== {} FUNCTION_PASS: {}", ident, fun_pass.name());
+                        #[cfg(feature = "trace")]
+                        let _pass_span = libeir_util_prof::span(0, 0, fun_pass.name(), "pass");
                         fun_pass.run_function_pass(&mut b);
                         trace!("{}", b.fun().to_text_standard());
                     }
@@ -77,6 +91,12 @@ impl Default for PassManager {
         man.push_function_pass(ValidatePass::new());
         man.push_function_pass(SimplifyCfgPass::new());
         man.push_function_pass(ValidatePass::new());
+        man.push_function_pass(FoldConstructMatchPass::new());
+        man.push_function_pass(ValidatePass::new());
+        man.push_function_pass(TailCallLoopPass::new());
+        man.push_function_pass(ValidatePass::new());
+        man.push_function_pass(CommonSubexpressionPass::new());
+        man.push_function_pass(ValidatePass::new());
         man.push_function_pass(NaiveInlineClosuresPass::new());
         man.push_function_pass(ValidatePass::new());
         man
@@ -0,0 +1,86 @@
+use log::trace;
+
+use libeir_ir::FunctionBuilder;
+use libeir_ir::{Block, Value};
+use libeir_ir::{MangleTo, Mangler};
+
+use super::FunctionPass;
+
+#[cfg(test)]
+mod tests;
+
+/// Turns self tail calls into jumps back to the start of the function, so
+/// that loops are visible in the CFG.
+///
+/// Only local calls are turned into loops. A qualified call to the module
+/// itself must go to the latest loaded version of the module, so that hot
+/// code loading works.
+///
+/// The body of the entry block is moved to a new loop header, which takes
+/// the arguments of the function without the continuations. Self tail calls
+/// jump to the header, and the entry block jumps to it with its own
+/// arguments.
+pub struct TailCallLoopPass {
+    calls_buf: Vec<Block>,
+    mangler: Mangler,
+}
+
+impl TailCallLoopPass {
+    pub fn new() -> Self {
+        TailCallLoopPass {
+            calls_buf: Vec::new(),
+            mangler: Mangler::new(),
+        }
+    }
+}
+
+impl FunctionPass for TailCallLoopPass {
+    fn name(&self) -> &str {
+        "tail_call_loop"
+    }
+    fn run_function_pass(&mut self, b: &mut FunctionBuilder) {
+        self.tail_call_loop(b);
+    }
+}
+
+impl TailCallLoopPass {
+    pub fn tail_call_loop(&mut self, b: &mut FunctionBuilder) {
+        self.calls_buf.clear();
+
+        let ident = *b.fun().ident();
+        for call in b.fun().tail_calls() {
+            if call.local && call.callee == ident {
+                self.calls_buf.push(call.block);
+            }
+        }
+        if self.calls_buf.is_empty() {
+            return;
+        }
+        trace!("{} self tail calls in {}", self.calls_buf.len(), ident);
+
+        let entry = b.fun().block_entry();
+        let args: Vec<Value> = b.fun().block_args(entry)[2..].to_vec();
+
+        let header = b.block_insert();
+        let header_args: Vec<Value> = args.iter().map(|_| b.block_arg_insert(header)).collect();
+
+        for block in self.calls_buf.iter().cloned() {
+            let call_args = b.fun().block_reads(block)[3..].to_vec();
+            b.block_clear(block);
+            b.op_call_flow(block, header, &call_args);
+        }
+
+        // The body of the entry block now reads the function arguments,
+        // mangle it into one that reads the header arguments instead.
+        b.block_copy_body_map(entry, header, |_| None);
+
+        self.mangler.start(MangleTo(header));
+        for (from, to) in args.iter().zip(header_args.iter()) {
+            self.mangler.add_rename(MangleTo(*from), MangleTo(*to));
+        }
+        let new_header = self.mangler.run(b);
+
+        b.block_clear(entry);
+        b.op_call_flow(entry, new_header, &args);
+    }
+}
@@ -0,0 +1,117 @@
+use libeir_ir::parse_function_unwrap;
+
+use crate::FunctionPass;
+
+#[test]
+fn self_tail_call_to_loop() {
+    let mut fun = parse_function_unwrap(
+        "
+a'foo':a'count'/2 {
+    entry(%ret, %thr, %n, %acc):
+        match %n {
+            value 0 => done;
+            _ => next;
+        };
+    done():
+        %ret(%acc);
+    next():
+        a'erlang':a'-'/2(%n, 1) => dec except %thr;
+    dec(%m):
+        _:a'count'/2(%m, {%acc}) => %ret except %thr;
+}
+",
+    );
+    let mut b = fun.builder();
+
+    let mut pass = super::TailCallLoopPass::new();
+    pass.run_function_pass(&mut b);
+
+    let after = parse_function_unwrap(
+        "
+a'foo':a'count'/2 {
+    entry(%ret, %thr, %n, %acc):
+        head(%n, %acc);
+    head(%hn, %hacc):
+        match %hn {
+            value 0 => done;
+            _ => next;
+        };
+    done():
+        %ret(%hacc);
+    next():
+        a'erlang':a'-'/2(%hn, 1) => dec except %thr;
+    dec(%m):
+        head(%m, {%hacc});
+}
+",
+    );
+
+    assert!(b
+        .fun()
+        .graph_eq(b.fun().block_entry(), &after, after.block_entry())
+        .is_ok());
+    assert!(b.fun().tail_calls().is_empty());
+}
+
+#[test]
+fn non_tail_self_call_untouched() {
+    let text = "
+a'foo':a'fac'/1 {
+    entry(%ret, %thr, %n):
+        match %n {
+            value 0 => done;
+            _ => next;
+        };
+    done():
+        %ret(1);
+    next():
+        a'erlang':a'-'/2(%n, 1) => dec except %thr;
+    dec(%m):
+        _:a'fac'/1(%m) => mul except %thr;
+    mul(%r):
+        a'erlang':a'*'/2(%n, %r) => %ret except %thr;
+}
+";
+    let mut fun = parse_function_unwrap(text);
+    let mut b = fun.builder();
+
+    let mut pass = super::TailCallLoopPass::new();
+    pass.run_function_pass(&mut b);
+
+    let after = parse_function_unwrap(text);
+    assert!(b
+        .fun()
+        .graph_eq(b.fun().block_entry(), &after, after.block_entry())
+        .is_ok());
+}
+
+#[test]
+fn qualified_self_call_untouched() {
+    let text = "
+a'foo':a'count'/1 {
+    entry(%ret, %thr, %n):
+        match %n {
+            value 0 => done;
+            _ => next;
+        };
+    done():
+        %ret(a'ok');
+    next():
+        a'erlang':a'-'/2(%n, 1) => dec except %thr;
+    dec(%m):
+        a'foo':a'count'/1(%m) => %ret except %thr;
+}
+";
+    let mut fun = parse_function_unwrap(text);
+    let mut b = fun.builder();
+
+    let mut pass = super::TailCallLoopPass::new();
+    pass.run_function_pass(&mut b);
+
+    let after = parse_function_unwrap(text);
+    assert!(b
+        .fun()
+        .graph_eq(b.fun().block_entry(), &after, after.block_entry())
+        .is_ok());
+    assert!(b.fun().tail_calls().len() == 1);
+}
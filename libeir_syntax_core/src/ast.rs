@@ -1,12 +1,17 @@
-use eir::{ Atom, Variable };
+use libeir_intern::Symbol;
+use libeir_util_number::BigInt;
 
-#[derive(Debug, Copy, Clone)]
+pub type Atom = Symbol;
+pub type Variable = Symbol;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum MapExactAssoc {
     Exact,
     Assoc,
 }
 
-#[derive(Debug, Clone)]
+/// Annotations are parsed, but not kept.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Annotated<I>(pub I, pub Vec<()>);
 impl<I> Annotated<I> {
     pub fn empty(inner: I) -> Self {
@@ -14,7 +19,7 @@ impl<I> Annotated<I> {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Module {
     pub name: Atom,
     pub declarations: Vec<FunctionName>,
@@ -27,130 +32,106 @@ pub struct FunctionName {
     pub name: Atom,
     pub arity: usize,
 }
-//impl FunctionName {
-//    pub fn to_eir(&self, module: Atom) -> FunctionIdent {
-//        FunctionIdent {
-//            module: module,
-//            name: self.name.clone(),
-//            arity: self.arity,
-//            lambda: None,
-//        }
-//    }
-//}
-
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Integer {
-    pub sign: bool,
-    pub digits: String,
-}
-impl Integer {
-    pub fn as_u32(&self) -> u32 {
-        assert!(self.sign);
-        self.digits.parse().unwrap()
-    }
-    pub fn as_usize(&self) -> usize {
-        assert!(self.sign);
-        self.digits.parse().unwrap()
-    }
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AtomicLiteral {
+    Integer(BigInt),
+    Float(f64),
+    Atom(Atom),
+    Nil,
+    Char(char),
+    String(String),
 }
 
-use eir::{ AtomicTerm, ConstantTerm };
-type AtomicLiteral = AtomicTerm;
-
-//#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-//pub enum AtomicLiteral {
-//    Integer(Integer),
-//    Float,
-//    Atom(Atom),
-//    Nil,
-//    Char(char),
-//    String(String),
-//}
-//impl Display for AtomicLiteral {
-//    fn fmt(&self, f: &mut Formatter) -> Result<(), ::std::fmt::Error> {
-//        match self {
-//            &AtomicLiteral::Integer(ref int) => write!(f, "{}{}", int.sign, int.digits),
-//            _ => write!(f, "unimpl"),
-//        }
-//    }
-//}
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Constant {
     Atomic(AtomicLiteral),
     Tuple(Vec<Constant>),
     List(Vec<Constant>, Box<Constant>),
 }
-//impl Constant {
-//    pub fn to_eir(&self) -> ConstantTerm {
-//        match self {
-//            Constant::Atomic(atomic) => ConstantTerm::Atomic(atomic.clone()),
-//            Constant::List(head, tail) =>
-//                ConstantTerm::List(
-//                    head.iter().map(|c| c.to_eir()).collect(),
-//                    Box::new(tail.to_eir()),
-//                ),
-//            _ => unimplemented!(),
-//        }
-//    }
-//}
-//impl Display for Constant {
-//    fn fmt(&self, f: &mut Formatter) -> Result<(), ::std::fmt::Error> {
-//        match self {
-//            &Constant::Atomic(ref lit) => write!(f, "{}", lit),
-//            _ => write!(f, "unimpl"),
-//        }
-//    }
-//}
-
-#[derive(Debug, Clone)]
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct FunctionDefinition {
     pub name: Annotated<FunctionName>,
     pub fun: Annotated<Function>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum SingleExpression {
     // Env reading
     FunctionName(FunctionName),
-    ExternalFunctionName { module: Atom, name: FunctionName },
+    ExternalFunctionName {
+        module: Atom,
+        name: FunctionName,
+    },
     Variable(Variable),
 
     // Control flow
-    Let { vars: Vec<Annotated<Variable>>, val: Box<Expression>, body: Box<Expression> },
+    Let {
+        vars: Vec<Annotated<Variable>>,
+        val: Box<Expression>,
+        body: Box<Expression>,
+    },
     Catch(Box<Expression>),
-    Case { val: Box<Expression>, clauses: Vec<Annotated<CaseClause>> },
+    Case {
+        val: Box<Expression>,
+        clauses: Vec<Annotated<CaseClause>>,
+    },
     Do(Box<Expression>, Box<Expression>),
-    Try { body: Box<Expression>, then_vars: Vec<Annotated<Variable>>, then: Box<Expression>,
-          catch_vars: Vec<Annotated<Variable>>, catch: Box<Expression> },
-    Receive { clauses: Vec<Annotated<CaseClause>>, timeout_time: Box<Expression>,
-              timeout_body: Box<Expression> },
+    Try {
+        body: Box<Expression>,
+        then_vars: Vec<Annotated<Variable>>,
+        then: Box<Expression>,
+        catch_vars: Vec<Annotated<Variable>>,
+        catch: Box<Expression>,
+    },
+    Receive {
+        clauses: Vec<Annotated<CaseClause>>,
+        timeout_time: Box<Expression>,
+        timeout_body: Box<Expression>,
+    },
 
     // Calling
     PrimOpCall(PrimOpCall),
-    ApplyCall { fun: Box<Expression>, args: Vec<Expression> },
-    InterModuleCall { module: Box<Expression>, name: Box<Expression>, args: Vec<Expression> },
+    ApplyCall {
+        fun: Box<Expression>,
+        args: Vec<Expression>,
+    },
+    InterModuleCall {
+        module: Box<Expression>,
+        name: Box<Expression>,
+        args: Vec<Expression>,
+    },
 
     // Lambda creation
     Fun(Box<Function>),
-    LetRec { funs: Vec<(FunctionName, Function)>, body: Box<Expression> },
+    LetRec {
+        funs: Vec<(FunctionName, Function)>,
+        body: Box<Expression>,
+    },
 
     // Term constructors
     AtomicLiteral(AtomicLiteral),
     Tuple(Vec<Expression>),
-    List { head: Vec<Expression>, tail: Box<Expression> },
-    Map(Vec<Annotated<(Expression, MapExactAssoc, Expression)>>, Option<Expression>),
+    List {
+        head: Vec<Expression>,
+        tail: Box<Expression>,
+    },
+    Map(
+        Vec<Annotated<(Expression, MapExactAssoc, Expression)>>,
+        Option<Expression>,
+    ),
     Binary(Vec<(Expression, Vec<Expression>)>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct CaseClause {
     pub patterns: Vec<Annotated<Pattern>>,
     pub guard: Expression,
     pub body: Expression,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Pattern {
     Wildcard,
     BindVar(Annotated<Variable>, Box<Annotated<Pattern>>),
@@ -162,25 +143,28 @@ pub enum Pattern {
 }
 impl Pattern {
     pub fn nil() -> Annotated<Pattern> {
-        Annotated::empty(Pattern::Atomic(AtomicTerm::Nil))
+        Annotated::empty(Pattern::Atomic(AtomicLiteral::Nil))
     }
 }
 
 pub type Expression = Annotated<Vec<Annotated<SingleExpression>>>;
 impl Expression {
     pub fn nil() -> Self {
-        Annotated::empty(vec![Annotated::empty(SingleExpression::AtomicLiteral(
-            AtomicTerm::Nil))])
+        Expression::single(SingleExpression::AtomicLiteral(AtomicLiteral::Nil))
+    }
+
+    pub fn single(expr: SingleExpression) -> Self {
+        Annotated::empty(vec![Annotated::empty(expr)])
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Function {
     pub vars: Vec<Annotated<Variable>>,
     pub body: Expression,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PrimOpCall {
     pub name: Annotated<Atom>,
     pub args: Vec<Expression>,
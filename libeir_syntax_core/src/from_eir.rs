@@ -0,0 +1,1347 @@
+//! Rebuilds Core Erlang from Eir.
+//!
+//! Eir is in continuation passing style, where every block ends in a tail
+//! call. In Core Erlang, the same control flow is expressed with nested
+//! expressions:
+//!
+//! * Every block that is not printed inline gets a `letrec` function in the
+//!   body of its immediate dominator. Captured blocks become closures, with
+//!   the return and throw continuations left implicit.
+//! * A block that is only branched to from one place is printed inline,
+//!   with its arguments bound by `let`, a case clause or a `try`.
+//! * Calling the return continuation of the current function produces the
+//!   value, calling the throw continuation raises.
+//! * A function call with a throw continuation in the function is wrapped
+//!   in a `try`.
+//! * `Case` becomes `case`, with the guard lambdas printed as guards.
+//!   The ok continuation of a guard is `'true'`, the fail continuation is
+//!   `'false'`.
+//! * The `receive_start`/`receive_wait`/`receive_done` structure produced
+//!   by the Erlang frontend becomes `receive`.
+//!
+//! A trace captured with `trace_capture_raw` is `[]`. When it is raised
+//! directly, the raise becomes a call to `erlang:error/1` and friends,
+//! which capture the stack trace again.
+//!
+//! Binary construction and matching are not supported yet.
+
+use std::collections::{HashMap, HashSet};
+
+use cranelift_entity::EntityRef;
+use petgraph::algo::dominators::simple_fast;
+
+use libeir_intern::Symbol;
+use libeir_ir::operation::case::Case;
+use libeir_ir::operation::receive::{ReceiveDone, ReceiveStart, ReceiveWait};
+use libeir_ir::pattern::PatternNodeKind;
+use libeir_ir::{
+    AtomicTerm, BasicType, BinOp, Block, CallKind, Const, ConstKind, Function, FunctionIdent,
+    FunctionTree, LogicOp, MapPutUpdate, MatchKind, OpKind, PatternClause, PatternContainer,
+    PatternNode, PatternValue, PrimOp, PrimOpKind, Value, ValueKind,
+};
+use libeir_util_binary::{BitCarrier, BitVec};
+use libeir_util_number::BigInt;
+
+use crate::ast::{
+    Annotated, AtomicLiteral, CaseClause, Expression, Function as CoreFunction, FunctionDefinition,
+    FunctionName, MapExactAssoc, Module, Pattern, PrimOpCall, SingleExpression, Variable,
+};
+
+#[derive(Debug)]
+pub enum PrintError {
+    /// The function contains a construct with no Core Erlang equivalent,
+    /// or one the printer does not handle yet.
+    Unsupported { fun: FunctionIdent, reason: String },
+}
+
+/// Rebuilds the Core Erlang AST of a module. All functions are exported.
+pub fn from_eir(module: &libeir_ir::Module) -> Result<Module, PrintError> {
+    let name = module.name().name;
+
+    let mut declarations = Vec::new();
+    let mut definitions = Vec::new();
+    for def in module.function_iter() {
+        let fun = def.function();
+        let ident = fun.ident();
+        let fun_name = FunctionName {
+            name: ident.name.name,
+            arity: ident.arity,
+        };
+
+        let core_fun = FunctionLower::new(name, fun).function()?;
+
+        declarations.push(fun_name.clone());
+        definitions.push(FunctionDefinition {
+            name: Annotated::empty(fun_name),
+            fun: Annotated::empty(core_fun),
+        });
+    }
+
+    Ok(Module {
+        name,
+        declarations,
+        attributes: Vec::new(),
+        definitions,
+    })
+}
+
+/// The blocks making up a `receive`, as built by the Erlang frontend.
+struct ReceiveShape {
+    timeout: Value,
+    /// `receive_wait`, branched to from the start and the no match block.
+    wait: Block,
+    after: Block,
+    /// The `Case` on the message.
+    check: Block,
+    /// Loops back to `wait`.
+    no_match: Block,
+    /// The clause bodies, each a `receive_done`.
+    bodies: Vec<Block>,
+}
+
+struct FunctionLower<'a> {
+    module: Symbol,
+    fun: &'a Function,
+    tree: FunctionTree,
+
+    /// The children of every block in the dominator tree.
+    dom_children: HashMap<Block, Vec<Block>>,
+    /// The number of times each block is referenced.
+    uses: HashMap<Block, usize>,
+    /// The function entry each block is in the scope of.
+    owner: HashMap<Block, Block>,
+
+    /// Blocks printed at their only use, instead of getting a function.
+    inline: HashSet<Block>,
+    /// Blocks that are part of a `receive`. These are not printed as
+    /// blocks at all, their children in the dominator tree are hoisted to
+    /// the block with the `receive_start`.
+    structural: HashSet<Block>,
+    /// Function entries that are guards of a `Case`.
+    guards: HashSet<Block>,
+    /// Traces produced by `trace_capture_raw`.
+    fresh_traces: HashSet<Value>,
+
+    names: HashMap<Value, Variable>,
+    next_fresh: usize,
+}
+
+impl<'a> FunctionLower<'a> {
+    fn new(module: Symbol, fun: &'a Function) -> Self {
+        let data = libeir_lowerutils::analyze(fun);
+
+        let graph = fun.block_graph();
+        let doms = simple_fast(&graph, fun.block_entry());
+        let blocks: Vec<Block> = graph.dfs_iter().collect();
+
+        let mut dom_children: HashMap<Block, Vec<Block>> = HashMap::new();
+        let mut uses = HashMap::new();
+        for block in blocks.iter() {
+            if let Some(idom) = doms.immediate_dominator(*block) {
+                dom_children.entry(idom).or_default().push(*block);
+            }
+            fun.block_walk_nested_values::<_, ()>(*block, &mut |value| {
+                if let Some(target) = fun.value_block(value) {
+                    *uses.entry(target).or_insert(0) += 1;
+                }
+                Ok(())
+            })
+            .unwrap();
+        }
+        for children in dom_children.values_mut() {
+            children.sort();
+        }
+
+        let mut owner = HashMap::new();
+        for (entry, function) in data.func_tree.functions.iter() {
+            for block in function.scope.iter() {
+                owner.insert(*block, *entry);
+            }
+        }
+
+        let mut lower = FunctionLower {
+            module,
+            fun,
+            tree: data.func_tree,
+
+            dom_children,
+            uses,
+            owner,
+
+            inline: HashSet::new(),
+            structural: HashSet::new(),
+            guards: HashSet::new(),
+            fresh_traces: HashSet::new(),
+
+            names: HashMap::new(),
+            next_fresh: 0,
+        };
+        for block in blocks.iter() {
+            lower.classify(*block);
+        }
+        lower
+    }
+
+    fn classify(&mut self, block: Block) {
+        let fun = self.fun;
+        let reads = fun.block_reads(block);
+        let kind = fun.block_kind(block).unwrap();
+
+        for (idx, target) in fun.op_branch_iter(block).enumerate() {
+            // With more than one update, the error continuation is branched
+            // to from one place per key.
+            if let OpKind::MapPut { action } = kind {
+                let updates = action.iter().filter(|a| **a == MapPutUpdate::Update);
+                if idx == 1 && updates.count() > 1 {
+                    continue;
+                }
+            }
+
+            if let Some(target) = fun.value_block(target) {
+                if self.uses[&target] == 1 && !self.tree.functions.contains_key(&target) {
+                    self.inline.insert(target);
+                }
+            }
+        }
+
+        match kind {
+            OpKind::TraceCaptureRaw => {
+                let then = fun.value_block(reads[0]).unwrap();
+                self.fresh_traces.insert(fun.block_args(then)[0]);
+            }
+            OpKind::Dyn(dyn_op) => {
+                if let Some(case) = dyn_op.downcast_ref::<Case>() {
+                    for idx in 0..case.clauses().len() {
+                        if let Some(guard) = fun.value_block(reads[1 + idx * 2]) {
+                            self.guards.insert(guard);
+                        }
+                    }
+                }
+                if dyn_op.downcast_ref::<ReceiveStart>().is_some() {
+                    if let Some(shape) = self.receive_shape(block) {
+                        self.structural.insert(shape.wait);
+                        self.structural.insert(shape.check);
+                        self.structural.insert(shape.no_match);
+                        self.structural.extend(shape.bodies.iter().cloned());
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn receive_shape(&self, block: Block) -> Option<ReceiveShape> {
+        let fun = self.fun;
+        let uses = |block: Block| self.uses[&block];
+
+        let start_reads = fun.block_reads(block);
+        let wait = fun.value_block(start_reads[0])?;
+        fun.block_kind(wait)?.get_dyn::<ReceiveWait>()?;
+        let recv_ref = fun.block_args(wait)[0];
+        let wait_reads = fun.block_reads(wait);
+        let after = fun.value_block(wait_reads[0])?;
+        let check = fun.value_block(wait_reads[1])?;
+        if wait_reads[2] != recv_ref || uses(wait) != 2 || uses(after) != 1 || uses(check) != 1 {
+            return None;
+        }
+
+        let case = fun.block_kind(check)?.get_dyn::<Case>()?;
+        let check_reads = fun.block_reads(check);
+        let num_clauses = case.clauses().len();
+        if check_reads[1 + num_clauses * 2] != fun.block_args(check)[0] {
+            return None;
+        }
+
+        let no_match = fun.value_block(check_reads[0])?;
+        match fun.block_kind(no_match)? {
+            OpKind::Call(CallKind::ControlFlow) => (),
+            _ => return None,
+        }
+        if fun.block_reads(no_match) != [start_reads[0], recv_ref] || uses(no_match) != 1 {
+            return None;
+        }
+
+        let mut bodies = Vec::with_capacity(num_clauses);
+        for idx in 0..num_clauses {
+            let body = fun.value_block(check_reads[2 + idx * 2])?;
+            fun.block_kind(body)?.get_dyn::<ReceiveDone>()?;
+            if fun.block_reads(body)[1] != recv_ref || uses(body) != 1 {
+                return None;
+            }
+            bodies.push(body);
+        }
+
+        Some(ReceiveShape {
+            timeout: start_reads[1],
+            wait,
+            after,
+            check,
+            no_match,
+            bodies,
+        })
+    }
+
+    fn unsupported<T>(&self, reason: impl Into<String>) -> Result<T, PrintError> {
+        Err(PrintError::Unsupported {
+            fun: *self.fun.ident(),
+            reason: reason.into(),
+        })
+    }
+
+    fn var(&mut self, value: Value) -> Variable {
+        *self
+            .names
+            .entry(value)
+            .or_insert_with(|| Symbol::intern(&format!("_{}", value.index())))
+    }
+
+    fn fresh(&mut self) -> Variable {
+        let var = Symbol::intern(&format!("_cor{}", self.next_fresh));
+        self.next_fresh += 1;
+        var
+    }
+
+    fn fresh_n(&mut self, num: usize) -> Vec<Variable> {
+        (0..num).map(|_| self.fresh()).collect()
+    }
+
+    /// The return and throw continuations in effect in a block.
+    fn escapes(&self, block: Block) -> (Option<Value>, Option<Value>) {
+        let entry = &self.tree.functions[&self.owner[&block]];
+        (entry.ret, entry.thr)
+    }
+
+    fn in_guard(&self, block: Block) -> bool {
+        self.guards.contains(&self.owner[&block])
+    }
+
+    fn block_name(&self, block: Block) -> FunctionName {
+        let mut arity = self.fun.block_args(block).len();
+        if self.tree.functions.contains_key(&block) {
+            arity -= 2;
+        }
+        FunctionName {
+            name: Symbol::intern(&format!("-b{}-", block.index())),
+            arity,
+        }
+    }
+
+    fn function(&mut self) -> Result<CoreFunction, PrintError> {
+        self.block_fun(self.fun.block_entry())
+    }
+
+    fn block_fun(&mut self, block: Block) -> Result<CoreFunction, PrintError> {
+        let mut args = self.fun.block_args(block);
+        if self.tree.functions.contains_key(&block) {
+            args = &args[2..];
+        }
+        let vars = args
+            .iter()
+            .map(|a| Annotated::empty(self.var(*a)))
+            .collect();
+        Ok(CoreFunction {
+            vars,
+            body: self.block(block)?,
+        })
+    }
+
+    /// The blocks that get a function in the `letrec` of a block.
+    fn letrec_children(&self, block: Block, out: &mut Vec<Block>) {
+        if let Some(children) = self.dom_children.get(&block) {
+            for child in children.iter() {
+                if self.structural.contains(child) {
+                    self.letrec_children(*child, out);
+                } else if !self.inline.contains(child) && !self.guards.contains(child) {
+                    out.push(*child);
+                }
+            }
+        }
+    }
+
+    fn block(&mut self, block: Block) -> Result<Expression, PrintError> {
+        let mut children = Vec::new();
+        self.letrec_children(block, &mut children);
+        if !children.is_empty() && self.in_guard(block) {
+            return self.unsupported("local function in a guard");
+        }
+
+        let mut funs = Vec::with_capacity(children.len());
+        for child in children.iter() {
+            funs.push((self.block_name(*child), self.block_fun(*child)?));
+        }
+        let body = self.op(block)?;
+
+        if funs.is_empty() {
+            Ok(body)
+        } else {
+            Ok(Expression::single(SingleExpression::LetRec {
+                funs,
+                body: Box::new(body),
+            }))
+        }
+    }
+
+    fn op(&mut self, block: Block) -> Result<Expression, PrintError> {
+        let fun = self.fun;
+        let reads = fun.block_reads(block);
+
+        match fun.block_kind(block).unwrap() {
+            OpKind::Call(CallKind::ControlFlow) => {
+                let args = self.values(&reads[1..])?;
+                let fresh_trace = reads.len() == 4 && self.fresh_traces.contains(&reads[3]);
+                self.jump(block, reads[0], args, fresh_trace)
+            }
+            OpKind::Call(CallKind::Function) => self.call(block, reads),
+            OpKind::IfBool => {
+                let (branches, value) = reads.split_at(reads.len() - 1);
+                let mut clauses = Vec::with_capacity(branches.len());
+                for (idx, branch) in branches.iter().enumerate() {
+                    let pattern = match idx {
+                        0 => Pattern::Atomic(atom_literal("true")),
+                        1 => Pattern::Atomic(atom_literal("false")),
+                        _ => bind_var(self.fresh()),
+                    };
+                    let body = self.jump(block, *branch, Vec::new(), false)?;
+                    clauses.push(clause(vec![pattern], atom("true"), body));
+                }
+                Ok(Expression::single(SingleExpression::Case {
+                    val: Box::new(self.value(value[0])?),
+                    clauses,
+                }))
+            }
+            OpKind::TraceCaptureRaw => self.jump(block, reads[0], vec![Expression::nil()], false),
+            OpKind::TraceConstruct => {
+                let trace = if self.fresh_traces.contains(&reads[1]) {
+                    Expression::nil()
+                } else {
+                    primop("build_stacktrace", vec![self.value(reads[1])?])
+                };
+                self.jump(block, reads[0], vec![trace], false)
+            }
+            OpKind::MapPut { action } => self.map_put(block, action, reads),
+            OpKind::UnpackValueList(num) => {
+                let (vars, body) = self.bind(block, reads[0], *num)?;
+                let list = self.value(reads[1])?;
+                Ok(let_(vars, list, body))
+            }
+            OpKind::Match { branches } => self.match_op(block, branches, reads),
+            OpKind::Unreachable => Ok(call_erlang("error", vec![atom("unreachable")])),
+            OpKind::Dyn(dyn_op) => {
+                if let Some(case) = dyn_op.downcast_ref::<Case>() {
+                    self.case(block, case, reads)
+                } else if dyn_op.downcast_ref::<ReceiveStart>().is_some() {
+                    self.receive(block)
+                } else {
+                    self.unsupported(format!("operation {}", dyn_op.name()))
+                }
+            }
+        }
+    }
+
+    /// Binds the arguments of a continuation. Returns the variables to bind,
+    /// and the expression that continues with them.
+    fn bind(
+        &mut self,
+        block: Block,
+        target: Value,
+        arity: usize,
+    ) -> Result<(Vec<Variable>, Expression), PrintError> {
+        let (ret, thr) = self.escapes(block);
+
+        if Some(target) == ret || Some(target) == thr {
+            let vars = self.fresh_n(arity);
+            let args = vars.iter().map(|v| var_expr(*v)).collect();
+            let body = self.jump(block, target, args, false)?;
+            return Ok((vars, body));
+        }
+
+        match self.fun.value_block(target) {
+            Some(target) if self.inline.contains(&target) => {
+                let args = self.fun.block_args(target);
+                let vars = args.iter().map(|a| self.var(*a)).collect();
+                Ok((vars, self.block(target)?))
+            }
+            Some(target) => {
+                let vars = self.fresh_n(arity);
+                let args = vars.iter().map(|v| var_expr(*v)).collect();
+                Ok((vars, apply(self.block_name(target), args)))
+            }
+            None => self.unsupported("dynamic continuation"),
+        }
+    }
+
+    /// Continues at `target` with the given arguments.
+    fn jump(
+        &mut self,
+        block: Block,
+        target: Value,
+        args: Vec<Expression>,
+        fresh_trace: bool,
+    ) -> Result<Expression, PrintError> {
+        let (ret, thr) = self.escapes(block);
+        let guard = self.in_guard(block);
+
+        if Some(target) == ret {
+            return Ok(if guard { atom("true") } else { values(args) });
+        }
+        if Some(target) == thr {
+            return Ok(if guard {
+                atom("false")
+            } else {
+                raise(args, fresh_trace)
+            });
+        }
+
+        match self.fun.value_block(target) {
+            Some(target) if self.inline.contains(&target) => {
+                let params = self.fun.block_args(target);
+                let vars = params.iter().map(|a| self.var(*a)).collect();
+                let body = self.block(target)?;
+                Ok(let_(vars, values(args), body))
+            }
+            Some(target) => Ok(apply(self.block_name(target), args)),
+            None => self.unsupported("dynamic continuation"),
+        }
+    }
+
+    fn call(&mut self, block: Block, reads: &[Value]) -> Result<Expression, PrintError> {
+        let call = self.call_expr(block, reads[0], &reads[3..])?;
+        let (ret, thr) = self.escapes(block);
+
+        if Some(reads[1]) == ret && Some(reads[2]) == thr && !self.in_guard(block) {
+            return Ok(call);
+        }
+
+        let (ok_vars, ok_body) = self.bind(block, reads[1], 1)?;
+        if Some(reads[2]) == thr {
+            return Ok(let_(ok_vars, call, ok_body));
+        }
+
+        let (err_vars, err_body) = self.bind(block, reads[2], 3)?;
+        Ok(Expression::single(SingleExpression::Try {
+            body: Box::new(call),
+            then_vars: annotate(ok_vars),
+            then: Box::new(ok_body),
+            catch_vars: annotate(err_vars),
+            catch: Box::new(err_body),
+        }))
+    }
+
+    fn call_expr(
+        &mut self,
+        block: Block,
+        callee: Value,
+        args: &[Value],
+    ) -> Result<Expression, PrintError> {
+        let args = self.values(args)?;
+
+        let capture = self
+            .fun
+            .value_primop(callee)
+            .and_then(|p| self.const_capture(p));
+        let expr = match capture {
+            Some((Some(module), name, _)) if module != self.module => {
+                SingleExpression::InterModuleCall {
+                    module: Box::new(atom_expr(module)),
+                    name: Box::new(atom_expr(name)),
+                    args,
+                }
+            }
+            Some((_, name, arity)) => SingleExpression::ApplyCall {
+                fun: Box::new(Expression::single(SingleExpression::FunctionName(
+                    FunctionName { name, arity },
+                ))),
+                args,
+            },
+            None => match self.fun.value_primop(callee) {
+                Some(prim) if *self.fun.primop_kind(prim) == PrimOpKind::CaptureFunction => {
+                    let reads = self.fun.primop_reads(prim);
+                    SingleExpression::InterModuleCall {
+                        module: Box::new(self.value(reads[0])?),
+                        name: Box::new(self.value(reads[1])?),
+                        args,
+                    }
+                }
+                _ => SingleExpression::ApplyCall {
+                    fun: Box::new(self.value(callee)?),
+                    args,
+                },
+            },
+        };
+
+        // Only BIFs can be called from guards.
+        if self.in_guard(block) {
+            match &expr {
+                SingleExpression::InterModuleCall { module, .. } if **module == atom("erlang") => {}
+                _ => return self.unsupported("function call in a guard"),
+            }
+        }
+
+        Ok(Expression::single(expr))
+    }
+
+    /// A function capture with constant operands, as
+    /// `(module, name, arity)`. Local captures have no module.
+    fn const_capture(&self, prim: PrimOp) -> Option<(Option<Symbol>, Symbol, usize)> {
+        let fun = self.fun;
+        let reads = fun.primop_reads(prim);
+        let (module, rest) = match fun.primop_kind(prim) {
+            PrimOpKind::CaptureFunction => (Some(self.const_atom(reads[0])?), &reads[1..]),
+            PrimOpKind::CaptureLocalFunction => (None, reads),
+            _ => return None,
+        };
+        let name = self.const_atom(rest[0])?;
+        let arity = match fun.cons().const_kind(fun.value_const(rest[1])?) {
+            ConstKind::Atomic(AtomicTerm::Int(int)) => int.value() as usize,
+            _ => return None,
+        };
+        Some((module, name, arity))
+    }
+
+    fn const_atom(&self, value: Value) -> Option<Symbol> {
+        let fun = self.fun;
+        match fun.cons().const_kind(fun.value_const(value)?) {
+            ConstKind::Atomic(AtomicTerm::Atom(atom)) => Some(atom.0),
+            _ => None,
+        }
+    }
+
+    fn map_put(
+        &mut self,
+        block: Block,
+        actions: &[MapPutUpdate],
+        reads: &[Value],
+    ) -> Result<Expression, PrintError> {
+        let map = self.value(reads[2])?;
+
+        let mut entries = Vec::with_capacity(actions.len());
+        for (idx, action) in actions.iter().enumerate() {
+            let op = match action {
+                MapPutUpdate::Put => MapExactAssoc::Assoc,
+                MapPutUpdate::Update => MapExactAssoc::Exact,
+            };
+            let key = self.value(reads[3 + idx * 2])?;
+            let value = self.value(reads[4 + idx * 2])?;
+            entries.push(Annotated::empty((key, op, value)));
+        }
+
+        let (vars, body) = self.bind(block, reads[0], 1)?;
+        let new_map = Expression::single(SingleExpression::Map(entries, Some(map.clone())));
+        let mut expr = let_(vars, new_map, body);
+
+        // The error continuation gets the first key that is missing.
+        for (idx, action) in actions.iter().enumerate().rev() {
+            if *action == MapPutUpdate::Update {
+                let key = self.value(reads[3 + idx * 2])?;
+                let missing = self.jump(block, reads[1], vec![key.clone()], false)?;
+                let check = call_erlang("is_map_key", vec![key, map.clone()]);
+                expr = Expression::single(SingleExpression::Case {
+                    val: Box::new(check),
+                    clauses: vec![
+                        clause(
+                            vec![Pattern::Atomic(atom_literal("true"))],
+                            atom("true"),
+                            expr,
+                        ),
+                        clause(
+                            vec![Pattern::Atomic(atom_literal("false"))],
+                            atom("true"),
+                            missing,
+                        ),
+                    ],
+                });
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn match_op(
+        &mut self,
+        block: Block,
+        branches: &[MatchKind],
+        reads: &[Value],
+    ) -> Result<Expression, PrintError> {
+        let fun = self.fun;
+
+        let mut clauses = Vec::with_capacity(branches.len());
+        for (idx, kind) in branches.iter().enumerate() {
+            let target = fun.value_list_get_n(reads[0], idx).unwrap();
+            let args_list = reads[2 + idx];
+            let args: Vec<Value> = (0..fun.value_list_length(args_list))
+                .map(|n| fun.value_list_get_n(args_list, n).unwrap())
+                .collect();
+
+            let clause = match kind {
+                MatchKind::Value => {
+                    let (_, body) = self.bind(block, target, 0)?;
+                    let var = self.fresh();
+                    let expected = self.value(args[0])?;
+                    let guard = call_erlang("=:=", vec![var_expr(var), expected]);
+                    clause(vec![bind_var(var)], guard, body)
+                }
+                MatchKind::Type(typ) => {
+                    let (_, body) = self.bind(block, target, 0)?;
+                    let var = self.fresh();
+                    let guard = self.type_test(var_expr(var), *typ, true)?;
+                    clause(vec![bind_var(var)], guard, body)
+                }
+                MatchKind::Tuple(arity) => {
+                    let (vars, body) = self.bind(block, target, *arity)?;
+                    let elems = vars.into_iter().map(|v| Annotated::empty(bind_var(v)));
+                    clause(vec![Pattern::Tuple(elems.collect())], atom("true"), body)
+                }
+                MatchKind::ListCell => {
+                    let (vars, body) = self.bind(block, target, 2)?;
+                    let pattern = Pattern::List(
+                        vec![Annotated::empty(bind_var(vars[0]))],
+                        Box::new(Annotated::empty(bind_var(vars[1]))),
+                    );
+                    clause(vec![pattern], atom("true"), body)
+                }
+                MatchKind::MapItem => {
+                    let (vars, body) = self.bind(block, target, 1)?;
+                    let key = self.value(args[0])?;
+                    let key = self.single(key)?;
+                    let entry = (Annotated::empty(key), Annotated::empty(bind_var(vars[0])));
+                    let pattern = Pattern::Map(vec![Annotated::empty(entry)]);
+                    clause(vec![pattern], atom("true"), body)
+                }
+                MatchKind::Wildcard => {
+                    let (_, body) = self.bind(block, target, 0)?;
+                    clause(vec![bind_var(self.fresh())], atom("true"), body)
+                }
+                MatchKind::Binary(_) => return self.unsupported("binary matching"),
+            };
+            clauses.push(clause);
+        }
+
+        Ok(Expression::single(SingleExpression::Case {
+            val: Box::new(self.value(reads[1])?),
+            clauses,
+        }))
+    }
+
+    fn case(
+        &mut self,
+        block: Block,
+        case: &Case,
+        reads: &[Value],
+    ) -> Result<Expression, PrintError> {
+        let num_clauses = case.clauses().len();
+        let match_val = reads[1 + num_clauses * 2];
+        let mut values = &reads[2 + num_clauses * 2..];
+
+        let mut clauses = Vec::with_capacity(num_clauses + 1);
+        for (idx, pat_clause) in case.clauses().iter().enumerate() {
+            let num_values = case.pat().clause_values(*pat_clause).len();
+            let (clause_values, rest) = values.split_at(num_values);
+            values = rest;
+
+            let num_binds = case.pat().clause_binds(*pat_clause).len();
+            let (vars, body) = self.bind(block, reads[2 + idx * 2], num_binds)?;
+            clauses.push(self.case_clause(
+                case.pat(),
+                *pat_clause,
+                clause_values,
+                reads[1 + idx * 2],
+                vars,
+                body,
+            )?);
+        }
+
+        let num_roots = self.fun.value_list_length(match_val);
+        let patterns = (0..num_roots).map(|_| bind_var(self.fresh())).collect();
+        let no_match = self.jump(block, reads[0], Vec::new(), false)?;
+        clauses.push(clause(patterns, atom("true"), no_match));
+
+        Ok(Expression::single(SingleExpression::Case {
+            val: Box::new(self.value(match_val)?),
+            clauses,
+        }))
+    }
+
+    fn receive(&mut self, block: Block) -> Result<Expression, PrintError> {
+        let shape = match self.receive_shape(block) {
+            Some(shape) => shape,
+            None => return self.unsupported("receive"),
+        };
+
+        let fun = self.fun;
+        let case = fun
+            .block_kind(shape.check)
+            .unwrap()
+            .get_dyn::<Case>()
+            .unwrap();
+        let reads = fun.block_reads(shape.check);
+        let mut values = &reads[2 + case.clauses().len() * 2..];
+
+        let mut clauses = Vec::with_capacity(shape.bodies.len());
+        for (idx, (pat_clause, body)) in case.clauses().iter().zip(shape.bodies.iter()).enumerate()
+        {
+            let num_values = case.pat().clause_values(*pat_clause).len();
+            let (clause_values, rest) = values.split_at(num_values);
+            values = rest;
+
+            // The values extracted from the message are passed on through
+            // `receive_done`.
+            let vars = fun.block_args(*body).iter().map(|a| self.var(*a)).collect();
+            let done_reads = fun.block_reads(*body);
+            let done_args = self.values(&done_reads[2..])?;
+            let body_expr = self.jump(*body, done_reads[0], done_args, false)?;
+
+            clauses.push(self.case_clause(
+                case.pat(),
+                *pat_clause,
+                clause_values,
+                reads[1 + idx * 2],
+                vars,
+                body_expr,
+            )?);
+        }
+
+        Ok(Expression::single(SingleExpression::Receive {
+            clauses,
+            timeout_time: Box::new(self.value(shape.timeout)?),
+            timeout_body: Box::new(self.block(shape.after)?),
+        }))
+    }
+
+    fn case_clause(
+        &mut self,
+        pat: &PatternContainer,
+        pat_clause: PatternClause,
+        values: &[Value],
+        guard: Value,
+        vars: Vec<Variable>,
+        body: Expression,
+    ) -> Result<Annotated<CaseClause>, PrintError> {
+        let binds: HashMap<PatternNode, Variable> = pat
+            .clause_binds(pat_clause)
+            .iter()
+            .cloned()
+            .zip(vars.iter().cloned())
+            .collect();
+        let pat_values: HashMap<PatternValue, Value> = pat
+            .clause_values(pat_clause)
+            .iter()
+            .cloned()
+            .zip(values.iter().cloned())
+            .collect();
+
+        let mut checks = Vec::new();
+        let mut patterns = Vec::new();
+        for node in pat.clause_root_nodes(pat_clause).iter() {
+            patterns.push(self.pattern(pat, *node, &binds, &pat_values, &mut checks)?);
+        }
+
+        // The guard sees the bound values under the same names.
+        let guard = match self.fun.value_block(guard) {
+            Some(guard) if self.uses[&guard] == 1 => guard,
+            _ => return self.unsupported("shared guard"),
+        };
+        for (arg, var) in self.fun.block_args(guard)[2..].iter().zip(vars.iter()) {
+            self.names.insert(*arg, *var);
+        }
+        let mut guard_expr = self.block(guard)?;
+        for check in checks.into_iter().rev() {
+            guard_expr = if guard_expr == atom("true") {
+                check
+            } else {
+                call_erlang("and", vec![check, guard_expr])
+            };
+        }
+
+        Ok(Annotated::empty(CaseClause {
+            patterns: patterns.into_iter().map(Annotated::empty).collect(),
+            guard: guard_expr,
+            body,
+        }))
+    }
+
+    /// Builds the pattern for a node. Values that are matched against are
+    /// bound to a variable, with an equality check added to `checks`.
+    fn pattern(
+        &mut self,
+        pat: &PatternContainer,
+        node: PatternNode,
+        binds: &HashMap<PatternNode, Variable>,
+        values: &HashMap<PatternValue, Value>,
+        checks: &mut Vec<Expression>,
+    ) -> Result<Pattern, PrintError> {
+        let inner = match pat.node_kind(node) {
+            PatternNodeKind::Wildcard => None,
+            PatternNodeKind::Const(cons) => Some(self.const_pattern(*cons)?),
+            PatternNodeKind::Value(pat_value) => {
+                let var = binds.get(&node).cloned().unwrap_or_else(|| self.fresh());
+                let expected = self.pattern_value(values, *pat_value)?;
+                checks.push(call_erlang("=:=", vec![var_expr(var), expected]));
+                return Ok(bind_var(var));
+            }
+            PatternNodeKind::Tuple(elems) => {
+                let mut out = Vec::new();
+                for elem in elems.as_slice(&pat.node_pool).iter() {
+                    out.push(Annotated::empty(
+                        self.pattern(pat, *elem, binds, values, checks)?,
+                    ));
+                }
+                Some(Pattern::Tuple(out))
+            }
+            PatternNodeKind::List { head, tail } => {
+                let head = self.pattern(pat, *head, binds, values, checks)?;
+                let tail = self.pattern(pat, *tail, binds, values, checks)?;
+                Some(list_pattern(head, tail))
+            }
+            PatternNodeKind::Map { keys, values: vals } => {
+                let keys = keys.as_slice(&pat.value_pool);
+                let vals = vals.as_slice(&pat.node_pool);
+                let mut entries = Vec::new();
+                for (key, val) in keys.iter().zip(vals.iter()) {
+                    let key = self.pattern_value(values, *key)?;
+                    let key = self.single(key)?;
+                    let val = self.pattern(pat, *val, binds, values, checks)?;
+                    entries.push(Annotated::empty((
+                        Annotated::empty(key),
+                        Annotated::empty(val),
+                    )));
+                }
+                Some(Pattern::Map(entries))
+            }
+            PatternNodeKind::Binary { .. } => return self.unsupported("binary pattern"),
+        };
+
+        Ok(match (binds.get(&node), inner) {
+            (Some(var), Some(inner)) => {
+                Pattern::BindVar(Annotated::empty(*var), Box::new(Annotated::empty(inner)))
+            }
+            (Some(var), None) => bind_var(*var),
+            (None, Some(inner)) => inner,
+            (None, None) => bind_var(self.fresh()),
+        })
+    }
+
+    fn pattern_value(
+        &mut self,
+        values: &HashMap<PatternValue, Value>,
+        pat_value: PatternValue,
+    ) -> Result<Expression, PrintError> {
+        match values.get(&pat_value) {
+            Some(value) => self.value(*value),
+            None => self.unsupported("pattern value bound in the pattern"),
+        }
+    }
+
+    fn single(&self, expr: Expression) -> Result<SingleExpression, PrintError> {
+        let mut exprs = expr.0;
+        if exprs.len() != 1 {
+            return self.unsupported("value list");
+        }
+        Ok(exprs.pop().unwrap().0)
+    }
+
+    fn values(&mut self, values: &[Value]) -> Result<Vec<Expression>, PrintError> {
+        values.iter().map(|v| self.value(*v)).collect()
+    }
+
+    fn value(&mut self, value: Value) -> Result<Expression, PrintError> {
+        match self.fun.value_kind(value) {
+            ValueKind::Argument(_, _) => Ok(var_expr(self.var(value))),
+            ValueKind::Block(block) => {
+                if !self.tree.functions.contains_key(&block) {
+                    return self.unsupported("block used as a value");
+                }
+                let name = self.block_name(block);
+                Ok(Expression::single(SingleExpression::FunctionName(name)))
+            }
+            ValueKind::Const(cons) => self.const_expr(cons),
+            ValueKind::PrimOp(prim) => self.primop(prim),
+        }
+    }
+
+    fn primop(&mut self, prim: PrimOp) -> Result<Expression, PrintError> {
+        let fun = self.fun;
+        let reads = fun.primop_reads(prim);
+
+        let expr = match fun.primop_kind(prim) {
+            PrimOpKind::Tuple => SingleExpression::Tuple(self.values(reads)?),
+            PrimOpKind::ListCell => {
+                let head = self.value(reads[0])?;
+                let tail = self.value(reads[1])?;
+                list_expr(head, tail)
+            }
+            PrimOpKind::Map => {
+                let mut entries = Vec::with_capacity(reads.len() / 2);
+                for pair in reads.chunks(2) {
+                    let key = self.value(pair[0])?;
+                    let value = self.value(pair[1])?;
+                    entries.push(Annotated::empty((key, MapExactAssoc::Assoc, value)));
+                }
+                SingleExpression::Map(entries, None)
+            }
+            PrimOpKind::ValueList => return Ok(values(self.values(reads)?)),
+            PrimOpKind::BinOp(op) => {
+                let name = match op {
+                    BinOp::Equal => "==",
+                    BinOp::NotEqual => "/=",
+                    BinOp::LessEqual => "=<",
+                    BinOp::Less => "<",
+                    BinOp::GreaterEqual => ">=",
+                    BinOp::Greater => ">",
+                    BinOp::ExactEqual => "=:=",
+                    BinOp::ExactNotEqual => "=/=",
+                };
+                return Ok(call_erlang(name, self.values(reads)?));
+            }
+            PrimOpKind::LogicOp(op) => {
+                let args = self.values(reads)?;
+                return Ok(match op {
+                    LogicOp::And => fold_bool("and", "true", args),
+                    LogicOp::Or => fold_bool("or", "false", args),
+                    LogicOp::Eq => {
+                        let mut checks = Vec::new();
+                        for arg in args.iter().skip(1) {
+                            checks.push(call_erlang("=:=", vec![args[0].clone(), arg.clone()]));
+                        }
+                        fold_bool("and", "true", checks)
+                    }
+                });
+            }
+            PrimOpKind::IsType(typ) => {
+                let value = self.value(reads[0])?;
+                return self.type_test(value, *typ, false);
+            }
+            PrimOpKind::CaptureFunction | PrimOpKind::CaptureLocalFunction => {
+                match self.const_capture(prim) {
+                    Some((Some(module), name, arity)) if module != self.module => {
+                        SingleExpression::ExternalFunctionName {
+                            module,
+                            name: FunctionName { name, arity },
+                        }
+                    }
+                    Some((_, name, arity)) => {
+                        SingleExpression::FunctionName(FunctionName { name, arity })
+                    }
+                    None if reads.len() == 3 => {
+                        return Ok(call_erlang("make_fun", self.values(reads)?));
+                    }
+                    None => return self.unsupported("dynamic local function capture"),
+                }
+            }
+            kind => return self.unsupported(format!("primop {:?}", kind)),
+        };
+
+        Ok(Expression::single(expr))
+    }
+
+    /// A boolean expression testing the type of a value. Outside of guards,
+    /// the test must not fail.
+    fn type_test(
+        &mut self,
+        value: Expression,
+        typ: BasicType,
+        guard: bool,
+    ) -> Result<Expression, PrintError> {
+        let is = |name: &str| call_erlang(name, vec![value.clone()]);
+        Ok(match typ {
+            BasicType::List => is("is_list"),
+            BasicType::ListCell => call_erlang(
+                "and",
+                vec![
+                    is("is_list"),
+                    call_erlang("=/=", vec![value.clone(), Expression::nil()]),
+                ],
+            ),
+            BasicType::Nil => call_erlang("=:=", vec![value.clone(), Expression::nil()]),
+            BasicType::Tuple(arity) if guard => call_erlang(
+                "and",
+                vec![
+                    is("is_tuple"),
+                    call_erlang("=:=", vec![is("tuple_size"), int(arity)]),
+                ],
+            ),
+            BasicType::Tuple(arity) => {
+                let elems = (0..arity)
+                    .map(|_| Annotated::empty(bind_var(self.fresh())))
+                    .collect();
+                Expression::single(SingleExpression::Case {
+                    val: Box::new(value.clone()),
+                    clauses: vec![
+                        clause(vec![Pattern::Tuple(elems)], atom("true"), atom("true")),
+                        clause(vec![bind_var(self.fresh())], atom("true"), atom("false")),
+                    ],
+                })
+            }
+            BasicType::Map => is("is_map"),
+            BasicType::Number => is("is_number"),
+            BasicType::Float => is("is_float"),
+            BasicType::Integer => is("is_integer"),
+            BasicType::SmallInteger | BasicType::BigInteger => {
+                return self.unsupported(format!("type test {:?}", typ));
+            }
+        })
+    }
+
+    fn const_expr(&self, cons: Const) -> Result<Expression, PrintError> {
+        let container = self.fun.cons();
+        let expr = match container.const_kind(cons) {
+            ConstKind::Atomic(AtomicTerm::Binary(bin)) => {
+                let segments = binary_segments(bin.value())
+                    .into_iter()
+                    .map(|(value, args)| {
+                        let args = args.into_iter().map(Expression::single).collect();
+                        (int(value), args)
+                    })
+                    .collect();
+                SingleExpression::Binary(segments)
+            }
+            ConstKind::Atomic(atomic) => SingleExpression::AtomicLiteral(atomic_literal(atomic)),
+            ConstKind::ListCell { head, tail } => {
+                list_expr(self.const_expr(*head)?, self.const_expr(*tail)?)
+            }
+            ConstKind::Tuple { entries } => {
+                let mut elems = Vec::new();
+                for entry in entries.as_slice(&container.const_pool).iter() {
+                    elems.push(self.const_expr(*entry)?);
+                }
+                SingleExpression::Tuple(elems)
+            }
+            ConstKind::Map { keys, values } => {
+                let keys = keys.as_slice(&container.const_pool);
+                let values = values.as_slice(&container.const_pool);
+                let mut entries = Vec::new();
+                for (key, value) in keys.iter().zip(values.iter()) {
+                    let entry = (
+                        self.const_expr(*key)?,
+                        MapExactAssoc::Assoc,
+                        self.const_expr(*value)?,
+                    );
+                    entries.push(Annotated::empty(entry));
+                }
+                SingleExpression::Map(entries, None)
+            }
+        };
+        Ok(Expression::single(expr))
+    }
+
+    fn const_pattern(&self, cons: Const) -> Result<Pattern, PrintError> {
+        let container = self.fun.cons();
+        Ok(match container.const_kind(cons) {
+            ConstKind::Atomic(AtomicTerm::Binary(bin)) => {
+                let segments = binary_segments(bin.value())
+                    .into_iter()
+                    .map(|(value, args)| {
+                        let value = Pattern::Atomic(AtomicLiteral::Integer(value.into()));
+                        let args = args.into_iter().map(Annotated::empty).collect();
+                        (Annotated::empty(value), args)
+                    })
+                    .collect();
+                Pattern::Binary(segments)
+            }
+            ConstKind::Atomic(atomic) => Pattern::Atomic(atomic_literal(atomic)),
+            ConstKind::ListCell { head, tail } => {
+                list_pattern(self.const_pattern(*head)?, self.const_pattern(*tail)?)
+            }
+            ConstKind::Tuple { entries } => {
+                let mut elems = Vec::new();
+                for entry in entries.as_slice(&container.const_pool).iter() {
+                    elems.push(Annotated::empty(self.const_pattern(*entry)?));
+                }
+                Pattern::Tuple(elems)
+            }
+            ConstKind::Map { .. } => return self.unsupported("map constant in a pattern"),
+        })
+    }
+}
+
+fn atomic_literal(atomic: &AtomicTerm) -> AtomicLiteral {
+    match atomic {
+        AtomicTerm::Int(int) => AtomicLiteral::Integer(int.value().into()),
+        AtomicTerm::BigInt(int) => AtomicLiteral::Integer(int.value().clone()),
+        AtomicTerm::Float(float) => AtomicLiteral::Float(float.value()),
+        AtomicTerm::Atom(atom) => AtomicLiteral::Atom(atom.0),
+        AtomicTerm::Nil => AtomicLiteral::Nil,
+        AtomicTerm::Binary(_) => unreachable!(),
+    }
+}
+
+/// The segments of a constant binary, one per byte. Any trailing bits are
+/// a final, shorter segment.
+fn binary_segments(bin: &BitVec) -> Vec<(u8, Vec<SingleExpression>)> {
+    let bits = bin.bit_len();
+    let bytes = bin.as_ref();
+
+    let segment = |value: u8, size: usize| {
+        let flags = SingleExpression::List {
+            head: vec![atom("unsigned"), atom("big")],
+            tail: Box::new(Expression::nil()),
+        };
+        let args = vec![
+            SingleExpression::AtomicLiteral(AtomicLiteral::Integer(size.into())),
+            SingleExpression::AtomicLiteral(AtomicLiteral::Integer(1.into())),
+            SingleExpression::AtomicLiteral(atom_literal("integer")),
+            flags,
+        ];
+        (value, args)
+    };
+
+    let mut segments: Vec<_> = bytes[..bits / 8].iter().map(|b| segment(*b, 8)).collect();
+    let rem = bits % 8;
+    if rem != 0 {
+        segments.push(segment(bytes[bits / 8] >> (8 - rem), rem));
+    }
+    segments
+}
+
+fn annotate(vars: Vec<Variable>) -> Vec<Annotated<Variable>> {
+    vars.into_iter().map(Annotated::empty).collect()
+}
+
+fn atom_literal(name: &str) -> AtomicLiteral {
+    AtomicLiteral::Atom(Symbol::intern(name))
+}
+
+fn atom(name: &str) -> Expression {
+    atom_expr(Symbol::intern(name))
+}
+
+fn atom_expr(atom: Symbol) -> Expression {
+    Expression::single(SingleExpression::AtomicLiteral(AtomicLiteral::Atom(atom)))
+}
+
+fn int<I: Into<BigInt>>(value: I) -> Expression {
+    Expression::single(SingleExpression::AtomicLiteral(AtomicLiteral::Integer(
+        value.into(),
+    )))
+}
+
+fn var_expr(var: Variable) -> Expression {
+    Expression::single(SingleExpression::Variable(var))
+}
+
+fn bind_var(var: Variable) -> Pattern {
+    Pattern::BindVar(
+        Annotated::empty(var),
+        Box::new(Annotated::empty(Pattern::Wildcard)),
+    )
+}
+
+/// Several values as one expression, a value list unless there is exactly
+/// one.
+fn values(exprs: Vec<Expression>) -> Expression {
+    if exprs.len() == 1 {
+        exprs.into_iter().next().unwrap()
+    } else {
+        Annotated::empty(exprs.into_iter().flat_map(|e| e.0).collect())
+    }
+}
+
+fn let_(vars: Vec<Variable>, val: Expression, body: Expression) -> Expression {
+    if vars.is_empty() {
+        return body;
+    }
+    Expression::single(SingleExpression::Let {
+        vars: annotate(vars),
+        val: Box::new(val),
+        body: Box::new(body),
+    })
+}
+
+fn clause(patterns: Vec<Pattern>, guard: Expression, body: Expression) -> Annotated<CaseClause> {
+    Annotated::empty(CaseClause {
+        patterns: patterns.into_iter().map(Annotated::empty).collect(),
+        guard,
+        body,
+    })
+}
+
+fn call_erlang(name: &str, args: Vec<Expression>) -> Expression {
+    Expression::single(SingleExpression::InterModuleCall {
+        module: Box::new(atom("erlang")),
+        name: Box::new(atom(name)),
+        args,
+    })
+}
+
+fn primop(name: &str, args: Vec<Expression>) -> Expression {
+    Expression::single(SingleExpression::PrimOpCall(PrimOpCall {
+        name: Annotated::empty(Symbol::intern(name)),
+        args,
+    }))
+}
+
+fn apply(name: FunctionName, args: Vec<Expression>) -> Expression {
+    Expression::single(SingleExpression::ApplyCall {
+        fun: Box::new(Expression::single(SingleExpression::FunctionName(name))),
+        args,
+    })
+}
+
+/// Raises an exception from `(class, reason, trace)`. A freshly captured
+/// trace is raised through the matching BIF, which captures it again.
+fn raise(args: Vec<Expression>, fresh_trace: bool) -> Expression {
+    let mut args = args;
+    if !fresh_trace {
+        return primop("raw_raise", args);
+    }
+
+    let bif = match &args[0].0[..] {
+        [Annotated(SingleExpression::AtomicLiteral(AtomicLiteral::Atom(class)), _)] => {
+            match &*class.as_str() {
+                "error" => Some("error"),
+                "exit" => Some("exit"),
+                "throw" => Some("throw"),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    match bif {
+        Some(bif) => call_erlang(bif, vec![args.swap_remove(1)]),
+        None => {
+            args[2] = Expression::nil();
+            call_erlang("raise", args)
+        }
+    }
+}
+
+/// Combines boolean expressions with a strict operator.
+fn fold_bool(op: &str, empty: &str, args: Vec<Expression>) -> Expression {
+    args.into_iter()
+        .rev()
+        .fold(None, |acc, arg| match acc {
+            None => Some(arg),
+            Some(acc) => Some(call_erlang(op, vec![arg, acc])),
+        })
+        .unwrap_or_else(|| atom(empty))
+}
+
+fn list_expr(head: Expression, tail: Expression) -> SingleExpression {
+    let mut tail = tail;
+    if let [Annotated(SingleExpression::List { .. }, _)] = &tail.0[..] {
+        if let SingleExpression::List {
+            head: mut rest,
+            tail,
+        } = tail.0.pop().unwrap().0
+        {
+            rest.insert(0, head);
+            return SingleExpression::List { head: rest, tail };
+        }
+    }
+    SingleExpression::List {
+        head: vec![head],
+        tail: Box::new(tail),
+    }
+}
+
+fn list_pattern(head: Pattern, tail: Pattern) -> Pattern {
+    match tail {
+        Pattern::List(mut rest, tail) => {
+            rest.insert(0, Annotated::empty(head));
+            Pattern::List(rest, tail)
+        }
+        tail => Pattern::List(
+            vec![Annotated::empty(head)],
+            Box::new(Annotated::empty(tail)),
+        ),
+    }
+}
@@ -1,14 +1,7 @@
-use regex::Regex;
 use std::str::CharIndices;
-use lazy_static::lazy_static;
-
-lazy_static! {
-    static ref TEST: Regex = Regex::new("...").unwrap();
-}
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum Tok<'input> {
-
     // Keywords
     Module,
     Attributes,
@@ -65,7 +58,7 @@ pub enum Tok<'input> {
     HashRocket,
 }
 
-const KEYWORDS: &'static [(&'static str, Tok<'static>)] = &[
+const KEYWORDS: &[(&str, Tok<'static>)] = &[
     ("module", Tok::Module),
     ("attributes", Tok::Attributes),
     ("fun", Tok::Fun),
@@ -87,36 +80,86 @@ const KEYWORDS: &'static [(&'static str, Tok<'static>)] = &[
 ];
 
 fn is_escapechar(c: char) -> bool {
-    c == 'b' || c == 'd' || c == 'e'
-        || c == 'f' || c == 'n' || c == 'r'
-        || c == 's' || c == 't' || c == 'v'
-        || c == '"' || c == '\'' || c == '\\'
+    c == 'b'
+        || c == 'd'
+        || c == 'e'
+        || c == 'f'
+        || c == 'n'
+        || c == 'r'
+        || c == 's'
+        || c == 't'
+        || c == 'v'
+        || c == '"'
+        || c == '\''
+        || c == '\\'
 }
 fn is_control(c: char) -> bool {
-    c >= '\u{0000}' && c <= '\u{001f}'
+    ('\u{0000}'..='\u{001f}').contains(&c)
 }
 fn is_inputchar(c: char) -> bool {
     c != '\n' && c != '\r'
 }
 fn is_digit(c: char) -> bool {
-    c >= '0' && c <= '9'
+    ('0'..='9').contains(&c)
 }
 fn is_octal(c: char) -> bool {
-    c >= '0' && c <= '7'
+    ('0'..='7').contains(&c)
 }
 fn is_uppercase(c: char) -> bool {
-    (c >= 'A' && c <= 'Z')
-        || (c >= '\u{00c0}' && c <= '\u{00d6}')
-        || (c >= '\u{00d8}' && c <= '\u{00de}')
+    ('A'..='Z').contains(&c)
+        || ('\u{00c0}'..='\u{00d6}').contains(&c)
+        || ('\u{00d8}'..='\u{00de}').contains(&c)
 }
 fn is_lowercase(c: char) -> bool {
-    (c >= 'a' && c <= 'z')
-        || (c >= '\u{00df}' && c <= '\u{00f6}')
-        || (c >= '\u{00f8}' && c <= '\u{00ff}')
+    ('a'..='z').contains(&c)
+        || ('\u{00df}'..='\u{00f6}').contains(&c)
+        || ('\u{00f8}'..='\u{00ff}').contains(&c)
 }
 fn is_namechar(c: char) -> bool {
-    is_uppercase(c) || is_lowercase(c) || is_digit(c)
-        || (c == '@') || (c == '_')
+    is_uppercase(c) || is_lowercase(c) || is_digit(c) || (c == '@') || (c == '_')
+}
+
+/// Resolves the escape sequences in the text of an atom or a string.
+/// The text is assumed to have been accepted by the tokenizer.
+pub fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next().unwrap() {
+            'b' => out.push('\u{0008}'),
+            'd' => out.push('\u{007f}'),
+            'e' => out.push('\u{001b}'),
+            'f' => out.push('\u{000c}'),
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            's' => out.push(' '),
+            't' => out.push('\t'),
+            'v' => out.push('\u{000b}'),
+            '^' => {
+                let ctrl = chars.next().unwrap() as u32 & 0x1f;
+                out.push(std::char::from_u32(ctrl).unwrap());
+            }
+            c if is_octal(c) => {
+                let mut num = c.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek() {
+                        Some(c) if is_octal(*c) => {
+                            num = num * 8 + c.to_digit(8).unwrap();
+                            chars.next();
+                        }
+                        _ => break,
+                    }
+                }
+                out.push(std::char::from_u32(num).unwrap());
+            }
+            c => out.push(c),
+        }
+    }
+    out
 }
 
 pub struct Tokenizer<'input> {
@@ -126,10 +169,9 @@ pub struct Tokenizer<'input> {
 }
 
 impl<'input> Tokenizer<'input> {
-
     pub fn new(text: &'input str) -> Self {
         let mut t = Tokenizer {
-            text: text,
+            text,
             chars: text.char_indices(),
             next: None,
         };
@@ -144,28 +186,22 @@ impl<'input> Tokenizer<'input> {
 
     fn bump_escape(&mut self) -> Result<(), ()> {
         match self.bump() {
-            Some((_idx0, '^')) => {
-                match self.bump() {
-                    Some((_idx1, c)) if c >= '\u{0040}' && c <= '\u{005f}' => {
-                        self.bump();
-                        Ok(())
-                    }
-                    _ => Err(()),
+            Some((_idx0, '^')) => match self.bump() {
+                Some((_idx1, c)) if ('\u{0040}'..='\u{005f}').contains(&c) => {
+                    self.bump();
+                    Ok(())
                 }
-            }
-            Some((_idx0, c)) if is_octal(c) => {
-                match self.bump() {
+                _ => Err(()),
+            },
+            Some((_idx0, c)) if is_octal(c) => match self.bump() {
+                Some((_idx1, c)) if is_octal(c) => match self.bump() {
                     Some((_idx1, c)) if is_octal(c) => {
-                        match self.bump() {
-                            Some((_idx1, c)) if is_octal(c) => {
-                                self.bump();
-                                Ok(())
-                            }
-                            _ => Ok(()),
-                        }
+                        self.bump();
+                        Ok(())
                     }
                     _ => Ok(()),
-                }
+                },
+                _ => Ok(()),
             },
             Some((_idx0, c)) if is_escapechar(c) => {
                 self.bump();
@@ -175,33 +211,63 @@ impl<'input> Tokenizer<'input> {
         }
     }
 
-    fn take_while<F>(&mut self, fun: F) -> &'input str where F: Fn(char) -> bool {
-        let start = self.next.unwrap().0;
-        let mut end = self.next.unwrap().0;
-        loop {
-            match self.next {
-                Some((idx0, c)) if fun(c) => {
-                    end = idx0;
-                    self.bump();
-                }
-                Some((idx0, _c)) => {
-                    end = idx0;
-                    break;
-                }
-                None => {
-                    end += 1; // TODO: Very wrong for unicode
-                    break;
-                }
+    /// The offset of the next character.
+    fn pos(&self) -> usize {
+        self.next
+            .map(|(idx, _)| idx)
+            .unwrap_or_else(|| self.text.len())
+    }
+
+    fn take_while<F>(&mut self, fun: F) -> &'input str
+    where
+        F: Fn(char) -> bool,
+    {
+        let start = self.pos();
+        while let Some((_, c)) = self.next {
+            if !fun(c) {
+                break;
             }
+            self.bump();
+        }
+        &self.text[start..self.pos()]
+    }
+
+    /// Lexes an integer or a float. `start` is the start of the token,
+    /// including any sign, the next character is the first digit.
+    fn number(
+        &mut self,
+        start: usize,
+        sign: bool,
+    ) -> Option<Result<(usize, Tok<'input>, usize), ()>> {
+        let digits = self.take_while(is_digit);
+
+        // A float needs digits on both sides of the dot.
+        let fraction = match (self.next, self.chars.clone().next()) {
+            (Some((_, '.')), Some((_, c))) => is_digit(c),
+            _ => false,
+        };
+        if !fraction {
+            return Some(Ok((start, Tok::Integer((sign, digits)), self.pos())));
         }
 
-        return &self.text[start..end];
+        self.bump();
+        self.take_while(is_digit);
+        if let Some((_, 'e')) | Some((_, 'E')) = self.next {
+            self.bump();
+            if let Some((_, '+')) | Some((_, '-')) = self.next {
+                self.bump();
+            }
+            if self.take_while(is_digit).is_empty() {
+                return Some(Err(()));
+            }
+        }
+        let end = self.pos();
+        Some(Ok((start, Tok::Float(&self.text[start..end]), end)))
     }
 
     fn next_token(&mut self) -> Option<Result<(usize, Tok<'input>, usize), ()>> {
         'outer: loop {
             return match self.next {
-
                 // Keywords and variables
                 Some((idx0, c)) if is_namechar(c) && !is_digit(c) => {
                     let mut end = idx0;
@@ -224,9 +290,7 @@ impl<'input> Tokenizer<'input> {
                     let word = &self.text[idx0..end];
 
                     // Check for keywords
-                    let kw = KEYWORDS.iter()
-                        .filter(|&&(w, _)| w == word)
-                        .next();
+                    let kw = KEYWORDS.iter().filter(|&&(w, _)| w == word).next();
                     if let Some((_, kw)) = kw {
                         return Some(Ok((idx0, kw.clone(), end)));
                     }
@@ -245,12 +309,10 @@ impl<'input> Tokenizer<'input> {
                     let end;
                     loop {
                         match self.next {
-                            Some((_idx1, '\\')) => {
-                                match self.bump_escape() {
-                                    Ok(()) => (),
-                                    Err(()) => return Some(Err(())),
-                                }
-                            }
+                            Some((_idx1, '\\')) => match self.bump_escape() {
+                                Ok(()) => (),
+                                Err(()) => return Some(Err(())),
+                            },
                             Some((idx1, '\'')) => {
                                 self.bump();
                                 end = idx1 + 1; // TODO: Very very wrong for unicode
@@ -264,189 +326,155 @@ impl<'input> Tokenizer<'input> {
                         }
                     }
 
-                    let atom = &self.text[idx0+1..end-1];
+                    let atom = &self.text[idx0 + 1..end - 1];
                     Some(Ok((idx0, Tok::Atom(atom), end)))
                 }
 
                 // Numbers
-                Some((idx0, c)) if is_digit(c) => {
-                    let string = self.take_while(is_digit);
-                    Some(Ok((idx0, Tok::Integer((true, string)), idx0+1)))
-                }
-                Some((idx0, '+')) => {
-                    match self.bump() {
-                        Some((idx1, c)) if is_digit(c) => {
-                            let string = self.take_while(is_digit);
-                            Some(Ok((idx0, Tok::Integer((true, string)), idx1+1)))
-                        }
-                        _ => return Some(Err(())),
-                    }
-                }
+                Some((idx0, c)) if is_digit(c) => self.number(idx0, true),
+                Some((idx0, '+')) => match self.bump() {
+                    Some((_idx1, c)) if is_digit(c) => self.number(idx0, true),
+                    _ => Some(Err(())),
+                },
 
                 // Symbols
                 Some((idx0, '(')) => {
                     self.bump();
-                    Some(Ok((idx0, Tok::ParenOpen, idx0+1)))
+                    Some(Ok((idx0, Tok::ParenOpen, idx0 + 1)))
                 }
                 Some((idx0, ')')) => {
                     self.bump();
-                    Some(Ok((idx0, Tok::ParenClose, idx0+1)))
+                    Some(Ok((idx0, Tok::ParenClose, idx0 + 1)))
                 }
                 Some((idx0, '{')) => {
                     self.bump();
-                    Some(Ok((idx0, Tok::CurlyOpen, idx0+1)))
+                    Some(Ok((idx0, Tok::CurlyOpen, idx0 + 1)))
                 }
-                Some((idx0, '~')) => {
-                    match self.bump() {
-                        Some((idx1, '{')) => {
-                            self.bump();
-                            Some(Ok((idx0, Tok::MapOpen, idx1+1)))
-                        }
-                        _ => return Some(Err(())),
+                Some((idx0, '~')) => match self.bump() {
+                    Some((idx1, '{')) => {
+                        self.bump();
+                        Some(Ok((idx0, Tok::MapOpen, idx1 + 1)))
                     }
-                }
-                Some((idx0, '}')) => {
-                    match self.bump() {
-                        Some((idx1, '#')) => {
-                            self.bump();
-                            Some(Ok((idx0, Tok::BitstringClose, idx1+1)))
-                        }
-                        Some((idx1, '~')) => {
-                            self.bump();
-                            Some(Ok((idx0, Tok::MapClose, idx1+1)))
-                        }
-                        _ => {
-                            Some(Ok((idx0, Tok::CurlyClose, idx0+1)))
-                        }
+                    _ => return Some(Err(())),
+                },
+                Some((idx0, '}')) => match self.bump() {
+                    Some((idx1, '#')) => {
+                        self.bump();
+                        Some(Ok((idx0, Tok::BitstringClose, idx1 + 1)))
                     }
-                }
+                    Some((idx1, '~')) => {
+                        self.bump();
+                        Some(Ok((idx0, Tok::MapClose, idx1 + 1)))
+                    }
+                    _ => Some(Ok((idx0, Tok::CurlyClose, idx0 + 1))),
+                },
                 Some((idx0, '[')) => {
                     self.bump();
-                    Some(Ok((idx0, Tok::SquareOpen, idx0+1)))
+                    Some(Ok((idx0, Tok::SquareOpen, idx0 + 1)))
                 }
                 Some((idx0, ']')) => {
                     self.bump();
-                    Some(Ok((idx0, Tok::SquareClose, idx0+1)))
+                    Some(Ok((idx0, Tok::SquareClose, idx0 + 1)))
                 }
-                Some((idx0, '#')) => {
-                    match self.bump() {
-                        Some((idx1, '{')) => {
-                            self.bump();
-                            Some(Ok((idx0, Tok::BitstringOpen, idx1+1)))
-                        }
-                        Some((idx1, '<')) => {
-                            self.bump();
-                            Some(Ok((idx0, Tok::BitstringPatternOpen, idx1+1)))
-                        }
-                        _ => Some(Err(())),
+                Some((idx0, '#')) => match self.bump() {
+                    Some((idx1, '{')) => {
+                        self.bump();
+                        Some(Ok((idx0, Tok::BitstringOpen, idx1 + 1)))
                     }
-                }
-                Some((idx0, '>')) => {
-                    match self.bump() {
-                        Some((idx1, '(')) => {
-                            self.bump();
-                            Some(Ok((idx0, Tok::BitstringPatternSep, idx1+1)))
-                        }
-                        _ => Some(Ok((idx0, Tok::TriClose, idx0+1))),
+                    Some((idx1, '<')) => {
+                        self.bump();
+                        Some(Ok((idx0, Tok::BitstringPatternOpen, idx1 + 1)))
                     }
-                }
+                    _ => Some(Err(())),
+                },
+                Some((idx0, '>')) => match self.bump() {
+                    Some((idx1, '(')) => {
+                        self.bump();
+                        Some(Ok((idx0, Tok::BitstringPatternSep, idx1 + 1)))
+                    }
+                    _ => Some(Ok((idx0, Tok::TriClose, idx0 + 1))),
+                },
                 Some((idx0, '<')) => {
                     self.bump();
-                    Some(Ok((idx0, Tok::TriOpen, idx0+1)))
+                    Some(Ok((idx0, Tok::TriOpen, idx0 + 1)))
                 }
-                Some((idx0, ':')) => {
-                    match self.bump() {
-                        Some((idx1, '=')) => {
-                            self.bump();
-                            Some(Ok((idx0, Tok::MapMatch, idx1+1)))
-                        }
-                        _ => Some(Ok((idx0, Tok::Colon, idx0+1)))
+                Some((idx0, ':')) => match self.bump() {
+                    Some((idx1, '=')) => {
+                        self.bump();
+                        Some(Ok((idx0, Tok::MapMatch, idx1 + 1)))
                     }
-                }
-                Some((idx0, '-')) => {
-                    match self.bump() {
-                        Some((idx1, '|')) => {
-                            self.bump();
-                            Some(Ok((idx0, Tok::Annotation, idx1+1)))
-                        }
-                        Some((idx1, '>')) => {
-                            self.bump();
-                            Some(Ok((idx0, Tok::Arrow, idx1+1)))
-                        }
-                        Some((idx1, c)) if is_digit(c) => {
-                            let string = self.take_while(is_digit);
-                            Some(Ok((idx0, Tok::Integer((false, string)), idx1+1)))
-                        }
-                        _ => Some(Err(())),
+                    _ => Some(Ok((idx0, Tok::Colon, idx0 + 1))),
+                },
+                Some((idx0, '-')) => match self.bump() {
+                    Some((idx1, '|')) => {
+                        self.bump();
+                        Some(Ok((idx0, Tok::Annotation, idx1 + 1)))
                     }
-                }
+                    Some((idx1, '>')) => {
+                        self.bump();
+                        Some(Ok((idx0, Tok::Arrow, idx1 + 1)))
+                    }
+                    Some((_idx1, c)) if is_digit(c) => self.number(idx0, false),
+                    _ => Some(Err(())),
+                },
                 Some((idx0, ',')) => {
                     self.bump();
-                    Some(Ok((idx0, Tok::Comma, idx0+1)))
+                    Some(Ok((idx0, Tok::Comma, idx0 + 1)))
                 }
                 Some((idx0, '/')) => {
                     self.bump();
-                    Some(Ok((idx0, Tok::ForwardSlash, idx0+1)))
+                    Some(Ok((idx0, Tok::ForwardSlash, idx0 + 1)))
                 }
-                Some((idx0, '=')) => {
-                    match self.bump() {
-                        Some((idx1, '>')) => {
-                            self.bump();
-                            Some(Ok((idx0, Tok::HashRocket, idx1+1)))
-                        }
-                        _ => Some(Ok((idx0, Tok::Equals, idx0+1))),
+                Some((idx0, '=')) => match self.bump() {
+                    Some((idx1, '>')) => {
+                        self.bump();
+                        Some(Ok((idx0, Tok::HashRocket, idx1 + 1)))
                     }
-                }
+                    _ => Some(Ok((idx0, Tok::Equals, idx0 + 1))),
+                },
                 Some((idx0, '|')) => {
                     self.bump();
-                    Some(Ok((idx0, Tok::Pipe, idx0+1)))
+                    Some(Ok((idx0, Tok::Pipe, idx0 + 1)))
                 }
 
                 // Supressed
-                Some((_idx0, '%')) => {
-                    loop {
-                        match self.bump() {
-                            Some((_idx1, '\n')) => {
-                                continue 'outer;
-                            }
-                            Some((_idx1, '\r')) => {
-                                continue 'outer;
-                            }
-                            _ => (),
+                Some((_idx0, '%')) => loop {
+                    match self.bump() {
+                        Some((_idx1, '\n')) => {
+                            continue 'outer;
                         }
-                    }
-                }
-                Some((_idx0, c)) if c == ' ' || c == '\t' => {
-                    loop {
-                        match self.bump() {
-                            Some((_idx1, ' ')) => (),
-                            Some((_idx1, '\t')) => (),
-                            _ => continue 'outer,
+                        Some((_idx1, '\r')) => {
+                            continue 'outer;
                         }
+                        _ => (),
                     }
-                }
+                },
+                Some((_idx0, c)) if c == ' ' || c == '\t' => loop {
+                    match self.bump() {
+                        Some((_idx1, ' ')) => (),
+                        Some((_idx1, '\t')) => (),
+                        _ => continue 'outer,
+                    }
+                },
 
                 Some((_idx1, '\n')) => {
                     self.bump();
                     continue 'outer;
                 }
-                Some((_idx1, '\r')) => {
-                    match self.bump() {
-                        Some((_idx2, '\n')) => {
-                            self.bump();
-                            continue 'outer;
-                        }
-                        _ => continue 'outer,
+                Some((_idx1, '\r')) => match self.bump() {
+                    Some((_idx2, '\n')) => {
+                        self.bump();
+                        continue 'outer;
                     }
-                }
+                    _ => continue 'outer,
+                },
 
                 None => None,
 
                 c => unimplemented!("{:?}", c),
-            }
+            };
         }
     }
-
 }
 
 impl<'input> Iterator for Tokenizer<'input> {
@@ -536,7 +564,7 @@ fn test_lex_compile_core_file() {
 
     let mut f = ::std::fs::File::open("../test_data/compile.core").unwrap();
     let mut s = String::new();
-    f.read_to_string(&mut s);
+    f.read_to_string(&mut s).unwrap();
 
     let mut tok = Tokenizer::new(&s);
 
@@ -544,19 +572,34 @@ fn test_lex_compile_core_file() {
         let res = tok.next_token();
         println!("{:?}", res);
         assert!(res != Some(Err(())));
-        if (res == None) {
+        if res == None {
             break;
         }
     }
 }
 
+#[test]
+fn test_numbers() {
+    let text = "12 -3 +4 1.5 -2.0e-3";
+    let tok = Tokenizer::new(&text);
+    let toks: Vec<_> = tok.map(|res| res.unwrap()).collect();
+
+    assert_eq!(
+        toks,
+        vec![
+            (0, Tok::Integer((true, "12")), 2),
+            (3, Tok::Integer((false, "3")), 5),
+            (6, Tok::Integer((true, "4")), 8),
+            (9, Tok::Float("1.5"), 12),
+            (13, Tok::Float("-2.0e-3"), 20),
+        ]
+    );
+}
 
-
-
-
-
-
-
-
-
-
+#[test]
+fn test_unescape() {
+    assert_eq!(unescape("abc"), "abc");
+    assert_eq!(unescape("\\'\\\\"), "'\\");
+    assert_eq!(unescape("\\5\\101\\1012"), "\u{5}AA2");
+    assert_eq!(unescape("\\n\\s\\^@\\^H"), "\n \u{0}\u{8}");
+}
@@ -1,8 +1,28 @@
-pub use eir::Atom;
-use eir::FunctionIdent;
+//! Core Erlang support.
+//!
+//! Contains a parser for Core Erlang source and a lowering from the parsed
+//! module to Eir, along with a printer that turns Eir back into Core
+//! Erlang. The printer makes it possible to inspect the output of the Eir
+//! passes with the standard Erlang tooling, and to compile it with `erlc`.
 
 mod lexer;
 mod parser;
-mod ast;
-mod lower_hir;
+pub use parser::{parse, ParseError};
 
+pub mod ast;
+
+mod printer;
+
+mod from_eir;
+pub use from_eir::{from_eir, PrintError};
+
+mod to_eir;
+pub use to_eir::{to_eir, LowerError};
+
+#[cfg(test)]
+mod tests;
+
+/// Prints an Eir module as Core Erlang source.
+pub fn print_module(module: &libeir_ir::Module) -> Result<String, PrintError> {
+    Ok(from_eir(module)?.to_text())
+}
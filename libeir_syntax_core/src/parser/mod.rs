@@ -1,11 +1,18 @@
-use crate::ast::{ Annotated, Module };
-use crate::lexer::{ Tokenizer, Tok };
+use crate::ast::{Annotated, Module};
+use crate::lexer::{Tok, Tokenizer};
 
-mod grammar;
+#[cfg_attr(rustfmt, rustfmt_skip)]
+#[allow(unknown_lints)]
+#[allow(clippy)]
+#[allow(unused_variables, dead_code, unused_imports, unused_parens)]
+pub(crate) mod grammar {
+    // The parser is generated into `OUT_DIR` by `build.rs`.
+    include!(concat!(env!("OUT_DIR"), "/parser/grammar.rs"));
+}
 
-pub fn parse<'input>(text: &'input str) -> Result<Annotated<Module>,
-                                                  ::lalrpop_util::ParseError<usize, Tok<'input>, ()>> {
+pub type ParseError<'input> = ::lalrpop_util::ParseError<usize, Tok<'input>, ()>;
 
+pub fn parse(text: &str) -> Result<Annotated<Module>, ParseError<'_>> {
     let tokenizer = Tokenizer::new(text);
     let parser = grammar::AnnotatedModuleParser::new();
     parser.parse(text, tokenizer)
@@ -0,0 +1,422 @@
+//! Core Erlang text, in a layout close to that of `erlc +to_core`.
+//!
+//! ```text
+//! module 'foo' ['bar'/1]
+//!     attributes []
+//! 'bar'/1 =
+//!     fun (_0) ->
+//!         call 'erlang':'+'(_0, 1)
+//! end
+//! ```
+//!
+//! The output parses back into an equal AST with `parse`.
+
+use std::fmt::{Display, Formatter, Result, Write};
+
+use crate::ast::{
+    Annotated, AtomicLiteral, CaseClause, Constant, Expression, Function, FunctionName,
+    MapExactAssoc, Module, Pattern, SingleExpression, Variable,
+};
+
+impl Module {
+    pub fn to_text(&self) -> String {
+        let mut printer = Printer { out: String::new() };
+        printer.module(self);
+        printer.out
+    }
+}
+
+impl Display for Module {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.write_str(&self.to_text())
+    }
+}
+
+struct Printer {
+    out: String,
+}
+
+impl Printer {
+    fn newline(&mut self, indent: usize) {
+        self.out.push('\n');
+        for _ in 0..indent {
+            self.out.push(' ');
+        }
+    }
+
+    fn comma_sep<T>(&mut self, items: &[T], mut fun: impl FnMut(&mut Self, &T)) {
+        for (idx, item) in items.iter().enumerate() {
+            if idx != 0 {
+                self.out.push_str(", ");
+            }
+            fun(self, item);
+        }
+    }
+
+    fn module(&mut self, module: &Module) {
+        self.out.push_str("module ");
+        self.atom(module.name);
+        self.out.push_str(" [");
+        self.comma_sep(&module.declarations, |p, name| p.function_name(name));
+        self.out.push(']');
+
+        self.newline(4);
+        self.out.push_str("attributes [");
+        self.comma_sep(&module.attributes, |p, (key, value)| {
+            p.atom(*key);
+            p.out.push_str(" = ");
+            p.constant(value);
+        });
+        self.out.push(']');
+
+        for def in module.definitions.iter() {
+            self.newline(0);
+            self.function_name(&def.name.0);
+            self.out.push_str(" =");
+            self.newline(4);
+            self.fun(&def.fun.0, 4);
+        }
+
+        self.newline(0);
+        self.out.push_str("end\n");
+    }
+
+    fn atom(&mut self, atom: libeir_intern::Symbol) {
+        self.out.push('\'');
+        self.escaped(&atom.as_str(), '\'');
+        self.out.push('\'');
+    }
+
+    fn escaped(&mut self, text: &str, quote: char) {
+        for c in text.chars() {
+            match c {
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\t' => self.out.push_str("\\t"),
+                c if c == quote => {
+                    self.out.push('\\');
+                    self.out.push(c);
+                }
+                c if (c as u32) < 0x20 || c as u32 == 0x7f => {
+                    write!(self.out, "\\{:03o}", c as u32).unwrap();
+                }
+                c => self.out.push(c),
+            }
+        }
+    }
+
+    fn var(&mut self, var: Variable) {
+        self.out.push_str(&var.as_str());
+    }
+
+    fn vars(&mut self, vars: &[Annotated<Variable>]) {
+        self.out.push('<');
+        self.comma_sep(vars, |p, var| p.var(var.0));
+        self.out.push('>');
+    }
+
+    fn function_name(&mut self, name: &FunctionName) {
+        self.atom(name.name);
+        write!(self.out, "/{}", name.arity).unwrap();
+    }
+
+    fn atomic(&mut self, literal: &AtomicLiteral) {
+        match literal {
+            AtomicLiteral::Integer(int) => write!(self.out, "{}", int).unwrap(),
+            AtomicLiteral::Float(float) => {
+                // A float needs a fractional part before any exponent.
+                let text = format!("{:?}", float);
+                match text.find('e') {
+                    Some(idx) if !text[..idx].contains('.') => {
+                        self.out.push_str(&text[..idx]);
+                        self.out.push_str(".0");
+                        self.out.push_str(&text[idx..]);
+                    }
+                    _ => self.out.push_str(&text),
+                }
+            }
+            AtomicLiteral::Atom(atom) => self.atom(*atom),
+            AtomicLiteral::Nil => self.out.push_str("[]"),
+            AtomicLiteral::Char(c) => {
+                self.out.push('$');
+                self.escaped(&c.to_string(), ' ');
+            }
+            AtomicLiteral::String(string) => {
+                self.out.push('"');
+                self.escaped(string, '"');
+                self.out.push('"');
+            }
+        }
+    }
+
+    fn constant(&mut self, constant: &Constant) {
+        match constant {
+            Constant::Atomic(literal) => self.atomic(literal),
+            Constant::Tuple(elems) => {
+                self.out.push('{');
+                self.comma_sep(elems, |p, elem| p.constant(elem));
+                self.out.push('}');
+            }
+            Constant::List(head, tail) => {
+                self.out.push('[');
+                self.comma_sep(head, |p, elem| p.constant(elem));
+                if **tail != Constant::Atomic(AtomicLiteral::Nil) {
+                    self.out.push('|');
+                    self.constant(tail);
+                }
+                self.out.push(']');
+            }
+        }
+    }
+
+    fn fun(&mut self, fun: &Function, indent: usize) {
+        self.out.push_str("fun (");
+        self.comma_sep(&fun.vars, |p, var| p.var(var.0));
+        self.out.push_str(") ->");
+        self.newline(indent + 4);
+        self.expr(&fun.body, indent + 4);
+    }
+
+    fn exprs(&mut self, exprs: &[Expression], indent: usize) {
+        self.comma_sep(exprs, |p, expr| p.expr(expr, indent));
+    }
+
+    fn expr(&mut self, expr: &Expression, indent: usize) {
+        if let [single] = &expr.0[..] {
+            self.single(&single.0, indent);
+        } else {
+            self.out.push('<');
+            self.comma_sep(&expr.0, |p, single| p.single(&single.0, indent));
+            self.out.push('>');
+        }
+    }
+
+    fn single(&mut self, expr: &SingleExpression, indent: usize) {
+        match expr {
+            SingleExpression::FunctionName(name) => self.function_name(name),
+            SingleExpression::ExternalFunctionName { module, name } => {
+                self.out.push_str("fun ");
+                self.atom(*module);
+                self.out.push(':');
+                self.function_name(name);
+            }
+            SingleExpression::Variable(var) => self.var(*var),
+
+            SingleExpression::Let { vars, val, body } => {
+                self.out.push_str("let ");
+                self.vars(vars);
+                self.out.push_str(" =");
+                self.newline(indent + 4);
+                self.expr(val, indent + 4);
+                self.newline(indent);
+                self.out.push_str("in");
+                self.newline(indent);
+                self.expr(body, indent);
+            }
+            SingleExpression::Catch(body) => {
+                self.out.push_str("catch");
+                self.newline(indent + 4);
+                self.expr(body, indent + 4);
+            }
+            SingleExpression::Case { val, clauses } => {
+                self.out.push_str("case ");
+                self.expr(val, indent + 4);
+                self.out.push_str(" of");
+                self.clauses(clauses, indent);
+                self.newline(indent);
+                self.out.push_str("end");
+            }
+            SingleExpression::Do(first, second) => {
+                self.out.push_str("do");
+                self.newline(indent + 4);
+                self.expr(first, indent + 4);
+                self.newline(indent + 4);
+                self.expr(second, indent + 4);
+            }
+            SingleExpression::Try {
+                body,
+                then_vars,
+                then,
+                catch_vars,
+                catch,
+            } => {
+                self.out.push_str("try");
+                self.newline(indent + 4);
+                self.expr(body, indent + 4);
+                self.newline(indent);
+                self.out.push_str("of ");
+                self.vars(then_vars);
+                self.out.push_str(" ->");
+                self.newline(indent + 4);
+                self.expr(then, indent + 4);
+                self.newline(indent);
+                self.out.push_str("catch ");
+                self.vars(catch_vars);
+                self.out.push_str(" ->");
+                self.newline(indent + 4);
+                self.expr(catch, indent + 4);
+            }
+            SingleExpression::Receive {
+                clauses,
+                timeout_time,
+                timeout_body,
+            } => {
+                self.out.push_str("receive");
+                self.clauses(clauses, indent);
+                self.newline(indent);
+                self.out.push_str("after ");
+                self.expr(timeout_time, indent + 4);
+                self.out.push_str(" ->");
+                self.newline(indent + 4);
+                self.expr(timeout_body, indent + 4);
+            }
+
+            SingleExpression::PrimOpCall(call) => {
+                self.out.push_str("primop ");
+                self.atom(call.name.0);
+                self.out.push('(');
+                self.exprs(&call.args, indent + 4);
+                self.out.push(')');
+            }
+            SingleExpression::ApplyCall { fun, args } => {
+                self.out.push_str("apply ");
+                self.expr(fun, indent + 4);
+                self.out.push('(');
+                self.exprs(args, indent + 4);
+                self.out.push(')');
+            }
+            SingleExpression::InterModuleCall { module, name, args } => {
+                self.out.push_str("call ");
+                self.expr(module, indent + 4);
+                self.out.push(':');
+                self.expr(name, indent + 4);
+                self.out.push('(');
+                self.exprs(args, indent + 4);
+                self.out.push(')');
+            }
+
+            SingleExpression::Fun(fun) => self.fun(fun, indent),
+            SingleExpression::LetRec { funs, body } => {
+                self.out.push_str("letrec");
+                for (name, fun) in funs.iter() {
+                    self.newline(indent + 4);
+                    self.function_name(name);
+                    self.out.push_str(" =");
+                    self.newline(indent + 8);
+                    self.fun(fun, indent + 8);
+                }
+                self.newline(indent);
+                self.out.push_str("in");
+                self.newline(indent);
+                self.expr(body, indent);
+            }
+
+            SingleExpression::AtomicLiteral(literal) => self.atomic(literal),
+            SingleExpression::Tuple(elems) => {
+                self.out.push('{');
+                self.exprs(elems, indent + 4);
+                self.out.push('}');
+            }
+            SingleExpression::List { head, tail } => {
+                self.out.push('[');
+                self.exprs(head, indent + 4);
+                if **tail != Expression::nil() {
+                    self.out.push('|');
+                    self.expr(tail, indent + 4);
+                }
+                self.out.push(']');
+            }
+            SingleExpression::Map(entries, base) => {
+                self.out.push_str("~{");
+                self.comma_sep(entries, |p, entry| {
+                    let (key, op, value) = &entry.0;
+                    p.expr(key, indent + 4);
+                    p.out.push_str(match op {
+                        MapExactAssoc::Exact => ":=",
+                        MapExactAssoc::Assoc => "=>",
+                    });
+                    p.expr(value, indent + 4);
+                });
+                if let Some(base) = base {
+                    self.out.push('|');
+                    self.expr(base, indent + 4);
+                }
+                self.out.push_str("}~");
+            }
+            SingleExpression::Binary(segments) => {
+                self.out.push_str("#{");
+                self.comma_sep(segments, |p, (value, args)| {
+                    p.out.push_str("#<");
+                    p.expr(value, indent + 4);
+                    p.out.push_str(">(");
+                    p.exprs(args, indent + 4);
+                    p.out.push(')');
+                });
+                self.out.push_str("}#");
+            }
+        }
+    }
+
+    fn clauses(&mut self, clauses: &[Annotated<CaseClause>], indent: usize) {
+        for clause in clauses.iter() {
+            let clause = &clause.0;
+            self.newline(indent + 2);
+            self.out.push('<');
+            self.comma_sep(&clause.patterns, |p, pat| p.pattern(&pat.0, indent + 4));
+            self.out.push_str("> when ");
+            self.expr(&clause.guard, indent + 8);
+            self.out.push_str(" ->");
+            self.newline(indent + 6);
+            self.expr(&clause.body, indent + 6);
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern, indent: usize) {
+        match pattern {
+            Pattern::Wildcard => self.out.push('_'),
+            Pattern::BindVar(var, inner) => {
+                self.var(var.0);
+                if inner.0 != Pattern::Wildcard {
+                    self.out.push_str(" = ");
+                    self.pattern(&inner.0, indent);
+                }
+            }
+            Pattern::Atomic(literal) => self.atomic(literal),
+            Pattern::Binary(segments) => {
+                self.out.push_str("#{");
+                self.comma_sep(segments, |p, (value, args)| {
+                    p.out.push_str("#<");
+                    p.pattern(&value.0, indent);
+                    p.out.push_str(">(");
+                    p.comma_sep(args, |p, arg| p.single(&arg.0, indent));
+                    p.out.push(')');
+                });
+                self.out.push_str("}#");
+            }
+            Pattern::Tuple(elems) => {
+                self.out.push('{');
+                self.comma_sep(elems, |p, elem| p.pattern(&elem.0, indent));
+                self.out.push('}');
+            }
+            Pattern::List(head, tail) => {
+                self.out.push('[');
+                self.comma_sep(head, |p, elem| p.pattern(&elem.0, indent));
+                if tail.0 != Pattern::Atomic(AtomicLiteral::Nil) {
+                    self.out.push('|');
+                    self.pattern(&tail.0, indent);
+                }
+                self.out.push(']');
+            }
+            Pattern::Map(entries) => {
+                self.out.push_str("~{");
+                self.comma_sep(entries, |p, entry| {
+                    let (key, value) = &entry.0;
+                    p.single(&key.0, indent);
+                    p.out.push_str(":=");
+                    p.pattern(&value.0, indent);
+                });
+                self.out.push_str("}~");
+            }
+        }
+    }
+}
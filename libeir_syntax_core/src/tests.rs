@@ -0,0 +1,187 @@
+use libeir_ir::parse_module_unwrap;
+
+use crate::{from_eir, parse, print_module, to_eir};
+
+fn print(text: &str) -> String {
+    let module = parse_module_unwrap(text);
+    let text = print_module(&module).unwrap();
+
+    // The output must parse back into the same AST.
+    let parsed = parse(&text).unwrap();
+    assert_eq!(parsed.0, from_eir(&module).unwrap());
+
+    // And lower back into valid Eir.
+    let lowered = to_eir(&parsed.0).unwrap();
+    for def in lowered.function_iter() {
+        let mut errors = Vec::new();
+        def.function().validate(&mut errors);
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    text
+}
+
+fn assert_contains(text: &str, expected: &str) {
+    let expected = expected.trim_start_matches('\n');
+    assert!(
+        text.contains(expected),
+        "expected:\n{}\nin:\n{}",
+        expected,
+        text
+    );
+}
+
+#[test]
+fn simple_return() {
+    let text = print(
+        "
+a'foo' {
+    a'id'/1 {
+        entry(%ret, %thr, %a):
+            %ret(%a);
+    }
+}
+",
+    );
+
+    assert!(text.starts_with("module 'foo' ['id'/1]\n    attributes []\n'id'/1 =\n"));
+    let arg = text
+        .split("fun (")
+        .nth(1)
+        .unwrap()
+        .split(')')
+        .next()
+        .unwrap();
+    assert_contains(&text, &format!("fun ({}) ->\n        {}\nend\n", arg, arg));
+}
+
+#[test]
+fn calls() {
+    let text = print(
+        "
+a'foo' {
+    a'local'/1 {
+        entry(%ret, %thr, %a):
+            a'foo':a'local'/1(%a) => %ret except %thr;
+    }
+    a'remote'/1 {
+        entry(%ret, %thr, %a):
+            a'lists':a'reverse'/1(%a) => next except %thr;
+        next(%r):
+            %ret({%a, %r});
+    }
+}
+",
+    );
+
+    assert_contains(&text, "apply 'local'/1(");
+    assert_contains(&text, "let <");
+    assert_contains(&text, "call 'lists':'reverse'(");
+}
+
+#[test]
+fn calls_with_handler() {
+    let text = print(
+        "
+a'foo' {
+    a'safe'/1 {
+        entry(%ret, %thr, %a):
+            a'lists':a'reverse'/1(%a) => %ret except handler;
+        handler(%c, %r, %t):
+            %ret(a'error');
+    }
+}
+",
+    );
+
+    assert_contains(&text, "try\n");
+    assert_contains(&text, "call 'lists':'reverse'(");
+    assert_contains(&text, "catch <");
+    assert_contains(&text, "'error'");
+}
+
+#[test]
+fn if_bool_and_shared_blocks() {
+    let text = print(
+        "
+a'foo' {
+    a'choose'/2 {
+        entry(%ret, %thr, %a, %b):
+            if_bool %a one two;
+        one():
+            join(%b);
+        two():
+            join(a'none');
+        join(%v):
+            %ret({%v});
+    }
+}
+",
+    );
+
+    assert_contains(&text, "letrec\n");
+    assert_contains(&text, "/1 =\n");
+    assert_contains(&text, "<'true'> when 'true' ->");
+    assert_contains(&text, "<'false'> when 'true' ->");
+    assert_contains(&text, "('none')");
+}
+
+#[test]
+fn closure() {
+    let text = print(
+        "
+a'foo' {
+    a'adder'/1 {
+        entry(%ret, %thr, %a):
+            %ret(inner);
+        inner(%iret, %ithr, %b):
+            a'erlang':a'+'/2(%a, %b) => %iret except %ithr;
+    }
+}
+",
+    );
+
+    assert_contains(&text, "fun (_");
+    assert_contains(&text, "call 'erlang':'+'(");
+}
+
+#[test]
+fn match_tuple() {
+    let text = print(
+        "
+a'foo' {
+    a'first'/1 {
+        entry(%ret, %thr, %a):
+            match %a {
+                {} arity 2 => tup;
+                _ => other;
+            };
+        tup(%x, %y):
+            %ret(%x);
+        other():
+            %ret(a'none');
+    }
+}
+",
+    );
+
+    assert_contains(&text, "case _");
+    assert_contains(&text, "<{_");
+    assert_contains(&text, "'none'");
+}
+
+#[test]
+fn constants() {
+    let text = print(
+        "
+a'foo' {
+    a'consts'/0 {
+        entry(%ret, %thr):
+            %ret({1, a'atom', [1, 2], []});
+    }
+}
+",
+    );
+
+    assert_contains(&text, "{1, 'atom', [1, 2], []}");
+}
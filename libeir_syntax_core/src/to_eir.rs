@@ -0,0 +1,904 @@
+//! Lowers Core Erlang to Eir.
+//!
+//! Every Core Erlang `fun` becomes a block taking the return and throw
+//! continuations followed by its arguments, the same calling convention as
+//! the function entry. Functions bound by `letrec` are lowered the same
+//! way, and are called like any other fun.
+//!
+//! * `case` and `receive` use the `Case` operation, with every guard
+//!   lowered into its own lambda. An exception in a guard makes the guard
+//!   fail.
+//! * `try` and `catch` install a new throw continuation for the body.
+//! * `primop 'raw_raise'(Class, Reason, Trace)` calls the throw
+//!   continuation directly, `primop 'build_stacktrace'(Trace)` builds a
+//!   stack trace from a raw trace. Together with `match_fail`, these are
+//!   the only primops supported.
+//!
+//! Only constant binaries are supported, both in expressions and
+//! patterns.
+
+use std::collections::{HashMap, HashSet};
+
+use libeir_diagnostics::SourceSpan;
+use libeir_intern::{Ident, Symbol};
+use libeir_ir::operation::case::{Case, CaseBuilder};
+use libeir_ir::operation::receive::{ReceiveDone, ReceiveStart, ReceiveWait};
+use libeir_ir::{
+    Block, Const, EmptyMap, FunctionBuilder, FunctionIdent, MapPutUpdate, NilTerm, PatternClause,
+    PatternContainer, PatternNode, ToPrimitive, Value,
+};
+use libeir_util_binary::{integer_to_carrier, BitVec, Endian};
+use libeir_util_number::BigInt;
+
+use crate::ast::{
+    Annotated, AtomicLiteral, CaseClause, Expression, Function as CoreFunction, FunctionName,
+    MapExactAssoc, Module, Pattern, PrimOpCall, SingleExpression, Variable,
+};
+
+const SPAN: SourceSpan = SourceSpan::UNKNOWN;
+
+#[derive(Debug)]
+pub enum LowerError {
+    /// The function contains a construct the lowering does not handle.
+    Unsupported { fun: FunctionIdent, reason: String },
+    /// A variable or local function that is not in scope.
+    Unbound { fun: FunctionIdent, name: String },
+}
+
+/// Lowers a Core Erlang module to Eir.
+pub fn to_eir(module: &Module) -> Result<libeir_ir::Module, LowerError> {
+    let mut ir_module = libeir_ir::Module::new(Ident::with_empty_span(module.name));
+
+    for def in module.definitions.iter() {
+        let name = &def.name.0;
+        let fun_def = ir_module.add_function(SPAN, Ident::with_empty_span(name.name), name.arity);
+        let fun = fun_def.function_mut();
+        let ident = *fun.ident();
+        let mut b = FunctionBuilder::new(fun);
+
+        let mut lower = FunctionLower {
+            ident,
+            scope: Scope::default(),
+            handler: None,
+            dead: HashSet::new(),
+        };
+        if def.fun.0.vars.len() != name.arity {
+            return lower.unsupported("function arity does not match its name");
+        }
+
+        let entry = b.block_insert();
+        b.block_set_entry(entry);
+        lower.fun_into(&mut b, entry, &def.fun.0)?;
+    }
+
+    Ok(ir_module)
+}
+
+#[derive(Clone, Default)]
+struct Scope {
+    vars: HashMap<Variable, Value>,
+    /// Functions bound by `letrec`.
+    funs: HashMap<FunctionName, Value>,
+}
+
+/// The patterns of a `case` or `receive` clause, added to the pattern
+/// container.
+struct LoweredClause {
+    clause: PatternClause,
+    /// The values referenced by the patterns.
+    values: Vec<Value>,
+    /// The variables bound by the patterns, in the order of the clause
+    /// binds.
+    binds: Vec<Variable>,
+}
+
+struct FunctionLower {
+    ident: FunctionIdent,
+    scope: Scope,
+    /// The current throw continuation.
+    handler: Option<Value>,
+    /// Blocks that control flow never reaches, since the expression before
+    /// them always raises.
+    dead: HashSet<Block>,
+}
+
+impl FunctionLower {
+    fn unsupported<T>(&self, reason: impl Into<String>) -> Result<T, LowerError> {
+        Err(LowerError::Unsupported {
+            fun: self.ident,
+            reason: reason.into(),
+        })
+    }
+
+    fn unbound<T>(&self, name: String) -> Result<T, LowerError> {
+        Err(LowerError::Unbound {
+            fun: self.ident,
+            name,
+        })
+    }
+
+    fn handler(&self) -> Value {
+        self.handler.unwrap()
+    }
+
+    fn dead_block(&mut self, b: &mut FunctionBuilder) -> Block {
+        let block = b.block_insert();
+        self.dead.insert(block);
+        block
+    }
+
+    /// Lowers a fun into `entry`, which gets the return and throw
+    /// continuations as its first arguments.
+    fn fun_into(
+        &mut self,
+        b: &mut FunctionBuilder,
+        entry: Block,
+        fun: &CoreFunction,
+    ) -> Result<(), LowerError> {
+        let ret = b.block_arg_insert(entry);
+        let thr = b.block_arg_insert(entry);
+
+        let scope = self.scope.clone();
+        let handler = self.handler.replace(thr);
+        for var in fun.vars.iter() {
+            let arg = b.block_arg_insert(entry);
+            self.scope.vars.insert(var.0, arg);
+        }
+
+        let (block, values) = self.expr(b, entry, &fun.body)?;
+        if !self.dead.contains(&block) {
+            b.op_call_flow(block, ret, &values);
+        }
+
+        self.scope = scope;
+        self.handler = handler;
+        Ok(())
+    }
+
+    /// Checks that an expression produced `num` values. In a dead block,
+    /// any number of values can be produced.
+    fn take(
+        &mut self,
+        b: &mut FunctionBuilder,
+        block: Block,
+        mut values: Vec<Value>,
+        num: usize,
+    ) -> Result<Vec<Value>, LowerError> {
+        if values.len() == num {
+            return Ok(values);
+        }
+        if !self.dead.contains(&block) {
+            return self.unsupported(format!("expected {} values, got {}", num, values.len()));
+        }
+        values.truncate(num);
+        while values.len() < num {
+            values.push(b.block_arg_insert(block));
+        }
+        Ok(values)
+    }
+
+    fn expr(
+        &mut self,
+        b: &mut FunctionBuilder,
+        block: Block,
+        expr: &Expression,
+    ) -> Result<(Block, Vec<Value>), LowerError> {
+        if let [single] = &expr.0[..] {
+            return self.single(b, block, &single.0);
+        }
+
+        let mut block = block;
+        let mut values = Vec::with_capacity(expr.0.len());
+        for single in expr.0.iter() {
+            let (next, value) = self.single_one(b, block, &single.0)?;
+            block = next;
+            values.push(value);
+        }
+        Ok((block, values))
+    }
+
+    fn expr_one(
+        &mut self,
+        b: &mut FunctionBuilder,
+        block: Block,
+        expr: &Expression,
+    ) -> Result<(Block, Value), LowerError> {
+        let (block, values) = self.expr(b, block, expr)?;
+        let values = self.take(b, block, values, 1)?;
+        Ok((block, values[0]))
+    }
+
+    fn exprs(
+        &mut self,
+        b: &mut FunctionBuilder,
+        block: Block,
+        exprs: &[Expression],
+    ) -> Result<(Block, Vec<Value>), LowerError> {
+        let mut block = block;
+        let mut values = Vec::with_capacity(exprs.len());
+        for expr in exprs.iter() {
+            let (next, value) = self.expr_one(b, block, expr)?;
+            block = next;
+            values.push(value);
+        }
+        Ok((block, values))
+    }
+
+    fn single_one(
+        &mut self,
+        b: &mut FunctionBuilder,
+        block: Block,
+        expr: &SingleExpression,
+    ) -> Result<(Block, Value), LowerError> {
+        let (block, values) = self.single(b, block, expr)?;
+        let values = self.take(b, block, values, 1)?;
+        Ok((block, values[0]))
+    }
+
+    fn single(
+        &mut self,
+        b: &mut FunctionBuilder,
+        block: Block,
+        expr: &SingleExpression,
+    ) -> Result<(Block, Vec<Value>), LowerError> {
+        match expr {
+            SingleExpression::FunctionName(name) => Ok((block, vec![self.local_fun(b, name)])),
+            SingleExpression::ExternalFunctionName { module, name } => {
+                let fun = b.prim_capture_function(SPAN, *module, name.name, name.arity);
+                Ok((block, vec![fun]))
+            }
+            SingleExpression::Variable(var) => match self.scope.vars.get(var) {
+                Some(value) => Ok((block, vec![*value])),
+                None => self.unbound(var.to_string()),
+            },
+
+            SingleExpression::Let { vars, val, body } => {
+                let (block, values) = self.expr(b, block, val)?;
+                let values = self.take(b, block, values, vars.len())?;
+
+                let scope = self.scope.clone();
+                for (var, value) in vars.iter().zip(values.iter()) {
+                    self.scope.vars.insert(var.0, *value);
+                }
+                let res = self.expr(b, block, body)?;
+                self.scope = scope;
+                Ok(res)
+            }
+            SingleExpression::Catch(body) => self.catch(b, block, body),
+            SingleExpression::Case { val, clauses } => {
+                let (block, values) = self.expr(b, block, val)?;
+                self.case(b, block, values, clauses)
+            }
+            SingleExpression::Do(first, second) => {
+                let (block, _) = self.expr(b, block, first)?;
+                self.expr(b, block, second)
+            }
+            SingleExpression::Try {
+                body,
+                then_vars,
+                then,
+                catch_vars,
+                catch,
+            } => {
+                let then_vars: Vec<_> = then_vars.iter().map(|v| v.0).collect();
+                let catch_vars: Vec<_> = catch_vars.iter().map(|v| v.0).collect();
+                self.try_(b, block, body, &then_vars, then, &catch_vars, catch)
+            }
+            SingleExpression::Receive {
+                clauses,
+                timeout_time,
+                timeout_body,
+            } => self.receive(b, block, clauses, timeout_time, timeout_body),
+
+            SingleExpression::PrimOpCall(call) => self.primop_call(b, block, call),
+            SingleExpression::ApplyCall { fun, args } => {
+                let (block, callee) = self.expr_one(b, block, fun)?;
+                let (block, args) = self.exprs(b, block, args)?;
+                Ok(self.call(b, block, callee, &args))
+            }
+            SingleExpression::InterModuleCall { module, name, args } => {
+                let (block, module) = self.expr_one(b, block, module)?;
+                let (block, name) = self.expr_one(b, block, name)?;
+                let (block, args) = self.exprs(b, block, args)?;
+                let callee = b.prim_capture_function(SPAN, module, name, args.len());
+                Ok(self.call(b, block, callee, &args))
+            }
+
+            SingleExpression::Fun(fun) => {
+                let entry = b.block_insert();
+                self.fun_into(b, entry, fun)?;
+                Ok((block, vec![b.value(entry)]))
+            }
+            SingleExpression::LetRec { funs, body } => {
+                let scope = self.scope.clone();
+
+                let mut entries = Vec::with_capacity(funs.len());
+                for (name, _) in funs.iter() {
+                    let entry = b.block_insert();
+                    let entry_val = b.value(entry);
+                    self.scope.funs.insert(name.clone(), entry_val);
+                    entries.push(entry);
+                }
+                for ((name, fun), entry) in funs.iter().zip(entries.iter()) {
+                    if fun.vars.len() != name.arity {
+                        return self.unsupported("function arity does not match its name");
+                    }
+                    self.fun_into(b, *entry, fun)?;
+                }
+
+                let res = self.expr(b, block, body)?;
+                self.scope = scope;
+                Ok(res)
+            }
+
+            SingleExpression::AtomicLiteral(lit) => {
+                let cons = literal_const(b, lit);
+                Ok((block, vec![b.value(cons)]))
+            }
+            SingleExpression::Tuple(elems) => {
+                let (block, elems) = self.exprs(b, block, elems)?;
+                Ok((block, vec![b.prim_tuple(SPAN, &elems)]))
+            }
+            SingleExpression::List { head, tail } => {
+                let (block, head) = self.exprs(b, block, head)?;
+                let (block, tail) = self.expr_one(b, block, tail)?;
+                let list = head
+                    .iter()
+                    .rev()
+                    .fold(tail, |acc, elem| b.prim_list_cell(SPAN, *elem, acc));
+                Ok((block, vec![list]))
+            }
+            SingleExpression::Map(entries, base) => self.map(b, block, entries, base.as_ref()),
+            SingleExpression::Binary(segments) => {
+                let mut bin = BitVec::new();
+                for (value, args) in segments.iter() {
+                    let value = match single_of(value) {
+                        Some(SingleExpression::AtomicLiteral(AtomicLiteral::Integer(int))) => int,
+                        _ => return self.unsupported("binary construction"),
+                    };
+                    let args: Option<Vec<_>> = args.iter().map(single_of).collect();
+                    match args.and_then(|args| push_segment(&mut bin, value, &args)) {
+                        Some(()) => (),
+                        None => return self.unsupported("binary construction"),
+                    }
+                }
+                Ok((block, vec![b.value(bin)]))
+            }
+        }
+    }
+
+    fn local_fun(&mut self, b: &mut FunctionBuilder, name: &FunctionName) -> Value {
+        match self.scope.funs.get(name) {
+            Some(fun) => *fun,
+            None => b.prim_capture_local_function(SPAN, name.name, name.arity),
+        }
+    }
+
+    fn call(
+        &mut self,
+        b: &mut FunctionBuilder,
+        block: Block,
+        callee: Value,
+        args: &[Value],
+    ) -> (Block, Vec<Value>) {
+        let (ok, fail) = b.op_call_function(SPAN, block, callee, args);
+        let fail_args = b.block_args(fail).to_vec();
+        b.op_call_flow(fail, self.handler(), &fail_args);
+        (ok, vec![b.block_args(ok)[0]])
+    }
+
+    /// Raises an error with a freshly captured trace. Returns the dead
+    /// block that follows.
+    fn raise(
+        &mut self,
+        b: &mut FunctionBuilder,
+        block: Block,
+        typ: &str,
+        reason: Value,
+    ) -> (Block, Vec<Value>) {
+        let typ = b.value(Symbol::intern(typ));
+        let cont = b.op_trace_capture_raw(SPAN, block);
+        let trace = b.block_args(cont)[0];
+        b.op_call_flow(cont, self.handler(), &[typ, reason, trace]);
+        (self.dead_block(b), Vec::new())
+    }
+
+    fn primop_call(
+        &mut self,
+        b: &mut FunctionBuilder,
+        block: Block,
+        call: &PrimOpCall,
+    ) -> Result<(Block, Vec<Value>), LowerError> {
+        let (block, args) = self.exprs(b, block, &call.args)?;
+        match (&*call.name.0.as_str(), &args[..]) {
+            ("raw_raise", [typ, reason, trace]) => {
+                b.op_call_flow(block, self.handler(), &[*typ, *reason, *trace]);
+                Ok((self.dead_block(b), Vec::new()))
+            }
+            ("match_fail", [reason]) => Ok(self.raise(b, block, "error", *reason)),
+            ("build_stacktrace", [trace]) => {
+                let cont = b.op_trace_construct(SPAN, block, *trace);
+                Ok((cont, vec![b.block_args(cont)[0]]))
+            }
+            (name, _) => self.unsupported(format!("primop {}/{}", name, args.len())),
+        }
+    }
+
+    fn map(
+        &mut self,
+        b: &mut FunctionBuilder,
+        block: Block,
+        entries: &[Annotated<(Expression, MapExactAssoc, Expression)>],
+        base: Option<&Expression>,
+    ) -> Result<(Block, Vec<Value>), LowerError> {
+        let (mut block, base) = match base {
+            Some(base) => self.expr_one(b, block, base)?,
+            None => (block, b.value(EmptyMap)),
+        };
+        if entries.is_empty() {
+            return Ok((block, vec![base]));
+        }
+
+        let mut map_builder = b.op_map_put_build(SPAN, base);
+        for entry in entries.iter() {
+            let (key, op, value) = &entry.0;
+            let action = match op {
+                MapExactAssoc::Assoc => MapPutUpdate::Put,
+                MapExactAssoc::Exact => MapPutUpdate::Update,
+            };
+            let (next, key) = self.expr_one(b, block, key)?;
+            let (next, value) = self.expr_one(b, next, value)?;
+            block = next;
+            map_builder.push_kv(key, value, action, b);
+        }
+        let (ok, fail) = map_builder.finish(block, b);
+
+        let badkey = b.value(Symbol::intern("badkey"));
+        let failed_key = b.block_args(fail)[0];
+        let reason = b.prim_tuple(SPAN, &[badkey, failed_key]);
+        self.raise(b, fail, "error", reason);
+
+        Ok((ok, vec![b.block_args(ok)[0]]))
+    }
+
+    /// Continues at the join block of a branching expression, creating it
+    /// on the first branch that is not dead.
+    fn join(
+        &mut self,
+        b: &mut FunctionBuilder,
+        join: &mut Option<Block>,
+        block: Block,
+        values: Vec<Value>,
+    ) -> Result<(), LowerError> {
+        if self.dead.contains(&block) {
+            return Ok(());
+        }
+        let target = match join {
+            Some(target) => *target,
+            None => {
+                let target = b.block_insert();
+                for _ in values.iter() {
+                    b.block_arg_insert(target);
+                }
+                *join = Some(target);
+                target
+            }
+        };
+        let arity = b.block_args(target).len();
+        let values = self.take(b, block, values, arity)?;
+        b.op_call_flow(block, target, &values);
+        Ok(())
+    }
+
+    fn join_finish(&mut self, b: &mut FunctionBuilder, join: Option<Block>) -> (Block, Vec<Value>) {
+        match join {
+            Some(join) => (join, b.block_args(join).to_vec()),
+            None => (self.dead_block(b), Vec::new()),
+        }
+    }
+
+    fn case(
+        &mut self,
+        b: &mut FunctionBuilder,
+        mut block: Block,
+        values: Vec<Value>,
+        clauses: &[Annotated<CaseClause>],
+    ) -> Result<(Block, Vec<Value>), LowerError> {
+        let match_on = match &values[..] {
+            [value] => *value,
+            _ => b.prim_value_list(&values),
+        };
+
+        let no_match = b.block_insert();
+        {
+            let value = match &values[..] {
+                [value] => *value,
+                _ => b.prim_tuple(SPAN, &values),
+            };
+            let case_clause = b.value(Symbol::intern("case_clause"));
+            let reason = b.prim_tuple(SPAN, &[case_clause, value]);
+            self.raise(b, no_match, "error", reason);
+        }
+
+        let mut case_b = Case::builder();
+        case_b.match_on = Some(match_on);
+        case_b.no_match = Some(b.value(no_match));
+
+        let mut join = None;
+        for clause in clauses.iter() {
+            let clause = &clause.0;
+            if clause.patterns.len() != values.len() {
+                return self.unsupported("wrong number of patterns in clause");
+            }
+            let (lowered, guard) = self.clause(b, &mut case_b, &mut block, clause)?;
+
+            let body = b.block_insert();
+            let scope = self.scope.clone();
+            for var in lowered.binds.iter() {
+                let arg = b.block_arg_insert(body);
+                self.scope.vars.insert(*var, arg);
+            }
+            let (body_ret, body_values) = self.expr(b, body, &clause.body)?;
+            self.join(b, &mut join, body_ret, body_values)?;
+            self.scope = scope;
+
+            let body_val = b.value(body);
+            case_b.push_clause(lowered.clause, guard, body_val, b);
+            for value in lowered.values.iter() {
+                case_b.push_value(*value, b);
+            }
+        }
+        case_b.finish(block, b);
+
+        Ok(self.join_finish(b, join))
+    }
+
+    fn receive(
+        &mut self,
+        b: &mut FunctionBuilder,
+        block: Block,
+        clauses: &[Annotated<CaseClause>],
+        timeout_time: &Expression,
+        timeout_body: &Expression,
+    ) -> Result<(Block, Vec<Value>), LowerError> {
+        let (block, timeout) = self.expr_one(b, block, timeout_time)?;
+
+        let wait = ReceiveStart::build(b, block, timeout);
+        let recv_ref = b.block_args(wait)[0];
+        let (after, mut check) = ReceiveWait::build(b, wait, recv_ref);
+        let message = b.block_args(check)[0];
+
+        let mut join = None;
+        let (after_ret, after_values) = self.expr(b, after, timeout_body)?;
+        self.join(b, &mut join, after_ret, after_values)?;
+
+        let no_match = b.block_insert();
+        b.op_call_flow(no_match, wait, &[recv_ref]);
+
+        let mut case_b = Case::builder();
+        case_b.match_on = Some(message);
+        case_b.no_match = Some(b.value(no_match));
+
+        for clause in clauses.iter() {
+            let clause = &clause.0;
+            if clause.patterns.len() != 1 {
+                return self.unsupported("wrong number of patterns in clause");
+            }
+            let (lowered, guard) = self.clause(b, &mut case_b, &mut check, clause)?;
+
+            // The bound values are passed through `receive_done`, like in
+            // the Erlang frontend.
+            let body = b.block_insert();
+            let values: Vec<_> = lowered
+                .binds
+                .iter()
+                .map(|_| b.block_arg_insert(body))
+                .collect();
+            let body_mapped = ReceiveDone::build(b, body, recv_ref, &values);
+
+            let scope = self.scope.clone();
+            for (idx, var) in lowered.binds.iter().enumerate() {
+                let value = b.block_args(body_mapped)[idx];
+                self.scope.vars.insert(*var, value);
+            }
+            let (body_ret, body_values) = self.expr(b, body_mapped, &clause.body)?;
+            self.join(b, &mut join, body_ret, body_values)?;
+            self.scope = scope;
+
+            let body_val = b.value(body);
+            case_b.push_clause(lowered.clause, guard, body_val, b);
+            for value in lowered.values.iter() {
+                case_b.push_value(*value, b);
+            }
+        }
+        case_b.finish(check, b);
+
+        Ok(self.join_finish(b, join))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn try_(
+        &mut self,
+        b: &mut FunctionBuilder,
+        block: Block,
+        body: &Expression,
+        then_vars: &[Variable],
+        then: &Expression,
+        catch_vars: &[Variable],
+        catch: &Expression,
+    ) -> Result<(Block, Vec<Value>), LowerError> {
+        if catch_vars.len() != 2 && catch_vars.len() != 3 {
+            return self.unsupported("wrong number of catch variables");
+        }
+
+        let exc = b.block_insert();
+        let exc_args = [
+            b.block_arg_insert(exc),
+            b.block_arg_insert(exc),
+            b.block_arg_insert(exc),
+        ];
+
+        let handler = self.handler.replace(b.value(exc));
+        let (body_ret, body_values) = self.expr(b, block, body)?;
+        self.handler = handler;
+        let body_values = self.take(b, body_ret, body_values, then_vars.len())?;
+
+        let mut join = None;
+        let scope = self.scope.clone();
+
+        for (var, value) in then_vars.iter().zip(body_values.iter()) {
+            self.scope.vars.insert(*var, *value);
+        }
+        let (then_ret, then_values) = self.expr(b, body_ret, then)?;
+        self.join(b, &mut join, then_ret, then_values)?;
+        self.scope = scope.clone();
+
+        for (var, value) in catch_vars.iter().zip(exc_args.iter()) {
+            self.scope.vars.insert(*var, *value);
+        }
+        let (catch_ret, catch_values) = self.expr(b, exc, catch)?;
+        self.join(b, &mut join, catch_ret, catch_values)?;
+        self.scope = scope;
+
+        Ok(self.join_finish(b, join))
+    }
+
+    fn catch(
+        &mut self,
+        b: &mut FunctionBuilder,
+        block: Block,
+        body: &Expression,
+    ) -> Result<(Block, Vec<Value>), LowerError> {
+        let exc = b.block_insert();
+        let exc_type = b.block_arg_insert(exc);
+        let exc_error = b.block_arg_insert(exc);
+        let exc_trace = b.block_arg_insert(exc);
+
+        let mut join = None;
+
+        let handler = self.handler.replace(b.value(exc));
+        let (body_ret, body_value) = self.expr_one(b, block, body)?;
+        self.handler = handler;
+        self.join(b, &mut join, body_ret, vec![body_value])?;
+
+        let throw_atom = b.value(Symbol::intern("throw"));
+        let exit_atom = b.value(Symbol::intern("exit"));
+        let big_exit_atom = b.value(Symbol::intern("EXIT"));
+
+        let mut match_b = b.op_match_build(SPAN);
+        let throw_block = match_b.push_value(throw_atom, b);
+        let exit_block = match_b.push_value(exit_atom, b);
+        let error_block = match_b.push_wildcard(SPAN, b);
+        match_b.finish(exc, exc_type, b);
+
+        self.join(b, &mut join, throw_block, vec![exc_error])?;
+
+        let exit_value = b.prim_tuple(SPAN, &[big_exit_atom, exc_error]);
+        self.join(b, &mut join, exit_block, vec![exit_value])?;
+
+        let trace_block = b.op_trace_construct(SPAN, error_block, exc_trace);
+        let trace = b.block_args(trace_block)[0];
+        let inner = b.prim_tuple(SPAN, &[exc_error, trace]);
+        let error_value = b.prim_tuple(SPAN, &[big_exit_atom, inner]);
+        self.join(b, &mut join, trace_block, vec![error_value])?;
+
+        Ok(self.join_finish(b, join))
+    }
+
+    /// Adds the patterns of a clause to a case, and lowers its guard into a
+    /// lambda. Values referenced by the patterns are lowered into
+    /// `pre_case`.
+    fn clause(
+        &mut self,
+        b: &mut FunctionBuilder,
+        case_b: &mut CaseBuilder,
+        pre_case: &mut Block,
+        clause: &CaseClause,
+    ) -> Result<(LoweredClause, Value), LowerError> {
+        let pat = &mut case_b.container;
+        let pat_clause = pat.clause_start(SPAN);
+
+        let mut lowered = LoweredClause {
+            clause: pat_clause,
+            values: Vec::new(),
+            binds: Vec::new(),
+        };
+        for pattern in clause.patterns.iter() {
+            let node = self.pattern(b, pat, pre_case, &mut lowered, &pattern.0)?;
+            pat.clause_node_push(pat_clause, node);
+        }
+        pat.clause_finish(pat_clause);
+
+        let guard = b.block_insert();
+        let ret = b.block_arg_insert(guard);
+        let _thr = b.block_arg_insert(guard);
+
+        // An exception in the guard makes it fail.
+        let fail = b.block_insert();
+        b.block_arg_insert(fail);
+        b.block_arg_insert(fail);
+        b.block_arg_insert(fail);
+        let false_val = b.value(false);
+        b.op_call_flow(fail, ret, &[false_val]);
+
+        let scope = self.scope.clone();
+        let handler = self.handler.replace(b.value(fail));
+        for var in lowered.binds.iter() {
+            let arg = b.block_arg_insert(guard);
+            self.scope.vars.insert(*var, arg);
+        }
+        let (guard_ret, guard_value) = self.expr_one(b, guard, &clause.guard)?;
+        if !self.dead.contains(&guard_ret) {
+            b.op_call_flow(guard_ret, ret, &[guard_value]);
+        }
+        self.scope = scope;
+        self.handler = handler;
+
+        Ok((lowered, b.value(guard)))
+    }
+
+    fn pattern(
+        &mut self,
+        b: &mut FunctionBuilder,
+        pat: &mut PatternContainer,
+        pre_case: &mut Block,
+        lowered: &mut LoweredClause,
+        pattern: &Pattern,
+    ) -> Result<PatternNode, LowerError> {
+        let node = match pattern {
+            Pattern::Wildcard => {
+                let node = pat.node_empty(None);
+                pat.wildcard(node);
+                node
+            }
+            Pattern::BindVar(var, inner) => {
+                let node = self.pattern(b, pat, pre_case, lowered, &inner.0)?;
+                pat.clause_bind_push(lowered.clause, node);
+                lowered.binds.push(var.0);
+                node
+            }
+            Pattern::Atomic(lit) => {
+                let node = pat.node_empty(None);
+                pat.constant(node, literal_const(b, lit));
+                node
+            }
+            Pattern::Tuple(elems) => {
+                let node = pat.node_empty(None);
+                pat.tuple(node);
+                for elem in elems.iter() {
+                    let child = self.pattern(b, pat, pre_case, lowered, &elem.0)?;
+                    pat.tuple_elem_push(node, child);
+                }
+                pat.node_finish(node);
+                node
+            }
+            Pattern::List(head, tail) => {
+                let mut heads = Vec::with_capacity(head.len());
+                for elem in head.iter() {
+                    heads.push(self.pattern(b, pat, pre_case, lowered, &elem.0)?);
+                }
+                let tail = self.pattern(b, pat, pre_case, lowered, &tail.0)?;
+                heads.into_iter().rev().fold(tail, |acc, head| {
+                    let node = pat.node_empty(None);
+                    pat.list(node, head, acc);
+                    node
+                })
+            }
+            Pattern::Map(entries) => {
+                let node = pat.node_empty(None);
+                pat.map(node);
+                for entry in entries.iter() {
+                    let (key, value) = &entry.0;
+                    let (next, key) = self.single_one(b, *pre_case, &key.0)?;
+                    *pre_case = next;
+                    let key_val = pat.clause_value(lowered.clause);
+                    lowered.values.push(key);
+
+                    let child = self.pattern(b, pat, pre_case, lowered, &value.0)?;
+                    pat.map_push(node, key_val, child);
+                }
+                pat.node_finish(node);
+                node
+            }
+            Pattern::Binary(segments) => {
+                let mut bin = BitVec::new();
+                for (value, args) in segments.iter() {
+                    let value = match &value.0 {
+                        Pattern::Atomic(AtomicLiteral::Integer(int)) => int,
+                        _ => return self.unsupported("binary pattern"),
+                    };
+                    let args: Vec<_> = args.iter().map(|arg| &arg.0).collect();
+                    if push_segment(&mut bin, value, &args).is_none() {
+                        return self.unsupported("binary pattern");
+                    }
+                }
+                let node = pat.node_empty(None);
+                let cons = b.cons_mut().from(bin);
+                pat.constant(node, cons);
+                node
+            }
+        };
+        Ok(node)
+    }
+}
+
+fn single_of(expr: &Expression) -> Option<&SingleExpression> {
+    match &expr.0[..] {
+        [single] => Some(&single.0),
+        _ => None,
+    }
+}
+
+/// Appends a constant integer segment to a binary. The arguments are
+/// `(Size, Unit, 'integer', Flags)`, as printed by `erlc`.
+fn push_segment(bin: &mut BitVec, value: &BigInt, args: &[&SingleExpression]) -> Option<()> {
+    let int = |expr: &SingleExpression| match expr {
+        SingleExpression::AtomicLiteral(AtomicLiteral::Integer(int)) => int.to_usize(),
+        _ => None,
+    };
+    let atom = |expr: &SingleExpression| match expr {
+        SingleExpression::AtomicLiteral(AtomicLiteral::Atom(atom)) => Some(*atom),
+        _ => None,
+    };
+
+    let (size, unit, typ, flags) = match args {
+        [size, unit, typ, flags] => (int(size)?, int(unit)?, atom(typ)?, flags),
+        _ => return None,
+    };
+    if typ != Symbol::intern("integer") {
+        return None;
+    }
+
+    let mut endian = Endian::Big;
+    if let SingleExpression::List { head, .. } = flags {
+        for flag in head.iter() {
+            if atom(single_of(flag)?)? == Symbol::intern("little") {
+                endian = Endian::Little;
+            }
+        }
+    }
+
+    bin.push(integer_to_carrier(value.clone(), size * unit, endian));
+    Some(())
+}
+
+fn literal_const(b: &mut FunctionBuilder, lit: &AtomicLiteral) -> Const {
+    let cons = b.cons_mut();
+    match lit {
+        AtomicLiteral::Integer(int) => match int.to_i64() {
+            Some(small) => cons.from(small),
+            None => cons.from(int.clone()),
+        },
+        AtomicLiteral::Float(float) => cons.from(*float),
+        AtomicLiteral::Atom(atom) => cons.from(*atom),
+        AtomicLiteral::Nil => cons.from(NilTerm),
+        AtomicLiteral::Char(c) => cons.from(*c),
+        AtomicLiteral::String(string) => {
+            let nil = cons.nil();
+            string.chars().rev().fold(nil, |tail, c| {
+                let head = cons.from(c);
+                cons.list_cell(head, tail)
+            })
+        }
+    }
+}
@@ -259,6 +259,20 @@ pub fn eval_expr(
                     Err(EvalError::InvalidBitwiseOperand { span })?
                 }
 
+                (
+                    op @ (B::And | B::AndAlso | B::Or | B::OrElse | B::Xor),
+                    Term::Atom(l),
+                    Term::Atom(r),
+                ) if is_boolean(l) && is_boolean(r) => {
+                    let l = l == symbols::True;
+                    let r = r == symbols::True;
+                    match op {
+                        B::And | B::AndAlso => (l && r).into(),
+                        B::Or | B::OrElse => (l || r).into(),
+                        _ => (l != r).into(),
+                    }
+                }
+
                 (B::Lt, l, r) => (l < r).into(),
                 (B::Lte, l, r) => (l <= r).into(),
                 (B::Gt, l, r) => (l > r).into(),
@@ -300,3 +314,7 @@ pub fn eval_expr(
     };
     Ok(res)
 }
+
+fn is_boolean(sym: Symbol) -> bool {
+    sym == symbols::True || sym == symbols::False
+}
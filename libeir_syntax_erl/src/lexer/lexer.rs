@@ -257,6 +257,7 @@ where
             '}' => pop!(self, Token::RBrace),
             '?' => match self.peek() {
                 '?' => pop2!(self, Token::DoubleQuestion),
+                '=' => pop2!(self, Token::QuestionEqual),
                 _ => pop!(self, Token::Question),
             },
             '-' => match self.peek() {
@@ -87,6 +87,12 @@ impl TryFrom<LexicalToken> for AtomToken {
             LexicalToken(start, Token::If, end) => {
                 return Ok(AtomToken(start, Token::Atom(symbols::If), end));
             }
+            LexicalToken(start, Token::Else, end) => {
+                return Ok(AtomToken(start, Token::Atom(symbols::Else), end));
+            }
+            LexicalToken(start, Token::Maybe, end) => {
+                return Ok(AtomToken(start, Token::Atom(Symbol::intern("maybe")), end));
+            }
             t => Err(TokenConvertError {
                 span: t.span(),
                 token: t.token(),
@@ -319,6 +325,9 @@ pub enum Token {
     Of,
     Receive,
     When,
+    // Keywords enabled by the `maybe_expr` feature
+    Maybe,
+    Else,
     // Attributes
     Record,
     Spec,
@@ -393,6 +402,8 @@ pub enum Token {
     DotDotDot,
     Question,
     DoubleQuestion,
+    // ?=
+    QuestionEqual,
 }
 impl PartialEq for Token {
     fn eq(&self, other: &Token) -> bool {
@@ -467,6 +478,10 @@ impl Token {
             "of" => Token::Of,
             "receive" => Token::Receive,
             "when" => Token::When,
+            // Only keywords when the `maybe_expr` feature is enabled, the
+            // preprocessor turns these back into atoms otherwise
+            "maybe" => Token::Maybe,
+            "else" => Token::Else,
             "andalso" => Token::AndAlso,
             "orelse" => Token::OrElse,
             "bnot" => Token::Bnot,
@@ -540,6 +555,8 @@ impl fmt::Display for Token {
             Token::Of => write!(f, "of"),
             Token::Receive => write!(f, "receive"),
             Token::When => write!(f, "when"),
+            Token::Maybe => write!(f, "maybe"),
+            Token::Else => write!(f, "else"),
             Token::Record => write!(f, "record"),
             Token::Spec => write!(f, "spec"),
             Token::Callback => write!(f, "callback"),
@@ -598,6 +615,7 @@ impl fmt::Display for Token {
             Token::DotDotDot => write!(f, "..."),
             Token::Question => write!(f, "?"),
             Token::DoubleQuestion => write!(f, "??"),
+            Token::QuestionEqual => write!(f, "?="),
         }
     }
 }
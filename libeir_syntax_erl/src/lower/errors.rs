@@ -36,7 +36,8 @@ pub enum LowerError {
 
     /// Equality in a pattern caused two nodes to be merged,
     /// but merging these two nodes is not supported.
-    /// Happens when trying to merge two binary patterns.
+    /// Happens when trying to merge two binary patterns with different
+    /// segment layouts.
     #[snafu(display("patterns cannot be merged"))]
     UnsupportedPatternUnion {
         left: Option<SourceSpan>,
@@ -166,7 +167,7 @@ impl ToDiagnostic for LowerError {
                 dig.with_labels(labels)
             }
             LowerError::UnsupportedPatternUnion { left, right } => {
-                let dig = Diagnostic::warning().with_message(msg);
+                let dig = Diagnostic::error().with_message(msg);
                 let mut labels = vec![];
                 if let Some(left) = left {
                     labels
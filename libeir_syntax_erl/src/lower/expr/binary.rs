@@ -1,3 +1,5 @@
+use libeir_diagnostics::SourceSpan;
+use libeir_intern::Symbol;
 pub use libeir_ir::binary::{BinaryEntrySpecifier, Endianness};
 use libeir_ir::{
     operation::binary_construct::{
@@ -47,6 +49,19 @@ fn make_size(
     }
 }
 
+/// Raises `error:badarg` from the error continuation of a binary push.
+/// The push fails when the value or size is not valid for the specifier.
+pub(super) fn make_push_error(
+    ctx: &mut LowerCtx,
+    b: &mut FunctionBuilder,
+    span: SourceSpan,
+    err_cont: IrBlock,
+) {
+    let typ = b.value(Symbol::intern("error"));
+    let badarg = b.value(Symbol::intern("badarg"));
+    ctx.exc_stack.make_error_jump(b, span, err_cont, typ, badarg);
+}
+
 /// Lowers a single entry of a binary construction element.
 ///
 /// A couple of gotchas with the syntax:
@@ -82,8 +97,7 @@ pub(crate) fn lower_binary_elem(
                         *bin_ref = b.block_args(ok_cont)[0];
                         block = ok_cont;
 
-                        // TODO: Proper error
-                        b.op_unreachable(span, err_cont);
+                        make_push_error(ctx, b, span, err_cont);
                     },
                     Err(err) => {
                         ctx.error(err.into());
@@ -105,8 +119,7 @@ pub(crate) fn lower_binary_elem(
         *bin_ref = b.block_args(ok_cont)[0];
         block = ok_cont;
 
-        // TODO: Proper error
-        b.op_unreachable(elem.span, err_cont);
+        make_push_error(ctx, b, elem.span, err_cont);
     }
 
     block
@@ -126,7 +139,7 @@ pub(super) fn lower_binary_expr(
         let spec = BinaryEntrySpecifier::Bytes { unit: 1 };
         let (ok_cont, err_cont) = BinaryConstructPush::build(b, block, bin_ref, bin, spec, None);
         block = ok_cont;
-        b.op_unreachable(binary.span, err_cont);
+        make_push_error(ctx, b, binary.span, err_cont);
 
         bin_ref = b.block_args(ok_cont)[0];
     }
@@ -135,7 +135,9 @@ pub(super) fn lower_try_expr(
                     }
 
                     // Bind stack trace in scope
-                    ctx.bind(clause.trace, exc_trace);
+                    let body = b.op_trace_construct(clause.span, body, exc_trace);
+                    let trace = b.block_args(body)[0];
+                    ctx.bind(clause.trace, trace);
 
                     let (body_ret_block, body_ret) = lower_block(ctx, b, body, &clause.body);
 
@@ -268,10 +270,13 @@ pub(super) fn lower_catch_expr(
         let error_block_val = b.value(error_block);
         case_b.push_clause(error_clause, guard_val, error_block_val, b);
 
-        let inner_tup = b.prim_tuple(span, &[exc_error, exc_trace]);
+        let trace_block = b.op_trace_construct(span, error_block, exc_trace);
+        let trace = b.block_args(trace_block)[0];
+
+        let inner_tup = b.prim_tuple(span, &[exc_error, trace]);
         let ret_tup = b.prim_tuple(span, &[big_exit_atom, inner_tup]);
 
-        b.op_call_flow(error_block, join_block, &[ret_tup]);
+        b.op_call_flow(trace_block, join_block, &[ret_tup]);
     }
 
     // Exit branch
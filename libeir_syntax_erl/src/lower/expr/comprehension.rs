@@ -15,7 +15,7 @@ use libeir_diagnostics::SourceSpan;
 
 use crate::parser::ast::{BinaryComprehension, Binary, BinaryElement, Expr, ListComprehension, NodeId, Var};
 
-use crate::lower::expr::binary::lower_binary_expr;
+use crate::lower::expr::binary::{lower_binary_expr, make_push_error};
 use crate::lower::expr::{lower_single, lower_single_same_scope};
 use crate::lower::pattern::lower_clause;
 use crate::lower::LowerCtx;
@@ -381,7 +381,7 @@ pub(super) fn lower_binary_comprehension_expr(
             let (ok_cont, err_cont) =
                 BinaryConstructPush::build(b, block, bin_ref, val, spec, None);
 
-            b.op_unreachable(compr.span, err_cont);
+            make_push_error(ctx, b, compr.span, err_cont);
 
             let bin_ref = b.block_args(ok_cont)[0];
             (ok_cont, bin_ref)
@@ -151,21 +151,21 @@ fn lower_expr(
                         arity: args.len(),
                     };
 
-                    let (module, function) = if ctx.module.functions.contains_key(&local) {
-                        (ctx.module.name, *name)
+                    let import = if ctx.module.functions.contains_key(&local) {
+                        None
                     } else {
-                        if let Some(resolved) = ctx.module.imports.get(&local) {
-                            assert!(resolved.arity == args.len());
-                            (resolved.module, resolved.function)
-                        } else {
-                            (ctx.module.name, *name)
-                        }
+                        ctx.module.imports.get(&local)
                     };
 
-                    let mod_val = b.value(module);
-                    let fun_val = b.value(function);
-
-                    b.prim_capture_function(span, mod_val, fun_val, arity_val)
+                    if let Some(resolved) = import {
+                        assert!(resolved.arity == args.len());
+                        let mod_val = b.value(resolved.module);
+                        let fun_val = b.value(resolved.function);
+                        b.prim_capture_function(span, mod_val, fun_val, arity_val)
+                    } else {
+                        let fun_val = b.value(*name);
+                        b.prim_capture_local_function(span, fun_val, arity_val)
+                    }
                 }
                 expr => map_block!(block, lower_single_same_scope(ctx, b, block, expr)),
             };
@@ -335,19 +335,17 @@ fn lower_expr(
                 (block, fun_val)
             }
             FunctionName::PartiallyResolved(partial) => {
-                let local = ctx.module.imports.get(&partial.to_local());
-                let resolved = if let Some(fun) = local {
-                    fun.clone()
+                let fun_val = if let Some(resolved) = ctx.module.imports.get(&partial.to_local()) {
+                    let module = b.value(resolved.module);
+                    let function = b.value(resolved.function);
+                    let arity = b.value(resolved.arity);
+                    b.prim_capture_function(partial.span, module, function, arity)
                 } else {
-                    partial.resolve(ctx.module.name)
+                    let function = b.value(partial.function);
+                    let arity = b.value(partial.arity);
+                    b.prim_capture_local_function(partial.span, function, arity)
                 };
 
-                let module = b.value(resolved.module);
-                let function = b.value(resolved.function);
-                let arity = b.value(resolved.arity);
-
-                let fun_val = b.prim_capture_function(partial.span, module, function, arity);
-
                 (block, fun_val)
             }
             FunctionName::Unresolved(unresolved) => {
@@ -16,8 +16,8 @@ use snafu::ResultExt;
 
 use crate::evaluator::{eval_expr, ResolveRecordIndexError, Term};
 use crate::lower::{lower_single, LowerCtx, LowerError};
-use crate::util::string_tokenizer::StringTokenizer;
 use crate::parser::ast::{Binary, BinaryExpr, BinaryOp, Expr, Literal, UnaryExpr, UnaryOp, Var};
+use crate::util::string_tokenizer::StringTokenizer;
 
 use super::{Tree, TreeNode, TreeNodeKind};
 
@@ -39,11 +39,11 @@ fn pattern_to_tree_node_append_tail(
                 match elem {
                     Ok((cp, _span)) => {
                         tokens.push(cp);
-                    },
+                    }
                     Err(err) => {
                         ctx.error(err.into());
                         return t.nodes.push(TreeNodeKind::Wildcard(span));
-                    },
+                    }
                 }
             }
 
@@ -67,11 +67,7 @@ fn pattern_to_tree_node_append_tail(
                 pattern_to_tree_node_append_tail(ctx, b, pre_block, t, &cons.tail, tail, span);
             let head = pattern_to_tree_node(ctx, b, pre_block, t, &cons.head);
 
-            t.nodes.push(TreeNodeKind::Cons {
-                span,
-                head,
-                tail,
-            })
+            t.nodes.push(TreeNodeKind::Cons { span, head, tail })
         }
         _ => unimplemented!("{:?}", expr),
     }
@@ -116,22 +112,20 @@ fn pattern_to_tree_node(
                         match elem {
                             Ok((cp, _span)) => {
                                 chars.push(cp);
-                            },
+                            }
                             Err(err) => {
                                 ctx.error(err.into());
                                 return t.nodes.push(TreeNodeKind::Wildcard(ident.span));
-                            },
+                            }
                         }
                     }
 
                     let nil_const = b.cons_mut().from(NilTerm);
-                    let mut node =
-                        t.nodes.push(TreeNodeKind::Atomic(ident.span, nil_const));
+                    let mut node = t.nodes.push(TreeNodeKind::Atomic(ident.span, nil_const));
 
                     for c in chars.iter().rev() {
                         let char_const = b.cons_mut().from(*c);
-                        let head =
-                            t.nodes.push(TreeNodeKind::Atomic(ident.span, char_const));
+                        let head = t.nodes.push(TreeNodeKind::Atomic(ident.span, char_const));
 
                         node = t.nodes.push(TreeNodeKind::Cons {
                             span: ident.span,
@@ -230,7 +224,9 @@ fn pattern_to_tree_node(
             pattern_to_tree_node_append_tail(ctx, b, pre_block, t, lhs, tail, *span)
         }
         Expr::Binary(Binary { span, elements, .. }) => {
-            use crate::parser::binary::{default_specifier, specifier_can_have_size, specifier_to_typename, TypeName};
+            use crate::parser::binary::{
+                default_specifier, specifier_can_have_size, specifier_to_typename, TypeName,
+            };
 
             // Desugar <<"binary string">>
             if elements.len() == 1 {
@@ -244,16 +240,15 @@ fn pattern_to_tree_node(
                             match elem {
                                 Ok((cp, _span)) => {
                                     chars.push(cp);
-                                },
+                                }
                                 Err(err) => {
                                     ctx.error(err.into());
                                     return t.nodes.push(TreeNodeKind::Wildcard(string.span));
-                                },
+                                }
                             }
                         }
 
-                        let bin =
-                            chars.iter().map(|ch| (ch & 0xff) as u8).collect::<Vec<_>>();
+                        let bin = chars.iter().map(|ch| (ch & 0xff) as u8).collect::<Vec<_>>();
                         let cons = b.cons_mut().from(bin);
                         return t.nodes.push(TreeNodeKind::Atomic(*span, cons));
                     }
@@ -267,13 +262,35 @@ fn pattern_to_tree_node(
             //));
 
             for (_idx, elem) in elements.iter().enumerate().rev() {
-                let spec = elem
-                    .specifier
-                    .unwrap_or(default_specifier());
+                let spec = elem.specifier.unwrap_or(default_specifier());
 
                 let spec_typ = specifier_to_typename(&spec);
 
-                let bit_val = pattern_to_tree_node(ctx, b, pre_block, t, &elem.bit_expr);
+                // A string literal is matched as one segment per character
+                let bit_vals = match &elem.bit_expr {
+                    Expr::Literal(Literal::String(_id, string)) => {
+                        let mut nodes = Vec::new();
+
+                        let tokenizer = StringTokenizer::new(*string);
+                        for token in tokenizer {
+                            match token {
+                                Ok((cp, _span)) => {
+                                    let cons = b.cons_mut().from(cp);
+                                    nodes.push(
+                                        t.nodes.push(TreeNodeKind::Atomic(string.span, cons)),
+                                    );
+                                }
+                                Err(err) => {
+                                    ctx.error(err.into());
+                                    return t.nodes.push(TreeNodeKind::Wildcard(string.span));
+                                }
+                            }
+                        }
+
+                        nodes
+                    }
+                    bit_expr => vec![pattern_to_tree_node(ctx, b, pre_block, t, bit_expr)],
+                };
 
                 let size_val = if let Some(size_expr) = &elem.bit_size {
                     if !specifier_can_have_size(&spec) {
@@ -302,14 +319,16 @@ fn pattern_to_tree_node(
                     .map(Either::Right)
                 };
 
-                bin_node = Some(t.nodes.push(TreeNodeKind::Binary {
-                    span: *span,
-                    specifier: spec,
-                    size: size_val,
-                    size_resolved: None,
-                    value: bit_val,
-                    tail: bin_node,
-                }));
+                for bit_val in bit_vals.iter().rev() {
+                    bin_node = Some(t.nodes.push(TreeNodeKind::Binary {
+                        span: *span,
+                        specifier: spec,
+                        size: size_val,
+                        size_resolved: None,
+                        value: *bit_val,
+                        tail: bin_node,
+                    }));
+                }
             }
 
             bin_node.unwrap_or_else(|| {
@@ -335,9 +354,7 @@ fn pattern_to_tree_node(
             })
         }
         expr => {
-            let resolve_rec_idx = |name: Ident, field: Ident| {
-                ctx.resolve_rec_idx(name, field)
-            };
+            let resolve_rec_idx = |name: Ident, field: Ident| ctx.resolve_rec_idx(name, field);
             match eval_expr(expr, Some(&resolve_rec_idx)) {
                 Ok(term) => {
                     let constant = match term {
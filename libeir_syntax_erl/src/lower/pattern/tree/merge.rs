@@ -168,7 +168,49 @@ fn merge_nodes(
                 tail: t_m,
             })
         }
-        (TreeNodeKind::Binary { .. }, TreeNodeKind::Binary { .. }) => unimplemented!(),
+        (
+            TreeNodeKind::Binary {
+                span: s1,
+                specifier: spec1,
+                size: size1,
+                value: v1,
+                tail: t1,
+                ..
+            },
+            TreeNodeKind::Binary {
+                span: s2,
+                specifier: spec2,
+                size: size2,
+                value: v2,
+                tail: t2,
+                ..
+            },
+        ) => {
+            if spec1 == spec2 && size1 == size2 && t1.is_some() == t2.is_some() {
+                // Both patterns have the same segment layout, the values
+                // and tails can be merged segment by segment.
+                let value = merge_nodes(ctx, b, t, v1, v2);
+                let tail = match (t1, t2) {
+                    (Some(t1), Some(t2)) => Some(merge_nodes(ctx, b, t, t1, t2)),
+                    _ => None,
+                };
+                t.nodes.push(TreeNodeKind::Binary {
+                    span: s1,
+                    specifier: spec1,
+                    size: size1,
+                    size_resolved: None,
+                    value,
+                    tail,
+                })
+            } else {
+                ctx.error(LowerError::UnsupportedPatternUnion {
+                    left: Some(s1),
+                    right: s2,
+                });
+                t.unmatchable = true;
+                t.nodes.push(TreeNodeKind::Wildcard(s1))
+            }
+        }
         (TreeNodeKind::Map { entries: e_l, span }, TreeNodeKind::Map { entries: e_r, .. }) => {
             // Entries vectors should already be sorted
             debug_assert!(e_l.windows(2).all(|w| w[0].0 < w[1].0));
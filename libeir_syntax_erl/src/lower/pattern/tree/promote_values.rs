@@ -52,6 +52,9 @@ impl<'a, 'b> PromoteCtx<'a, 'b> {
     fn resolve_only(&self, ident: Ident) -> Option<Either<TreeNode, IrValue>> {
         if let Some(bound_node) = self.binds_scope.get(&ident) {
             Some(Either::Left(*bound_node))
+        } else if let Some(bound_node) = self.binds.get(&ident) {
+            // Bound earlier in the same pattern, outside of the binary
+            Some(Either::Left(*bound_node))
         } else {
             if let Ok(prev_bound) = self.ctx.scope.resolve(ident) {
                 Some(Either::Right(prev_bound))
@@ -4,8 +4,8 @@ use libeir_diagnostics::SourceSpan;
 use libeir_util_number::{Float, Integer, Number};
 use libeir_ir::binary::BinaryEntrySpecifier;
 
-use super::NodeId;
-use super::{BinaryOp, Ident, UnaryOp};
+use super::{BinaryOp, Ident, Symbol, UnaryOp};
+use super::{NodeId, NodeIdGenerator};
 use super::{Function, FunctionName, Guard, Name, Type};
 
 use crate::lexer::DelayedSubstitution;
@@ -747,6 +747,146 @@ impl PartialEq for After {
     }
 }
 
+/// An expression in the body of a `maybe` block
+#[derive(Debug, Clone)]
+pub enum MaybeBodyExpr {
+    Expr(Expr),
+    /// A conditional match, e.g. `{ok, X} ?= Expr`
+    Match(SourceSpan, Expr, Expr),
+}
+
+/// Desugars `maybe Body else ElseClauses end` into nested `case` expressions.
+///
+/// Each `Pattern ?= Expr` becomes a `case` whose first clause continues with
+/// the rest of the body, and whose second clause short-circuits the block,
+/// either returning the unmatched value or matching it against the `else`
+/// clauses. An `else` block that does not match raises `{else_clause, Value}`.
+pub fn desugar_maybe(
+    nid: &mut NodeIdGenerator,
+    span: SourceSpan,
+    body: Vec<MaybeBodyExpr>,
+    else_clauses: Option<Vec<Clause>>,
+) -> Expr {
+    let body = desugar_maybe_body(nid, body.into_iter(), else_clauses.as_ref());
+    Expr::Begin(Begin {
+        span,
+        id: nid.next(),
+        body,
+    })
+}
+
+fn desugar_maybe_body<I>(
+    nid: &mut NodeIdGenerator,
+    mut exprs: I,
+    else_clauses: Option<&Vec<Clause>>,
+) -> Vec<Expr>
+where
+    I: Iterator<Item = MaybeBodyExpr>,
+{
+    let mut body = Vec::new();
+    while let Some(expr) = exprs.next() {
+        let (span, pattern, input) = match expr {
+            MaybeBodyExpr::Expr(expr) => {
+                body.push(expr);
+                continue;
+            }
+            MaybeBodyExpr::Match(span, pattern, input) => (span, pattern, input),
+        };
+
+        let rest = desugar_maybe_body(nid, exprs, else_clauses);
+        let (pattern, rest) = if rest.is_empty() {
+            // The value of a trailing `?=` is the matched value
+            let value = maybe_var(span);
+            let pattern = Expr::Match(Match {
+                span,
+                id: nid.next(),
+                pattern: Box::new(pattern),
+                expr: Box::new(Expr::Var(Var(nid.next(), value))),
+            });
+            (pattern, vec![Expr::Var(Var(nid.next(), value))])
+        } else {
+            (pattern, rest)
+        };
+
+        let other = maybe_var(span);
+        let other_body = match else_clauses {
+            None => Expr::Var(Var(nid.next(), other)),
+            Some(clauses) => {
+                let mut clauses = clauses.clone();
+                let unmatched = maybe_var(span);
+                let error = Expr::Apply(Apply {
+                    span,
+                    id: nid.next(),
+                    callee: Box::new(Expr::Remote(Remote {
+                        span,
+                        id: nid.next(),
+                        module: Box::new(Expr::Literal(Literal::Atom(
+                            nid.next(),
+                            Ident::new(Symbol::intern("erlang"), span),
+                        ))),
+                        function: Box::new(Expr::Literal(Literal::Atom(
+                            nid.next(),
+                            Ident::new(Symbol::intern("error"), span),
+                        ))),
+                    })),
+                    args: vec![Expr::Tuple(Tuple {
+                        span,
+                        id: nid.next(),
+                        elements: vec![
+                            Expr::Literal(Literal::Atom(
+                                nid.next(),
+                                Ident::new(Symbol::intern("else_clause"), span),
+                            )),
+                            Expr::Var(Var(nid.next(), unmatched)),
+                        ],
+                    })],
+                });
+                clauses.push(Clause {
+                    span,
+                    id: nid.next(),
+                    pattern: Expr::Var(Var(nid.next(), unmatched)),
+                    guard: None,
+                    body: vec![error],
+                });
+                Expr::Case(Case {
+                    span,
+                    id: nid.next(),
+                    expr: Box::new(Expr::Var(Var(nid.next(), other))),
+                    clauses,
+                })
+            }
+        };
+
+        body.push(Expr::Case(Case {
+            span,
+            id: nid.next(),
+            expr: Box::new(input),
+            clauses: vec![
+                Clause {
+                    span,
+                    id: nid.next(),
+                    pattern,
+                    guard: None,
+                    body: rest,
+                },
+                Clause {
+                    span,
+                    id: nid.next(),
+                    pattern: Expr::Var(Var(nid.next(), other)),
+                    guard: None,
+                    body: vec![other_body],
+                },
+            ],
+        }));
+        break;
+    }
+    body
+}
+
+fn maybe_var(span: SourceSpan) -> Ident {
+    Ident::new(Symbol::intern("MaybeValue"), span).gensym()
+}
+
 /// Represents a single match clause in a `case`, `try`, or `receive` expression
 #[derive(Debug, Clone)]
 pub struct Clause {
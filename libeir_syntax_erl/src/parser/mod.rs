@@ -50,7 +50,8 @@ pub type Parser = GParser<ParseConfig>;
 pub trait Parse<T> = GParse<T, Config = ParseConfig, Error = ParserError>;
 
 use crate::lexer::Lexer;
-use crate::preprocessor::{MacroContainer, Preprocessed, Preprocessor};
+use crate::lexer::LexicalToken;
+use crate::preprocessor::{MacroContainer, Preprocessed, PreprocessedSource, Preprocessor};
 
 pub use self::ast::{NodeId, NodeIdGenerator};
 pub use self::errors::*;
@@ -65,6 +66,9 @@ pub struct ParseConfig {
     pub include_paths: VecDeque<PathBuf>,
     pub code_paths: VecDeque<PathBuf>,
     pub macros: Option<MacroContainer>,
+    /// The OTP release to target, which determines `?OTP_RELEASE` and the
+    /// language features that are available.
+    pub otp_release: u32,
 }
 impl ParseConfig {
     pub fn new() -> Self {
@@ -79,6 +83,7 @@ impl Default for ParseConfig {
             include_paths: VecDeque::new(),
             code_paths: VecDeque::new(),
             macros: None,
+            otp_release: 26,
         }
     }
 }
@@ -155,6 +160,45 @@ impl GParse for ast::Expr {
     }
 }
 
+impl GParse for PreprocessedSource {
+    type Parser = ();
+    type Error = ParserError;
+    type Config = ParseConfig;
+    type Token = Preprocessed;
+
+    fn root_file_error(source: std::io::Error, path: std::path::PathBuf) -> Self::Error {
+        ParserError::RootFile { source, path }
+    }
+
+    fn parse<S>(parser: &Parser, err: &mut ParserErrorReceiver, source: S) -> Result<Self, ()>
+    where
+        S: Source,
+    {
+        error_tee(err, |mut errors| {
+            let scanner = Scanner::new(source);
+            let lexer = Lexer::new(scanner);
+            error_tee(&mut errors.clone().make_into_adapter(), |preproc_errors| {
+                let tokens = Preprocessor::new(parser, lexer, preproc_errors);
+                Self::parse_tokens(&mut errors, tokens)
+            })
+        })
+    }
+
+    fn parse_tokens<S: IntoIterator<Item = Preprocessed>>(
+        err: &mut ParserErrorReceiver,
+        tokens: S,
+    ) -> Result<Self, ()> {
+        let tokens = tokens
+            .into_iter()
+            .map(|token| token.map(LexicalToken::from))
+            .collect::<Result<Vec<_>, ()>>()?;
+        if err.is_failed() {
+            return Err(());
+        }
+        Ok(PreprocessedSource { tokens })
+    }
+}
+
 fn to_parse_result<T>(
     errs: &mut ParserErrorReceiver,
     result: Result<T, ParseError>,
@@ -916,6 +960,88 @@ bar() -> yay.
         );
     }
 
+    #[test]
+    fn parse_maybe_feature() {
+        let _result: Module = parse(
+            ParseConfig::default(),
+            Arc::new(CodeMap::new()),
+            r#"-module(foo).
+-feature(maybe_expr, enable).
+
+-if(?FEATURE_ENABLED(maybe_expr) andalso ?OTP_RELEASE >= 25).
+-define(HAS_MAYBE, true).
+-endif.
+
+foo(A) ->
+    maybe
+        {ok, B} ?= bar(A),
+        C = B + 1,
+        {ok, D} ?= bar(C),
+        D
+    else
+        {error, Reason} -> Reason;
+        _ -> ?HAS_MAYBE
+    end.
+
+bar(X) -> {ok, X}.
+"#,
+        );
+    }
+
+    #[test]
+    fn parse_maybe_atom_without_feature() {
+        let _result: Module = parse(
+            ParseConfig::default(),
+            Arc::new(CodeMap::new()),
+            r#"-module(foo).
+
+-if(?FEATURE_AVAILABLE(maybe_expr) andalso not ?FEATURE_ENABLED(maybe_expr)).
+-define(VALUE, {maybe, else}).
+-endif.
+
+foo() -> ?VALUE.
+"#,
+        );
+    }
+
+    #[test]
+    fn preprocess_to_source() {
+        use crate::preprocessor::{MacroDef, MacroIdent};
+
+        let codemap = Arc::new(CodeMap::new());
+        let mut macros = MacroContainer::new();
+        macros.insert(MacroIdent::Const(Symbol::intern("DEBUG")), MacroDef::Boolean(true));
+        let mut config = ParseConfig::default();
+        config.macros = Some(macros);
+
+        let result: PreprocessedSource = parse(
+            config,
+            codemap.clone(),
+            "-module(foo).
+-ifdef(DEBUG).
+-define(LEVEL, debug).
+-else.
+-define(LEVEL, info).
+-endif.
+
+level() -> {?LEVEL, 'Quoted', \"str\", $a}.
+",
+        );
+        assert_eq!(
+            result.to_source(&codemap),
+            "-file(\"<nofile>\", 1).
+-module(foo).
+
+
+
+
+
+
+level() -> {debug, 'Quoted', \"str\", $a}.
+"
+        );
+    }
+
     #[test]
     fn parse_elixir_enum_erl() {
         use std::io::Read;
@@ -107,6 +107,19 @@ pub enum PreprocessorError {
         message: Symbol,
         as_error: bool,
     },
+
+    #[snafu(display("unknown feature '{}'", name))]
+    UnknownFeature { span: SourceSpan, name: Symbol },
+
+    #[snafu(display("feature '{}' is not available in OTP {}", name, otp_release))]
+    FeatureUnavailable {
+        span: SourceSpan,
+        name: Symbol,
+        otp_release: u32,
+    },
+
+    #[snafu(display("invalid feature directive"))]
+    InvalidFeatureAction { span: SourceSpan },
 }
 impl PreprocessorError {
     pub fn to_diagnostic(&self) -> Diagnostic {
@@ -209,6 +222,15 @@ impl PreprocessorError {
                             .with_message(reason.to_owned())
                     ])
             }
+            PreprocessorError::BadMacroCall { call, def: MacroDef::Feature(_), reason, .. } => {
+                let span = call.span();
+                Diagnostic::error()
+                    .with_message(self.to_string())
+                    .with_labels(vec![
+                        Label::primary(span.source_id(), span)
+                            .with_message(reason.to_owned())
+                    ])
+            }
             PreprocessorError::BadMacroCall { call, def, reason, .. } => {
                 let secondary_span = match def {
                     MacroDef::Static(ref define) => define.span(),
@@ -271,6 +293,26 @@ impl PreprocessorError {
                         Label::primary(span.source_id(), *span).with_message(message_str),
                     ])
             }
+            PreprocessorError::UnknownFeature { span, .. } =>
+                Diagnostic::error()
+                    .with_message(self.to_string())
+                    .with_labels(vec![
+                        Label::primary(span.source_id(), *span)
+                    ]),
+            PreprocessorError::FeatureUnavailable { span, .. } =>
+                Diagnostic::error()
+                    .with_message(self.to_string())
+                    .with_labels(vec![
+                        Label::primary(span.source_id(), *span)
+                            .with_message("the target OTP release can be changed in the parser configuration")
+                    ]),
+            PreprocessorError::InvalidFeatureAction { span } =>
+                Diagnostic::error()
+                    .with_message(self.to_string())
+                    .with_labels(vec![
+                        Label::primary(span.source_id(), *span)
+                            .with_message("expected 'enable' or 'disable'")
+                    ]),
         }
     }
 }
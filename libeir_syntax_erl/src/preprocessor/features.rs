@@ -0,0 +1,105 @@
+//! Language features that are gated behind `-feature(Name, enable).`
+//!
+//! See [Features](https://www.erlang.org/doc/reference_manual/features.html)
+//! for detailed information.
+
+use std::collections::HashSet;
+
+use crate::lexer::{LexicalToken, Symbol, Token};
+
+/// A known language feature.
+pub struct Feature {
+    pub name: &'static str,
+    /// The first OTP release where the feature can be enabled.
+    pub available_since: u32,
+    /// The first OTP release where the feature is enabled by default.
+    pub enabled_since: Option<u32>,
+    /// The reserved words introduced by the feature.
+    pub keywords: &'static [&'static str],
+}
+
+pub const FEATURES: &[Feature] = &[Feature {
+    name: "maybe_expr",
+    available_since: 25,
+    enabled_since: Some(27),
+    keywords: &["maybe", "else"],
+}];
+
+/// The features enabled at a given point in a module.
+#[derive(Debug, Clone)]
+pub struct FeatureSet {
+    otp_release: u32,
+    enabled: HashSet<&'static str>,
+}
+impl FeatureSet {
+    /// Creates the set of features enabled by default in the given release.
+    pub fn new(otp_release: u32) -> Self {
+        let enabled = FEATURES
+            .iter()
+            .filter(|f| f.enabled_since.map(|r| otp_release >= r).unwrap_or(false))
+            .map(|f| f.name)
+            .collect();
+        FeatureSet {
+            otp_release,
+            enabled,
+        }
+    }
+
+    pub fn otp_release(&self) -> u32 {
+        self.otp_release
+    }
+
+    fn get(name: Symbol) -> Option<&'static Feature> {
+        let name = name.as_str();
+        FEATURES.iter().find(|f| f.name == &*name)
+    }
+
+    /// Whether the feature is known.
+    pub fn exists(&self, name: Symbol) -> bool {
+        Self::get(name).is_some()
+    }
+
+    /// Whether the feature can be enabled in the target release.
+    pub fn is_available(&self, name: Symbol) -> bool {
+        Self::get(name)
+            .map(|f| self.otp_release >= f.available_since)
+            .unwrap_or(false)
+    }
+
+    pub fn is_enabled(&self, name: Symbol) -> bool {
+        Self::get(name)
+            .map(|f| self.enabled.contains(f.name))
+            .unwrap_or(false)
+    }
+
+    /// Enables or disables an available feature.
+    pub fn set_enabled(&mut self, name: Symbol, enabled: bool) {
+        assert!(self.is_available(name));
+        let feature = Self::get(name).unwrap();
+        if enabled {
+            self.enabled.insert(feature.name);
+        } else {
+            self.enabled.remove(feature.name);
+        }
+    }
+
+    /// The lexer always produces keyword tokens for reserved words introduced
+    /// by features. This turns them back into atoms when the feature is not
+    /// enabled.
+    pub fn demote_keyword(&self, token: LexicalToken) -> LexicalToken {
+        let word = match token.1 {
+            Token::Maybe => "maybe",
+            Token::Else => "else",
+            _ => return token,
+        };
+        let enabled = FEATURES
+            .iter()
+            .filter(|f| f.keywords.contains(&word))
+            .any(|f| self.enabled.contains(f.name));
+        if enabled {
+            token
+        } else {
+            LexicalToken(token.0, Token::Atom(Symbol::intern(word)), token.2)
+        }
+    }
+}
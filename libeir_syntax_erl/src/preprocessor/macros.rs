@@ -130,6 +130,8 @@ pub enum MacroDef {
     Static(Define),
     Dynamic(Vec<LexicalToken>),
     DelayedSubstitution(DelayedSubstitution),
+    /// `?FEATURE_AVAILABLE(Feature)` or `?FEATURE_ENABLED(Feature)`
+    Feature(FeatureMacro),
 }
 impl MacroDef {
     /// Returns `true` if this macro has variables, otherwise `false`.
@@ -140,10 +142,18 @@ impl MacroDef {
             MacroDef::String(_) => false,
             MacroDef::Boolean(_) => false,
             MacroDef::DelayedSubstitution(_) => false,
+            MacroDef::Feature(_) => true,
         }
     }
 }
 
+/// The predefined macros querying the state of a feature.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FeatureMacro {
+    Available,
+    Enabled,
+}
+
 /// Macro call.
 #[derive(Debug, Clone)]
 pub struct MacroCall {
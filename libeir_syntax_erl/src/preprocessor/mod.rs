@@ -1,7 +1,9 @@
 mod directive;
 mod errors;
+mod features;
 //mod evaluator;
 mod macros;
+mod output;
 mod preprocessor;
 mod token_reader;
 mod token_stream;
@@ -11,7 +13,9 @@ pub mod types;
 
 pub use self::directive::Directive;
 pub use self::errors::PreprocessorError;
-pub use self::macros::{MacroCall, MacroContainer, MacroDef, MacroIdent};
+pub use self::features::{Feature, FeatureSet, FEATURES};
+pub use self::macros::{FeatureMacro, MacroCall, MacroContainer, MacroDef, MacroIdent};
+pub use self::output::PreprocessedSource;
 pub use self::preprocessor::Preprocessor;
 
 use libeir_diagnostics::SourceIndex;
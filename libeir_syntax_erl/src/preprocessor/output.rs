@@ -0,0 +1,150 @@
+use std::fmt::Write;
+
+use libeir_diagnostics::{CodeMap, SourceId, SourceIndex};
+
+use crate::lexer::{DelayedSubstitution, LexicalToken, Token};
+
+/// Words that have to be quoted when used as atoms.
+const RESERVED_WORDS: &[&str] = &[
+    "after", "and", "andalso", "band", "begin", "bnot", "bor", "bsl", "bsr", "bxor", "case",
+    "catch", "cond", "div", "end", "fun", "if", "let", "not", "of", "or", "orelse", "receive",
+    "rem", "try", "when", "xor",
+];
+
+/// The fully preprocessed token stream of a source file, with includes
+/// inlined, conditional sections resolved and macros expanded.
+///
+/// Tokens keep the location they were read from. Tokens produced by a macro
+/// expansion are located at the macro call.
+pub struct PreprocessedSource {
+    pub tokens: Vec<LexicalToken>,
+}
+
+impl PreprocessedSource {
+    /// Prints the tokens back as Erlang source, in the manner of `erlc -P`.
+    ///
+    /// Tokens are kept on their original lines, and are separated by a space
+    /// unless they were adjacent in the source. Whenever the tokens move
+    /// between files, a `-file` attribute is emitted so that the output maps
+    /// back to the original sources. Included files are annotated with the
+    /// location of the include directive.
+    pub fn to_source(&self, codemap: &CodeMap) -> String {
+        let mut out = String::new();
+        let mut current: Option<(SourceId, usize)> = None;
+        let mut prev_end: Option<SourceIndex> = None;
+
+        for token in self.tokens.iter() {
+            let start = token.span().start();
+            let source_id = start.source_id();
+            let line = line_number(codemap, start);
+
+            match current {
+                Some((id, current_line)) if id == source_id && line >= current_line => {
+                    if line > current_line {
+                        for _ in current_line..line {
+                            out.push('\n');
+                        }
+                    } else if prev_end != Some(start) {
+                        out.push(' ');
+                    }
+                }
+                _ => {
+                    if current.is_some() {
+                        out.push('\n');
+                    }
+                    let name = codemap
+                        .name(source_id)
+                        .map(|n| n.to_string())
+                        .unwrap_or_else(|| "nofile".to_owned());
+                    if let Some(parent) = codemap.parent(source_id) {
+                        let parent_start = parent.start();
+                        if let Some(parent_name) = codemap.name(parent_start.source_id()) {
+                            writeln!(
+                                out,
+                                "%% included from {}:{}",
+                                parent_name,
+                                line_number(codemap, parent_start)
+                            )
+                            .unwrap();
+                        }
+                    }
+                    writeln!(out, "-file(\"{}\", {}).", escape(&name, Some('"')), line).unwrap();
+                }
+            }
+            current = Some((source_id, line));
+            prev_end = Some(token.span().end());
+
+            write_token(&mut out, token.token());
+        }
+
+        out.push('\n');
+        out
+    }
+}
+
+fn line_number(codemap: &CodeMap, index: SourceIndex) -> usize {
+    codemap
+        .line_index(index.source_id(), index.index())
+        .map(|line| line.to_usize() + 1)
+        .unwrap_or(0)
+}
+
+fn write_token(out: &mut String, token: Token) {
+    match token {
+        Token::Char(c) => {
+            out.push('$');
+            match c {
+                ' ' => out.push_str("\\s"),
+                c => out.push_str(&escape(&c.to_string(), None)),
+            }
+        }
+        Token::Float(f) => write!(out, "{:?}", f.inner()).unwrap(),
+        Token::Atom(atom) => {
+            let name = atom.as_str();
+            if is_unquoted_atom(&name) {
+                out.push_str(&name);
+            } else {
+                write!(out, "'{}'", escape(&name, Some('\''))).unwrap();
+            }
+        }
+        Token::String(string) => {
+            write!(out, "\"{}\"", escape(&string.as_str(), Some('"'))).unwrap()
+        }
+        Token::DelayedSubstitution(DelayedSubstitution::FunctionName) => {
+            out.push_str("?FUNCTION_NAME")
+        }
+        Token::DelayedSubstitution(DelayedSubstitution::FunctionArity) => {
+            out.push_str("?FUNCTION_ARITY")
+        }
+        token => write!(out, "{}", token).unwrap(),
+    }
+}
+
+fn is_unquoted_atom(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_lowercase() => (),
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '@')
+        && !RESERVED_WORDS.contains(&name)
+}
+
+fn escape(string: &str, quote: Option<char>) -> String {
+    let mut out = String::with_capacity(string.len());
+    for c in string.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if Some(c) == quote => {
+                out.push('\\');
+                out.push(c);
+            }
+            c if c.is_control() => write!(out, "\\x{{{:X}}}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -14,9 +14,9 @@ use crate::lexer::{symbols, DelayedSubstitution, IdentToken, Lexed, LexicalToken
 use crate::parser::Parser;
 
 use super::errors;
-use super::macros::Stringify;
+use super::macros::{FeatureMacro, Stringify};
 use super::token_reader::{TokenBufferReader, TokenReader, TokenStreamReader};
-use super::{Directive, MacroCall, MacroContainer, MacroDef, MacroIdent};
+use super::{Directive, FeatureSet, MacroCall, MacroContainer, MacroDef, MacroIdent};
 use super::{Preprocessed, PreprocessorError, Result as PResult};
 
 type Errors<'a> = ErrorReceiverTee<'a, PreprocessorError, PreprocessorError>;
@@ -47,6 +47,7 @@ pub struct Preprocessor<'a, Reader: TokenReader> {
     expanded_tokens: VecDeque<LexicalToken>,
     warnings_as_errors: bool,
     no_warn: bool,
+    features: FeatureSet,
 }
 impl<'a, S> Preprocessor<'a, TokenStreamReader<S>>
 where
@@ -70,6 +71,27 @@ where
             MacroDef::DelayedSubstitution(DelayedSubstitution::FunctionArity),
         );
 
+        let otp_release = parser.config.otp_release;
+        macros.insert(
+            MacroIdent::Const(Symbol::intern("OTP_RELEASE")),
+            MacroDef::Dynamic(vec![LexicalToken(
+                SourceIndex::UNKNOWN,
+                Token::Integer((otp_release as i64).into()),
+                SourceIndex::UNKNOWN,
+            )]),
+        );
+        // The feature macros were introduced together with features in OTP 25
+        if otp_release >= 25 {
+            macros.insert(
+                MacroIdent::Func(Symbol::intern("FEATURE_AVAILABLE"), 1),
+                MacroDef::Feature(FeatureMacro::Available),
+            );
+            macros.insert(
+                MacroIdent::Func(Symbol::intern("FEATURE_ENABLED"), 1),
+                MacroDef::Feature(FeatureMacro::Enabled),
+            );
+        }
+
         Preprocessor {
             errors,
             codemap: parser.codemap.clone(),
@@ -84,6 +106,7 @@ where
             expanded_tokens: VecDeque::new(),
             warnings_as_errors: parser.config.warnings_as_errors,
             no_warn: parser.config.no_warn,
+            features: FeatureSet::new(otp_release),
         }
     }
 }
@@ -108,6 +131,7 @@ where
             expanded_tokens: VecDeque::new(),
             warnings_as_errors: self.warnings_as_errors,
             no_warn: self.no_warn,
+            features: self.features.clone(),
         }
     }
 
@@ -118,7 +142,7 @@ where
     fn next_token(&mut self) -> Result<Option<LexicalToken>, ()> {
         loop {
             if let Some(token) = self.expanded_tokens.pop_front() {
-                return Ok(Some(token));
+                return Ok(Some(self.features.demote_keyword(token)));
             }
             if self.can_directive_start {
                 match self.try_read_directive()? {
@@ -155,7 +179,7 @@ where
                 } else {
                     self.can_directive_start = false;
                 }
-                return Ok(Some(token));
+                return Ok(Some(self.features.demote_keyword(token)));
             } else {
                 break;
             }
@@ -269,6 +293,34 @@ where
                 span.end(),
             )]
             .into()),
+            MacroDef::Feature(kind) => {
+                let arg = call.args.as_ref().and_then(|a| a.iter().next());
+                let name = match arg.map(|a| a.tokens.as_slice()) {
+                    Some([LexicalToken(_, Token::Atom(name), _)]) => Some(*name),
+                    _ => None,
+                };
+                // Unknown features are neither available nor enabled
+                let name = match name {
+                    Some(name) => name,
+                    None => {
+                        return Err(PreprocessorError::BadMacroCall {
+                            call,
+                            def: definition.clone(),
+                            reason: "expected the name of a feature".to_owned(),
+                        })
+                    }
+                };
+                let result = match kind {
+                    FeatureMacro::Available => self.features.is_available(name),
+                    FeatureMacro::Enabled => self.features.is_enabled(name),
+                };
+                let atom = if result {
+                    symbols::True
+                } else {
+                    symbols::False
+                };
+                Ok(vec![LexicalToken(span.start(), Token::Atom(atom), span.end())].into())
+            }
         }
     }
 
@@ -448,6 +500,35 @@ where
                     self.errors.warning(err);
                 }
             }
+            Directive::Feature(ref d) if !ignore => {
+                let name = d.name();
+                let enable = match d.action.symbol().as_str().get() {
+                    "enable" => true,
+                    "disable" => false,
+                    _ => {
+                        let err = PreprocessorError::InvalidFeatureAction {
+                            span: d.action.span(),
+                        };
+                        return error_into!(self.errors, Err(err));
+                    }
+                };
+                if !self.features.exists(name) {
+                    let err = PreprocessorError::UnknownFeature {
+                        span: d.span(),
+                        name,
+                    };
+                    return error_into!(self.errors, Err(err));
+                }
+                if !self.features.is_available(name) {
+                    let err = PreprocessorError::FeatureUnavailable {
+                        span: d.span(),
+                        name,
+                        otp_release: self.features.otp_release(),
+                    };
+                    return error_into!(self.errors, Err(err));
+                }
+                self.features.set_enabled(name, enable);
+            }
             Directive::File(ref f) if !ignore => {
                 // TODO
                 println!("TODO file directive {}", f);
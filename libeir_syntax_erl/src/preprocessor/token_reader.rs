@@ -129,7 +129,7 @@ impl TokenReader for TokenBufferReader {
             path: path.to_owned(),
             span: directive,
         })?;
-        let id = self.codemap.add(path, content);
+        let id = self.codemap.add_child(path, content, directive);
         let file = self.codemap.get(id).unwrap();
         let source = FileMapSource::new(file);
         let scanner = Scanner::new(source);
@@ -0,0 +1,168 @@
+use std::rc::Rc;
+
+use super::lower;
+
+use libeir_intern::Ident;
+use libeir_ir::FunctionIdent;
+use libeir_passes::PassManager;
+use libeir_syntax_erl::ParseConfig;
+
+use libeir_interpreter::{ErlEq, ErlExactEq, Term, VMState};
+
+#[test]
+fn test_erlang_bifs() {
+    let _ = env_logger::try_init();
+
+    let mut eir_mod = lower(
+        r#"
+-module(woo).
+
+lists() ->
+    {[1, 2] ++ [3], list_to_atom("abc"), integer_to_list(-255, 16),
+     binary_to_list(<<1, 2>>), tuple_to_list({a, b}), erlang:make_tuple(2, x),
+     erlang:append_element({a}, b), list_to_integer("-12")}.
+
+binaries() ->
+    {iolist_to_binary([$a, [<<"bc">>], <<"d">> | <<"e">>]),
+     byte_size(<<1, 2, 3:4>>), bit_size(<<1, 2, 3:4>>),
+     binary_part(<<"hello">>, {1, 3}), atom_to_binary(abc, utf8)}.
+
+arith(A, B) ->
+    {A band B, A bor B, A bxor B, A bsl 2, -A bsr 1, A div B, A rem B, -A rem B}.
+
+floats(F) -> {float(3), round(F), trunc(F), round(-F), is_float(F)}.
+
+types(A) -> {is_boolean(A), is_reference(A), is_reference(make_ref())}.
+
+exc(throw) -> try throw(foo) catch throw:R -> {thrown, R} end;
+exc(error) -> try error(foo, [1]) catch error:R -> {error, R} end;
+exc(exit) -> try exit(foo) catch exit:R -> {exit, R} end;
+exc(badarith) -> try 1 div 0 catch error:R -> {error, R} end.
+
+add(A, B) -> A + B.
+
+applies(A) -> {apply(fun add/2, [A, 1]), apply(woo, add, [A, 2]),
+               apply(fun(X) -> X * 2 end, [A]), apply(fun(X, Y) -> X * Y end, [A, 3]),
+               try apply(fun(X) -> X end, []) catch error:{badarity, _} -> badarity end,
+               try apply(A, []) catch error:{badfun, _} -> badfun end,
+               is_function(fun(X, Y) -> X + Y end, 2), is_function(fun(X, Y) -> X + Y end, 1)}.
+
+hash(T) -> erlang:phash2(T) =:= erlang:phash2(T) andalso erlang:phash2(T, 16) < 16.
+
+hashes() ->
+    {erlang:phash2([]), erlang:phash2(a), erlang:phash2(1), erlang:phash2(-1),
+     erlang:phash2({ok, 1}), erlang:phash2([1, 2, 3]), erlang:phash2(<<"hello">>),
+     erlang:phash2([a, 97]), erlang:phash2({ok, 1}, 16)}.
+"#,
+        ParseConfig::default(),
+    )
+    .unwrap();
+
+    let mut pass_manager = PassManager::default();
+    pass_manager.run(&mut eir_mod);
+
+    let mut vm = VMState::new();
+    vm.add_builtin_modules();
+    vm.add_erlang_module(eir_mod);
+
+    let bin = |bytes: &[u8]| -> Rc<Term> { Term::Binary(Rc::new(bytes.to_vec().into())).into() };
+    let int = |num: i64| -> Rc<Term> { Term::new_i64(num).into() };
+    let atom = |name: &str| -> Rc<Term> { Term::new_atom(name).into() };
+    let list = |elems: &[Rc<Term>]| Term::slice_to_list(elems, Term::Nil.into());
+    let string = |string: &str| {
+        let chars: Vec<_> = string.chars().map(|c| int(c as i64)).collect();
+        list(&chars)
+    };
+    let tup = |elems: Vec<Rc<Term>>| -> Rc<Term> { Term::Tuple(elems).into() };
+    let mut call = |name: &str, args: &[Term]| {
+        let fun = FunctionIdent {
+            module: Ident::from_str("woo"),
+            name: Ident::from_str(name),
+            arity: args.len(),
+        };
+        vm.call(&fun, args)
+    };
+
+    let res = call("lists", &[]).unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![
+        list(&[int(1), int(2), int(3)]),
+        atom("abc"),
+        string("-FF"),
+        list(&[int(1), int(2)]),
+        list(&[atom("a"), atom("b")]),
+        tup(vec![atom("x"), atom("x")]),
+        tup(vec![atom("a"), atom("b")]),
+        int(-12),
+    ])));
+
+    let res = call("binaries", &[]).unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![
+        bin(b"abcde"),
+        int(3),
+        int(20),
+        bin(b"ell"),
+        bin(b"abc"),
+    ])));
+
+    let res = call("arith", &[Term::new_i64(13), Term::new_i64(5)]).unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![
+        int(5),
+        int(13),
+        int(8),
+        int(52),
+        int(-7),
+        int(2),
+        int(3),
+        int(-3),
+    ])));
+
+    let res = call("floats", &[Term::Float(2.5.into())]).unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![
+        Term::Float(3.0.into()).into(),
+        int(3),
+        int(2),
+        int(-3),
+        atom("true"),
+    ])));
+
+    let res = call("types", &[Term::new_atom("true")]).unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![atom("true"), atom("false"), atom("true")])));
+
+    let res = call("exc", &[Term::new_atom("throw")]).unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![atom("thrown"), atom("foo")])));
+    let res = call("exc", &[Term::new_atom("error")]).unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![atom("error"), atom("foo")])));
+    let res = call("exc", &[Term::new_atom("exit")]).unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![atom("exit"), atom("foo")])));
+    let res = call("exc", &[Term::new_atom("badarith")]).unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![atom("error"), atom("badarith")])));
+
+    let res = call("applies", &[Term::new_i64(4)]).unwrap();
+    assert!(res.erl_eq(&*tup(vec![
+        int(5),
+        int(6),
+        int(8),
+        int(12),
+        atom("badarity"),
+        atom("badfun"),
+        atom("true"),
+        atom("false"),
+    ])));
+
+    let res = call("hash", &[Term::new_atom("woo")]).unwrap();
+    assert!(res.erl_exact_eq(&Term::new_atom("true")));
+
+    // The hash only depends on the contents of the term
+    let res = call("hashes", &[]).unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![
+        int(113427502),
+        int(97),
+        int(2614250),
+        int(44071773),
+        int(101439905),
+        int(25788620),
+        int(47480723),
+        int(78630706),
+        int(1),
+    ])));
+}
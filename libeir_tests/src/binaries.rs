@@ -0,0 +1,124 @@
+use std::rc::Rc;
+
+use super::lower;
+
+use libeir_intern::Ident;
+use libeir_ir::FunctionIdent;
+use libeir_passes::PassManager;
+use libeir_syntax_erl::ParseConfig;
+
+use libeir_interpreter::{ErlExactEq, Term, VMState};
+
+#[test]
+fn test_binary_construct() {
+    let _ = env_logger::try_init();
+
+    let mut eir_mod = lower(
+        r#"
+-module(woo).
+
+int(A, B, C) -> <<A:8, B:16/little, C:4/signed, 5:4>>.
+
+float(F) -> <<F:32/float, F/float, F:16/float-little>>.
+
+units(A, B) -> <<A:2/integer-unit:8, B:1/binary-unit:16>>.
+
+utf(A) -> <<A/utf8, A/utf16, A/utf32-little>>.
+
+all(Bin, Bits) -> <<Bin/binary, Bits/bitstring>>.
+
+prefix(Bin) -> <<Bin:2/binary>>.
+
+bad_utf(A) -> try <<A/utf8>> catch error:badarg -> badarg end.
+
+bad_binary(B) -> try <<B/binary>> catch error:badarg -> badarg end.
+
+bad_size(A, N) -> try <<A:N>> catch error:badarg -> badarg end.
+"#,
+        ParseConfig::default(),
+    )
+    .unwrap();
+
+    let mut pass_manager = PassManager::default();
+    pass_manager.run(&mut eir_mod);
+
+    let mut vm = VMState::new();
+    vm.add_builtin_modules();
+    vm.add_erlang_module(eir_mod);
+
+    let bin = |bytes: &[u8]| Term::Binary(Rc::new(bytes.to_vec().into()));
+    let int = |num: i64| Term::new_i64(num);
+    let badarg = Term::new_atom("badarg");
+    let mut call = |name: &str, args: &[Term]| {
+        let fun = FunctionIdent {
+            module: Ident::from_str("woo"),
+            name: Ident::from_str(name),
+            arity: args.len(),
+        };
+        vm.call(&fun, args)
+    };
+
+    let res = call("int", &[int(1), int(0x1234), int(-1)]).unwrap();
+    assert!(res.erl_exact_eq(&bin(&[0x01, 0x34, 0x12, 0xf5])));
+
+    let res = call("float", &[Term::Float(1.5.into())]).unwrap();
+    let expected = bin(&[
+        0x3f, 0xc0, 0x00, 0x00, 0x3f, 0xf8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3e,
+    ]);
+    assert!(res.erl_exact_eq(&expected));
+    let res = call("float", &[int(2)]).unwrap();
+    let expected = bin(&[
+        0x40, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40,
+    ]);
+    assert!(res.erl_exact_eq(&expected));
+
+    let res = call("units", &[int(0x0102), bin(&[0x03, 0x04, 0x05])]).unwrap();
+    assert!(res.erl_exact_eq(&bin(&[0x01, 0x02, 0x03, 0x04])));
+
+    let res = call("utf", &[int(0x1f600)]).unwrap();
+    let expected = bin(&[
+        0xf0, 0x9f, 0x98, 0x80, 0xd8, 0x3d, 0xde, 0x00, 0x00, 0xf6, 0x01, 0x00,
+    ]);
+    assert!(res.erl_exact_eq(&expected));
+
+    let bits = Term::BinarySlice {
+        buf: Rc::new(vec![0b1010_0000].into()),
+        bit_offset: 0,
+        bit_length: 3,
+    };
+    let res = call("all", &[bin(b"ab"), bits]).unwrap();
+    let expected = Term::BinarySlice {
+        buf: Rc::new(vec![b'a', b'b', 0b1010_0000].into()),
+        bit_offset: 0,
+        bit_length: 19,
+    };
+    assert!(res.erl_exact_eq(&expected));
+
+    let res = call("prefix", &[bin(b"abc")]).unwrap();
+    assert!(res.erl_exact_eq(&bin(b"ab")));
+    assert!(call("prefix", &[bin(b"a")]).is_err());
+
+    let res = call("bad_utf", &[int(0xd800)]).unwrap();
+    assert!(res.erl_exact_eq(&badarg));
+    let res = call("bad_utf", &[int(0x110000)]).unwrap();
+    assert!(res.erl_exact_eq(&badarg));
+    let res = call("bad_utf", &[Term::new_atom("a")]).unwrap();
+    assert!(res.erl_exact_eq(&badarg));
+
+    let res = call("bad_binary", &[int(1)]).unwrap();
+    assert!(res.erl_exact_eq(&badarg));
+    let half = Term::BinarySlice {
+        buf: Rc::new(vec![0xff].into()),
+        bit_offset: 0,
+        bit_length: 4,
+    };
+    let res = call("bad_binary", &[half]).unwrap();
+    assert!(res.erl_exact_eq(&badarg));
+
+    let res = call("bad_size", &[int(1), int(-1)]).unwrap();
+    assert!(res.erl_exact_eq(&badarg));
+    let res = call("bad_size", &[int(1), Term::new_atom("a")]).unwrap();
+    assert!(res.erl_exact_eq(&badarg));
+    let res = call("bad_size", &[int(1), int(8)]).unwrap();
+    assert!(res.erl_exact_eq(&bin(&[1])));
+}
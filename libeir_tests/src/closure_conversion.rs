@@ -0,0 +1,101 @@
+use super::lower;
+
+use libeir_intern::Ident;
+use libeir_ir::{FunctionIdent, Module};
+use libeir_lowerutils::closure_convert;
+use libeir_passes::PassManager;
+use libeir_syntax_erl::ParseConfig;
+
+use libeir_interpreter::{ErlExactEq, Term, VMState};
+
+/// Replaces every function of the module with its closure converted form,
+/// along with the lambda lifted functions.
+fn closure_convert_module(module: &Module) -> Module {
+    let mut converted = Module::new(module.name());
+    for def in module.function_iter() {
+        let conversion = closure_convert(def.function());
+        for fun in std::iter::once(conversion.root).chain(conversion.lifted) {
+            let ident = *fun.ident();
+            *converted
+                .add_function(fun.span(), ident.name, ident.arity)
+                .function_mut() = fun;
+        }
+    }
+    converted
+}
+
+#[test]
+fn closure_converted_behaves_the_same() {
+    let _ = env_logger::try_init();
+
+    let mut eir_mod = lower(
+        r#"
+-module(woo).
+
+adder(N) -> fun(X) -> X + N end.
+
+apply_adder(A, B) ->
+    F = adder(A),
+    F(B).
+
+with_env(L) -> lists:map(fun(X) -> {X, length(L)} end, L).
+
+nested(A) ->
+    F = fun(B) -> fun(C) -> {A, B, C} end end,
+    G = F(b),
+    G(c).
+
+scale(A, L) -> lists:foldl(fun(X, Acc) -> [X * A | Acc] end, [], L).
+
+countdown(0) -> done;
+countdown(N) ->
+    F = fun() -> countdown(N - 1) end,
+    F().
+"#,
+        ParseConfig::default(),
+    )
+    .unwrap();
+
+    let mut pass_manager = PassManager::default();
+    pass_manager.run(&mut eir_mod);
+
+    let converted = closure_convert_module(&eir_mod);
+    assert!(converted.function_iter().count() > eir_mod.function_iter().count());
+
+    let mut vm = VMState::new();
+    vm.add_builtin_modules();
+    vm.add_erlang_module(eir_mod);
+
+    let mut converted_vm = VMState::new();
+    converted_vm.add_builtin_modules();
+    converted_vm.add_erlang_module(converted);
+
+    let list = |elems: &[i64]| {
+        let elems: Vec<_> = elems.iter().map(|e| Term::new_i64(*e).into()).collect();
+        Term::slice_to_list(&elems, Term::Nil.into())
+    };
+
+    let calls: Vec<(&str, Vec<Term>)> = vec![
+        ("apply_adder", vec![Term::new_i64(2), Term::new_i64(3)]),
+        ("with_env", vec![(*list(&[1, 2, 3])).clone()]),
+        ("nested", vec![Term::new_atom("a")]),
+        ("scale", vec![Term::new_i64(3), (*list(&[1, 2, 3])).clone()]),
+        ("countdown", vec![Term::new_i64(3)]),
+    ];
+    for (name, args) in calls.iter() {
+        let fun = FunctionIdent {
+            module: Ident::from_str("woo"),
+            name: Ident::from_str(name),
+            arity: args.len(),
+        };
+        let expected = vm.call(&fun, args).unwrap();
+        let result = converted_vm.call(&fun, args).unwrap();
+        assert!(
+            result.erl_exact_eq(&*expected),
+            "{}: {} != {}",
+            name,
+            expected,
+            result
+        );
+    }
+}
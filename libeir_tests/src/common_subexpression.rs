@@ -0,0 +1,102 @@
+//! Checks that `CommonSubexpressionPass` removes the repeated tests of
+//! lowered Erlang code, without changing its results.
+
+use crate::lower;
+
+use libeir_intern::Ident;
+use libeir_ir::{FunctionIdent, Module, OpKind};
+use libeir_passes::{CommonSubexpressionPass, CompilePatternPass, SimplifyCfgPass, ValidatePass};
+use libeir_syntax_erl::ParseConfig;
+
+use libeir_interpreter::{ErlExactEq, Term, VMState};
+
+const SOURCE: &str = "
+-module(cse).
+
+-record(point, {x, y}).
+
+sum(P) -> P#point.x + P#point.y.
+";
+
+fn lower_cse(cse: bool) -> Module {
+    let mut eir_mod = lower(SOURCE, ParseConfig::default()).unwrap();
+
+    let mut pass_manager = libeir_passes::PassManager::new();
+    pass_manager.push_function_pass(ValidatePass::new());
+    pass_manager.push_function_pass(CompilePatternPass::new());
+    pass_manager.push_function_pass(SimplifyCfgPass::new());
+    pass_manager.push_function_pass(ValidatePass::new());
+    if cse {
+        pass_manager.push_function_pass(CommonSubexpressionPass::new());
+        pass_manager.push_function_pass(ValidatePass::new());
+    }
+    pass_manager.run(&mut eir_mod);
+
+    eir_mod
+}
+
+fn count_matches(module: &Module, ident: &FunctionIdent) -> usize {
+    let fun = module[module.ident_index(ident).unwrap()].function();
+    fun.block_graph()
+        .dfs_iter()
+        .filter(|block| match fun.block_kind(*block) {
+            Some(OpKind::Match { .. }) => true,
+            _ => false,
+        })
+        .count()
+}
+
+#[test]
+fn repeated_record_test() {
+    let _ = env_logger::try_init();
+
+    let sum = FunctionIdent {
+        module: Ident::from_str("cse"),
+        name: Ident::from_str("sum"),
+        arity: 1,
+    };
+
+    let before = lower_cse(false);
+    let after = lower_cse(true);
+
+    // Both field accesses test that `P` is a tuple of arity 3, the second
+    // test is dominated by the first.
+    assert_eq!(
+        count_matches(&after, &sum),
+        count_matches(&before, &sum) - 1
+    );
+
+    let mut before_vm = VMState::new();
+    before_vm.add_builtin_modules();
+    before_vm.add_erlang_module(before);
+
+    let mut after_vm = VMState::new();
+    after_vm.add_builtin_modules();
+    after_vm.add_erlang_module(after);
+
+    let point = Term::Tuple(vec![
+        Term::new_atom("point").into(),
+        Term::new_i64(1).into(),
+        Term::new_i64(2).into(),
+    ]);
+    let other = Term::Tuple(vec![
+        Term::new_atom("other").into(),
+        Term::new_i64(1).into(),
+        Term::new_i64(2).into(),
+    ]);
+
+    for arg in [point.clone(), other, Term::new_atom("foo")].iter() {
+        let args = &[arg.clone()];
+        match (before_vm.call(&sum, args), after_vm.call(&sum, args)) {
+            (Ok(expected), Ok(actual)) => assert!(expected.erl_exact_eq(&*actual)),
+            (Err(expected), Err(actual)) => {
+                assert!(expected.0.erl_exact_eq(&*actual.0));
+                assert!(expected.1.erl_exact_eq(&*actual.1));
+            }
+            (expected, actual) => panic!("{:?} != {:?}", expected, actual),
+        }
+    }
+
+    let result = after_vm.call(&sum, &[point]).unwrap();
+    assert_eq!(result.as_i64(), Some(3));
+}
@@ -0,0 +1,155 @@
+use std::rc::Rc;
+
+use super::lower;
+
+use libeir_intern::Ident;
+use libeir_ir::{FunctionIdent, Module};
+use libeir_passes::PassManager;
+use libeir_syntax_erl::ParseConfig;
+
+use libeir_interpreter::{ErlExactEq, Term, VMState};
+
+const SOURCE: &str = "
+-module(woo).
+
+classify(X) when is_integer(X), X > 10 -> big;
+classify(X) when is_integer(X) -> small;
+classify({A, B}) -> A + B;
+classify([H | _]) -> H;
+classify(_) -> other.
+
+safe_div(A, B) ->
+    try A / B of
+        R -> {ok, R}
+    catch
+        error:badarith -> error
+    end.
+
+wait(Timeout) ->
+    receive
+        {msg, M} -> M;
+        stop -> stopped
+    after Timeout -> timeout
+    end.
+
+adder(A) -> fun(B) -> A + B end.
+
+apply_adder(A, B) ->
+    F = adder(A),
+    F(B).
+
+reverse_all(L) -> lists:reverse(classify(L)).
+";
+
+fn assert_round_trip(text: &str) {
+    let parsed = libeir_syntax_core::parse(text).unwrap();
+    assert_eq!(parsed.0.to_text(), text);
+}
+
+/// Prints the module as Core Erlang, and lowers the text back into Eir.
+fn core_round_trip(eir_mod: &Module) -> Module {
+    let text = libeir_syntax_core::print_module(eir_mod).unwrap();
+    let parsed = libeir_syntax_core::parse(&text).unwrap();
+    let lowered = libeir_syntax_core::to_eir(&parsed.0).unwrap();
+
+    for fun_def in lowered.function_iter() {
+        let fun = fun_def.function();
+        let mut out = Vec::new();
+        fun.validate(&mut out);
+        assert!(out.len() == 0);
+    }
+
+    lowered
+}
+
+/// Runs the functions of `SOURCE` in the original module and in the one
+/// lowered back from Core Erlang, and compares the results.
+fn assert_same_results(eir_mod: Module) {
+    let lowered = core_round_trip(&eir_mod);
+
+    let mut vm = VMState::new();
+    vm.add_builtin_modules();
+    vm.add_erlang_module(eir_mod);
+
+    let mut lowered_vm = VMState::new();
+    lowered_vm.add_builtin_modules();
+    lowered_vm.add_erlang_module(lowered);
+
+    let int = |num: i64| -> Rc<Term> { Term::new_i64(num).into() };
+    let atom = |name: &str| -> Rc<Term> { Term::new_atom(name).into() };
+    let list = |elems: &[Rc<Term>]| Term::slice_to_list(elems, Term::Nil.into());
+
+    let calls: Vec<(&str, Vec<Rc<Term>>)> = vec![
+        ("classify", vec![int(20)]),
+        ("classify", vec![int(3)]),
+        ("classify", vec![Term::Tuple(vec![int(1), int(2)]).into()]),
+        ("classify", vec![list(&[atom("a"), atom("b")])]),
+        ("classify", vec![atom("foo")]),
+        ("safe_div", vec![int(6), int(3)]),
+        ("safe_div", vec![int(1), int(0)]),
+        ("apply_adder", vec![int(2), int(3)]),
+        ("reverse_all", vec![list(&[list(&[int(1), int(2)])])]),
+    ];
+    for (name, args) in calls.iter() {
+        let fun = FunctionIdent {
+            module: Ident::from_str("woo"),
+            name: Ident::from_str(name),
+            arity: args.len(),
+        };
+        let args: Vec<Term> = args.iter().map(|a| (**a).clone()).collect();
+        let expected = vm.call(&fun, &args).unwrap();
+        let result = lowered_vm.call(&fun, &args).unwrap();
+        assert!(
+            result.erl_exact_eq(&*expected),
+            "{}: {} != {}",
+            name,
+            expected,
+            result
+        );
+    }
+}
+
+#[test]
+fn print_lowered_module() {
+    let eir_mod = lower(SOURCE, ParseConfig::default()).unwrap();
+
+    let text = libeir_syntax_core::print_module(&eir_mod).unwrap();
+    assert_round_trip(&text);
+
+    assert!(text.starts_with("module 'woo' ["));
+    assert!(text.contains("'classify'/1 ="));
+    assert!(text.contains("call 'erlang':'is_integer'("));
+    assert!(text.contains("receive"));
+    assert!(text.contains("call 'lists':'reverse'("));
+    assert!(text.contains("apply 'classify'/1("));
+}
+
+#[test]
+fn print_optimized_module() {
+    let mut eir_mod = lower(SOURCE, ParseConfig::default()).unwrap();
+
+    let mut pass_manager = PassManager::default();
+    pass_manager.run(&mut eir_mod);
+
+    let text = libeir_syntax_core::print_module(&eir_mod).unwrap();
+    assert_round_trip(&text);
+
+    assert!(text.contains("'safe_div'/2 ="));
+    assert!(text.contains("'adder'/1 ="));
+}
+
+#[test]
+fn lowered_module_round_trips() {
+    let eir_mod = lower(SOURCE, ParseConfig::default()).unwrap();
+    assert_same_results(eir_mod);
+}
+
+#[test]
+fn optimized_module_round_trips() {
+    let mut eir_mod = lower(SOURCE, ParseConfig::default()).unwrap();
+
+    let mut pass_manager = PassManager::default();
+    pass_manager.run(&mut eir_mod);
+
+    assert_same_results(eir_mod);
+}
@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use super::lower_with_codemap;
+
+use libeir_diagnostics::CodeMap;
+use libeir_intern::{Ident, Symbol};
+use libeir_ir::FunctionIdent;
+use libeir_passes::PassManager;
+use libeir_syntax_erl::ParseConfig;
+
+use libeir_interpreter::VMState;
+
+#[test]
+fn test_call_counts() {
+    let _ = env_logger::try_init();
+
+    let codemap = Arc::new(CodeMap::new());
+    let mut eir_mod = lower_with_codemap(
+        "
+-module(woo).
+
+fib(0) -> 0;
+fib(1) -> 1;
+fib(N) -> fib(N - 1) + fib(N - 2).
+
+unused() -> ok.
+",
+        ParseConfig::default(),
+        codemap.clone(),
+    )
+    .unwrap();
+
+    let mut pass_manager = PassManager::default();
+    pass_manager.run(&mut eir_mod);
+
+    let fib = FunctionIdent {
+        module: Ident::from_str("woo"),
+        name: Ident::from_str("fib"),
+        arity: 1,
+    };
+    let unused = FunctionIdent {
+        module: Ident::from_str("woo"),
+        name: Ident::from_str("unused"),
+        arity: 0,
+    };
+
+    let mut vm = VMState::new();
+    vm.add_builtin_modules();
+    vm.add_erlang_module(eir_mod);
+
+    vm.enable_coverage();
+    assert!(vm.call(&fib, &[4.into()]).unwrap().as_i64() == Some(3));
+    let coverage = vm.take_coverage().unwrap();
+
+    // fib(4) results in 9 calls to fib/1
+    assert!(coverage.call_count(&fib) == 9);
+    assert!(coverage.call_count(&unused) == 0);
+
+    let mut report = Vec::new();
+    coverage.write_call_counts(&vm, &mut report).unwrap();
+    let report = String::from_utf8(report).unwrap();
+    let first = report.lines().next().unwrap();
+    assert!(first.trim() == "9 woo:fib/1");
+
+    let mut lcov = Vec::new();
+    coverage
+        .write_lcov(&vm, &[Symbol::intern("woo")], Some(&codemap), &mut lcov)
+        .unwrap();
+    let lcov = String::from_utf8(lcov).unwrap();
+    let lcov: Vec<_> = lcov.lines().collect();
+    assert!(lcov.contains(&"SF:nofile"));
+    assert!(lcov.contains(&"FN:4,woo:fib/1"));
+    assert!(lcov.contains(&"FN:8,woo:unused/0"));
+    assert!(lcov.contains(&"FNDA:9,woo:fib/1"));
+    assert!(lcov.contains(&"FNDA:0,woo:unused/0"));
+    // The function heads run for every call, the last clause for the 4 calls
+    // that recurse.
+    assert!(lcov.contains(&"DA:4,9"));
+    assert!(lcov.contains(&"DA:6,4"));
+    assert!(lcov.contains(&"DA:8,0"));
+    assert!(lcov.last() == Some(&"end_of_record"));
+}
@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use super::lower_with_codemap;
+
+use libeir_diagnostics::CodeMap;
+use libeir_intern::Ident;
+use libeir_ir::FunctionIdent;
+use libeir_passes::PassManager;
+use libeir_syntax_erl::ParseConfig;
+
+use libeir_interpreter::{Breakpoint, Debugger, ErlExactEq, StopReason, Term, VMState};
+
+const SOURCE: &str = "-module(woo).
+
+add(A, B) ->
+    C = A + B,
+    D = double(C),
+    D + 1.
+
+double(X) ->
+    X * 2.
+";
+
+fn load() -> (VMState, Arc<CodeMap>) {
+    let codemap = Arc::new(CodeMap::new());
+    let mut eir_mod = lower_with_codemap(SOURCE, ParseConfig::default(), codemap.clone()).unwrap();
+
+    let mut pass_manager = PassManager::default();
+    pass_manager.run(&mut eir_mod);
+
+    let mut vm = VMState::new();
+    vm.add_builtin_modules();
+    vm.add_erlang_module(eir_mod);
+
+    (vm, codemap)
+}
+
+fn ident(name: &str, arity: usize) -> FunctionIdent {
+    FunctionIdent {
+        module: Ident::from_str("woo"),
+        name: Ident::from_str(name),
+        arity,
+    }
+}
+
+fn assert_returned(dbg: &Debugger, expected: i64) {
+    match dbg.result() {
+        Some(Ok(ret)) => assert!(ret.erl_exact_eq(&Term::new_i64(expected))),
+        result => panic!("{:?}", result),
+    }
+}
+
+#[test]
+fn breakpoint_ids() {
+    let (vm, codemap) = load();
+    let mut dbg = Debugger::new(&vm, Some(codemap));
+
+    let add = dbg.add_breakpoint(Breakpoint::Function(ident("add", 2)));
+    let double = dbg.add_breakpoint(Breakpoint::Function(ident("double", 1)));
+
+    // Removing a breakpoint does not change the ids of the others
+    assert_eq!(
+        dbg.remove_breakpoint(add),
+        Some(Breakpoint::Function(ident("add", 2)))
+    );
+    assert_eq!(dbg.remove_breakpoint(add), None);
+    assert_eq!(
+        dbg.breakpoint(double),
+        Some(&Breakpoint::Function(ident("double", 1)))
+    );
+
+    let line = dbg.add_breakpoint(Breakpoint::Line {
+        file: "nofile".to_string(),
+        line: 9,
+    });
+    assert!(line != add && line != double);
+    let ids: Vec<usize> = dbg.breakpoints().map(|(id, _)| id).collect();
+    assert_eq!(ids, vec![double, line]);
+
+    dbg.start(&ident("add", 2), &[1.into(), 2.into()]);
+    assert_eq!(dbg.cont(), StopReason::Breakpoint(double));
+    let (fun, _block) = dbg.current_block().unwrap();
+    assert_eq!(*fun.fun.ident(), ident("double", 1));
+
+    assert_eq!(dbg.cont(), StopReason::Breakpoint(line));
+    assert_eq!(dbg.current_position().unwrap().line, 9);
+
+    assert_eq!(dbg.cont(), StopReason::Finished);
+    assert!(!dbg.is_running());
+    assert_returned(&dbg, 7);
+}
+
+#[test]
+fn stepping() {
+    let (vm, codemap) = load();
+    let mut dbg = Debugger::new(&vm, Some(codemap));
+
+    dbg.start(&ident("add", 2), &[1.into(), 2.into()]);
+    assert_eq!(dbg.step_block(), StopReason::Step);
+    let (fun, _block) = dbg.current_block().unwrap();
+    assert_eq!(*fun.fun.ident(), ident("add", 2));
+
+    // Every line step stops on a new line, until the call finishes
+    let mut lines = Vec::new();
+    let mut last = dbg.current_position();
+    loop {
+        match dbg.step_line() {
+            StopReason::Step => {
+                let position = dbg.current_position();
+                assert!(position.is_some());
+                assert!(position != last);
+                lines.push(position.as_ref().unwrap().line);
+                last = position;
+            }
+            StopReason::Finished => break,
+            reason => panic!("{:?}", reason),
+        }
+    }
+    assert!(lines.contains(&9));
+
+    assert_returned(&dbg, 7);
+}
+
+#[test]
+fn bindings_and_stack() {
+    let (vm, codemap) = load();
+    let mut dbg = Debugger::new(&vm, Some(codemap));
+
+    dbg.add_breakpoint(Breakpoint::Function(ident("double", 1)));
+    dbg.start(&ident("add", 2), &[1.into(), 2.into()]);
+    match dbg.cont() {
+        StopReason::Breakpoint(_) => (),
+        reason => panic!("{:?}", reason),
+    }
+
+    // `X` is bound to `A + B`
+    let three = Term::new_i64(3);
+    let bindings = dbg.bindings();
+    assert!(bindings.iter().any(|(_, term)| term.erl_exact_eq(&three)));
+    let (value, _) = bindings
+        .iter()
+        .find(|(_, term)| term.erl_exact_eq(&three))
+        .unwrap();
+    assert!(dbg.binding(*value).unwrap().erl_exact_eq(&three));
+
+    // `add` is waiting for `double` to return
+    let frame = dbg
+        .stack()
+        .find(|frame| frame.ident == ident("add", 2))
+        .unwrap();
+    assert_eq!(dbg.frame_position(frame).unwrap().file, "nofile");
+
+    dbg.cont();
+    assert_returned(&dbg, 7);
+    assert!(dbg.bindings().is_empty());
+}
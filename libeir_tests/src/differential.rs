@@ -0,0 +1,224 @@
+//! Differential testing against results captured from BEAM.
+//!
+//! Every `.erl` file in `test_data/differential` has an `.etf` file next to
+//! it, produced by `generate.escript` with a real BEAM. It contains the
+//! result of calling every exported function of arity 0, as a list of
+//! `{Name, {ok, Value}}` or `{Name, {Class, Reason}}`.
+//!
+//! Each module is lowered and run in the interpreter through a set of
+//! compilation pipelines, and the results are compared to the captured ones.
+//! No BEAM is needed when running the tests.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use libeir_etf::{Reader, Term as EtfTerm};
+use libeir_intern::{Ident, Symbol};
+use libeir_ir::FunctionIdent;
+use libeir_passes::{
+    CompilePatternPass, NaiveInlineClosuresPass, PassManager, SimplifyCfgPass, ValidatePass,
+};
+use libeir_syntax_erl::ParseConfig;
+
+use libeir_interpreter::{ErlExactEq, MapTerm, Term, VMState};
+
+use crate::lower_file;
+
+/// A compilation pipeline the test cases are run through.
+/// Patterns always need to be compiled for the interpreter to be able to run
+/// the code, so the highest level only does that.
+struct CompileLevel {
+    name: &'static str,
+    make: fn() -> PassManager,
+}
+
+const COMPILE_LEVELS: &[CompileLevel] = &[
+    CompileLevel {
+        name: "high",
+        make: || {
+            let mut man = PassManager::new();
+            man.push_function_pass(CompilePatternPass::new());
+            man.push_function_pass(ValidatePass::new());
+            man
+        },
+    },
+    CompileLevel {
+        name: "normal",
+        make: PassManager::default,
+    },
+    CompileLevel {
+        name: "simplify_cfg",
+        make: || {
+            let mut man = PassManager::new();
+            man.push_function_pass(CompilePatternPass::new());
+            man.push_function_pass(ValidatePass::new());
+            man.push_function_pass(SimplifyCfgPass::new());
+            man.push_function_pass(ValidatePass::new());
+            man
+        },
+    },
+    CompileLevel {
+        name: "inline_closures",
+        make: || {
+            let mut man = PassManager::new();
+            man.push_function_pass(CompilePatternPass::new());
+            man.push_function_pass(ValidatePass::new());
+            man.push_function_pass(NaiveInlineClosuresPass::new());
+            man.push_function_pass(ValidatePass::new());
+            man
+        },
+    },
+];
+
+fn etf_to_term(etf: &EtfTerm) -> Rc<Term> {
+    match etf {
+        EtfTerm::Integer(int) => Term::new_i64(*int as i64).into(),
+        EtfTerm::BigInt(int) => Term::Integer(int.clone()).into(),
+        EtfTerm::Float(flt) => Term::Float((*flt).into()).into(),
+        EtfTerm::Atom(atom) => Term::new_atom(atom).into(),
+        EtfTerm::Nil => Term::Nil.into(),
+        EtfTerm::Tuple(entries) => Term::Tuple(entries.iter().map(etf_to_term).collect()).into(),
+        EtfTerm::List(head, tail) => {
+            let head: Vec<_> = head.iter().map(etf_to_term).collect();
+            Term::slice_to_list(&head, etf_to_term(tail))
+        }
+        EtfTerm::ByteList(bytes) => {
+            let head: Vec<_> = bytes
+                .iter()
+                .map(|b| Term::new_i64(*b as i64).into())
+                .collect();
+            Term::slice_to_list(&head, Term::Nil.into())
+        }
+        EtfTerm::Map(entries) => {
+            let mut map = MapTerm::new();
+            for (key, value) in entries.iter() {
+                map.insert(etf_to_term(key), etf_to_term(value));
+            }
+            Term::Map(map).into()
+        }
+        EtfTerm::Binary(bytes) => Term::Binary(Rc::new(bytes.clone().into())).into(),
+        EtfTerm::BitBinary(bytes, bits) => {
+            let bit_length = bytes.len().saturating_sub(1) * 8 + *bits as usize;
+            Term::BinarySlice {
+                buf: Rc::new(bytes.clone().into()),
+                bit_offset: 0,
+                bit_length,
+            }
+            .into()
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Expected {
+    Ok(Rc<Term>),
+    Raise(Symbol, Rc<Term>),
+}
+
+fn read_expected(path: &Path) -> Vec<(Symbol, Expected)> {
+    let bin = std::fs::read(path).unwrap();
+    let mut cursor = std::io::Cursor::new(&bin);
+    let mut reader = Reader::new(&mut cursor);
+    reader.header().unwrap();
+
+    let cases = match reader.term().unwrap() {
+        EtfTerm::List(cases, tail) if *tail == EtfTerm::Nil => cases,
+        EtfTerm::Nil => vec![],
+        term => panic!("malformed expected file {:?}: {:?}", path, term),
+    };
+
+    cases
+        .iter()
+        .map(|case| match case {
+            EtfTerm::Tuple(entries) => match entries.as_slice() {
+                [EtfTerm::Atom(name), EtfTerm::Tuple(result)] => {
+                    let expected = match result.as_slice() {
+                        [EtfTerm::Atom(kind), value] if kind == "ok" => {
+                            Expected::Ok(etf_to_term(value))
+                        }
+                        [EtfTerm::Atom(class), reason] => {
+                            Expected::Raise(Symbol::intern(class), etf_to_term(reason))
+                        }
+                        _ => panic!("malformed result {:?}", result),
+                    };
+                    (Symbol::intern(name), expected)
+                }
+                _ => panic!("malformed case {:?}", case),
+            },
+            _ => panic!("malformed case {:?}", case),
+        })
+        .collect()
+}
+
+fn run_case(
+    vm: &mut VMState,
+    module: Ident,
+    name: Symbol,
+) -> Result<Result<Rc<Term>, (Symbol, Rc<Term>)>, ()> {
+    let fun = FunctionIdent {
+        module,
+        name: Ident::with_empty_span(name),
+        arity: 0,
+    };
+
+    catch_unwind(AssertUnwindSafe(|| match vm.call(&fun, &[]) {
+        Ok(ret) => Ok(ret),
+        Err((class, reason, _trace)) => Err((class.as_atom().unwrap(), reason)),
+    }))
+    .map_err(|_| ())
+}
+
+/// Runs all cases of a single module at every compile level, returning a
+/// description of every mismatch.
+fn run_module(source: &Path) -> Vec<String> {
+    let expected = read_expected(&source.with_extension("etf"));
+
+    let mut failures = Vec::new();
+    for level in COMPILE_LEVELS {
+        let mut eir_mod = lower_file(source, ParseConfig::default())
+            .unwrap_or_else(|_| panic!("failed to lower {:?}", source));
+        (level.make)().run(&mut eir_mod);
+        let module = eir_mod.name();
+
+        let mut vm = VMState::new();
+        vm.add_builtin_modules();
+        vm.add_erlang_module(eir_mod);
+
+        for (name, expected) in expected.iter() {
+            let prefix = format!("{}:{}/0 ({})", module, name, level.name);
+            match (run_case(&mut vm, module, *name), expected) {
+                (Err(()), _) => failures.push(format!("{}: interpreter panicked", prefix)),
+                (Ok(Ok(ret)), Expected::Ok(exp)) if ret.erl_exact_eq(exp) => {}
+                (Ok(Err((class, reason))), Expected::Raise(exp_class, exp_reason))
+                    if class == *exp_class && reason.erl_exact_eq(exp_reason) => {}
+                (Ok(actual), expected) => failures.push(format!(
+                    "{}: expected {:?}, got {:?}",
+                    prefix, expected, actual
+                )),
+            }
+        }
+    }
+    failures
+}
+
+#[test]
+fn differential() {
+    let _ = env_logger::try_init();
+
+    let mut sources: Vec<PathBuf> = std::fs::read_dir("test_data/differential")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map(|e| e == "erl").unwrap_or(false))
+        .collect();
+    sources.sort();
+    assert!(!sources.is_empty());
+
+    let failures: Vec<String> = sources.iter().flat_map(|s| run_module(s)).collect();
+    assert!(
+        failures.is_empty(),
+        "{} differential failures:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
@@ -5,7 +5,7 @@ use libeir_ir::FunctionIdent;
 use libeir_passes::PassManager;
 use libeir_syntax_erl::ParseConfig;
 
-use libeir_interpreter::{Term, VMState};
+use libeir_interpreter::{ErlExactEq, Term, VMState};
 
 #[test]
 fn test_basic_catch() {
@@ -88,3 +88,100 @@ end.
     );
     assert!(vm.call(&fun, &[1.into()]).is_err());
 }
+
+#[test]
+fn test_catch_stacktrace() {
+    let _ = env_logger::try_init();
+
+    let mut eir_mod = lower(
+        "
+-module(woo).
+
+foo(foo) -> false.
+
+woo(A) -> try foo(A) catch
+    error:function_clause:Stack ->
+        Stack
+end.
+",
+        ParseConfig::default(),
+    )
+    .unwrap();
+
+    let mut pass_manager = PassManager::default();
+    pass_manager.run(&mut eir_mod);
+
+    let fun = FunctionIdent {
+        module: Ident::from_str("woo"),
+        name: Ident::from_str("woo"),
+        arity: 1,
+    };
+
+    let mut vm = VMState::new();
+    vm.add_builtin_modules();
+    vm.add_erlang_module(eir_mod);
+
+    let trace = vm.call(&fun, &[1.into()]).unwrap();
+    let frames = Term::as_list(&trace).unwrap();
+    assert!(frames.len() == 2);
+
+    let check_frame = |frame: &Term, name: &str| {
+        let entry = frame.as_tuple().unwrap();
+        assert!(entry.len() == 4);
+        assert!(entry[0].as_atom() == Some(Symbol::intern("woo")));
+        assert!(entry[1].as_atom() == Some(Symbol::intern(name)));
+        assert!(entry[2].as_usize() == Some(1));
+        assert!(Term::as_list(&entry[3]).is_some());
+    };
+    check_frame(&frames[0], "foo");
+    check_frame(&frames[1], "woo");
+}
+
+#[test]
+fn test_runtime_errors() {
+    let _ = env_logger::try_init();
+
+    let mut eir_mod = lower(
+        "
+-module(woo).
+
+one(a) -> ok.
+pair() -> single.
+
+undef() -> try woo:missing() catch error:undef -> undef end.
+badarity() -> F = fun(X) -> X end, try F(1, 2) catch error:{badarity, _} -> badarity end.
+badfun() -> F = one(a), try F() catch error:{badfun, ok} -> badfun end.
+function_clause() -> try one(b) catch error:function_clause -> function_clause end.
+badmatch() -> try {_, _} = pair() catch error:{badmatch, single} -> badmatch end.
+badkey() -> M = #{a => 1}, try M#{b := 2} catch error:{badkey, b} -> badkey end.
+badmap() -> M = one(a), try M#{b => 2} catch error:{badmap, ok} -> badmap end.
+",
+        ParseConfig::default(),
+    )
+    .unwrap();
+
+    let mut pass_manager = PassManager::default();
+    pass_manager.run(&mut eir_mod);
+
+    let mut vm = VMState::new();
+    vm.add_builtin_modules();
+    vm.add_erlang_module(eir_mod);
+
+    for name in &[
+        "undef",
+        "badarity",
+        "badfun",
+        "function_clause",
+        "badmatch",
+        "badkey",
+        "badmap",
+    ] {
+        let fun = FunctionIdent {
+            module: Ident::from_str("woo"),
+            name: Ident::from_str(name),
+            arity: 0,
+        };
+        let res = vm.call(&fun, &[]).unwrap();
+        assert!(res.erl_exact_eq(&Term::new_atom(name)));
+    }
+}
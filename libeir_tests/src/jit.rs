@@ -0,0 +1,108 @@
+//! Runs the `control_flow` programs through the JIT, and checks that the
+//! results match the interpreter.
+
+use std::rc::Rc;
+
+use crate::lower;
+
+use libeir_intern::Ident;
+use libeir_ir::FunctionIdent;
+use libeir_jit::testing::assert_same;
+use libeir_jit::Jit;
+use libeir_passes::PassManager;
+use libeir_syntax_erl::ParseConfig;
+
+use libeir_interpreter::{Term, VMState};
+
+fn load(source: &str) -> (Jit, VMState) {
+    let mut eir_mod = lower(source, ParseConfig::default()).unwrap();
+
+    let mut pass_manager = PassManager::default();
+    pass_manager.run(&mut eir_mod);
+
+    libeir_jit::testing::load(eir_mod)
+}
+
+fn ident(module: &str, name: &str, arity: usize) -> FunctionIdent {
+    FunctionIdent {
+        module: Ident::from_str(module),
+        name: Ident::from_str(name),
+        arity,
+    }
+}
+
+#[test]
+fn jit_fib() {
+    let _ = env_logger::try_init();
+
+    let (jit, mut vm) = load(
+        "-module(fib).
+
+fib(X) when X < 2 -> 1;
+fib(X) -> fib(X - 1) + fib(X-2).
+",
+    );
+
+    let fun = ident("fib", "fib", 1);
+    assert!(jit.is_compiled(&fun));
+
+    for n in 0..20 {
+        assert_same(&jit, &mut vm, &fun, &[n.into()]);
+    }
+    assert_same(&jit, &mut vm, &fun, &[Term::new_atom("a")]);
+}
+
+#[test]
+fn jit_accumulate_list() {
+    let _ = env_logger::try_init();
+
+    let (jit, mut vm) = load(
+        "-module(woo).
+
+woo([], Acc) -> Acc;
+woo([H | T], Acc) -> woo(T, H + Acc).
+
+woo(V) -> woo(V, 0).
+",
+    );
+
+    let fun = ident("woo", "woo", 1);
+    assert!(jit.is_compiled(&fun));
+
+    let arg = Term::slice_to_list(
+        &[
+            Term::Integer(1.into()).into(),
+            Term::Integer(2.into()).into(),
+            Term::Integer(4.into()).into(),
+        ],
+        Term::Nil.into(),
+    );
+    assert_same(&jit, &mut vm, &fun, &[Rc::try_unwrap(arg).unwrap()]);
+}
+
+#[test]
+fn jit_nth_root() {
+    let _ = env_logger::try_init();
+
+    let (jit, mut vm) = load(
+        "
+-module(woo).
+
+fixed_point(F, Guess, Tolerance) ->
+    fixed_point(F, Guess, Tolerance, F(Guess)).
+fixed_point(_, Guess, Tolerance, Next) when erlang:abs(Guess - Next) < Tolerance ->
+    Next;
+fixed_point(F, _, Tolerance, Next) ->
+    fixed_point(F, Next, Tolerance, F(Next)).
+
+nth_root(N, X) -> nth_root(N, X, 1.0e-5).
+nth_root(N, X, Precision) ->
+    F = fun(Prev) -> ((N - 1) * Prev + X / math:pow(Prev, (N-1))) / N end,
+    fixed_point(F, X, Precision).
+",
+    );
+
+    let fun = ident("woo", "nth_root", 2);
+    assert!(jit.is_compiled(&fun));
+    assert_same(&jit, &mut vm, &fun, &[2.into(), 2.into()]);
+}
@@ -12,13 +12,27 @@ use libeir_util_parse::{error_tee, Errors};
 
 use libeir_util_dot_graph::GraphPrinter;
 
+mod bifs;
+mod binaries;
+mod closure_conversion;
+mod common_subexpression;
 mod control_flow;
+mod core_printer;
+mod coverage;
 mod ct_runner;
+mod debugger;
+mod differential;
 mod errors;
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+mod jit;
 mod list_comprehensions;
+mod loader;
+mod maybe;
 mod otp;
 mod patterns;
+mod process_state;
 mod records;
+mod stdlib;
 
 fn lower_file<S>(path: S, config: ParseConfig) -> Result<Module, ()>
 where
@@ -39,11 +53,24 @@ where
 }
 
 pub fn lower<S>(input: S, config: ParseConfig) -> Result<Module, ()>
+where
+    S: AsRef<str>,
+{
+    lower_with_codemap(input, config, Arc::new(CodeMap::new()))
+}
+
+/// Like `lower`, but adds the source to the given codemap, so that source
+/// lines can be resolved from the spans of the module. The source is named
+/// `nofile`.
+pub fn lower_with_codemap<S>(
+    input: S,
+    config: ParseConfig,
+    codemap: Arc<CodeMap>,
+) -> Result<Module, ()>
 where
     S: AsRef<str>,
 {
     let mut errors: Errors<ErlangError, ErlangError> = Errors::new();
-    let codemap = Arc::new(CodeMap::new());
     let eir_res = error_tee(&mut errors, |mut errors| {
         let parser = Parser::new(config, codemap.clone());
         let ast = parser.parse_string(&mut errors.make_into_adapter(), input)?;
@@ -0,0 +1,176 @@
+use std::rc::Rc;
+use std::sync::Arc;
+
+use super::lower;
+
+use libeir_diagnostics::CodeMap;
+use libeir_intern::Ident;
+use libeir_ir::FunctionIdent;
+use libeir_passes::PassManager;
+use libeir_syntax_erl::ParseConfig;
+
+use libeir_interpreter::{CodeLoader, ErlExactEq, LoadError, Term, VMState};
+
+#[test]
+fn test_load_on_demand() {
+    let _ = env_logger::try_init();
+
+    let mut eir_mod = lower(
+        r#"
+-module(woo).
+
+call(X) -> loader_helper:nested(X).
+
+missing() -> try loader_missing:foo() catch error:undef -> undef end.
+
+missing_fun() -> try loader_helper:foo() catch error:undef -> undef end.
+"#,
+        ParseConfig::default(),
+    )
+    .unwrap();
+
+    let mut pass_manager = PassManager::default();
+    pass_manager.run(&mut eir_mod);
+
+    let mut vm = VMState::new();
+    vm.add_builtin_modules();
+    vm.add_erlang_module(eir_mod);
+
+    let mut loader = CodeLoader::new(Arc::new(CodeMap::new()));
+    loader.add_path("test_data/loader");
+    vm.set_code_loader(loader);
+
+    let mut call = |name: &str, args: &[Term]| {
+        let fun = FunctionIdent {
+            module: Ident::from_str("woo"),
+            name: Ident::from_str(name),
+            arity: args.len(),
+        };
+        vm.call(&fun, args)
+    };
+
+    let res = call("call", &[Term::new_i64(4)]).unwrap();
+    assert!(res.erl_exact_eq(&Term::new_i64(9)));
+
+    let res = call("missing", &[]).unwrap();
+    assert!(res.erl_exact_eq(&Term::new_atom("undef")));
+
+    let res = call("missing_fun", &[]).unwrap();
+    assert!(res.erl_exact_eq(&Term::new_atom("undef")));
+
+    let loaded = vm.modules.borrow();
+    assert!(loaded.contains_key(&Ident::from_str("loader_helper").name));
+    assert!(loaded.contains_key(&Ident::from_str("loader_nested").name));
+    assert!(!loaded.contains_key(&Ident::from_str("loader_missing").name));
+}
+
+const UPGRADE_V1: &str = r#"
+-module(upgrade).
+
+version() -> 1.
+
+upgrade(Src) ->
+    {module, upgrade} = code:load_binary(upgrade, "upgrade.erl", Src),
+    Local = version(),
+    Remote = upgrade:version(),
+    Fun = fun version/0,
+    Check = erlang:check_process_code(self(), upgrade),
+    Soft = code:soft_purge(upgrade),
+    NotPurged = code:load_binary(upgrade, "upgrade.erl", Src),
+    {Local, Remote, Fun(), Check, Soft, NotPurged}.
+
+upgrade_purge(Src) ->
+    {module, upgrade} = code:load_binary(upgrade, "upgrade.erl", Src),
+    true = code:purge(upgrade),
+    version().
+"#;
+
+const UPGRADE_V2: &str = r#"
+-module(upgrade).
+
+version() -> 2.
+
+purge() ->
+    {erlang:check_process_code(self(), upgrade), code:soft_purge(upgrade), code:purge(upgrade)}.
+"#;
+
+#[test]
+fn test_hot_code_loading() {
+    let _ = env_logger::try_init();
+
+    let make_vm = || {
+        let mut eir_mod = lower(UPGRADE_V1, ParseConfig::default()).unwrap();
+        let mut pass_manager = PassManager::default();
+        pass_manager.run(&mut eir_mod);
+
+        let mut vm = VMState::new();
+        vm.add_builtin_modules();
+        vm.add_erlang_module(eir_mod);
+        vm
+    };
+    let fun = |name: &str, arity: usize| FunctionIdent {
+        module: Ident::from_str("upgrade"),
+        name: Ident::from_str(name),
+        arity,
+    };
+    let src = Term::Binary(Rc::new(UPGRADE_V2.as_bytes().to_vec().into()));
+    let atom = |name: &str| -> Rc<Term> { Term::new_atom(name).into() };
+
+    // Running code stays in the old version, fully qualified calls go to the
+    // new one.
+    let mut vm = make_vm();
+    let res = vm.call(&fun("upgrade", 1), &[src.clone()]).unwrap();
+    let expected = Term::Tuple(vec![
+        Term::new_i64(1).into(),
+        Term::new_i64(2).into(),
+        Term::new_i64(1).into(),
+        atom("true"),
+        atom("false"),
+        Term::Tuple(vec![atom("error"), atom("not_purged")]).into(),
+    ]);
+    assert!(res.erl_exact_eq(&expected));
+
+    // Nothing runs the old code anymore once the call has returned
+    let res = vm.call(&fun("purge", 0), &[]).unwrap();
+    let expected = Term::Tuple(vec![atom("false"), atom("true"), atom("false")]);
+    assert!(res.erl_exact_eq(&expected));
+    let res = vm.call(&fun("version", 0), &[]).unwrap();
+    assert!(res.erl_exact_eq(&Term::new_i64(2)));
+
+    // A process returning into purged code is killed
+    let mut vm = make_vm();
+    let (typ, reason, _) = vm.call(&fun("upgrade_purge", 1), &[src]).unwrap_err();
+    assert!(typ.erl_exact_eq(&Term::new_atom("exit")));
+    assert!(reason.erl_exact_eq(&Term::new_atom("killed")));
+}
+
+#[test]
+fn test_load_module_errors() {
+    let _ = env_logger::try_init();
+
+    let load = |source: &str| {
+        let mut eir_mod = lower(source, ParseConfig::default()).unwrap();
+        let mut pass_manager = PassManager::default();
+        pass_manager.run(&mut eir_mod);
+        eir_mod
+    };
+    let name = Ident::from_str("upgrade").name;
+
+    let mut vm = VMState::new();
+    vm.add_builtin_modules();
+    assert!(!vm.add_erlang_module(load(UPGRADE_V1)));
+
+    // A module that is held on to can not be replaced
+    let current = vm.module(name).unwrap();
+    assert_eq!(vm.load_module(load(UPGRADE_V2)), Err(LoadError::InUse));
+    assert_eq!(vm.old_module_version(name), None);
+    drop(current);
+
+    assert_eq!(vm.load_module(load(UPGRADE_V2)), Ok(()));
+    assert_eq!(vm.old_module_version(name), Some(0));
+    assert_eq!(vm.load_module(load(UPGRADE_V1)), Err(LoadError::NotPurged));
+
+    // Adding a module purges the old version
+    assert!(vm.add_erlang_module(load(UPGRADE_V1)));
+    assert_eq!(vm.old_module_version(name), Some(1));
+}
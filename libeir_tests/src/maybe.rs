@@ -0,0 +1,105 @@
+use std::rc::Rc;
+
+use super::lower;
+
+use libeir_intern::Ident;
+use libeir_ir::FunctionIdent;
+use libeir_passes::PassManager;
+use libeir_syntax_erl::ParseConfig;
+
+use libeir_interpreter::{ErlExactEq, Term, VMState};
+
+#[test]
+fn maybe_expressions() {
+    let _ = env_logger::try_init();
+
+    let mut eir_mod = lower(
+        r#"
+-module(woo).
+-feature(maybe_expr, enable).
+
+-if(?FEATURE_ENABLED(no_such_feature) orelse ?FEATURE_AVAILABLE(no_such_feature)).
+unknown_feature() -> true.
+-else.
+unknown_feature() -> false.
+-endif.
+
+fetch(N) when N < 10 -> {ok, N + 1};
+fetch(10) -> {error, too_big};
+fetch(_) -> other.
+
+chain(A) ->
+    maybe
+        {ok, B} ?= fetch(A),
+        C = B * 2,
+        {ok, D} ?= fetch(C),
+        D
+    end.
+
+with_else(A) ->
+    maybe
+        {ok, B} ?= fetch(A),
+        {ok, C} ?= fetch(B),
+        C
+    else
+        {error, Reason} -> {failed, Reason};
+        other -> not_fetched
+    end.
+
+no_else_match(A) ->
+    try
+        maybe
+            {ok, B} ?= fetch(A),
+            B
+        else
+            {error, Reason} -> Reason
+        end
+    catch
+        error:Error -> Error
+    end.
+"#,
+        ParseConfig::default(),
+    )
+    .unwrap();
+
+    let mut pass_manager = PassManager::default();
+    pass_manager.run(&mut eir_mod);
+
+    let mut vm = VMState::new();
+    vm.add_builtin_modules();
+    vm.add_erlang_module(eir_mod);
+
+    let int = |num: i64| -> Rc<Term> { Term::new_i64(num).into() };
+    let atom = |name: &str| -> Rc<Term> { Term::new_atom(name).into() };
+    let tup = |elems: Vec<Rc<Term>>| -> Rc<Term> { Term::Tuple(elems).into() };
+    let mut call = |name: &str, args: &[Term]| {
+        let fun = FunctionIdent {
+            module: Ident::from_str("woo"),
+            name: Ident::from_str(name),
+            arity: args.len(),
+        };
+        vm.call(&fun, args).unwrap()
+    };
+
+    assert!(call("unknown_feature", &[]).erl_exact_eq(&*atom("false")));
+
+    // Every conditional match matches
+    assert!(call("chain", &[Term::new_i64(1)]).erl_exact_eq(&*int(5)));
+    assert!(call("with_else", &[Term::new_i64(1)]).erl_exact_eq(&*int(3)));
+
+    // Without `else`, the first value that does not match is the result
+    let res = call("chain", &[Term::new_i64(10)]);
+    assert!(res.erl_exact_eq(&*tup(vec![atom("error"), atom("too_big")])));
+    let res = call("chain", &[Term::new_i64(4)]);
+    assert!(res.erl_exact_eq(&*tup(vec![atom("error"), atom("too_big")])));
+
+    // With `else`, it is matched against the `else` clauses
+    let res = call("with_else", &[Term::new_i64(9)]);
+    assert!(res.erl_exact_eq(&*tup(vec![atom("failed"), atom("too_big")])));
+    let res = call("with_else", &[Term::new_i64(20)]);
+    assert!(res.erl_exact_eq(&*atom("not_fetched")));
+
+    let res = call("no_else_match", &[Term::new_i64(20)]);
+    assert!(res.erl_exact_eq(&*tup(vec![atom("else_clause"), atom("other")])));
+    assert!(call("no_else_match", &[Term::new_i64(3)]).erl_exact_eq(&*int(4)));
+}
@@ -7,7 +7,7 @@ use libeir_ir::FunctionIdent;
 use libeir_passes::PassManager;
 use libeir_syntax_erl::ParseConfig;
 
-use libeir_interpreter::{ErlEq, Term, VMState};
+use libeir_interpreter::{ErlEq, ErlExactEq, Term, VMState};
 
 #[test]
 fn test_pattern_equality() {
@@ -263,3 +263,114 @@ end.
         ])));
     }
 }
+
+#[test]
+fn test_binary_pattern() {
+    let _ = env_logger::try_init();
+
+    let mut eir_mod = lower(
+        r#"
+-module(woo).
+
+int(<<A:8, B:16/little, C:4/signed, D:4>>) -> {A, B, C, D}.
+
+float(<<F:32/float, G/float>>) -> {F, G}.
+
+sized(<<Len:8, Data:Len/binary, Rest/binary>>) -> {Data, Rest}.
+
+head_sized(N, <<X:N, _/bitstring>>) -> X.
+
+units(<<A:2/integer-unit:8, B:1/binary-unit:16>>) -> {A, B}.
+
+utf(<<A/utf8, B/utf16, C/utf32-little>>) -> {A, B, C}.
+
+bits(<<A:3/bits, _/bitstring>>) -> A.
+
+proto(<<"GET ", Path/binary>>) -> {get, Path};
+proto(<<1:8, N:8, _/binary>>) -> {one, N};
+proto(<<Tag:8, _:8>>) -> {tag, Tag};
+proto(_) -> other.
+"#,
+        ParseConfig::default(),
+    )
+    .unwrap();
+
+    let mut pass_manager = PassManager::default();
+    pass_manager.run(&mut eir_mod);
+
+    let mut vm = VMState::new();
+    vm.add_builtin_modules();
+    vm.add_erlang_module(eir_mod);
+
+    let bin = |bytes: &[u8]| Term::Binary(Rc::new(bytes.to_vec().into()));
+    let int = |num: i64| -> Rc<Term> { Term::new_i64(num).into() };
+    let atom = |name: &str| -> Rc<Term> { Term::new_atom(name).into() };
+    let tup = |elems: Vec<Rc<Term>>| Term::Tuple(elems);
+    let mut call = |name: &str, args: &[Term]| {
+        let fun = FunctionIdent {
+            module: Ident::from_str("woo"),
+            name: Ident::from_str(name),
+            arity: args.len(),
+        };
+        vm.call(&fun, args)
+    };
+
+    let res = call("int", &[bin(&[0x01, 0x34, 0x12, 0xf5])]).unwrap();
+    assert!(res.erl_exact_eq(&tup(vec![int(1), int(0x1234), int(-1), int(5)])));
+    assert!(call("int", &[bin(&[0x01, 0x34, 0x12, 0xf5, 0x00])]).is_err());
+
+    let res = call(
+        "float",
+        &[bin(&[
+            0x3f, 0xc0, 0x00, 0x00, 0x40, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ])],
+    )
+    .unwrap();
+    assert!(res.erl_exact_eq(&tup(vec![
+        Term::Float(1.5.into()).into(),
+        Term::Float(2.25.into()).into(),
+    ])));
+
+    let res = call("sized", &[bin(b"\x03abcx")]).unwrap();
+    assert!(res.erl_exact_eq(&tup(vec![bin(b"abc").into(), bin(b"x").into()])));
+    assert!(call("sized", &[bin(b"\x05abc")]).is_err());
+
+    let res = call("head_sized", &[Term::new_i64(4), bin(&[0xa0])]).unwrap();
+    assert!(res.erl_exact_eq(&Term::new_i64(10)));
+
+    let res = call("units", &[bin(&[0x01, 0x02, 0x03, 0x04])]).unwrap();
+    assert!(res.erl_exact_eq(&tup(vec![int(0x0102), bin(&[0x03, 0x04]).into()])));
+
+    let res = call(
+        "utf",
+        &[bin(&[
+            0xc3, 0xa9, 0xd8, 0x3d, 0xde, 0x00, 0x41, 0x00, 0x00, 0x00,
+        ])],
+    )
+    .unwrap();
+    assert!(res.erl_exact_eq(&tup(vec![int(0xe9), int(0x1f600), int(0x41)])));
+    assert!(call(
+        "utf",
+        &[bin(&[0xc0, 0x80, 0x00, 0x41, 0x41, 0x00, 0x00, 0x00])]
+    )
+    .is_err());
+
+    let res = call("bits", &[bin(&[0b1010_0000, 0xff])]).unwrap();
+    let expected = Term::BinarySlice {
+        buf: Rc::new(vec![0b1010_0000].into()),
+        bit_offset: 0,
+        bit_length: 3,
+    };
+    assert!(res.erl_exact_eq(&expected));
+
+    let res = call("proto", &[bin(b"GET /index")]).unwrap();
+    assert!(res.erl_exact_eq(&tup(vec![atom("get"), bin(b"/index").into()])));
+    let res = call("proto", &[bin(&[1, 7, 9, 9])]).unwrap();
+    assert!(res.erl_exact_eq(&tup(vec![atom("one"), int(7)])));
+    let res = call("proto", &[bin(&[1, 7])]).unwrap();
+    assert!(res.erl_exact_eq(&tup(vec![atom("one"), int(7)])));
+    let res = call("proto", &[bin(&[2, 3])]).unwrap();
+    assert!(res.erl_exact_eq(&tup(vec![atom("tag"), int(2)])));
+    let res = call("proto", &[bin(b"GEX")]).unwrap();
+    assert!(res.erl_exact_eq(&Term::new_atom("other")));
+}
@@ -0,0 +1,138 @@
+use std::rc::Rc;
+
+use super::lower;
+
+use libeir_intern::Ident;
+use libeir_ir::FunctionIdent;
+use libeir_passes::PassManager;
+use libeir_syntax_erl::ParseConfig;
+
+use libeir_interpreter::{ErlExactEq, Term, VMState};
+
+#[test]
+fn test_process_state() {
+    let _ = env_logger::try_init();
+
+    let mut eir_mod = lower(
+        r#"
+-module(woo).
+
+dict() ->
+    put(a, 1),
+    put(b, 2),
+    put(c, 1),
+    Keys = {get_keys(), get_keys(1)},
+    All = get(),
+    Erased = erase(),
+    {Keys, All, Erased, get()}.
+
+registry() ->
+    true = register(me, self()),
+    Reg = {whereis(me) =:= self(), registered(), whereis(other)},
+    Dup = try register(me, self()) catch error:badarg -> badarg end,
+    true = unregister(me),
+    {Reg, Dup, whereis(me)}.
+
+ets_set() ->
+    T = ets:new(t, [set]),
+    true = ets:insert(T, [{a, 1}, {b, 2}]),
+    true = ets:insert(T, {a, 3}),
+    Res = {ets:lookup(T, a), ets:lookup(T, c), ets:tab2list(T)},
+    true = ets:delete(T, a),
+    {Res, ets:tab2list(T)}.
+
+ets_ordered() ->
+    named = ets:new(named, [ordered_set, named_table, {keypos, 2}]),
+    ets:insert(named, [{x, 3}, {y, 1}, {z, 2}]),
+    Res = {ets:tab2list(named),
+           ets:match(named, {'$1', '$2'}),
+           ets:select(named, [{{'$1', '$2'}, [{'>', '$2', 1}], ['$1']}])},
+    true = ets:delete(named),
+    {Res, try ets:lookup(named, 1) catch error:badarg -> badarg end}.
+
+ets_bag() ->
+    T = ets:new(t, [bag]),
+    ets:insert(T, [{k, 1}, {k, 1}, {k, 2}, {j, 3}]),
+    ets:lookup(T, k).
+"#,
+        ParseConfig::default(),
+    )
+    .unwrap();
+
+    let mut pass_manager = PassManager::default();
+    pass_manager.run(&mut eir_mod);
+
+    let mut vm = VMState::new();
+    vm.add_builtin_modules();
+    vm.add_erlang_module(eir_mod);
+
+    let int = |num: i64| -> Rc<Term> { Term::new_i64(num).into() };
+    let atom = |name: &str| -> Rc<Term> { Term::new_atom(name).into() };
+    let list = |elems: &[Rc<Term>]| Term::slice_to_list(elems, Term::Nil.into());
+    let tup = |elems: Vec<Rc<Term>>| -> Rc<Term> { Term::Tuple(elems).into() };
+    let mut call = |name: &str| {
+        let fun = FunctionIdent {
+            module: Ident::from_str("woo"),
+            name: Ident::from_str(name),
+            arity: 0,
+        };
+        vm.call(&fun, &[])
+    };
+
+    let entries = list(&[
+        tup(vec![atom("a"), int(1)]),
+        tup(vec![atom("b"), int(2)]),
+        tup(vec![atom("c"), int(1)]),
+    ]);
+    let res = call("dict").unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![
+        tup(vec![
+            list(&[atom("a"), atom("b"), atom("c")]),
+            list(&[atom("a"), atom("c")]),
+        ]),
+        entries.clone(),
+        entries,
+        list(&[]),
+    ])));
+
+    let res = call("registry").unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![
+        tup(vec![atom("true"), list(&[atom("me")]), atom("undefined")]),
+        atom("badarg"),
+        atom("undefined"),
+    ])));
+
+    let res = call("ets_set").unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![
+        tup(vec![
+            list(&[tup(vec![atom("a"), int(3)])]),
+            list(&[]),
+            list(&[tup(vec![atom("a"), int(3)]), tup(vec![atom("b"), int(2)])]),
+        ]),
+        list(&[tup(vec![atom("b"), int(2)])]),
+    ])));
+
+    let res = call("ets_ordered").unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![
+        tup(vec![
+            list(&[
+                tup(vec![atom("y"), int(1)]),
+                tup(vec![atom("z"), int(2)]),
+                tup(vec![atom("x"), int(3)]),
+            ]),
+            list(&[
+                list(&[atom("y"), int(1)]),
+                list(&[atom("z"), int(2)]),
+                list(&[atom("x"), int(3)]),
+            ]),
+            list(&[atom("z"), atom("x")]),
+        ]),
+        atom("badarg"),
+    ])));
+
+    let res = call("ets_bag").unwrap();
+    assert!(res.erl_exact_eq(&*list(&[
+        tup(vec![atom("k"), int(1)]),
+        tup(vec![atom("k"), int(2)]),
+    ])));
+}
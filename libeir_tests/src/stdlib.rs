@@ -0,0 +1,208 @@
+use std::rc::Rc;
+
+use super::lower;
+
+use libeir_intern::Ident;
+use libeir_ir::FunctionIdent;
+use libeir_passes::PassManager;
+use libeir_syntax_erl::ParseConfig;
+
+use libeir_interpreter::{ErlExactEq, Term, VMState};
+
+#[test]
+fn test_stdlib_modules() {
+    let _ = env_logger::try_init();
+
+    let mut eir_mod = lower(
+        r#"
+-module(woo).
+
+lists() ->
+    {lists:map(fun(X) -> X * 2 end, [1, 2, 3]),
+     lists:foldl(fun(X, Acc) -> X + Acc end, 0, [1, 2, 3]),
+     lists:filter(fun(X) -> X > 1 end, [1, 2, 3]),
+     lists:sort([3, b, 1.5, {a}, a, 2]),
+     lists:sort(fun(A, B) -> A >= B end, [1, 3, 2]),
+     lists:keyfind(b, 1, [{a, 1}, {b, 2}]),
+     lists:seq(1, 5, 2),
+     lists:flatten([1, [2, [3]], []]),
+     lists:usort([b, a, b]),
+     lists:usort([2, 1, 1.0, 2.0]),
+     lists:nth(2, [a, b, c]),
+     lists:member(b, [a, b])}.
+
+bad_fun() ->
+    try lists:map(fun(X, Y) -> X + Y end, [1]) catch error:{badarity, _} -> badarity end.
+
+throws() ->
+    try lists:foreach(fun(X) -> throw(X) end, [inner]) catch throw:R -> {caught, R} end.
+
+maps() ->
+    M = maps:put(c, 3, #{a => 1, b => 2}),
+    {maps:get(c, M), maps:find(d, M), maps:keys(M),
+     maps:fold(fun(_K, V, Acc) -> V + Acc end, 0, M),
+     maps:to_list(maps:remove(a, M)),
+     try maps:get(d, M) catch error:{badkey, K} -> K end}.
+
+strings() ->
+    {string:split(<<"a,b,c">>, <<",">>, all), string:trim("  ab  "),
+     string:to_upper("abc"), string:lexemes("a  b", " "),
+     string:find("hello", "ll"), string:to_integer("42rest")}.
+
+binaries() ->
+    {binary:split(<<"a-b-c">>, <<"-">>), binary:replace(<<"aXbX">>, <<"X">>, <<"y">>, [global]),
+     binary:match(<<"abc">>, <<"c">>), binary:encode_unsigned(256),
+     binary:decode_unsigned(<<1, 0>>)}.
+
+unicode() ->
+    {unicode:characters_to_binary([104, <<"i">>, [955]]),
+     unicode:characters_to_list(<<"hi"/utf8>>),
+     try unicode:characters_to_binary([-1]) catch error:badarg -> badarg end}.
+"#,
+        ParseConfig::default(),
+    )
+    .unwrap();
+
+    let mut pass_manager = PassManager::default();
+    pass_manager.run(&mut eir_mod);
+
+    let mut vm = VMState::new();
+    vm.add_builtin_modules();
+    vm.add_erlang_module(eir_mod);
+
+    let bin = |bytes: &[u8]| -> Rc<Term> { Term::Binary(Rc::new(bytes.to_vec().into())).into() };
+    let int = |num: i64| -> Rc<Term> { Term::new_i64(num).into() };
+    let atom = |name: &str| -> Rc<Term> { Term::new_atom(name).into() };
+    let list = |elems: &[Rc<Term>]| Term::slice_to_list(elems, Term::Nil.into());
+    let string = |string: &str| {
+        let chars: Vec<_> = string.chars().map(|c| int(c as i64)).collect();
+        list(&chars)
+    };
+    let tup = |elems: Vec<Rc<Term>>| -> Rc<Term> { Term::Tuple(elems).into() };
+    let mut call = |name: &str| {
+        let fun = FunctionIdent {
+            module: Ident::from_str("woo"),
+            name: Ident::from_str(name),
+            arity: 0,
+        };
+        vm.call(&fun, &[])
+    };
+
+    let res = call("lists").unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![
+        list(&[int(2), int(4), int(6)]),
+        int(6),
+        list(&[int(2), int(3)]),
+        list(&[
+            Term::Float(1.5.into()).into(),
+            int(2),
+            int(3),
+            atom("a"),
+            atom("b"),
+            tup(vec![atom("a")]),
+        ]),
+        list(&[int(3), int(2), int(1)]),
+        tup(vec![atom("b"), int(2)]),
+        list(&[int(1), int(3), int(5)]),
+        list(&[int(1), int(2), int(3)]),
+        list(&[atom("a"), atom("b")]),
+        // Compared with `==`, the first of equal elements is kept
+        list(&[int(1), int(2)]),
+        atom("b"),
+        atom("true"),
+    ])));
+
+    let res = call("bad_fun").unwrap();
+    assert!(res.erl_exact_eq(&*atom("badarity")));
+
+    let res = call("throws").unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![atom("caught"), atom("inner")])));
+
+    let res = call("maps").unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![
+        int(3),
+        atom("error"),
+        list(&[atom("a"), atom("b"), atom("c")]),
+        int(6),
+        list(&[tup(vec![atom("b"), int(2)]), tup(vec![atom("c"), int(3)])]),
+        atom("d"),
+    ])));
+
+    let res = call("strings").unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![
+        list(&[bin(b"a"), bin(b"b"), bin(b"c")]),
+        string("ab"),
+        string("ABC"),
+        list(&[string("a"), string("b")]),
+        string("llo"),
+        tup(vec![int(42), string("rest")]),
+    ])));
+
+    let res = call("binaries").unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![
+        list(&[bin(b"a"), bin(b"b-c")]),
+        bin(b"ayby"),
+        tup(vec![int(2), int(1)]),
+        bin(&[1, 0]),
+        int(256),
+    ])));
+
+    let res = call("unicode").unwrap();
+    assert!(res.erl_exact_eq(&*tup(vec![
+        bin(&[104, 105, 0xce, 0xbb]),
+        string("hi"),
+        atom("badarg"),
+    ])));
+}
+
+#[test]
+fn lambda_arity() {
+    let _ = env_logger::try_init();
+
+    let mut eir_mod = lower(
+        r#"
+-module(woo).
+
+map() ->
+    lists:map(fun(X) -> X + 1 end, [1, 2]).
+
+fold(A, B, C) ->
+    lists:foldl(fun(X, Acc) -> X + Acc end, A, [B, C]).
+
+bad(A) ->
+    try lists:map(fun(X, Y) -> X + Y end, [A]) catch error:{badarity, _} -> badarity end.
+"#,
+        ParseConfig::default(),
+    )
+    .unwrap();
+
+    let mut pass_manager = PassManager::default();
+    pass_manager.run(&mut eir_mod);
+
+    let mut vm = VMState::new();
+    vm.add_builtin_modules();
+    vm.add_erlang_module(eir_mod);
+
+    let ident = |name: &str, arity: usize| FunctionIdent {
+        module: Ident::from_str("woo"),
+        name: Ident::from_str(name),
+        arity,
+    };
+
+    // The lambdas take a different number of arguments than the functions
+    // they are defined in.
+    let res = vm.call(&ident("map", 0), &[]).unwrap();
+    let expected = Term::slice_to_list(
+        &[Term::new_i64(2).into(), Term::new_i64(3).into()],
+        Term::Nil.into(),
+    );
+    assert!(res.erl_exact_eq(&*expected));
+
+    let res = vm
+        .call(&ident("fold", 3), &[1.into(), 2.into(), 3.into()])
+        .unwrap();
+    assert!(res.erl_exact_eq(&Term::new_i64(6)));
+
+    let res = vm.call(&ident("bad", 1), &[1.into()]).unwrap();
+    assert!(res.erl_exact_eq(&Term::new_atom("badarity")));
+}
@@ -0,0 +1,45 @@
+-module(basic).
+
+-export([add/0, tuple/0, list_rev/0, case_guard/0, fact/0, closures/0,
+         fun_clause/0, badmatch/0, catch_error/0]).
+
+add() -> 1 + 2 * 3.
+
+tuple() -> {a, [1, 2], {}}.
+
+list_rev() -> rev([1, 2, 3], []).
+
+rev([], Acc) -> Acc;
+rev([H | T], Acc) -> rev(T, [H | Acc]).
+
+case_guard() -> [classify(X) || X <- [-1, 0, 5]].
+
+classify(X) when X < 0 -> neg;
+classify(0) -> zero;
+classify(_) -> pos.
+
+fact() -> fact(10).
+
+fact(0) -> 1;
+fact(N) -> N * fact(N - 1).
+
+closures() ->
+    Factor = 2,
+    map(fun(X) -> X * Factor end, [1, 2, 3]).
+
+map(_F, []) -> [];
+map(F, [H | T]) -> [F(H) | map(F, T)].
+
+fun_clause() -> only_a(b).
+
+only_a(a) -> a.
+
+badmatch() -> match_a(b).
+
+match_a(X) ->
+    {a, _} = {X, c}.
+
+catch_error() ->
+    try match_a(b)
+    catch error:{badmatch, V} -> {caught, V}
+    end.
@@ -0,0 +1,7 @@
+-module(loader_helper).
+-export([double/1, nested/1]).
+
+double(X) -> X * 2.
+
+%% Calls into another module that is only found through the code path
+nested(X) -> loader_nested:inc(double(X)).
@@ -0,0 +1,4 @@
+-module(loader_nested).
+-export([inc/1]).
+
+inc(X) -> X + 1.
@@ -15,12 +15,19 @@ use libeir_frontend::{
 };
 use libeir_ir::FunctionIdent;
 use libeir_passes::PassManager;
+use libeir_syntax_erl::{
+    Lexer, MacroContainer, MacroDef, MacroIdent, ParseConfig, ParserError, PreprocessedSource,
+    Symbol,
+};
+use libeir_util_parse::{Errors, FileMapSource, Parser, Scanner, Source};
 
 arg_enum! {
     #[derive(Debug, PartialEq, Eq)]
     pub enum OutputType {
         Eir,
         Dot,
+        Asm,
+        Beam,
     }
 }
 
@@ -74,9 +81,33 @@ impl LogLevel {
     }
 }
 
-fn make_erlang_frontend(codemap: Arc<CodeMap>, matches: &ArgMatches) -> ErlangFrontend {
-    use libeir_syntax_erl::ParseConfig;
+/// Parses a `-D NAME[=VALUE]` macro definition. A macro defined without a
+/// value expands to `true`, as with `erlc`.
+fn parse_define(codemap: &CodeMap, define: &str) -> Result<(Symbol, MacroDef), String> {
+    let (name, value) = match define.find('=') {
+        Some(idx) => (&define[..idx], Some(&define[idx + 1..])),
+        None => (define, None),
+    };
+    if name.is_empty() {
+        return Err(format!("invalid macro definition `{}`", define));
+    }
+
+    let def = match value {
+        None => MacroDef::Boolean(true),
+        Some(value) => {
+            let id = codemap.add(format!("-D{}", name), value.to_owned());
+            let file = codemap.get(id).unwrap();
+            let lexer = Lexer::new(Scanner::new(FileMapSource::new(file)));
+            let tokens = lexer
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| format!("invalid value for macro `{}`: {}", name, err))?;
+            MacroDef::Dynamic(tokens)
+        }
+    };
+    Ok((Symbol::intern(name), def))
+}
 
+fn make_parse_config(codemap: &CodeMap, matches: &ArgMatches) -> ParseConfig {
     let mut config = ParseConfig::default();
 
     if let Some(includes) = matches.values_of("INCLUDE_PATHS") {
@@ -89,7 +120,33 @@ fn make_erlang_frontend(codemap: Arc<CodeMap>, matches: &ArgMatches) -> ErlangFr
             config.code_paths.push_front(PathBuf::from(include));
         }
     }
+    if let Some(defines) = matches.values_of("DEFINES") {
+        let mut macros = MacroContainer::new();
+        for define in defines {
+            match parse_define(codemap, define) {
+                Ok((name, def)) => {
+                    macros.insert(MacroIdent::Const(name), def);
+                }
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        config.macros = Some(macros);
+    }
+    if let Some(mut warnings) = matches.values_of("WARNINGS") {
+        config.warnings_as_errors = warnings.any(|w| w == "error");
+    }
+    if matches.is_present("OTP_RELEASE") {
+        config.otp_release = value_t!(matches, "OTP_RELEASE", u32).unwrap_or_else(|e| e.exit());
+    }
+
+    config
+}
 
+fn make_erlang_frontend(codemap: Arc<CodeMap>, matches: &ArgMatches) -> ErlangFrontend {
+    let config = make_parse_config(&codemap, matches);
     ErlangFrontend::new(config, codemap)
 }
 
@@ -101,6 +158,41 @@ fn make_frontend(codemap: Arc<CodeMap>, matches: &ArgMatches) -> AnyFrontend {
     }
 }
 
+/// Runs the preprocessor on an erlang source file, and writes out the
+/// preprocessed source. This is the equivalent of `erlc -P`.
+fn preprocess(codemap: Arc<CodeMap>, matches: &ArgMatches, in_file_path: &Path) {
+    if value_t!(matches, "IN_FORMAT", InputType).unwrap() != InputType::Erl {
+        eprintln!("error: only erlang sources can be preprocessed");
+        std::process::exit(1);
+    }
+
+    let config = make_parse_config(&codemap, matches);
+    let parser = Parser::new(config, codemap.clone());
+    let mut errors: Errors<ParserError, ParserError> = Errors::new();
+    let res = parser.parse_file::<PreprocessedSource, _>(&mut errors, in_file_path);
+    errors.print(&codemap);
+
+    let source = match res {
+        Ok(source) => source,
+        Err(()) => std::process::exit(1),
+    };
+    let out_data = source.to_source(&codemap);
+
+    if matches.is_present("to-stdout") {
+        print!("{}", out_data);
+        return;
+    }
+
+    let out_file_name = matches
+        .value_of("OUT_FILE")
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| format!("{}.P", in_file_path.display()));
+
+    println!("Writing to {}", out_file_name);
+    let mut out = ::std::fs::File::create(&out_file_name).unwrap();
+    out.write(out_data.as_bytes()).unwrap();
+}
+
 fn setup_logger(level: log::LevelFilter) {
     fern::Dispatch::new()
         .format(|out, message, record| {
@@ -174,6 +266,28 @@ fn main() {
             .required(false)
             .multiple(true),
         )
+        .arg(
+            Arg::from_usage(
+                "<DEFINES> -D <DEFINE> 'define a macro for the erlang preprocessor as NAME or NAME=VALUE'",
+            )
+            .required(false)
+            .multiple(true)
+            .number_of_values(1),
+        )
+        .arg(
+            Arg::from_usage("<WARNINGS> -W <WARNING> 'warning options, -Werror treats warnings as errors'")
+                .required(false)
+                .multiple(true)
+                .number_of_values(1)
+                .possible_values(&["error"]),
+        )
+        .arg(
+            Arg::from_usage("<OTP_RELEASE> --otp-release <RELEASE> 'the OTP release to target'")
+                .required(false),
+        )
+        .arg(Arg::from_usage(
+            "[PREPROCESS] -P,--preprocess 'only run the erlang preprocessor, and output the preprocessed source'",
+        ))
         .arg(
             Arg::from_usage("<PASSES> --pass <PASS> 'run the given compilation pass'")
                 .required(false)
@@ -188,6 +302,12 @@ fn main() {
                 .case_insensitive(true)
                 .possible_values(&LogLevel::variants()),
         )
+        .arg(
+            Arg::from_usage(
+                "<TRACE_FILE> --trace <FILE> 'write a chrome trace event file, requires the trace feature'",
+            )
+            .required(false),
+        )
         .get_matches();
 
     setup_logger(
@@ -196,12 +316,22 @@ fn main() {
             .to_filter(),
     );
 
+    if matches.is_present("TRACE_FILE") {
+        libeir_util_prof::set_enabled(true);
+    }
+
     let codemap = Arc::new(CodeMap::new());
-    let frontend = make_frontend(codemap.clone(), &matches);
 
     let in_file_name = matches.value_of("IN_FILE").unwrap();
     let in_file_path = Path::new(in_file_name);
 
+    if matches.is_present("PREPROCESS") {
+        preprocess(codemap, &matches, in_file_path);
+        return;
+    }
+
+    let frontend = make_frontend(codemap.clone(), &matches);
+
     let (eir_res, diagnostics) = frontend.parse_file_dyn(&in_file_path);
     {
         let term_config = term::Config::default();
@@ -262,9 +392,9 @@ fn main() {
     match out_type {
         OutputType::Eir => {
             if let Some(selected) = selected_function {
-                out_data = eir[&selected].function().to_text_standard();
+                out_data = eir[&selected].function().to_text_standard().into_bytes();
             } else {
-                out_data = eir.to_text_standard();
+                out_data = eir.to_text_standard().into_bytes();
             }
             out_ext = "eir";
         }
@@ -274,10 +404,20 @@ fn main() {
             let fun_def = &eir[&selected_function];
             let fun = fun_def.function();
 
-            out_data = ::libeir_ir::text::function_to_dot(&fun);
+            out_data = ::libeir_ir::text::function_to_dot(&fun).into_bytes();
 
             out_ext = "dot";
         }
+        OutputType::Asm => {
+            let module = libeir_beam::compile_module(&eir).unwrap();
+            out_data = module.to_text().into_bytes();
+            out_ext = "S";
+        }
+        OutputType::Beam => {
+            let module = libeir_beam::compile_module(&eir).unwrap();
+            out_data = module.to_beam();
+            out_ext = "beam";
+        }
     }
 
     let out_file_name = matches
@@ -287,7 +427,12 @@ fn main() {
 
     println!("Writing to {}", out_file_name);
     let mut out = ::std::fs::File::create(&out_file_name).unwrap();
-    out.write(out_data.as_bytes()).unwrap();
+    out.write_all(&out_data).unwrap();
+
+    if let Some(trace_file) = matches.value_of("TRACE_FILE") {
+        println!("Writing trace to {}", trace_file);
+        libeir_util_prof::write_file(trace_file).unwrap();
+    }
 
     if let Some(dot_format) = matches.value_of("DOT_FORMAT") {
         assert!(out_type == OutputType::Dot);
@@ -0,0 +1,434 @@
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use clap::{arg_enum, value_t, App, Arg, ArgMatches};
+
+use libeir_diagnostics::term::{
+    self,
+    termcolor::{ColorChoice, StandardStream},
+};
+use libeir_diagnostics::CodeMap;
+use libeir_frontend::{
+    abstr_erlang::AbstrErlangFrontend, eir::EirFrontend, erlang::ErlangFrontend, AnyFrontend,
+    DynFrontend,
+};
+use libeir_interpreter::{Breakpoint, CodeLoader, Debugger, StopReason, Term, VMState};
+use libeir_ir::FunctionIdent;
+use libeir_passes::PassManager;
+
+arg_enum! {
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum InputType {
+        Eir,
+        Abstr,
+        Erl,
+    }
+}
+
+arg_enum! {
+    #[derive(Debug)]
+    pub enum CompileLevel {
+        High,
+        Normal,
+    }
+}
+
+const HELP: &str = "\
+Commands:
+  b, break <file>:<line>       break when entering a source line
+  b, break <m>:<f>/<a>         break when entering a function
+  d, delete <n>                delete breakpoint
+  i, info                      list breakpoints
+  r, run                       start the entry function
+  s, step                      step to the next block
+  n, next                      step to the next source line
+  c, continue                  continue to the next breakpoint
+  p, print [value]             print bound values
+  bt, backtrace                print the call stack
+  w, where                     print the current position
+  q, quit                      exit the debugger";
+
+fn make_frontend(codemap: Arc<CodeMap>, matches: &ArgMatches) -> AnyFrontend {
+    match value_t!(matches, "IN_FORMAT", InputType).unwrap() {
+        InputType::Erl => {
+            use libeir_syntax_erl::ParseConfig;
+
+            let mut config = ParseConfig::default();
+            if let Some(includes) = matches.values_of("INCLUDE_PATHS") {
+                for include in includes {
+                    config.include_paths.push_front(PathBuf::from(include));
+                }
+            }
+
+            ErlangFrontend::new(config, codemap).into()
+        }
+        InputType::Abstr => AbstrErlangFrontend::new(codemap).into(),
+        InputType::Eir => EirFrontend::new(codemap).into(),
+    }
+}
+
+/// Parses the small subset of terms accepted as arguments on the command
+/// line: integers and atoms.
+fn parse_arg(arg: &str) -> Option<Term> {
+    if let Ok(int) = arg.parse::<i64>() {
+        return Some(Term::new_i64(int));
+    }
+    if arg.chars().next()?.is_alphabetic() {
+        return Some(Term::new_atom(arg));
+    }
+    None
+}
+
+fn parse_breakpoint(arg: &str) -> Option<Breakpoint> {
+    if let Ok(ident) = FunctionIdent::parse(arg) {
+        return Some(Breakpoint::Function(ident));
+    }
+    let split = arg.rfind(':')?;
+    let line = arg[split + 1..].parse().ok()?;
+    Some(Breakpoint::Line {
+        file: arg[..split].to_string(),
+        line,
+    })
+}
+
+fn print_position(dbg: &Debugger, out: &mut dyn Write) {
+    match (dbg.current_block(), dbg.current_position()) {
+        (Some((fun, block)), Some(pos)) => {
+            writeln!(out, "{} {} at {}", fun.fun.ident(), block, pos).unwrap()
+        }
+        (Some((fun, block)), None) => writeln!(out, "{} {}", fun.fun.ident(), block).unwrap(),
+        (None, _) => writeln!(out, "not in erlang code").unwrap(),
+    }
+}
+
+fn print_stop(dbg: &Debugger, reason: StopReason, out: &mut dyn Write) {
+    match reason {
+        StopReason::Breakpoint(id) => {
+            let bp = dbg.breakpoint(id).unwrap();
+            write!(out, "breakpoint {} ({}): ", id, bp).unwrap();
+            print_position(dbg, out);
+        }
+        StopReason::Step => print_position(dbg, out),
+        StopReason::Finished => match dbg.result() {
+            Some(Ok(ret)) => writeln!(out, "returned: {}", ret).unwrap(),
+            Some(Err((typ, reason, _trace))) => {
+                writeln!(out, "raised: {}:{}", typ, reason).unwrap()
+            }
+            None => unreachable!(),
+        },
+    }
+}
+
+/// Runs debugger commands read from `input` until it ends or `quit` is
+/// given.
+fn repl(
+    dbg: &mut Debugger,
+    entry: &FunctionIdent,
+    args: &[Term],
+    input: impl BufRead,
+    out: &mut dyn Write,
+) {
+    let mut lines = input.lines();
+
+    loop {
+        write!(out, "(eir) ").unwrap();
+        out.flush().unwrap();
+
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => return,
+        };
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => continue,
+        };
+        let arg = words.next();
+
+        let resumed = match command {
+            "b" | "break" => {
+                match arg.and_then(parse_breakpoint) {
+                    Some(bp) => {
+                        let id = dbg.add_breakpoint(bp.clone());
+                        writeln!(out, "breakpoint {} at {}", id, bp).unwrap();
+                    }
+                    None => writeln!(out, "expected <file>:<line> or <m>:<f>/<a>").unwrap(),
+                }
+                None
+            }
+            "d" | "delete" => {
+                match arg.and_then(|a| a.parse().ok()) {
+                    Some(id) => match dbg.remove_breakpoint(id) {
+                        Some(bp) => writeln!(out, "deleted breakpoint {} at {}", id, bp).unwrap(),
+                        None => writeln!(out, "no breakpoint {}", id).unwrap(),
+                    },
+                    None => writeln!(out, "expected breakpoint number").unwrap(),
+                }
+                None
+            }
+            "i" | "info" => {
+                for (id, bp) in dbg.breakpoints() {
+                    writeln!(out, "{}: {}", id, bp).unwrap();
+                }
+                None
+            }
+            "r" | "run" => {
+                dbg.start(entry, args);
+                Some(dbg.cont())
+            }
+            "s" | "step" if dbg.is_running() => Some(dbg.step_block()),
+            "n" | "next" if dbg.is_running() => Some(dbg.step_line()),
+            "c" | "continue" if dbg.is_running() => Some(dbg.cont()),
+            "s" | "step" | "n" | "next" | "c" | "continue" => {
+                writeln!(out, "not running").unwrap();
+                None
+            }
+            "p" | "print" => {
+                for (value, term) in dbg.bindings() {
+                    let name = value.to_string();
+                    if arg.map(|a| a == name).unwrap_or(true) {
+                        writeln!(out, "{} = {}", name, term).unwrap();
+                    }
+                }
+                None
+            }
+            "bt" | "backtrace" => {
+                for (idx, frame) in dbg.stack().enumerate() {
+                    match dbg.frame_position(frame) {
+                        Some(pos) => writeln!(out, "#{} {} at {}", idx, frame.ident, pos).unwrap(),
+                        None => writeln!(out, "#{} {}", idx, frame.ident).unwrap(),
+                    }
+                }
+                None
+            }
+            "w" | "where" => {
+                print_position(dbg, out);
+                None
+            }
+            "q" | "quit" => return,
+            _ => {
+                writeln!(out, "{}", HELP).unwrap();
+                None
+            }
+        };
+
+        if let Some(reason) = resumed {
+            print_stop(dbg, reason, out);
+        }
+    }
+}
+
+fn main() {
+    let matches = App::new("Eir Debugger CLI")
+        .version("alpha")
+        .author("Hans Elias B. Josephsen")
+        .about("Source level debugger for the Eir interpreter")
+        .arg(
+            Arg::with_name("IN_FILE")
+                .help("Input file for compiler")
+                .required(true),
+        )
+        .arg(
+            Arg::from_usage("<IN_FORMAT> -f,--in-format <IN_FORMAT> 'input format'")
+                .default_value("erl")
+                .required(true)
+                .case_insensitive(true)
+                .possible_values(&InputType::variants()),
+        )
+        .arg(
+            Arg::from_usage(
+                "<COMPILE_LEVEL> -l,--compile-level <COMPILE_LEVEL> 'compilation level'",
+            )
+            .default_value("normal")
+            .required(false)
+            .case_insensitive(true)
+            .possible_values(&CompileLevel::variants()),
+        )
+        .arg(
+            Arg::from_usage(
+                "<INCLUDE_PATHS> -I <INCLUDE_PATH> 'add include path for the erlang preprocessor'",
+            )
+            .required(false)
+            .multiple(true),
+        )
+        .arg(
+            Arg::from_usage(
+                "<CODE_PATHS> -P <CODE_PATH> 'add a directory to load called modules from'",
+            )
+            .required(false)
+            .multiple(true),
+        )
+        .arg(
+            Arg::from_usage("<ENTRY> -e,--entry <FUN_IDENT> 'function to run, as m:f/a'")
+                .required(true),
+        )
+        .arg(
+            Arg::from_usage("<ARGS> -a,--arg <TERM> 'argument to the entry function'")
+                .required(false)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::from_usage(
+                "<TRACE_FILE> --trace <FILE> 'write a chrome trace event file, requires the trace feature'",
+            )
+            .required(false),
+        )
+        .get_matches();
+
+    if matches.is_present("TRACE_FILE") {
+        libeir_util_prof::set_enabled(true);
+    }
+
+    let codemap = Arc::new(CodeMap::new());
+    let frontend = make_frontend(codemap.clone(), &matches);
+
+    let in_file_path = Path::new(matches.value_of("IN_FILE").unwrap());
+    let (eir_res, diagnostics) = frontend.parse_file_dyn(&in_file_path);
+    {
+        let term_config = term::Config::default();
+        let mut out = StandardStream::stderr(ColorChoice::Auto);
+        for diag in diagnostics.iter() {
+            term::emit(&mut out, &term_config, &*codemap, diag).unwrap();
+        }
+    }
+    let mut eir = match eir_res {
+        Ok(eir) => eir,
+        Err(()) => return,
+    };
+
+    match value_t!(matches, "COMPILE_LEVEL", CompileLevel).unwrap() {
+        CompileLevel::High => {}
+        CompileLevel::Normal => {
+            let mut pass_manager = PassManager::default();
+            pass_manager.run(&mut eir);
+        }
+    }
+
+    let entry = FunctionIdent::parse(matches.value_of("ENTRY").unwrap())
+        .expect("Expected entry function as m:f/a");
+    let args = matches
+        .values_of("ARGS")
+        .map(|vals| {
+            vals.map(|v| parse_arg(v).expect("Expected integer or atom argument"))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    assert!(args.len() == entry.arity, "Argument count mismatch");
+
+    let mut vm = VMState::new();
+    vm.add_builtin_modules();
+    vm.add_erlang_module(eir);
+
+    if let Some(code_paths) = matches.values_of("CODE_PATHS") {
+        let mut loader = CodeLoader::new(codemap.clone());
+        for path in code_paths {
+            loader.add_path(path);
+        }
+        vm.set_code_loader(loader);
+    }
+
+    let mut dbg = Debugger::new(&vm, Some(codemap));
+    let stdin = std::io::stdin();
+    repl(
+        &mut dbg,
+        &entry,
+        &args,
+        stdin.lock(),
+        &mut std::io::stdout(),
+    );
+
+    if let Some(trace_file) = matches.value_of("TRACE_FILE") {
+        libeir_util_prof::write_file(trace_file).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use libeir_diagnostics::CodeMap;
+    use libeir_frontend::{erlang::ErlangFrontend, DynFrontend};
+    use libeir_interpreter::{Debugger, Term, VMState};
+    use libeir_ir::FunctionIdent;
+    use libeir_passes::PassManager;
+    use libeir_syntax_erl::ParseConfig;
+
+    const SOURCE: &str = "-module(woo).
+
+add(A, B) ->
+    C = A + B,
+    D = double(C),
+    D + 1.
+
+double(X) ->
+    X * 2.
+";
+
+    /// Runs the commands against `woo:add(1, 2)`, returns the output.
+    fn run(commands: &str) -> String {
+        let codemap = Arc::new(CodeMap::new());
+        let frontend = ErlangFrontend::new(ParseConfig::default(), codemap.clone());
+        let mut eir = frontend.parse_string_dyn(SOURCE).0.unwrap();
+        PassManager::default().run(&mut eir);
+
+        let mut vm = VMState::new();
+        vm.add_builtin_modules();
+        vm.add_erlang_module(eir);
+
+        let entry = FunctionIdent::parse("woo:add/2").unwrap();
+        let args = [Term::new_i64(1), Term::new_i64(2)];
+
+        let mut dbg = Debugger::new(&vm, Some(codemap));
+        let mut out = Vec::new();
+        super::repl(&mut dbg, &entry, &args, commands.as_bytes(), &mut out);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn breakpoints() {
+        let out = run("b woo:add/2\nb woo:double/1\nb nofile:9\nd 0\ni\nd 0\nr\nc\nc\n");
+        assert!(out.contains("breakpoint 0 at woo:add/2\n"));
+        assert!(out.contains("breakpoint 1 at woo:double/1\n"));
+        assert!(out.contains("breakpoint 2 at nofile:9\n"));
+        assert!(out.contains("deleted breakpoint 0 at woo:add/2\n"));
+        // The remaining breakpoints keep their ids
+        assert!(out.contains("(eir) 1: woo:double/1\n2: nofile:9\n(eir) "));
+        assert!(out.contains("no breakpoint 0\n"));
+        assert!(out.contains("breakpoint 1 (woo:double/1): woo:double/1 "));
+        assert!(out.contains("breakpoint 2 (nofile:9): woo:double/1 "));
+        assert!(out.contains(" at nofile:9\n"));
+        assert!(out.contains("returned: 7\n"));
+    }
+
+    #[test]
+    fn stepping() {
+        let out = run("s\nq\n");
+        assert!(out.starts_with("(eir) not running\n"));
+
+        let out = run("r\n");
+        assert!(out.contains("returned: 7\n"));
+
+        let out = run("b woo:add/2\nr\ns\nw\nq\n");
+        assert!(out.contains("breakpoint 0 (woo:add/2): woo:add/2 "));
+        let stops: Vec<&str> = out
+            .lines()
+            .filter(|line| line.starts_with("(eir) woo:add/2 "))
+            .collect();
+        // Both the step and `where` print the new position
+        assert_eq!(stops.len(), 2);
+        assert_eq!(stops[0], stops[1]);
+    }
+
+    #[test]
+    fn bindings_and_stack() {
+        let out = run("b woo:double/1\nr\np\nbt\nq\n");
+        // `X` is bound to `A + B`
+        assert!(out.contains(" = 3\n"));
+        assert!(out
+            .lines()
+            .any(|line| line.starts_with("#") && line.contains(" woo:add/2 at nofile:")));
+        assert!(!out.contains("returned"));
+    }
+}
@@ -51,7 +51,7 @@ pub mod tag {
     pub const EXPORT_EXT: u8 = 113;
     /// (len:u32be) (bits:u8 <= 7) bytes..
     pub const BIT_BINARY_EXT: u8 = 77;
-    /// (num:f32)
+    /// (num:f64be)
     pub const NEW_FLOAT_EXT: u8 = 70;
     /// (len:u16be) name_bytes..
     /// latin1
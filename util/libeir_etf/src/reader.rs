@@ -72,7 +72,7 @@ pub enum RawTag {
         bits: u8,
     },
     NewFloatExt {
-        num: f32,
+        num: f64,
     },
     Atom {
         len: u16,
@@ -222,7 +222,7 @@ impl<S: Read> Reader<S> {
             }
             tag::NEW_FLOAT_EXT => {
                 trace!("new_float_ext");
-                let num = self.source.read_f32::<BigEndian>()?;
+                let num = self.source.read_f64::<BigEndian>()?;
                 RawTag::NewFloatExt { num }
             }
             tag::ATOM_EXT => {
@@ -7,7 +7,7 @@ trait TermTypes {
 #[derive(Debug, PartialEq)]
 pub enum Term {
     Integer(i32),
-    Float(f32),
+    Float(f64),
     Tuple(Vec<Term>),
     Map(Vec<(Term, Term)>),
     Nil,
@@ -1,4 +1,4 @@
-use crate::{Reader, Term};
+use crate::{Reader, Term, Writer};
 
 macro_rules! make_reader {
     ($var:ident = $path:expr) => {
@@ -51,3 +51,19 @@ fn test_2() {
 
     println!("{:?}", term);
 }
+
+#[test]
+fn write_read_float_map() {
+    let term = Term::Map(vec![(
+        Term::Atom("pi".into()),
+        Term::List(vec![Term::Float(3.141592653589793)], Box::new(Term::Nil)),
+    )]);
+
+    let mut bin = Vec::new();
+    Writer::new(&mut bin).term(&term).unwrap();
+    assert_eq!(bin.len(), 24);
+
+    let mut cursor = std::io::Cursor::new(&bin);
+    let mut reader = Reader::new(&mut cursor);
+    assert_eq!(reader.term().unwrap(), term);
+}
@@ -40,6 +40,7 @@ impl<S> Writer<S> {
     pub fn pop(&mut self) {
         match self.state.pop().expect("tried to pop empty stack") {
             WriterState::RemainingTerms(0) => (),
+            WriterState::RemainingMap(0, 0) => (),
             _ => panic!("tried to pop invalid state"),
         }
     }
@@ -98,7 +99,7 @@ impl<S: Write> Writer<S> {
     pub fn raw_big_ext(&mut self, int: &BigInt) -> Result<()> {
         self.state.last_mut().unwrap().decr();
 
-        let (sign, bytes) = int.to_bytes_be();
+        let (sign, bytes) = int.to_bytes_le();
         let bytes_len = bytes.len();
 
         let sign_u8 = if sign == Sign::Minus { 1 } else { 0 };
@@ -118,10 +119,10 @@ impl<S: Write> Writer<S> {
         }
     }
 
-    pub fn raw_new_float_ext(&mut self, float: f32) -> Result<()> {
+    pub fn raw_new_float_ext(&mut self, float: f64) -> Result<()> {
         self.state.last_mut().unwrap().decr();
         self.sink.write_u8(tag::NEW_FLOAT_EXT)?;
-        self.sink.write_f32::<BigEndian>(float)
+        self.sink.write_f64::<BigEndian>(float)
     }
 
     pub fn raw_nil(&mut self) -> Result<()> {
@@ -173,6 +174,13 @@ impl<S: Write> Writer<S> {
         self.sink.write_u32::<BigEndian>(size)
     }
 
+    pub fn raw_export_ext(&mut self) -> Result<()> {
+        self.state.last_mut().unwrap().decr();
+        self.state.push(WriterState::RemainingTerms(3));
+
+        self.sink.write_u8(tag::EXPORT_EXT)
+    }
+
     pub fn raw_list_ext(&mut self, length: u32) -> Result<()> {
         self.state.last_mut().unwrap().decr();
         self.state.push(WriterState::RemainingList(length as usize));
@@ -213,7 +221,7 @@ impl<S: Write> Writer<S> {
         self.raw_big_ext(int)
     }
 
-    pub fn float(&mut self, num: f32) -> Result<()> {
+    pub fn float(&mut self, num: f64) -> Result<()> {
         self.raw_new_float_ext(num)
     }
     pub fn nil(&mut self) -> Result<()> {
@@ -252,6 +260,14 @@ impl<S: Write> Writer<S> {
             panic!("list cannot be longer than 2^32");
         }
     }
+    pub fn export(&mut self, module: &str, function: &str, arity: u8) -> Result<()> {
+        self.raw_export_ext()?;
+        self.atom(module)?;
+        self.atom(function)?;
+        self.integer_u8(arity)?;
+        self.pop();
+        Ok(())
+    }
     pub fn binary<N: TryInto<u32>>(&mut self, len: N) -> Result<()> {
         if let Ok(len) = len.try_into() {
             self.raw_binary_ext(len)
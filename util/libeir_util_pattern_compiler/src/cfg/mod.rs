@@ -43,13 +43,10 @@ where
 
     pub(crate) fn add_leaf(
         &mut self,
-        parent: CfgNodeIndex,
         leaf_num: usize,
-        edge: CfgEdge<P>,
         binds: HashMap<P::PatternNodeKey, P::CfgVariable>,
     ) -> CfgNodeIndex {
         let index = self.graph.add_node(CfgNodeKind::Leaf(leaf_num));
-        self.graph.add_edge(parent, index, edge);
         self.leaf_bindings.insert(index, binds);
         index
     }
@@ -66,17 +63,14 @@ where
         self.graph.add_edge(parent, child, edge);
     }
 
-    pub(crate) fn add_child(
+    pub(crate) fn add_match(
         &mut self,
-        parent: CfgNodeIndex,
-        typ: CfgEdge<P>,
         var: P::CfgVariable,
         binds: HashMap<P::PatternNodeKey, P::CfgVariable>,
     ) -> CfgNodeIndex {
-        let child = self.graph.add_node(CfgNodeKind::Match(var));
-        self.graph.add_edge(parent, child, typ);
-        self.leaf_bindings.insert(child, binds);
-        child
+        let index = self.graph.add_node(CfgNodeKind::Match(var));
+        self.leaf_bindings.insert(index, binds);
+        index
     }
 }
 
@@ -77,6 +77,25 @@ fn matrix_to_decision_tree<P>(
     level: usize,
 ) where
     P: PatternProvider,
+{
+    let edge = cfg::CfgEdge {
+        kind: spec.clone(),
+        variable_binds: introduced_vars,
+    };
+
+    let node = matrix_to_cfg_node(ctx, matrix, level);
+    ctx.cfg.add_edge(parent, node, edge);
+}
+
+/// Constructs the CFG for the given matrix, returning the node that
+/// should be entered to match it.
+fn matrix_to_cfg_node<P>(
+    ctx: &mut MatchCompileContext<P>,
+    matrix: &matrix::MatchMatrix<P>,
+    level: usize,
+) -> cfg::CfgNodeIndex
+where
+    P: PatternProvider,
 {
     #[cfg(feature = "debug_table_print")]
     {
@@ -88,27 +107,21 @@ fn matrix_to_decision_tree<P>(
         trace!(target: TARGET, "{}", buf);
     }
 
-    let edge = cfg::CfgEdge {
-        kind: spec.clone(),
-        variable_binds: introduced_vars,
-    };
-
     // Matrix is empty, no specializations can be done.
     if matrix.is_empty() {
-        ctx.cfg.add_edge(parent, ctx.fail_leaf, edge);
-        return;
+        return ctx.fail_leaf;
     }
 
     // If the head of the matrix has only wildcards, none of the other rows
     // can happen.
     if let Some(node_id) = matrix.has_wildcard_head(&ctx.pattern) {
         let binds = matrix.binds_for(node_id).unwrap();
-        let node = ctx.cfg.add_leaf(parent, node_id.0, edge, binds.clone());
+        let node = ctx.cfg.add_leaf(node_id.0, binds.clone());
 
         let new_mat = matrix.without_head();
         matrix_to_decision_tree(node, ctx, None, &new_mat, vec![], level + 1);
 
-        return;
+        return node;
     }
 
     // Select the variable we should specialize on.
@@ -117,13 +130,26 @@ fn matrix_to_decision_tree<P>(
     let specialize_variable = matrix.select_specialize_variable(&ctx.pattern);
     let specialize_variable_cfg_var = matrix.get_var(specialize_variable);
 
+    // If a clause can match a value that an earlier clause of a different
+    // kind can also match, we can't specialize both at once. The clauses
+    // are split, and the later ones are entered when the earlier ones
+    // fail.
+    if let Some(split) = matrix.overlap_split_point(&ctx.pattern, specialize_variable) {
+        let (head, tail) = matrix.split_clauses(split);
+
+        let tail_node = matrix_to_cfg_node(ctx, &tail, level);
+
+        let fail_leaf = std::mem::replace(&mut ctx.fail_leaf, tail_node);
+        let head_node = matrix_to_cfg_node(ctx, &head, level);
+        ctx.fail_leaf = fail_leaf;
+
+        return head_node;
+    }
+
     // Add new CFG node for current
-    let cfg_node = ctx.cfg.add_child(
-        parent,
-        edge,
-        specialize_variable_cfg_var,
-        matrix.binds_for_head().clone(),
-    );
+    let cfg_node = ctx
+        .cfg
+        .add_match(specialize_variable_cfg_var, matrix.binds_for_all());
 
     // Find what pattern types we have as children, so that we can
     // specialize and branch to them in the CFG
@@ -157,6 +183,8 @@ fn matrix_to_decision_tree<P>(
         introduced,
         level + 1,
     );
+
+    cfg_node
 }
 
 pub fn to_decision_tree<P>(pattern: &mut P) -> cfg::PatternCfg<P>
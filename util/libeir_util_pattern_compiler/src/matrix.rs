@@ -342,8 +342,57 @@ where
             .map(|(idx, _)| &self.leaf_bindings[idx])
     }
 
-    pub(crate) fn binds_for_head<'a>(&'a self) -> &'a HashMap<P::PatternNodeKey, P::CfgVariable> {
-        &self.leaf_bindings[0]
+    /// The bindings of all clauses in the matrix.
+    /// Pattern nodes are unique to a clause, so these never conflict.
+    pub(crate) fn binds_for_all(&self) -> HashMap<P::PatternNodeKey, P::CfgVariable> {
+        let mut binds = HashMap::new();
+        for clause_binds in self.leaf_bindings.iter() {
+            binds.extend(clause_binds.iter().map(|(k, v)| (*k, *v)));
+        }
+        binds
+    }
+
+    /// Finds the first clause that has a node in the given variable which
+    /// overlaps with a node of a different kind in a previous clause.
+    /// Every clause before this point can be specialized on independently
+    /// of the clauses after it.
+    pub fn overlap_split_point(&self, pattern: &P, variable: usize) -> Option<usize> {
+        let mut seen: Vec<P::PatternNodeKind> = Vec::new();
+
+        let clauses = self.data.chunks(self.variables.len());
+        for (clause_num, clause) in clauses.enumerate() {
+            let kind = pattern.get_kind(clause[variable].node);
+            if pattern.is_wildcard(kind) || seen.contains(&kind) {
+                continue;
+            }
+            if seen.iter().any(|prev| pattern.kinds_overlap(*prev, kind)) {
+                return Some(clause_num);
+            }
+            seen.push(kind);
+        }
+
+        None
+    }
+
+    /// Splits the matrix in two at the given clause.
+    pub fn split_clauses(&self, at: usize) -> (MatchMatrix<P>, MatchMatrix<P>) {
+        let var_num = self.variables.len();
+        let nodes: Vec<_> = self.data.iter().map(|elem| elem.node).collect();
+
+        let head = Self::with_bindings(
+            &nodes[..(at * var_num)],
+            self.clause_leaves[..at].to_vec(),
+            self.variables.clone(),
+            self.leaf_bindings[..at].to_vec(),
+        );
+        let tail = Self::with_bindings(
+            &nodes[(at * var_num)..],
+            self.clause_leaves[at..].to_vec(),
+            self.variables.clone(),
+            self.leaf_bindings[at..].to_vec(),
+        );
+
+        (head, tail)
     }
 
     //pub(crate) fn iterate_clauses<'a>(&'a self) -> impl Iterator<Item = (LeafId, &'a [MatchMatrixElement<P>])> + 'a {
@@ -53,6 +53,16 @@ pub trait PatternProvider: Debug {
     /// `PatternNodeKind`.
     fn get_kind(&self, key: Self::PatternNodeKey) -> Self::PatternNodeKind;
 
+    /// Used to determine if a single value can be matched by both of the
+    /// given kinds.
+    ///
+    /// Different kinds are normally assumed to be mutually exclusive. When
+    /// two kinds overlap, the clauses of the matrix are split so that the
+    /// earlier clauses are tried in full before the later ones.
+    fn kinds_overlap(&self, _left: Self::PatternNodeKind, _right: Self::PatternNodeKind) -> bool {
+        false
+    }
+
     fn is_wildcard(&self, kind: Self::PatternNodeKind) -> bool {
         kind == Self::WILDCARD
     }
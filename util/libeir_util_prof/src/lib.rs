@@ -1,20 +1,247 @@
+//! Collection of profiling events in the Chrome trace event format.
+//!
+//! The resulting JSON file can be opened in `chrome://tracing` or Perfetto.
+//!
+//! A tracer is kept per thread. Recording is disabled by default, and events
+//! are only collected after `set_enabled(true)` is called.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
 use std::time::Instant;
-use std::thread::ThreadId;
 
-pub struct Event {
-    instant: Instant,
-    thread: ThreadId,
-    kind: EventKind,
-}
+use serde::Serialize;
+
+pub type TraceArgs = HashMap<String, serde_json::Value>;
 
-pub enum EventKind {
-    Begin {
+/// A single entry in the trace event format.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "ph")]
+pub enum TraceEntry {
+    #[serde(rename = "B")]
+    DurationStart {
+        name: String,
+        #[serde(rename = "cat")]
+        categories: String,
+        #[serde(rename = "ts")]
+        timestamp: u64,
+        pid: u64,
+        tid: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cname: Option<String>,
+        args: TraceArgs,
+    },
+    #[serde(rename = "E")]
+    DurationEnd {
+        #[serde(rename = "ts")]
+        timestamp: u64,
+        pid: u64,
+        tid: u64,
+        args: TraceArgs,
+    },
+    #[serde(rename = "i")]
+    Instant {
+        name: String,
+        #[serde(rename = "ts")]
+        timestamp: u64,
+        pid: u64,
+        tid: u64,
+        #[serde(rename = "s")]
+        scope: &'static str,
+        args: TraceArgs,
     },
-    End {
+    #[serde(rename = "M")]
+    Metadata {
+        name: &'static str,
+        pid: u64,
+        tid: u64,
+        args: TraceArgs,
     },
 }
 
+/// Collects trace entries, timestamped relative to the creation of the
+/// tracer.
 pub struct Tracer {
     start: Instant,
-    events: Vec<Event>,
+    entries: Vec<TraceEntry>,
+}
+
+impl Tracer {
+    pub fn new() -> Self {
+        Tracer {
+            start: Instant::now(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Microseconds since the tracer was created.
+    fn timestamp(&self) -> u64 {
+        self.start.elapsed().as_micros() as u64
+    }
+
+    pub fn begin(&mut self, pid: u64, tid: u64, name: String, category: &str, args: TraceArgs) {
+        let timestamp = self.timestamp();
+        self.entries.push(TraceEntry::DurationStart {
+            name,
+            categories: category.to_string(),
+            timestamp,
+            pid,
+            tid,
+            cname: None,
+            args,
+        });
+    }
+
+    pub fn end(&mut self, pid: u64, tid: u64, args: TraceArgs) {
+        let timestamp = self.timestamp();
+        self.entries.push(TraceEntry::DurationEnd {
+            timestamp,
+            pid,
+            tid,
+            args,
+        });
+    }
+
+    pub fn instant(&mut self, pid: u64, tid: u64, name: String, args: TraceArgs) {
+        let timestamp = self.timestamp();
+        self.entries.push(TraceEntry::Instant {
+            name,
+            timestamp,
+            pid,
+            tid,
+            scope: "t",
+            args,
+        });
+    }
+
+    /// Sets the name displayed for the given pid.
+    pub fn process_name(&mut self, pid: u64, name: String) {
+        let mut args = TraceArgs::new();
+        args.insert("name".to_string(), serde_json::Value::String(name));
+        self.entries.push(TraceEntry::Metadata {
+            name: "process_name",
+            pid,
+            tid: 0,
+            args,
+        });
+    }
+
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.entries
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn write_json<W: Write>(&self, writer: W) -> io::Result<()> {
+        serde_json::to_writer(writer, &self.entries).map_err(|e| e.into())
+    }
+
+    pub fn write_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = std::fs::File::create(path)?;
+        self.write_json(io::BufWriter::new(file))
+    }
+}
+
+impl Default for Tracer {
+    fn default() -> Self {
+        Tracer::new()
+    }
+}
+
+std::thread_local! {
+    static TRACER: RefCell<Option<Tracer>> = RefCell::new(None);
+}
+
+/// Enables or disables recording on the current thread.
+/// Disabling discards any recorded events.
+pub fn set_enabled(enabled: bool) {
+    TRACER.with(|t| {
+        let mut t = t.borrow_mut();
+        match (enabled, t.is_some()) {
+            (true, false) => *t = Some(Tracer::new()),
+            (false, true) => *t = None,
+            _ => (),
+        }
+    })
+}
+
+pub fn is_enabled() -> bool {
+    TRACER.with(|t| t.borrow().is_some())
+}
+
+/// Runs the closure with the tracer of the current thread, if recording is
+/// enabled.
+pub fn with_tracer<F>(fun: F)
+where
+    F: FnOnce(&mut Tracer),
+{
+    TRACER.with(|t| {
+        if let Some(tracer) = &mut *t.borrow_mut() {
+            fun(tracer)
+        }
+    })
+}
+
+/// Writes the events recorded on the current thread to a JSON file.
+pub fn write_file<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    TRACER.with(|t| match &*t.borrow() {
+        Some(tracer) => tracer.write_file(path),
+        None => Tracer::new().write_file(path),
+    })
+}
+
+/// A duration event on the current thread, ended when dropped.
+pub struct Span {
+    pid: u64,
+    tid: u64,
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let (pid, tid) = (self.pid, self.tid);
+        with_tracer(|t| t.end(pid, tid, TraceArgs::new()));
+    }
+}
+
+pub fn span(pid: u64, tid: u64, name: &str, category: &str) -> Span {
+    with_tracer(|t| t.begin(pid, tid, name.to_string(), category, TraceArgs::new()));
+    Span { pid, tid }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn span_nesting() {
+        set_enabled(true);
+        {
+            let _outer = span(0, 0, "outer", "test");
+            let _inner = span(0, 0, "inner", "test");
+        }
+
+        let mut out = Vec::new();
+        TRACER.with(|t| {
+            let t = t.borrow();
+            let entries = t.as_ref().unwrap().entries();
+            assert!(entries.len() == 4);
+            t.as_ref().unwrap().write_json(&mut out).unwrap();
+        });
+
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let phases: Vec<_> = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["ph"].as_str().unwrap().to_string())
+            .collect();
+        assert!(phases == ["B", "B", "E", "E"]);
+        assert!(json[1]["name"] == "inner");
+
+        set_enabled(false);
+        assert!(!is_enabled());
+    }
 }